@@ -10,7 +10,7 @@ use ffmpeg_dev::sys as ff;
 use crate::avc::{bitstream, nal, AvcError, DecoderConfigurationRecord};
 use crate::ffmpeg::codec::AvCodecContext;
 use crate::ffmpeg::media::Video;
-use crate::ffmpeg::{AvFrame, AvError, AvDict, AvPacket, PixelFormat};
+use crate::ffmpeg::{AvFrame, AvError, AvDict, AvPacket, PixelFormat, Colorimetry};
 
 #[derive(Debug)]
 pub struct AvcEncoder {
@@ -20,7 +20,7 @@ pub struct AvcEncoder {
 pub struct AvcParams {
     pub time_base: usize,
     pub pixel_format: PixelFormat,
-    pub color_space: ff::AVColorSpace,
+    pub color: Colorimetry,
     pub picture_width: usize,
     pub picture_height: usize,
     pub rate_control: RateControl,
@@ -127,7 +127,11 @@ impl AvcEncoder {
             avctx.level = 41;
             avctx.width = params.picture_width.try_into().expect("picture_width too large");
             avctx.height = params.picture_height.try_into().expect("picture_height too large");
-            avctx.colorspace = params.color_space;
+            // tagged in the stream so that players don't have to guess:
+            avctx.colorspace = params.color.space.into_raw();
+            avctx.color_primaries = params.color.space.primaries();
+            avctx.color_trc = params.color.space.transfer();
+            avctx.color_range = params.color.range.into_raw();
             avctx.pix_fmt = params.pixel_format.into_raw();
             avctx.time_base.num = 1;
             avctx.time_base.den = params.time_base as c_int;
@@ -196,6 +200,18 @@ impl AvcEncoder {
         Ok(())
     }
 
+    /// Signals the end of the stream so that frames held for lookahead are
+    /// encoded, recv_packet reports EOF once all have been received
+    pub fn send_eof(&mut self) -> Result<(), AvError> {
+        let rc = unsafe { ff::avcodec_send_frame(self.ctx.as_mut_ptr(), ptr::null()) };
+
+        if rc < 0 {
+            return Err(AvError(rc));
+        }
+
+        Ok(())
+    }
+
     pub fn recv_packet(&mut self) -> Result<AvPacket, AvError> {
         unsafe {
             let mut packet = MaybeUninit::<ff::AVPacket>::uninit();
@@ -8,6 +8,7 @@ use sys as ff;
 
 pub mod codec;
 pub mod media;
+mod color;
 mod format;
 mod frame;
 mod ioctx;
@@ -15,6 +16,7 @@ mod packet;
 mod pixfmt;
 mod scale;
 
+pub use color::{ColorSpace, ColorRange, Colorimetry, UnknownColorSpace};
 pub use format::InputContainer;
 pub use frame::{AvFrame, PictureSettings, PictureData, PictureDataMut};
 pub use ioctx::{AvIoError, IoReader, AvIoReader};
@@ -34,6 +36,10 @@ impl AvError {
     pub fn again(&self) -> bool {
         self.0 == -(ff::EAGAIN as c_int)
     }
+
+    pub fn eof(&self) -> bool {
+        self.0 == EOF
+    }
 }
 
 impl Display for AvError {
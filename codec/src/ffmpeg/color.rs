@@ -0,0 +1,133 @@
+use std::fmt::{self, Display};
+use std::os::raw::c_int;
+use std::str::FromStr;
+
+use ffmpeg_dev::sys as ff;
+use serde_derive::{Deserialize, Serialize};
+
+/// Matrix used to convert between YUV and RGB, along with the primaries and
+/// transfer function that go with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorSpace {
+    // standard definition:
+    Bt601,
+    // high definition:
+    Bt709,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorRange {
+    // 16-235 luma, which is what players expect from broadcast and
+    // streaming video:
+    Limited,
+    // 0-255 luma, as produced by jpeg decoders and some cameras:
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Colorimetry {
+    pub space: ColorSpace,
+    pub range: ColorRange,
+}
+
+impl Default for ColorSpace {
+    fn default() -> Self {
+        ColorSpace::Bt709
+    }
+}
+
+impl Default for Colorimetry {
+    fn default() -> Self {
+        Colorimetry {
+            space: ColorSpace::default(),
+            range: ColorRange::Limited,
+        }
+    }
+}
+
+impl ColorSpace {
+    /// Guesses the color space of untagged video the way players do
+    pub fn for_height(height: usize) -> Self {
+        if height >= 720 {
+            ColorSpace::Bt709
+        } else {
+            ColorSpace::Bt601
+        }
+    }
+
+    pub fn into_raw(self) -> ff::AVColorSpace {
+        match self {
+            ColorSpace::Bt601 => ff::AVColorSpace_AVCOL_SPC_SMPTE170M,
+            ColorSpace::Bt709 => ff::AVColorSpace_AVCOL_SPC_BT709,
+        }
+    }
+
+    pub fn primaries(self) -> ff::AVColorPrimaries {
+        match self {
+            ColorSpace::Bt601 => ff::AVColorPrimaries_AVCOL_PRI_SMPTE170M,
+            ColorSpace::Bt709 => ff::AVColorPrimaries_AVCOL_PRI_BT709,
+        }
+    }
+
+    pub fn transfer(self) -> ff::AVColorTransferCharacteristic {
+        match self {
+            ColorSpace::Bt601 => ff::AVColorTransferCharacteristic_AVCOL_TRC_SMPTE170M,
+            ColorSpace::Bt709 => ff::AVColorTransferCharacteristic_AVCOL_TRC_BT709,
+        }
+    }
+
+    pub(in crate::ffmpeg) fn sws_coefficients(self) -> *const c_int {
+        let colorspace = match self {
+            ColorSpace::Bt601 => ff::SWS_CS_ITU601,
+            ColorSpace::Bt709 => ff::SWS_CS_ITU709,
+        };
+
+        unsafe { ff::sws_getCoefficients(colorspace as c_int) }
+    }
+}
+
+impl Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorSpace::Bt601 => write!(f, "bt601"),
+            ColorSpace::Bt709 => write!(f, "bt709"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownColorSpace(pub String);
+
+impl Display for UnknownColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown color space {:?}, expected bt601 or bt709", self.0)
+    }
+}
+
+impl FromStr for ColorSpace {
+    type Err = UnknownColorSpace;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bt601" => Ok(ColorSpace::Bt601),
+            "bt709" => Ok(ColorSpace::Bt709),
+            _ => Err(UnknownColorSpace(s.to_owned())),
+        }
+    }
+}
+
+impl ColorRange {
+    pub fn into_raw(self) -> ff::AVColorRange {
+        match self {
+            ColorRange::Limited => ff::AVColorRange_AVCOL_RANGE_MPEG,
+            ColorRange::Full => ff::AVColorRange_AVCOL_RANGE_JPEG,
+        }
+    }
+
+    pub(in crate::ffmpeg) fn sws_range(self) -> c_int {
+        match self {
+            ColorRange::Limited => 0,
+            ColorRange::Full => 1,
+        }
+    }
+}
@@ -5,8 +5,8 @@ use std::ptr;
 
 use ffmpeg_dev::sys as ff;
 
-use crate::ffmpeg::media::{MediaType, Video};
-use crate::ffmpeg::{AvError, PixelFormat, ColorFormat};
+use crate::ffmpeg::media::{Audio, MediaType, Video};
+use crate::ffmpeg::{AvError, PixelFormat, ColorSpace, ColorRange, Colorimetry};
 
 #[derive(Debug)]
 pub struct AvFrame<Mt: MediaType> {
@@ -81,6 +81,8 @@ impl AvFrame<Video> {
         underlying.height = settings.height.try_into().expect("height too large");
         underlying.format = settings.pixel_format.into_raw();
 
+        frame.set_colorimetry(settings.color);
+
         unsafe {
             ff::av_frame_get_buffer(frame.as_mut_ptr(), 0);
         }
@@ -91,7 +93,7 @@ impl AvFrame<Video> {
         let pixdesc = settings.pixel_format.descriptor();
         let mut cleared = [false; 8];
 
-        for (idx, comp) in pixdesc.components().enumerate() {
+        for comp in pixdesc.components() {
             let plane = comp.plane();
 
             if cleared[plane] {
@@ -100,10 +102,7 @@ impl AvFrame<Video> {
 
             cleared[plane] = true;
 
-            let is_chroma = match pixdesc.color() {
-                ColorFormat::Yuv => idx > 0,
-                _ => false,
-            };
+            let is_chroma = comp.is_chroma();
 
             let width = if is_chroma {
                 settings.width >> pixdesc.log2_chroma_w()
@@ -125,8 +124,11 @@ impl AvFrame<Video> {
             // guaranteed to exist between lines.
             let size = stride * height.saturating_sub(1) + comp.step() * width;
 
+            // black sits above zero in limited range:
             let byte = if is_chroma {
                 0x80
+            } else if comp.is_luma() && settings.color.range == ColorRange::Limited {
+                16
             } else {
                 0
             };
@@ -165,6 +167,14 @@ impl AvFrame<Video> {
         self.as_underlying().key_frame != 0
     }
 
+    pub fn is_interlaced(&self) -> bool {
+        self.as_underlying().interlaced_frame != 0
+    }
+
+    pub fn top_field_first(&self) -> bool {
+        self.as_underlying().top_field_first != 0
+    }
+
     pub fn picture_type(&self) -> ff::AVPictureType {
         self.as_underlying().pict_type
     }
@@ -177,11 +187,43 @@ impl AvFrame<Video> {
         self.as_underlying().colorspace
     }
 
+    /// Color space and range of the frame, guessed from its size and pixel
+    /// format if it is untagged
+    pub fn colorimetry(&self) -> Colorimetry {
+        let underlying = self.as_underlying();
+
+        let space = match underlying.colorspace {
+            ff::AVColorSpace_AVCOL_SPC_BT709 => ColorSpace::Bt709,
+            ff::AVColorSpace_AVCOL_SPC_SMPTE170M |
+            ff::AVColorSpace_AVCOL_SPC_BT470BG |
+            ff::AVColorSpace_AVCOL_SPC_FCC => ColorSpace::Bt601,
+            _ => ColorSpace::for_height(self.coded_height()),
+        };
+
+        let range = match underlying.color_range {
+            ff::AVColorRange_AVCOL_RANGE_MPEG => ColorRange::Limited,
+            ff::AVColorRange_AVCOL_RANGE_JPEG => ColorRange::Full,
+            _ if self.pixel_format().is_full_range() => ColorRange::Full,
+            _ => ColorRange::Limited,
+        };
+
+        Colorimetry { space, range }
+    }
+
+    pub fn set_colorimetry(&mut self, color: Colorimetry) {
+        let underlying = self.as_underlying_mut();
+        underlying.colorspace = color.space.into_raw();
+        underlying.color_primaries = color.space.primaries();
+        underlying.color_trc = color.space.transfer();
+        underlying.color_range = color.range.into_raw();
+    }
+
     pub fn picture_settings(&self) -> PictureSettings {
         PictureSettings {
             width: self.coded_width(),
             height: self.coded_height(),
             pixel_format: self.pixel_format(),
+            color: self.colorimetry(),
         }
     }
 
@@ -243,6 +285,7 @@ impl AvFrame<Video> {
             width: w,
             height: h,
             pixel_format: self.pixel_format(),
+            color: self.colorimetry(),
         };
 
         let mut data = [ptr::null_mut(); 8];
@@ -250,11 +293,8 @@ impl AvFrame<Video> {
 
         // TODO - this should work just fine for non-planar pixfmts as long as
         // we don't mutate data - only assign into it from underlying.data
-        for (idx, component) in pixdesc.components().enumerate() {
-            let is_chroma = match pixdesc.color() {
-                ColorFormat::Yuv => idx > 0,
-                _ => false,
-            };
+        for component in pixdesc.components() {
+            let is_chroma = component.is_chroma();
 
             let plane = component.plane();
 
@@ -303,6 +343,75 @@ impl AvFrame<Video> {
     }
 }
 
+impl AvFrame<Audio> {
+    pub fn sample_rate(&self) -> usize {
+        self.as_underlying().sample_rate.try_into().expect("sample_rate >= 0")
+    }
+
+    pub fn channels(&self) -> usize {
+        self.as_underlying().channels.try_into().expect("channels >= 0")
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.as_underlying().nb_samples.try_into().expect("nb_samples >= 0")
+    }
+
+    /// Copies the samples out as interleaved stereo, doubling up mono and
+    /// dropping any channels past the first two. Returns None for sample
+    /// formats which aren't converted from
+    pub fn stereo_samples(&self) -> Option<Vec<f32>> {
+        let underlying = self.as_underlying();
+        let channels = self.channels();
+        let count = self.sample_count();
+
+        let (planar, size, read): (bool, usize, unsafe fn(*const u8) -> f32) = match underlying.format {
+            ff::AVSampleFormat_AV_SAMPLE_FMT_S16 => (false, 2, read_s16),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_S16P => (true, 2, read_s16),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_S32 => (false, 4, read_s32),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_S32P => (true, 4, read_s32),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_FLT => (false, 4, read_flt),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_FLTP => (true, 4, read_flt),
+            _ => return None,
+        };
+
+        if channels == 0 {
+            return None;
+        }
+
+        let mut samples = Vec::with_capacity(count * 2);
+
+        for index in 0..count {
+            for channel in 0..2 {
+                let channel = channel.min(channels - 1);
+
+                let sample = unsafe {
+                    if planar {
+                        read((*underlying.extended_data.add(channel)).add(index * size))
+                    } else {
+                        read((*underlying.extended_data).add((index * channels + channel) * size))
+                    }
+                };
+
+                samples.push(sample);
+            }
+        }
+
+        Some(samples)
+    }
+}
+
+unsafe fn read_s16(ptr: *const u8) -> f32 {
+    (ptr as *const i16).read_unaligned() as f32 / -(i16::min_value() as f32)
+}
+
+unsafe fn read_s32(ptr: *const u8) -> f32 {
+    ((ptr as *const i32).read_unaligned() as f64 / -(i32::min_value() as f64)) as f32
+}
+
+unsafe fn read_flt(ptr: *const u8) -> f32 {
+    (ptr as *const f32).read_unaligned()
+}
+
 type PlanarData = [*mut u8; ff::AV_NUM_DATA_POINTERS as usize];
 type PlanarStride = [c_int; ff::AV_NUM_DATA_POINTERS as usize];
 
@@ -373,6 +482,7 @@ pub struct PictureSettings {
     pub width: usize,
     pub height: usize,
     pub pixel_format: PixelFormat,
+    pub color: Colorimetry,
 }
 
 impl PictureSettings {
@@ -381,6 +491,11 @@ impl PictureSettings {
             width,
             height,
             pixel_format: PixelFormat::yuv420p(),
+            color: Colorimetry::default(),
         }
     }
+
+    pub fn with_color(self, color: Colorimetry) -> Self {
+        PictureSettings { color, ..self }
+    }
 }
@@ -13,6 +13,14 @@ impl PixelFormat {
         PixelFormat(ff::AVPixelFormat_AV_PIX_FMT_YUV420P)
     }
 
+    pub const fn yuva420p() -> Self {
+        PixelFormat(ff::AVPixelFormat_AV_PIX_FMT_YUVA420P)
+    }
+
+    pub const fn rgba() -> Self {
+        PixelFormat(ff::AVPixelFormat_AV_PIX_FMT_RGBA)
+    }
+
     pub unsafe fn from_raw(pixfmt: ff::AVPixelFormat) -> Self {
         PixelFormat(pixfmt)
     }
@@ -21,6 +29,19 @@ impl PixelFormat {
         self.0
     }
 
+    /// Whether this is one of the deprecated yuvj formats, which are full
+    /// range whether or not a frame is tagged as such
+    pub fn is_full_range(&self) -> bool {
+        match self.0 {
+            ff::AVPixelFormat_AV_PIX_FMT_YUVJ420P |
+            ff::AVPixelFormat_AV_PIX_FMT_YUVJ422P |
+            ff::AVPixelFormat_AV_PIX_FMT_YUVJ444P |
+            ff::AVPixelFormat_AV_PIX_FMT_YUVJ440P |
+            ff::AVPixelFormat_AV_PIX_FMT_YUVJ411P => true,
+            _ => self.descriptor().rgb(),
+        }
+    }
+
     pub fn name(&self) -> &'static str {
         unsafe {
             let ptr = ff::av_get_pix_fmt_name(self.0);
@@ -131,21 +152,29 @@ impl PlaneInfo {
         self.comp.plane.try_into().unwrap()
     }
 
+    /// Whether this is a U or V component. Luma and alpha are never
+    /// subsampled
+    pub fn is_chroma(&self) -> bool {
+        self.desc.color() == ColorFormat::Yuv && (self.idx == 1 || self.idx == 2)
+    }
+
+    pub fn is_luma(&self) -> bool {
+        self.desc.color() == ColorFormat::Yuv && self.idx == 0
+    }
+
     pub fn log2_horz(&self) -> usize {
-        if self.desc.color() == ColorFormat::Yuv && self.idx == 0 {
-            // luma
-            0
-        } else {
+        if self.is_chroma() {
             self.desc.log2_chroma_w()
+        } else {
+            0
         }
     }
 
     pub fn log2_vert(&self) -> usize {
-        if self.desc.color() == ColorFormat::Yuv && self.idx == 0 {
-            // luma
-            0
-        } else {
+        if self.is_chroma() {
             self.desc.log2_chroma_h()
+        } else {
+            0
         }
     }
 
@@ -31,6 +31,15 @@ impl SwsContext {
             panic!("sws_context_alloc: ENOMEM");
         }
 
+        // convert between color spaces and ranges as well as sizes. this
+        // fails harmlessly for rgb, which has no yuv matrix to speak of
+        unsafe {
+            ff::sws_setColorspaceDetails(ptr,
+                input.color.space.sws_coefficients(), input.color.range.sws_range(),
+                output.color.space.sws_coefficients(), output.color.range.sws_range(),
+                0, 1 << 16, 1 << 16);
+        }
+
         SwsContext {
             ptr,
             input,
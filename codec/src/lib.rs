@@ -2,6 +2,7 @@ pub mod aac;
 pub mod avc;
 pub mod ffmpeg;
 pub mod ogg;
+pub mod png;
 
 use std::io;
 
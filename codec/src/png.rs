@@ -0,0 +1,68 @@
+use std::convert::TryInto;
+use std::mem::MaybeUninit;
+use std::ptr;
+
+use ffmpeg_dev::sys as ff;
+
+use crate::ffmpeg::codec::AvCodecContext;
+use crate::ffmpeg::media::Video;
+use crate::ffmpeg::{AvError, AvFrame, AvPacket, ColorRange, Colorimetry, PictureSettings, PixelFormat, SwsContext};
+
+/// Encodes a single picture as an rgba png, keeping any alpha it has
+pub fn encode(frame: &AvFrame<Video>) -> Result<Vec<u8>, AvError> {
+    let input = frame.picture_settings();
+
+    let output = PictureSettings {
+        width: input.width,
+        height: input.height,
+        pixel_format: PixelFormat::rgba(),
+        color: Colorimetry { space: input.color.space, range: ColorRange::Full },
+    };
+
+    let mut rgba = AvFrame::blank(&output);
+    SwsContext::new(input, output.clone()).process(&frame.frame_data(), &mut rgba.frame_data_mut());
+
+    let codec = unsafe { ff::avcodec_find_encoder(ff::AVCodecID_AV_CODEC_ID_PNG) };
+
+    if codec == ptr::null_mut() {
+        panic!("avcodec_find_encoder: could not find png codec");
+    }
+
+    let mut ctx = unsafe { AvCodecContext::alloc(codec) };
+
+    unsafe {
+        let avctx = &mut *ctx.as_mut_ptr();
+        avctx.width = output.width.try_into().expect("width too large");
+        avctx.height = output.height.try_into().expect("height too large");
+        avctx.pix_fmt = output.pixel_format.into_raw();
+        avctx.time_base.num = 1;
+        avctx.time_base.den = 1;
+    }
+
+    let rc = unsafe { ff::avcodec_open2(ctx.as_mut_ptr(), codec, ptr::null_mut()) };
+
+    if rc < 0 {
+        return Err(AvError(rc));
+    }
+
+    let rc = unsafe { ff::avcodec_send_frame(ctx.as_mut_ptr(), rgba.as_ptr()) };
+
+    if rc < 0 {
+        return Err(AvError(rc));
+    }
+
+    let packet = unsafe {
+        let mut packet = MaybeUninit::<ff::AVPacket>::uninit();
+        ff::av_init_packet(packet.as_mut_ptr());
+
+        let rc = ff::avcodec_receive_packet(ctx.as_mut_ptr(), packet.as_mut_ptr());
+
+        if rc < 0 {
+            return Err(AvError(rc));
+        }
+
+        AvPacket::new(packet.assume_init())
+    };
+
+    Ok(packet.data().to_vec())
+}
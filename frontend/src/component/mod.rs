@@ -2,3 +2,4 @@ pub mod drag_target;
 pub mod midi_target;
 pub mod pure_module;
 pub mod scroll_target;
+pub mod stand_in;
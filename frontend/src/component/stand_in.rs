@@ -0,0 +1,103 @@
+use std::fmt::{self, Display};
+use std::rc::Rc;
+
+use yew::{html, Callback, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew_components::Select;
+
+use mixlab_protocol::{MediaId, MediaLibrary};
+
+use crate::session::SessionRef;
+use crate::util::notify;
+
+/// Picks the media an input module plays instead of its live source while
+/// the workspace is being rehearsed
+pub struct StandInSelect {
+    props: StandInProps,
+    link: ComponentLink<Self>,
+    library: Option<Rc<MediaLibrary>>,
+    _notify: notify::Handle,
+}
+
+#[derive(Properties, Clone)]
+pub struct StandInProps {
+    pub session: SessionRef,
+    pub selected: Option<MediaId>,
+    pub on_change: Callback<Option<MediaId>>,
+}
+
+pub enum StandInMsg {
+    MediaLibrary(Rc<MediaLibrary>),
+    Change(StandInItem),
+}
+
+impl Component for StandInSelect {
+    type Properties = StandInProps;
+    type Message = StandInMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let notify = props.session.listen_media(link.callback(StandInMsg::MediaLibrary));
+
+        StandInSelect {
+            props,
+            link,
+            library: None,
+            _notify: notify,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            StandInMsg::MediaLibrary(library) => {
+                self.library = Some(library);
+                true
+            }
+            StandInMsg::Change(item) => {
+                self.props.on_change.emit(item.0);
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let mut options = vec![StandInItem(None, "None".to_owned())];
+
+        options.extend(self.library.iter()
+            .flat_map(|library| library.items.iter())
+            .map(|item| StandInItem(Some(item.id), item.name.clone())));
+
+        let selected = options.iter()
+            .find(|item| item.0 == self.props.selected)
+            .cloned();
+
+        html! {
+            <label class="form-field">
+                <span class="form-field-label">{"Rehearsal stand-in"}</span>
+                <Select<StandInItem>
+                    options={options}
+                    selected={selected}
+                    on_change={self.link.callback(StandInMsg::Change)}
+                />
+            </label>
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct StandInItem(Option<MediaId>, String);
+
+impl PartialEq for StandInItem {
+    fn eq(&self, other: &StandInItem) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Display for StandInItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.1)
+    }
+}
@@ -4,6 +4,7 @@ mod component;
 mod control;
 mod library;
 mod module;
+mod param_panel;
 mod service;
 mod session;
 mod sidebar;
@@ -11,12 +12,16 @@ mod util;
 mod workspace;
 
 use std::fmt::Display;
+use std::rc::Rc;
 
 use derive_more::Display;
+use gloo_events::EventListener;
+use wasm_bindgen::JsCast;
 use wasm_bindgen::prelude::*;
+use web_sys::{Element, KeyboardEvent};
 use yew::{html, Component, ComponentLink, Html, ShouldRender, Callback, Properties};
 
-use mixlab_protocol::WorkspaceOp;
+use mixlab_protocol::{Access, WorkspaceOp};
 
 use library::MediaLibrary;
 use session::{Session, SessionRef};
@@ -28,6 +33,9 @@ pub struct App {
     link: ComponentLink<Self>,
     session: SessionRef,
     selected_tab: Tab,
+    access: Option<Rc<Access>>,
+    _keydown: EventListener,
+    _access_notify: notify::Handle,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Display)]
@@ -42,6 +50,7 @@ pub enum Tab {
 pub enum AppMsg {
     ClientUpdate(WorkspaceOp),
     ChangeTab(Tab),
+    Access(Rc<Access>),
 }
 
 impl Component for App {
@@ -49,10 +58,36 @@ impl Component for App {
     type Properties = ();
 
     fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let session = Session::new();
+
+        let keydown = EventListener::new(&web_sys::window().unwrap(), "keydown", {
+            let session = session.clone();
+
+            move |ev| {
+                let ev = match ev.dyn_ref::<KeyboardEvent>() {
+                    Some(ev) => ev,
+                    None => return,
+                };
+
+                if ev.ctrl_key() || ev.meta_key() || ev.alt_key() || is_text_entry(ev) {
+                    return;
+                }
+
+                if session.invoke_key(&ev.key()) {
+                    ev.prevent_default();
+                }
+            }
+        });
+
+        let access_notify = session.listen_access(link.callback(AppMsg::Access));
+
         App {
             link,
-            session: Session::new(),
+            session,
             selected_tab: Tab::Workspace,
+            access: None,
+            _keydown: keydown,
+            _access_notify: access_notify,
         }
     }
 
@@ -70,10 +105,28 @@ impl Component for App {
                 self.selected_tab = tab;
                 true
             }
+            AppMsg::Access(access) => {
+                self.access = Some(access);
+                true
+            }
         }
     }
 
     fn view(&self) -> Html {
+        // guests get the one view they were linked to and nothing else:
+        if let Some(Access::Guest(_)) = self.access.as_deref() {
+            return html! {
+                <div class="app app-guest">
+                    <div class="main">
+                        <WorkspaceContainer
+                            app={self.link.clone()}
+                            session={self.session.clone()}
+                        />
+                    </div>
+                </div>
+            };
+        }
+
         html! {
             <div class="app">
                 <SidebarContainer session={self.session.clone()} />
@@ -256,6 +309,17 @@ impl<T: Display + Clone + PartialEq + 'static> Component for TabBar<T> {
     }
 }
 
+// keys typed into a form field are left alone rather than invoking bindings
+fn is_text_entry(ev: &KeyboardEvent) -> bool {
+    ev.target()
+        .and_then(|target| target.dyn_into::<Element>().ok())
+        .map(|element| match element.tag_name().as_str() {
+            "INPUT" | "TEXTAREA" | "SELECT" => true,
+            _ => false,
+        })
+        .unwrap_or(false)
+}
+
 #[wasm_bindgen]
 pub fn start() {
     console_error_panic_hook::set_once();
@@ -15,10 +15,14 @@ use crate::util::{self, notify, Sequence};
 
 pub struct MediaLibrary {
     link: ComponentLink<Self>,
+    session: SessionRef,
     upload_seq: Sequence,
     uploads: BTreeMap<NonZeroUsize, InProgressUpload>,
     library: Option<Rc<protocol::MediaLibrary>>,
+    remote: String,
+    sync: Option<Rc<protocol::MediaSyncStatus>>,
     _notify: notify::Handle,
+    _sync_notify: notify::Handle,
 }
 
 #[derive(Properties, Clone)]
@@ -30,6 +34,9 @@ pub enum LibraryMsg {
     Update(Rc<protocol::MediaLibrary>),
     SelectFiles(Vec<File>),
     Upload(NonZeroUsize, UploadEvent),
+    Remote(String),
+    Push,
+    Sync(Rc<protocol::MediaSyncStatus>),
 }
 
 impl Component for MediaLibrary {
@@ -38,13 +45,18 @@ impl Component for MediaLibrary {
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         let notify = props.session.listen_media(link.callback(LibraryMsg::Update));
+        let sync_notify = props.session.listen_media_sync(link.callback(LibraryMsg::Sync));
 
         MediaLibrary {
             link,
+            session: props.session,
             upload_seq: Sequence::new(),
             uploads: BTreeMap::new(),
             library: None,
+            remote: String::new(),
+            sync: None,
             _notify: notify,
+            _sync_notify: sync_notify,
         }
     }
 
@@ -94,6 +106,24 @@ impl Component for MediaLibrary {
                     }
                 }
             }
+            LibraryMsg::Remote(remote) => {
+                self.remote = remote;
+                false
+            }
+            LibraryMsg::Push => {
+                let remote = self.remote.trim().to_owned();
+
+                if remote.is_empty() {
+                    return false;
+                }
+
+                self.session.push_media(remote);
+                false
+            }
+            LibraryMsg::Sync(status) => {
+                self.sync = Some(status);
+                true
+            }
         }
     }
 
@@ -103,6 +133,7 @@ impl Component for MediaLibrary {
                 <div class="media-library-main-button-row">
                     <UploadButton on_file_upload={self.link.callback(LibraryMsg::SelectFiles)} />
                 </div>
+                {self.view_sync()}
                 { if self.uploads.is_empty() {
                     html! {}
                 } else {
@@ -168,6 +199,41 @@ impl Component for MediaLibrary {
     }
 }
 
+impl MediaLibrary {
+    fn view_sync(&self) -> Html {
+        let status = self.sync.as_ref().map(|status| {
+            let progress = match (status.missing, &status.error) {
+                (_, Some(error)) => format!("Failed: {}", error),
+                (None, None) => format!("Comparing with {}", status.remote),
+                (Some(missing), None) if status.finished => {
+                    format!("Sent {} of {} missing items to {}", status.transferred, missing, status.remote)
+                }
+                (Some(missing), None) => {
+                    format!("Sending {} of {} to {}: {}", status.transferred + 1, missing, status.remote,
+                        status.uploading.as_deref().unwrap_or(""))
+                }
+            };
+
+            html! { <div class="media-library-sync-status">{progress}</div> }
+        }).unwrap_or(html! {});
+
+        html! {
+            <div class="media-library-sync">
+                <input type="text"
+                    placeholder="Push media to, eg. studio:8000/?key=..."
+                    value={&self.remote}
+                    onchange={self.link.callback(|ev| LibraryMsg::Remote(match ev {
+                        ChangeData::Value(value) => value,
+                        _ => String::new(),
+                    }))}
+                />
+                <button onclick={self.link.callback(|_| LibraryMsg::Push)}>{"Push"}</button>
+                {status}
+            </div>
+        }
+    }
+}
+
 fn format_size(bytes: usize) -> String {
     const KIB: usize = 1024;
     const MIB: usize = 1024 * 1024;
@@ -291,7 +357,7 @@ pub enum UploadEvent {
 impl UploadTask {
     fn start(file: File, callback: Callback<UploadEvent>) -> Result<UploadTask, JsValue> {
         crate::log!("origin: {:?}", util::origin());
-        let url = util::origin() + "/_upload/" + &file.name();
+        let url = util::origin() + "/_upload/" + &file.name() + &util::location_query();
 
         let mut kind = file.type_();
         if kind == "" {
@@ -0,0 +1,140 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ArtNetOutputParams, ArtNetOutputIndication, TemporalWarningStatus};
+
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
+use crate::control::Fader;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone)]
+pub struct ArtNetOutputProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: ArtNetOutputParams,
+    pub indication: ArtNetOutputIndication,
+    pub midi_mode: MidiUiMode,
+}
+
+pub struct ArtNetOutput {
+    props: ArtNetOutputProps,
+}
+
+impl Component for ArtNetOutput {
+    type Properties = ArtNetOutputProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={warning_class(self.props.indication.error)}>{"ERROR"}</div>
+                </div>
+
+                <label class="form-field">
+                    <span class="form-field-label">{"Destination"}</span>
+                    <input type="text"
+                        onchange={self.callback(text(|destination, params| {
+                            ArtNetOutputParams { destination, ..params }
+                        }))}
+                        value={&self.props.params.destination}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{"Universe"}</span>
+                    <input type="number"
+                        min={0}
+                        max={32767}
+                        onchange={self.callback(text(|universe, params| {
+                            let universe = universe.parse().unwrap_or(params.universe);
+                            ArtNetOutputParams { universe, ..params }
+                        }))}
+                        value={self.props.params.universe}
+                    />
+                </label>
+
+                <div class="artnet-channels">
+                    { for self.props.params.channels.iter().enumerate().map(|(idx, channel)| html! {
+                        <div class="artnet-channel">
+                            <input type="number"
+                                class="artnet-channel-address"
+                                min={1}
+                                max={512}
+                                onchange={self.callback(text(move |address, mut params| {
+                                    if let Ok(address) = address.parse() {
+                                        params.channels[idx].address = address;
+                                    }
+                                    params
+                                }))}
+                                value={channel.address}
+                            />
+                            <MidiRangeTarget
+                                ui_mode={self.props.midi_mode}
+                                onchange={self.callback(update_level(idx))}
+                            >
+                                <Fader
+                                    value={channel.level}
+                                    onchange={self.callback(update_level(idx))}
+                                />
+                            </MidiRangeTarget>
+                        </div>
+                    }) }
+                </div>
+            </>
+        }
+    }
+}
+
+impl ArtNetOutput {
+    fn callback<Ev>(&self, f: impl Fn(Ev, ArtNetOutputParams) -> ArtNetOutputParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            WindowMsg::UpdateParams(
+                ModuleParams::ArtNetOutput(f(ev, params.clone())))
+        })
+    }
+}
+
+fn update_level(idx: usize) -> impl Fn(f64, ArtNetOutputParams) -> ArtNetOutputParams {
+    move |level, mut params| {
+        params.channels[idx].level = level;
+        params
+    }
+}
+
+fn text<T>(f: impl Fn(String, ArtNetOutputParams) -> T)
+    -> impl Fn(ChangeData, ArtNetOutputParams) -> T
+{
+    move |change, params| {
+        if let ChangeData::Value(value) = change {
+            f(value, params)
+        } else {
+            unreachable!()
+        }
+    }
+}
+
+fn warning_class(warning_status: Option<TemporalWarningStatus>) -> &'static str {
+    match warning_status {
+        None => "status-light",
+        Some(TemporalWarningStatus::Active) => "status-light status-light-red-active",
+        Some(TemporalWarningStatus::Recent) => "status-light status-light-red",
+    }
+}
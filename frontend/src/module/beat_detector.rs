@@ -0,0 +1,100 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, BeatDetectorParams, BeatDetectorIndication};
+
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct BeatDetectorProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: BeatDetectorParams,
+    pub indication: BeatDetectorIndication,
+}
+
+pub struct BeatDetector {
+    props: BeatDetectorProps,
+}
+
+impl Component for BeatDetector {
+    type Properties = BeatDetectorProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let sensitivity_id = format!("w{}-sensitivity", self.props.id.0);
+        let sensitivity_params = self.props.params.clone();
+
+        let gate_id = format!("w{}-gate", self.props.id.0);
+        let gate_params = self.props.params.clone();
+
+        let beat_class = if self.props.indication.gate {
+            "status-light status-light-green-active"
+        } else {
+            "status-light"
+        };
+
+        let bpm = match self.props.indication.bpm {
+            Some(bpm) => format!("{:.1}", bpm),
+            None => "-".to_owned(),
+        };
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={beat_class}>{"BEAT"}</div>
+                </div>
+                <div class="beat-detector-bpm">
+                    {bpm}
+                    <span class="beat-detector-bpm-unit">{"BPM"}</span>
+                </div>
+                <label for={&sensitivity_id}>{"Sensitivity"}</label>
+                <input type="range"
+                    id={&sensitivity_id}
+                    min={1}
+                    max={4}
+                    step={0.05}
+                    onchange={self.props.module.callback(move |ev| {
+                        let sensitivity = extract_float_value(ev).unwrap_or(1.0);
+                        let params = BeatDetectorParams { sensitivity, ..sensitivity_params };
+                        WindowMsg::UpdateParams(ModuleParams::BeatDetector(params))
+                    })}
+                    value={self.props.params.sensitivity}
+                />
+                <label for={&gate_id}>{"Gate"}</label>
+                <input type="range"
+                    id={&gate_id}
+                    min={5}
+                    max={500}
+                    step={1}
+                    onchange={self.props.module.callback(move |ev| {
+                        let gate_ms = extract_float_value(ev).unwrap_or(0.0);
+                        let params = BeatDetectorParams { gate_ms, ..gate_params };
+                        WindowMsg::UpdateParams(ModuleParams::BeatDetector(params))
+                    })}
+                    value={self.props.params.gate_ms}
+                />
+            </>
+        }
+    }
+}
+
+fn extract_float_value(event: ChangeData) -> Option<f64> {
+    match event {
+        ChangeData::Value(float_str) => float_str.parse().ok(),
+        _ => None
+    }
+}
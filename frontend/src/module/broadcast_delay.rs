@@ -0,0 +1,124 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, BroadcastDelayParams, BroadcastDelayIndication, BROADCAST_DELAY_MAX_SECONDS};
+
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct BroadcastDelayProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: BroadcastDelayParams,
+    pub indication: BroadcastDelayIndication,
+}
+
+pub struct BroadcastDelay {
+    props: BroadcastDelayProps,
+}
+
+impl Component for BroadcastDelay {
+    type Properties = BroadcastDelayProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let indication = &self.props.indication;
+        let params = &self.props.params;
+
+        let seconds_id = format!("w{}-seconds", self.props.id.0);
+        let dump_seconds_id = format!("w{}-dump-seconds", self.props.id.0);
+
+        let building_class = if indication.building {
+            "status-light status-light-red-active"
+        } else {
+            "status-light"
+        };
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={building_class}>{"BUILDING"}</div>
+                </div>
+
+                <div class="broadcast-delay-time">
+                    {format!("{}.{}s", indication.delay_tenths / 10, indication.delay_tenths % 10)}
+                </div>
+
+                <div class="broadcast-delay-buttons">
+                    <button
+                        class="broadcast-delay-dump"
+                        onclick={self.callback(|_, params| {
+                            BroadcastDelayParams { dump: params.dump + 1, ..params }
+                        })}
+                    >
+                        {"Dump"}
+                    </button>
+                </div>
+
+                <label for={&seconds_id}>{format!("Delay {:.0}s", params.seconds)}</label>
+                <input type="range"
+                    id={&seconds_id}
+                    min={0}
+                    max={BROADCAST_DELAY_MAX_SECONDS}
+                    step={1}
+                    onchange={self.callback(float(|seconds, params| {
+                        BroadcastDelayParams { seconds, ..params }
+                    }))}
+                    value={params.seconds}
+                />
+
+                <label for={&dump_seconds_id}>{format!("Dump {:.0}s", params.dump_seconds)}</label>
+                <input type="range"
+                    id={&dump_seconds_id}
+                    min={1}
+                    max={BROADCAST_DELAY_MAX_SECONDS}
+                    step={1}
+                    onchange={self.callback(float(|dump_seconds, params| {
+                        BroadcastDelayParams { dump_seconds, ..params }
+                    }))}
+                    value={params.dump_seconds}
+                />
+            </>
+        }
+    }
+}
+
+impl BroadcastDelay {
+    fn callback<Ev>(&self, f: impl Fn(Ev, BroadcastDelayParams) -> BroadcastDelayParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            WindowMsg::UpdateParams(
+                ModuleParams::BroadcastDelay(f(ev, params.clone())))
+        })
+    }
+}
+
+fn float(f: impl Fn(f64, BroadcastDelayParams) -> BroadcastDelayParams)
+    -> impl Fn(ChangeData, BroadcastDelayParams) -> BroadcastDelayParams
+{
+    move |change, params| {
+        match change {
+            ChangeData::Value(value) => match value.parse() {
+                Ok(value) => f(value, params),
+                Err(_) => params,
+            },
+            _ => params,
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, BrowserSourceParams, BrowserSourceIndication};
+
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct BrowserSourceProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: BrowserSourceParams,
+    pub indication: BrowserSourceIndication,
+}
+
+pub struct BrowserSource {
+    props: BrowserSourceProps,
+}
+
+impl Component for BrowserSource {
+    type Properties = BrowserSourceProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let indication = &self.props.indication;
+        let params = &self.props.params;
+
+        let fps_id = format!("w{}-fps", self.props.id.0);
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={light_class(indication.running, "status-light-green-active")}>{"LIVE"}</div>
+                    <div class={light_class(indication.error, "status-light-red-active")}>{"ERROR"}</div>
+                </div>
+
+                <label class="form-field">
+                    <span class="form-field-label">{"URL"}</span>
+                    <input type="text"
+                        onchange={self.callback(text(|url, params| {
+                            BrowserSourceParams { url, ..params }
+                        }))}
+                        value={&params.url}
+                    />
+                </label>
+
+                <div class="browser-source-size">
+                    <label class="form-field">
+                        <span class="form-field-label">{"Width"}</span>
+                        <input type="number"
+                            min={16}
+                            onchange={self.callback(text(|width, params| {
+                                match width.parse() {
+                                    Ok(width) => BrowserSourceParams { width, ..params },
+                                    Err(_) => params,
+                                }
+                            }))}
+                            value={params.width}
+                        />
+                    </label>
+
+                    <label class="form-field">
+                        <span class="form-field-label">{"Height"}</span>
+                        <input type="number"
+                            min={16}
+                            onchange={self.callback(text(|height, params| {
+                                match height.parse() {
+                                    Ok(height) => BrowserSourceParams { height, ..params },
+                                    Err(_) => params,
+                                }
+                            }))}
+                            value={params.height}
+                        />
+                    </label>
+                </div>
+
+                <label for={&fps_id}>{format!("Frame rate {:.0} fps", params.fps)}</label>
+                <input type="range"
+                    id={&fps_id}
+                    min={1}
+                    max={60}
+                    step={1}
+                    onchange={self.callback(text(|fps, params| {
+                        match fps.parse() {
+                            Ok(fps) => BrowserSourceParams { fps, ..params },
+                            Err(_) => params,
+                        }
+                    }))}
+                    value={params.fps}
+                />
+
+                <div class="browser-source-buttons">
+                    <button
+                        onclick={self.callback(|_, params| {
+                            BrowserSourceParams { reload: params.reload + 1, ..params }
+                        })}
+                    >
+                        {"Reload"}
+                    </button>
+                </div>
+            </>
+        }
+    }
+}
+
+impl BrowserSource {
+    fn callback<Ev>(&self, f: impl Fn(Ev, BrowserSourceParams) -> BrowserSourceParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            WindowMsg::UpdateParams(
+                ModuleParams::BrowserSource(f(ev, params.clone())))
+        })
+    }
+}
+
+fn text(f: impl Fn(String, BrowserSourceParams) -> BrowserSourceParams)
+    -> impl Fn(ChangeData, BrowserSourceParams) -> BrowserSourceParams
+{
+    move |change, params| {
+        match change {
+            ChangeData::Value(value) => f(value, params),
+            _ => params,
+        }
+    }
+}
+
+fn light_class(active: bool, active_class: &str) -> String {
+    if active {
+        format!("status-light {}", active_class)
+    } else {
+        "status-light".to_owned()
+    }
+}
@@ -0,0 +1,86 @@
+use yew::{html, ComponentLink, Html};
+
+use mixlab_protocol::{ModuleId, ModuleParams, BusParams, Decibel};
+
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
+use crate::component::pure_module::{Pure, PureModule};
+use crate::control::{Fader, Rotary};
+use crate::workspace::{Window, WindowMsg};
+
+pub type Bus = Pure<BusParams>;
+
+impl PureModule for BusParams {
+    fn view(&self, _: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
+        html! {
+            <div class="bus">
+                <div class="bus-inputs">
+                    { for self.inputs.iter().enumerate().map(|(idx, input)| html! {
+                        <div class="bus-input">
+                            <div>{idx + 1}</div>
+                            <MidiRangeTarget
+                                ui_mode={midi_mode}
+                                onchange={module.callback(update_gain(self, idx, |gain| Decibel(gain * 30.0 - 24.0)))}
+                            >
+                                <Rotary<Decibel>
+                                    value={input.gain}
+                                    min={Decibel(-24.0)}
+                                    max={Decibel(6.0)}
+                                    default={Decibel(0.0)}
+                                    onchange={module.callback(update_gain(self, idx, |gain| gain))}
+                                />
+                            </MidiRangeTarget>
+                            <div>{"PAN"}</div>
+                            <MidiRangeTarget
+                                ui_mode={midi_mode}
+                                onchange={module.callback(update_pan(self, idx, |pan| pan * 2.0 - 1.0))}
+                            >
+                                <Rotary<f64>
+                                    value={input.pan}
+                                    min={-1.0}
+                                    max={1.0}
+                                    default={0.0}
+                                    onchange={module.callback(update_pan(self, idx, |pan| pan))}
+                                />
+                            </MidiRangeTarget>
+                        </div>
+                    }) }
+                </div>
+                <MidiRangeTarget
+                    ui_mode={midi_mode}
+                    onchange={module.callback(update_master(self))}
+                >
+                    <Fader
+                        value={self.master}
+                        onchange={module.callback(update_master(self))}
+                    />
+                </MidiRangeTarget>
+            </div>
+        }
+    }
+}
+
+fn update_gain<T>(params: &BusParams, idx: usize, f: impl Fn(T) -> Decibel) -> impl Fn(T) -> WindowMsg {
+    let params = params.clone();
+    move |value| {
+        let mut params = params.clone();
+        params.inputs[idx].gain = f(value);
+        WindowMsg::UpdateParams(ModuleParams::Bus(params))
+    }
+}
+
+fn update_pan(params: &BusParams, idx: usize, f: impl Fn(f64) -> f64) -> impl Fn(f64) -> WindowMsg {
+    let params = params.clone();
+    move |value| {
+        let mut params = params.clone();
+        params.inputs[idx].pan = f(value);
+        WindowMsg::UpdateParams(ModuleParams::Bus(params))
+    }
+}
+
+fn update_master(params: &BusParams) -> impl Fn(f64) -> WindowMsg {
+    let params = params.clone();
+    move |master| {
+        let params = BusParams { master, ..params.clone() };
+        WindowMsg::UpdateParams(ModuleParams::Bus(params))
+    }
+}
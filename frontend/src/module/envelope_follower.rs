@@ -0,0 +1,75 @@
+use yew::{html, ComponentLink, Html};
+
+use mixlab_protocol::{ModuleId, ModuleParams, EnvelopeFollowerParams, Decibel};
+
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
+use crate::component::pure_module::{Pure, PureModule};
+use crate::control::Rotary;
+use crate::workspace::{Window, WindowMsg};
+
+pub type EnvelopeFollower = Pure<EnvelopeFollowerParams>;
+
+const MAX_ATTACK_MS: f64 = 500.0;
+const MAX_RELEASE_MS: f64 = 2000.0;
+
+impl PureModule for EnvelopeFollowerParams {
+    fn view(&self, _: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
+        html! {
+            <div class="envelope-follower">
+                <div>{"GAIN"}</div>
+                <MidiRangeTarget
+                    ui_mode={midi_mode}
+                    onchange={module.callback(update_params(self,
+                        |params, gain| EnvelopeFollowerParams { gain: Decibel(gain * 30.0 - 6.0), ..params }))}
+                >
+                    <Rotary<Decibel>
+                        value={self.gain}
+                        min={Decibel(-6.0)}
+                        max={Decibel(24.0)}
+                        default={Decibel(0.0)}
+                        onchange={module.callback(update_params(self,
+                            |params, gain| EnvelopeFollowerParams { gain, ..params }))}
+                    />
+                </MidiRangeTarget>
+                <div>{"ATTACK"}</div>
+                <MidiRangeTarget
+                    ui_mode={midi_mode}
+                    onchange={module.callback(update_params(self,
+                        |params, attack: f64| EnvelopeFollowerParams { attack_ms: attack * MAX_ATTACK_MS, ..params }))}
+                >
+                    <Rotary<f64>
+                        value={self.attack_ms}
+                        min={0.0}
+                        max={MAX_ATTACK_MS}
+                        default={10.0}
+                        onchange={module.callback(update_params(self,
+                            |params, attack_ms| EnvelopeFollowerParams { attack_ms, ..params }))}
+                    />
+                </MidiRangeTarget>
+                <div>{"RELEASE"}</div>
+                <MidiRangeTarget
+                    ui_mode={midi_mode}
+                    onchange={module.callback(update_params(self,
+                        |params, release: f64| EnvelopeFollowerParams { release_ms: release * MAX_RELEASE_MS, ..params }))}
+                >
+                    <Rotary<f64>
+                        value={self.release_ms}
+                        min={0.0}
+                        max={MAX_RELEASE_MS}
+                        default={250.0}
+                        onchange={module.callback(update_params(self,
+                            |params, release_ms| EnvelopeFollowerParams { release_ms, ..params }))}
+                    />
+                </MidiRangeTarget>
+            </div>
+        }
+    }
+}
+
+fn update_params<T>(params: &EnvelopeFollowerParams, f: impl Fn(EnvelopeFollowerParams, T) -> EnvelopeFollowerParams) -> impl Fn(T) -> WindowMsg {
+    let params = params.clone();
+    move |value| {
+        let params = f(params.clone(), value);
+        WindowMsg::UpdateParams(ModuleParams::EnvelopeFollower(params))
+    }
+}
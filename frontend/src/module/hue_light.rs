@@ -0,0 +1,147 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, HueLightParams, HueLightIndication, TemporalWarningStatus};
+
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
+use crate::control::Rotary;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone)]
+pub struct HueLightProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: HueLightParams,
+    pub indication: HueLightIndication,
+    pub midi_mode: MidiUiMode,
+}
+
+pub struct HueLight {
+    props: HueLightProps,
+}
+
+impl Component for HueLight {
+    type Properties = HueLightProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={warning_class(self.props.indication.error)}>{"ERROR"}</div>
+                </div>
+
+                <label class="form-field">
+                    <span class="form-field-label">{"Bridge"}</span>
+                    <input type="text"
+                        onchange={self.callback(text(|bridge, params| {
+                            HueLightParams { bridge, ..params }
+                        }))}
+                        value={&self.props.params.bridge}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{"Username"}</span>
+                    <input type="text"
+                        onchange={self.callback(text(|username, params| {
+                            HueLightParams { username, ..params }
+                        }))}
+                        value={&self.props.params.username}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{"Light"}</span>
+                    <input type="text"
+                        onchange={self.callback(text(|light, params| {
+                            HueLightParams { light, ..params }
+                        }))}
+                        value={&self.props.params.light}
+                    />
+                </label>
+
+                <div class="hue-light-controls">
+                    {self.view_control("BRI", self.props.params.brightness, 1.0,
+                        |brightness, params| HueLightParams { brightness, ..params })}
+                    {self.view_control("HUE", self.props.params.hue, 0.0,
+                        |hue, params| HueLightParams { hue, ..params })}
+                    {self.view_control("SAT", self.props.params.saturation, 0.0,
+                        |saturation, params| HueLightParams { saturation, ..params })}
+                </div>
+            </>
+        }
+    }
+}
+
+impl HueLight {
+    fn view_control(
+        &self,
+        label: &str,
+        value: f64,
+        default: f64,
+        f: impl Fn(f64, HueLightParams) -> HueLightParams + Copy + 'static,
+    ) -> Html {
+        html! {
+            <div class="hue-light-control">
+                <div>{label}</div>
+                <MidiRangeTarget
+                    ui_mode={self.props.midi_mode}
+                    onchange={self.callback(f)}
+                >
+                    <Rotary<f64>
+                        value={value}
+                        min={0.0}
+                        max={1.0}
+                        default={default}
+                        onchange={self.callback(f)}
+                    />
+                </MidiRangeTarget>
+            </div>
+        }
+    }
+
+    fn callback<Ev>(&self, f: impl Fn(Ev, HueLightParams) -> HueLightParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            WindowMsg::UpdateParams(
+                ModuleParams::HueLight(f(ev, params.clone())))
+        })
+    }
+}
+
+fn text<T>(f: impl Fn(String, HueLightParams) -> T)
+    -> impl Fn(ChangeData, HueLightParams) -> T
+{
+    move |change, params| {
+        if let ChangeData::Value(value) = change {
+            f(value, params)
+        } else {
+            unreachable!()
+        }
+    }
+}
+
+fn warning_class(warning_status: Option<TemporalWarningStatus>) -> &'static str {
+    match warning_status {
+        None => "status-light",
+        Some(TemporalWarningStatus::Active) => "status-light status-light-red-active",
+        Some(TemporalWarningStatus::Recent) => "status-light status-light-red",
+    }
+}
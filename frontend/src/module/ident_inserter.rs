@@ -0,0 +1,203 @@
+use std::fmt::{self, Display};
+use std::rc::Rc;
+
+use yew::{html, Callback, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, IdentInserterParams, IdentInserterIndication, MediaLibrary, MediaId};
+
+use crate::util::notify;
+use crate::session::SessionRef;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct IdentInserterProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: IdentInserterParams,
+    pub indication: IdentInserterIndication,
+    pub session: SessionRef,
+}
+
+pub struct IdentInserter {
+    props: IdentInserterProps,
+    link: ComponentLink<Self>,
+    library: Option<Rc<MediaLibrary>>,
+    _notify: notify::Handle,
+}
+
+pub enum IdentInserterMsg {
+    MediaLibrary(Rc<MediaLibrary>),
+    ChangeMedia(IdentItem),
+}
+
+impl Component for IdentInserter {
+    type Properties = IdentInserterProps;
+    type Message = IdentInserterMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let notify = props.session.listen_media(link.callback(IdentInserterMsg::MediaLibrary));
+
+        Self {
+            props,
+            link,
+            library: None,
+            _notify: notify,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            IdentInserterMsg::MediaLibrary(library) => {
+                self.library = Some(library);
+                true
+            }
+            IdentInserterMsg::ChangeMedia(item) => {
+                self.props.module.send_message(
+                    WindowMsg::UpdateParams(
+                        ModuleParams::IdentInserter(
+                            IdentInserterParams {
+                                media_id: Some(item.id),
+                                ..self.props.params.clone()
+                            })));
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let indication = &self.props.indication;
+        let params = &self.props.params;
+
+        let interval_id = format!("w{}-interval", self.props.id.0);
+        let duck_id = format!("w{}-duck", self.props.id.0);
+
+        let options = self.library.iter()
+            .flat_map(|library| library.items.iter())
+            .map(|item| IdentItem { id: item.id, name: item.name.clone() })
+            .collect::<Vec<_>>();
+
+        let selected = params.media_id.map(|id| {
+            // name can be empty, we never display this item
+            IdentItem { id, name: String::new() }
+        });
+
+        let playing_class = if indication.playing {
+            "status-light status-light-green-active"
+        } else {
+            "status-light"
+        };
+
+        let next = match indication.next_secs {
+            Some(secs) => format!("Next in {}:{:02}", secs / 60, secs % 60),
+            None if params.interval_minutes > 0.0 => String::new(),
+            None => "On trigger only".to_owned(),
+        };
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={playing_class}>{"IDENT"}</div>
+                    <div class="ident-inserter-next">{next}</div>
+                </div>
+
+                <Select<IdentItem>
+                    options={options}
+                    selected={selected}
+                    on_change={self.link.callback(IdentInserterMsg::ChangeMedia)}
+                />
+
+                <div class="ident-inserter-buttons">
+                    <button
+                        onclick={self.callback(|_, params| {
+                            IdentInserterParams { trigger: params.trigger + 1, ..params }
+                        })}
+                    >
+                        {"Play now"}
+                    </button>
+                </div>
+
+                <label for={&interval_id}>
+                    {if params.interval_minutes > 0.0 {
+                        format!("Every {:.0} min", params.interval_minutes)
+                    } else {
+                        "No interval".to_owned()
+                    }}
+                </label>
+                <input type="range"
+                    id={&interval_id}
+                    min={0}
+                    max={60}
+                    step={1}
+                    onchange={self.callback(float(|interval_minutes, params| {
+                        IdentInserterParams { interval_minutes, ..params }
+                    }))}
+                    value={params.interval_minutes}
+                />
+
+                <label for={&duck_id}>{format!("Duck {:.0} dB", params.duck_db)}</label>
+                <input type="range"
+                    id={&duck_id}
+                    min={-40}
+                    max={0}
+                    step={1}
+                    onchange={self.callback(float(|duck_db, params| {
+                        IdentInserterParams { duck_db, ..params }
+                    }))}
+                    value={params.duck_db}
+                />
+            </>
+        }
+    }
+}
+
+impl IdentInserter {
+    fn callback<Ev>(&self, f: impl Fn(Ev, IdentInserterParams) -> IdentInserterParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            WindowMsg::UpdateParams(
+                ModuleParams::IdentInserter(f(ev, params.clone())))
+        })
+    }
+}
+
+fn float(f: impl Fn(f64, IdentInserterParams) -> IdentInserterParams)
+    -> impl Fn(ChangeData, IdentInserterParams) -> IdentInserterParams
+{
+    move |change, params| {
+        match change {
+            ChangeData::Value(value) => match value.parse() {
+                Ok(value) => f(value, params),
+                Err(_) => params,
+            },
+            _ => params,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct IdentItem {
+    pub id: MediaId,
+    pub name: String,
+}
+
+impl PartialEq for IdentItem {
+    fn eq(&self, other: &IdentItem) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Display for IdentItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
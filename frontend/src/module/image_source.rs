@@ -0,0 +1,168 @@
+use std::rc::Rc;
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ImageSourceParams, ImageSourceIndication, MediaLibrary};
+
+use crate::module::media_source::MediaSourceItem;
+use crate::util::notify;
+use crate::session::SessionRef;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct ImageSourceProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: ImageSourceParams,
+    pub indication: ImageSourceIndication,
+    pub session: SessionRef,
+}
+
+pub struct ImageSource {
+    props: ImageSourceProps,
+    link: ComponentLink<Self>,
+    library: Option<Rc<MediaLibrary>>,
+    _notify: notify::Handle,
+}
+
+pub enum ImageSourceMsg {
+    MediaLibrary(Rc<MediaLibrary>),
+    ChangeSource(MediaSourceItem),
+    ToggleSequence,
+    ChangeFps(ChangeData),
+}
+
+impl Component for ImageSource {
+    type Properties = ImageSourceProps;
+    type Message = ImageSourceMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let notify = props.session.listen_media(link.callback(ImageSourceMsg::MediaLibrary));
+
+        Self {
+            props,
+            link,
+            library: None,
+            _notify: notify,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        let params = self.props.params.clone();
+
+        let params = match msg {
+            ImageSourceMsg::MediaLibrary(library) => {
+                self.library = Some(library);
+                return true;
+            }
+            ImageSourceMsg::ChangeSource(source) => {
+                ImageSourceParams { media_id: Some(source.id), ..params }
+            }
+            ImageSourceMsg::ToggleSequence => {
+                ImageSourceParams { sequence: !params.sequence, ..params }
+            }
+            ImageSourceMsg::ChangeFps(ChangeData::Value(value)) => {
+                match value.parse() {
+                    Ok(fps) => ImageSourceParams { fps, ..params },
+                    Err(_) => return false,
+                }
+            }
+            ImageSourceMsg::ChangeFps(_) => {
+                return false;
+            }
+        };
+
+        self.props.module.send_message(
+            WindowMsg::UpdateParams(
+                ModuleParams::ImageSource(params)));
+
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let params = &self.props.params;
+        let indication = &self.props.indication;
+
+        let options = self.library.iter()
+            .flat_map(|library| library.items.iter().cloned())
+            .map(|item| {
+                MediaSourceItem {
+                    id: item.id,
+                    name: item.name.clone(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let selected = params.media_id.map(|id| {
+            MediaSourceItem {
+                id,
+                // name can be empty, we never display this item
+                name: String::new(),
+            }
+        });
+
+        let sequence_class = if params.sequence {
+            "image-source-sequence image-source-sequence-active"
+        } else {
+            "image-source-sequence"
+        };
+
+        let status = if indication.loading {
+            "Loading...".to_owned()
+        } else if indication.error {
+            "Could not load image".to_owned()
+        } else if indication.frames > 0 {
+            format!("{} frames", indication.frames)
+        } else {
+            String::new()
+        };
+
+        let fps_id = format!("w{}-fps", self.props.id.0);
+
+        html! {
+            <>
+                <Select<MediaSourceItem>
+                    options={options}
+                    selected={selected}
+                    on_change={self.link.callback(ImageSourceMsg::ChangeSource)}
+                />
+
+                <div class="image-source-status">{status}</div>
+
+                <div class="image-source-buttons">
+                    <button
+                        class={sequence_class}
+                        onclick={self.link.callback(|_| ImageSourceMsg::ToggleSequence)}
+                    >
+                        {"Numbered sequence"}
+                    </button>
+                </div>
+
+                { if params.sequence {
+                    html! {
+                        <>
+                            <label for={&fps_id}>{format!("Frame rate {:.0} fps", params.fps)}</label>
+                            <input type="range"
+                                id={&fps_id}
+                                min={1}
+                                max={60}
+                                step={1}
+                                onchange={self.link.callback(ImageSourceMsg::ChangeFps)}
+                                value={params.fps}
+                            />
+                        </>
+                    }
+                } else {
+                    html! {}
+                } }
+            </>
+        }
+    }
+}
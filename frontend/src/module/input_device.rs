@@ -0,0 +1,254 @@
+use std::fmt::{self, Display};
+use std::iter;
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, InputDeviceParams, InputDeviceIndication, TemporalWarningStatus, Decibel};
+
+use crate::component::stand_in::StandInSelect;
+use crate::session::SessionRef;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct InputDeviceProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: InputDeviceParams,
+    pub indication: InputDeviceIndication,
+    pub session: SessionRef,
+}
+
+pub struct InputDevice {
+    props: InputDeviceProps,
+}
+
+impl Component for InputDevice {
+    type Properties = InputDeviceProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        #[derive(PartialEq, Clone)]
+        struct InputChannel(Option<usize>);
+
+        impl Display for InputChannel {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self.0 {
+                    Some(ch) => {
+                        // channels are 0-indexed internally, but 1-indexed in the UI:
+                        let display_channel_number = ch + 1;
+
+                        write!(f, "Channel #{}", display_channel_number)
+                    }
+                    None => {
+                        write!(f, "None")
+                    }
+                }
+            }
+        }
+
+        #[derive(PartialEq, Clone)]
+        struct MonitorDevice(Option<String>);
+
+        impl Display for MonitorDevice {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match &self.0 {
+                    Some(device) => write!(f, "{}", device),
+                    None => write!(f, "Off"),
+                }
+            }
+        }
+
+        let devices = self.props.indication.devices.as_ref()
+            .map(|devices| devices.as_slice())
+            .unwrap_or(&[]);
+
+        let device_names = devices.iter()
+            .map(|(device_name, _)| device_name)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let channels = iter::once(None)
+            .chain(
+                devices.iter()
+                    .find(|(dev, _)| Some(dev) == self.props.params.device.as_ref())
+                    .into_iter()
+                    .flat_map(|(_, channel_count)| 0..*channel_count)
+                    .map(Some))
+            .map(InputChannel)
+            .collect::<Vec<_>>();
+
+        let monitor_devices = iter::once(None)
+            .chain(self.props.indication.monitor_devices.iter().flatten().cloned().map(Some))
+            .map(MonitorDevice)
+            .collect::<Vec<_>>();
+
+        let gain_id = format!("w{}-monitor-gain", self.props.id.0);
+
+        let params = &self.props.params;
+        let indication = &self.props.indication;
+
+        let missing = (params.device.is_some() && indication.resolved.is_none())
+            || (params.monitor.is_some() && indication.monitor_resolved.is_none());
+
+        let linked = |device: &Option<String>, resolved: &Option<String>| {
+            match (device, resolved) {
+                (Some(device), Some(resolved)) if device != resolved => html! {
+                    <div class="device-linked">{format!("Linked to {}", resolved)}</div>
+                },
+                _ => html! {},
+            }
+        };
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={warning_class(self.props.indication.clip)}>{"CLIP"}</div>
+                    <div class={warning_class(self.props.indication.lag)}>{"LAG"}</div>
+                    <div class={missing_class(missing)}>{"MISSING"}</div>
+                </div>
+                <button
+                    onclick={self.props.module.callback({
+                        let device = self.props.indication.default_device.clone();
+
+                        let channel_count = devices.iter()
+                            .find(|(name, _)| Some(name) == device.as_ref())
+                            .map(|(_, channels)| channels);
+
+                        let left = Some(0).filter(|ch| channel_count > Some(ch));
+                        let right = Some(1).filter(|ch| channel_count > Some(ch)).or(left);
+
+                        let params = InputDeviceParams {
+                            device,
+                            left,
+                            right,
+                            ..self.props.params.clone()
+                        };
+
+                        move |_| WindowMsg::UpdateParams(
+                            ModuleParams::InputDevice(params.clone()))
+                    })}
+                >
+                    {"Use system defaults"}
+                </button>
+
+                <label>{"Input device"}</label>
+                {linked(&params.device, &indication.resolved)}
+                <Select<String>
+                    selected={&self.props.params.device}
+                    options={device_names}
+                    on_change={self.props.module.callback({
+                        let params = self.props.params.clone();
+                        move |device: String| {
+                            let params = InputDeviceParams { device: Some(device), ..params.clone() };
+                            WindowMsg::UpdateParams(ModuleParams::InputDevice(params))
+                        }
+                    })}
+                />
+
+                <label>{"Left channel"}</label>
+                <Select<InputChannel>
+                    selected={InputChannel(self.props.params.left)}
+                    options={channels.clone()}
+                    on_change={self.props.module.callback({
+                        let params = self.props.params.clone();
+                        move |chan: InputChannel| {
+                            let params = InputDeviceParams { left: chan.0, ..params.clone() };
+                            WindowMsg::UpdateParams(ModuleParams::InputDevice(params))
+                        }
+                    })}
+                />
+
+                <label>{"Right channel"}</label>
+                <Select<InputChannel>
+                    selected={InputChannel(self.props.params.right)}
+                    options={channels}
+                    on_change={self.props.module.callback({
+                        let params = self.props.params.clone();
+                        move |chan: InputChannel| {
+                            let params = InputDeviceParams { right: chan.0, ..params.clone() };
+                            WindowMsg::UpdateParams(ModuleParams::InputDevice(params))
+                        }
+                    })}
+                />
+
+                <label>{"Direct monitor"}</label>
+                {linked(&params.monitor, &indication.monitor_resolved)}
+                <Select<MonitorDevice>
+                    selected={MonitorDevice(self.props.params.monitor.clone())}
+                    options={monitor_devices}
+                    on_change={self.props.module.callback({
+                        let params = self.props.params.clone();
+                        move |monitor: MonitorDevice| {
+                            let params = InputDeviceParams { monitor: monitor.0, ..params.clone() };
+                            WindowMsg::UpdateParams(ModuleParams::InputDevice(params))
+                        }
+                    })}
+                />
+
+                <label for={&gain_id}>{format!("Monitor level {}", self.props.params.monitor_gain)}</label>
+                <input type="range"
+                    id={&gain_id}
+                    min={-40}
+                    max={12}
+                    step={0.5}
+                    value={self.props.params.monitor_gain.0}
+                    onchange={self.props.module.callback({
+                        let params = self.props.params.clone();
+                        move |change| {
+                            let monitor_gain = match change {
+                                ChangeData::Value(value) => value.parse().map(Decibel).unwrap_or(params.monitor_gain),
+                                _ => params.monitor_gain,
+                            };
+
+                            let params = InputDeviceParams { monitor_gain, ..params.clone() };
+                            WindowMsg::UpdateParams(ModuleParams::InputDevice(params))
+                        }
+                    })}
+                />
+
+                <StandInSelect
+                    session={self.props.session.clone()}
+                    selected={self.props.params.stand_in}
+                    on_change={self.props.module.callback({
+                        let params = self.props.params.clone();
+                        move |stand_in| {
+                            let params = InputDeviceParams { stand_in, ..params.clone() };
+                            WindowMsg::UpdateParams(ModuleParams::InputDevice(params))
+                        }
+                    })}
+                />
+            </>
+        }
+    }
+}
+
+fn missing_class(missing: bool) -> &'static str {
+    if missing {
+        "status-light status-light-red-active"
+    } else {
+        "status-light"
+    }
+}
+
+fn warning_class(warning_status: Option<TemporalWarningStatus>) -> &'static str {
+    match warning_status {
+        None => "status-light",
+        Some(TemporalWarningStatus::Active) => "status-light status-light-red-active",
+        Some(TemporalWarningStatus::Recent) => "status-light status-light-red",
+    }
+}
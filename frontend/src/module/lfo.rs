@@ -0,0 +1,99 @@
+use std::fmt::{self, Display};
+
+use yew::{html, ComponentLink, Html};
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, LfoParams, LfoWaveform};
+
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
+use crate::component::pure_module::{Pure, PureModule};
+use crate::control::Rotary;
+use crate::workspace::{Window, WindowMsg};
+
+pub type Lfo = Pure<LfoParams>;
+
+const MAX_RATE_HZ: f64 = 20.0;
+
+impl PureModule for LfoParams {
+    fn view(&self, _: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
+        let waveforms = vec![
+            SelectableWaveform(LfoWaveform::Sine),
+            SelectableWaveform(LfoWaveform::Triangle),
+            SelectableWaveform(LfoWaveform::Square),
+            SelectableWaveform(LfoWaveform::Random),
+        ];
+
+        html! {
+            <div class="lfo">
+                <Select<SelectableWaveform>
+                    selected={SelectableWaveform(self.waveform)}
+                    options={waveforms}
+                    on_change={module.callback({
+                        let params = self.clone();
+                        move |SelectableWaveform(waveform)| {
+                            WindowMsg::UpdateParams(
+                                ModuleParams::Lfo(LfoParams { waveform, ..params.clone() }))
+                        }
+                    })}
+                />
+                <div>{"RATE"}</div>
+                <MidiRangeTarget
+                    ui_mode={midi_mode}
+                    onchange={module.callback(update_rate(self, |rate| rate * MAX_RATE_HZ))}
+                >
+                    <Rotary<f64>
+                        value={self.rate_hz}
+                        min={0.01}
+                        max={MAX_RATE_HZ}
+                        default={1.0}
+                        onchange={module.callback(update_rate(self, |rate| rate))}
+                    />
+                </MidiRangeTarget>
+                <div>{"DEPTH"}</div>
+                <MidiRangeTarget
+                    ui_mode={midi_mode}
+                    onchange={module.callback(update_depth(self))}
+                >
+                    <Rotary<f64>
+                        value={self.depth}
+                        min={0.0}
+                        max={1.0}
+                        default={1.0}
+                        onchange={module.callback(update_depth(self))}
+                    />
+                </MidiRangeTarget>
+            </div>
+        }
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct SelectableWaveform(LfoWaveform);
+
+impl Display for SelectableWaveform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.0 {
+            LfoWaveform::Sine => "Sine",
+            LfoWaveform::Triangle => "Triangle",
+            LfoWaveform::Square => "Square",
+            LfoWaveform::Random => "Random",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn update_rate(params: &LfoParams, f: impl Fn(f64) -> f64) -> impl Fn(f64) -> WindowMsg {
+    let params = params.clone();
+    move |value| {
+        let params = LfoParams { rate_hz: f(value), ..params.clone() };
+        WindowMsg::UpdateParams(ModuleParams::Lfo(params))
+    }
+}
+
+fn update_depth(params: &LfoParams) -> impl Fn(f64) -> WindowMsg {
+    let params = params.clone();
+    move |depth| {
+        let params = LfoParams { depth, ..params.clone() };
+        WindowMsg::UpdateParams(ModuleParams::Lfo(params))
+    }
+}
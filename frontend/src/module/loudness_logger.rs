@@ -0,0 +1,102 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, LoudnessLoggerParams, LoudnessLoggerIndication};
+
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct LoudnessLoggerProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: LoudnessLoggerParams,
+    pub indication: LoudnessLoggerIndication,
+}
+
+pub struct LoudnessLogger {
+    props: LoudnessLoggerProps,
+}
+
+impl Component for LoudnessLogger {
+    type Properties = LoudnessLoggerProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let indication = &self.props.indication;
+        let params = &self.props.params;
+
+        let reading = match indication.short_term_lufs {
+            Some(lufs) => format!("{:.1}", lufs),
+            None => "-inf".to_owned(),
+        };
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={light_class(indication.logging, "status-light-green-active")}>{"LOG"}</div>
+                    <div class={light_class(indication.error, "status-light-red-active")}>{"ERROR"}</div>
+                </div>
+
+                <div class="loudness-logger-reading">
+                    {reading}
+                    <span class="loudness-logger-unit">{"LUFS"}</span>
+                </div>
+
+                <label class="form-field">
+                    <span class="form-field-label">{"Source"}</span>
+                    <input type="text"
+                        onchange={self.callback(text(|source, params| {
+                            LoudnessLoggerParams { source, ..params }
+                        }))}
+                        value={&params.source}
+                    />
+                </label>
+            </>
+        }
+    }
+}
+
+impl LoudnessLogger {
+    fn callback<Ev>(&self, f: impl Fn(Ev, LoudnessLoggerParams) -> LoudnessLoggerParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            WindowMsg::UpdateParams(
+                ModuleParams::LoudnessLogger(f(ev, params.clone())))
+        })
+    }
+}
+
+fn text(f: impl Fn(String, LoudnessLoggerParams) -> LoudnessLoggerParams)
+    -> impl Fn(ChangeData, LoudnessLoggerParams) -> LoudnessLoggerParams
+{
+    move |change, params| {
+        match change {
+            ChangeData::Value(value) => f(value, params),
+            _ => params,
+        }
+    }
+}
+
+fn light_class(active: bool, active_class: &str) -> String {
+    if active {
+        format!("status-light {}", active_class)
+    } else {
+        "status-light".to_owned()
+    }
+}
@@ -6,6 +6,7 @@ use yew_components::Select;
 
 use mixlab_protocol::{ModuleId, ModuleParams, MediaSourceParams, MediaLibrary, MediaId};
 
+use crate::module::stream_input::DisplayDeinterlace;
 use crate::util::notify;
 use crate::session::SessionRef;
 use crate::workspace::{Window, WindowMsg};
@@ -28,6 +29,7 @@ pub struct MediaSource {
 pub enum MediaSourceMsg {
     MediaLibrary(Rc<MediaLibrary>),
     ChangeSource(MediaSourceItem),
+    ChangeDeinterlace(DisplayDeinterlace),
 }
 
 impl Component for MediaSource {
@@ -61,6 +63,16 @@ impl Component for MediaSource {
                             })));
                 false
             }
+            MediaSourceMsg::ChangeDeinterlace(deinterlace) => {
+                self.props.module.send_message(
+                    WindowMsg::UpdateParams(
+                        ModuleParams::MediaSource(
+                            MediaSourceParams {
+                                deinterlace: deinterlace.0,
+                                ..self.props.params.clone()
+                            })));
+                false
+            }
         }
     }
 
@@ -89,19 +101,30 @@ impl Component for MediaSource {
         });
 
         html! {
-            <Select<MediaSourceItem>
-                options={options}
-                selected={selected}
-                on_change={self.link.callback(MediaSourceMsg::ChangeSource)}
-            />
+            <>
+                <Select<MediaSourceItem>
+                    options={options}
+                    selected={selected}
+                    on_change={self.link.callback(MediaSourceMsg::ChangeSource)}
+                />
+
+                <label class="form-field">
+                    <span class="form-field-label">{"Deinterlace"}</span>
+                    <Select<DisplayDeinterlace>
+                        selected={Some(DisplayDeinterlace(self.props.params.deinterlace))}
+                        options={DisplayDeinterlace::options()}
+                        on_change={self.link.callback(MediaSourceMsg::ChangeDeinterlace)}
+                    />
+                </label>
+            </>
         }
     }
 }
 
 #[derive(Clone)]
 pub struct MediaSourceItem {
-    id: MediaId,
-    name: String,
+    pub id: MediaId,
+    pub name: String,
 }
 
 impl PartialEq for MediaSourceItem {
@@ -1,4 +1,7 @@
+use std::fmt::{self, Display};
+
 use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew_components::Select;
 
 use mixlab_protocol::{ModuleId, MixerParams, MixerChannelParams, ModuleParams, Decibel};
 
@@ -17,6 +20,8 @@ pub struct MixerProps {
     pub module: ComponentLink<Window>,
     pub params: MixerParams,
     pub midi_mode: MidiUiMode,
+    // vca group modules available for channels to join:
+    pub groups: Vec<(ModuleId, String)>,
 }
 
 pub enum MixerMsg {
@@ -61,6 +66,7 @@ impl Component for Mixer {
                                 onchange={self.link.callback(move |params|
                                     MixerMsg::ChannelChanged(idx, params))}
                                 midi_mode={self.props.midi_mode}
+                                groups={self.props.groups.clone()}
                             />
                         }
                     })
@@ -79,6 +85,7 @@ pub enum ChannelMsg {
     GainChanged(Decibel),
     CueClick,
     FaderChanged(f64),
+    GroupChanged(Option<ModuleId>),
 }
 
 #[derive(Properties, Clone)]
@@ -86,6 +93,7 @@ pub struct ChannelProps {
     pub params: MixerChannelParams,
     pub onchange: Callback<MixerChannelParams>,
     pub midi_mode: MidiUiMode,
+    pub groups: Vec<(ModuleId, String)>,
 }
 
 impl Component for Channel {
@@ -123,6 +131,12 @@ impl Component for Channel {
                     ..params
                 });
             }
+            ChannelMsg::GroupChanged(group) => {
+                self.props.onchange.emit(MixerChannelParams {
+                    group,
+                    ..params
+                });
+            }
         }
 
         false
@@ -163,7 +177,44 @@ impl Component for Channel {
                         onchange={self.link.callback(ChannelMsg::FaderChanged)}
                     />
                 </MidiRangeTarget>
+                {self.view_group_select()}
             </div>
         }
     }
 }
+
+impl Channel {
+    fn view_group_select(&self) -> Html {
+        if self.props.groups.is_empty() && self.props.params.group.is_none() {
+            return html! {};
+        }
+
+        let options = Some(DisplayGroup(None, "No group".to_owned())).into_iter()
+            .chain(self.props.groups.iter()
+                .map(|(id, name)| DisplayGroup(Some(*id), name.clone())))
+            .collect::<Vec<_>>();
+
+        let selected = options.iter()
+            .find(|option| option.0 == self.props.params.group)
+            .cloned();
+
+        html! {
+            <div class="mixer-channel-group">
+                <Select<DisplayGroup>
+                    selected={selected}
+                    options={options}
+                    on_change={self.link.callback(|group: DisplayGroup| ChannelMsg::GroupChanged(group.0))}
+                />
+            </div>
+        }
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct DisplayGroup(Option<ModuleId>, String);
+
+impl Display for DisplayGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.1)
+    }
+}
@@ -1,14 +1,34 @@
 pub mod amplifier;
+pub mod artnet_output;
+pub mod beat_detector;
+pub mod broadcast_delay;
+pub mod browser_source;
+pub mod bus;
 pub mod envelope;
+pub mod envelope_follower;
 pub mod eq_three;
 pub mod fm_sine;
+pub mod hue_light;
+pub mod ident_inserter;
+pub mod image_source;
+pub mod input_device;
+pub mod lfo;
+pub mod loudness_logger;
 pub mod media_source;
 pub mod mixer;
 pub mod monitor;
+pub mod multiviewer;
+pub mod null_test;
 pub mod oscillator;
 pub mod output_device;
 pub mod plotter;
+pub mod plugin;
+pub mod preview_overlay;
+pub mod recorder;
+pub mod replay_buffer;
 pub mod stream_input;
 pub mod stream_output;
 pub mod trigger;
+pub mod vca_group;
 pub mod video_mixer;
+pub mod zone_output;
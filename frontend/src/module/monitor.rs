@@ -58,7 +58,7 @@ impl Component for Monitor {
     type Message = MonitorMsg;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let socket_url = format!("{}/_monitor/{}", util::websocket_origin(), props.indication.socket_id);
+        let socket_url = format!("{}/_monitor/{}{}", util::websocket_origin(), props.indication.socket_id, util::location_query());
 
         Monitor {
             link,
@@ -0,0 +1,40 @@
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, MultiviewerParams};
+
+use crate::component::midi_target::MidiUiMode;
+use crate::component::pure_module::{Pure, PureModule};
+use crate::workspace::{Window, WindowMsg};
+
+pub type Multiviewer = Pure<MultiviewerParams>;
+
+impl PureModule for MultiviewerParams {
+    fn view(&self, _: ModuleId, module: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        html! {
+            <div class="multiviewer-labels">
+                { for self.labels.iter().enumerate().map(|(idx, label)| html! {
+                    <label class="form-field">
+                        <span class="form-field-label">{format!("Input {}", idx + 1)}</span>
+                        <input type="text"
+                            onchange={module.callback({
+                                let params = self.clone();
+                                move |ev| {
+                                    if let ChangeData::Value(label) = ev {
+                                        let mut params = params.clone();
+                                        params.labels[idx] = label;
+                                        WindowMsg::UpdateParams(
+                                            ModuleParams::Multiviewer(params))
+                                    } else {
+                                        unreachable!()
+                                    }
+                                }
+                            })}
+                            value={label}
+                        />
+                    </label>
+                }) }
+            </div>
+        }
+    }
+}
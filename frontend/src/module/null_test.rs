@@ -0,0 +1,88 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+
+use mixlab_protocol::{ModuleParams, NullTestParams, NullTestIndication, Decibel};
+
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct NullTestProps {
+    pub module: ComponentLink<Window>,
+    pub params: NullTestParams,
+    pub indication: NullTestIndication,
+}
+
+pub struct NullTest {
+    props: NullTestProps,
+}
+
+impl Component for NullTest {
+    type Properties = NullTestProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let indication = &self.props.indication;
+
+        let null_class = if indication.connected && indication.null {
+            "status-light status-light-green-active"
+        } else {
+            "status-light"
+        };
+
+        let failed_class = if indication.failed {
+            "status-light status-light-red-active"
+        } else {
+            "status-light"
+        };
+
+        let level = |level: Option<Decibel>| {
+            match (indication.connected, level) {
+                (false, _) => "-".to_owned(),
+                (true, Some(level)) => level.to_string(),
+                (true, None) => "-inf dB".to_owned(),
+            }
+        };
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={null_class}>{"NULL"}</div>
+                    <div class={failed_class}>{"FAILED"}</div>
+                </div>
+                <table class="null-test-levels">
+                    <tr>
+                        <td>{"Residual RMS"}</td>
+                        <td class="null-test-level">{level(indication.residual_rms)}</td>
+                    </tr>
+                    <tr>
+                        <td>{"Residual peak"}</td>
+                        <td class="null-test-level">{level(indication.residual_peak)}</td>
+                    </tr>
+                </table>
+                <button
+                    onclick={self.props.module.callback({
+                        let params = self.props.params.clone();
+                        move |_| {
+                            let params = NullTestParams { reset: params.reset + 1 };
+                            WindowMsg::UpdateParams(ModuleParams::NullTest(params))
+                        }
+                    })}
+                >
+                    {"Reset"}
+                </button>
+            </>
+        }
+    }
+}
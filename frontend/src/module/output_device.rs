@@ -76,11 +76,20 @@ impl Component for OutputDevice {
             .map(OutputChannel)
             .collect::<Vec<_>>();
 
+        let varispeed_class = if self.props.params.varispeed {
+            "output-device-varispeed output-device-varispeed-active"
+        } else {
+            "output-device-varispeed"
+        };
+
+        let missing = self.props.params.device.is_some() && self.props.indication.resolved.is_none();
+
         html! {
             <>
                 <div class="status-light-bar">
                     <div class={warning_class(self.props.indication.clip)}>{"CLIP"}</div>
                     <div class={warning_class(self.props.indication.lag)}>{"LAG"}</div>
+                    <div class={missing_class(missing)}>{"MISSING"}</div>
                 </div>
                 <button
                     onclick={self.props.module.callback({
@@ -108,6 +117,12 @@ impl Component for OutputDevice {
                 </button>
 
                 <label>{"Output device"}</label>
+                { match (&self.props.params.device, &self.props.indication.resolved) {
+                    (Some(device), Some(resolved)) if device != resolved => html! {
+                        <div class="device-linked">{format!("Linked to {}", resolved)}</div>
+                    },
+                    _ => html! {},
+                } }
                 <Select<String>
                     selected={&self.props.params.device}
                     options={device_names}
@@ -145,11 +160,32 @@ impl Component for OutputDevice {
                         }
                     })}
                 />
+
+                <button
+                    class={varispeed_class}
+                    onclick={self.props.module.callback({
+                        let params = self.props.params.clone();
+                        move |_| {
+                            let params = OutputDeviceParams { varispeed: !params.varispeed, ..params.clone() };
+                            WindowMsg::UpdateParams(ModuleParams::OutputDevice(params))
+                        }
+                    })}
+                >
+                    {"Varispeed"}
+                </button>
             </>
         }
     }
 }
 
+fn missing_class(missing: bool) -> &'static str {
+    if missing {
+        "status-light status-light-red-active"
+    } else {
+        "status-light"
+    }
+}
+
 fn warning_class(warning_status: Option<TemporalWarningStatus>) -> &'static str {
     match warning_status {
         None => "status-light",
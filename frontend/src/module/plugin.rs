@@ -0,0 +1,67 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, PluginParams};
+
+use crate::workspace::{Window, WindowMsg};
+
+// the frontend knows nothing of modules registered by the server's build, so
+// offers their params as the json they're carried in, and shows their
+// indication as it comes
+
+#[derive(Properties, Clone, Debug)]
+pub struct PluginProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: PluginParams,
+    pub indication: String,
+}
+
+pub struct Plugin {
+    props: PluginProps,
+}
+
+impl Component for Plugin {
+    type Properties = PluginProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let params = self.props.params.clone();
+        let params_id = format!("w{}-plugin-params", self.props.id.0);
+
+        html! {
+            <>
+                <label for={&params_id}>{&params.kind}</label>
+                <textarea class="plugin-params"
+                    id={&params_id}
+                    spellcheck="false"
+                    value={&params.json}
+                    onchange={self.props.module.callback(move |change| {
+                        let json = match change {
+                            ChangeData::Value(json) => json,
+                            _ => params.json.clone(),
+                        };
+
+                        WindowMsg::UpdateParams(
+                            ModuleParams::Plugin(PluginParams { json, ..params.clone() }))
+                    })}
+                />
+
+                <pre class="plugin-indication">{&self.props.indication}</pre>
+            </>
+        }
+    }
+}
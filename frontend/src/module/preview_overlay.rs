@@ -0,0 +1,74 @@
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, PreviewOverlayParams};
+
+use crate::component::midi_target::MidiUiMode;
+use crate::component::pure_module::{Pure, PureModule};
+use crate::workspace::{Window, WindowMsg};
+
+pub type PreviewOverlay = Pure<PreviewOverlayParams>;
+
+impl PureModule for PreviewOverlayParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        let zebra_level_id = format!("w{}-zebra-level", id.0);
+
+        html! {
+            <div class="preview-overlay">
+                <div class="preview-overlay-toggles">
+                    {toggle(&module, "Safe area", self.safe_area, {
+                        let params = self.clone();
+                        move || PreviewOverlayParams { safe_area: !params.safe_area, ..params.clone() }
+                    })}
+                    {toggle(&module, "Zebra", self.zebra, {
+                        let params = self.clone();
+                        move || PreviewOverlayParams { zebra: !params.zebra, ..params.clone() }
+                    })}
+                    {toggle(&module, "Peaking", self.focus_peaking, {
+                        let params = self.clone();
+                        move || PreviewOverlayParams { focus_peaking: !params.focus_peaking, ..params.clone() }
+                    })}
+                </div>
+                <label for={&zebra_level_id}>{format!("Zebra level {:.0}%", self.zebra_level * 100.0)}</label>
+                <input type="range"
+                    id={&zebra_level_id}
+                    min={0.5}
+                    max={1.0}
+                    step={0.01}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            let zebra_level = match ev {
+                                ChangeData::Value(value) => value.parse().unwrap_or(params.zebra_level),
+                                _ => params.zebra_level,
+                            };
+
+                            WindowMsg::UpdateParams(
+                                ModuleParams::PreviewOverlay(PreviewOverlayParams { zebra_level, ..params.clone() }))
+                        }
+                    })}
+                    value={self.zebra_level}
+                />
+            </div>
+        }
+    }
+}
+
+fn toggle(module: &ComponentLink<Window>, label: &str, active: bool, toggled: impl Fn() -> PreviewOverlayParams + 'static) -> Html {
+    let class = if active {
+        "preview-overlay-toggle preview-overlay-toggle-active"
+    } else {
+        "preview-overlay-toggle"
+    };
+
+    html! {
+        <button
+            class={class}
+            onclick={module.callback(move |_| {
+                WindowMsg::UpdateParams(ModuleParams::PreviewOverlay(toggled()))
+            })}
+        >
+            {label}
+        </button>
+    }
+}
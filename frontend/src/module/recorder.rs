@@ -0,0 +1,94 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+
+use mixlab_protocol::{ModuleId, ModuleParams, RecorderParams, RecorderIndication, TemporalWarningStatus};
+
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct RecorderProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: RecorderParams,
+    pub indication: RecorderIndication,
+}
+
+pub struct Recorder {
+    props: RecorderProps,
+}
+
+impl Component for Recorder {
+    type Properties = RecorderProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let indication = &self.props.indication;
+
+        let rec_class = match (&indication.take, indication.error) {
+            (Some(_), false) => "status-light status-light-red-active",
+            _ => "status-light",
+        };
+
+        let error_class = match indication.error {
+            false => "status-light",
+            true => "status-light status-light-red-active",
+        };
+
+        let params = self.props.params.clone();
+        let recording = params.recording;
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={rec_class}>{"REC"}</div>
+                    <div class={error_class}>{"ERROR"}</div>
+                    <div class={lag_class(indication.lag)}>{"LAG"}</div>
+                </div>
+
+                <div class="recorder-file">
+                    {indication.take.as_deref().unwrap_or("")}
+                </div>
+
+                <div class="recorder-buttons">
+                    <button
+                        onclick={self.props.module.callback({
+                            let params = params.clone();
+                            move |_| WindowMsg::UpdateParams(
+                                ModuleParams::Recorder(RecorderParams { recording: !recording, ..params.clone() }))
+                        })}
+                    >
+                        {if recording { "Stop" } else { "Record" }}
+                    </button>
+                    <button
+                        disabled={!recording}
+                        onclick={self.props.module.callback(move |_|
+                            WindowMsg::UpdateParams(
+                                ModuleParams::Recorder(RecorderParams { marker: params.marker + 1, ..params.clone() })))}
+                    >
+                        {"Marker"}
+                    </button>
+                </div>
+            </>
+        }
+    }
+}
+
+fn lag_class(warning_status: Option<TemporalWarningStatus>) -> &'static str {
+    match warning_status {
+        None => "status-light",
+        Some(TemporalWarningStatus::Active) => "status-light status-light-red-active",
+        Some(TemporalWarningStatus::Recent) => "status-light status-light-red",
+    }
+}
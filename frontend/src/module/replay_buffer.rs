@@ -0,0 +1,137 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ReplayBufferParams, ReplayBufferIndication, REPLAY_BUFFER_MAX_SECONDS};
+
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct ReplayBufferProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: ReplayBufferParams,
+    pub indication: ReplayBufferIndication,
+}
+
+pub struct ReplayBuffer {
+    props: ReplayBufferProps,
+}
+
+impl Component for ReplayBuffer {
+    type Properties = ReplayBufferProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let indication = &self.props.indication;
+        let params = &self.props.params;
+
+        let seconds_id = format!("w{}-seconds", self.props.id.0);
+        let speed_id = format!("w{}-speed", self.props.id.0);
+
+        let save_class = if params.save {
+            "replay-buffer-save replay-buffer-save-active"
+        } else {
+            "replay-buffer-save"
+        };
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={light_class(indication.playing, "status-light-green-active")}>{"PLAY"}</div>
+                    <div class={light_class(indication.saving, "status-light-green")}>{"SAVING"}</div>
+                    <div class={light_class(indication.save_error, "status-light-red-active")}>{"ERROR"}</div>
+                </div>
+
+                <div class="replay-buffer-buttons">
+                    <button
+                        onclick={self.callback(|_, params| {
+                            ReplayBufferParams { replay: params.replay + 1, ..params }
+                        })}
+                    >
+                        {"Replay"}
+                    </button>
+                    <button
+                        class={save_class}
+                        onclick={self.callback(|_, params| {
+                            ReplayBufferParams { save: !params.save, ..params }
+                        })}
+                    >
+                        {"Save to library"}
+                    </button>
+                </div>
+
+                <label for={&seconds_id}>{format!("Length {:.0}s", params.seconds)}</label>
+                <input type="range"
+                    id={&seconds_id}
+                    min={1}
+                    max={REPLAY_BUFFER_MAX_SECONDS}
+                    step={1}
+                    onchange={self.callback(float(|seconds, params| {
+                        ReplayBufferParams { seconds, ..params }
+                    }))}
+                    value={params.seconds}
+                />
+
+                <label for={&speed_id}>{format!("Speed {:.0}%", params.speed * 100.0)}</label>
+                <input type="range"
+                    id={&speed_id}
+                    min={0.1}
+                    max={1}
+                    step={0.05}
+                    onchange={self.callback(float(|speed, params| {
+                        ReplayBufferParams { speed, ..params }
+                    }))}
+                    value={params.speed}
+                />
+            </>
+        }
+    }
+}
+
+impl ReplayBuffer {
+    fn callback<Ev>(&self, f: impl Fn(Ev, ReplayBufferParams) -> ReplayBufferParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            WindowMsg::UpdateParams(
+                ModuleParams::ReplayBuffer(f(ev, params.clone())))
+        })
+    }
+}
+
+fn float(f: impl Fn(f64, ReplayBufferParams) -> ReplayBufferParams)
+    -> impl Fn(ChangeData, ReplayBufferParams) -> ReplayBufferParams
+{
+    move |change, params| {
+        match change {
+            ChangeData::Value(value) => match value.parse() {
+                Ok(value) => f(value, params),
+                Err(_) => params,
+            },
+            _ => params,
+        }
+    }
+}
+
+fn light_class(active: bool, active_class: &str) -> String {
+    if active {
+        format!("status-light {}", active_class)
+    } else {
+        "status-light".to_owned()
+    }
+}
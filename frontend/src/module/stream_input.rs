@@ -5,8 +5,10 @@ use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callba
 use yew_components::Select;
 use yew::events::ChangeData;
 
-use mixlab_protocol::{ModuleId, ModuleParams, StreamInputParams, StreamProtocol};
+use mixlab_protocol::{ModuleId, ModuleParams, StreamInputParams, StreamProtocol, ResampleQuality, Deinterlace};
 
+use crate::component::stand_in::StandInSelect;
+use crate::session::SessionRef;
 use crate::workspace::{Window, WindowMsg};
 
 #[derive(Properties, Clone, Debug)]
@@ -14,6 +16,7 @@ pub struct StreamInputProps {
     pub id: ModuleId,
     pub module: ComponentLink<Window>,
     pub params: StreamInputParams,
+    pub session: SessionRef,
 }
 
 pub struct StreamInput {
@@ -66,6 +69,40 @@ impl Component for StreamInput {
                         value={self.props.params.mountpoint.as_ref().map(String::as_str).unwrap_or("")}
                     />
                 </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{"Resampling"}</span>
+                    <Select<DisplayResampleQuality>
+                        selected={Some(DisplayResampleQuality(self.props.params.resample_quality))}
+                        options={vec![
+                            DisplayResampleQuality(ResampleQuality::Linear),
+                            DisplayResampleQuality(ResampleQuality::Polyphase),
+                            DisplayResampleQuality(ResampleQuality::Sinc),
+                        ]}
+                        on_change={self.callback(move |quality: DisplayResampleQuality, params| {
+                            StreamInputParams { resample_quality: quality.0, ..params }
+                        })}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{"Deinterlace"}</span>
+                    <Select<DisplayDeinterlace>
+                        selected={Some(DisplayDeinterlace(self.props.params.deinterlace))}
+                        options={DisplayDeinterlace::options()}
+                        on_change={self.callback(move |deinterlace: DisplayDeinterlace, params| {
+                            StreamInputParams { deinterlace: deinterlace.0, ..params }
+                        })}
+                    />
+                </label>
+
+                <StandInSelect
+                    session={self.props.session.clone()}
+                    selected={self.props.params.stand_in}
+                    on_change={self.callback(move |stand_in, params| {
+                        StreamInputParams { stand_in, ..params }
+                    })}
+                />
             </>
         }
     }
@@ -112,3 +149,39 @@ impl Display for DisplayProtocol {
         }
     }
 }
+
+#[derive(From, Into, PartialEq, Clone)]
+pub struct DisplayResampleQuality(ResampleQuality);
+
+impl Display for DisplayResampleQuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            ResampleQuality::Linear => write!(f, "Linear"),
+            ResampleQuality::Polyphase => write!(f, "Polyphase"),
+            ResampleQuality::Sinc => write!(f, "Sinc"),
+        }
+    }
+}
+
+#[derive(From, Into, PartialEq, Clone)]
+pub struct DisplayDeinterlace(pub Deinterlace);
+
+impl DisplayDeinterlace {
+    pub fn options() -> Vec<Self> {
+        vec![
+            DisplayDeinterlace(Deinterlace::Weave),
+            DisplayDeinterlace(Deinterlace::Bob),
+            DisplayDeinterlace(Deinterlace::Adaptive),
+        ]
+    }
+}
+
+impl Display for DisplayDeinterlace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Deinterlace::Weave => write!(f, "Off (weave)"),
+            Deinterlace::Bob => write!(f, "Bob"),
+            Deinterlace::Adaptive => write!(f, "Adaptive"),
+        }
+    }
+}
@@ -37,16 +37,38 @@ impl Component for StreamOutput {
     fn view(&self) -> Html {
         let is_conn_active = match self.props.indication.live {
             StreamOutputLiveStatus::Offline => false,
-            StreamOutputLiveStatus::Connecting | StreamOutputLiveStatus::Live => true,
+            StreamOutputLiveStatus::Connecting |
+            StreamOutputLiveStatus::Reconnecting { .. } |
+            StreamOutputLiveStatus::Live => true,
         };
 
+        let mut status = match (self.props.indication.live, self.props.indication.kbps) {
+            (StreamOutputLiveStatus::Live, Some(kbps)) => format!("{} kbps", kbps),
+            (StreamOutputLiveStatus::Reconnecting { attempt }, _) => format!("Reconnecting (attempt {})", attempt),
+            _ => String::new(),
+        };
+
+        if is_conn_active && self.props.indication.failovers > 0 {
+            if !status.is_empty() {
+                status.push_str(", ");
+            }
+
+            status.push_str(&match self.props.indication.failovers {
+                1 => "failed over once".to_owned(),
+                n => format!("failed over {} times", n),
+            });
+        }
+
         html! {
             <>
                 <div class="status-light-bar">
                     <div class={live_class(self.props.indication.live)}>{"LIVE"}</div>
                     <div class={warning_class(self.props.indication.error)}>{"ERROR"}</div>
+                    <div class={backup_class(self.props.indication.backup)}>{"BACKUP"}</div>
                 </div>
 
+                <div class="stream-output-status">{status}</div>
+
                 { if is_conn_active {
                     html! {
                         <button
@@ -88,6 +110,26 @@ impl Component for StreamOutput {
                         value={&self.props.params.rtmp_stream_key}
                     />
                 </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{"Backup RTMP URL"}</span>
+                    <input type="text"
+                        onchange={self.callback(text(move |backup_rtmp_url, params| {
+                            StreamOutputParams { backup_rtmp_url, ..params }
+                        }))}
+                        value={&self.props.params.backup_rtmp_url}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{"Backup Stream Key"}</span>
+                    <input type="text"
+                        onchange={self.callback(text(move |backup_rtmp_stream_key, params| {
+                            StreamOutputParams { backup_rtmp_stream_key, ..params }
+                        }))}
+                        value={&self.props.params.backup_rtmp_stream_key}
+                    />
+                </label>
             </>
         }
     }
@@ -127,11 +169,19 @@ fn text<T>(f: impl Fn(String, StreamOutputParams) -> T)
 fn live_class(live_status: StreamOutputLiveStatus) -> &'static str {
     match live_status {
         StreamOutputLiveStatus::Offline => "status-light",
-        StreamOutputLiveStatus::Connecting => "status-light status-light-green",
+        StreamOutputLiveStatus::Connecting |
+        StreamOutputLiveStatus::Reconnecting { .. } => "status-light status-light-green",
         StreamOutputLiveStatus::Live => "status-light status-light-green-active",
     }
 }
 
+fn backup_class(is_backup: bool) -> &'static str {
+    match is_backup {
+        false => "status-light",
+        true => "status-light status-light-yellow-active",
+    }
+}
+
 fn warning_class(is_warning: bool) -> &'static str {
     match is_warning {
         false => "status-light",
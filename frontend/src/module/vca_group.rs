@@ -0,0 +1,56 @@
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, VcaGroupParams};
+
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
+use crate::component::pure_module::{Pure, PureModule};
+use crate::control::Fader;
+use crate::workspace::{Window, WindowMsg};
+
+pub type VcaGroup = Pure<VcaGroupParams>;
+
+impl PureModule for VcaGroupParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
+        let name_id = format!("w{}-vca-name", id.0);
+
+        html! {
+            <div class="vca-group">
+                <label for={&name_id}>{"Name"}</label>
+                <input type="text"
+                    id={&name_id}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            if let ChangeData::Value(name) = ev {
+                                let params = VcaGroupParams { name, ..params.clone() };
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::VcaGroup(params))
+                            } else {
+                                unreachable!()
+                            }
+                        }
+                    })}
+                    value={&self.name}
+                />
+                <MidiRangeTarget
+                    ui_mode={midi_mode}
+                    onchange={module.callback(update_fader(self))}
+                >
+                    <Fader
+                        value={self.fader}
+                        onchange={module.callback(update_fader(self))}
+                    />
+                </MidiRangeTarget>
+            </div>
+        }
+    }
+}
+
+fn update_fader(params: &VcaGroupParams) -> impl Fn(f64) -> WindowMsg {
+    let params = params.clone();
+    move |fader| {
+        let params = VcaGroupParams { fader, ..params.clone() };
+        WindowMsg::UpdateParams(ModuleParams::VcaGroup(params))
+    }
+}
@@ -1,30 +1,85 @@
-use yew::{html, ComponentLink, Html, Callback, MouseEvent};
+use std::rc::Rc;
 
-use mixlab_protocol::{ModuleId, ModuleParams, VideoMixerParams, VIDEO_MIXER_CHANNELS};
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback, MouseEvent};
+use yew::events::ChangeData;
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, VideoMixerParams, StingerParams, MediaLibrary, VIDEO_MIXER_CHANNELS};
 
-use crate::component::pure_module::{Pure, PureModule};
 use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
 use crate::control::Fader;
+use crate::module::media_source::MediaSourceItem;
+use crate::session::SessionRef;
+use crate::util::{notify, prevent_default};
 use crate::workspace::{Window, WindowMsg};
-use crate::util::prevent_default;
 
-pub type VideoMixer = Pure<VideoMixerParams>;
+#[derive(Properties, Clone)]
+pub struct VideoMixerProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: VideoMixerParams,
+    pub midi_mode: MidiUiMode,
+    pub session: SessionRef,
+}
+
+pub struct VideoMixer {
+    props: VideoMixerProps,
+    link: ComponentLink<Self>,
+    library: Option<Rc<MediaLibrary>>,
+    _notify: notify::Handle,
+}
+
+pub enum VideoMixerMsg {
+    MediaLibrary(Rc<MediaLibrary>),
+}
+
+impl Component for VideoMixer {
+    type Properties = VideoMixerProps;
+    type Message = VideoMixerMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let notify = props.session.listen_media(link.callback(VideoMixerMsg::MediaLibrary));
+
+        Self {
+            props,
+            link,
+            library: None,
+            _notify: notify,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            VideoMixerMsg::MediaLibrary(library) => {
+                self.library = Some(library);
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let params = &self.props.params;
+        let module = &self.props.module;
+        let midi_mode = self.props.midi_mode;
 
-impl PureModule for VideoMixerParams {
-    fn view(&self, _: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
         html! {
             <>
                 <div class="video-mixer">
                     <div class="video-mixer-channels">
                         <div class="video-mixer-channel-row">
-                            {view_channel_row(Selector::A, self.a, module.callback(
-                                update_params(self, move |params, selection|
+                            {view_channel_row(Selector::A, params.a, module.callback(
+                                update_params(params, move |params, selection|
                                     VideoMixerParams { a: selection, ..params })))}
                         </div>
 
                         <div class="video-mixer-channel-row">
-                            {view_channel_row(Selector::B, self.b, module.callback(
-                                update_params(self, move |params, selection|
+                            {view_channel_row(Selector::B, params.b, module.callback(
+                                update_params(params, move |params, selection|
                                     VideoMixerParams { b: selection, ..params })))}
                         </div>
                     </div>
@@ -32,23 +87,96 @@ impl PureModule for VideoMixerParams {
                         <MidiRangeTarget
                             ui_mode={midi_mode}
                             onchange={module.callback(
-                                update_params(self, move |params, fader|
+                                update_params(params, move |params, fader|
                                     VideoMixerParams { fader, ..params }))}
                         >
                             <Fader
-                                value={self.fader}
+                                value={params.fader}
                                 onchange={module.callback(
-                                    update_params(self, move |params, fader|
+                                    update_params(params, move |params, fader|
                                         VideoMixerParams { fader, ..params }))}
                             />
                         </MidiRangeTarget>
                     </div>
                 </div>
+                {self.view_stinger()}
             </>
         }
     }
 }
 
+impl VideoMixer {
+    fn view_stinger(&self) -> Html {
+        let params = &self.props.params;
+        let module = &self.props.module;
+
+        let options = self.library.iter()
+            .flat_map(|library| library.items.iter())
+            .map(|item| MediaSourceItem { id: item.id, name: item.name.clone() })
+            .collect::<Vec<_>>();
+
+        let selected = params.stinger.media_id.map(|id| {
+            // name can be empty, we never display this item
+            MediaSourceItem { id, name: String::new() }
+        });
+
+        html! {
+            <div class="video-mixer-stinger">
+                <label>{"Stinger"}</label>
+                <Select<MediaSourceItem>
+                    options={options}
+                    selected={selected}
+                    on_change={module.callback(
+                        update_params(params, |params, item: MediaSourceItem|
+                            VideoMixerParams {
+                                stinger: StingerParams { media_id: Some(item.id), ..params.stinger.clone() },
+                                ..params
+                            }))}
+                />
+                <label>{"Cut"}</label>
+                <input type="number"
+                    class="video-mixer-stinger-cut"
+                    min={0}
+                    step={10}
+                    value={params.stinger.cut_ms}
+                    onchange={module.callback(
+                        update_params(params, |params, ev: ChangeData| {
+                            let cut_ms = match ev {
+                                ChangeData::Value(value) => value.parse().unwrap_or(params.stinger.cut_ms),
+                                _ => params.stinger.cut_ms,
+                            };
+
+                            VideoMixerParams {
+                                stinger: StingerParams { cut_ms, ..params.stinger.clone() },
+                                ..params
+                            }
+                        }))}
+                />
+                <label>{"ms"}</label>
+                <button
+                    class="video-mixer-stinger-take"
+                    disabled={params.stinger.media_id.is_none()}
+                    onclick={module.callback(
+                        update_params(params, |params, _: MouseEvent| {
+                            // take to whichever side is not currently on air,
+                            // the fader moves when the stinger covers the
+                            // picture
+                            let fader = if params.fader >= 0.5 { 0.0 } else { 1.0 };
+
+                            VideoMixerParams {
+                                fader,
+                                stinger: StingerParams { take: params.stinger.take + 1, ..params.stinger.clone() },
+                                ..params
+                            }
+                        }))}
+                >
+                    {"Take"}
+                </button>
+            </div>
+        }
+    }
+}
+
 enum Selector {
     A,
     B,
@@ -0,0 +1,85 @@
+use std::fmt::{self, Display};
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ZoneId, ZoneOutputParams};
+
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct ZoneOutputProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: ZoneOutputParams,
+    pub zones: Vec<(ZoneId, String)>,
+}
+
+pub struct ZoneOutput {
+    link: ComponentLink<Self>,
+    props: ZoneOutputProps,
+}
+
+pub enum ZoneOutputMsg {
+    ZoneChanged(Option<ZoneId>),
+}
+
+impl Component for ZoneOutput {
+    type Properties = ZoneOutputProps;
+    type Message = ZoneOutputMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        ZoneOutput { link, props }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            ZoneOutputMsg::ZoneChanged(zone) => {
+                self.props.module.send_message(
+                    WindowMsg::UpdateParams(
+                        ModuleParams::ZoneOutput(ZoneOutputParams { zone })));
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let options = Some(DisplayZone(None, "No zone".to_owned())).into_iter()
+            .chain(self.props.zones.iter()
+                .map(|(id, name)| DisplayZone(Some(*id), name.clone())))
+            .collect::<Vec<_>>();
+
+        let selected = options.iter()
+            .find(|option| option.0 == self.props.params.zone)
+            .cloned();
+
+        html! {
+            <div class="zone-output">
+                <Select<DisplayZone>
+                    selected={selected}
+                    options={options}
+                    on_change={self.link.callback(|zone: DisplayZone| ZoneOutputMsg::ZoneChanged(zone.0))}
+                />
+                { if self.props.zones.is_empty() {
+                    html! { <div class="zone-output-hint">{"Add zones from the sidebar"}</div> }
+                } else {
+                    html! {}
+                } }
+            </div>
+        }
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct DisplayZone(Option<ZoneId>, String);
+
+impl Display for DisplayZone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.1)
+    }
+}
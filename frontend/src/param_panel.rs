@@ -0,0 +1,230 @@
+use std::rc::Rc;
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, InputData};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ParamSearch, ParamMatch, ParamRef, ParamSpec, WorkspaceOp};
+
+use crate::session::SessionRef;
+use crate::util::notify;
+
+/// Compact control panel over a module's params, listing its favorites or
+/// searching through all of them on the server
+pub struct ParamPanel {
+    link: ComponentLink<Self>,
+    props: ParamPanelProps,
+    query: String,
+    results: Vec<ParamMatch>,
+    _search_notify: notify::Handle,
+}
+
+#[derive(Properties, Clone, Debug)]
+pub struct ParamPanelProps {
+    pub id: ModuleId,
+    pub params: ModuleParams,
+    pub session: SessionRef,
+}
+
+pub enum ParamPanelMsg {
+    Query(String),
+    Results(Rc<(ParamSearch, Vec<ParamMatch>)>),
+    SetParam(String, f64),
+    Favorite(String, bool),
+}
+
+impl Component for ParamPanel {
+    type Properties = ParamPanelProps;
+    type Message = ParamPanelMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let search_notify = props.session.listen_param_search(link.callback(ParamPanelMsg::Results));
+
+        let panel = ParamPanel {
+            link,
+            props,
+            query: String::new(),
+            results: Vec::new(),
+            _search_notify: search_notify,
+        };
+
+        panel.refresh();
+        panel
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            ParamPanelMsg::Query(query) => {
+                self.query = query;
+                self.refresh();
+                false
+            }
+            ParamPanelMsg::Results(results) => {
+                let (search, results) = &*results;
+
+                // every open panel hears every answer, only take our own:
+                if *search != self.search() {
+                    return false;
+                }
+
+                self.results = results.clone();
+                true
+            }
+            ParamPanelMsg::SetParam(path, value) => {
+                self.props.session.update_workspace(
+                    WorkspaceOp::SetParam(ParamRef { module: self.props.id, path }, value));
+                false
+            }
+            ParamPanelMsg::Favorite(path, favorite) => {
+                self.props.session.update_workspace(
+                    WorkspaceOp::FavoriteParam(ParamRef { module: self.props.id, path }, favorite));
+
+                // the server handles messages in order, so the search
+                // sees the new favorite:
+                self.refresh();
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        // params aren't comparable, so compare them encoded. values shown
+        // came from the server and are fetched again when they change:
+        let changed = bincode::serialize(&props.params).ok() != bincode::serialize(&self.props.params).ok();
+
+        self.props = props;
+
+        if changed {
+            self.refresh();
+        }
+
+        false
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <div class="module-window-param-panel">
+                <input type="search"
+                    placeholder="Search params"
+                    value={&self.query}
+                    oninput={self.link.callback(|ev: InputData| ParamPanelMsg::Query(ev.value))}
+                />
+                { if self.results.is_empty() {
+                    let note = if self.query.trim().is_empty() {
+                        "No favorite params, search to add some"
+                    } else {
+                        "No matching params"
+                    };
+
+                    html! { <div class="param-panel-empty">{note}</div> }
+                } else {
+                    html! {
+                        <table class="param-panel-table">
+                            { for self.results.iter().map(|result| self.view_result(result)) }
+                        </table>
+                    }
+                } }
+            </div>
+        }
+    }
+}
+
+impl ParamPanel {
+    fn search(&self) -> ParamSearch {
+        ParamSearch {
+            module: Some(self.props.id),
+            query: self.query.clone(),
+        }
+    }
+
+    fn refresh(&self) {
+        self.props.session.search_params(self.search());
+    }
+
+    fn view_result(&self, result: &ParamMatch) -> Html {
+        let favorite = result.favorite;
+
+        let spec = self.props.session.workspace().and_then(|workspace| {
+            workspace.borrow().param_spec(self.props.id, &result.param.path).cloned()
+        });
+
+        let favorite_class = if favorite {
+            "param-favorite param-favorite-active"
+        } else {
+            "param-favorite"
+        };
+
+        html! {
+            <tr>
+                <td>
+                    <button class={favorite_class}
+                        onclick={self.link.callback({
+                            let path = result.param.path.clone();
+                            move |_| ParamPanelMsg::Favorite(path.clone(), !favorite)
+                        })}
+                    >
+                        {"★"}
+                    </button>
+                </td>
+                { match &spec {
+                    Some(spec) => self.view_spec_control(result, spec),
+                    None => self.view_raw_control(result),
+                } }
+            </tr>
+        }
+    }
+
+    // params the module describes get a slider along their range and curve
+    fn view_spec_control(&self, result: &ParamMatch, spec: &ParamSpec) -> Html {
+        html! {
+            <>
+                <td class="param-label" title={&result.param.path}>{&spec.label}</td>
+                <td>
+                    <input type="range"
+                        min="0"
+                        max="1"
+                        step="0.001"
+                        value={spec.position(result.value).to_string()}
+                        oninput={self.link.callback({
+                            let spec = spec.clone();
+                            let value = result.value;
+                            move |ev: InputData| {
+                                let value = ev.value.parse()
+                                    .map(|position| spec.value_at(position))
+                                    .unwrap_or(value);
+
+                                ParamPanelMsg::SetParam(spec.path.clone(), value)
+                            }
+                        })}
+                    />
+                </td>
+                <td class="param-value">{spec.format(result.value)}</td>
+            </>
+        }
+    }
+
+    fn view_raw_control(&self, result: &ParamMatch) -> Html {
+        html! {
+            <>
+                <td class="param-path" colspan="2">{&result.param.path}</td>
+                <td>
+                    <input type="number"
+                        step="any"
+                        value={result.value.to_string()}
+                        onchange={self.link.callback({
+                            let path = result.param.path.clone();
+                            let value = result.value;
+                            move |ev| {
+                                let value = match ev {
+                                    ChangeData::Value(new_value) => new_value.parse().unwrap_or(value),
+                                    _ => value,
+                                };
+
+                                ParamPanelMsg::SetParam(path.clone(), value)
+                            }
+                        })}
+                    />
+                </td>
+            </>
+        }
+    }
+}
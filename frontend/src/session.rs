@@ -6,7 +6,7 @@ use yew::services::websocket::{WebSocketService, WebSocketStatus, WebSocketTask}
 use yew::format::Binary;
 use yew::Callback;
 
-use mixlab_protocol::{ServerMessage, ServerUpdate, ClientMessage, ClientSequence, ModuleId, ModuleParams, WindowGeometry, InputId, OutputId, Indication, Terminal, WorkspaceOp, WorkspaceMessage};
+use mixlab_protocol::{ServerMessage, ServerUpdate, ClientMessage, ClientSequence, ModuleId, ModuleParams, WindowGeometry, InputId, OutputId, Indication, Terminal, WorkspaceOp, WorkspaceMessage, GainStagingReport, ParamLinkId, ParamLink, MorphState, SnapshotId, SnapshotInfo, ModulationId, Modulation, ClockSource, Viewport, ViewId, View, Binding, ParamSearch, ParamMatch, ParamSpec, ProjectInfo, BackupStatus, MediaSyncStatus, Access, GuestScope, GuestToken, GuestTokenRequest, Diagnostic, PluginInfo, EngineIntrospection, ZoneId, Zone};
 
 use crate::util;
 use crate::util::notify::{self, Notify};
@@ -17,6 +17,7 @@ pub struct Session {
     websocket: RefCell<Option<WebSocketTask>>,
     state: RefCell<Option<WorkspaceStateRef>>,
     seq: RefCell<Seq>,
+    access: RefCell<Option<Rc<Access>>>,
     notify: Notifiers,
 }
 
@@ -31,6 +32,14 @@ struct Notifiers {
     workspace: Notify<()>,
     performance: Notify<Rc<mixlab_protocol::PerformanceInfo>>,
     media: Notify<Rc<mixlab_protocol::MediaLibrary>>,
+    snapshots: Notify<Rc<Vec<SnapshotInfo>>>,
+    param_search: Notify<Rc<(ParamSearch, Vec<ParamMatch>)>>,
+    project: Notify<Rc<ProjectInfo>>,
+    backup: Notify<Rc<BackupStatus>>,
+    media_sync: Notify<Rc<MediaSyncStatus>>,
+    access: Notify<Rc<Access>>,
+    guest_tokens: Notify<Rc<Vec<GuestToken>>>,
+    introspection: Notify<Rc<EngineIntrospection>>,
 }
 
 pub type SessionRef = Rc<Session>;
@@ -44,14 +53,24 @@ impl Session {
                 client: Sequence::new(),
                 server: None,
             }),
+            access: RefCell::new(None),
             notify: Notifiers {
                 workspace: Notify::new(),
                 performance: Notify::new(),
                 media: Notify::new(),
+                snapshots: Notify::new(),
+                param_search: Notify::new(),
+                project: Notify::new(),
+                backup: Notify::new(),
+                media_sync: Notify::new(),
+                access: Notify::new(),
+                guest_tokens: Notify::new(),
+                introspection: Notify::new(),
             },
         });
 
-        let websocket_url = util::websocket_origin() + "/session";
+        // carries the page's guest link or owner key through to the server:
+        let websocket_url = util::websocket_origin() + "/session" + &util::location_query();
 
         let websocket = WebSocketService::connect_binary(&websocket_url,
             Callback::from({
@@ -110,7 +129,20 @@ impl Session {
     fn on_server_message(&self, msg: ServerMessage) {
         match msg {
             ServerMessage::WorkspaceState(state) => {
-                *self.state.borrow_mut() = Some(Rc::new(RefCell::new(state.into())));
+                let mut state = WorkspaceState::from(state);
+
+                // open whichever view the url asks for, if it exists:
+                state.current_view = util::location_view().and_then(|name| {
+                    state.views.iter()
+                        .find(|(_, view)| view.name == name)
+                        .map(|(id, _)| *id)
+                });
+
+                if let Some(view) = self.guest_view() {
+                    state.current_view = Some(view);
+                }
+
+                *self.state.borrow_mut() = Some(Rc::new(RefCell::new(state)));
                 self.notify.workspace.broadcast(());
             }
             ServerMessage::Sync(seq) => {
@@ -159,6 +191,10 @@ impl Session {
                             state.indications.remove(&id);
                             state.inputs.remove(&id);
                             state.outputs.remove(&id);
+                            state.morphs.remove(&id);
+                            state.favorite_params.remove(&id);
+                            state.param_specs.remove(&id);
+                            state.diagnostics.remove(&id);
                         }
                         ServerUpdate::CreateConnection(input, output) => {
                             state.connections.insert(input, output);
@@ -166,6 +202,94 @@ impl Session {
                         ServerUpdate::DeleteConnection(input) => {
                             state.connections.remove(&input);
                         }
+                        ServerUpdate::GainStagingReport(report) => {
+                            state.gain_staging = Some(report);
+                        }
+                        ServerUpdate::CreateParamLink(id, link) => {
+                            state.param_links.insert(id, link);
+                        }
+                        ServerUpdate::DeleteParamLink(id) => {
+                            state.param_links.remove(&id);
+                        }
+                        ServerUpdate::UpdateMorph(id, Some(morph)) => {
+                            state.morphs.insert(id, morph);
+                        }
+                        ServerUpdate::UpdateMorph(id, None) => {
+                            state.morphs.remove(&id);
+                        }
+                        ServerUpdate::CreateModulation(id, modulation) => {
+                            state.modulations.insert(id, modulation);
+                        }
+                        ServerUpdate::DeleteModulation(id) => {
+                            state.modulations.remove(&id);
+                        }
+                        ServerUpdate::SetClockSource(source) => {
+                            state.clock_source = source;
+                        }
+                        ServerUpdate::SetRehearsal(rehearsal) => {
+                            state.rehearsal = rehearsal;
+                        }
+                        ServerUpdate::UpdateViewport(viewport) => {
+                            state.viewport = viewport;
+                        }
+                        ServerUpdate::UpdateView(id, Some(view)) => {
+                            state.views.insert(id, view);
+                        }
+                        ServerUpdate::UpdateView(id, None) => {
+                            state.views.remove(&id);
+
+                            if state.current_view == Some(id) {
+                                state.current_view = None;
+                            }
+                        }
+                        ServerUpdate::UpdateViewGeometry(id, module, geometry) => {
+                            if let Some(view) = state.views.get_mut(&id) {
+                                view.set_geometry(module, geometry);
+                            }
+                        }
+                        ServerUpdate::UpdateBinding(name, Some(binding)) => {
+                            state.bindings.insert(name, binding);
+                        }
+                        ServerUpdate::UpdateBinding(name, None) => {
+                            state.bindings.remove(&name);
+                        }
+                        ServerUpdate::UpdateDeviceLink(device, Some(local)) => {
+                            state.device_links.insert(device, local);
+                        }
+                        ServerUpdate::UpdateDeviceLink(device, None) => {
+                            state.device_links.remove(&device);
+                        }
+                        ServerUpdate::UpdateZone(id, Some(zone)) => {
+                            state.zones.insert(id, zone);
+                        }
+                        ServerUpdate::UpdateZone(id, None) => {
+                            state.zones.remove(&id);
+                        }
+                        ServerUpdate::UpdateParamSpecs(id, specs) => {
+                            state.param_specs.insert(id, specs);
+                        }
+                        ServerUpdate::UpdateFavoriteParams(id, paths) => {
+                            if paths.is_empty() {
+                                state.favorite_params.remove(&id);
+                            } else {
+                                state.favorite_params.insert(id, paths);
+                            }
+                        }
+                        ServerUpdate::UpdateModuleDiagnostics(id, diagnostics) => {
+                            if diagnostics.is_empty() {
+                                state.diagnostics.remove(&id);
+                            } else {
+                                state.diagnostics.insert(id, diagnostics);
+                            }
+                        }
+                        ServerUpdate::ReplaceWorkspace(new_state) => {
+                            let current_view = self.guest_view().or(state.current_view);
+                            *state = new_state.into();
+
+                            if current_view.map(|id| state.views.contains_key(&id)).unwrap_or(false) {
+                                state.current_view = current_view;
+                            }
+                        }
                     }
                 }
 
@@ -183,6 +307,47 @@ impl Session {
                 crate::log!("Receiving media library!");
                 self.notify.media.broadcast(Rc::new(library));
             }
+            ServerMessage::Snapshots(snapshots) => {
+                self.notify.snapshots.broadcast(Rc::new(snapshots));
+            }
+            ServerMessage::ParamSearchResults(search, results) => {
+                self.notify.param_search.broadcast(Rc::new((search, results)));
+            }
+            ServerMessage::Project(info) => {
+                self.notify.project.broadcast(Rc::new(info));
+            }
+            ServerMessage::Backup(status) => {
+                self.notify.backup.broadcast(Rc::new(status));
+            }
+            ServerMessage::MediaSync(status) => {
+                self.notify.media_sync.broadcast(Rc::new(status));
+            }
+            ServerMessage::Access(access) => {
+                let access = Rc::new(access);
+                *self.access.borrow_mut() = Some(access.clone());
+
+                if let Some(view) = self.guest_view() {
+                    self.select_view(Some(view));
+                }
+
+                self.notify.access.broadcast(access);
+            }
+            ServerMessage::GuestTokens(tokens) => {
+                self.notify.guest_tokens.broadcast(Rc::new(tokens));
+            }
+            ServerMessage::Introspection(introspection) => {
+                self.notify.introspection.broadcast(Rc::new(introspection));
+            }
+        }
+    }
+
+    /// The view a guest link is limited to, if this session is using one
+    fn guest_view(&self) -> Option<ViewId> {
+        match self.access.borrow().as_deref() {
+            Some(Access::Guest(guest)) => match guest.scope {
+                GuestScope::View(view) => Some(view),
+            },
+            _ => None,
         }
     }
 
@@ -203,6 +368,43 @@ impl Session {
         self.send_message(msg);
     }
 
+    /// Switches this client to another view of the workspace, or back to
+    /// the workspace's own layout
+    pub fn select_view(&self, view: Option<ViewId>) {
+        // guests stay on the view they were given:
+        let view = self.guest_view().or(view);
+
+        if let Some(state) = self.workspace() {
+            let mut state = state.borrow_mut();
+            state.current_view = view.filter(|id| state.views.contains_key(id));
+            util::set_location_view(state.view().map(|view| view.name.as_str()));
+        }
+
+        self.notify.workspace.broadcast(());
+    }
+
+    /// Invokes the binding for a key pressed in the ui, returning whether
+    /// there was one
+    pub fn invoke_key(&self, key: &str) -> bool {
+        if self.guest_view().is_some() {
+            return false;
+        }
+
+        let name = self.workspace().and_then(|state| {
+            state.borrow().bindings.iter()
+                .find(|(_, binding)| binding.key.as_deref() == Some(key))
+                .map(|(name, _)| name.clone())
+        });
+
+        match name {
+            Some(name) => {
+                self.update_workspace(WorkspaceOp::InvokeBinding(name));
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn listen_performance(&self, callback: Callback<Rc<mixlab_protocol::PerformanceInfo>>) -> notify::Handle {
         self.notify.performance.subscribe(callback)
     }
@@ -211,6 +413,79 @@ impl Session {
         self.notify.media.subscribe(callback)
     }
 
+    pub fn listen_media_sync(&self, callback: Callback<Rc<MediaSyncStatus>>) -> notify::Handle {
+        self.notify.media_sync.subscribe(callback)
+    }
+
+    /// Sends media the instance at `remote` doesn't have yet to it
+    pub fn push_media(&self, remote: String) {
+        self.send_message(ClientMessage::PushMedia(remote));
+    }
+
+    pub fn listen_snapshots(&self, callback: Callback<Rc<Vec<SnapshotInfo>>>) -> notify::Handle {
+        self.notify.snapshots.subscribe(callback)
+    }
+
+    pub fn create_snapshot(&self, name: String) {
+        self.send_message(ClientMessage::CreateSnapshot(name));
+    }
+
+    pub fn restore_snapshot(&self, id: SnapshotId) {
+        self.send_message(ClientMessage::RestoreSnapshot(id));
+    }
+
+    /// Asks the server for params matching the search, answered through
+    /// `listen_param_search`
+    pub fn search_params(&self, search: ParamSearch) {
+        self.send_message(ClientMessage::SearchParams(search));
+    }
+
+    pub fn listen_param_search(&self, callback: Callback<Rc<(ParamSearch, Vec<ParamMatch>)>>) -> notify::Handle {
+        self.notify.param_search.subscribe(callback)
+    }
+
+    pub fn listen_project(&self, callback: Callback<Rc<ProjectInfo>>) -> notify::Handle {
+        self.notify.project.subscribe(callback)
+    }
+
+    pub fn listen_backup(&self, callback: Callback<Rc<BackupStatus>>) -> notify::Handle {
+        self.notify.backup.subscribe(callback)
+    }
+
+    pub fn listen_access(&self, callback: Callback<Rc<Access>>) -> notify::Handle {
+        self.notify.access.subscribe(callback)
+    }
+
+    pub fn listen_guest_tokens(&self, callback: Callback<Rc<Vec<GuestToken>>>) -> notify::Handle {
+        self.notify.guest_tokens.subscribe(callback)
+    }
+
+    pub fn create_guest_token(&self, request: GuestTokenRequest) {
+        self.send_message(ClientMessage::CreateGuestToken(request));
+    }
+
+    pub fn revoke_guest_token(&self, token: String) {
+        self.send_message(ClientMessage::RevokeGuestToken(token));
+    }
+
+    /// Asks the server what the engine is running, answered through
+    /// `listen_introspection`
+    pub fn introspect(&self) {
+        self.send_message(ClientMessage::Introspect);
+    }
+
+    pub fn listen_introspection(&self, callback: Callback<Rc<EngineIntrospection>>) -> notify::Handle {
+        self.notify.introspection.subscribe(callback)
+    }
+
+    pub fn duplicate_project(&self, name: String) {
+        self.send_message(ClientMessage::DuplicateProject(name));
+    }
+
+    pub fn keep_project(&self) {
+        self.send_message(ClientMessage::KeepProject);
+    }
+
     fn send_message(&self, msg: ClientMessage) {
         let packet = bincode::serialize(&msg)
             .expect("bincode::serialize");
@@ -233,6 +508,76 @@ pub struct WorkspaceState {
     pub indications: HashMap<ModuleId, Indication>,
     pub inputs: HashMap<ModuleId, Vec<Terminal>>,
     pub outputs: HashMap<ModuleId, Vec<Terminal>>,
+    pub gain_staging: Option<GainStagingReport>,
+    pub param_links: BTreeMap<ParamLinkId, ParamLink>,
+    pub morphs: HashMap<ModuleId, MorphState>,
+    pub modulations: BTreeMap<ModulationId, Modulation>,
+    pub clock_source: ClockSource,
+    pub rehearsal: bool,
+    pub viewport: Viewport,
+    pub views: BTreeMap<ViewId, View>,
+    pub bindings: BTreeMap<String, Binding>,
+    pub device_links: BTreeMap<String, String>,
+    pub zones: BTreeMap<ZoneId, Zone>,
+    pub favorite_params: HashMap<ModuleId, Vec<String>>,
+    pub param_specs: HashMap<ModuleId, Vec<ParamSpec>>,
+    // most severe first:
+    pub diagnostics: HashMap<ModuleId, Vec<Diagnostic>>,
+    pub plugins: Vec<PluginInfo>,
+    // not part of the shared state, each client picks its own view:
+    pub current_view: Option<ViewId>,
+}
+
+impl WorkspaceState {
+    pub fn view(&self) -> Option<&View> {
+        self.current_view.and_then(|id| self.views.get(&id))
+    }
+
+    /// How the module describes a param, if it does
+    pub fn param_spec(&self, module: ModuleId, path: &str) -> Option<&ParamSpec> {
+        self.param_specs.get(&module)?
+            .iter()
+            .find(|spec| spec.path == path)
+    }
+
+    /// A window's geometry as laid out in the current view
+    pub fn window_geometry(&self, module: ModuleId) -> Option<&WindowGeometry> {
+        let geometry = self.geometry.get(&module)?;
+        Some(self.view().and_then(|view| view.geometry(module)).unwrap_or(geometry))
+    }
+
+    pub fn set_window_geometry(&mut self, module: ModuleId, geometry: WindowGeometry) {
+        match self.current_view.and_then(|id| self.views.get_mut(&id)) {
+            Some(view) => { view.set_geometry(module, geometry); }
+            None => { self.geometry.insert(module, geometry); }
+        }
+    }
+
+    /// The op which saves a window's geometry to the current view
+    pub fn window_geometry_op(&self, module: ModuleId, geometry: WindowGeometry) -> WorkspaceOp {
+        match self.current_view {
+            Some(id) => WorkspaceOp::UpdateViewGeometry(id, module, geometry),
+            None => WorkspaceOp::UpdateWindowGeometry(module, geometry),
+        }
+    }
+
+    pub fn viewport(&self) -> &Viewport {
+        self.view().map(|view| &view.viewport).unwrap_or(&self.viewport)
+    }
+
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        match self.current_view.and_then(|id| self.views.get_mut(&id)) {
+            Some(view) => { view.viewport = viewport; }
+            None => { self.viewport = viewport; }
+        }
+    }
+
+    pub fn viewport_op(&self, viewport: Viewport) -> WorkspaceOp {
+        match self.current_view {
+            Some(id) => WorkspaceOp::UpdateViewViewport(id, viewport),
+            None => WorkspaceOp::UpdateViewport(viewport),
+        }
+    }
 }
 
 impl From<mixlab_protocol::WorkspaceState> for WorkspaceState {
@@ -244,6 +589,22 @@ impl From<mixlab_protocol::WorkspaceState> for WorkspaceState {
             connections: wstate.connections.into_iter().collect(),
             inputs: wstate.inputs.into_iter().collect(),
             outputs: wstate.outputs.into_iter().collect(),
+            gain_staging: None,
+            param_links: wstate.param_links.into_iter().collect(),
+            morphs: wstate.morphs.into_iter().collect(),
+            modulations: wstate.modulations.into_iter().collect(),
+            clock_source: wstate.clock_source,
+            rehearsal: wstate.rehearsal,
+            viewport: wstate.viewport,
+            views: wstate.views.into_iter().collect(),
+            bindings: wstate.bindings.into_iter().collect(),
+            device_links: wstate.device_links.into_iter().collect(),
+            zones: wstate.zones.into_iter().collect(),
+            favorite_params: wstate.favorite_params.into_iter().collect(),
+            param_specs: wstate.param_specs.into_iter().collect(),
+            diagnostics: wstate.diagnostics.into_iter().collect(),
+            plugins: wstate.plugins,
+            current_view: None,
         }
     }
 }
@@ -1,16 +1,131 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
 use std::rc::Rc;
 
-use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::{html, Callback, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+use yew_components::Select;
 
-use mixlab_protocol::{PerformanceInfo, PerformanceAccount, TemporalWarningStatus, ModuleId};
+use mixlab_protocol::{PerformanceInfo, PerformanceAccount, TemporalWarningStatus, ModuleId, WorkspaceOp, GainStagingRequest, ParamLink, ParamLinkId, ParamRef, SnapshotId, SnapshotInfo, Modulation, ModulationId, OutputId, LineType, ClockSource, ModuleParams, ViewId, Binding, Command, Indication, ProjectInfo, BackupStatus, GuestToken, GuestTokenRequest, GuestScope, EngineIntrospection, InputIntrospection, ZoneId, Zone, Decibel};
 
 use crate::session::{SessionRef, WorkspaceStateRef};
-use crate::util::notify;
+use crate::util::{self, notify};
+
+const GAIN_STAGING_DURATION_MS: u64 = 5000;
 
 pub struct Sidebar {
+    link: ComponentLink<Self>,
     props: SidebarProps,
     perf_info: Option<Rc<PerformanceInfo>>,
+    link_form: LinkForm,
+    modulation_form: ModulationForm,
+    binding_form: BindingForm,
+    snapshots: Rc<Vec<SnapshotInfo>>,
+    snapshot_name: String,
+    view_name: String,
+    zone_name: String,
+    project: Option<Rc<ProjectInfo>>,
+    duplicate_name: String,
+    backup: Option<Rc<BackupStatus>>,
+    guest_tokens: Rc<Vec<GuestToken>>,
+    guest_label: String,
+    guest_hours: String,
+    introspection: Option<Rc<EngineIntrospection>>,
     _perf_notify: notify::Handle,
+    _snapshots_notify: notify::Handle,
+    _project_notify: notify::Handle,
+    _backup_notify: notify::Handle,
+    _guest_tokens_notify: notify::Handle,
+    _introspection_notify: notify::Handle,
+}
+
+struct LinkForm {
+    source_module: Option<ModuleId>,
+    source_path: String,
+    target_module: Option<ModuleId>,
+    target_path: String,
+    scale: String,
+    offset: String,
+}
+
+impl Default for LinkForm {
+    fn default() -> Self {
+        LinkForm {
+            source_module: None,
+            source_path: String::new(),
+            target_module: None,
+            target_path: String::new(),
+            scale: "1".to_owned(),
+            offset: "0".to_owned(),
+        }
+    }
+}
+
+struct ModulationForm {
+    source: Option<OutputId>,
+    target_module: Option<ModuleId>,
+    target_path: String,
+    min: String,
+    max: String,
+}
+
+impl Default for ModulationForm {
+    fn default() -> Self {
+        ModulationForm {
+            source: None,
+            target_module: None,
+            target_path: String::new(),
+            min: "0".to_owned(),
+            max: "1".to_owned(),
+        }
+    }
+}
+
+struct BindingForm {
+    name: String,
+    key: String,
+    kind: CommandKind,
+    module: Option<ModuleId>,
+    path: String,
+    value: String,
+    snapshot: Option<SnapshotId>,
+}
+
+impl Default for BindingForm {
+    fn default() -> Self {
+        BindingForm {
+            name: String::new(),
+            key: String::new(),
+            kind: CommandKind::Toggle,
+            module: None,
+            path: String::new(),
+            value: "0".to_owned(),
+            snapshot: None,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum CommandKind {
+    Toggle,
+    Set,
+    Bump,
+    Morph,
+    Snapshot,
+}
+
+impl Display for CommandKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self {
+            CommandKind::Toggle => "Toggle param",
+            CommandKind::Set => "Set param",
+            CommandKind::Bump => "Bump param",
+            CommandKind::Morph => "Set morph",
+            CommandKind::Snapshot => "Restore snapshot",
+        };
+
+        write!(f, "{}", label)
+    }
 }
 
 #[derive(Properties, Clone, Debug)]
@@ -21,6 +136,73 @@ pub struct SidebarProps {
 
 pub enum SidebarMsg {
     PerfInfo(Rc<PerformanceInfo>),
+    AnalyzeGainStaging { apply: bool },
+    EditLinkForm(LinkFormMsg),
+    CreateParamLink,
+    DeleteParamLink(ParamLinkId),
+    EditModulationForm(ModulationFormMsg),
+    CreateModulation,
+    DeleteModulation(ModulationId),
+    Snapshots(Rc<Vec<SnapshotInfo>>),
+    SnapshotName(String),
+    CreateSnapshot,
+    RestoreSnapshot(SnapshotId),
+    SetClockSource(ClockSource),
+    SetRehearsal(bool),
+    SelectView(Option<ViewId>),
+    ViewName(String),
+    CreateView,
+    RenameView(ViewId),
+    DeleteView(ViewId),
+    EditBindingForm(BindingFormMsg),
+    CreateBinding,
+    DeleteBinding(String),
+    InvokeBinding(String),
+    LinkDevice(String, Option<String>),
+    ZoneName(String),
+    CreateZone,
+    RenameZone(ZoneId),
+    UpdateZone(ZoneId, Zone),
+    DeleteZone(ZoneId),
+    Project(Rc<ProjectInfo>),
+    DuplicateName(String),
+    DuplicateProject,
+    KeepProject,
+    Backup(Rc<BackupStatus>),
+    GuestTokens(Rc<Vec<GuestToken>>),
+    GuestLabel(String),
+    GuestHours(String),
+    CreateGuestToken(ViewId),
+    RevokeGuestToken(String),
+    Introspect,
+    Introspection(Rc<EngineIntrospection>),
+}
+
+pub enum LinkFormMsg {
+    SourceModule(ModuleId),
+    SourcePath(String),
+    TargetModule(ModuleId),
+    TargetPath(String),
+    Scale(String),
+    Offset(String),
+}
+
+pub enum BindingFormMsg {
+    Name(String),
+    Key(String),
+    Kind(CommandKind),
+    Module(ModuleId),
+    Path(String),
+    Value(String),
+    Snapshot(SnapshotId),
+}
+
+pub enum ModulationFormMsg {
+    Source(OutputId),
+    TargetModule(ModuleId),
+    TargetPath(String),
+    Min(String),
+    Max(String),
 }
 
 impl Component for Sidebar {
@@ -29,11 +211,36 @@ impl Component for Sidebar {
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         let perf_notify = props.session.listen_performance(link.callback(SidebarMsg::PerfInfo));
+        let snapshots_notify = props.session.listen_snapshots(link.callback(SidebarMsg::Snapshots));
+        let project_notify = props.session.listen_project(link.callback(SidebarMsg::Project));
+        let backup_notify = props.session.listen_backup(link.callback(SidebarMsg::Backup));
+        let guest_tokens_notify = props.session.listen_guest_tokens(link.callback(SidebarMsg::GuestTokens));
+        let introspection_notify = props.session.listen_introspection(link.callback(SidebarMsg::Introspection));
 
         Sidebar {
+            link,
             props,
             perf_info: None,
+            link_form: LinkForm::default(),
+            modulation_form: ModulationForm::default(),
+            binding_form: BindingForm::default(),
+            snapshots: Rc::new(Vec::new()),
+            snapshot_name: String::new(),
+            view_name: String::new(),
+            zone_name: String::new(),
+            project: None,
+            duplicate_name: String::new(),
+            backup: None,
+            guest_tokens: Rc::new(Vec::new()),
+            guest_label: String::new(),
+            guest_hours: "4".to_owned(),
+            introspection: None,
             _perf_notify: perf_notify,
+            _snapshots_notify: snapshots_notify,
+            _project_notify: project_notify,
+            _backup_notify: backup_notify,
+            _guest_tokens_notify: guest_tokens_notify,
+            _introspection_notify: introspection_notify,
         }
     }
 
@@ -48,6 +255,306 @@ impl Component for Sidebar {
                 self.perf_info = Some(info);
                 true
             }
+            SidebarMsg::AnalyzeGainStaging { apply } => {
+                self.props.session.update_workspace(
+                    WorkspaceOp::AnalyzeGainStaging(GainStagingRequest {
+                        duration_ms: GAIN_STAGING_DURATION_MS,
+                        apply,
+                    }));
+
+                false
+            }
+            SidebarMsg::EditLinkForm(msg) => {
+                let form = &mut self.link_form;
+
+                match msg {
+                    LinkFormMsg::SourceModule(id) => { form.source_module = Some(id); }
+                    LinkFormMsg::SourcePath(path) => { form.source_path = path; }
+                    LinkFormMsg::TargetModule(id) => { form.target_module = Some(id); }
+                    LinkFormMsg::TargetPath(path) => { form.target_path = path; }
+                    LinkFormMsg::Scale(scale) => { form.scale = scale; }
+                    LinkFormMsg::Offset(offset) => { form.offset = offset; }
+                }
+
+                true
+            }
+            SidebarMsg::CreateParamLink => {
+                let form = &self.link_form;
+
+                let link = match (form.source_module, form.target_module, form.scale.parse(), form.offset.parse()) {
+                    (Some(source), Some(target), Ok(scale), Ok(offset)) => ParamLink {
+                        source: ParamRef { module: source, path: form.source_path.clone() },
+                        target: ParamRef { module: target, path: form.target_path.clone() },
+                        scale,
+                        offset,
+                    },
+                    _ => { return false; }
+                };
+
+                self.props.session.update_workspace(WorkspaceOp::CreateParamLink(link));
+                self.link_form = LinkForm::default();
+                true
+            }
+            SidebarMsg::DeleteParamLink(id) => {
+                self.props.session.update_workspace(WorkspaceOp::DeleteParamLink(id));
+                false
+            }
+            SidebarMsg::EditModulationForm(msg) => {
+                let form = &mut self.modulation_form;
+
+                match msg {
+                    ModulationFormMsg::Source(output) => { form.source = Some(output); }
+                    ModulationFormMsg::TargetModule(id) => { form.target_module = Some(id); }
+                    ModulationFormMsg::TargetPath(path) => { form.target_path = path; }
+                    ModulationFormMsg::Min(min) => { form.min = min; }
+                    ModulationFormMsg::Max(max) => { form.max = max; }
+                }
+
+                true
+            }
+            SidebarMsg::CreateModulation => {
+                let form = &self.modulation_form;
+
+                let modulation = match (form.source, form.target_module, form.min.parse(), form.max.parse()) {
+                    (Some(source), Some(target), Ok(min), Ok(max)) => Modulation {
+                        source,
+                        target: ParamRef { module: target, path: form.target_path.clone() },
+                        min,
+                        max,
+                    },
+                    _ => { return false; }
+                };
+
+                self.props.session.update_workspace(WorkspaceOp::CreateModulation(modulation));
+                self.modulation_form = ModulationForm::default();
+                true
+            }
+            SidebarMsg::DeleteModulation(id) => {
+                self.props.session.update_workspace(WorkspaceOp::DeleteModulation(id));
+                false
+            }
+            SidebarMsg::Snapshots(snapshots) => {
+                self.snapshots = snapshots;
+                true
+            }
+            SidebarMsg::SnapshotName(name) => {
+                self.snapshot_name = name;
+                false
+            }
+            SidebarMsg::CreateSnapshot => {
+                let name = self.snapshot_name.trim().to_owned();
+
+                if name.is_empty() {
+                    return false;
+                }
+
+                self.props.session.create_snapshot(name);
+                self.snapshot_name = String::new();
+                true
+            }
+            SidebarMsg::RestoreSnapshot(id) => {
+                self.props.session.restore_snapshot(id);
+                false
+            }
+            SidebarMsg::SetClockSource(source) => {
+                self.props.session.update_workspace(WorkspaceOp::SetClockSource(source));
+                false
+            }
+            SidebarMsg::SetRehearsal(rehearsal) => {
+                self.props.session.update_workspace(WorkspaceOp::SetRehearsal(rehearsal));
+                false
+            }
+            SidebarMsg::SelectView(view) => {
+                self.props.session.select_view(view);
+                false
+            }
+            SidebarMsg::ViewName(name) => {
+                self.view_name = name;
+                false
+            }
+            SidebarMsg::CreateView => {
+                let name = self.view_name.trim().to_owned();
+
+                if name.is_empty() {
+                    return false;
+                }
+
+                self.props.session.update_workspace(WorkspaceOp::CreateView(name));
+                self.view_name = String::new();
+                true
+            }
+            SidebarMsg::RenameView(id) => {
+                let name = self.view_name.trim().to_owned();
+
+                if name.is_empty() {
+                    return false;
+                }
+
+                self.props.session.update_workspace(WorkspaceOp::RenameView(id, name));
+                self.view_name = String::new();
+                true
+            }
+            SidebarMsg::DeleteView(id) => {
+                self.props.session.update_workspace(WorkspaceOp::DeleteView(id));
+                self.props.session.select_view(None);
+                false
+            }
+            SidebarMsg::EditBindingForm(msg) => {
+                let form = &mut self.binding_form;
+
+                match msg {
+                    BindingFormMsg::Name(name) => { form.name = name; }
+                    BindingFormMsg::Key(key) => { form.key = key; }
+                    BindingFormMsg::Kind(kind) => { form.kind = kind; }
+                    BindingFormMsg::Module(id) => { form.module = Some(id); }
+                    BindingFormMsg::Path(path) => { form.path = path; }
+                    BindingFormMsg::Value(value) => { form.value = value; }
+                    BindingFormMsg::Snapshot(id) => { form.snapshot = Some(id); }
+                }
+
+                true
+            }
+            SidebarMsg::CreateBinding => {
+                let form = &self.binding_form;
+                let name = form.name.trim().to_owned();
+
+                if name.is_empty() {
+                    return false;
+                }
+
+                let param = form.module.map(|module| ParamRef { module, path: form.path.clone() });
+
+                let command = match (form.kind, param, form.value.parse::<f64>(), form.snapshot) {
+                    (CommandKind::Toggle, Some(param), _, _) => Command::ToggleParam(param),
+                    (CommandKind::Set, Some(param), Ok(value), _) => Command::SetParam(param, value),
+                    (CommandKind::Bump, Some(param), _, _) => Command::BumpParam(param),
+                    (CommandKind::Morph, Some(param), Ok(value), _) => Command::SetMorph(param.module, value),
+                    (CommandKind::Snapshot, _, _, Some(id)) => Command::RestoreSnapshot(id),
+                    _ => { return false; }
+                };
+
+                // not trimmed, a single space is the key name for the space bar:
+                let key = Some(form.key.clone()).filter(|key| !key.is_empty());
+
+                self.props.session.update_workspace(
+                    WorkspaceOp::SetBinding(name, Some(Binding { command, key })));
+
+                self.binding_form = BindingForm::default();
+                true
+            }
+            SidebarMsg::DeleteBinding(name) => {
+                self.props.session.update_workspace(WorkspaceOp::SetBinding(name, None));
+                false
+            }
+            SidebarMsg::InvokeBinding(name) => {
+                self.props.session.update_workspace(WorkspaceOp::InvokeBinding(name));
+                false
+            }
+            SidebarMsg::LinkDevice(device, local) => {
+                self.props.session.update_workspace(WorkspaceOp::LinkDevice(device, local));
+                false
+            }
+            SidebarMsg::ZoneName(name) => {
+                self.zone_name = name;
+                false
+            }
+            SidebarMsg::CreateZone => {
+                let name = self.zone_name.trim().to_owned();
+
+                if name.is_empty() {
+                    return false;
+                }
+
+                self.props.session.update_workspace(WorkspaceOp::CreateZone(name));
+                self.zone_name = String::new();
+                true
+            }
+            SidebarMsg::RenameZone(id) => {
+                let name = self.zone_name.trim().to_owned();
+
+                let zone = match self.props.workspace.borrow().zones.get(&id) {
+                    Some(zone) if !name.is_empty() => Zone { name, ..zone.clone() },
+                    _ => { return false; }
+                };
+
+                self.props.session.update_workspace(WorkspaceOp::UpdateZone(id, zone));
+                self.zone_name = String::new();
+                true
+            }
+            SidebarMsg::UpdateZone(id, zone) => {
+                self.props.session.update_workspace(WorkspaceOp::UpdateZone(id, zone));
+                false
+            }
+            SidebarMsg::DeleteZone(id) => {
+                self.props.session.update_workspace(WorkspaceOp::DeleteZone(id));
+                false
+            }
+            SidebarMsg::Project(info) => {
+                self.project = Some(info);
+                true
+            }
+            SidebarMsg::DuplicateName(name) => {
+                self.duplicate_name = name;
+                false
+            }
+            SidebarMsg::DuplicateProject => {
+                let name = self.duplicate_name.trim().to_owned();
+
+                if name.is_empty() {
+                    return false;
+                }
+
+                self.props.session.duplicate_project(name);
+                self.duplicate_name = String::new();
+                true
+            }
+            SidebarMsg::KeepProject => {
+                self.props.session.keep_project();
+                false
+            }
+            SidebarMsg::Backup(status) => {
+                self.backup = Some(status);
+                true
+            }
+            SidebarMsg::GuestTokens(tokens) => {
+                self.guest_tokens = tokens;
+                true
+            }
+            SidebarMsg::GuestLabel(label) => {
+                self.guest_label = label;
+                false
+            }
+            SidebarMsg::GuestHours(hours) => {
+                self.guest_hours = hours;
+                false
+            }
+            SidebarMsg::CreateGuestToken(view) => {
+                let hours = match self.guest_hours.trim().parse::<f64>() {
+                    Ok(hours) if hours > 0.0 => hours,
+                    _ => { return false; }
+                };
+
+                self.props.session.create_guest_token(GuestTokenRequest {
+                    label: self.guest_label.trim().to_owned(),
+                    scope: GuestScope::View(view),
+                    duration_secs: (hours * 3600.0) as u64,
+                });
+
+                self.guest_label = String::new();
+                true
+            }
+            SidebarMsg::RevokeGuestToken(token) => {
+                self.props.session.revoke_guest_token(token);
+                false
+            }
+            SidebarMsg::Introspect => {
+                self.props.session.introspect();
+                false
+            }
+            SidebarMsg::Introspection(introspection) => {
+                self.introspection = Some(introspection);
+                true
+            }
         }
     }
 
@@ -55,7 +562,21 @@ impl Component for Sidebar {
         html! {
             <div class="sidebar">
                 <div class="sidebar-title">{"Mixlab"}</div>
+                {self.view_project()}
+                {self.view_backup()}
                 {self.view_perf_info()}
+                {self.view_views()}
+                {self.view_guest_links()}
+                {self.view_clock()}
+                {self.view_rehearsal()}
+                {self.view_devices()}
+                {self.view_zones()}
+                {self.view_gain_staging()}
+                {self.view_param_links()}
+                {self.view_modulations()}
+                {self.view_bindings()}
+                {self.view_snapshots()}
+                {self.view_introspection()}
             </div>
         }
     }
@@ -71,6 +592,931 @@ impl Sidebar {
         }).unwrap_or("-".to_owned())
     }
 
+    fn view_param_links(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        let modules = workspace.modules.keys()
+            .map(|id| DisplayModule(*id, self.module_name(*id)))
+            .collect::<Vec<_>>();
+
+        let selected = |id: Option<ModuleId>| {
+            modules.iter().find(|module| Some(module.0) == id).cloned()
+        };
+
+        let describe = |param: &ParamRef| {
+            format!("{} #{} {}", self.module_name(param.module), param.module.0, param.path)
+        };
+
+        let form = &self.link_form;
+
+        html! {
+            <div class="param-links">
+                <table class="param-links-table">
+                    { for workspace.param_links.iter().map(|(id, link)| {
+                        let id = *id;
+
+                        html! {
+                            <tr>
+                                <td>{describe(&link.source)}</td>
+                                <td>{"\u{2192}"}</td>
+                                <td>{describe(&link.target)}</td>
+                                <td>{format!("\u{d7}{} {:+}", link.scale, link.offset)}</td>
+                                <td>
+                                    <button onclick={self.link.callback(move |_| SidebarMsg::DeleteParamLink(id))}>
+                                        {"Unlink"}
+                                    </button>
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                </table>
+                <div class="param-links-form">
+                    <Select<DisplayModule>
+                        selected={selected(form.source_module)}
+                        options={modules.clone()}
+                        on_change={self.link.callback(|module: DisplayModule|
+                            SidebarMsg::EditLinkForm(LinkFormMsg::SourceModule(module.0)))}
+                    />
+                    <input type="text"
+                        placeholder="/source/path"
+                        value={&form.source_path}
+                        onchange={self.link.callback(|ev| SidebarMsg::EditLinkForm(LinkFormMsg::SourcePath(change_value(ev))))}
+                    />
+                    <Select<DisplayModule>
+                        selected={selected(form.target_module)}
+                        options={modules.clone()}
+                        on_change={self.link.callback(|module: DisplayModule|
+                            SidebarMsg::EditLinkForm(LinkFormMsg::TargetModule(module.0)))}
+                    />
+                    <input type="text"
+                        placeholder="/target/path"
+                        value={&form.target_path}
+                        onchange={self.link.callback(|ev| SidebarMsg::EditLinkForm(LinkFormMsg::TargetPath(change_value(ev))))}
+                    />
+                    <label>{"Scale"}</label>
+                    <input type="number"
+                        value={&form.scale}
+                        onchange={self.link.callback(|ev| SidebarMsg::EditLinkForm(LinkFormMsg::Scale(change_value(ev))))}
+                    />
+                    <label>{"Offset"}</label>
+                    <input type="number"
+                        value={&form.offset}
+                        onchange={self.link.callback(|ev| SidebarMsg::EditLinkForm(LinkFormMsg::Offset(change_value(ev))))}
+                    />
+                    <button onclick={self.link.callback(|_| SidebarMsg::CreateParamLink)}>
+                        {"Link"}
+                    </button>
+                </div>
+            </div>
+        }
+    }
+
+    fn view_modulations(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        let modules = workspace.modules.keys()
+            .map(|id| DisplayModule(*id, self.module_name(*id)))
+            .collect::<Vec<_>>();
+
+        // only mono outputs carry control signals:
+        let sources = workspace.outputs.iter()
+            .flat_map(|(module_id, terminals)| {
+                terminals.iter().enumerate()
+                    .filter(|(_, terminal)| terminal.line_type() == LineType::Mono)
+                    .map(move |(index, terminal)| (OutputId(*module_id, index), terminal.label().map(String::from)))
+            })
+            .map(|(output, label)| {
+                let name = self.module_name(output.module_id());
+                let label = label.unwrap_or_else(|| (output.index() + 1).to_string());
+                DisplayOutput(output, format!("{} #{} {}", name, output.module_id().0, label))
+            })
+            .collect::<Vec<_>>();
+
+        let form = &self.modulation_form;
+
+        let selected_source = sources.iter().find(|source| Some(source.0) == form.source).cloned();
+        let selected_target = modules.iter().find(|module| Some(module.0) == form.target_module).cloned();
+
+        html! {
+            <div class="modulations">
+                <table class="modulations-table">
+                    { for workspace.modulations.iter().map(|(id, modulation)| {
+                        let id = *id;
+                        let source = modulation.source;
+
+                        html! {
+                            <tr>
+                                <td>{format!("{} #{} {}", self.module_name(source.module_id()), source.module_id().0, source.index() + 1)}</td>
+                                <td>{"\u{2192}"}</td>
+                                <td>{format!("{} #{} {}", self.module_name(modulation.target.module), modulation.target.module.0, modulation.target.path)}</td>
+                                <td>{format!("{} - {}", modulation.min, modulation.max)}</td>
+                                <td>
+                                    <button onclick={self.link.callback(move |_| SidebarMsg::DeleteModulation(id))}>
+                                        {"Remove"}
+                                    </button>
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                </table>
+                <div class="modulations-form">
+                    <Select<DisplayOutput>
+                        selected={selected_source}
+                        options={sources.clone()}
+                        on_change={self.link.callback(|output: DisplayOutput|
+                            SidebarMsg::EditModulationForm(ModulationFormMsg::Source(output.0)))}
+                    />
+                    <Select<DisplayModule>
+                        selected={selected_target}
+                        options={modules.clone()}
+                        on_change={self.link.callback(|module: DisplayModule|
+                            SidebarMsg::EditModulationForm(ModulationFormMsg::TargetModule(module.0)))}
+                    />
+                    <input type="text"
+                        placeholder="/target/path"
+                        value={&form.target_path}
+                        onchange={self.link.callback(|ev| SidebarMsg::EditModulationForm(ModulationFormMsg::TargetPath(change_value(ev))))}
+                    />
+                    <label>{"Min"}</label>
+                    <input type="number"
+                        value={&form.min}
+                        onchange={self.link.callback(|ev| SidebarMsg::EditModulationForm(ModulationFormMsg::Min(change_value(ev))))}
+                    />
+                    <label>{"Max"}</label>
+                    <input type="number"
+                        value={&form.max}
+                        onchange={self.link.callback(|ev| SidebarMsg::EditModulationForm(ModulationFormMsg::Max(change_value(ev))))}
+                    />
+                    <button onclick={self.link.callback(|_| SidebarMsg::CreateModulation)}>
+                        {"Modulate"}
+                    </button>
+                </div>
+            </div>
+        }
+    }
+
+    fn view_bindings(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        let modules = workspace.modules.keys()
+            .map(|id| DisplayModule(*id, self.module_name(*id)))
+            .collect::<Vec<_>>();
+
+        // automatic backups come and go, only named snapshots make sense to
+        // bind to:
+        let snapshots = self.snapshots.iter()
+            .filter_map(|snapshot| Some(DisplaySnapshot(snapshot.id, snapshot.name.clone()?)))
+            .collect::<Vec<_>>();
+
+        let form = &self.binding_form;
+
+        let kinds = vec![
+            CommandKind::Toggle,
+            CommandKind::Set,
+            CommandKind::Bump,
+            CommandKind::Morph,
+            CommandKind::Snapshot,
+        ];
+
+        let describe_param = |param: &ParamRef| {
+            format!("{} #{} {}", self.module_name(param.module), param.module.0, param.path)
+        };
+
+        let describe = |command: &Command| {
+            match command {
+                Command::ToggleParam(param) => format!("Toggle {}", describe_param(param)),
+                Command::SetParam(param, value) => format!("Set {} to {}", describe_param(param), value),
+                Command::BumpParam(param) => format!("Bump {}", describe_param(param)),
+                Command::SetMorph(id, position) => format!("Morph {} #{} to {}", self.module_name(*id), id.0, position),
+                Command::RestoreSnapshot(id) => {
+                    let name = snapshots.iter()
+                        .find(|snapshot| snapshot.0 == *id)
+                        .map(|snapshot| snapshot.1.clone())
+                        .unwrap_or_else(|| format!("#{}", id.0));
+
+                    format!("Restore {}", name)
+                }
+            }
+        };
+
+        html! {
+            <div class="bindings">
+                <table class="bindings-table">
+                    { for workspace.bindings.iter().map(|(name, binding)| {
+                        let invoke = name.clone();
+                        let delete = name.clone();
+
+                        html! {
+                            <tr>
+                                <td class="binding-name">{name}</td>
+                                <td class="binding-key">{binding.key.as_deref().unwrap_or("")}</td>
+                                <td>{describe(&binding.command)}</td>
+                                <td>
+                                    <button onclick={self.link.callback(move |_| SidebarMsg::InvokeBinding(invoke.clone()))}>
+                                        {"Run"}
+                                    </button>
+                                </td>
+                                <td>
+                                    <button onclick={self.link.callback(move |_| SidebarMsg::DeleteBinding(delete.clone()))}>
+                                        {"Delete"}
+                                    </button>
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                </table>
+                <div class="bindings-form">
+                    <input type="text"
+                        placeholder="Binding name"
+                        value={&form.name}
+                        onchange={self.link.callback(|ev| SidebarMsg::EditBindingForm(BindingFormMsg::Name(change_value(ev))))}
+                    />
+                    <input type="text"
+                        placeholder="Key"
+                        value={&form.key}
+                        onchange={self.link.callback(|ev| SidebarMsg::EditBindingForm(BindingFormMsg::Key(change_value(ev))))}
+                    />
+                    <Select<CommandKind>
+                        selected={Some(form.kind)}
+                        options={kinds}
+                        on_change={self.link.callback(|kind| SidebarMsg::EditBindingForm(BindingFormMsg::Kind(kind)))}
+                    />
+                    { match form.kind {
+                        CommandKind::Snapshot => html! {
+                            <Select<DisplaySnapshot>
+                                selected={snapshots.iter().find(|snapshot| Some(snapshot.0) == form.snapshot).cloned()}
+                                options={snapshots.clone()}
+                                on_change={self.link.callback(|snapshot: DisplaySnapshot|
+                                    SidebarMsg::EditBindingForm(BindingFormMsg::Snapshot(snapshot.0)))}
+                            />
+                        },
+                        kind => html! {
+                            <>
+                                <Select<DisplayModule>
+                                    selected={modules.iter().find(|module| Some(module.0) == form.module).cloned()}
+                                    options={modules.clone()}
+                                    on_change={self.link.callback(|module: DisplayModule|
+                                        SidebarMsg::EditBindingForm(BindingFormMsg::Module(module.0)))}
+                                />
+                                { if kind == CommandKind::Morph {
+                                    html! {}
+                                } else {
+                                    html! {
+                                        <input type="text"
+                                            placeholder="/param/path"
+                                            value={&form.path}
+                                            onchange={self.link.callback(|ev| SidebarMsg::EditBindingForm(BindingFormMsg::Path(change_value(ev))))}
+                                        />
+                                    }
+                                } }
+                                { if kind == CommandKind::Set || kind == CommandKind::Morph {
+                                    html! {
+                                        <input type="number"
+                                            value={&form.value}
+                                            onchange={self.link.callback(|ev| SidebarMsg::EditBindingForm(BindingFormMsg::Value(change_value(ev))))}
+                                        />
+                                    }
+                                } else {
+                                    html! {}
+                                } }
+                            </>
+                        },
+                    } }
+                    <button onclick={self.link.callback(|_| SidebarMsg::CreateBinding)}>
+                        {"Bind"}
+                    </button>
+                </div>
+            </div>
+        }
+    }
+
+    fn view_project(&self) -> Html {
+        let project = match &self.project {
+            Some(project) => project,
+            None => { return html! {}; }
+        };
+
+        html! {
+            <div class="project">
+                <div class="project-name">{&project.name}</div>
+                { if project.scratch {
+                    html! {
+                        <div class="project-scratch">
+                            {"Scratch project, deleted on exit"}
+                            <button onclick={self.link.callback(|_| SidebarMsg::KeepProject)}>
+                                {"Keep"}
+                            </button>
+                        </div>
+                    }
+                } else {
+                    html! {}
+                } }
+                <div class="project-form">
+                    <input type="text"
+                        placeholder="Copy name"
+                        value={&self.duplicate_name}
+                        onchange={self.link.callback(|ev| SidebarMsg::DuplicateName(change_value(ev)))}
+                    />
+                    <button onclick={self.link.callback(|_| SidebarMsg::DuplicateProject)}>
+                        {"Duplicate"}
+                    </button>
+                </div>
+            </div>
+        }
+    }
+
+    fn view_backup(&self) -> Html {
+        let status = match &self.backup {
+            Some(status) => status,
+            None => { return html! {}; }
+        };
+
+        let activity = match &status.uploading {
+            Some(key) => format!("Uploading {}", key),
+            None if status.pending > 0 => "Waiting to retry".to_owned(),
+            None => "Up to date".to_owned(),
+        };
+
+        html! {
+            <div class="backup">
+                <div class="backup-target">{format!("Backing up to {}", status.target)}</div>
+                <div class="backup-activity">{activity}</div>
+                <div class="backup-counts">
+                    {format!("{} uploaded, {} pending", status.uploaded, status.pending)}
+                    { if status.failed > 0 {
+                        html! { <span class="backup-failed">{format!(", {} failed", status.failed)}</span> }
+                    } else {
+                        html! {}
+                    } }
+                </div>
+                { if let Some(error) = &status.last_error {
+                    html! { <div class="backup-error" title={error}>{"Last error: "}{error}</div> }
+                } else {
+                    html! {}
+                } }
+            </div>
+        }
+    }
+
+    fn view_snapshots(&self) -> Html {
+        html! {
+            <div class="snapshots">
+                <div class="snapshots-form">
+                    <input type="text"
+                        placeholder="Snapshot name"
+                        value={&self.snapshot_name}
+                        onchange={self.link.callback(|ev| SidebarMsg::SnapshotName(change_value(ev)))}
+                    />
+                    <button onclick={self.link.callback(|_| SidebarMsg::CreateSnapshot)}>
+                        {"Save snapshot"}
+                    </button>
+                </div>
+                <table class="snapshots-table">
+                    { for self.snapshots.iter().map(|snapshot| {
+                        let id = snapshot.id;
+
+                        let created_at = js_sys::Date::new(&(snapshot.created_at as f64 * 1000.0).into())
+                            .to_locale_string("default", &js_sys::Object::new());
+
+                        let name_class = match snapshot.name {
+                            Some(_) => "snapshot-name",
+                            None => "snapshot-name snapshot-backup",
+                        };
+
+                        html! {
+                            <tr>
+                                <td class={name_class}>{snapshot.name.as_deref().unwrap_or("Backup")}</td>
+                                <td class="snapshot-time">{String::from(created_at)}</td>
+                                <td>
+                                    <button onclick={self.link.callback(move |_| SidebarMsg::RestoreSnapshot(id))}>
+                                        {"Restore"}
+                                    </button>
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                </table>
+            </div>
+        }
+    }
+
+    fn view_gain_staging(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        html! {
+            <div class="gain-staging">
+                <div class="gain-staging-actions">
+                    <button onclick={self.link.callback(|_| SidebarMsg::AnalyzeGainStaging { apply: false })}>
+                        {"Analyze levels"}
+                    </button>
+                    <button onclick={self.link.callback(|_| SidebarMsg::AnalyzeGainStaging { apply: true })}>
+                        {"Auto trim"}
+                    </button>
+                </div>
+                { if let Some(report) = &workspace.gain_staging {
+                    html! {
+                        <table class="gain-staging-table">
+                            { for report.suggestions.iter().map(|suggestion| {
+                                let name = self.module_name(suggestion.input.module_id());
+
+                                html! {
+                                    <tr>
+                                        <td>{format!("{} {}", name, suggestion.input.index() + 1)}</td>
+                                        <td>{format!("{}", suggestion.level.rms)}</td>
+                                        <td>{format!("{} \u{2192} {}", suggestion.current, suggestion.suggested)}</td>
+                                    </tr>
+                                }
+                            }) }
+                        </table>
+                    }
+                } else {
+                    html! {}
+                } }
+            </div>
+        }
+    }
+
+    fn view_introspection(&self) -> Html {
+        html! {
+            <div class="introspection">
+                <div class="introspection-actions">
+                    <button onclick={self.link.callback(|_| SidebarMsg::Introspect)}>
+                        {"Inspect engine"}
+                    </button>
+                </div>
+                { if let Some(introspection) = &self.introspection {
+                    html! {
+                        <>
+                            <div class="introspection-config">
+                                {format!("{} Hz, {} samples per tick", introspection.sample_rate, introspection.block_size)}
+                            </div>
+                            <table class="introspection-table">
+                                { for introspection.modules.iter().enumerate().map(|(position, module)| {
+                                    html! {
+                                        <tr>
+                                            <td class="introspection-position">{position + 1}</td>
+                                            <td>
+                                                <div class="introspection-module">
+                                                    {format!("{} #{}", module.kind, module.id.0)}
+                                                </div>
+                                                { for module.inputs.iter().enumerate().map(|(index, input)| {
+                                                    html! {
+                                                        <div class="introspection-input">
+                                                            {describe_input(index, input)}
+                                                        </div>
+                                                    }
+                                                }) }
+                                                { for module.outputs.iter().enumerate().map(|(index, line_type)| {
+                                                    html! {
+                                                        <div class="introspection-output">
+                                                            {format!("out {} {:?}", index + 1, line_type)}
+                                                        </div>
+                                                    }
+                                                }) }
+                                            </td>
+                                            <td class="introspection-latency">
+                                                {format!("+{} \u{2192} {}", module.latency, module.path_latency)}
+                                            </td>
+                                        </tr>
+                                    }
+                                }) }
+                            </table>
+                        </>
+                    }
+                } else {
+                    html! {}
+                } }
+            </div>
+        }
+    }
+
+    fn view_guest_links(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        html! {
+            <div class="guest-links">
+                { if let Some(view) = workspace.current_view {
+                    html! {
+                        <div class="guest-links-form">
+                            <input type="text"
+                                placeholder="Guest name"
+                                value={&self.guest_label}
+                                onchange={self.link.callback(|ev| SidebarMsg::GuestLabel(change_value(ev)))}
+                            />
+                            <input type="number"
+                                class="guest-links-hours"
+                                min="0"
+                                title="Hours until the link expires"
+                                value={&self.guest_hours}
+                                onchange={self.link.callback(|ev| SidebarMsg::GuestHours(change_value(ev)))}
+                            />
+                            <button onclick={self.link.callback(move |_| SidebarMsg::CreateGuestToken(view))}>
+                                {"Share view"}
+                            </button>
+                        </div>
+                    }
+                } else {
+                    html! {}
+                } }
+                <table class="guest-links-table">
+                    { for self.guest_tokens.iter().map(|guest| {
+                        let view_name = match guest.scope {
+                            GuestScope::View(view) => workspace.views.get(&view).map(|view| view.name.as_str()),
+                        };
+
+                        let url = format!("{}/?guest={}#view={}", util::origin(), guest.token,
+                            String::from(js_sys::encode_uri_component(view_name.unwrap_or(""))));
+
+                        let expires_at = js_sys::Date::new(&(guest.expires_at as f64 * 1000.0).into())
+                            .to_locale_string("default", &js_sys::Object::new());
+
+                        let token = guest.token.clone();
+
+                        html! {
+                            <tr>
+                                <td class="guest-links-label">
+                                    <a href={url} target="_blank">
+                                        { if guest.label.is_empty() { "Guest" } else { guest.label.as_str() } }
+                                    </a>
+                                    <div class="guest-links-view">{view_name.unwrap_or("Deleted view")}</div>
+                                </td>
+                                <td class="guest-links-expiry">{String::from(expires_at)}</td>
+                                <td>
+                                    <button onclick={self.link.callback(move |_| SidebarMsg::RevokeGuestToken(token.clone()))}>
+                                        {"Revoke"}
+                                    </button>
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                </table>
+            </div>
+        }
+    }
+
+    fn view_views(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        let mut views = vec![DisplayView(None, "Workspace layout".to_owned())];
+
+        for (id, view) in &workspace.views {
+            views.push(DisplayView(Some(*id), view.name.clone()));
+        }
+
+        let selected = views.iter()
+            .find(|view| view.0 == workspace.current_view)
+            .cloned();
+
+        let view = workspace.current_view.and_then(|id| Some((id, workspace.views.get(&id)?)));
+
+        html! {
+            <div class="views">
+                <div class="views-select">
+                    <label>{"View"}</label>
+                    <Select<DisplayView>
+                        selected={selected}
+                        options={views}
+                        on_change={self.link.callback(|view: DisplayView| SidebarMsg::SelectView(view.0))}
+                    />
+                </div>
+                <div class="views-form">
+                    <input type="text"
+                        placeholder="View name"
+                        value={&self.view_name}
+                        onchange={self.link.callback(|ev| SidebarMsg::ViewName(change_value(ev)))}
+                    />
+                    <button onclick={self.link.callback(|_| SidebarMsg::CreateView)}>
+                        {"New view"}
+                    </button>
+                    { if let Some((id, _)) = view {
+                        html! {
+                            <>
+                                <button onclick={self.link.callback(move |_| SidebarMsg::RenameView(id))}>
+                                    {"Rename"}
+                                </button>
+                                <button onclick={self.link.callback(move |_| SidebarMsg::DeleteView(id))}>
+                                    {"Delete"}
+                                </button>
+                            </>
+                        }
+                    } else {
+                        html! {}
+                    } }
+                </div>
+                { match view {
+                    Some((_, view)) if !view.pinned.is_empty() => html! {
+                        <table class="views-meters-table">
+                            { for view.pinned.iter().map(|id| {
+                                // levels are as of the last analysis, if it
+                                // measured this module at all:
+                                let levels = workspace.gain_staging.iter()
+                                    .flat_map(|report| report.wires.iter())
+                                    .filter(|(output, _)| output.module_id() == *id)
+                                    .map(|(output, level)| format!("{}: {} / {}", output.index() + 1, level.peak, level.rms))
+                                    .collect::<Vec<_>>();
+
+                                let levels = if levels.is_empty() {
+                                    "-".to_owned()
+                                } else {
+                                    levels.join(", ")
+                                };
+
+                                html! {
+                                    <tr>
+                                        <td>{format!("{} #{}", self.module_name(*id), id.0)}</td>
+                                        <td class="views-meter">{levels}</td>
+                                    </tr>
+                                }
+                            }) }
+                        </table>
+                    },
+                    _ => html! {},
+                } }
+            </div>
+        }
+    }
+
+    fn view_rehearsal(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+        let rehearsal = workspace.rehearsal;
+
+        // inputs without a stand-in stay live while rehearsing:
+        let live = workspace.modules.values()
+            .filter(|params| match params {
+                ModuleParams::InputDevice(params) => params.stand_in.is_none(),
+                ModuleParams::StreamInput(params) => params.stand_in.is_none(),
+                _ => false,
+            })
+            .count();
+
+        let status_class = if rehearsal {
+            "status-light status-light-red-active"
+        } else {
+            "status-light"
+        };
+
+        html! {
+            <div class="rehearsal">
+                <div class="rehearsal-status">
+                    <button onclick={self.link.callback(move |_| SidebarMsg::SetRehearsal(!rehearsal))}>
+                        {if rehearsal { "End rehearsal" } else { "Rehearse" }}
+                    </button>
+                    <div class={status_class}>{"REHEARSAL"}</div>
+                </div>
+                { if live > 0 {
+                    html! {
+                        <div class="rehearsal-live">
+                            {format!("{} {} no stand-in and will stay live", live, if live == 1 { "input has" } else { "inputs have" })}
+                        </div>
+                    }
+                } else {
+                    html! {}
+                } }
+            </div>
+        }
+    }
+
+    fn view_clock(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        let mut sources = vec![DisplayClock(ClockSource::Internal, "Internal".to_owned())];
+
+        for (id, params) in &workspace.modules {
+            match params {
+                ModuleParams::InputDevice(_) | ModuleParams::OutputDevice(_) | ModuleParams::StreamInput(_) => {
+                    let name = format!("{} #{}", self.module_name(*id), id.0);
+                    sources.push(DisplayClock(ClockSource::Module(*id), name));
+                }
+                _ => {}
+            }
+        }
+
+        let selected = sources.iter()
+            .find(|source| source.0 == workspace.clock_source)
+            .cloned();
+
+        let clock = self.perf_info.as_ref().map(|perf_info| &perf_info.clock);
+
+        let fallback_class = match clock {
+            Some(clock) if clock.fallback => "status-light status-light-red-active",
+            _ => "status-light",
+        };
+
+        html! {
+            <div class="clock">
+                <div class="clock-source">
+                    <label>{"Clock"}</label>
+                    <Select<DisplayClock>
+                        selected={selected}
+                        options={sources}
+                        on_change={self.link.callback(|source: DisplayClock| SidebarMsg::SetClockSource(source.0))}
+                    />
+                    <div class={fallback_class}>{"FALLBACK"}</div>
+                </div>
+                { match clock {
+                    Some(clock) if !clock.drift.is_empty() || !clock.varispeed.is_empty() => html! {
+                        <table class="clock-drift-table">
+                            { for clock.drift.iter().map(|(id, ppm)| {
+                                html! {
+                                    <tr>
+                                        <td>{format!("{} #{}", self.module_name(*id), id.0)}</td>
+                                        <td class="clock-drift">{format!("{:+.1} ppm", ppm)}</td>
+                                    </tr>
+                                }
+                            }) }
+                            { for clock.varispeed.iter().map(|(id, ppm)| {
+                                html! {
+                                    <tr>
+                                        <td>{format!("{} #{} varispeed", self.module_name(*id), id.0)}</td>
+                                        <td class="clock-drift">{format!("{:+.1} ppm", ppm)}</td>
+                                    </tr>
+                                }
+                            }) }
+                        </table>
+                    },
+                    _ => html! {},
+                } }
+            </div>
+        }
+    }
+
+    // lists devices the workspace refers to which couldn't be opened on this
+    // machine, eg. in a project made on another one, for relinking
+    fn view_devices(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        let mut missing = BTreeMap::<String, Vec<String>>::new();
+
+        for (id, params) in &workspace.modules {
+            match (params, workspace.indications.get(id)) {
+                (ModuleParams::OutputDevice(params), Some(Indication::OutputDevice(indication))) => {
+                    if let (Some(device), None) = (&params.device, &indication.resolved) {
+                        let available = indication.devices.iter().flatten().map(|(name, _)| name.clone());
+                        missing.entry(device.clone()).or_default().extend(available);
+                    }
+                }
+                (ModuleParams::InputDevice(params), Some(Indication::InputDevice(indication))) => {
+                    if let (Some(device), None) = (&params.device, &indication.resolved) {
+                        let available = indication.devices.iter().flatten().map(|(name, _)| name.clone());
+                        missing.entry(device.clone()).or_default().extend(available);
+                    }
+
+                    if let (Some(monitor), None) = (&params.monitor, &indication.monitor_resolved) {
+                        let available = indication.monitor_devices.iter().flatten().cloned();
+                        missing.entry(monitor.clone()).or_default().extend(available);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if missing.is_empty() && workspace.device_links.is_empty() {
+            return html! {};
+        }
+
+        html! {
+            <div class="devices">
+                <table class="devices-table">
+                    { for missing.into_iter().map(|(device, mut available)| {
+                        available.sort();
+                        available.dedup();
+
+                        let linked = workspace.device_links.get(&device).cloned();
+                        let link_device = device.clone();
+
+                        html! {
+                            <tr>
+                                <td class="devices-missing">{&device}</td>
+                                <td>
+                                    <Select<String>
+                                        selected={linked}
+                                        options={available}
+                                        on_change={self.link.callback(move |local: String|
+                                            SidebarMsg::LinkDevice(link_device.clone(), Some(local)))}
+                                    />
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                    { for workspace.device_links.iter().map(|(device, local)| {
+                        let unlink_device = device.clone();
+
+                        html! {
+                            <tr>
+                                <td>{format!("{} \u{2192} {}", device, local)}</td>
+                                <td>
+                                    <button onclick={self.link.callback(move |_| SidebarMsg::LinkDevice(unlink_device.clone(), None))}>
+                                        {"Unlink"}
+                                    </button>
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                </table>
+            </div>
+        }
+    }
+
+    fn view_zones(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        let mut sources = vec![DisplaySource(None, "No source".to_owned())];
+
+        sources.extend(workspace.outputs.iter()
+            .flat_map(|(module_id, terminals)| {
+                terminals.iter().enumerate()
+                    .filter(|(_, terminal)| terminal.line_type() != LineType::Video)
+                    .map(move |(index, terminal)| (OutputId(*module_id, index), terminal.label().map(String::from)))
+            })
+            .map(|(output, label)| {
+                let name = self.module_name(output.module_id());
+                let label = label.unwrap_or_else(|| (output.index() + 1).to_string());
+                DisplaySource(Some(output), format!("{} #{} {}", name, output.module_id().0, label))
+            }));
+
+        html! {
+            <div class="zones">
+                <table class="zones-table">
+                    { for workspace.zones.iter().map(|(id, zone)| {
+                        let id = *id;
+                        let selected = sources.iter().find(|source| source.0 == zone.source).cloned();
+                        let source_zone = zone.clone();
+
+                        html! {
+                            <tr>
+                                <td class="zones-name">{&zone.name}</td>
+                                <td>
+                                    <Select<DisplaySource>
+                                        selected={selected}
+                                        options={sources.clone()}
+                                        on_change={self.link.callback(move |source: DisplaySource|
+                                            SidebarMsg::UpdateZone(id, Zone { source: source.0, ..source_zone.clone() }))}
+                                    />
+                                    <div class="zones-processing">
+                                        <label>{"Delay ms"}</label>
+                                        <input type="number" min={0} max={1000} step={1}
+                                            value={zone.delay_ms}
+                                            onchange={self.zone_callback(id, zone, |zone, delay_ms| { zone.delay_ms = delay_ms; })}
+                                        />
+                                        <label>{"Low"}</label>
+                                        <input type="number" min={-24} max={6} step={0.5}
+                                            value={zone.eq.gain_lo.0}
+                                            onchange={self.zone_callback(id, zone, |zone, db| { zone.eq.gain_lo = Decibel(db); })}
+                                        />
+                                        <label>{"Mid"}</label>
+                                        <input type="number" min={-24} max={6} step={0.5}
+                                            value={zone.eq.gain_mid.0}
+                                            onchange={self.zone_callback(id, zone, |zone, db| { zone.eq.gain_mid = Decibel(db); })}
+                                        />
+                                        <label>{"High"}</label>
+                                        <input type="number" min={-24} max={6} step={0.5}
+                                            value={zone.eq.gain_hi.0}
+                                            onchange={self.zone_callback(id, zone, |zone, db| { zone.eq.gain_hi = Decibel(db); })}
+                                        />
+                                    </div>
+                                </td>
+                                <td>
+                                    <button onclick={self.link.callback(move |_| SidebarMsg::RenameZone(id))}>
+                                        {"Rename"}
+                                    </button>
+                                    <button onclick={self.link.callback(move |_| SidebarMsg::DeleteZone(id))}>
+                                        {"Delete"}
+                                    </button>
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                </table>
+                <div class="zones-form">
+                    <input type="text"
+                        placeholder="Zone name"
+                        value={&self.zone_name}
+                        onchange={self.link.callback(|ev| SidebarMsg::ZoneName(change_value(ev)))}
+                    />
+                    <button onclick={self.link.callback(|_| SidebarMsg::CreateZone)}>
+                        {"New zone"}
+                    </button>
+                </div>
+            </div>
+        }
+    }
+
+    // updates one number of a zone as its input changes, leaving the zone
+    // as it was if the input doesn't parse
+    fn zone_callback(&self, id: ZoneId, zone: &Zone, f: impl Fn(&mut Zone, f64) + 'static) -> Callback<ChangeData> {
+        let zone = zone.clone();
+
+        self.link.callback(move |ev| {
+            let mut zone = zone.clone();
+
+            if let Ok(value) = change_value(ev).parse() {
+                f(&mut zone, value);
+            }
+
+            SidebarMsg::UpdateZone(id, zone)
+        })
+    }
+
     fn view_perf_info(&self) -> Html {
         if let Some(perf_info) = &self.perf_info {
 
@@ -95,7 +1541,10 @@ impl Sidebar {
             let total_tick_percent = (total_tick_time as f64 / tick_budget) * 100.0;
 
             let mut sorted_accounts = perf_info.accounts.clone();
-            sorted_accounts.sort_by(|(_, a), (_, b)| b.last.cmp(&a.last));
+            sorted_accounts.sort_by(|(_, a), (_, b)| b.mean.cmp(&a.mean));
+
+            let overloaded_module = perf_info.overload.as_ref()
+                .and_then(|overload| overload.module);
 
             html! {
                 <div class="perf-info">
@@ -106,12 +1555,41 @@ impl Sidebar {
                     <div class="perf-info-tick-util">
                         {format!("{:2.1}%", total_tick_percent)}
                     </div>
+                    { match &perf_info.overload {
+                        Some(overload) => {
+                            let culprit = overload.module
+                                .map(|id| self.module_name(id))
+                                .unwrap_or_else(|| "Engine".to_owned());
+
+                            html! {
+                                <div class="perf-info-overload">
+                                    {format!("Overload: {:2.1}% of budget, slowest: {}",
+                                        (overload.tick_time.0 as f64 / tick_budget) * 100.0,
+                                        culprit)}
+                                </div>
+                            }
+                        }
+                        None => html! {},
+                    } }
                     <table class="perf-info-accounts-table">
+                        <tr>
+                            <th></th>
+                            <th class="perf-info-metric">{"Mean"}</th>
+                            <th class="perf-info-metric">{"Max"}</th>
+                        </tr>
                         { for sorted_accounts.iter().map(|(account, metric)| {
-                            let percent = (metric.last.0 as f64 / tick_budget) * 100.0;
+                            let mean_percent = (metric.mean.0 as f64 / tick_budget) * 100.0;
+                            let max_percent = (metric.max.0 as f64 / tick_budget) * 100.0;
+
+                            let row_class = match account {
+                                PerformanceAccount::Module(id) if Some(*id) == overloaded_module => {
+                                    "perf-info-overloaded"
+                                }
+                                _ => "",
+                            };
 
                             html! {
-                                <tr>
+                                <tr class={row_class}>
                                     { match account {
                                         PerformanceAccount::Engine => {
                                             html! { <td class="perf-info-account perf-info-account-engine">{"Engine"}</td> }
@@ -121,7 +1599,8 @@ impl Sidebar {
                                             html! { <td class="perf-info-account perf-info-account-module">{name}</td> }
                                         }
                                     } }
-                                    <td class="perf-info-metric">{format!("{:2.1}%", percent)}</td>
+                                    <td class="perf-info-metric">{format!("{:2.1}%", mean_percent)}</td>
+                                    <td class="perf-info-metric">{format!("{:2.1}%", max_percent)}</td>
                                 </tr>
                             }
                         }) }
@@ -133,3 +1612,87 @@ impl Sidebar {
         }
     }
 }
+
+fn change_value(ev: ChangeData) -> String {
+    match ev {
+        ChangeData::Value(value) => value,
+        _ => unreachable!(),
+    }
+}
+
+fn describe_input(index: usize, input: &InputIntrospection) -> String {
+    let mut description = format!("in {} {:?}", index + 1, input.line_type);
+
+    match input.source {
+        Some((output, line_type)) => {
+            description += &format!(" \u{2190} #{} out {} {:?}", output.module_id().0, output.index() + 1, line_type);
+        }
+        None => {
+            description += " disconnected";
+        }
+    }
+
+    if input.converted {
+        description += ", converted";
+    }
+
+    if input.delay > 0 {
+        description += &format!(", delayed {}", input.delay);
+    }
+
+    description
+}
+
+#[derive(PartialEq, Clone)]
+struct DisplayModule(ModuleId, String);
+
+impl Display for DisplayModule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} #{}", self.1, (self.0).0)
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct DisplayOutput(OutputId, String);
+
+impl Display for DisplayOutput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.1)
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct DisplaySnapshot(SnapshotId, String);
+
+impl Display for DisplaySnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.1)
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct DisplayView(Option<ViewId>, String);
+
+impl Display for DisplayView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.1)
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct DisplaySource(Option<OutputId>, String);
+
+impl Display for DisplaySource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.1)
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct DisplayClock(ClockSource, String);
+
+impl Display for DisplayClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.1)
+    }
+}
@@ -76,6 +76,27 @@ pub fn websocket_origin() -> String {
     format!("{}//{}", proto, host)
 }
 
+/// The page's query string, eg. `?guest=...`, passed along to the session
+pub fn location_query() -> String {
+    web_sys::window().unwrap().location().search().unwrap_or_default()
+}
+
+/// Name of the view picked in the page's url, as in `#view=Audio%20desk`
+pub fn location_view() -> Option<String> {
+    let hash = web_sys::window().unwrap().location().hash().ok()?;
+    let name = hash.strip_prefix("#view=")?;
+    js_sys::decode_uri_component(name).ok().map(String::from)
+}
+
+pub fn set_location_view(name: Option<&str>) {
+    let hash = match name {
+        Some(name) => format!("view={}", String::from(js_sys::encode_uri_component(name))),
+        None => String::new(),
+    };
+
+    let _ = web_sys::window().unwrap().location().set_hash(&hash);
+}
+
 fn html_element_parent(mut element: Element) -> Option<HtmlElement> {
     loop {
         match element.dyn_ref::<HtmlElement>() {
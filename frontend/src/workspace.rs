@@ -1,38 +1,75 @@
 use std::collections::{BTreeMap, HashSet};
 use std::mem;
+use std::time::Duration;
 
 use wasm_bindgen::JsCast;
 use web_sys::{CanvasRenderingContext2d, HtmlElement, HtmlCanvasElement, MouseEvent, Element};
-use yew::{html, Callback, Component, ComponentLink, Html, ShouldRender, Properties, NodeRef};
+use yew::{html, Callback, Component, ComponentLink, Html, ShouldRender, Properties, NodeRef, InputData};
+use yew::services::timeout::{TimeoutService, TimeoutTask};
 
-use mixlab_protocol::{ModuleId, TerminalId, InputId, OutputId, ModuleParams, OscillatorParams, Waveform, WorkspaceOp, WindowGeometry, Coords, Indication, OutputDeviceParams, FmSineParams, AmplifierParams, GateState, LineType, EnvelopeParams, MixerParams, StreamInputParams, EqThreeParams, StreamOutputParams, VideoMixerParams, MediaSourceParams};
+use mixlab_protocol::{ModuleId, TerminalId, InputId, OutputId, ModuleParams, OscillatorParams, Waveform, WorkspaceOp, WindowGeometry, Coords, Indication, OutputDeviceParams, InputDeviceParams, FmSineParams, AmplifierParams, GateState, LineType, EnvelopeParams, MixerParams, StreamInputParams, EqThreeParams, StreamOutputParams, VideoMixerParams, MediaSourceParams, VcaGroupParams, BusParams, BeatDetectorParams, BroadcastDelayParams, BrowserSourceParams, LfoParams, LoudnessLoggerParams, EnvelopeFollowerParams, ArtNetOutputParams, HueLightParams, IdentInserterParams, ImageSourceParams, MultiviewerParams, NullTestParams, PreviewOverlayParams, RecorderParams, ReplayBufferParams, ZoneOutputParams, ZoneId, MorphSlot, Batch, Viewport, ViewId, Diagnostic, Severity};
 
-use crate::component::midi_target::MidiUiMode;
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
 use crate::module::amplifier::Amplifier;
+use crate::module::artnet_output::ArtNetOutput;
+use crate::module::beat_detector::BeatDetector;
+use crate::module::broadcast_delay::BroadcastDelay;
+use crate::module::browser_source::BrowserSource;
+use crate::module::bus::Bus;
 use crate::module::envelope::Envelope;
+use crate::module::envelope_follower::EnvelopeFollower;
 use crate::module::eq_three::EqThree;
 use crate::module::fm_sine::FmSine;
+use crate::module::hue_light::HueLight;
+use crate::module::ident_inserter::IdentInserter;
+use crate::module::image_source::ImageSource;
+use crate::module::lfo::Lfo;
+use crate::module::loudness_logger::LoudnessLogger;
 use crate::module::media_source::MediaSource;
 use crate::module::mixer::Mixer;
 use crate::module::monitor::Monitor;
+use crate::module::multiviewer::Multiviewer;
+use crate::module::null_test::NullTest;
 use crate::module::oscillator::Oscillator;
 use crate::module::output_device::OutputDevice;
+use crate::module::input_device::InputDevice;
 use crate::module::plotter::Plotter;
+use crate::module::plugin::Plugin;
+use crate::module::preview_overlay::PreviewOverlay;
+use crate::module::recorder::Recorder;
+use crate::module::replay_buffer::ReplayBuffer;
 use crate::module::stream_input::StreamInput;
 use crate::module::stream_output::StreamOutput;
 use crate::module::trigger::Trigger;
+use crate::module::vca_group::VcaGroup;
+use crate::param_panel::ParamPanel;
 use crate::module::video_mixer::VideoMixer;
+use crate::module::zone_output::ZoneOutput;
 use crate::util::{self, stop_propagation, prevent_default, Sequence};
 use crate::session::{WorkspaceStateRef, WorkspaceState, SessionRef};
 use crate::{App, AppMsg};
 
+// scrolling fires a stream of events, the viewport is only sent once it
+// has been still for this long:
+const SCROLL_DEBOUNCE: Duration = Duration::from_millis(250);
+
+const ZOOM_STEP: f64 = 1.25;
+const MIN_ZOOM: f64 = 0.25;
+const MAX_ZOOM: f64 = 2.0;
+
 pub struct Workspace {
     link: ComponentLink<Self>,
     props: WorkspaceProps,
     workspace_ref: NodeRef,
+    canvas_ref: NodeRef,
     gen_z_index: Sequence,
     mouse: MouseMode,
     window_refs: BTreeMap<ModuleId, WindowRef>,
+    // set while a local scroll is waiting to be sent:
+    scroll_task: Option<TimeoutTask>,
+    // the scroll position last applied from the shared viewport, so that
+    // the scroll event it causes isn't sent straight back:
+    applied_scroll: Option<Coords>,
 }
 
 #[derive(Properties, Clone)]
@@ -52,6 +89,8 @@ pub enum MouseMode {
 pub struct Drag {
     module: ModuleId,
     origin: Coords,
+    // window position when the drag started:
+    start: Coords,
 }
 
 #[derive(Debug)]
@@ -66,6 +105,9 @@ pub enum WorkspaceMsg {
     DeleteWindow(ModuleId),
     UpdateModuleParams(ModuleId, ModuleParams),
     CreateModule(ModuleParams, Coords),
+    Scroll,
+    SendViewport,
+    SetZoom(f64),
 }
 
 impl Component for Workspace {
@@ -77,9 +119,12 @@ impl Component for Workspace {
             link,
             props,
             workspace_ref: NodeRef::default(),
+            canvas_ref: NodeRef::default(),
             gen_z_index: Sequence::new(),
             mouse: MouseMode::Normal,
             window_refs: BTreeMap::new(),
+            scroll_task: None,
+            applied_scroll: None,
         };
 
         workspace.update_state();
@@ -101,13 +146,15 @@ impl Component for Workspace {
             WorkspaceMsg::DragStart(module, ev) => {
                 let mut state = self.props.state.borrow_mut();
 
-                if let Some(geom) = state.geometry.get_mut(&module) {
+                if let Some(mut geom) = state.window_geometry(module).cloned() {
                     self.mouse = MouseMode::Drag(Drag {
                         module,
                         origin: Coords { x: ev.page_x(), y: ev.page_y() },
+                        start: geom.position,
                     });
 
                     geom.z_index = self.gen_z_index.next().get();
+                    state.set_window_geometry(module, geom);
 
                     true
                 } else {
@@ -145,15 +192,15 @@ impl Component for Workspace {
             WorkspaceMsg::MouseUp(ev) => {
                 match self.mouse {
                     MouseMode::Normal => false,
-                    MouseMode::Drag(ref mut drag) => {
+                    MouseMode::Drag(ref drag) => {
                         let mut state = self.props.state.borrow_mut();
 
                         let should_render = drag_event(&mut state, &self.window_refs, drag, ev);
 
-                        if let Some(geometry) = state.geometry.get(&drag.module) {
+                        if let Some(geometry) = state.window_geometry(drag.module) {
                             self.props.app.send_message(
                                 AppMsg::ClientUpdate(
-                                    WorkspaceOp::UpdateWindowGeometry(drag.module, geometry.clone())));
+                                    state.window_geometry_op(drag.module, geometry.clone())));
                         }
 
                         self.mouse = MouseMode::Normal;
@@ -167,13 +214,15 @@ impl Component for Workspace {
             WorkspaceMsg::MouseMove(ev) => {
                 match &mut self.mouse {
                     MouseMode::Normal | MouseMode::ContextMenu(_) => false,
-                    MouseMode::Drag(ref mut drag) => {
+                    MouseMode::Drag(drag) => {
                         drag_event(&mut self.props.state.borrow_mut(), &self.window_refs, drag, ev)
                     }
                     MouseMode::Connect(_, _, ref mut coords) => {
-                        let workspace = self.workspace_ref.cast::<HtmlElement>().unwrap();
+                        // offsets are measured before the zoom transform, so
+                        // these come out in workspace coordinates:
+                        let canvas = self.canvas_ref.cast::<HtmlElement>().unwrap();
                         let target = ev.target().and_then(|target| target.dyn_into::<Element>().ok()).unwrap();
-                        let target_offset_coords = util::offset_coords_in(workspace, target).expect("offset_coords_in");
+                        let target_offset_coords = util::offset_coords_in(canvas, target).expect("offset_coords_in");
                         *coords = Some(target_offset_coords.add(Coords {
                             x: ev.offset_x(),
                             y: ev.offset_y(),
@@ -194,7 +243,7 @@ impl Component for Workspace {
                             (TerminalId::Output(output), TerminalId::Input(input)) => {
                                 let mut state = self.props.state.borrow_mut();
 
-                                if terminal_ref.line_type == other_terminal_ref.line_type {
+                                if terminal_ref.line_type.can_connect(other_terminal_ref.line_type) {
                                     state.connections.insert(input, output);
 
                                     self.mouse = MouseMode::Normal;
@@ -231,14 +280,13 @@ impl Component for Workspace {
                                 WorkspaceOp::DeleteConnection(input)));
                     }
                     TerminalId::Output(output) => {
-                        let mut msgs = Vec::new();
+                        let mut ops = Vec::new();
 
                         let mut state = self.props.state.borrow_mut();
 
                         for (in_, out_) in &state.connections {
                             if *out_ == output {
-                                msgs.push(AppMsg::ClientUpdate(
-                                    WorkspaceOp::DeleteConnection(*in_)));
+                                ops.push(WorkspaceOp::DeleteConnection(*in_));
                             }
                         }
 
@@ -246,7 +294,12 @@ impl Component for Workspace {
                         // but it's good enough for now
                         state.connections.retain(|_, out| output != *out);
 
-                        self.props.app.send_message_batch(msgs);
+                        // all of the output's connections go at once
+                        if !ops.is_empty() {
+                            self.props.app.send_message(
+                                AppMsg::ClientUpdate(
+                                    WorkspaceOp::Batch(Batch { placeholders: Vec::new(), ops })));
+                        }
                     }
                 }
                 true
@@ -300,16 +353,77 @@ impl Component for Workspace {
 
                 true
             }
+            WorkspaceMsg::Scroll => {
+                let scroll = self.scroll_position();
+
+                if scroll.is_some() && scroll == self.applied_scroll.take() {
+                    return false;
+                }
+
+                self.scroll_task = Some(TimeoutService::spawn(SCROLL_DEBOUNCE,
+                    self.link.callback(|()| WorkspaceMsg::SendViewport)));
+
+                false
+            }
+            WorkspaceMsg::SendViewport => {
+                self.scroll_task = None;
+
+                let mut state = self.props.state.borrow_mut();
+
+                if let Some(scroll) = self.scroll_position() {
+                    let zoom = state.viewport().zoom;
+
+                    let viewport = Viewport {
+                        scroll: scroll.scale(1.0 / zoom),
+                        zoom,
+                    };
+
+                    if viewport != *state.viewport() {
+                        state.set_viewport(viewport.clone());
+
+                        self.props.app.send_message(
+                            AppMsg::ClientUpdate(
+                                state.viewport_op(viewport)));
+                    }
+                }
+
+                false
+            }
+            WorkspaceMsg::SetZoom(zoom) => {
+                // a pending scroll is sent along with the zoom instead:
+                self.scroll_task = None;
+
+                let mut state = self.props.state.borrow_mut();
+
+                let current = state.viewport().clone();
+
+                let scroll = self.scroll_position()
+                    .map(|scroll| scroll.scale(1.0 / current.zoom))
+                    .unwrap_or(current.scroll);
+
+                let viewport = Viewport {
+                    scroll,
+                    zoom: zoom.max(MIN_ZOOM).min(MAX_ZOOM),
+                };
+
+                state.set_viewport(viewport.clone());
+
+                self.props.app.send_message(
+                    AppMsg::ClientUpdate(
+                        state.viewport_op(viewport)));
+
+                true
+            }
         };
 
-        fn drag_event(state: &mut WorkspaceState, window_refs: &BTreeMap<ModuleId, WindowRef>, drag: &mut Drag, ev: MouseEvent) -> ShouldRender {
+        fn drag_event(state: &mut WorkspaceState, window_refs: &BTreeMap<ModuleId, WindowRef>, drag: &Drag, ev: MouseEvent) -> ShouldRender {
             let mouse_pos = Coords { x: ev.page_x(), y: ev.page_y() };
 
-            let delta = mouse_pos.sub(drag.origin);
-            drag.origin = mouse_pos;
+            // the mouse moves in screen pixels, windows in workspace ones:
+            let delta = mouse_pos.sub(drag.origin).scale(1.0 / state.viewport().zoom);
 
-            if let Some(geom) = state.geometry.get_mut(&drag.module) {
-                geom.position = geom.position.add(delta);
+            if let Some(mut geom) = state.window_geometry(drag.module).cloned() {
+                geom.position = drag.start.add(delta);
 
                 let el = window_refs.get(&drag.module)
                     .and_then(|refs| refs.module.cast::<HtmlElement>());
@@ -320,6 +434,8 @@ impl Component for Workspace {
                     let _ = style.set_property("top", &format!("{}px", geom.position.y));
                 }
 
+                state.set_window_geometry(drag.module, geom);
+
                 true
             } else {
                 false
@@ -349,44 +465,74 @@ impl Component for Workspace {
             }
         }
 
+        let zoom = self.props.state.borrow().viewport().zoom;
+
+        // the canvas is scaled down to fit the workspace, so it's made larger
+        // by the same factor to keep filling it:
+        let canvas_style = format!("transform:scale({}); width:{}%; height:{}%;",
+            zoom, 100.0 / zoom, 100.0 / zoom);
+
         html! {
             <div class="workspace"
                 ref={self.workspace_ref.clone()}
                 onmousemove={self.link.callback(WorkspaceMsg::MouseMove)}
+                onscroll={self.link.callback(|_| WorkspaceMsg::Scroll)}
                 oncontextmenu={prevent_default()}
             >
-                <div class="workspace-event-target"
-                    onmouseup={self.link.callback(WorkspaceMsg::MouseUp)}
-                    onmousedown={self.link.callback(WorkspaceMsg::MouseDown)}
-                />
-
-                { for self.window_refs.iter().map(|(id, refs)| {
-                    let state = self.props.state.borrow();
-                    let module = state.modules.get(id);
-                    let geometry = state.geometry.get(id);
-                    let workspace = self.link.clone();
-                    let indication = state.indications.get(id);
-
-                    if let (Some(module), Some(geometry)) = (module, geometry) {
-                        let name = format!("{:?}", module).chars().take_while(|c| c.is_alphanumeric()).collect::<String>();
-                        html! { <Window
-                            id={id}
-                            module={module}
-                            refs={refs}
-                            name={name}
-                            workspace={workspace}
-                            geometry={geometry}
-                            indication={indication.cloned()}
-                            session={self.props.session.clone()}
-                        /> }
-                    } else {
-                        html! {}
-                    }
-                }) }
+                <div class="workspace-zoom">
+                    <button onclick={self.link.callback(move |_| WorkspaceMsg::SetZoom(zoom / ZOOM_STEP))}>
+                        {"−"}
+                    </button>
+                    <button onclick={self.link.callback(|_| WorkspaceMsg::SetZoom(1.0))}>
+                        {format!("{:.0}%", zoom * 100.0)}
+                    </button>
+                    <button onclick={self.link.callback(move |_| WorkspaceMsg::SetZoom(zoom * ZOOM_STEP))}>
+                        {"+"}
+                    </button>
+                </div>
 
-                <Connections connections={connections} />
+                <div class="workspace-canvas"
+                    ref={self.canvas_ref.clone()}
+                    style={canvas_style}
+                >
+                    <div class="workspace-event-target"
+                        onmouseup={self.link.callback(WorkspaceMsg::MouseUp)}
+                        onmousedown={self.link.callback(WorkspaceMsg::MouseDown)}
+                    />
+
+                    { for self.window_refs.iter().map(|(id, refs)| {
+                        let state = self.props.state.borrow();
+                        let module = state.modules.get(id);
+                        let geometry = state.window_geometry(*id);
+                        let workspace = self.link.clone();
+                        let indication = state.indications.get(id);
+                        let diagnostics = state.diagnostics.get(id).cloned().unwrap_or_default();
+                        let pinned = state.view().map(|view| view.pinned.contains(id)).unwrap_or(false);
+
+                        if let (Some(module), Some(geometry)) = (module, geometry) {
+                            let name = format!("{:?}", module).chars().take_while(|c| c.is_alphanumeric()).collect::<String>();
+                            html! { <Window
+                                id={id}
+                                module={module}
+                                refs={refs}
+                                name={name}
+                                workspace={workspace}
+                                geometry={geometry}
+                                indication={indication.cloned()}
+                                diagnostics={diagnostics}
+                                view={state.current_view}
+                                pinned={pinned}
+                                session={self.props.session.clone()}
+                            /> }
+                        } else {
+                            html! {}
+                        }
+                    }) }
 
-                {self.view_context_menu()}
+                    <Connections connections={connections} />
+
+                    {self.view_context_menu()}
+                </div>
             </div>
         }
     }
@@ -397,23 +543,53 @@ impl Component for Workspace {
         if first_render {
             self.link.send_message(WorkspaceMsg::Rerender);
         }
+
+        // follow the shared scroll position, unless this client is in the
+        // middle of scrolling itself:
+        if self.scroll_task.is_none() {
+            let viewport = self.props.state.borrow().viewport().clone();
+            let scroll = viewport.scroll.scale(viewport.zoom);
+
+            if let Some(workspace) = self.workspace_ref.cast::<Element>() {
+                if self.scroll_position() != Some(scroll) {
+                    workspace.set_scroll_left(scroll.x);
+                    workspace.set_scroll_top(scroll.y);
+                    self.applied_scroll = self.scroll_position();
+                }
+            }
+        }
     }
 }
 
 impl Workspace {
+    fn scroll_position(&self) -> Option<Coords> {
+        let workspace = self.workspace_ref.cast::<Element>()?;
+        Some(Coords { x: workspace.scroll_left(), y: workspace.scroll_top() })
+    }
+
     fn update_state(&mut self) {
         let mut deleted_windows = self.window_refs.keys().copied().collect::<HashSet<_>>();
 
         let state = self.props.state.borrow();
 
         for id in state.modules.keys() {
-            if deleted_windows.remove(id) {
+            let inputs = state.inputs.get(id);
+            let outputs = state.outputs.get(id);
+
+            let unchanged = match (self.window_refs.get(id), inputs, outputs) {
+                // a restored workspace can reuse module ids for different
+                // modules, so check terminals still line up:
+                (Some(refs), Some(inputs), Some(outputs)) =>
+                    terminals_match(&refs.inputs, inputs) && terminals_match(&refs.outputs, outputs),
+                _ => false,
+            };
+
+            deleted_windows.remove(id);
+
+            if unchanged {
                 // cool, nothing changes with this module
             } else {
                 // this module was not present before, create a window ref for it
-                let inputs = state.inputs.get(id);
-                let outputs = state.outputs.get(id);
-
                 if let (Some(inputs), Some(outputs)) = (inputs, outputs) {
                     let refs = WindowRef {
                         module: NodeRef::default(),
@@ -441,11 +617,16 @@ impl Workspace {
         for deleted_window in deleted_windows {
             self.window_refs.remove(&deleted_window);
         }
+
+        fn terminals_match(refs: &[TerminalRef], terminals: &[mixlab_protocol::Terminal]) -> bool {
+            refs.len() == terminals.len() &&
+                refs.iter().zip(terminals).all(|(r, terminal)| r.line_type == terminal.line_type())
+        }
     }
 
     fn screen_coords_for_terminal(&self, terminal_id: TerminalId) -> Option<Coords> {
         let state = self.props.state.borrow();
-        let geometry = state.geometry.get(&terminal_id.module_id())?;
+        let geometry = state.window_geometry(terminal_id.module_id())?;
         let refs = self.window_refs.get(&terminal_id.module_id())?;
 
         let terminal_ref = match terminal_id {
@@ -470,7 +651,9 @@ impl Workspace {
             ("Mixer (2 channel)", ModuleParams::Mixer(MixerParams::with_channels(2))),
             ("Mixer (4 channel)", ModuleParams::Mixer(MixerParams::with_channels(4))),
             ("Mixer (8 channel)", ModuleParams::Mixer(MixerParams::with_channels(8))),
-            ("Output Device", ModuleParams::OutputDevice(OutputDeviceParams { device: None, left: None, right: None })),
+            ("Bus (4 input)", ModuleParams::Bus(BusParams::with_inputs(4))),
+            ("Input Device", ModuleParams::InputDevice(InputDeviceParams::default())),
+            ("Output Device", ModuleParams::OutputDevice(OutputDeviceParams { device: None, left: None, right: None, varispeed: false })),
             ("Plotter", ModuleParams::Plotter(())),
             ("FM Sine", ModuleParams::FmSine(FmSineParams { freq_lo: 90.0, freq_hi: 110.0 })),
             ("Amplifier", ModuleParams::Amplifier(AmplifierParams { amplitude: 1.0, mod_depth: 0.5 })),
@@ -480,21 +663,45 @@ impl Workspace {
             ("Stereo Splitter", ModuleParams::StereoSplitter(())),
             ("Stream Input", ModuleParams::StreamInput(StreamInputParams::default())),
             ("Stream Output", ModuleParams::StreamOutput(StreamOutputParams::default())),
+            ("Recorder", ModuleParams::Recorder(RecorderParams::default())),
+            ("Replay Buffer", ModuleParams::ReplayBuffer(ReplayBufferParams::default())),
+            ("Broadcast Delay", ModuleParams::BroadcastDelay(BroadcastDelayParams::default())),
+            ("Ident Inserter", ModuleParams::IdentInserter(IdentInserterParams::default())),
+            ("Loudness Logger", ModuleParams::LoudnessLogger(LoudnessLoggerParams::default())),
             ("EQ Three", ModuleParams::EqThree(EqThreeParams::default())),
             ("Monitor", ModuleParams::Monitor(())),
             ("Video Mixer", ModuleParams::VideoMixer(VideoMixerParams::default())),
+            ("Multiviewer", ModuleParams::Multiviewer(MultiviewerParams::default())),
+            ("Preview Overlay", ModuleParams::PreviewOverlay(PreviewOverlayParams::default())),
             ("Media Source", ModuleParams::MediaSource(MediaSourceParams::default())),
+            ("Image Source", ModuleParams::ImageSource(ImageSourceParams::default())),
+            ("Browser Source", ModuleParams::BrowserSource(BrowserSourceParams::default())),
+            ("VCA Group", ModuleParams::VcaGroup(VcaGroupParams::default())),
+            ("Beat Detector", ModuleParams::BeatDetector(BeatDetectorParams::default())),
+            ("Null Test", ModuleParams::NullTest(NullTestParams::default())),
+            ("LFO", ModuleParams::Lfo(LfoParams::default())),
+            ("Envelope Follower", ModuleParams::EnvelopeFollower(EnvelopeFollowerParams::default())),
+            ("Art-Net Output (8 channel)", ModuleParams::ArtNetOutput(ArtNetOutputParams::with_channels(8))),
+            ("Hue Light", ModuleParams::HueLight(HueLightParams::default())),
+            ("Zone Output", ModuleParams::ZoneOutput(ZoneOutputParams::default())),
         ];
 
+        // then any the server's build registers beyond those:
+        let plugins = self.props.state.borrow().plugins.iter()
+            .map(|plugin| (plugin.label.clone(), ModuleParams::Plugin(plugin.default_params.clone())))
+            .collect::<Vec<_>>();
+
+        let items = items.iter()
+            .map(|(label, params)| (label.to_string(), params.clone()))
+            .chain(plugins);
+
         html! {
             <div class="context-menu"
                 style={format!("left:{}px; top:{}px;", coords.x, coords.y)}
                 onmousedown={stop_propagation()}
             >
                 <div class="context-menu-heading">{"Add module"}</div>
-                { for items.iter().map(|(label, params)| {
-                    let params = params.clone();
-
+                { for items.map(|(label, params)| {
                     html! {
                         <div class="context-menu-item"
                             onmousedown={self.link.callback(move |_|
@@ -513,6 +720,8 @@ pub struct Window {
     link: ComponentLink<Self>,
     props: WindowProps,
     midi_mode: MidiUiMode,
+    show_morph: bool,
+    show_params: bool,
 }
 
 pub enum WindowMsg {
@@ -521,6 +730,12 @@ pub enum WindowMsg {
     Delete,
     UpdateParams(ModuleParams),
     SetMidiMode(MidiUiMode),
+    ToggleMorph,
+    ToggleParams,
+    TogglePin,
+    StoreMorph(MorphSlot),
+    SetMorph(f64),
+    ClearMorph,
 }
 
 #[derive(Properties, Clone, Debug)]
@@ -532,6 +747,10 @@ pub struct WindowProps {
     pub workspace: ComponentLink<Workspace>,
     pub refs: WindowRef,
     pub indication: Option<Indication>,
+    pub diagnostics: Vec<Diagnostic>,
+    // meters can only be pinned to a named view:
+    pub view: Option<ViewId>,
+    pub pinned: bool,
     pub session: SessionRef,
 }
 
@@ -570,10 +789,20 @@ impl Component for Window {
     type Properties = WindowProps;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let show_morph = props.session.workspace()
+            .map(|workspace| workspace.borrow().morphs.contains_key(&props.id))
+            .unwrap_or(false);
+
+        let show_params = props.session.workspace()
+            .map(|workspace| workspace.borrow().favorite_params.contains_key(&props.id))
+            .unwrap_or(false);
+
         Window {
             link,
             props,
             midi_mode: MidiUiMode::Normal,
+            show_morph,
+            show_params,
         }
     }
 
@@ -586,6 +815,15 @@ impl Component for Window {
                 false
             }
             WindowMsg::TerminalMouseDown(ev, terminal_id, terminal_ref) => {
+                if let TerminalId::Output(output_id) = terminal_id {
+                    if ev.alt_key() && terminal_ref.line_type == LineType::Video {
+                        self.props.session.update_workspace(
+                            WorkspaceOp::CaptureFrame(output_id));
+
+                        return false;
+                    }
+                }
+
                 let msg =
                     if (ev.buttons() & 2) != 0 {
                         // right click
@@ -614,6 +852,36 @@ impl Component for Window {
                 self.midi_mode = new_midi_mode;
                 true
             }
+            WindowMsg::ToggleMorph => {
+                self.show_morph = !self.show_morph;
+                true
+            }
+            WindowMsg::ToggleParams => {
+                self.show_params = !self.show_params;
+                true
+            }
+            WindowMsg::TogglePin => {
+                if let Some(view) = self.props.view {
+                    self.props.session.update_workspace(
+                        WorkspaceOp::PinMeter(view, self.props.id, !self.props.pinned));
+                }
+                false
+            }
+            WindowMsg::StoreMorph(slot) => {
+                self.props.session.update_workspace(
+                    WorkspaceOp::StoreMorphState(self.props.id, slot));
+                false
+            }
+            WindowMsg::SetMorph(position) => {
+                self.props.session.update_workspace(
+                    WorkspaceOp::SetMorph(self.props.id, position));
+                false
+            }
+            WindowMsg::ClearMorph => {
+                self.props.session.update_workspace(
+                    WorkspaceOp::ClearMorph(self.props.id));
+                false
+            }
         }
     }
 
@@ -642,11 +910,16 @@ impl Component for Window {
                     <div class="module-window-title-label">
                         {&self.props.name}
                     </div>
+                    {self.view_diagnostics_badge()}
                     {self.view_custom_title_buttons()}
+                    {self.view_pin_title_button()}
+                    {self.view_morph_title_button()}
+                    {self.view_params_title_button()}
                     <div class="module-window-title-button module-window-title-delete" onmousedown={self.link.callback(|_| WindowMsg::Delete)}>
                         {"×"}
                     </div>
                 </div>
+                {self.view_diagnostics()}
                 <div class="module-window-content">
                     <div class="module-window-inputs">
                         {self.view_inputs()}
@@ -658,16 +931,187 @@ impl Component for Window {
                         {self.view_outputs()}
                     </div>
                 </div>
+                {self.view_morph()}
+                {self.view_param_panel()}
             </div>
         }
     }
 }
 
 impl Window {
+    fn view_diagnostics_badge(&self) -> Html {
+        // diagnostics come most severe first:
+        let severity = match self.props.diagnostics.first() {
+            Some(diagnostic) => diagnostic.severity,
+            None => { return html! {}; }
+        };
+
+        let title = self.props.diagnostics.iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        html! {
+            <div class={format!("module-window-title-badge {}", severity_class(severity))} title={title}>
+                {self.props.diagnostics.len()}
+            </div>
+        }
+    }
+
+    fn view_diagnostics(&self) -> Html {
+        if self.props.diagnostics.is_empty() {
+            return html! {};
+        }
+
+        html! {
+            <div class="module-window-diagnostics">
+                { for self.props.diagnostics.iter().map(|diagnostic| html! {
+                    <div class={format!("module-window-diagnostic {}", severity_class(diagnostic.severity))}>
+                        {&diagnostic.message}
+                    </div>
+                }) }
+            </div>
+        }
+    }
+
+    fn view_pin_title_button(&self) -> Html {
+        if self.props.view.is_none() {
+            return html! {};
+        }
+
+        let class = if self.props.pinned {
+            "module-window-title-button module-window-title-pin-btn module-window-title-pin-btn-active"
+        } else {
+            "module-window-title-button module-window-title-pin-btn"
+        };
+
+        html! {
+            <div class={class} onmousedown={self.link.callback(|_| WindowMsg::TogglePin)}>
+                {"PIN"}
+            </div>
+        }
+    }
+
+    // modules without params (or with only a reset) have nothing to morph
+    // or pick favorites from
+    fn has_params(&self) -> bool {
+        match &self.props.module {
+            ModuleParams::Monitor(()) |
+            ModuleParams::NullTest(_) |
+            ModuleParams::Plotter(()) |
+            ModuleParams::StereoPanner(()) |
+            ModuleParams::StereoSplitter(()) => false,
+            _ => true,
+        }
+    }
+
+    fn view_morph_title_button(&self) -> Html {
+        if !self.has_params() {
+            return html! {};
+        }
+
+        let class = if self.show_morph {
+            "module-window-title-button module-window-title-morph-btn module-window-title-morph-btn-active"
+        } else {
+            "module-window-title-button module-window-title-morph-btn"
+        };
+
+        html! {
+            <div class={class} onmousedown={self.link.callback(|_| WindowMsg::ToggleMorph)}>
+                {"A/B"}
+            </div>
+        }
+    }
+
+    fn view_params_title_button(&self) -> Html {
+        if !self.has_params() {
+            return html! {};
+        }
+
+        let class = if self.show_params {
+            "module-window-title-button module-window-title-params-btn module-window-title-params-btn-active"
+        } else {
+            "module-window-title-button module-window-title-params-btn"
+        };
+
+        html! {
+            <div class={class} onmousedown={self.link.callback(|_| WindowMsg::ToggleParams)}>
+                {"★"}
+            </div>
+        }
+    }
+
+    fn view_param_panel(&self) -> Html {
+        if !self.show_params {
+            return html! {};
+        }
+
+        html! {
+            <ParamPanel
+                id={self.props.id}
+                params={self.props.module.clone()}
+                session={self.props.session.clone()}
+            />
+        }
+    }
+
+    fn view_morph(&self) -> Html {
+        if !self.show_morph {
+            return html! {};
+        }
+
+        let morph = self.props.session.workspace()
+            .and_then(|workspace| workspace.borrow().morphs.get(&self.props.id).cloned())
+            .unwrap_or_default();
+
+        let slot_class = |stored: bool| {
+            if stored { "morph-slot morph-slot-stored" } else { "morph-slot" }
+        };
+
+        html! {
+            <div class="module-window-morph">
+                <button class={slot_class(morph.a.is_some())}
+                    onclick={self.link.callback(|_| WindowMsg::StoreMorph(MorphSlot::A))}
+                >
+                    {"A"}
+                </button>
+                <MidiRangeTarget
+                    ui_mode={self.midi_mode}
+                    onchange={self.link.callback(WindowMsg::SetMorph)}
+                >
+                    <input type="range"
+                        min="0"
+                        max="1"
+                        step="0.001"
+                        disabled={morph.a.is_none() || morph.b.is_none()}
+                        value={morph.position.to_string()}
+                        oninput={self.link.callback(|ev: InputData| {
+                            WindowMsg::SetMorph(ev.value.parse().unwrap_or(0.0))
+                        })}
+                    />
+                </MidiRangeTarget>
+                <button class={slot_class(morph.b.is_some())}
+                    onclick={self.link.callback(|_| WindowMsg::StoreMorph(MorphSlot::B))}
+                >
+                    {"B"}
+                </button>
+                <button class="morph-clear" onclick={self.link.callback(|_| WindowMsg::ClearMorph)}>
+                    {"Clear"}
+                </button>
+            </div>
+        }
+    }
+
     fn view_custom_title_buttons(&self) -> Html {
         match &self.props.module {
+            ModuleParams::ArtNetOutput(..) |
+            ModuleParams::Bus(..) |
+            ModuleParams::EnvelopeFollower(..) |
             ModuleParams::EqThree(..) |
-            ModuleParams::Mixer(..) => {
+            ModuleParams::HueLight(..) |
+            ModuleParams::Lfo(..) |
+            ModuleParams::Mixer(..) |
+            ModuleParams::VcaGroup(..) => {
                 let class = match self.midi_mode {
                     MidiUiMode::Normal =>
                         "module-window-title-button module-window-title-midi-btn",
@@ -739,6 +1183,13 @@ impl Window {
                     unreachable!()
                 }
             }
+            ModuleParams::InputDevice(params) => {
+                if let Some(Indication::InputDevice(indication)) = &self.props.indication {
+                    html! { <InputDevice id={self.props.id} module={self.link.clone()} params={params} indication={indication} session={self.props.session.clone()} /> }
+                } else {
+                    unreachable!()
+                }
+            }
             ModuleParams::Plotter(_) => {
                 if let Some(Indication::Plotter(indication)) = &self.props.indication {
                     html! { <Plotter id={self.props.id} indication={indication} /> }
@@ -759,10 +1210,10 @@ impl Window {
                 html! { <Envelope id={self.props.id} module={self.link.clone()} params={params} /> }
             }
             ModuleParams::Mixer(params) => {
-                html! { <Mixer id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+                html! { <Mixer id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} groups={self.vca_groups()} /> }
             }
             ModuleParams::StreamInput(params) => {
-                html! { <StreamInput id={self.props.id} module={self.link.clone()} params={params} /> }
+                html! { <StreamInput id={self.props.id} module={self.link.clone()} params={params} session={self.props.session.clone()} /> }
             }
             ModuleParams::StreamOutput(params) => {
                 if let Some(Indication::StreamOutput(indication)) = &self.props.indication {
@@ -771,6 +1222,27 @@ impl Window {
                     unreachable!()
                 }
             }
+            ModuleParams::Recorder(params) => {
+                if let Some(Indication::Recorder(indication)) = &self.props.indication {
+                    html! { <Recorder id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::ReplayBuffer(params) => {
+                if let Some(Indication::ReplayBuffer(indication)) = &self.props.indication {
+                    html! { <ReplayBuffer id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::BroadcastDelay(params) => {
+                if let Some(Indication::BroadcastDelay(indication)) = &self.props.indication {
+                    html! { <BroadcastDelay id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
             ModuleParams::EqThree(params) => {
                 html! { <EqThree id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
             }
@@ -782,13 +1254,126 @@ impl Window {
                 }
             }
             ModuleParams::VideoMixer(params) => {
-                html! { <VideoMixer id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+                html! { <VideoMixer id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} session={self.props.session.clone()} /> }
+            }
+            ModuleParams::Multiviewer(params) => {
+                html! { <Multiviewer id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::PreviewOverlay(params) => {
+                html! { <PreviewOverlay id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
             }
             ModuleParams::MediaSource(params) => {
                 html! { <MediaSource id={self.props.id} module={self.link.clone()} params={params} session={self.props.session.clone()} /> }
             }
+            ModuleParams::IdentInserter(params) => {
+                if let Some(Indication::IdentInserter(indication)) = &self.props.indication {
+                    html! { <IdentInserter id={self.props.id} module={self.link.clone()} params={params} indication={indication} session={self.props.session.clone()} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::BrowserSource(params) => {
+                if let Some(Indication::BrowserSource(indication)) = &self.props.indication {
+                    html! { <BrowserSource id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::ImageSource(params) => {
+                if let Some(Indication::ImageSource(indication)) = &self.props.indication {
+                    html! { <ImageSource id={self.props.id} module={self.link.clone()} params={params} indication={indication} session={self.props.session.clone()} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::Bus(params) => {
+                html! { <Bus id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::VcaGroup(params) => {
+                html! { <VcaGroup id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::ArtNetOutput(params) => {
+                if let Some(Indication::ArtNetOutput(indication)) = &self.props.indication {
+                    html! { <ArtNetOutput id={self.props.id} module={self.link.clone()} params={params} indication={indication} midi_mode={self.midi_mode} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::HueLight(params) => {
+                if let Some(Indication::HueLight(indication)) = &self.props.indication {
+                    html! { <HueLight id={self.props.id} module={self.link.clone()} params={params} indication={indication} midi_mode={self.midi_mode} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::Lfo(params) => {
+                html! { <Lfo id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::LoudnessLogger(params) => {
+                if let Some(Indication::LoudnessLogger(indication)) = &self.props.indication {
+                    html! { <LoudnessLogger id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::EnvelopeFollower(params) => {
+                html! { <EnvelopeFollower id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::BeatDetector(params) => {
+                if let Some(Indication::BeatDetector(indication)) = &self.props.indication {
+                    html! { <BeatDetector id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::NullTest(params) => {
+                if let Some(Indication::NullTest(indication)) = &self.props.indication {
+                    html! { <NullTest module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::Plugin(params) => {
+                if let Some(Indication::Plugin(indication)) = &self.props.indication {
+                    html! { <Plugin id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::ZoneOutput(params) => {
+                html! { <ZoneOutput id={self.props.id} module={self.link.clone()} params={params} zones={self.zones()} /> }
+            }
         }
     }
+
+    fn vca_groups(&self) -> Vec<(ModuleId, String)> {
+        let workspace = match self.props.session.workspace() {
+            Some(workspace) => workspace,
+            None => return Vec::new(),
+        };
+
+        let workspace = workspace.borrow();
+
+        workspace.modules.iter()
+            .filter_map(|(id, params)| match params {
+                ModuleParams::VcaGroup(group) => Some((*id, group.name.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn zones(&self) -> Vec<(ZoneId, String)> {
+        let workspace = match self.props.session.workspace() {
+            Some(workspace) => workspace,
+            None => return Vec::new(),
+        };
+
+        let workspace = workspace.borrow();
+
+        workspace.zones.iter()
+            .map(|(id, zone)| (*id, zone.name.clone()))
+            .collect()
+    }
 }
 
 pub struct Terminal {
@@ -834,6 +1419,7 @@ impl Component for Terminal {
                 onmouseover={self.link.callback(|_| true)}
                 onmouseout={self.link.callback(|_| false)}
                 oncontextmenu={prevent_default()}
+                title={self.title()}
             >
                 <div class="terminal-label">
                     {format!("{}", &self.props.terminal.label.as_ref().unwrap_or(&"".to_string()))}
@@ -855,6 +1441,15 @@ impl Component for Terminal {
     }
 }
 
+impl Terminal {
+    fn title(&self) -> &'static str {
+        match (self.props.terminal.terminal_type, self.props.terminal.line_type) {
+            (TerminalType::Output, LineType::Video) => "Alt-click to save a frame to the media library",
+            _ => "",
+        }
+    }
+}
+
 pub struct Connections {
     canvas: NodeRef,
     props: ConnectionsProps,
@@ -946,6 +1541,14 @@ impl Component for Connections {
     }
 }
 
+fn severity_class(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "diagnostic-info",
+        Severity::Warning => "diagnostic-warning",
+        Severity::Error => "diagnostic-error",
+    }
+}
+
 fn plan_line_points(start: Coords, end: Coords) -> Vec<Coords> {
     let mut segments = vec![];
 
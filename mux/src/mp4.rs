@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 use std::ffi::CString;
 
-use bytes::{Bytes, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
 use bytes::buf::BufMutExt;
 use mse_fmp4::aac::{AacProfile, SamplingFrequency, ChannelConfiguration};
 use mse_fmp4::fmp4::{
@@ -40,6 +40,7 @@ pub enum TrackData {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Mp4Params<'a> {
     pub timescale: u32,
+    pub sample_rate: u32,
     pub width: u32,
     pub height: u32,
     pub dcr: Cow<'a, [u8]>,
@@ -66,6 +67,116 @@ impl Mp4Mux {
     }
 }
 
+// room for a hundred or so chapters with short titles, reserved in the init
+// segment of files that may have chapters written into them later:
+const CHAPTER_SPACE: usize = 8 * 1024;
+
+// nero chapter list limits:
+const MAX_CHAPTERS: usize = 255;
+const MAX_CHAPTER_TITLE: usize = 255;
+
+// chapter start times are in units of 100ns:
+const CHAPTER_TIMESCALE: i64 = 10_000_000;
+
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    pub start: MediaTime,
+    pub title: String,
+}
+
+/// Appends a placeholder `udta` box to the `moov` box of an init segment,
+/// returning the new init segment and the offset of the placeholder within it.
+/// Media segments address their data relative to their own `moof` box, so a
+/// file can have its placeholder overwritten by `chapter_box` once it is
+/// complete.
+pub fn reserve_chapter_space(init: &[u8]) -> (Bytes, usize) {
+    let mut pos = 0;
+
+    while pos + 8 <= init.len() {
+        let size = u32::from_be_bytes([init[pos], init[pos + 1], init[pos + 2], init[pos + 3]]) as usize;
+
+        if &init[pos + 4..pos + 8] == b"moov" {
+            let end = pos + size;
+            let mut out = BytesMut::with_capacity(init.len() + CHAPTER_SPACE);
+            out.put_slice(&init[..pos]);
+            out.put_u32((size + CHAPTER_SPACE) as u32);
+            out.put_slice(&init[pos + 4..end]);
+            out.put_slice(&udta_box(&[]));
+            out.put_slice(&init[end..]);
+            return (out.freeze(), end);
+        }
+
+        if size < 8 {
+            break;
+        }
+
+        pos += size;
+    }
+
+    panic!("init segment has no moov box");
+}
+
+/// Encodes chapters as a nero chapter list in a `udta` box the same size as
+/// the placeholder left by `reserve_chapter_space`. Chapters that do not fit
+/// are left out.
+pub fn chapter_box(chapters: &[Chapter]) -> Bytes {
+    udta_box(chapters)
+}
+
+fn udta_box(chapters: &[Chapter]) -> Bytes {
+    // box headers of the udta box and the free box that pads it out:
+    const HEADERS: usize = 16;
+    // fixed part of the chpl box: header, version and flags, reserved, count
+    const CHPL_HEADER: usize = 8 + 4 + 4 + 1;
+
+    let mut chpl = BytesMut::new();
+    let mut count = 0;
+
+    for chapter in chapters.iter().take(MAX_CHAPTERS) {
+        let title = truncate(&chapter.title, MAX_CHAPTER_TITLE);
+
+        if HEADERS + CHPL_HEADER + chpl.len() + 9 + title.len() > CHAPTER_SPACE {
+            break;
+        }
+
+        chpl.put_u64(chapter.start.round_to_base(CHAPTER_TIMESCALE) as u64);
+        chpl.put_u8(title.len() as u8);
+        chpl.put_slice(title.as_bytes());
+        count += 1;
+    }
+
+    let mut udta = BytesMut::with_capacity(CHAPTER_SPACE);
+    udta.put_u32(CHAPTER_SPACE as u32);
+    udta.put_slice(b"udta");
+
+    if count > 0 {
+        udta.put_u32((CHPL_HEADER + chpl.len()) as u32);
+        udta.put_slice(b"chpl");
+        // version 1, no flags:
+        udta.put_u32(0x01000000);
+        udta.put_u32(0);
+        udta.put_u8(count as u8);
+        udta.put_slice(&chpl);
+    }
+
+    let padding = CHAPTER_SPACE - udta.len();
+    udta.put_u32(padding as u32);
+    udta.put_slice(b"free");
+    udta.put_slice(&vec![0; padding - 8]);
+
+    udta.freeze()
+}
+
+fn truncate(s: &str, max_len: usize) -> &str {
+    let mut len = std::cmp::min(s.len(), max_len);
+
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+
+    &s[..len]
+}
+
 fn to_bytes(segment: impl WriteTo) -> Bytes {
     let mut bytes = BytesMut::new();
 
@@ -136,7 +247,7 @@ fn make_init_segment(
                                             esds_box: Mpeg4EsDescriptorBox {
                                                 // TODO set these from ADTS header - or are they always constant?
                                                 profile: AacProfile::Lc,
-                                                frequency: SamplingFrequency::Hz44100,
+                                                frequency: sampling_frequency(params.sample_rate),
                                                 channel_configuration: ChannelConfiguration::TwoChannels,
                                             },
                                         }),
@@ -222,6 +333,15 @@ fn make_init_segment(
     }
 }
 
+fn sampling_frequency(sample_rate: u32) -> SamplingFrequency {
+    match sample_rate {
+        48000 => SamplingFrequency::Hz48000,
+        88200 => SamplingFrequency::Hz88200,
+        96000 => SamplingFrequency::Hz96000,
+        _ => SamplingFrequency::Hz44100,
+    }
+}
+
 fn make_media_segment(
     mux: &mut Mp4Mux,
     duration: MediaDuration,
@@ -17,6 +17,17 @@ pub enum ServerMessage<'a> {
     Sync(ClientSequence),
     Performance(Cow<'a, PerformanceInfo>),
     MediaLibrary(MediaLibrary),
+    Snapshots(Vec<SnapshotInfo>),
+    // answers a search, echoing it back so stale results can be told apart:
+    ParamSearchResults(ParamSearch, Vec<ParamMatch>),
+    Project(ProjectInfo),
+    Backup(BackupStatus),
+    MediaSync(MediaSyncStatus),
+    // what this session may do, sent once on connect:
+    Access(Access),
+    // sent to owner sessions only:
+    GuestTokens(Vec<GuestToken>),
+    Introspection(EngineIntrospection),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -27,6 +38,22 @@ pub struct WorkspaceState {
     pub connections: Vec<(InputId, OutputId)>,
     pub inputs: Vec<(ModuleId, Vec<Terminal>)>,
     pub outputs: Vec<(ModuleId, Vec<Terminal>)>,
+    pub param_links: Vec<(ParamLinkId, ParamLink)>,
+    pub morphs: Vec<(ModuleId, MorphState)>,
+    pub modulations: Vec<(ModulationId, Modulation)>,
+    pub clock_source: ClockSource,
+    pub rehearsal: bool,
+    pub viewport: Viewport,
+    pub views: Vec<(ViewId, View)>,
+    pub bindings: Vec<(String, Binding)>,
+    pub device_links: Vec<(String, String)>,
+    pub zones: Vec<(ZoneId, Zone)>,
+    pub favorite_params: Vec<(ModuleId, Vec<String>)>,
+    pub param_specs: Vec<(ModuleId, Vec<ParamSpec>)>,
+    pub diagnostics: Vec<(ModuleId, Vec<Diagnostic>)>,
+    // modules registered by the build the server runs, beyond the built in
+    // ones:
+    pub plugins: Vec<PluginInfo>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -36,6 +63,45 @@ pub struct PerformanceInfo {
     pub tick_rate: usize,
     pub tick_budget: Microseconds,
     pub accounts: Vec<(PerformanceAccount, PerformanceMetric)>,
+    pub overload: Option<Overload>,
+    pub clock: ClockInfo,
+}
+
+/// What the engine times its ticks against
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockSource {
+    Internal,
+    // an output device or stream input, which tick as their audio is played
+    // or received:
+    Module(ModuleId),
+}
+
+impl Default for ClockSource {
+    fn default() -> Self {
+        ClockSource::Internal
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClockInfo {
+    pub source: ClockSource,
+    // set while the selected clock has stopped and the engine is running
+    // from its internal timer in the meantime:
+    pub fallback: bool,
+    // rate of each module clock relative to the internal timer, in parts per
+    // million. only clocks measured for long enough are included:
+    pub drift: Vec<(ModuleId, f64)>,
+    // skew in parts per million of each output varispeeding to chase its
+    // device, positive when playing faster than the engine:
+    pub varispeed: Vec<(ModuleId, f64)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Overload {
+    pub status: TemporalWarningStatus,
+    pub tick_time: Microseconds,
+    // module which took the longest during the overloaded tick:
+    pub module: Option<ModuleId>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
@@ -53,6 +119,9 @@ pub enum PerformanceAccount {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PerformanceMetric {
     pub last: Microseconds,
+    // mean and max are taken over roughly the last second of ticks:
+    pub mean: Microseconds,
+    pub max: Microseconds,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
@@ -74,9 +143,145 @@ pub struct MediaItem {
     pub size: usize,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotId(pub i64);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SnapshotInfo {
+    pub id: SnapshotId,
+    // automatic backups have no name:
+    pub name: Option<String>,
+    // seconds since unix epoch:
+    pub created_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProjectInfo {
+    pub name: String,
+    // scratch projects are deleted when the server exits, unless kept:
+    pub scratch: bool,
+}
+
+/// Progress of uploading the project's snapshots and recordings to its
+/// backup target
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BackupStatus {
+    pub target: String,
+    // key of the file being uploaded:
+    pub uploading: Option<String>,
+    pub pending: usize,
+    // counted since the server started:
+    pub uploaded: usize,
+    // given up on after repeated failures:
+    pub failed: usize,
+    pub last_error: Option<String>,
+}
+
+/// Progress of pushing the media library to another mixlab instance
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MediaSyncStatus {
+    pub remote: String,
+    // items the remote didn't have, counted once its library is known:
+    pub missing: Option<usize>,
+    pub transferred: usize,
+    pub uploading: Option<String>,
+    pub finished: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Access {
+    Owner,
+    Guest(GuestToken),
+}
+
+/// What a guest link lets its holder control
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum GuestScope {
+    // params of the modules placed in the view:
+    View(ViewId),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GuestToken {
+    pub token: String,
+    pub label: String,
+    pub scope: GuestScope,
+    // seconds since unix epoch:
+    pub expires_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GuestTokenRequest {
+    pub label: String,
+    pub scope: GuestScope,
+    pub duration_secs: u64,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum ClientMessage {
     Workspace(WorkspaceMessage),
+    CreateSnapshot(String),
+    RestoreSnapshot(SnapshotId),
+    SearchParams(ParamSearch),
+    // copies the project to a new one of the given name beside it:
+    DuplicateProject(String),
+    KeepProject,
+    // sends the remote instance, eg. studio.local:8000, any media it lacks:
+    PushMedia(String),
+    CreateGuestToken(GuestTokenRequest),
+    RevokeGuestToken(String),
+    // asks what the engine is running, answered with an introspection:
+    Introspect,
+}
+
+/// Looks for params whose module kind or path contains every word of
+/// `query`. An empty query lists just the favorite params
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParamSearch {
+    // searches every module when None:
+    pub module: Option<ModuleId>,
+    pub query: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParamMatch {
+    pub param: ParamRef,
+    pub value: f64,
+    pub favorite: bool,
+}
+
+/// The graph as the engine runs it, for debugging and bug reports
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EngineIntrospection {
+    pub sample_rate: usize,
+    pub block_size: usize,
+    // in the order they run each tick:
+    pub modules: Vec<ModuleIntrospection>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModuleIntrospection {
+    pub id: ModuleId,
+    pub kind: String,
+    // samples of latency the module adds itself, and that of its outputs
+    // from the sources of the graph:
+    pub latency: usize,
+    pub path_latency: usize,
+    pub inputs: Vec<InputIntrospection>,
+    // each output is a buffer of its own, read by any inputs connected:
+    pub outputs: Vec<LineType>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InputIntrospection {
+    pub line_type: LineType,
+    // the output buffer read and its line type, none if disconnected:
+    pub source: Option<(OutputId, LineType)>,
+    // converted between mono and stereo on the way in:
+    pub converted: bool,
+    // samples the input is held back by, to line up with slower inputs:
+    pub delay: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -96,6 +301,344 @@ pub enum WorkspaceOp {
     DeleteModule(ModuleId),
     CreateConnection(InputId, OutputId),
     DeleteConnection(InputId),
+    AnalyzeGainStaging(GainStagingRequest),
+    // saves the next frame from a video output to the media library:
+    CaptureFrame(OutputId),
+    CreateParamLink(ParamLink),
+    DeleteParamLink(ParamLinkId),
+    // captures the module's current params into one side of its morph:
+    StoreMorphState(ModuleId, MorphSlot),
+    SetMorph(ModuleId, f64),
+    ClearMorph(ModuleId),
+    CreateModulation(Modulation),
+    DeleteModulation(ModulationId),
+    SetClockSource(ClockSource),
+    // input modules with stand-in media play it instead of their live
+    // source while rehearsing:
+    SetRehearsal(bool),
+    UpdateViewport(Viewport),
+    // new views start out as a copy of the workspace's own layout:
+    CreateView(String),
+    RenameView(ViewId, String),
+    DeleteView(ViewId),
+    UpdateViewGeometry(ViewId, ModuleId, WindowGeometry),
+    UpdateViewViewport(ViewId, Viewport),
+    PinMeter(ViewId, ModuleId, bool),
+    // creates, replaces or (with None) removes the binding by this name:
+    SetBinding(String, Option<Binding>),
+    InvokeBinding(String),
+    // relinks a device the project refers to but this machine doesn't have
+    // to one of its own devices, or with None unlinks it again:
+    LinkDevice(String, Option<String>),
+    // new zones start out silent, with no source picked:
+    CreateZone(String),
+    UpdateZone(ZoneId, Zone),
+    DeleteZone(ZoneId),
+    SetParam(ParamRef, f64),
+    // adds a param to or removes it from its module's favorites:
+    FavoriteParam(ParamRef, bool),
+    Batch(Batch),
+}
+
+/// Ops applied together, all or nothing. If any op fails the workspace is
+/// left as it was before the batch.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Batch {
+    // ids picked by the client for the modules this batch creates, one per
+    // CreateModule op in order, each made with ModuleId::placeholder. later
+    // ops in the batch refer to the new modules by these, and the engine
+    // swaps in the real ids:
+    pub placeholders: Vec<ModuleId>,
+    pub ops: Vec<WorkspaceOp>,
+}
+
+impl WorkspaceOp {
+    /// Rewrites every module id the op refers to
+    pub fn map_modules(self, f: &dyn Fn(ModuleId) -> ModuleId) -> Self {
+        let input = |input: InputId| InputId(f(input.0), input.1);
+        let output = |output: OutputId| OutputId(f(output.0), output.1);
+        let param = |param: ParamRef| ParamRef { module: f(param.module), ..param };
+
+        match self {
+            WorkspaceOp::CreateModule(params, geometry) => WorkspaceOp::CreateModule(params, geometry),
+            WorkspaceOp::UpdateModuleParams(id, params) => WorkspaceOp::UpdateModuleParams(f(id), params),
+            WorkspaceOp::UpdateWindowGeometry(id, geometry) => WorkspaceOp::UpdateWindowGeometry(f(id), geometry),
+            WorkspaceOp::DeleteModule(id) => WorkspaceOp::DeleteModule(f(id)),
+            WorkspaceOp::CreateConnection(in_, out) => WorkspaceOp::CreateConnection(input(in_), output(out)),
+            WorkspaceOp::DeleteConnection(in_) => WorkspaceOp::DeleteConnection(input(in_)),
+            WorkspaceOp::AnalyzeGainStaging(request) => WorkspaceOp::AnalyzeGainStaging(request),
+            WorkspaceOp::CaptureFrame(out) => WorkspaceOp::CaptureFrame(output(out)),
+            WorkspaceOp::CreateParamLink(link) => WorkspaceOp::CreateParamLink(ParamLink {
+                source: param(link.source),
+                target: param(link.target),
+                ..link
+            }),
+            WorkspaceOp::DeleteParamLink(id) => WorkspaceOp::DeleteParamLink(id),
+            WorkspaceOp::StoreMorphState(id, slot) => WorkspaceOp::StoreMorphState(f(id), slot),
+            WorkspaceOp::SetMorph(id, position) => WorkspaceOp::SetMorph(f(id), position),
+            WorkspaceOp::ClearMorph(id) => WorkspaceOp::ClearMorph(f(id)),
+            WorkspaceOp::CreateModulation(modulation) => WorkspaceOp::CreateModulation(Modulation {
+                source: output(modulation.source),
+                target: param(modulation.target),
+                ..modulation
+            }),
+            WorkspaceOp::DeleteModulation(id) => WorkspaceOp::DeleteModulation(id),
+            WorkspaceOp::SetClockSource(ClockSource::Module(id)) => WorkspaceOp::SetClockSource(ClockSource::Module(f(id))),
+            WorkspaceOp::SetClockSource(ClockSource::Internal) => WorkspaceOp::SetClockSource(ClockSource::Internal),
+            WorkspaceOp::SetRehearsal(rehearsal) => WorkspaceOp::SetRehearsal(rehearsal),
+            WorkspaceOp::UpdateViewport(viewport) => WorkspaceOp::UpdateViewport(viewport),
+            WorkspaceOp::CreateView(name) => WorkspaceOp::CreateView(name),
+            WorkspaceOp::RenameView(view, name) => WorkspaceOp::RenameView(view, name),
+            WorkspaceOp::DeleteView(view) => WorkspaceOp::DeleteView(view),
+            WorkspaceOp::UpdateViewGeometry(view, id, geometry) => WorkspaceOp::UpdateViewGeometry(view, f(id), geometry),
+            WorkspaceOp::UpdateViewViewport(view, viewport) => WorkspaceOp::UpdateViewViewport(view, viewport),
+            WorkspaceOp::PinMeter(view, id, pinned) => WorkspaceOp::PinMeter(view, f(id), pinned),
+            WorkspaceOp::SetBinding(name, binding) => WorkspaceOp::SetBinding(name, binding.map(|binding| Binding {
+                command: match binding.command {
+                    Command::ToggleParam(p) => Command::ToggleParam(param(p)),
+                    Command::SetParam(p, value) => Command::SetParam(param(p), value),
+                    Command::BumpParam(p) => Command::BumpParam(param(p)),
+                    Command::SetMorph(id, position) => Command::SetMorph(f(id), position),
+                    Command::RestoreSnapshot(id) => Command::RestoreSnapshot(id),
+                },
+                ..binding
+            })),
+            WorkspaceOp::InvokeBinding(name) => WorkspaceOp::InvokeBinding(name),
+            WorkspaceOp::LinkDevice(device, local) => WorkspaceOp::LinkDevice(device, local),
+            WorkspaceOp::CreateZone(name) => WorkspaceOp::CreateZone(name),
+            WorkspaceOp::UpdateZone(zone_id, zone) => WorkspaceOp::UpdateZone(zone_id, Zone {
+                source: zone.source.map(output),
+                ..zone
+            }),
+            WorkspaceOp::DeleteZone(zone_id) => WorkspaceOp::DeleteZone(zone_id),
+            WorkspaceOp::SetParam(p, value) => WorkspaceOp::SetParam(param(p), value),
+            WorkspaceOp::FavoriteParam(p, favorite) => WorkspaceOp::FavoriteParam(param(p), favorite),
+            WorkspaceOp::Batch(batch) => WorkspaceOp::Batch(Batch {
+                placeholders: batch.placeholders,
+                ops: batch.ops.into_iter().map(|op| op.map_modules(f)).collect(),
+            }),
+        }
+    }
+}
+
+/// A command stored under a name, so that clients and control surfaces can
+/// invoke it without knowing what it does
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Binding {
+    pub command: Command,
+    // key which invokes the binding from the web ui, as in KeyboardEvent.key:
+    pub key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Command {
+    // flips a boolean param, eg. a mixer channel's cue:
+    ToggleParam(ParamRef),
+    SetParam(ParamRef, f64),
+    // adds one to a counter param, eg. a replay buffer's replay:
+    BumpParam(ParamRef),
+    SetMorph(ModuleId, f64),
+    RestoreSnapshot(SnapshotId),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MorphSlot {
+    A,
+    B,
+}
+
+/// Two stored parameter states for a module. Moving `position` between 0.0
+/// (all A) and 1.0 (all B) interpolates continuous params, everything else
+/// snaps to whichever state is nearer.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MorphState {
+    pub a: Option<ModuleParams>,
+    pub b: Option<ModuleParams>,
+    pub position: f64,
+}
+
+/// Addresses a single numeric (or boolean) parameter of a module. `path` is a
+/// JSON pointer into the module's params, eg. "/mid" or "/channels/0/fader"
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParamRef {
+    pub module: ModuleId,
+    pub path: String,
+}
+
+/// A problem a module has run into while running, eg. a missing file or a
+/// lost device. Unlike indications these are the same shape for every
+/// module, so clients can show them without knowing the module
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Describes a numeric param, so that clients and control surfaces can show
+/// and scale it without knowing anything of the module it belongs to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParamSpec {
+    // json pointer into the module's params, as in ParamRef:
+    pub path: String,
+    pub label: String,
+    pub unit: ParamUnit,
+    pub min: f64,
+    pub max: f64,
+    pub curve: ParamCurve,
+    // digits shown after the decimal point:
+    pub precision: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamUnit {
+    None,
+    Hertz,
+    Decibel,
+    Milliseconds,
+    // a 0.0 - 1.0 param, shown as 0 - 100%:
+    Percent,
+}
+
+/// How a param's range is laid out along a fader or knob
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamCurve {
+    Linear,
+    // each doubling takes up the same travel, for frequencies and times.
+    // min must be above zero:
+    Log,
+}
+
+impl ParamSpec {
+    pub fn new(path: impl Into<String>, label: impl Into<String>, unit: ParamUnit, min: f64, max: f64) -> Self {
+        let precision = match unit {
+            ParamUnit::None => 2,
+            ParamUnit::Hertz => 1,
+            ParamUnit::Decibel => 1,
+            ParamUnit::Milliseconds => 0,
+            ParamUnit::Percent => 0,
+        };
+
+        ParamSpec {
+            path: path.into(),
+            label: label.into(),
+            unit,
+            min,
+            max,
+            curve: ParamCurve::Linear,
+            precision,
+        }
+    }
+
+    pub fn log(self) -> Self {
+        ParamSpec { curve: ParamCurve::Log, ..self }
+    }
+
+    pub fn precision(self, precision: usize) -> Self {
+        ParamSpec { precision, ..self }
+    }
+
+    /// Where a value lies along the param's range, from 0.0 at min to 1.0 at
+    /// max, following its curve
+    pub fn position(&self, value: f64) -> f64 {
+        let value = f64::max(self.min, f64::min(self.max, value));
+
+        let position = match self.curve {
+            ParamCurve::Linear => (value - self.min) / (self.max - self.min),
+            ParamCurve::Log => (value / self.min).ln() / (self.max / self.min).ln(),
+        };
+
+        if position.is_finite() { position } else { 0.0 }
+    }
+
+    /// The value at a position along the param's range, the inverse of
+    /// `position`
+    pub fn value_at(&self, position: f64) -> f64 {
+        let position = f64::max(0.0, f64::min(1.0, position));
+
+        match self.curve {
+            ParamCurve::Linear => self.min + position * (self.max - self.min),
+            ParamCurve::Log => self.min * (self.max / self.min).powf(position),
+        }
+    }
+
+    pub fn format(&self, value: f64) -> String {
+        let precision = self.precision;
+
+        match self.unit {
+            ParamUnit::None => format!("{:.*}", precision, value),
+            ParamUnit::Hertz if value.abs() >= 1000.0 => format!("{:.*} kHz", precision + 1, value / 1000.0),
+            ParamUnit::Hertz => format!("{:.*} Hz", precision, value),
+            ParamUnit::Decibel if value == f64::NEG_INFINITY => "-inf dB".to_owned(),
+            ParamUnit::Decibel => format!("{:.*} dB", precision, value),
+            ParamUnit::Milliseconds if value.abs() >= 1000.0 => format!("{:.*} s", precision + 2, value / 1000.0),
+            ParamUnit::Milliseconds => format!("{:.*} ms", precision, value),
+            ParamUnit::Percent => format!("{:.*}%", precision, value * 100.0),
+        }
+    }
+}
+
+/// Mirrors the source parameter onto the target parameter whenever the source
+/// changes, as `target = source * scale + offset`. A scale of -1.0 inverts a
+/// decibel parameter, scale -1.0 and offset 1.0 inverts a 0.0 - 1.0 fader.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParamLink {
+    pub source: ParamRef,
+    pub target: ParamRef,
+    pub scale: f64,
+    pub offset: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct ParamLinkId(pub NonZeroUsize);
+
+/// Drives a parameter from a mono control output such as an LFO. Each tick
+/// the output's level, 0.0 - 1.0, is mapped onto `min` - `max`. The modulated
+/// value is not persisted, the parameter keeps whatever value was last set
+/// by hand as its base.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Modulation {
+    pub source: OutputId,
+    pub target: ParamRef,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct ModulationId(pub NonZeroUsize);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GainStagingRequest {
+    pub duration_ms: u64,
+    // apply suggested trims once analysis completes:
+    pub apply: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GainStagingReport {
+    pub wires: Vec<(OutputId, WireLevel)>,
+    pub suggestions: Vec<TrimSuggestion>,
+    pub applied: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WireLevel {
+    pub peak: Decibel,
+    pub rms: Decibel,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrimSuggestion {
+    pub input: InputId,
+    pub level: WireLevel,
+    pub current: Decibel,
+    pub suggested: Decibel,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -114,11 +657,49 @@ pub enum ServerUpdate {
     DeleteModule(ModuleId),
     CreateConnection(InputId, OutputId),
     DeleteConnection(InputId),
+    GainStagingReport(GainStagingReport),
+    CreateParamLink(ParamLinkId, ParamLink),
+    DeleteParamLink(ParamLinkId),
+    UpdateMorph(ModuleId, Option<MorphState>),
+    CreateModulation(ModulationId, Modulation),
+    DeleteModulation(ModulationId),
+    SetClockSource(ClockSource),
+    SetRehearsal(bool),
+    UpdateViewport(Viewport),
+    UpdateView(ViewId, Option<View>),
+    UpdateViewGeometry(ViewId, ModuleId, WindowGeometry),
+    UpdateBinding(String, Option<Binding>),
+    UpdateDeviceLink(String, Option<String>),
+    UpdateZone(ZoneId, Option<Zone>),
+    UpdateFavoriteParams(ModuleId, Vec<String>),
+    // sent for new modules, and whenever a module's params change shape,
+    // eg. a mixer gaining channels:
+    UpdateParamSpecs(ModuleId, Vec<ParamSpec>),
+    // the module's current diagnostics in full, empty once all are cleared:
+    UpdateModuleDiagnostics(ModuleId, Vec<Diagnostic>),
+    // sent when the whole workspace is swapped out, eg. on snapshot restore:
+    ReplaceWorkspace(WorkspaceState),
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct ModuleId(pub NonZeroUsize);
 
+// ids from here up are never handed out to modules, they're kept for
+// clients to name the modules a batch creates. fixed rather than taken from
+// usize, which is narrower on the wasm frontend than on the server:
+const PLACEHOLDER_BASE: usize = 1 << 31;
+
+impl ModuleId {
+    /// The nth placeholder id a batch can refer to a module it creates by
+    pub fn placeholder(n: usize) -> ModuleId {
+        ModuleId(NonZeroUsize::new(PLACEHOLDER_BASE | n).unwrap())
+    }
+
+    pub fn is_placeholder(&self) -> bool {
+        self.0.get() >= PLACEHOLDER_BASE
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub enum TerminalId {
     Input(InputId),
@@ -188,46 +769,98 @@ impl LineType {
     pub fn unlabeled(self) -> Terminal {
         Terminal(None, self)
     }
+
+    /// Whether an output of this type may be connected to an input of
+    /// `other`. Mono and stereo lines are converted by the engine.
+    pub fn can_connect(self, other: LineType) -> bool {
+        matches!((self, other),
+            (LineType::Mono, LineType::Mono) |
+            (LineType::Mono, LineType::Stereo) |
+            (LineType::Stereo, LineType::Mono) |
+            (LineType::Stereo, LineType::Stereo) |
+            (LineType::Video, LineType::Video))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ModuleParams {
     Amplifier(AmplifierParams),
+    ArtNetOutput(ArtNetOutputParams),
+    BeatDetector(BeatDetectorParams),
+    BroadcastDelay(BroadcastDelayParams),
+    BrowserSource(BrowserSourceParams),
+    Bus(BusParams),
     Envelope(EnvelopeParams),
+    EnvelopeFollower(EnvelopeFollowerParams),
     EqThree(EqThreeParams),
     FmSine(FmSineParams),
+    HueLight(HueLightParams),
+    IdentInserter(IdentInserterParams),
+    ImageSource(ImageSourceParams),
+    InputDevice(InputDeviceParams),
+    Lfo(LfoParams),
+    LoudnessLogger(LoudnessLoggerParams),
     MediaSource(MediaSourceParams),
     Mixer(MixerParams),
     Monitor(()),
+    Multiviewer(MultiviewerParams),
+    NullTest(NullTestParams),
     Oscillator(OscillatorParams),
     OutputDevice(OutputDeviceParams),
     Plotter(()),
+    Plugin(PluginParams),
+    PreviewOverlay(PreviewOverlayParams),
+    Recorder(RecorderParams),
+    ReplayBuffer(ReplayBufferParams),
     StereoPanner(()),
     StereoSplitter(()),
     StreamInput(StreamInputParams),
     StreamOutput(StreamOutputParams),
     Trigger(GateState),
+    VcaGroup(VcaGroupParams),
     VideoMixer(VideoMixerParams),
+    ZoneOutput(ZoneOutputParams),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum Indication {
     Amplifier(()),
+    ArtNetOutput(ArtNetOutputIndication),
+    BeatDetector(BeatDetectorIndication),
+    BroadcastDelay(BroadcastDelayIndication),
+    BrowserSource(BrowserSourceIndication),
+    Bus(()),
     Envelope(()),
+    EnvelopeFollower(()),
     EqThree(()),
     FmSine(()),
+    HueLight(HueLightIndication),
+    IdentInserter(IdentInserterIndication),
+    ImageSource(ImageSourceIndication),
+    InputDevice(InputDeviceIndication),
+    Lfo(()),
+    LoudnessLogger(LoudnessLoggerIndication),
     MediaSource(()),
     Mixer(()),
     Monitor(MonitorIndication),
+    Multiviewer(()),
+    NullTest(NullTestIndication),
     Oscillator(()),
     OutputDevice(OutputDeviceIndication),
     Plotter(PlotterIndication),
+    // as json, see PluginParams:
+    Plugin(String),
+    PreviewOverlay(()),
+    Recorder(RecorderIndication),
+    ReplayBuffer(ReplayBufferIndication),
     StereoPanner(()),
     StereoSplitter(()),
     StreamInput(()),
     StreamOutput(StreamOutputIndication),
     Trigger(()),
+    VcaGroup(()),
     VideoMixer(()),
+    ZoneOutput(()),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Copy, PartialEq)]
@@ -267,6 +900,10 @@ pub struct OutputDeviceParams {
     pub device: Option<String>,
     pub left: Option<usize>,
     pub right: Option<usize>,
+    // resample to track the device's clock instead of letting its buffer
+    // under or overrun:
+    #[serde(default)]
+    pub varispeed: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -275,6 +912,37 @@ pub struct OutputDeviceIndication {
     pub lag: Option<TemporalWarningStatus>,
     pub default_device: Option<String>,
     pub devices: Option<Vec<(String, usize)>>,
+    // the device actually opened, after relinking. None where the device
+    // the params refer to is missing:
+    pub resolved: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct InputDeviceParams {
+    pub device: Option<String>,
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    // output device the input is also played straight out of, in blocks far
+    // smaller than the engine's, so performers can hear themselves without
+    // a noticeable delay:
+    pub monitor: Option<String>,
+    pub monitor_gain: Decibel,
+    // played instead of the device while rehearsing:
+    #[serde(default)]
+    pub stand_in: Option<MediaId>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InputDeviceIndication {
+    pub clip: Option<TemporalWarningStatus>,
+    pub lag: Option<TemporalWarningStatus>,
+    pub default_device: Option<String>,
+    pub devices: Option<Vec<(String, usize)>>,
+    pub monitor_devices: Option<Vec<String>>,
+    // the devices actually opened, after relinking. None where the device
+    // the params refer to is missing:
+    pub resolved: Option<String>,
+    pub monitor_resolved: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -301,6 +969,112 @@ pub struct AmplifierParams {
     pub mod_depth: f64,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ArtNetOutputParams {
+    // ip address of the art-net node, or a broadcast address:
+    pub destination: String,
+    // 15 bit port-address, net/sub-net/universe:
+    pub universe: u16,
+    pub channels: Vec<DmxChannelParams>,
+}
+
+impl ArtNetOutputParams {
+    pub fn with_channels(n: u16) -> Self {
+        ArtNetOutputParams {
+            destination: "255.255.255.255".to_owned(),
+            universe: 0,
+            channels: (1..=n).map(|address| DmxChannelParams { address, level: 0.0 }).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DmxChannelParams {
+    // 1 - 512:
+    pub address: u16,
+    // 0.0 - 1.0, used while the channel's control input is disconnected:
+    pub level: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ArtNetOutputIndication {
+    pub error: Option<TemporalWarningStatus>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HueLightParams {
+    // ip address of the hue bridge on the local network:
+    pub bridge: String,
+    // api username, issued by the bridge after pressing its link button:
+    pub username: String,
+    pub light: String,
+    // 0.0 - 1.0, each used while its control input is disconnected:
+    pub brightness: f64,
+    pub hue: f64,
+    pub saturation: f64,
+}
+
+impl Default for HueLightParams {
+    fn default() -> Self {
+        HueLightParams {
+            bridge: String::new(),
+            username: String::new(),
+            light: "1".to_owned(),
+            brightness: 1.0,
+            hue: 0.0,
+            saturation: 0.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HueLightIndication {
+    pub error: Option<TemporalWarningStatus>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BeatDetectorParams {
+    // onset threshold as a multiple of the recent average energy:
+    pub sensitivity: f64,
+    // length of the gate pulse emitted on each beat:
+    pub gate_ms: f64,
+}
+
+impl Default for BeatDetectorParams {
+    fn default() -> Self {
+        BeatDetectorParams {
+            sensitivity: 1.5,
+            gate_ms: 50.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BeatDetectorIndication {
+    // None until enough beats have been heard to estimate a tempo:
+    pub bpm: Option<f64>,
+    pub gate: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct NullTestParams {
+    // bumped to clear the failure latch and start verifying afresh:
+    pub reset: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct NullTestIndication {
+    pub connected: bool,
+    // residual of A minus B over the last measurement window, None when
+    // every sample matched exactly:
+    pub residual_rms: Option<Decibel>,
+    pub residual_peak: Option<Decibel>,
+    // the last window was bit-exact:
+    pub null: bool,
+    // some window since the last reset was not, however briefly:
+    pub failed: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum GateState {
     Open,
@@ -326,6 +1100,52 @@ impl Default for EnvelopeParams {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EnvelopeFollowerParams {
+    pub attack_ms: f64,
+    pub release_ms: f64,
+    // applied to the input before measuring, so quiet sources can still
+    // drive the full control range:
+    pub gain: Decibel,
+}
+
+impl Default for EnvelopeFollowerParams {
+    fn default() -> Self {
+        EnvelopeFollowerParams {
+            attack_ms: 10.0,
+            release_ms: 250.0,
+            gain: Decibel(0.0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum LfoWaveform {
+    Sine,
+    Triangle,
+    Square,
+    // a new random level each cycle:
+    Random,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LfoParams {
+    pub waveform: LfoWaveform,
+    pub rate_hz: f64,
+    // 0.0 - 1.0, output swings this far either side of 0.5:
+    pub depth: f64,
+}
+
+impl Default for LfoParams {
+    fn default() -> Self {
+        LfoParams {
+            waveform: LfoWaveform::Sine,
+            rate_hz: 1.0,
+            depth: 1.0,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MixerParams {
     pub channels: Vec<MixerChannelParams>
@@ -344,12 +1164,89 @@ pub struct MixerChannelParams {
     pub gain: Decibel,
     pub fader: f64,
     pub cue: bool,
+    // vca group module this channel is a member of:
+    #[serde(default)]
+    pub group: Option<ModuleId>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BusParams {
+    pub inputs: Vec<BusInputParams>,
+    pub master: f64,
+}
+
+impl BusParams {
+    pub fn with_inputs(n: usize) -> BusParams {
+        BusParams {
+            inputs: vec![BusInputParams::default(); n],
+            master: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BusInputParams {
+    pub gain: Decibel,
+    // -1.0 is hard left, 1.0 is hard right:
+    pub pan: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VcaGroupParams {
+    pub name: String,
+    pub fader: f64,
+}
+
+impl Default for VcaGroupParams {
+    fn default() -> Self {
+        VcaGroupParams {
+            name: "Group".to_owned(),
+            fader: 1.0,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct StreamInputParams {
     pub protocol: Option<StreamProtocol>,
     pub mountpoint: Option<String>,
+    #[serde(default)]
+    pub resample_quality: ResampleQuality,
+    #[serde(default)]
+    pub deinterlace: Deinterlace,
+    // played instead of the stream while rehearsing:
+    #[serde(default)]
+    pub stand_in: Option<MediaId>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResampleQuality {
+    Linear,
+    Polyphase,
+    Sinc,
+}
+
+impl Default for ResampleQuality {
+    fn default() -> Self {
+        ResampleQuality::Polyphase
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Deinterlace {
+    // frames are passed through as they arrive, for progressive sources:
+    Weave,
+    // each field becomes a frame of its own, doubling the frame rate:
+    Bob,
+    // moving parts of the picture are interpolated from one field, still
+    // parts keep both:
+    Adaptive,
+}
+
+impl Default for Deinterlace {
+    fn default() -> Self {
+        Deinterlace::Weave
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
@@ -367,6 +1264,14 @@ pub struct StreamOutputParams {
     pub disconnect_seq: u64,
     pub rtmp_url: String,
     pub rtmp_stream_key: String,
+    // a second ingest, eg. the platform's backup server, streamed to when
+    // the primary fails. it's connected to only then, so the stream drops
+    // out while it connects rather than carrying on seamlessly. none is
+    // used while the url is left empty:
+    #[serde(default)]
+    pub backup_rtmp_url: String,
+    #[serde(default)]
+    pub backup_rtmp_stream_key: String,
 }
 
 impl Default for StreamOutputParams {
@@ -377,14 +1282,172 @@ impl Default for StreamOutputParams {
             disconnect_seq: 0,
             rtmp_url: "".to_owned(),
             rtmp_stream_key: "".to_owned(),
+            backup_rtmp_url: "".to_owned(),
+            backup_rtmp_stream_key: "".to_owned(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct RecorderParams {
+    pub recording: bool,
+    // bumped to drop a chapter marker into the current take:
+    #[serde(default)]
+    pub marker: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecorderIndication {
+    // directory the current take is being recorded to, relative to the
+    // project's recordings directory:
+    pub take: Option<String>,
+    pub error: bool,
+    // ticks dropped because the encoder could not keep up:
+    pub lag: Option<TemporalWarningStatus>,
+}
+
+pub const REPLAY_BUFFER_MAX_SECONDS: f64 = 30.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReplayBufferParams {
+    // length of input history kept, and so of each replay:
+    pub seconds: f64,
+    // playback speed, 1.0 is real time:
+    pub speed: f64,
+    // whether replays are also added to the media library:
+    pub save: bool,
+    // bumped to capture the buffer and play it back
+    pub replay: u64,
+}
+
+impl Default for ReplayBufferParams {
+    fn default() -> Self {
+        ReplayBufferParams {
+            seconds: 10.0,
+            speed: 0.5,
+            save: true,
+            replay: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReplayBufferIndication {
+    pub playing: bool,
+    pub saving: bool,
+    // whether the last replay failed to save:
+    pub save_error: bool,
+}
+
+pub const BROADCAST_DELAY_MAX_SECONDS: f64 = 60.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BroadcastDelayParams {
+    // how far behind its input the output runs, once built up:
+    pub seconds: f64,
+    // how much of the newest, not yet aired, program a dump throws away:
+    pub dump_seconds: f64,
+    // bumped to dump
+    pub dump: u64,
+}
+
+impl Default for BroadcastDelayParams {
+    fn default() -> Self {
+        BroadcastDelayParams {
+            seconds: 7.0,
+            dump_seconds: 7.0,
+            dump: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BroadcastDelayIndication {
+    // the delay in hand, in tenths of a second. it starts out at zero and
+    // builds up to the target, and again after each dump:
+    pub delay_tenths: u64,
+    pub building: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IdentInserterParams {
+    pub media_id: Option<MediaId>,
+    // minutes from one ident to the next, zero to only play on trigger:
+    pub interval_minutes: f64,
+    // level of the program under an ident, relative to unducked:
+    pub duck_db: f64,
+    // bumped to play the ident now
+    pub trigger: u64,
+}
+
+impl Default for IdentInserterParams {
+    fn default() -> Self {
+        IdentInserterParams {
+            media_id: None,
+            interval_minutes: 15.0,
+            duck_db: -15.0,
+            trigger: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IdentInserterIndication {
+    pub playing: bool,
+    // until the next ident on the interval, none while one plays or if
+    // there's no interval:
+    pub next_secs: Option<u64>,
+}
+
+/// Params of a module registered by a crate linking against mixlab, which
+/// the protocol can't know the type of, so carries as json
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PluginParams {
+    // the name it was registered under, which it's persisted by:
+    pub kind: String,
+    pub json: String,
+}
+
+/// A plugin module as offered in the catalog
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PluginInfo {
+    pub label: String,
+    pub default_params: PluginParams,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LoudnessLoggerParams {
+    // names the log, so that each source logged keeps its own:
+    pub source: String,
+}
+
+impl Default for LoudnessLoggerParams {
+    fn default() -> Self {
+        LoudnessLoggerParams {
+            source: "program".to_owned(),
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LoudnessLoggerIndication {
+    // as last logged, none when silent:
+    pub short_term_lufs: Option<f64>,
+    // entries aren't written while the workspace is being rehearsed:
+    pub logging: bool,
+    pub error: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct StreamOutputIndication {
     pub live: StreamOutputLiveStatus,
     pub error: bool,
+    // measured over the last second while live:
+    pub kbps: Option<u32>,
+    // streaming to (or connecting to) the backup ingest over the primary:
+    pub backup: bool,
+    // times the stream has switched ingest since it was connected:
+    pub failovers: u32,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
@@ -392,6 +1455,8 @@ pub enum StreamOutputLiveStatus {
     Offline,
     Connecting,
     Live,
+    // connection dropped, waiting to retry:
+    Reconnecting { attempt: u32 },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
@@ -407,6 +1472,8 @@ pub struct VideoMixerParams {
     pub a: Option<usize>,
     pub b: Option<usize>,
     pub fader: f64,
+    #[serde(default)]
+    pub stinger: StingerParams,
 }
 
 impl Default for VideoMixerParams {
@@ -415,6 +1482,62 @@ impl Default for VideoMixerParams {
             a: None,
             b: None,
             fader: 1.0, // start at A
+            stinger: StingerParams::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ZoneOutputParams {
+    pub zone: Option<ZoneId>,
+}
+
+/// A clip from the media library played over the mix to cover a change of
+/// fader position. Its alpha channel, if it has one, shows the mix beneath.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct StingerParams {
+    pub media_id: Option<MediaId>,
+    // time into the clip at which the new fader position takes effect:
+    pub cut_ms: u64,
+    // bumped with each take. a fader change arriving along with a new take
+    // is held back until the cut point
+    pub take: u64,
+}
+
+/// Camera setup aids, drawn into the picture so only for use on paths that
+/// do not go to air
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PreviewOverlayParams {
+    pub safe_area: bool,
+    pub zebra: bool,
+    // 0.0 - 1.0 of the luma range, stripes are drawn over anything brighter:
+    pub zebra_level: f64,
+    pub focus_peaking: bool,
+}
+
+impl Default for PreviewOverlayParams {
+    fn default() -> Self {
+        PreviewOverlayParams {
+            safe_area: true,
+            zebra: false,
+            zebra_level: 0.9,
+            focus_peaking: false,
+        }
+    }
+}
+
+pub const MULTIVIEWER_SOURCES: usize = 8;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MultiviewerParams {
+    // shown under each source tile, one per source:
+    pub labels: Vec<String>,
+}
+
+impl Default for MultiviewerParams {
+    fn default() -> Self {
+        MultiviewerParams {
+            labels: (1..=MULTIVIEWER_SOURCES).map(|i| format!("Input {}", i)).collect(),
         }
     }
 }
@@ -422,6 +1545,63 @@ impl Default for VideoMixerParams {
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct MediaSourceParams {
     pub media_id: Option<MediaId>,
+    #[serde(default)]
+    pub deinterlace: Deinterlace,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BrowserSourceParams {
+    pub url: String,
+    pub width: usize,
+    pub height: usize,
+    pub fps: f64,
+    // impulse, reopens the page:
+    pub reload: u64,
+}
+
+impl Default for BrowserSourceParams {
+    fn default() -> Self {
+        BrowserSourceParams {
+            url: String::new(),
+            width: 1280,
+            height: 720,
+            fps: 30.0,
+            reload: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BrowserSourceIndication {
+    pub running: bool,
+    pub error: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ImageSourceParams {
+    pub media_id: Option<MediaId>,
+    // plays every item in the library numbered like the chosen one, as in
+    // title_001.png, title_002.png and so on:
+    pub sequence: bool,
+    // frame rate of image sequences, animations carry their own timing:
+    pub fps: f64,
+}
+
+impl Default for ImageSourceParams {
+    fn default() -> Self {
+        ImageSourceParams {
+            media_id: None,
+            sequence: false,
+            fps: 25.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ImageSourceIndication {
+    pub loading: bool,
+    pub frames: usize,
+    pub error: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -436,6 +1616,77 @@ pub struct WindowGeometry {
     pub z_index: usize,
 }
 
+/// How the workspace is scrolled and zoomed, shared by every client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Viewport {
+    // in workspace coordinates, ie. before zoom is applied:
+    pub scroll: Coords,
+    pub zoom: f64,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport {
+            scroll: Coords::default(),
+            zoom: 1.0,
+        }
+    }
+}
+
+/// A named layout of the workspace, eg. one for the audio desk and another
+/// for video. Windows the view hasn't placed itself fall back to the
+/// workspace's own geometry.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct View {
+    pub name: String,
+    pub geometry: Vec<(ModuleId, WindowGeometry)>,
+    pub viewport: Viewport,
+    // modules whose levels are pinned to the sidebar:
+    pub pinned: Vec<ModuleId>,
+}
+
+impl View {
+    pub fn geometry(&self, module: ModuleId) -> Option<&WindowGeometry> {
+        self.geometry.iter()
+            .find(|(id, _)| *id == module)
+            .map(|(_, geometry)| geometry)
+    }
+
+    pub fn set_geometry(&mut self, module: ModuleId, geometry: WindowGeometry) {
+        match self.geometry.iter_mut().find(|(id, _)| *id == module) {
+            Some((_, geom)) => { *geom = geometry; }
+            None => { self.geometry.push((module, geometry)); }
+        }
+    }
+
+    /// Drops everything the view holds for a module, returning whether
+    /// there was anything
+    pub fn remove_module(&mut self, module: ModuleId) -> bool {
+        let len = self.geometry.len() + self.pinned.len();
+        self.geometry.retain(|(id, _)| *id != module);
+        self.pinned.retain(|id| *id != module);
+        len != self.geometry.len() + self.pinned.len()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct ViewId(pub NonZeroUsize);
+
+/// One of the places a show is played out to, eg. the stream, the stage
+/// monitors or the lobby. Each zone takes its own source, delayed and
+/// eq'd for the zone, and is patched out through a zone output module
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Zone {
+    pub name: String,
+    pub source: Option<OutputId>,
+    // to line the zone up with the room, eg. delay speakers at the back:
+    pub delay_ms: f64,
+    pub eq: EqThreeParams,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct ZoneId(pub NonZeroUsize);
+
 impl Coords {
     pub fn add(&self, other: Coords) -> Coords {
         Coords {
@@ -450,6 +1701,13 @@ impl Coords {
             y: self.y - other.y,
         }
     }
+
+    pub fn scale(&self, factor: f64) -> Coords {
+        Coords {
+            x: (self.x as f64 * factor).round() as i32,
+            y: (self.y as f64 * factor).round() as i32,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
@@ -482,3 +1740,28 @@ impl From<Decibel> for f64 {
         db.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ModuleId;
+
+    #[test]
+    fn placeholders_survive_pointer_width() {
+        // the third placeholder as a wasm32 frontend makes it, with a 32 bit
+        // usize. bincode writes usizes out as u64 whatever their width:
+        let wasm_id = (1u32 << 31) | 3;
+        let sent = bincode::serialize(&(wasm_id as u64)).unwrap();
+
+        let received = bincode::deserialize::<ModuleId>(&sent).unwrap();
+
+        assert!(received.is_placeholder());
+        assert_eq!(received, ModuleId::placeholder(3));
+        assert_eq!(bincode::serialize(&ModuleId::placeholder(3)).unwrap(), sent);
+    }
+
+    #[test]
+    fn module_ids_are_not_placeholders() {
+        let id = ModuleId(std::num::NonZeroUsize::new(1).unwrap());
+        assert!(!id.is_placeholder());
+    }
+}
@@ -0,0 +1,48 @@
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::process;
+
+use structopt::StructOpt;
+
+use mixlab_protocol::ModuleId;
+
+use crate::engine::BenchTarget;
+use crate::project;
+
+/// Measures what a project costs to run per tick on this machine, without
+/// serving it, and prints the report as json. Modules run dry as for
+/// --check, so benching a project opens no devices and sends nothing, and
+/// what they'd spend on either isn't measured
+#[derive(StructOpt)]
+pub struct BenchOpts {
+    // id of one module in the project to bench on its own. otherwise every
+    // module is, and then the workspace as a whole:
+    #[structopt(long)]
+    module: Option<NonZeroUsize>,
+    // comma separated, the project's own if not given:
+    #[structopt(long, use_delimiter = true)]
+    sample_rates: Vec<usize>,
+    #[structopt(long, use_delimiter = true)]
+    block_sizes: Vec<usize>,
+    // per module and config:
+    #[structopt(long, default_value = "1000")]
+    ticks: u64,
+    workspace_path: PathBuf,
+}
+
+pub async fn run(opts: BenchOpts) {
+    let target = match opts.module {
+        Some(id) => BenchTarget::Module(ModuleId(id)),
+        None => BenchTarget::Workspace,
+    };
+
+    match project::bench(opts.workspace_path, opts.sample_rates, opts.block_sizes, target, opts.ticks).await {
+        Ok(report) => {
+            println!("{}", serde_json::to_string_pretty(&report).expect("serde_json::to_string_pretty"));
+        }
+        Err(e) => {
+            eprintln!("bench: {:?}", e);
+            process::exit(1);
+        }
+    }
+}
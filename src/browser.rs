@@ -0,0 +1,331 @@
+// renders web pages in a headless chromium, driven over the devtools
+// protocol. pages are captured by screencasting, which sends a frame
+// whenever the page repaints
+
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use derive_more::From;
+use serde_json::{json, Value};
+use tungstenite::{Message, WebSocket};
+use uuid::Uuid;
+
+use mixlab_codec::ffmpeg::codec::{self, CodecBuilder, Decode, RecvFrameError};
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::{sys, AvError, AvFrame, AvPacketRef, Colorimetry, PacketInfo, PictureSettings, PixelFormat, SwsContext};
+use mixlab_util::time::TimeBase;
+
+// how often the browser thread checks whether it's still wanted while a page
+// is idle:
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+lazy_static::lazy_static! {
+    static ref EXECUTABLE: Mutex<PathBuf> = Mutex::new(PathBuf::from("chromium"));
+}
+
+/// Sets the chromium (or chrome) executable that pages are rendered with
+pub fn set_executable(path: PathBuf) {
+    *EXECUTABLE.lock().unwrap() = path;
+}
+
+#[derive(Debug, Clone)]
+pub struct PageSettings {
+    pub url: String,
+    pub width: usize,
+    pub height: usize,
+    pub color: Colorimetry,
+}
+
+/// A page rendering on a thread of its own. Dropping the page closes its
+/// browser.
+#[derive(Debug)]
+pub struct Page {
+    rx: Receiver<AvFrame<Video>>,
+    closed: Arc<AtomicBool>,
+    failed: Arc<AtomicBool>,
+}
+
+#[derive(Debug, From)]
+enum BrowserError {
+    Io(io::Error),
+    NoDevToolsUrl,
+    WebSocket(tungstenite::Error),
+    Json(serde_json::Error),
+    Base64(base64::DecodeError),
+    // error returned by the browser for a command:
+    Protocol(Value),
+    Crashed,
+    NoFrame,
+    CodecBuild(codec::BuildError),
+    CodecOpen(codec::OpenError),
+    RecvFrame(RecvFrameError),
+    Av(AvError),
+}
+
+impl Page {
+    pub fn open(settings: PageSettings) -> Self {
+        // only the latest frame matters, older ones are dropped if the
+        // engine hasn't taken them yet:
+        let (tx, rx) = mpsc::sync_channel(1);
+        let closed = Arc::new(AtomicBool::new(false));
+        let failed = Arc::new(AtomicBool::new(false));
+
+        thread::spawn({
+            let closed = closed.clone();
+            let failed = failed.clone();
+
+            move || {
+                if let Err(e) = run_browser(&settings, tx, &closed) {
+                    eprintln!("browser: could not render {}: {:?}", settings.url, e);
+                    failed.store(true, Ordering::Relaxed);
+                }
+            }
+        });
+
+        Page { rx, closed, failed }
+    }
+
+    /// Returns the most recent frame rendered since the last call, if any
+    pub fn recv_frame(&mut self) -> Option<AvFrame<Video>> {
+        let mut latest = None;
+
+        loop {
+            match self.rx.try_recv() {
+                Ok(frame) => { latest = Some(frame); }
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => { return latest; }
+            }
+        }
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Page {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+// kills the browser and removes its profile when dropped
+struct BrowserProcess {
+    child: Child,
+    profile_dir: PathBuf,
+}
+
+impl Drop for BrowserProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.profile_dir);
+    }
+}
+
+fn run_browser(settings: &PageSettings, tx: SyncSender<AvFrame<Video>>, closed: &AtomicBool) -> Result<(), BrowserError> {
+    // every page gets a fresh profile, so that nothing is shared between
+    // pages or left over from the last run:
+    let profile_dir = std::env::temp_dir().join(format!("mixlab-browser-{}", Uuid::new_v4()));
+
+    let child = Command::new(&*EXECUTABLE.lock().unwrap())
+        .arg("--headless")
+        .arg("--disable-gpu")
+        .arg("--hide-scrollbars")
+        .arg("--mute-audio")
+        .arg("--autoplay-policy=no-user-gesture-required")
+        .arg("--remote-debugging-port=0")
+        .arg(format!("--user-data-dir={}", profile_dir.display()))
+        .arg(format!("--window-size={},{}", settings.width, settings.height))
+        .arg("about:blank")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let mut process = BrowserProcess { child, profile_dir };
+
+    let devtools_url = devtools_url(&mut process.child)?;
+    let (socket, _) = tungstenite::connect(devtools_url.as_str())?;
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+
+    let mut devtools = DevTools { socket, next_id: 0 };
+
+    let target = devtools.call(None, "Target.createTarget", json!({ "url": "about:blank" }))?;
+    let session = devtools.call(None, "Target.attachToTarget", json!({ "targetId": target["targetId"], "flatten": true }))?;
+    let session = session["sessionId"].as_str().unwrap_or_default().to_owned();
+    let session = Some(session.as_str());
+
+    devtools.call(session, "Emulation.setDeviceMetricsOverride", json!({
+        "width": settings.width,
+        "height": settings.height,
+        "deviceScaleFactor": 1,
+        "mobile": false,
+    }))?;
+
+    // overlay pages expect to be keyed over whatever is underneath them:
+    devtools.call(session, "Emulation.setDefaultBackgroundColorOverride", json!({
+        "color": { "r": 0, "g": 0, "b": 0, "a": 0 },
+    }))?;
+
+    devtools.call(session, "Page.enable", json!({}))?;
+    devtools.call(session, "Page.navigate", json!({ "url": settings.url }))?;
+
+    // png rather than jpeg, for the alpha channel:
+    devtools.call(session, "Page.startScreencast", json!({
+        "format": "png",
+        "maxWidth": settings.width,
+        "maxHeight": settings.height,
+        "everyNthFrame": 1,
+    }))?;
+
+    let mut decode = CodecBuilder::<Video>::new(sys::AVCodecID_AV_CODEC_ID_PNG, TimeBase::new(1, 1))?
+        .open_decoder()?;
+
+    let output = PictureSettings {
+        width: settings.width & !1,
+        height: settings.height & !1,
+        pixel_format: PixelFormat::yuva420p(),
+        color: settings.color,
+    };
+
+    let mut convert: Option<SwsContext> = None;
+
+    while !closed.load(Ordering::Relaxed) {
+        let event = match devtools.recv()? {
+            Some(event) => event,
+            None => continue,
+        };
+
+        match event["method"].as_str() {
+            Some("Page.screencastFrame") => {
+                let params = &event["params"];
+
+                // the browser waits for each frame to be acknowledged before
+                // sending another:
+                devtools.send(session, "Page.screencastFrameAck", json!({ "sessionId": params["sessionId"] }))?;
+
+                let png = base64::decode(params["data"].as_str().unwrap_or_default())?;
+                let decoded = decode_png(&mut decode, &png)?;
+
+                let input = decoded.picture_settings();
+
+                if convert.as_ref().map(|sws| sws.input_settings()) != Some(&input) {
+                    convert = Some(SwsContext::new(input, output.clone()));
+                }
+
+                let mut frame = AvFrame::blank(&output);
+                convert.as_mut().unwrap().process(&decoded.frame_data(), &mut frame.frame_data_mut());
+
+                match tx.try_send(frame) {
+                    Ok(()) | Err(mpsc::TrySendError::Full(_)) => {}
+                    Err(mpsc::TrySendError::Disconnected(_)) => { break; }
+                }
+            }
+            Some("Inspector.targetCrashed") => {
+                return Err(BrowserError::Crashed);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+// chromium prints the address of its devtools endpoint to stderr once it's
+// listening
+fn devtools_url(child: &mut Child) -> Result<String, BrowserError> {
+    let mut stderr = BufReader::new(child.stderr.take().expect("stderr"));
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+
+        if stderr.read_line(&mut line)? == 0 {
+            return Err(BrowserError::NoDevToolsUrl);
+        }
+
+        if let Some(idx) = line.find("ws://") {
+            let url = line[idx..].trim().to_owned();
+
+            // keep draining stderr so that the browser never blocks writing
+            // to it:
+            thread::spawn(move || io::copy(&mut stderr, &mut io::sink()));
+
+            return Ok(url);
+        }
+    }
+}
+
+fn decode_png(decode: &mut Decode<Video>, png: &[u8]) -> Result<AvFrame<Video>, BrowserError> {
+    decode.send_packet(&AvPacketRef::borrowed(PacketInfo { pts: 0, dts: 0, data: png }))?;
+
+    // png frames are self contained, so each packet decodes to a frame:
+    match decode.recv_frame() {
+        Ok(frame) => Ok(frame),
+        Err(RecvFrameError::NeedMoreInput) => Err(BrowserError::NoFrame),
+        Err(e) => Err(e.into()),
+    }
+}
+
+struct DevTools {
+    socket: WebSocket<TcpStream>,
+    next_id: u64,
+}
+
+impl DevTools {
+    fn send(&mut self, session: Option<&str>, method: &str, params: Value) -> Result<u64, BrowserError> {
+        self.next_id += 1;
+
+        let mut message = json!({
+            "id": self.next_id,
+            "method": method,
+            "params": params,
+        });
+
+        if let Some(session) = session {
+            message["sessionId"] = json!(session);
+        }
+
+        self.socket.write_message(Message::Text(message.to_string()))?;
+        Ok(self.next_id)
+    }
+
+    // sends a command and waits for its result, events arriving in the
+    // meantime are dropped as nothing is listening for them yet
+    fn call(&mut self, session: Option<&str>, method: &str, params: Value) -> Result<Value, BrowserError> {
+        let id = self.send(session, method, params)?;
+
+        loop {
+            let mut message = match self.recv()? {
+                Some(message) => message,
+                None => continue,
+            };
+
+            if message["id"].as_u64() == Some(id) {
+                if !message["error"].is_null() {
+                    return Err(BrowserError::Protocol(message["error"].take()));
+                }
+
+                return Ok(message["result"].take());
+            }
+        }
+    }
+
+    // returns None if nothing arrived within the poll interval
+    fn recv(&mut self) -> Result<Option<Value>, BrowserError> {
+        match self.socket.read_message() {
+            Ok(Message::Text(text)) => Ok(Some(serde_json::from_str(&text)?)),
+            Ok(_) => Ok(None),
+            Err(tungstenite::Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -2,4 +2,8 @@ pub static MIGRATIONS: &'static [(i64, &'static str)] = &[
     (0, include_str!("migrations/0_init.sql")),
     (20200804, include_str!("migrations/20200804_create_media_tables.sql")),
     (20200805, include_str!("migrations/20200805_create_workspace_table.sql")),
+    (20200901, include_str!("migrations/20200901_create_workspace_snapshots_table.sql")),
+    (20261014, include_str!("migrations/20261014_create_backup_uploads_table.sql")),
+    (20261015, include_str!("migrations/20261015_add_stream_hashes.sql")),
+    (20261016, include_str!("migrations/20261016_create_guest_tokens_table.sql")),
 ];
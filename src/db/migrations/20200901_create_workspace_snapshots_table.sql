@@ -0,0 +1,8 @@
+CREATE TABLE workspace_snapshots (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT,
+    created_at INTEGER NOT NULL,
+    serialized TEXT NOT NULL
+);
+
+CREATE INDEX workspace_snapshots_created_at_idx ON workspace_snapshots (created_at);
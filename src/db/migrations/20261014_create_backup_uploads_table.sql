@@ -0,0 +1,4 @@
+CREATE TABLE backup_uploads (
+    key TEXT PRIMARY KEY NOT NULL,
+    uploaded_at INTEGER NOT NULL
+);
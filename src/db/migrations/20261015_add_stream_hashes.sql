@@ -0,0 +1,4 @@
+-- null until hashed, streams written before this were hashed on demand:
+ALTER TABLE streams ADD COLUMN sha256 TEXT;
+
+CREATE INDEX streams_sha256_idx ON streams (sha256);
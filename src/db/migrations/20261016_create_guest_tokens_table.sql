@@ -0,0 +1,7 @@
+CREATE TABLE guest_tokens (
+    token TEXT PRIMARY KEY NOT NULL,
+    label TEXT NOT NULL,
+    -- json encoded GuestScope:
+    scope TEXT NOT NULL,
+    expires_at INTEGER NOT NULL
+);
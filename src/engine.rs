@@ -1,33 +1,75 @@
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::f32;
+use std::mem;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
 use std::sync::mpsc::{self, SyncSender, Receiver, RecvTimeoutError, TrySendError, TryRecvError};
 use std::thread;
-use std::time::{Instant, Duration};
+use std::time::Instant;
 
+use derive_more::From;
 use futures::future;
 use futures::stream::{Stream, StreamExt};
 use tokio::runtime;
 use tokio::sync::{oneshot, broadcast, watch};
 
-use mixlab_protocol::{ModuleId, InputId, OutputId, WorkspaceState, ServerUpdate, Indication, ClientSequence, WorkspaceMessage, WorkspaceOp, PerformanceInfo};
+use mixlab_protocol::{ModuleId, InputId, OutputId, WorkspaceState, ServerUpdate, Indication, ClientSequence, WorkspaceMessage, WorkspaceOp, PerformanceInfo, MorphSlot, ClockSource, Batch, Viewport, ViewId, View, Command, ParamRef, SnapshotId, ModuleParams, ParamSearch, ParamMatch, EngineIntrospection, ZoneId, Zone};
 
+use crate::persist;
 use crate::project::ProjectBaseRef;
 use crate::util::Sequence;
 
+mod bench;
+mod capture;
+mod check;
+mod checkpoint;
+mod clock;
+mod config;
+mod devices;
+mod diagnostics;
+mod gain_staging;
+mod group;
+mod introspect;
 mod io;
+mod latency;
 mod module;
+mod modulation;
+mod morph;
+mod param_link;
+mod param_search;
+mod preview;
+mod rehearsal;
+mod schedule;
 mod timing;
 mod workspace;
+mod zones;
 
+use capture::FrameCapture;
+use checkpoint::Checkpoint;
+use clock::EngineClock;
+use gain_staging::GainAnalysis;
 use timing::{EngineStat, TickStat};
-use workspace::SyncWorkspace;
-
+use modulation::ModulationError;
+use param_link::LinkError;
+use workspace::{ConnectError, SyncWorkspace, Workspace};
+
+pub use bench::{bench, BenchReport, BenchTarget};
+pub use check::{check, CheckReport};
+pub use clock::{ClockRef, SampleClock};
+pub use config::{EngineConfig, ConfigError};
+pub use devices::{DeviceLinks, ResolvedDevice};
+pub use diagnostics::Diagnostics;
+pub use group::GroupLevels;
 pub use io::{InputRef, OutputRef, Output, VideoFrame};
 pub use module::{ModuleCtx, DynModuleHost};
+pub(crate) use module::{host_plugin, missing_plugin};
+pub use param_link::{read_param, write_param};
+pub use preview::{preview, PreviewError, PreviewRequest};
+pub use rehearsal::Rehearsal;
+pub use schedule::{Schedule, Segment};
 pub use workspace::WorkspaceEmbryo;
+pub use zones::ZoneAudio;
 
 pub type Sample = f32;
 
@@ -50,13 +92,13 @@ impl PartialOrd for OpClock {
 }
 
 pub const CHANNELS: usize = 2;
-pub const SAMPLE_RATE: usize = 44100;
-pub const TICKS_PER_SECOND: usize = 60;
-pub const SAMPLES_PER_TICK: usize = SAMPLE_RATE / TICKS_PER_SECOND;
 
 pub enum EngineMessage {
     ConnectSession(oneshot::Sender<(SessionId, WorkspaceState, EngineEvents)>),
     Workspace(SessionId, WorkspaceMessage),
+    Restore(persist::Workspace),
+    SearchParams(ParamSearch, oneshot::Sender<Vec<ParamMatch>>),
+    Introspect(oneshot::Sender<EngineIntrospection>),
 }
 
 #[derive(Clone)]
@@ -79,13 +121,22 @@ pub fn start(tokio_runtime: runtime::Handle, workspace: WorkspaceEmbryo, base: P
         // enter the tokio runtime context for the engine thread
         // this allows modules to spawn async tasks
         tokio_runtime.enter(|| {
+            let workspace = workspace.spawn(base.clone());
+            let config = workspace.borrow().config;
+            let (recall_tx, recall_rx) = mpsc::channel();
+
             let mut engine = Engine {
                 cmd_rx,
                 log_tx,
                 perf_tx,
                 session_seq: Sequence::new(),
-                workspace: workspace.spawn(base.clone()),
+                workspace,
+                config,
                 base,
+                gain_analysis: None,
+                frame_captures: Vec::new(),
+                recall_tx,
+                recall_rx,
             };
 
             engine.run();
@@ -101,6 +152,37 @@ pub enum EngineError {
     Busy,
 }
 
+// why an op from a client could not be applied
+#[derive(Debug, From)]
+enum OpError {
+    #[from(ignore)]
+    NoSuchModule(ModuleId),
+    #[from(ignore)]
+    NoMorph(ModuleId),
+    #[from(ignore)]
+    NoClock(ClockSource),
+    #[from(ignore)]
+    BadPlaceholder(ModuleId),
+    #[from(ignore)]
+    BadZoom(f64),
+    #[from(ignore)]
+    NoSuchView(ViewId),
+    #[from(ignore)]
+    NoSuchZone(ZoneId),
+    #[from(ignore)]
+    BadZoneDelay(f64),
+    #[from(ignore)]
+    NoSuchBinding(String),
+    #[from(ignore)]
+    NoSuchParam(ParamRef),
+    #[from(ignore)]
+    OutOfModuleIds,
+    NestedBatch,
+    Connect(ConnectError),
+    Link(LinkError),
+    Modulation(ModulationError),
+}
+
 impl<T> From<TrySendError<T>> for EngineError {
     fn from(e: TrySendError<T>) -> Self {
         match e {
@@ -135,6 +217,12 @@ impl EngineHandle {
     pub fn performance_info(&self) -> impl Stream<Item = Arc<PerformanceInfo>> {
         self.perf_rx.clone().filter_map(|info| future::ready(info))
     }
+
+    /// Replaces the running workspace, connected sessions are sent the new
+    /// state in full
+    pub fn restore(&self, workspace: persist::Workspace) -> Result<(), EngineError> {
+        Ok(self.cmd_tx.try_send(EngineMessage::Restore(workspace))?)
+    }
 }
 
 impl EngineSession {
@@ -147,6 +235,18 @@ impl EngineSession {
         self.send_message(EngineMessage::Workspace(self.session_id, msg))
     }
 
+    pub async fn search_params(&self, search: ParamSearch) -> Result<Vec<ParamMatch>, EngineError> {
+        let (tx, rx) = oneshot::channel();
+        self.send_message(EngineMessage::SearchParams(search, tx))?;
+        rx.await.map_err(|_| EngineError::Stopped)
+    }
+
+    pub async fn introspect(&self) -> Result<EngineIntrospection, EngineError> {
+        let (tx, rx) = oneshot::channel();
+        self.send_message(EngineMessage::Introspect(tx))?;
+        rx.await.map_err(|_| EngineError::Stopped)
+    }
+
     fn send_message(&self, msg: EngineMessage) -> Result<(), EngineError> {
         Ok(self.cmd_tx.try_send(msg)?)
     }
@@ -158,21 +258,25 @@ pub struct Engine {
     perf_tx: watch::Sender<Option<Arc<PerformanceInfo>>>,
     session_seq: Sequence,
     workspace: SyncWorkspace,
+    config: EngineConfig,
     base: ProjectBaseRef,
+    gain_analysis: Option<GainAnalysis>,
+    frame_captures: Vec<FrameCapture>,
+    recall_tx: mpsc::Sender<persist::Workspace>,
+    recall_rx: Receiver<persist::Workspace>,
 }
 
 impl Engine {
     fn run(&mut self) {
-        let start = Instant::now();
-        let mut stat = EngineStat::new();
+        let mut clock = EngineClock::new(self.config);
+        let mut stat = EngineStat::new(self.config);
         let mut tick = 0;
 
         loop {
             let this_tick = tick;
             tick += 1;
 
-            // we don't simply calculate `tick * TICK_BUDGET` here to prevent loss of precision over time:
-            let scheduled_tick_end = start + Duration::from_millis((tick * 1_000) / TICKS_PER_SECOND as u64);
+            let scheduled_tick_end = clock.tick_end(tick);
 
             // run tick
             let indications = stat.record_tick(scheduled_tick_end,
@@ -184,9 +288,40 @@ impl Engine {
                 self.log_op(ServerUpdate::UpdateModuleIndication(module_id, indication));
             }
 
+            // and any diagnostics modules have reported or cleared since
+            let diagnostics = self.workspace.borrow().modules.iter()
+                .filter_map(|(module_id, module)| Some((*module_id, module.diagnostics().take_changed()?)))
+                .collect::<Vec<_>>();
+
+            for (module_id, diagnostics) in diagnostics {
+                self.log_op(ServerUpdate::UpdateModuleDiagnostics(module_id, diagnostics));
+            }
+
+            // report on gain staging once enough has been measured
+            let analysis_done = self.gain_analysis.as_mut()
+                .map(|analysis| analysis.tick())
+                .unwrap_or(false);
+
+            if analysis_done {
+                let analysis = self.gain_analysis.take().unwrap();
+                self.finish_gain_analysis(analysis);
+            }
+
+            // follow the selected clock from the next tick on
+            {
+                let workspace = self.workspace.borrow();
+                clock.sync(tick, workspace.clock_source, &workspace.modules);
+            }
+
             // send out performance metrics
-            if (this_tick % (TICKS_PER_SECOND as u64 / 2)) == 0 {
-                let _ = self.perf_tx.broadcast(Some(Arc::new(stat.report())));
+            if (this_tick % u64::max(1, self.config.ticks_per_second() as u64 / 2)) == 0 {
+                let clock_info = clock.info(&self.workspace.borrow().modules);
+                let _ = self.perf_tx.broadcast(Some(Arc::new(stat.report(clock_info))));
+            }
+
+            // restore any snapshots recalled by a binding
+            while let Ok(restored) = self.recall_rx.try_recv() {
+                self.restore(restored, &mut stat);
             }
 
             // process all waiting commands immediately
@@ -198,15 +333,18 @@ impl Engine {
                 }
             }
 
-            // wait for next tick and process commands while waiting
+            // wait for next tick and process commands while waiting. the
+            // clock may have moved the deadline since the tick started
+            let next_tick_due = clock.tick_end(tick);
+
             loop {
                 let now = Instant::now();
 
-                if now >= scheduled_tick_end {
+                if now >= next_tick_due {
                     break;
                 }
 
-                match self.cmd_rx.recv_timeout(scheduled_tick_end - now) {
+                match self.cmd_rx.recv_timeout(next_tick_due - now) {
                     Ok(msg) => { self.process_message(msg, &mut stat); }
                     Err(RecvTimeoutError::Timeout) => { break; }
                     Err(RecvTimeoutError::Disconnected) => { return; }
@@ -223,9 +361,40 @@ impl Engine {
             EngineMessage::Workspace(session, msg) => {
                 self.client_update(session, msg, stat);
             }
+            EngineMessage::Restore(workspace) => {
+                self.restore(workspace, stat);
+            }
+            EngineMessage::SearchParams(search, tx) => {
+                let _ = tx.send(param_search::search(&search, &self.workspace.borrow()));
+            }
+            EngineMessage::Introspect(tx) => {
+                let _ = tx.send(introspect::introspect(self.workspace.borrow()));
+            }
         }
     }
 
+    fn restore(&mut self, mut save: persist::Workspace, stat: &mut EngineStat) {
+        // engine settings are fixed for the lifetime of the engine
+        save.config = self.config;
+
+        {
+            let mut workspace = self.workspace.borrow_mut();
+
+            for module_id in workspace.modules.keys() {
+                stat.remove_module(*module_id);
+            }
+
+            *workspace = Workspace::from_persist(&save, self.base.clone());
+        }
+
+        // any analysis or capture in progress was of the old workspace
+        self.gain_analysis = None;
+        self.frame_captures.clear();
+
+        let state = self.dump_state();
+        self.log_op(ServerUpdate::ReplaceWorkspace(state));
+    }
+
     fn connect_session(&mut self) -> (SessionId, WorkspaceState, EngineEvents) {
         let session_id = SessionId(self.session_seq.next());
         let log_rx = self.log_tx.subscribe();
@@ -241,14 +410,36 @@ impl Engine {
             connections: Vec::new(),
             inputs: Vec::new(),
             outputs: Vec::new(),
+            param_links: Vec::new(),
+            morphs: Vec::new(),
+            modulations: Vec::new(),
+            clock_source: ClockSource::Internal,
+            rehearsal: false,
+            viewport: Viewport::default(),
+            views: Vec::new(),
+            bindings: Vec::new(),
+            device_links: Vec::new(),
+            zones: Vec::new(),
+            favorite_params: Vec::new(),
+            param_specs: Vec::new(),
+            diagnostics: Vec::new(),
+            plugins: crate::plugin::catalog(),
         };
 
         let workspace = self.workspace.borrow();
 
         for (module_id, module) in &workspace.modules {
-            state.modules.push((*module_id, module.params()));
+            let params = workspace.params(*module_id).unwrap_or_else(|| module.params());
+            state.modules.push((*module_id, params));
             state.inputs.push((*module_id, module.inputs().to_vec()));
             state.outputs.push((*module_id, module.outputs().to_vec()));
+            state.param_specs.push((*module_id, module.param_specs()));
+
+            let diagnostics = module.diagnostics().current();
+
+            if !diagnostics.is_empty() {
+                state.diagnostics.push((*module_id, diagnostics));
+            }
         }
 
         for (module_id, geometry) in &workspace.geometry {
@@ -263,6 +454,40 @@ impl Engine {
             state.connections.push((*input, *output));
         }
 
+        for (link_id, link) in &workspace.param_links {
+            state.param_links.push((*link_id, link.clone()));
+        }
+
+        for (module_id, morph) in &workspace.morphs {
+            state.morphs.push((*module_id, morph.clone()));
+        }
+
+        for (modulation_id, modulation) in &workspace.modulations {
+            state.modulations.push((*modulation_id, modulation.clone()));
+        }
+
+        state.clock_source = workspace.clock_source;
+        state.rehearsal = workspace.rehearsal.active();
+        state.viewport = workspace.viewport.clone();
+
+        for (view_id, view) in &workspace.views {
+            state.views.push((*view_id, view.clone()));
+        }
+
+        for (name, binding) in &workspace.bindings {
+            state.bindings.push((name.clone(), binding.clone()));
+        }
+
+        state.device_links = workspace.devices.to_map().into_iter().collect();
+
+        for (zone_id, zone) in &workspace.zones {
+            state.zones.push((*zone_id, zone.clone()));
+        }
+
+        for (module_id, paths) in &workspace.favorite_params {
+            state.favorite_params.push((*module_id, paths.clone()));
+        }
+
         state
     }
 
@@ -277,67 +502,97 @@ impl Engine {
     fn client_update(&mut self, session_id: SessionId, msg: WorkspaceMessage, stat: &mut EngineStat) {
         let clock = OpClock(session_id, msg.sequence);
 
-        match msg.op {
+        match self.apply_op(msg.op, stat) {
+            Ok(operations) => {
+                for op in operations {
+                    self.log_op(op);
+                }
+
+                // modules may have been created or changed shape:
+                let changed_specs = self.workspace.borrow_mut_without_sync().sync_param_specs();
+
+                for (module_id, specs) in changed_specs {
+                    self.log_op(ServerUpdate::UpdateParamSpecs(module_id, specs));
+                }
+            }
+            Err(e) => {
+                eprintln!("engine: could not apply op: {:?}", e);
+            }
+        }
+
+        return self.sync_log(clock);
+    }
+
+    // applies an op to the workspace, returning the updates to send out to
+    // sessions. nothing is logged here so that batches can hold back their
+    // updates until every op has succeeded
+    fn apply_op(&mut self, op: WorkspaceOp, stat: &mut EngineStat) -> Result<Vec<ServerUpdate>, OpError> {
+        let mut operations = Vec::new();
+
+        match op {
             WorkspaceOp::CreateModule(params, geometry) => {
                 // TODO - the audio engine is not actually concerned with
                 // window geometry and so should not own this data and force
                 // all accesses to it to go via the live audio thread
-                let op = {
-                    let mut workspace = self.workspace.borrow_mut();
-                    let id = ModuleId(workspace.module_seq.next());
-                    let (module, indication) = module::host(params.clone(), self.base.clone());
-                    let inputs = module.inputs().to_vec();
-                    let outputs = module.outputs().to_vec();
-                    workspace.modules.insert(id, module);
-                    workspace.geometry.insert(id, geometry.clone());
-                    workspace.indications.insert(id, indication.clone());
-
-                    ServerUpdate::CreateModule {
-                        id,
-                        params,
-                        geometry,
-                        indication,
-                        inputs,
-                        outputs,
-                    }
-                };
+                let mut workspace = self.workspace.borrow_mut();
+                let id = ModuleId(workspace.module_seq.next());
 
-                self.log_op(op);
+                // past here ids would be taken for placeholders:
+                if id.is_placeholder() {
+                    return Err(OpError::OutOfModuleIds);
+                }
+
+                let (module, indication) = module::host(params.clone(), self.base.clone(), self.config, workspace.groups.clone(), workspace.devices.clone(), workspace.rehearsal.clone(), workspace.zone_audio.clone());
+                let inputs = module.inputs().to_vec();
+                let outputs = module.outputs().to_vec();
+                workspace.groups.sync(id, Some(&params));
+                workspace.modules.insert(id, module);
+                workspace.geometry.insert(id, geometry.clone());
+                workspace.indications.insert(id, indication.clone());
+
+                operations.push(ServerUpdate::CreateModule {
+                    id,
+                    params,
+                    geometry,
+                    indication,
+                    inputs,
+                    outputs,
+                });
             }
             WorkspaceOp::UpdateModuleParams(module_id, params) => {
-                let op = {
-                    let mut workspace = self.workspace.borrow_mut();
+                let mut workspace = self.workspace.borrow_mut();
 
-                    workspace.modules.get_mut(&module_id).map(|module| {
-                        module.update(params.clone());
-                        ServerUpdate::UpdateModuleParams(module_id, module.params())
-                    })
-                };
+                let groups = workspace.groups.clone();
 
-                if let Some(op) = op {
-                    self.log_op(op);
+                let module = workspace.modules.get_mut(&module_id)
+                    .ok_or(OpError::NoSuchModule(module_id))?;
+
+                module.update(params);
+                let params = module.params();
+                groups.sync(module_id, Some(&params));
+                operations.push(ServerUpdate::UpdateModuleParams(module_id, params));
+
+                for (linked_id, linked_params) in workspace.propagate_params(module_id) {
+                    operations.push(ServerUpdate::UpdateModuleParams(linked_id, linked_params));
                 }
             }
             WorkspaceOp::UpdateWindowGeometry(module_id, geometry) => {
-                let op = {
-                    let mut workspace = self.workspace.borrow_mut();
+                let mut workspace = self.workspace.borrow_mut();
 
-                    workspace.geometry.get_mut(&module_id).map(|geom| {
-                        *geom = geometry.clone();
-                        ServerUpdate::UpdateWindowGeometry(module_id, geometry)
-                    })
-                };
+                let geom = workspace.geometry.get_mut(&module_id)
+                    .ok_or(OpError::NoSuchModule(module_id))?;
 
-                if let Some(op) = op {
-                    self.log_op(op);
-                }
+                *geom = geometry.clone();
+                operations.push(ServerUpdate::UpdateWindowGeometry(module_id, geometry));
             }
             WorkspaceOp::DeleteModule(module_id) => {
-                let mut operations = Vec::new();
-
                 {
                     let mut workspace = self.workspace.borrow_mut();
 
+                    if !workspace.modules.contains_key(&module_id) {
+                        return Err(OpError::NoSuchModule(module_id));
+                    }
+
                     // find any connections connected to this module's inputs or
                     // outputs and delete them, generating oplog entries
 
@@ -354,132 +609,583 @@ impl Engine {
                         operations.push(ServerUpdate::DeleteConnection(deleted_connection));
                     }
 
-                    // finally, delete the module:
+                    for deleted_link in workspace.unlink_module(module_id) {
+                        operations.push(ServerUpdate::DeleteParamLink(deleted_link));
+                    }
 
-                    if workspace.modules.contains_key(&module_id) {
-                        workspace.modules.remove(&module_id);
-                        operations.push(ServerUpdate::DeleteModule(module_id));
+                    for deleted_modulation in workspace.unmodulate_module(module_id) {
+                        operations.push(ServerUpdate::DeleteModulation(deleted_modulation));
                     }
-                }
 
-                for op in operations {
-                    self.log_op(op);
+                    if workspace.clock_source == ClockSource::Module(module_id) {
+                        workspace.clock_source = ClockSource::Internal;
+                        operations.push(ServerUpdate::SetClockSource(ClockSource::Internal));
+                    }
+
+                    for (view_id, view) in &mut workspace.views {
+                        if view.remove_module(module_id) {
+                            operations.push(ServerUpdate::UpdateView(*view_id, Some(view.clone())));
+                        }
+                    }
+
+                    for (zone_id, zone) in &mut workspace.zones {
+                        if zone.source.map(|source| source.module_id()) == Some(module_id) {
+                            zone.source = None;
+                            operations.push(ServerUpdate::UpdateZone(*zone_id, Some(zone.clone())));
+                        }
+                    }
+
+                    // finally, delete the module:
+
+                    workspace.modules.remove(&module_id);
+                    workspace.morphs.remove(&module_id);
+                    workspace.favorite_params.remove(&module_id);
+                    workspace.groups.sync(module_id, None);
+                    operations.push(ServerUpdate::DeleteModule(module_id));
                 }
 
                 stat.remove_module(module_id);
             }
             WorkspaceOp::CreateConnection(input_id, output_id) => {
-                let previous = self.workspace.borrow_mut().connect(input_id, output_id);
+                // client should have guarded against a type mismatched
+                // connection, this only fails for a misbehaving client
+                let old_output = self.workspace.borrow_mut().connect(input_id, output_id)?;
 
-                match previous {
-                    Ok(old_output) => {
-                        if let Some(_) = old_output {
-                            self.log_op(ServerUpdate::DeleteConnection(input_id));
-                        }
+                if let Some(_) = old_output {
+                    operations.push(ServerUpdate::DeleteConnection(input_id));
+                }
+
+                operations.push(ServerUpdate::CreateConnection(input_id, output_id));
+            }
+            WorkspaceOp::DeleteConnection(input_id) => {
+                let previous = self.workspace.borrow_mut().disconnect(input_id);
+
+                if let Some(_) = previous {
+                    operations.push(ServerUpdate::DeleteConnection(input_id));
+                }
+            }
+            WorkspaceOp::AnalyzeGainStaging(request) => {
+                // starting a new analysis discards any already in progress
+                self.gain_analysis = Some(GainAnalysis::new(request, self.config));
+            }
+            WorkspaceOp::CaptureFrame(output_id) => {
+                self.frame_captures.push(FrameCapture::new(output_id, self.config));
+            }
+            WorkspaceOp::CreateParamLink(link) => {
+                let mut workspace = self.workspace.borrow_mut();
+
+                let link_id = workspace.link_params(link.clone())?;
+                let source = link.source.module;
+                operations.push(ServerUpdate::CreateParamLink(link_id, link));
+
+                // bring the target in line with the source straight away
+                for (linked_id, linked_params) in workspace.propagate_params(source) {
+                    operations.push(ServerUpdate::UpdateModuleParams(linked_id, linked_params));
+                }
+            }
+            WorkspaceOp::DeleteParamLink(link_id) => {
+                let previous = self.workspace.borrow_mut().unlink_params(link_id);
+
+                if let Some(_) = previous {
+                    operations.push(ServerUpdate::DeleteParamLink(link_id));
+                }
+            }
+            WorkspaceOp::StoreMorphState(module_id, slot) => {
+                let mut workspace = self.workspace.borrow_mut();
 
-                        self.log_op(ServerUpdate::CreateConnection(input_id, output_id));
+                let params = workspace.params(module_id)
+                    .ok_or(OpError::NoSuchModule(module_id))?;
+
+                let morph = workspace.morphs.entry(module_id).or_default();
+
+                match slot {
+                    MorphSlot::A => { morph.a = Some(params); }
+                    MorphSlot::B => { morph.b = Some(params); }
+                }
+
+                operations.push(ServerUpdate::UpdateMorph(module_id, Some(morph.clone())));
+            }
+            WorkspaceOp::SetMorph(module_id, position) => {
+                let mut workspace = self.workspace.borrow_mut();
+
+                let morph = workspace.morphs.get_mut(&module_id)
+                    .ok_or(OpError::NoMorph(module_id))?;
+
+                morph.position = f64::max(0.0, f64::min(1.0, position));
+                operations.push(ServerUpdate::UpdateMorph(module_id, Some(morph.clone())));
+                let params = morph::params_at(morph);
+
+                let groups = workspace.groups.clone();
+
+                let op = params.and_then(|params| {
+                    workspace.modules.get_mut(&module_id).map(|module| {
+                        module.update(params);
+                        let params = module.params();
+                        groups.sync(module_id, Some(&params));
+                        ServerUpdate::UpdateModuleParams(module_id, params)
+                    })
+                });
+
+                if let Some(op) = op {
+                    operations.push(op);
+
+                    for (linked_id, linked_params) in workspace.propagate_params(module_id) {
+                        operations.push(ServerUpdate::UpdateModuleParams(linked_id, linked_params));
                     }
-                    Err(_) => {
-                        // client should have guarded against a type mismatched
-                        // connection, just drop
+                }
+            }
+            WorkspaceOp::ClearMorph(module_id) => {
+                let previous = self.workspace.borrow_mut().morphs.remove(&module_id);
+
+                if let Some(_) = previous {
+                    operations.push(ServerUpdate::UpdateMorph(module_id, None));
+                }
+            }
+            WorkspaceOp::CreateModulation(modulation) => {
+                let modulation_id = self.workspace.borrow_mut().modulate(modulation.clone())?;
+                operations.push(ServerUpdate::CreateModulation(modulation_id, modulation));
+            }
+            WorkspaceOp::DeleteModulation(modulation_id) => {
+                let previous = self.workspace.borrow_mut().unmodulate(modulation_id);
+
+                if let Some(_) = previous {
+                    operations.push(ServerUpdate::DeleteModulation(modulation_id));
+                }
+            }
+            WorkspaceOp::SetClockSource(source) => {
+                let valid = match source {
+                    ClockSource::Internal => true,
+                    ClockSource::Module(module_id) => {
+                        self.workspace.borrow().modules.get(&module_id)
+                            .map(|module| module.clock().is_some())
+                            .unwrap_or(false)
                     }
+                };
+
+                if !valid {
+                    return Err(OpError::NoClock(source));
+                }
+
+                if self.workspace.borrow().clock_source != source {
+                    self.workspace.borrow_mut().clock_source = source;
+                    operations.push(ServerUpdate::SetClockSource(source));
                 }
             }
-            WorkspaceOp::DeleteConnection(input_id) => {
-                let previous = self.workspace.borrow_mut().disconnect(input_id);
+            WorkspaceOp::SetRehearsal(rehearsal) => {
+                let workspace = self.workspace.borrow();
+
+                // modules pick the change up on their next tick:
+                if workspace.rehearsal.active() != rehearsal {
+                    workspace.rehearsal.set(rehearsal);
+                    operations.push(ServerUpdate::SetRehearsal(rehearsal));
+                }
+            }
+            WorkspaceOp::UpdateViewport(viewport) => {
+                check_zoom(&viewport)?;
+
+                if self.workspace.borrow().viewport != viewport {
+                    self.workspace.borrow_mut().viewport = viewport.clone();
+                    operations.push(ServerUpdate::UpdateViewport(viewport));
+                }
+            }
+            WorkspaceOp::CreateView(name) => {
+                let mut workspace = self.workspace.borrow_mut();
+
+                let view_id = ViewId(workspace.view_seq.next());
+
+                let view = View {
+                    name,
+                    geometry: workspace.geometry.iter()
+                        .map(|(module_id, geometry)| (*module_id, geometry.clone()))
+                        .collect(),
+                    viewport: workspace.viewport.clone(),
+                    pinned: Vec::new(),
+                };
+
+                workspace.views.insert(view_id, view.clone());
+                operations.push(ServerUpdate::UpdateView(view_id, Some(view)));
+            }
+            WorkspaceOp::RenameView(view_id, name) => {
+                let mut workspace = self.workspace.borrow_mut();
+
+                let view = workspace.views.get_mut(&view_id)
+                    .ok_or(OpError::NoSuchView(view_id))?;
+
+                view.name = name;
+                operations.push(ServerUpdate::UpdateView(view_id, Some(view.clone())));
+            }
+            WorkspaceOp::DeleteView(view_id) => {
+                let previous = self.workspace.borrow_mut().views.remove(&view_id);
 
                 if let Some(_) = previous {
-                    self.log_op(ServerUpdate::DeleteConnection(input_id));
+                    operations.push(ServerUpdate::UpdateView(view_id, None));
                 }
             }
-        }
+            WorkspaceOp::UpdateViewGeometry(view_id, module_id, geometry) => {
+                let mut workspace = self.workspace.borrow_mut();
 
-        return self.sync_log(clock);
-    }
+                if !workspace.modules.contains_key(&module_id) {
+                    return Err(OpError::NoSuchModule(module_id));
+                }
 
-    fn run_tick(&mut self, tick: u64, stat: &mut TickStat) -> Vec<(ModuleId, Indication)> {
-        // tick is not allowed to update any persisted information such as
-        // module params or connections
-        let workspace = self.workspace.borrow_mut_without_sync();
+                let view = workspace.views.get_mut(&view_id)
+                    .ok_or(OpError::NoSuchView(view_id))?;
+
+                view.set_geometry(module_id, geometry.clone());
+                operations.push(ServerUpdate::UpdateViewGeometry(view_id, module_id, geometry));
+            }
+            WorkspaceOp::UpdateViewViewport(view_id, viewport) => {
+                check_zoom(&viewport)?;
 
-        // find terminal modules - modules which do not send their output to
-        // the input of any other module
+                let mut workspace = self.workspace.borrow_mut();
 
-        let mut terminal_modules = HashSet::new();
+                let view = workspace.views.get_mut(&view_id)
+                    .ok_or(OpError::NoSuchView(view_id))?;
 
-        for (id, _) in &workspace.modules {
-            terminal_modules.insert(*id);
-        }
+                if view.viewport != viewport {
+                    view.viewport = viewport;
+                    operations.push(ServerUpdate::UpdateView(view_id, Some(view.clone())));
+                }
+            }
+            WorkspaceOp::PinMeter(view_id, module_id, pinned) => {
+                let mut workspace = self.workspace.borrow_mut();
+
+                if !workspace.modules.contains_key(&module_id) {
+                    return Err(OpError::NoSuchModule(module_id));
+                }
+
+                let view = workspace.views.get_mut(&view_id)
+                    .ok_or(OpError::NoSuchView(view_id))?;
+
+                let was_pinned = view.pinned.contains(&module_id);
+
+                if pinned && !was_pinned {
+                    view.pinned.push(module_id);
+                } else if !pinned && was_pinned {
+                    view.pinned.retain(|id| *id != module_id);
+                }
+
+                if pinned != was_pinned {
+                    operations.push(ServerUpdate::UpdateView(view_id, Some(view.clone())));
+                }
+            }
+            WorkspaceOp::SetBinding(name, Some(binding)) => {
+                self.workspace.borrow_mut().bindings.insert(name.clone(), binding.clone());
+                operations.push(ServerUpdate::UpdateBinding(name, Some(binding)));
+            }
+            WorkspaceOp::SetBinding(name, None) => {
+                let previous = self.workspace.borrow_mut().bindings.remove(&name);
+
+                if let Some(_) = previous {
+                    operations.push(ServerUpdate::UpdateBinding(name, None));
+                }
+            }
+            WorkspaceOp::InvokeBinding(name) => {
+                let binding = self.workspace.borrow().bindings.get(&name).cloned()
+                    .ok_or(OpError::NoSuchBinding(name))?;
+
+                operations = self.run_command(binding.command, stat)?;
+            }
+            WorkspaceOp::LinkDevice(device, local) => {
+                let mut workspace = self.workspace.borrow_mut();
+                workspace.devices.link(device.clone(), local.clone());
+
+                // device modules resolve their devices again the next time
+                // params are applied after links change, so reapplying them
+                // picks up the new link:
+                for module in workspace.modules.values_mut() {
+                    let params = module.params();
+
+                    if let ModuleParams::InputDevice(_) | ModuleParams::OutputDevice(_) = params {
+                        module.update(params);
+                    }
+                }
+
+                operations.push(ServerUpdate::UpdateDeviceLink(device, local));
+            }
+            WorkspaceOp::CreateZone(name) => {
+                let mut workspace = self.workspace.borrow_mut();
+
+                let zone_id = ZoneId(workspace.zone_seq.next());
+                let zone = Zone { name, ..Zone::default() };
+
+                workspace.zones.insert(zone_id, zone.clone());
+                workspace.zone_audio.create(zone_id, self.config);
+                operations.push(ServerUpdate::UpdateZone(zone_id, Some(zone)));
+            }
+            WorkspaceOp::UpdateZone(zone_id, zone) => {
+                if !(0.0..=zones::MAX_DELAY_MS).contains(&zone.delay_ms) {
+                    return Err(OpError::BadZoneDelay(zone.delay_ms));
+                }
+
+                let mut workspace = self.workspace.borrow_mut();
+
+                if let Some(source) = zone.source {
+                    if !workspace.modules.contains_key(&source.module_id()) {
+                        return Err(OpError::NoSuchModule(source.module_id()));
+                    }
+                }
+
+                let current = workspace.zones.get_mut(&zone_id)
+                    .ok_or(OpError::NoSuchZone(zone_id))?;
+
+                if *current != zone {
+                    *current = zone.clone();
+                    operations.push(ServerUpdate::UpdateZone(zone_id, Some(zone)));
+                }
+            }
+            WorkspaceOp::DeleteZone(zone_id) => {
+                let mut workspace = self.workspace.borrow_mut();
+
+                if let Some(_) = workspace.zones.remove(&zone_id) {
+                    workspace.zone_audio.remove(zone_id);
+                    operations.push(ServerUpdate::UpdateZone(zone_id, None));
+                }
+            }
+            WorkspaceOp::SetParam(param, value) => {
+                operations = self.run_command(Command::SetParam(param, value), stat)?;
+            }
+            WorkspaceOp::FavoriteParam(param, favorite) => {
+                self.param_value(&param)?;
+
+                let mut workspace = self.workspace.borrow_mut();
+                let paths = workspace.favorite_params.entry(param.module).or_default();
+                let was_favorite = paths.contains(&param.path);
+
+                if favorite && !was_favorite {
+                    paths.push(param.path.clone());
+                } else if !favorite && was_favorite {
+                    paths.retain(|path| *path != param.path);
+                }
+
+                if favorite != was_favorite {
+                    operations.push(ServerUpdate::UpdateFavoriteParams(param.module, paths.clone()));
+                }
 
-        for (_, output) in &workspace.connections {
-            terminal_modules.remove(&output.module_id());
+                if paths.is_empty() {
+                    workspace.favorite_params.remove(&param.module);
+                }
+            }
+            WorkspaceOp::Batch(batch) => {
+                operations = self.apply_batch(batch, stat)?;
+            }
         }
 
-        // depth-first-search modules out via their inputs, starting from
-        // terminal modules
+        Ok(operations)
+    }
 
-        let mut topsort = Topsort {
-            modules: &workspace.modules,
-            connections: &workspace.connections,
-            run_order: Vec::new(),
-            seen: HashSet::new(),
+    fn run_command(&mut self, command: Command, stat: &mut EngineStat) -> Result<Vec<ServerUpdate>, OpError> {
+        let (param, value) = match command {
+            Command::SetMorph(module_id, position) => {
+                return self.apply_op(WorkspaceOp::SetMorph(module_id, position), stat);
+            }
+            Command::RestoreSnapshot(snapshot_id) => {
+                self.recall_snapshot(snapshot_id);
+                return Ok(Vec::new());
+            }
+            Command::ToggleParam(param) => {
+                let current = self.param_value(&param)?;
+                (param, if current >= 0.5 { 0.0 } else { 1.0 })
+            }
+            Command::BumpParam(param) => {
+                let current = self.param_value(&param)?;
+                (param, current + 1.0)
+            }
+            Command::SetParam(param, value) => (param, value),
         };
 
-        for id in terminal_modules.into_iter() {
-            traverse(id, &mut topsort);
+        let params = self.workspace.borrow().params(param.module)
+            .ok_or(OpError::NoSuchModule(param.module))?;
+
+        match param_link::write_param(&params, &param.path, value) {
+            Some(params) => self.apply_op(WorkspaceOp::UpdateModuleParams(param.module, params), stat),
+            // already set to this value, or no such param:
+            None => {
+                self.param_value(&param)?;
+                Ok(Vec::new())
+            }
         }
+    }
 
-        struct Topsort<'a> {
-            modules: &'a HashMap<ModuleId, DynModuleHost>,
-            connections: &'a HashMap<InputId, OutputId>,
-            run_order: Vec<ModuleId>,
-            seen: HashSet<ModuleId>,
+    fn param_value(&self, param: &ParamRef) -> Result<f64, OpError> {
+        let params = self.workspace.borrow().params(param.module)
+            .ok_or(OpError::NoSuchModule(param.module))?;
+
+        param_link::read_param(&params, &param.path)
+            .ok_or_else(|| OpError::NoSuchParam(param.clone()))
+    }
+
+    // snapshots live in the project database, so are loaded off the engine
+    // thread and handed back to restore between ticks
+    fn recall_snapshot(&self, snapshot_id: SnapshotId) {
+        let base = self.base.clone();
+        let recall_tx = self.recall_tx.clone();
+        let current = self.workspace.borrow().to_persist();
+
+        tokio::spawn(async move {
+            match base.recall_snapshot(snapshot_id, &current).await {
+                Ok(restored) => { let _ = recall_tx.send(restored); }
+                Err(e) => { eprintln!("engine: could not recall snapshot {}: {:?}", snapshot_id.0, e); }
+            }
+        });
+    }
+
+    fn apply_batch(&mut self, batch: Batch, stat: &mut EngineStat) -> Result<Vec<ServerUpdate>, OpError> {
+        // placeholders come out of an id range of their own, so that none
+        // can be mistaken for a module created earlier in the batch:
+        if let Some(placeholder) = batch.placeholders.iter().find(|id| !id.is_placeholder()) {
+            return Err(OpError::BadPlaceholder(*placeholder));
         }
 
-        fn traverse(module_id: ModuleId, state: &mut Topsort) {
-            if state.seen.contains(&module_id) {
-                return;
+        let mut checkpoint = Checkpoint::take(&self.workspace.borrow());
+        let mut placeholders = batch.placeholders.into_iter();
+        let mut created = HashMap::new();
+        let mut operations = Vec::new();
+
+        for op in batch.ops {
+            // placeholders not yet created are left as they are, and so
+            // refer to no module at all
+            let op = op.map_modules(&|id| created.get(&id).copied().unwrap_or(id));
+            checkpoint.touch(&self.workspace.borrow(), &op);
+
+            let result = match op {
+                WorkspaceOp::Batch(_) => Err(OpError::NestedBatch),
+                WorkspaceOp::CreateModule(..) => {
+                    let placeholder = placeholders.next();
+
+                    self.apply_op(op, stat).map(|updates| {
+                        for update in &updates {
+                            if let (Some(placeholder), ServerUpdate::CreateModule { id, .. }) = (placeholder, update) {
+                                created.insert(placeholder, *id);
+                            }
+                        }
+
+                        updates
+                    })
+                }
+                op => self.apply_op(op, stat),
+            };
+
+            match result {
+                Ok(updates) => {
+                    operations.extend(updates);
+                }
+                Err(e) => {
+                    // none of the batch's updates have been sent out yet,
+                    // so putting back what the batch changed leaves clients
+                    // in step without telling them anything
+                    if !operations.is_empty() {
+                        let created = checkpoint.rollback(&mut self.workspace.borrow_mut(), self.base.clone());
+
+                        for module_id in created {
+                            stat.remove_module(module_id);
+                        }
+                    }
+
+                    return Err(e);
+                }
             }
+        }
+
+        Ok(operations)
+    }
+
+    fn finish_gain_analysis(&mut self, analysis: GainAnalysis) {
+        let apply = analysis.apply();
 
-            state.seen.insert(module_id);
+        let report = {
+            let workspace = self.workspace.borrow();
+            analysis.finish(&workspace.modules, &workspace.connections)
+        };
 
-            let module = &state.modules[&module_id];
+        if apply {
+            let mut operations = Vec::new();
+
+            {
+                let mut workspace = self.workspace.borrow_mut();
 
-            for i in 0..module.inputs().len() {
-                let terminal_id = InputId(module_id, i);
+                for (module_id, params) in gain_staging::apply_report(&report, &workspace.modules) {
+                    if let Some(module) = workspace.modules.get_mut(&module_id) {
+                        module.update(params);
+                        operations.push(ServerUpdate::UpdateModuleParams(module_id, module.params()));
 
-                if let Some(output_id) = state.connections.get(&terminal_id) {
-                    traverse(output_id.module_id(), state);
+                        for (linked_id, linked_params) in workspace.propagate_params(module_id) {
+                            operations.push(ServerUpdate::UpdateModuleParams(linked_id, linked_params));
+                        }
+                    }
                 }
             }
 
-            state.run_order.push(module_id);
+            for op in operations {
+                self.log_op(op);
+            }
         }
 
-        // run modules in dependency order according to BFS above
+        self.log_op(ServerUpdate::GainStagingReport(report));
+    }
+
+    fn run_tick(&mut self, tick: u64, stat: &mut TickStat) -> Vec<(ModuleId, Indication)> {
+        self.run_tick_with(tick, stat, |_| {})
+    }
+
+    // as run_tick, handing the tick's output buffers to `tap` once every
+    // module has run
+    fn run_tick_with(&mut self, tick: u64, stat: &mut TickStat, tap: impl FnOnce(&HashMap<OutputId, Output>)) -> Vec<(ModuleId, Indication)> {
+        // tick is not allowed to update any persisted information such as
+        // module params or connections
+        let workspace = self.workspace.borrow_mut_without_sync();
+        let block_size = self.config.block_size;
+
+        // modulation is applied with the control levels of the previous tick,
+        // as modulation sources need not run before their targets
+        let mut indications = workspace.apply_modulations();
+
+        // run modules in dependency order
 
         let mut buffers = HashMap::<OutputId, Output>::new();
-        let mut indications = Vec::new();
 
-        for module_id in topsort.run_order.iter() {
+        for module_id in workspace.run_order().iter() {
             let module = workspace.modules.get_mut(&module_id)
                 .expect("module get_mut");
 
             let connections = &workspace.connections;
+            let compensation = &mut workspace.latency;
 
             let mut output_buffers = module.outputs().iter()
-                .map(|output| Output::from_line_type(output.line_type()))
+                .map(|output| Output::from_line_type(output.line_type(), block_size))
                 .collect::<Vec<_>>();
 
             {
-                let input_refs = module.inputs().iter()
+                let sources = (0..module.inputs().len())
+                    .map(|i| connections.get(&InputId(*module_id, i)).map(|output_id| output_id.module_id()))
+                    .collect::<Vec<_>>();
+
+                let delays = compensation.align(*module_id, &sources, module.latency());
+
+                let connected = module.inputs().iter()
                     .enumerate()
-                    .map(|(i, _ty)| InputId(*module_id, i))
-                    .map(|input_id| {
-                        connections.get(&input_id)
-                            .and_then(|output_id| buffers.get(output_id))
+                    .map(|(i, terminal)| {
+                        let output = connections.get(&InputId(*module_id, i))
+                            .and_then(|output_id| buffers.get(output_id));
+
+                        // mono <-> stereo connections need converting first
+                        let converted = output.and_then(|output| output.convert_for(terminal.line_type()));
+
+                        // then held back to line up with slower inputs
+                        let delayed = converted.as_ref().or(output)
+                            .and_then(|output| compensation.delay(InputId(*module_id, i), delays[i], output));
+
+                        (output, converted, delayed)
+                    })
+                    .collect::<Vec<_>>();
+
+                let input_refs = connected.iter()
+                    .map(|(output, converted, delayed)| {
+                        delayed.as_ref()
+                            .or(converted.as_ref())
+                            .or(*output)
                             .map(|output| output.as_input_ref())
-                            .unwrap_or(InputRef::Disconnected)
+                            .unwrap_or(InputRef::Disconnected(block_size))
                     })
                     .collect::<Vec<_>>();
 
@@ -487,7 +1193,7 @@ impl Engine {
                     .map(|output| output.as_output_ref())
                     .collect::<Vec<_>>();
 
-                let t = tick * SAMPLES_PER_TICK as u64;
+                let t = tick * block_size as u64;
 
                 let result = stat.record_module(*module_id, || {
                     module.run_tick(t, &input_refs, &mut output_refs)
@@ -502,10 +1208,34 @@ impl Engine {
             }
 
             for (i, output) in output_buffers.into_iter().enumerate() {
+                if let Some(analysis) = self.gain_analysis.as_mut() {
+                    analysis.measure(OutputId(*module_id, i), &output);
+                }
+
                 buffers.insert(OutputId(*module_id, i), output);
             }
         }
 
+        tap(&buffers);
+
+        let base = &self.base;
+
+        self.frame_captures = mem::take(&mut self.frame_captures).into_iter()
+            .filter_map(|capture| capture.capture(&buffers, base))
+            .collect();
+
+        workspace.measure_control_levels(&buffers);
+        workspace.process_zones(&buffers);
+        workspace.latency.end_tick();
+
         indications
     }
 }
+
+fn check_zoom(viewport: &Viewport) -> Result<(), OpError> {
+    if viewport.zoom.is_finite() && viewport.zoom > 0.0 {
+        Ok(())
+    } else {
+        Err(OpError::BadZoom(viewport.zoom))
+    }
+}
@@ -0,0 +1,168 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::runtime;
+use tokio::sync::oneshot;
+
+use mixlab_protocol::{ModuleId, ModuleParams};
+
+use crate::engine::check::{panic_message, run_module, with_headless_engine};
+use crate::engine::param_link;
+use crate::engine::timing::EngineStat;
+use crate::engine::EngineConfig;
+use crate::persist;
+use crate::project::ProjectBaseRef;
+
+/// Per-tick cost of a module, or of the whole workspace, at each of the
+/// engine configs benchmarked
+#[derive(Serialize, Debug)]
+pub struct BenchReport {
+    pub ticks: u64,
+    pub results: Vec<BenchResult>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BenchResult {
+    // none for the workspace as a whole:
+    pub module: Option<ModuleId>,
+    pub kind: String,
+    pub sample_rate: usize,
+    pub block_size: usize,
+    pub budget_us: u64,
+    pub mean_us: f64,
+    pub p99_us: u64,
+    pub max_us: u64,
+    // mean tick cost as a share of the budget, the part of one core it
+    // keeps busy:
+    pub load: f64,
+    pub panic: Option<String>,
+}
+
+/// Which modules to benchmark, on their own or running together
+#[derive(Debug, Clone, Copy)]
+pub enum BenchTarget {
+    Module(ModuleId),
+    Workspace,
+}
+
+/// Runs the target for `ticks` ticks at each config, as fast as it can
+/// rather than in real time. Each module is run with nothing connected to
+/// its inputs, the workspace as it's wired up
+pub async fn bench(workspace: persist::Workspace, base: ProjectBaseRef, target: BenchTarget, configs: Vec<EngineConfig>, ticks: u64) -> BenchReport {
+    let tokio_runtime = runtime::Handle::current();
+    let (tx, rx) = oneshot::channel();
+
+    // like the engine itself, off the runtime as modules may block:
+    thread::spawn(move || {
+        tokio_runtime.enter(|| {
+            let results = configs.into_iter()
+                .flat_map(|config| run_bench(&workspace, base.clone(), target, config, ticks))
+                .collect();
+
+            let _ = tx.send(BenchReport { ticks, results });
+        })
+    });
+
+    rx.await.expect("bench thread")
+}
+
+fn run_bench(workspace: &persist::Workspace, base: ProjectBaseRef, target: BenchTarget, config: EngineConfig, ticks: u64) -> Vec<BenchResult> {
+    let mut save = workspace.clone();
+    save.config = config;
+
+    match target {
+        BenchTarget::Module(id) => {
+            save.modules.get(&id)
+                .map(|module| bench_module(id, module.params.clone(), &save, base, ticks))
+                .into_iter()
+                .collect()
+        }
+        BenchTarget::Workspace => {
+            let mut results = save.modules.iter()
+                .map(|(id, module)| bench_module(*id, module.params.clone(), &save, base.clone(), ticks))
+                .collect::<Vec<_>>();
+
+            results.push(bench_workspace(save, base, ticks));
+            results
+        }
+    }
+}
+
+fn bench_module(id: ModuleId, params: ModuleParams, save: &persist::Workspace, base: ProjectBaseRef, ticks: u64) -> BenchResult {
+    let kind = param_link::list_params(&params).map(|(kind, _)| kind).unwrap_or_else(|| "Module".to_owned());
+    let mut samples = Vec::new();
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        run_module(params, save, base, ticks, |tick, elapsed| {
+            // the first tick pays for warming up:
+            if tick > 0 {
+                samples.push(elapsed);
+            }
+        });
+    }));
+
+    BenchResult::new(Some(id), kind, save.config, samples, result.err().map(panic_message))
+}
+
+fn bench_workspace(save: persist::Workspace, base: ProjectBaseRef, ticks: u64) -> BenchResult {
+    let config = save.config;
+    let mut samples = Vec::new();
+
+    let panic = with_headless_engine(save, base, |engine| {
+        let mut stat = EngineStat::new(config);
+
+        for tick in 0..ticks {
+            let tick_start = Instant::now();
+
+            // never late, as nothing waits on it:
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                stat.record_tick(tick_start + config.tick_budget(), |tick_stat| engine.run_tick(tick, tick_stat))
+            }));
+
+            if let Err(panic) = result {
+                return Some(panic_message(panic));
+            }
+
+            if tick > 0 {
+                samples.push(tick_start.elapsed());
+            }
+        }
+
+        None
+    });
+
+    BenchResult::new(None, "Workspace".to_owned(), config, samples, panic)
+}
+
+impl BenchResult {
+    fn new(module: Option<ModuleId>, kind: String, config: EngineConfig, mut samples: Vec<Duration>, panic: Option<String>) -> Self {
+        samples.sort();
+
+        let budget_us = config.tick_budget().as_micros() as u64;
+
+        let mean_us = if samples.is_empty() {
+            0.0
+        } else {
+            samples.iter().map(|sample| sample.as_micros() as f64).sum::<f64>() / samples.len() as f64
+        };
+
+        let percentile = |p: f64| samples.get(((samples.len() as f64 * p) as usize).min(samples.len().saturating_sub(1)))
+            .map(|sample| sample.as_micros() as u64)
+            .unwrap_or(0);
+
+        BenchResult {
+            module,
+            kind,
+            sample_rate: config.sample_rate,
+            block_size: config.block_size,
+            budget_us,
+            mean_us,
+            p99_us: percentile(0.99),
+            max_us: samples.last().map(|sample| sample.as_micros() as u64).unwrap_or(0),
+            load: mean_us / budget_us as f64,
+            panic,
+        }
+    }
+}
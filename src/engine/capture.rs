@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use tokio::task;
+
+use mixlab_codec::ffmpeg::AvFrame;
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::png;
+use mixlab_protocol::OutputId;
+
+use crate::engine::{EngineConfig, Output};
+use crate::project::ProjectBaseRef;
+use crate::project::media::{MediaUpload, UploadInfo};
+use crate::util;
+
+// video outputs only carry a frame on ticks where a new picture is ready, so
+// a capture waits this long for one before giving up:
+const TIMEOUT_SECONDS: usize = 5;
+
+/// Waits for the next frame on a video output and saves it to the media
+/// library as a png
+pub struct FrameCapture {
+    output: OutputId,
+    remaining_ticks: usize,
+}
+
+impl FrameCapture {
+    pub fn new(output: OutputId, config: EngineConfig) -> Self {
+        FrameCapture {
+            output,
+            remaining_ticks: config.ticks_per_second() * TIMEOUT_SECONDS,
+        }
+    }
+
+    /// Looks for a frame in this tick's outputs, returning the capture back
+    /// if it should keep waiting
+    pub fn capture(mut self, buffers: &HashMap<OutputId, Output>, base: &ProjectBaseRef) -> Option<Self> {
+        match buffers.get(&self.output) {
+            Some(Output::Video(Some(frame))) => {
+                tokio::spawn(save_frame(base.clone(), frame.data.decoded.clone()));
+                None
+            }
+            Some(Output::Video(None)) if self.remaining_ticks > 0 => {
+                self.remaining_ticks -= 1;
+                Some(self)
+            }
+            Some(Output::Video(None)) => {
+                eprintln!("capture: no frame from {:?} within {}s", self.output, TIMEOUT_SECONDS);
+                None
+            }
+            // not a video output, or one that no longer exists:
+            Some(_) | None => None,
+        }
+    }
+}
+
+async fn save_frame(base: ProjectBaseRef, frame: AvFrame<Video>) {
+    let png = task::spawn_blocking(move || png::encode(&frame))
+        .await
+        .expect("encode frame");
+
+    let png = match png {
+        Ok(png) => png,
+        Err(e) => {
+            eprintln!("capture: could not encode frame: {:?}", e);
+            return;
+        }
+    };
+
+    let info = UploadInfo {
+        name: format!("frame-{}.png", util::unix_time()),
+        kind: "image/png".to_owned(),
+    };
+
+    let result = async {
+        let mut upload = MediaUpload::new(base, info).await?;
+        upload.receive_bytes(&png).await?;
+        upload.finalize().await
+    }.await;
+
+    if let Err(e) = result {
+        eprintln!("capture: could not save frame: {:?}", e);
+    }
+}
@@ -0,0 +1,299 @@
+use std::any::Any;
+use std::fmt::{self, Display};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tokio::runtime;
+use tokio::sync::{broadcast, oneshot, watch};
+
+use mixlab_protocol::{Diagnostic, ModuleId, ModuleParams, Severity};
+
+use crate::engine::module;
+use crate::engine::param_link;
+use crate::engine::timing::EngineStat;
+use crate::engine::{DeviceLinks, Engine, EngineConfig, GroupLevels, InputRef, Output, Rehearsal, WorkspaceEmbryo, ZoneAudio};
+use crate::persist;
+use crate::project::ProjectBaseRef;
+use crate::util::Sequence;
+
+/// What came of running a workspace through `check`
+#[derive(Debug)]
+pub struct CheckReport {
+    pub ticks: u64,
+    pub tick_budget: Duration,
+    pub modules: Vec<ModuleCheck>,
+    // of the whole workspace running together:
+    pub max_tick: Duration,
+    pub overruns: u64,
+    pub panic: Option<String>,
+}
+
+/// How a module fared run on its own, with nothing connected to its inputs
+#[derive(Debug)]
+pub struct ModuleCheck {
+    pub id: ModuleId,
+    pub kind: String,
+    pub panic: Option<String>,
+    pub max_tick: Duration,
+    pub overruns: u64,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ModuleCheck {
+    fn passed(&self) -> bool {
+        self.panic.is_none()
+            && self.overruns == 0
+            && !self.diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+impl CheckReport {
+    /// Whether nothing failed or reported an error. Warnings, eg. of a
+    /// device not being linked, are left to whoever reads the report
+    pub fn passed(&self) -> bool {
+        self.panic.is_none()
+            && self.overruns == 0
+            && self.modules.iter().all(ModuleCheck::passed)
+    }
+}
+
+/// Instantiates every module in the workspace and runs each on its own for
+/// `ticks` ticks, then runs the workspace as a whole in real time for as
+/// many again. Nothing is persisted, no sessions can connect, and modules
+/// run dry, touching no devices, network or files
+pub async fn check(workspace: persist::Workspace, base: ProjectBaseRef, ticks: u64) -> CheckReport {
+    let tokio_runtime = runtime::Handle::current();
+    let (tx, rx) = oneshot::channel();
+
+    // like the engine itself, off the runtime as modules may block:
+    thread::spawn(move || {
+        tokio_runtime.enter(|| {
+            let _ = tx.send(run_check(workspace, base, ticks));
+        })
+    });
+
+    rx.await.expect("check thread")
+}
+
+fn run_check(save: persist::Workspace, base: ProjectBaseRef, ticks: u64) -> CheckReport {
+    let config = save.config;
+
+    let modules = save.modules.iter()
+        .map(|(module_id, saved_module)| {
+            check_module(*module_id, saved_module.params.clone(), &save, base.clone(), ticks)
+        })
+        .collect::<Vec<_>>();
+
+    let mut report = CheckReport {
+        ticks,
+        tick_budget: config.tick_budget(),
+        modules,
+        max_tick: Duration::default(),
+        overruns: 0,
+        panic: None,
+    };
+
+    // modules which fail on their own would only take the rest down with
+    // them:
+    if report.modules.iter().any(|module| module.panic.is_some()) {
+        return report;
+    }
+
+    with_headless_engine(save, base, move |engine| {
+        let mut stat = EngineStat::new(config);
+        let started = Instant::now();
+
+        for tick in 0..ticks {
+            let scheduled_tick_end = started + config.tick_end(tick + 1);
+            let tick_start = Instant::now();
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                stat.record_tick(scheduled_tick_end, |tick_stat| engine.run_tick(tick, tick_stat))
+            }));
+
+            if let Err(panic) = result {
+                report.panic = Some(panic_message(panic));
+                return report;
+            }
+
+            if tick > 0 {
+                tally(&mut report.max_tick, &mut report.overruns, tick_start.elapsed(), config);
+            }
+
+            let now = Instant::now();
+
+            if now < scheduled_tick_end {
+                thread::sleep(scheduled_tick_end - now);
+            }
+        }
+
+        // devices can be lost, or streams fail, once everything is running:
+        for module in &mut report.modules {
+            if let Some(host) = engine.workspace.borrow().modules.get(&module.id) {
+                for diagnostic in host.diagnostics().current() {
+                    if !module.diagnostics.contains(&diagnostic) {
+                        module.diagnostics.push(diagnostic);
+                    }
+                }
+            }
+        }
+
+        report
+    })
+}
+
+/// Runs `f` with an engine for the workspace which no sessions can reach,
+/// which persists nothing and whose modules run dry
+pub(in crate::engine) fn with_headless_engine<T>(mut save: persist::Workspace, base: ProjectBaseRef, f: impl FnOnce(&mut Engine) -> T) -> T {
+    save.config.dry_run = true;
+    let config = save.config;
+
+    let (embryo, _persist_rx) = WorkspaceEmbryo::new(save);
+    let (_cmd_tx, cmd_rx) = mpsc::sync_channel(1);
+    let (log_tx, _) = broadcast::channel(64);
+    let (perf_tx, _) = watch::channel(None);
+    let (recall_tx, recall_rx) = mpsc::channel();
+
+    let mut engine = Engine {
+        cmd_rx,
+        log_tx,
+        perf_tx,
+        session_seq: Sequence::new(),
+        workspace: embryo.spawn(base.clone()),
+        config,
+        base,
+        gain_analysis: None,
+        frame_captures: Vec::new(),
+        recall_tx,
+        recall_rx,
+    };
+
+    f(&mut engine)
+}
+
+// runs as fast as it can, rather than in real time, so that checking a large
+// workspace doesn't take minutes
+fn check_module(id: ModuleId, params: ModuleParams, save: &persist::Workspace, base: ProjectBaseRef, ticks: u64) -> ModuleCheck {
+    let config = save.config;
+
+    let mut check = ModuleCheck {
+        id,
+        kind: param_link::list_params(&params).map(|(kind, _)| kind).unwrap_or_else(|| "Module".to_owned()),
+        panic: None,
+        max_tick: Duration::default(),
+        overruns: 0,
+        diagnostics: Vec::new(),
+    };
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        run_module(params, save, base, ticks, |tick, elapsed| {
+            if tick > 0 {
+                tally(&mut check.max_tick, &mut check.overruns, elapsed, config);
+            }
+        })
+    }));
+
+    match result {
+        Ok(diagnostics) => { check.diagnostics = diagnostics; }
+        Err(panic) => { check.panic = Some(panic_message(panic)); }
+    }
+
+    check
+}
+
+/// Runs a module on its own and dry, with nothing connected to its inputs,
+/// handing how long each tick took to `on_tick`. Returns the diagnostics
+/// the module is left with
+pub(in crate::engine) fn run_module(params: ModuleParams, save: &persist::Workspace, base: ProjectBaseRef, ticks: u64, mut on_tick: impl FnMut(u64, Duration)) -> Vec<Diagnostic> {
+    let config = EngineConfig { dry_run: true, ..save.config };
+    let groups = GroupLevels::new();
+    let devices = DeviceLinks::new(save.device_links.clone());
+    let rehearsal = Rehearsal::new(save.rehearsal);
+
+    // the workspace's zones are there to be played out, but silent with
+    // nothing running upstream of them:
+    let zone_audio = ZoneAudio::new();
+
+    for zone_id in save.zones.keys() {
+        zone_audio.create(*zone_id, config);
+    }
+
+    let (mut module, _) = module::host(params, base, config, groups, devices, rehearsal, zone_audio);
+
+    let inputs = module.inputs().iter()
+        .map(|_| InputRef::Disconnected(config.block_size))
+        .collect::<Vec<_>>();
+
+    for tick in 0..ticks {
+        let mut outputs = module.outputs().iter()
+            .map(|output| Output::from_line_type(output.line_type(), config.block_size))
+            .collect::<Vec<_>>();
+
+        let mut output_refs = outputs.iter_mut()
+            .map(|output| output.as_output_ref())
+            .collect::<Vec<_>>();
+
+        let tick_start = Instant::now();
+        module.run_tick(tick * config.block_size as u64, &inputs, &mut output_refs);
+        on_tick(tick, tick_start.elapsed());
+    }
+
+    module.diagnostics().current()
+}
+
+// the first tick is left out of the tally, as it pays for warming up
+fn tally(max_tick: &mut Duration, overruns: &mut u64, elapsed: Duration, config: EngineConfig) {
+    *max_tick = (*max_tick).max(elapsed);
+
+    if elapsed > config.tick_budget() {
+        *overruns += 1;
+    }
+}
+
+pub(in crate::engine) fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panicked".to_owned()
+    }
+}
+
+impl Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let budget = self.tick_budget.as_micros();
+
+        writeln!(f, "{} modules, {} ticks each, budget {} us per tick", self.modules.len(), self.ticks, budget)?;
+
+        for module in &self.modules {
+            let status = if module.passed() { "ok" } else { "FAIL" };
+
+            writeln!(f, "  {:<4} {} #{}: max {} us", status, module.kind, module.id.0, module.max_tick.as_micros())?;
+
+            if let Some(panic) = &module.panic {
+                writeln!(f, "         panicked: {}", panic)?;
+            }
+
+            if module.overruns > 0 {
+                writeln!(f, "         over budget in {} of {} ticks", module.overruns, self.ticks)?;
+            }
+
+            for diagnostic in &module.diagnostics {
+                writeln!(f, "         {:?}: {}", diagnostic.severity, diagnostic.message)?;
+            }
+        }
+
+        if let Some(panic) = &self.panic {
+            writeln!(f, "workspace panicked: {}", panic)?;
+        } else if self.modules.iter().any(|module| module.panic.is_some()) {
+            writeln!(f, "workspace not run, as modules failed on their own")?;
+        } else {
+            writeln!(f, "workspace: max {} us, over budget in {} of {} ticks", self.max_tick.as_micros(), self.overruns, self.ticks)?;
+        }
+
+        write!(f, "{}", if self.passed() { "PASSED" } else { "FAILED" })
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::iter;
+
+use mixlab_protocol::{ModuleId, ModuleParams, InputId, OutputId, WindowGeometry, ParamLinkId, ParamLink, MorphState, ModulationId, Modulation, ClockSource, Viewport, ViewId, View, Binding, Command, WorkspaceOp, ZoneId, Zone};
+
+use crate::engine::module;
+use crate::engine::workspace::Workspace;
+use crate::project::ProjectBaseRef;
+use crate::util::Sequence;
+
+/// The workspace as it was before a batch, for undoing the batch if one of
+/// its ops fails. Modules are left running: only the params of modules the
+/// batch changes are kept, and only modules it creates or deletes are
+/// hosted or dropped again
+pub struct Checkpoint {
+    module_seq: Sequence,
+    geometry: HashMap<ModuleId, WindowGeometry>,
+    connections: HashMap<InputId, OutputId>,
+    param_link_seq: Sequence,
+    param_links: HashMap<ParamLinkId, ParamLink>,
+    morphs: HashMap<ModuleId, MorphState>,
+    favorite_params: HashMap<ModuleId, Vec<String>>,
+    modulation_seq: Sequence,
+    modulations: HashMap<ModulationId, Modulation>,
+    clock_source: ClockSource,
+    rehearsal: bool,
+    viewport: Viewport,
+    view_seq: Sequence,
+    views: HashMap<ViewId, View>,
+    bindings: HashMap<String, Binding>,
+    device_links: HashMap<String, String>,
+    zone_seq: Sequence,
+    zones: HashMap<ZoneId, Zone>,
+    // filled in op by op, ahead of each op that can change params:
+    params: HashMap<ModuleId, ModuleParams>,
+}
+
+impl Checkpoint {
+    pub fn take(workspace: &Workspace) -> Self {
+        Checkpoint {
+            module_seq: workspace.module_seq.clone(),
+            geometry: workspace.geometry.clone(),
+            connections: workspace.connections.clone(),
+            param_link_seq: workspace.param_link_seq.clone(),
+            param_links: workspace.param_links.clone(),
+            morphs: workspace.morphs.clone(),
+            favorite_params: workspace.favorite_params.clone(),
+            modulation_seq: workspace.modulation_seq.clone(),
+            modulations: workspace.modulations.clone(),
+            clock_source: workspace.clock_source,
+            rehearsal: workspace.rehearsal.active(),
+            viewport: workspace.viewport.clone(),
+            view_seq: workspace.view_seq.clone(),
+            views: workspace.views.clone(),
+            bindings: workspace.bindings.clone(),
+            device_links: workspace.devices.to_map(),
+            zone_seq: workspace.zone_seq.clone(),
+            zones: workspace.zones.clone(),
+            params: HashMap::new(),
+        }
+    }
+
+    /// Keeps aside the params of every module an op could change, call
+    /// before applying it
+    pub fn touch(&mut self, workspace: &Workspace, op: &WorkspaceOp) {
+        let module_id = match op {
+            WorkspaceOp::UpdateModuleParams(module_id, _) |
+            WorkspaceOp::DeleteModule(module_id) |
+            WorkspaceOp::SetMorph(module_id, _) => *module_id,
+            WorkspaceOp::SetParam(param, _) => param.module,
+            WorkspaceOp::CreateParamLink(link) => link.target.module,
+            WorkspaceOp::InvokeBinding(name) => {
+                match workspace.bindings.get(name).map(|binding| &binding.command) {
+                    Some(Command::ToggleParam(param)) |
+                    Some(Command::SetParam(param, _)) |
+                    Some(Command::BumpParam(param)) => param.module,
+                    Some(Command::SetMorph(module_id, _)) => *module_id,
+                    Some(Command::RestoreSnapshot(_)) | None => { return; }
+                }
+            }
+            _ => { return; }
+        };
+
+        // a change to one module's params carries on to modules linked to it
+        let linked = workspace.param_links.values().map(|link| link.target.module);
+
+        for module_id in iter::once(module_id).chain(linked) {
+            if self.params.contains_key(&module_id) {
+                continue;
+            }
+
+            if let Some(params) = workspace.params(module_id) {
+                self.params.insert(module_id, params);
+            }
+        }
+    }
+
+    /// Puts the workspace back as it was when the checkpoint was taken.
+    /// Returns the modules created since, which are gone again
+    pub fn rollback(self, workspace: &mut Workspace, base: ProjectBaseRef) -> Vec<ModuleId> {
+        // every module there was before has geometry:
+        let created = workspace.modules.keys()
+            .filter(|module_id| !self.geometry.contains_key(module_id))
+            .copied()
+            .collect::<Vec<_>>();
+
+        for module_id in &created {
+            workspace.modules.remove(module_id);
+            workspace.indications.remove(module_id);
+            workspace.groups.sync(*module_id, None);
+        }
+
+        for (module_id, params) in self.params {
+            if !self.geometry.contains_key(&module_id) {
+                continue;
+            }
+
+            if let Some(module) = workspace.modules.get_mut(&module_id) {
+                module.update(params.clone());
+            } else {
+                // deleted by the batch:
+                let (module, indication) = module::host(params.clone(), base.clone(), workspace.config, workspace.groups.clone(), workspace.devices.clone(), workspace.rehearsal.clone(), workspace.zone_audio.clone());
+                workspace.modules.insert(module_id, module);
+                workspace.indications.insert(module_id, indication);
+            }
+
+            workspace.groups.sync(module_id, Some(&params));
+        }
+
+        for zone_id in workspace.zones.keys() {
+            if !self.zones.contains_key(zone_id) {
+                workspace.zone_audio.remove(*zone_id);
+            }
+        }
+
+        for zone_id in self.zones.keys() {
+            if !workspace.zones.contains_key(zone_id) {
+                workspace.zone_audio.create(*zone_id, workspace.config);
+            }
+        }
+
+        workspace.module_seq = self.module_seq;
+        workspace.geometry = self.geometry;
+        workspace.connections = self.connections;
+        workspace.param_link_seq = self.param_link_seq;
+        workspace.param_links = self.param_links;
+        workspace.morphs = self.morphs;
+        workspace.favorite_params = self.favorite_params;
+        workspace.modulation_seq = self.modulation_seq;
+        workspace.modulations = self.modulations;
+        workspace.clock_source = self.clock_source;
+        workspace.rehearsal.set(self.rehearsal);
+        workspace.viewport = self.viewport;
+        workspace.view_seq = self.view_seq;
+        workspace.views = self.views;
+        workspace.bindings = self.bindings;
+        workspace.zone_seq = self.zone_seq;
+        workspace.zones = self.zones;
+        workspace.release_unmodulated();
+
+        if workspace.devices.to_map() != self.device_links {
+            for device in workspace.devices.to_map().keys() {
+                workspace.devices.link(device.clone(), None);
+            }
+
+            for (device, local) in self.device_links {
+                workspace.devices.link(device, Some(local));
+            }
+
+            // as for LinkDevice, reapplying params resolves devices again:
+            for module in workspace.modules.values_mut() {
+                let params = module.params();
+
+                if let ModuleParams::InputDevice(_) | ModuleParams::OutputDevice(_) = params {
+                    module.update(params);
+                }
+            }
+        }
+
+        created
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use mixlab_protocol::{ClockInfo, ClockSource, ModuleId};
+
+use crate::engine::{DynModuleHost, EngineConfig};
+
+// clocks count in flicks, which every common sample rate divides evenly, so
+// that no rounding error builds up however long a clock runs:
+const FLICKS_PER_SECOND: u64 = 705_600_000;
+
+// a clock which hasn't moved for this long is considered stopped, long
+// enough to ride out network jitter on stream inputs:
+const STALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+// the engine slews towards the clock it's following over roughly this long,
+// which smooths out clocks that advance in bursts:
+const SLEW_SECONDS: f64 = 5.0;
+
+// beyond this much error the engine jumps to the clock rather than slewing:
+const MAX_ERROR_SECONDS: f64 = 1.0;
+
+// drift isn't reported until it has been measured over at least this long:
+const MIN_DRIFT_SECONDS: f64 = 5.0;
+
+pub type ClockRef = Arc<SampleClock>;
+
+/// Counts the samples a module's device has played or its source has
+/// delivered, from whichever thread is doing the work
+#[derive(Debug, Default)]
+pub struct SampleClock {
+    flicks: AtomicU64,
+}
+
+impl SampleClock {
+    pub fn new() -> ClockRef {
+        Arc::new(SampleClock::default())
+    }
+
+    pub fn advance(&self, samples: usize, sample_rate: usize) {
+        if sample_rate == 0 {
+            return;
+        }
+
+        let flicks = samples as u64 * (FLICKS_PER_SECOND / sample_rate as u64);
+        self.flicks.fetch_add(flicks, Ordering::Relaxed);
+    }
+
+    pub fn position(&self) -> Duration {
+        let flicks = self.flicks.load(Ordering::Relaxed) as u128;
+        let nanos = flicks * 1_000_000_000 / FLICKS_PER_SECOND as u128;
+        Duration::from_nanos(nanos as u64)
+    }
+}
+
+/// Schedules engine ticks against the internal timer, slewed to follow a
+/// module clock when one is selected
+pub struct EngineClock {
+    config: EngineConfig,
+    epoch: Instant,
+    source: ClockSource,
+    // engine and clock positions when following last (re)started, None while
+    // running from the internal timer:
+    anchor: Option<Anchor>,
+    meters: HashMap<ModuleId, ClockMeter>,
+}
+
+#[derive(Clone, Copy)]
+struct Anchor {
+    engine: Duration,
+    clock: Duration,
+}
+
+impl EngineClock {
+    pub fn new(config: EngineConfig) -> Self {
+        EngineClock {
+            config,
+            epoch: Instant::now(),
+            source: ClockSource::Internal,
+            anchor: None,
+            meters: HashMap::new(),
+        }
+    }
+
+    /// When the given number of ticks should have finished running
+    pub fn tick_end(&self, tick: u64) -> Instant {
+        self.epoch + self.config.tick_end(tick)
+    }
+
+    /// Measures module clocks and corrects the schedule towards the selected
+    /// one, called once every tick
+    pub fn sync(&mut self, tick: u64, source: ClockSource, modules: &HashMap<ModuleId, DynModuleHost>) {
+        let now = Instant::now();
+
+        self.meters.retain(|module_id, meter| {
+            modules.get(module_id)
+                .and_then(|module| module.clock())
+                .map(|clock| Arc::ptr_eq(&clock, &meter.clock))
+                .unwrap_or(false)
+        });
+
+        for (module_id, module) in modules {
+            if let Some(clock) = module.clock() {
+                self.meters.entry(*module_id)
+                    .or_insert_with(|| ClockMeter::new(clock, now))
+                    .update(now);
+            }
+        }
+
+        if source != self.source {
+            self.source = source;
+            self.anchor = None;
+        }
+
+        let meter = match self.source {
+            ClockSource::Internal => None,
+            ClockSource::Module(module_id) => self.meters.get(&module_id),
+        };
+
+        let clock_position = match meter {
+            Some(meter) if !meter.stalled(now) => meter.extrapolate(now),
+            _ => {
+                // nothing to follow, run on the internal timer until the
+                // clock comes back:
+                self.anchor = None;
+                return;
+            }
+        };
+
+        let engine_position = self.config.tick_end(tick);
+
+        let anchor = *self.anchor.get_or_insert(Anchor {
+            engine: engine_position,
+            clock: clock_position,
+        });
+
+        // positive when the engine has run ahead of the clock. extrapolated
+        // positions can land either side of the anchor, so this is signed:
+        let engine_elapsed = engine_position.as_secs_f64() - anchor.engine.as_secs_f64();
+        let clock_elapsed = clock_position.as_secs_f64() - anchor.clock.as_secs_f64();
+        let error = engine_elapsed - clock_elapsed;
+
+        if error.abs() > MAX_ERROR_SECONDS {
+            self.anchor = None;
+            return;
+        }
+
+        let correction = error / (SLEW_SECONDS * self.config.ticks_per_second() as f64);
+
+        if correction > 0.0 {
+            self.epoch += Duration::from_secs_f64(correction);
+        } else {
+            self.epoch -= Duration::from_secs_f64(-correction);
+        }
+    }
+
+    pub fn info(&self, modules: &HashMap<ModuleId, DynModuleHost>) -> ClockInfo {
+        let mut drift = self.meters.iter()
+            .filter_map(|(module_id, meter)| Some((*module_id, meter.drift_ppm()?)))
+            .collect::<Vec<_>>();
+
+        drift.sort_by_key(|(module_id, _)| *module_id);
+
+        let mut varispeed = modules.iter()
+            .filter_map(|(module_id, module)| Some((*module_id, module.varispeed()?)))
+            .collect::<Vec<_>>();
+
+        varispeed.sort_by_key(|(module_id, _)| *module_id);
+
+        ClockInfo {
+            source: self.source,
+            fallback: self.source != ClockSource::Internal && self.anchor.is_none(),
+            drift,
+            varispeed,
+        }
+    }
+}
+
+// tracks a module clock against the internal timer
+struct ClockMeter {
+    clock: ClockRef,
+    last_position: Duration,
+    last_advanced: Instant,
+    // when measuring (re)started and the clock's position then:
+    start: Option<(Instant, Duration)>,
+}
+
+impl ClockMeter {
+    fn new(clock: ClockRef, now: Instant) -> Self {
+        let last_position = clock.position();
+
+        ClockMeter {
+            clock,
+            last_position,
+            last_advanced: now,
+            start: None,
+        }
+    }
+
+    fn update(&mut self, now: Instant) {
+        let position = self.clock.position();
+
+        if position != self.last_position {
+            self.last_position = position;
+            self.last_advanced = now;
+            self.start.get_or_insert((now, position));
+        } else if self.stalled(now) {
+            // a stopped clock says nothing about its rate, start over once
+            // it's running again:
+            self.start = None;
+        }
+    }
+
+    fn stalled(&self, now: Instant) -> bool {
+        now - self.last_advanced > STALL_TIMEOUT
+    }
+
+    // clocks only move when their device or source does some work, so
+    // assume they've kept running since
+    fn extrapolate(&self, now: Instant) -> Duration {
+        self.last_position + (now - self.last_advanced)
+    }
+
+    fn drift_ppm(&self) -> Option<f64> {
+        let (start, start_position) = self.start?;
+        let elapsed = (self.last_advanced - start).as_secs_f64();
+
+        if elapsed < MIN_DRIFT_SECONDS {
+            return None;
+        }
+
+        let advanced = (self.last_position - start_position).as_secs_f64();
+        Some((advanced / elapsed - 1.0) * 1_000_000.0)
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use mixlab_codec::ffmpeg::{ColorSpace, ColorRange, Colorimetry, PictureSettings};
+use mixlab_util::time::{MediaTime, MediaDuration};
+
+// upper bound on block size, zero buffers for disconnected inputs are
+// statically allocated at this size:
+pub const MAX_BLOCK_SIZE: usize = 8192;
+
+// sample rates which every part of the pipeline (encoders in particular)
+// can deal with:
+const SUPPORTED_SAMPLE_RATES: &[usize] = &[44100, 48000, 88200, 96000];
+
+/// Project level engine settings, fixed for the lifetime of the engine
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineConfig {
+    pub sample_rate: usize,
+    // samples per channel processed each tick:
+    pub block_size: usize,
+    // every picture is converted to this on its way through the pipeline,
+    // and outputs are tagged with it:
+    #[serde(default)]
+    pub color_space: ColorSpace,
+    // set for engines which are only tried out, ie. by --check and bench,
+    // see ModuleCtx::dry_run. never saved:
+    #[serde(skip)]
+    pub dry_run: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            sample_rate: 44100,
+            block_size: 44100 / 60,
+            color_space: ColorSpace::default(),
+            dry_run: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    UnsupportedSampleRate(usize),
+    BlockSizeOutOfRange(usize),
+}
+
+impl EngineConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !SUPPORTED_SAMPLE_RATES.contains(&self.sample_rate) {
+            return Err(ConfigError::UnsupportedSampleRate(self.sample_rate));
+        }
+
+        // a tick must not be longer than a second, or the engine's
+        // once-per-second bookkeeping falls apart:
+        if self.block_size == 0 || self.block_size > MAX_BLOCK_SIZE || self.block_size > self.sample_rate {
+            return Err(ConfigError::BlockSizeOutOfRange(self.block_size));
+        }
+
+        Ok(())
+    }
+
+    /// Number of whole ticks per second, rounded down
+    pub fn ticks_per_second(&self) -> usize {
+        self.sample_rate / self.block_size
+    }
+
+    pub fn tick_duration(&self) -> MediaDuration {
+        MediaDuration::new(self.block_size as i64, self.sample_rate as i64)
+    }
+
+    pub fn tick_budget(&self) -> Duration {
+        Duration::from_micros(self.block_size as u64 * 1_000_000 / self.sample_rate as u64)
+    }
+
+    /// Wall clock time from engine start at which `tick` is due to finish
+    pub fn tick_end(&self, tick: u64) -> Duration {
+        // we don't simply calculate `tick * tick_budget` here to prevent loss of precision over time:
+        Duration::from_micros(tick * self.block_size as u64 * 1_000_000 / self.sample_rate as u64)
+    }
+
+    /// Color of every picture in the pipeline. Always limited range, as
+    /// that's what players expect of streamed video
+    pub fn colorimetry(&self) -> Colorimetry {
+        Colorimetry {
+            space: self.color_space,
+            range: ColorRange::Limited,
+        }
+    }
+
+    /// Settings for a yuv420p picture in the project's colorimetry
+    pub fn picture(&self, width: usize, height: usize) -> PictureSettings {
+        PictureSettings::yuv420p(width, height).with_color(self.colorimetry())
+    }
+
+    /// Converts an engine sample time as passed to run_tick to media time
+    pub fn media_time(&self, t: u64) -> MediaTime {
+        MediaTime::new(t as i64, self.sample_rate as i64)
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::util;
+
+/// Maps the audio devices a project refers to onto devices present on this
+/// machine. Projects keep the device names they were set up with, so that
+/// they still open as intended back on the machine that made them, and are
+/// only relinked where a device by that name is missing. Links are kept per
+/// machine, as a project moving between several would otherwise carry one
+/// machine's links onto the next.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceLinks {
+    // keyed by machine name:
+    machines: Arc<RwLock<HashMap<String, HashMap<String, String>>>>,
+    // bumped on every change to this machine's links:
+    generation: Arc<AtomicUsize>,
+}
+
+/// A device resolved as of the params and links it was last resolved for.
+/// Listing a host's devices is slow, too slow to do every time params are
+/// applied, so devices are only resolved again when either changes
+#[derive(Debug, Default)]
+pub struct ResolvedDevice {
+    key: Option<(Option<String>, usize)>,
+    resolved: Option<String>,
+}
+
+impl DeviceLinks {
+    pub fn new(machines: HashMap<String, HashMap<String, String>>) -> Self {
+        DeviceLinks {
+            machines: Arc::new(RwLock::new(machines)),
+            generation: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Picks the local device to open for a device the project refers to,
+    /// given the names of the devices available. Returns None if the device
+    /// is missing and hasn't been relinked to one that's present
+    pub fn resolve(&self, device: &str, available: &[String]) -> Option<String> {
+        if available.iter().any(|name| name == device) {
+            return Some(device.to_owned());
+        }
+
+        let machines = self.machines.read().unwrap();
+        let linked = machines.get(util::machine_name())?.get(device)?;
+
+        available.iter()
+            .find(|name| *name == linked)
+            .cloned()
+    }
+
+    /// As resolve, listing the devices available with `available` only if
+    /// the device or the links have changed since `cache` was last resolved
+    pub fn resolve_cached(&self, cache: &mut ResolvedDevice, device: Option<&str>, available: impl FnOnce() -> Option<Vec<String>>) -> Option<String> {
+        let key = (device.map(str::to_owned), self.generation.load(Ordering::Relaxed));
+
+        if cache.key.as_ref() != Some(&key) {
+            cache.resolved = device.and_then(|device| self.resolve(device, &available()?));
+            cache.key = Some(key);
+        }
+
+        cache.resolved.clone()
+    }
+
+    pub(in crate::engine) fn link(&self, device: String, local: Option<String>) {
+        let mut machines = self.machines.write().unwrap();
+        let links = machines.entry(util::machine_name().to_owned()).or_default();
+
+        match local {
+            Some(local) => { links.insert(device, local); }
+            None => { links.remove(&device); }
+        }
+
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// This machine's links
+    pub(in crate::engine) fn to_map(&self) -> HashMap<String, String> {
+        self.machines.read().unwrap()
+            .get(util::machine_name())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Every machine's links, for saving with the project
+    pub(in crate::engine) fn to_persist(&self) -> HashMap<String, HashMap<String, String>> {
+        self.machines.read().unwrap().clone()
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use mixlab_protocol::{Diagnostic, Severity};
+
+/// Where a module reports problems it runs into, usable from the module
+/// itself or from any thread or task it starts. Each diagnostic is filed
+/// under a key, eg. "device", so that reporting a problem again replaces it
+/// and clearing the key takes it back down once it's resolved.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    entries: Mutex<BTreeMap<&'static str, Diagnostic>>,
+    // set whenever entries change, until the engine next picks them up:
+    changed: AtomicBool,
+}
+
+impl Diagnostics {
+    pub fn report(&self, key: &'static str, severity: Severity, message: impl Into<String>) {
+        let diagnostic = Diagnostic { severity, message: message.into() };
+        let mut entries = self.shared.entries.lock().unwrap();
+
+        if entries.get(key) != Some(&diagnostic) {
+            entries.insert(key, diagnostic);
+            self.shared.changed.store(true, Ordering::Release);
+        }
+    }
+
+    pub fn error(&self, key: &'static str, message: impl Into<String>) {
+        self.report(key, Severity::Error, message)
+    }
+
+    pub fn warning(&self, key: &'static str, message: impl Into<String>) {
+        self.report(key, Severity::Warning, message)
+    }
+
+    pub fn clear(&self, key: &'static str) {
+        if self.shared.entries.lock().unwrap().remove(key).is_some() {
+            self.shared.changed.store(true, Ordering::Release);
+        }
+    }
+
+    pub(in crate::engine) fn current(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = self.shared.entries.lock().unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // most severe first, for clients to badge by:
+        diagnostics.sort_by(|a, b| b.severity.cmp(&a.severity));
+        diagnostics
+    }
+
+    /// The module's diagnostics, if they've changed since last taken. Cheap
+    /// when they haven't, so the engine checks every tick
+    pub(in crate::engine) fn take_changed(&self) -> Option<Vec<Diagnostic>> {
+        if self.shared.changed.swap(false, Ordering::Acquire) {
+            Some(self.current())
+        } else {
+            None
+        }
+    }
+}
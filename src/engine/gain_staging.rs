@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use mixlab_protocol::{ModuleId, ModuleParams, InputId, OutputId, Decibel, GainStagingRequest, GainStagingReport, WireLevel, TrimSuggestion};
+
+use crate::engine::{DynModuleHost, EngineConfig, Output, Sample};
+
+// nominal average level we aim to hit at each trim point:
+const NOMINAL_RMS: Decibel = Decibel(-18.0);
+// never suggest a trim which would push peaks above this:
+const PEAK_CEILING: Decibel = Decibel(-1.0);
+// wires quieter than this are considered silent and left alone:
+const SILENCE_THRESHOLD: Decibel = Decibel(-70.0);
+// furthest we will move a trim in either direction:
+const MAX_TRIM: f64 = 24.0;
+
+pub struct GainAnalysis {
+    request: GainStagingRequest,
+    remaining_ticks: u64,
+    meters: HashMap<OutputId, Meter>,
+}
+
+#[derive(Default)]
+struct Meter {
+    peak: Sample,
+    sum_squares: f64,
+    samples: usize,
+}
+
+impl Meter {
+    fn measure(&mut self, samples: &[Sample]) {
+        for sample in samples {
+            self.peak = Sample::max(self.peak, sample.abs());
+            self.sum_squares += (*sample as f64) * (*sample as f64);
+        }
+
+        self.samples += samples.len();
+    }
+
+    fn level(&self) -> WireLevel {
+        let rms = if self.samples == 0 {
+            0.0
+        } else {
+            (self.sum_squares / self.samples as f64).sqrt()
+        };
+
+        WireLevel {
+            peak: Decibel::from_linear(self.peak as f64),
+            rms: Decibel::from_linear(rms),
+        }
+    }
+}
+
+impl GainAnalysis {
+    pub fn new(request: GainStagingRequest, config: EngineConfig) -> Self {
+        let samples = request.duration_ms * config.sample_rate as u64 / 1000;
+        let remaining_ticks = u64::max(1, samples / config.block_size as u64);
+
+        GainAnalysis {
+            request,
+            remaining_ticks,
+            meters: HashMap::new(),
+        }
+    }
+
+    pub fn measure(&mut self, output_id: OutputId, output: &Output) {
+        let samples = match output {
+            Output::Mono(buff) | Output::Stereo(buff) => buff,
+            Output::Video(_) => { return; }
+        };
+
+        self.meters.entry(output_id)
+            .or_default()
+            .measure(samples);
+    }
+
+    /// Counts down one tick of measurement, returns true once analysis has
+    /// run for the requested duration
+    pub fn tick(&mut self) -> bool {
+        self.remaining_ticks = self.remaining_ticks.saturating_sub(1);
+        self.remaining_ticks == 0
+    }
+
+    pub fn apply(&self) -> bool {
+        self.request.apply
+    }
+
+    pub fn finish(self, modules: &HashMap<ModuleId, DynModuleHost>, connections: &HashMap<InputId, OutputId>)
+        -> GainStagingReport
+    {
+        let mut wires = self.meters.iter()
+            .map(|(output_id, meter)| (*output_id, meter.level()))
+            .collect::<Vec<_>>();
+
+        wires.sort_by_key(|(output_id, _)| *output_id);
+
+        let mut suggestions = Vec::new();
+
+        for (input_id, output_id) in connections {
+            let level = match self.meters.get(output_id) {
+                Some(meter) => meter.level(),
+                None => continue,
+            };
+
+            let current = match modules.get(&input_id.module_id()).map(|module| module.params()) {
+                Some(ModuleParams::Mixer(params)) => {
+                    match params.channels.get(input_id.index()) {
+                        Some(channel) => channel.gain,
+                        None => continue,
+                    }
+                }
+                // mixer channel gain is currently the only trim point
+                _ => continue,
+            };
+
+            if let Some(suggested) = suggest_trim(&level) {
+                suggestions.push(TrimSuggestion {
+                    input: *input_id,
+                    level,
+                    current,
+                    suggested,
+                });
+            }
+        }
+
+        suggestions.sort_by_key(|suggestion| suggestion.input);
+
+        GainStagingReport {
+            wires,
+            suggestions,
+            applied: self.request.apply,
+        }
+    }
+}
+
+fn suggest_trim(level: &WireLevel) -> Option<Decibel> {
+    if level.rms.0 < SILENCE_THRESHOLD.0 {
+        return None;
+    }
+
+    let to_nominal = NOMINAL_RMS.0 - level.rms.0;
+    let to_ceiling = PEAK_CEILING.0 - level.peak.0;
+
+    let trim = f64::min(to_nominal, to_ceiling);
+    let trim = f64::max(-MAX_TRIM, f64::min(MAX_TRIM, trim));
+
+    Some(Decibel(trim))
+}
+
+/// Returns updated params for modules whose trims should change according to
+/// the report
+pub fn apply_report(report: &GainStagingReport, modules: &HashMap<ModuleId, DynModuleHost>)
+    -> Vec<(ModuleId, ModuleParams)>
+{
+    let mut updated = HashMap::<ModuleId, ModuleParams>::new();
+
+    for suggestion in &report.suggestions {
+        let module_id = suggestion.input.module_id();
+
+        let module = match modules.get(&module_id) {
+            Some(module) => module,
+            None => continue,
+        };
+
+        let params = updated.entry(module_id)
+            .or_insert_with(|| module.params());
+
+        if let ModuleParams::Mixer(params) = params {
+            if let Some(channel) = params.channels.get_mut(suggestion.input.index()) {
+                channel.gain = suggestion.suggested;
+            }
+        }
+    }
+
+    updated.into_iter().collect()
+}
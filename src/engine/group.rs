@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use mixlab_protocol::{ModuleId, ModuleParams};
+
+/// Fader levels of every VCA group module in the workspace, shared with
+/// modules so that member channels can scale themselves accordingly
+#[derive(Debug, Clone, Default)]
+pub struct GroupLevels {
+    levels: Arc<RwLock<HashMap<ModuleId, f64>>>,
+}
+
+impl GroupLevels {
+    pub fn new() -> Self {
+        GroupLevels::default()
+    }
+
+    /// Returns the fader level of the given group, unity if not assigned to
+    /// a group or if the group no longer exists
+    pub fn level(&self, group: Option<ModuleId>) -> f64 {
+        group
+            .and_then(|group| self.levels.read().unwrap().get(&group).copied())
+            .unwrap_or(1.0)
+    }
+
+    /// Records the current state of a module, pass None if it was deleted
+    pub(in crate::engine) fn sync(&self, module_id: ModuleId, params: Option<&ModuleParams>) {
+        let mut levels = self.levels.write().unwrap();
+
+        match params {
+            Some(ModuleParams::VcaGroup(params)) => {
+                levels.insert(module_id, params.fader);
+            }
+            _ => {
+                levels.remove(&module_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use mixlab_protocol::{EngineIntrospection, InputId, InputIntrospection, ModuleIntrospection};
+
+use crate::engine::latency::Compensation;
+use crate::engine::param_link;
+use crate::engine::workspace::Workspace;
+
+/// Describes the graph as the next tick will run it. Latency compensation is
+/// worked out afresh in the same way the tick does, as the engine keeps it
+/// for the length of a tick only
+pub fn introspect(workspace: &Workspace) -> EngineIntrospection {
+    let mut compensation = Compensation::default();
+
+    let modules = workspace.run_order().into_iter()
+        .map(|module_id| {
+            let module = &workspace.modules[&module_id];
+
+            let sources = (0..module.inputs().len())
+                .map(|i| workspace.connections.get(&InputId(module_id, i)).copied())
+                .collect::<Vec<_>>();
+
+            let delays = compensation.align(module_id,
+                &sources.iter().map(|source| source.map(|output_id| output_id.module_id())).collect::<Vec<_>>(),
+                module.latency());
+
+            let inputs = module.inputs().iter()
+                .zip(sources)
+                .zip(delays)
+                .map(|((terminal, source), delay)| {
+                    // as the tick reads them, from the source's own outputs:
+                    let source = source.and_then(|output_id| {
+                        let source = workspace.modules.get(&output_id.module_id())?;
+                        let output = source.outputs().get(output_id.index())?;
+                        Some((output_id, output.line_type()))
+                    });
+
+                    InputIntrospection {
+                        line_type: terminal.line_type(),
+                        converted: source.map(|(_, line_type)| line_type != terminal.line_type()).unwrap_or(false),
+                        source,
+                        delay,
+                    }
+                })
+                .collect();
+
+            let params = workspace.params(module_id).unwrap_or_else(|| module.params());
+
+            ModuleIntrospection {
+                id: module_id,
+                kind: param_link::list_params(&params).map(|(kind, _)| kind).unwrap_or_else(|| "Module".to_owned()),
+                latency: module.latency(),
+                path_latency: compensation.path_latency(module_id),
+                inputs,
+                outputs: module.outputs().iter().map(|output| output.line_type()).collect(),
+            }
+        })
+        .collect();
+
+    EngineIntrospection {
+        sample_rate: workspace.config.sample_rate,
+        block_size: workspace.config.block_size,
+        modules,
+    }
+}
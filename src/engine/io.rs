@@ -1,12 +1,13 @@
 use mixlab_protocol::LineType;
 use mixlab_util::time::MediaDuration;
 
-use crate::engine::{CHANNELS, SAMPLES_PER_TICK};
+use crate::engine::CHANNELS;
 use crate::engine::Sample;
+use crate::engine::config::MAX_BLOCK_SIZE;
 use crate::video;
 
-pub static ZERO_BUFFER_STEREO: [Sample; SAMPLES_PER_TICK * CHANNELS] = [0.0; SAMPLES_PER_TICK * CHANNELS];
-pub static ZERO_BUFFER_MONO: [Sample; SAMPLES_PER_TICK] = [0.0; SAMPLES_PER_TICK];
+pub static ZERO_BUFFER_STEREO: [Sample; MAX_BLOCK_SIZE * CHANNELS] = [0.0; MAX_BLOCK_SIZE * CHANNELS];
+pub static ZERO_BUFFER_MONO: [Sample; MAX_BLOCK_SIZE] = [0.0; MAX_BLOCK_SIZE];
 
 #[derive(Debug, Clone)]
 pub struct VideoFrame {
@@ -17,7 +18,9 @@ pub struct VideoFrame {
 }
 
 pub enum InputRef<'a> {
-    Disconnected,
+    // carries the engine block size so that disconnected inputs read as
+    // silence of the right length:
+    Disconnected(usize),
     Mono(&'a [Sample]),
     Stereo(&'a [Sample]),
     Video(Option<&'a VideoFrame>),
@@ -26,7 +29,7 @@ pub enum InputRef<'a> {
 impl<'a> InputRef<'a> {
     pub fn connected(&self) -> bool {
         match self {
-            InputRef::Disconnected => false,
+            InputRef::Disconnected(_) => false,
             InputRef::Mono(_) |
             InputRef::Stereo(_) |
             InputRef::Video(_) => true,
@@ -35,7 +38,7 @@ impl<'a> InputRef<'a> {
 
     pub fn expect_mono(&self) -> &'a [Sample] {
         match self {
-            InputRef::Disconnected => &ZERO_BUFFER_MONO,
+            InputRef::Disconnected(block_size) => &ZERO_BUFFER_MONO[0..*block_size],
             InputRef::Mono(buff) => buff,
             InputRef::Stereo(_) => panic!("expected mono input, got stereo"),
             InputRef::Video(_) => panic!("expected mono input, got avc"),
@@ -44,7 +47,7 @@ impl<'a> InputRef<'a> {
 
     pub fn expect_stereo(&self) -> &'a [Sample] {
         match self {
-            InputRef::Disconnected => &ZERO_BUFFER_STEREO,
+            InputRef::Disconnected(block_size) => &ZERO_BUFFER_STEREO[0..(*block_size * CHANNELS)],
             InputRef::Stereo(buff) => buff,
             InputRef::Mono(_) => panic!("expected stereo input, got mono"),
             InputRef::Video(_) => panic!("expected stereo input, got avc"),
@@ -53,7 +56,7 @@ impl<'a> InputRef<'a> {
 
     pub fn expect_video(&self) -> Option<&VideoFrame> {
         match self {
-            InputRef::Disconnected => None,
+            InputRef::Disconnected(_) => None,
             InputRef::Stereo(_) => panic!("expected stereo input, got stereo"),
             InputRef::Mono(_) => panic!("expected stereo input, got mono"),
             InputRef::Video(frame) => *frame,
@@ -68,10 +71,10 @@ pub enum Output {
 }
 
 impl Output {
-    pub fn from_line_type(line_type: LineType) -> Output {
+    pub fn from_line_type(line_type: LineType, block_size: usize) -> Output {
         match line_type {
-            LineType::Mono => Output::Mono(vec![0.0; SAMPLES_PER_TICK]),
-            LineType::Stereo => Output::Stereo(vec![0.0; SAMPLES_PER_TICK * CHANNELS]),
+            LineType::Mono => Output::Mono(vec![0.0; block_size]),
+            LineType::Stereo => Output::Stereo(vec![0.0; block_size * CHANNELS]),
             LineType::Video => Output::Video(None),
         }
     }
@@ -84,6 +87,32 @@ impl Output {
         }
     }
 
+    /// Converts audio for connection to an input of a different line type,
+    /// returns None if no conversion is needed. Mono is duplicated into both
+    /// channels, stereo is averaged down to mono.
+    pub fn convert_for(&self, line_type: LineType) -> Option<Output> {
+        match (self, line_type) {
+            (Output::Mono(buff), LineType::Stereo) => {
+                let mut stereo = Vec::with_capacity(buff.len() * CHANNELS);
+
+                for sample in buff {
+                    stereo.push(*sample);
+                    stereo.push(*sample);
+                }
+
+                Some(Output::Stereo(stereo))
+            }
+            (Output::Stereo(buff), LineType::Mono) => {
+                let mono = buff.chunks(CHANNELS)
+                    .map(|frame| frame.iter().sum::<Sample>() / CHANNELS as Sample)
+                    .collect();
+
+                Some(Output::Mono(mono))
+            }
+            _ => None,
+        }
+    }
+
     pub fn as_output_ref(&mut self) -> OutputRef<'_> {
         match self {
             Output::Mono(buff) => OutputRef::Mono(buff),
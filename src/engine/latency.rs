@@ -0,0 +1,185 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use mixlab_protocol::{InputId, ModuleId};
+
+use crate::engine::{Output, Sample, CHANNELS};
+
+/// Delays inputs arriving ahead of inputs from slower parallel paths so that
+/// every input of a module lines up in time. Latencies are in samples.
+#[derive(Default)]
+pub struct Compensation {
+    // latency of each module's outputs this tick, relative to the sources
+    // of the graph:
+    path_latency: HashMap<ModuleId, usize>,
+    delays: HashMap<InputId, DelayLine>,
+    touched: HashSet<InputId>,
+}
+
+impl Compensation {
+    /// Works out how much each input of a module needs delaying by, given
+    /// the sources of its inputs have all run already this tick. Records the
+    /// latency of the module's own outputs for modules further down the graph.
+    pub fn align(&mut self, module_id: ModuleId, sources: &[Option<ModuleId>], latency: usize) -> Vec<usize> {
+        let arrivals = sources.iter()
+            .map(|source| source.map(|id| self.path_latency.get(&id).copied().unwrap_or(0)))
+            .collect::<Vec<_>>();
+
+        let aligned = arrivals.iter().flatten().copied().max().unwrap_or(0);
+
+        self.path_latency.insert(module_id, aligned + latency);
+
+        arrivals.into_iter()
+            .map(|arrival| arrival.map(|arrival| aligned - arrival).unwrap_or(0))
+            .collect()
+    }
+
+    /// Latency of a module's outputs as last aligned, zero if it hasn't been
+    pub fn path_latency(&self, module_id: ModuleId) -> usize {
+        self.path_latency.get(&module_id).copied().unwrap_or(0)
+    }
+
+    /// Returns the delayed signal for an input, or None if the input needs
+    /// no delaying
+    pub fn delay(&mut self, input: InputId, samples: usize, output: &Output) -> Option<Output> {
+        if samples == 0 {
+            return None;
+        }
+
+        self.touched.insert(input);
+
+        let line = self.delays.entry(input)
+            .or_insert_with(|| DelayLine::new(samples));
+
+        line.set_delay(samples);
+        line.process(output)
+    }
+
+    /// Drops delay lines for inputs which no longer need them, call after
+    /// each tick
+    pub fn end_tick(&mut self) {
+        let touched = &self.touched;
+        self.delays.retain(|input, _| touched.contains(input));
+        self.touched.clear();
+        self.path_latency.clear();
+    }
+}
+
+/// Delays audio by a whole number of samples, kept across ticks
+pub(in crate::engine) struct DelayLine {
+    delay: usize,
+    channels: usize,
+    buffer: VecDeque<Sample>,
+}
+
+impl DelayLine {
+    pub(in crate::engine) fn new(delay: usize) -> Self {
+        DelayLine { delay, channels: 0, buffer: VecDeque::new() }
+    }
+
+    // growing the delay inserts silence and shrinking it skips ahead, either
+    // is audible but much less so than starting the line over
+    pub(in crate::engine) fn set_delay(&mut self, delay: usize) {
+        if delay > self.delay {
+            for _ in 0..((delay - self.delay) * self.channels) {
+                self.buffer.push_front(0.0);
+            }
+        } else {
+            self.buffer.drain(..((self.delay - delay) * self.channels).min(self.buffer.len()));
+        }
+
+        self.delay = delay;
+    }
+
+    // video frames are passed through as is, there is no buffering them at
+    // the sample level
+    pub(in crate::engine) fn process(&mut self, output: &Output) -> Option<Output> {
+        let (samples, channels) = match output {
+            Output::Mono(samples) => (samples, 1),
+            Output::Stereo(samples) => (samples, CHANNELS),
+            Output::Video(_) => return None,
+        };
+
+        if channels != self.channels {
+            self.channels = channels;
+            self.buffer.clear();
+            self.buffer.resize(self.delay * channels, 0.0);
+        }
+
+        self.buffer.extend(samples.iter().copied());
+
+        let delayed = self.buffer.drain(..samples.len()).collect();
+
+        Some(match output {
+            Output::Mono(_) => Output::Mono(delayed),
+            _ => Output::Stereo(delayed),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use mixlab_protocol::{InputId, ModuleId};
+
+    use crate::engine::Output;
+    use super::{Compensation, DelayLine};
+
+    fn module(id: usize) -> ModuleId {
+        ModuleId(NonZeroUsize::new(id).unwrap())
+    }
+
+    fn mono(output: Option<Output>) -> Vec<f32> {
+        match output {
+            Some(Output::Mono(samples)) => samples,
+            _ => panic!("expected mono output"),
+        }
+    }
+
+    #[test]
+    fn aligns_parallel_paths() {
+        let (source, slow, mix) = (module(1), module(2), module(3));
+
+        let mut compensation = Compensation::default();
+        assert_eq!(compensation.align(source, &[], 0), Vec::<usize>::new());
+        assert_eq!(compensation.align(slow, &[Some(source)], 64), vec![0]);
+
+        // the direct path is held back to meet the slow one:
+        let delays = compensation.align(mix, &[Some(source), Some(slow)], 0);
+        assert_eq!(delays, vec![64, 0]);
+        assert_eq!(compensation.path_latency(mix), 64);
+
+        // an impulse from the source arrives at the same sample both ways
+        let mut slow_module = DelayLine::new(64);
+        let mut direct = Vec::new();
+        let mut through_slow = Vec::new();
+
+        for block in 0..4 {
+            let mut samples = vec![0.0; 32];
+            if block == 0 {
+                samples[0] = 1.0;
+            }
+
+            let signal = Output::Mono(samples);
+            direct.extend(mono(compensation.delay(InputId(mix, 0), delays[0], &signal)));
+            through_slow.extend(mono(slow_module.process(&signal)));
+        }
+
+        assert_eq!(direct, through_slow);
+        assert_eq!(direct.iter().position(|x| *x == 1.0), Some(64));
+    }
+
+    #[test]
+    fn set_delay_grows_and_shrinks() {
+        let mut line = DelayLine::new(2);
+        assert_eq!(mono(line.process(&Output::Mono(vec![1.0, 2.0, 3.0, 4.0]))), vec![0.0, 0.0, 1.0, 2.0]);
+
+        // silence is inserted ahead of what's already buffered:
+        line.set_delay(4);
+        assert_eq!(mono(line.process(&Output::Mono(vec![5.0, 6.0, 7.0, 8.0]))), vec![0.0, 0.0, 3.0, 4.0]);
+
+        // and the oldest samples skipped:
+        line.set_delay(1);
+        assert_eq!(mono(line.process(&Output::Mono(vec![9.0, 10.0, 11.0, 12.0]))), vec![8.0, 9.0, 10.0, 11.0]);
+    }
+}
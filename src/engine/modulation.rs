@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use mixlab_protocol::{LineType, ModuleId, ModuleParams, Modulation, ModulationId, OutputId, ParamRef};
+
+use crate::engine::Output;
+use crate::engine::module::DynModuleHost;
+use crate::engine::param_link;
+
+#[derive(Debug)]
+pub enum ModulationError {
+    NoSuchOutput(OutputId),
+    NotControlOutput(OutputId),
+    NoSuchModule(ModuleId),
+    NoSuchParam(ParamRef),
+}
+
+pub fn validate(modulation: &Modulation, modules: &HashMap<ModuleId, DynModuleHost>) -> Result<(), ModulationError> {
+    let source = modules.get(&modulation.source.module_id())
+        .and_then(|module| module.outputs().get(modulation.source.index()))
+        .ok_or(ModulationError::NoSuchOutput(modulation.source))?;
+
+    if source.line_type() != LineType::Mono {
+        return Err(ModulationError::NotControlOutput(modulation.source));
+    }
+
+    let target = modules.get(&modulation.target.module)
+        .ok_or(ModulationError::NoSuchModule(modulation.target.module))?;
+
+    if param_link::read_param(&target.params(), &modulation.target.path).is_none() {
+        return Err(ModulationError::NoSuchParam(modulation.target.clone()));
+    }
+
+    Ok(())
+}
+
+/// Control outputs are sampled once per tick, at the end of the block
+pub fn control_level(output: &Output) -> Option<f64> {
+    match output {
+        Output::Mono(buffer) => buffer.last().map(|sample| *sample as f64),
+        _ => None,
+    }
+}
+
+/// Params of a module as last set by hand, kept aside while modulation
+/// overwrites the params the module itself holds
+pub struct Modulated {
+    base: ModuleParams,
+    // what the module's params serialized to after modulation was last
+    // applied, anything else means the params have been set since:
+    applied: Value,
+}
+
+impl Modulated {
+    pub fn new(base: ModuleParams) -> Self {
+        Modulated { base, applied: Value::Null }
+    }
+
+    /// Returns the module's base params given its current params
+    pub fn base(&self, current: &ModuleParams) -> ModuleParams {
+        if serde_json::to_value(current).ok().as_ref() == Some(&self.applied) {
+            self.base.clone()
+        } else {
+            current.clone()
+        }
+    }
+
+    pub fn record(&mut self, base: ModuleParams, applied: &ModuleParams) {
+        self.base = base;
+        self.applied = serde_json::to_value(applied).unwrap_or(Value::Null);
+    }
+}
+
+/// Applies every modulation targeting `module_id` to its base params,
+/// returning the params the module should run with
+pub fn apply(
+    module_id: ModuleId,
+    modulations: &HashMap<ModulationId, Modulation>,
+    levels: &HashMap<OutputId, f64>,
+    base: &ModuleParams,
+) -> ModuleParams {
+    let mut params = base.clone();
+
+    // sort for a consistent order when several modulations share a target:
+    let mut targeting = modulations.iter()
+        .filter(|(_, modulation)| modulation.target.module == module_id)
+        .collect::<Vec<_>>();
+
+    targeting.sort_by_key(|(id, _)| **id);
+
+    for (_, modulation) in targeting {
+        let level = match levels.get(&modulation.source) {
+            Some(level) => f64::max(0.0, f64::min(1.0, *level)),
+            None => continue,
+        };
+
+        let value = modulation.min + (modulation.max - modulation.min) * level;
+
+        if let Some(new_params) = param_link::write_param(&params, &modulation.target.path, value) {
+            params = new_params;
+        }
+    }
+
+    params
+}
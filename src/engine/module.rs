@@ -1,19 +1,28 @@
 use std::fmt;
 use std::future::Future;
 
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use tokio::runtime;
 use tokio::sync::mpsc;
 
-use mixlab_protocol::{ModuleParams, Indication, Terminal};
+use mixlab_protocol::{ModuleParams, Indication, Terminal, ParamSpec, PluginParams};
 
-use crate::engine::{InputRef, OutputRef};
+use crate::engine::{ClockRef, DeviceLinks, Diagnostics, EngineConfig, GroupLevels, InputRef, OutputRef, Rehearsal, Schedule, ZoneAudio};
 use crate::module::{self, ModuleT};
+use crate::plugin;
 use crate::project::ProjectBaseRef;
 
 #[derive(Debug)]
 pub struct ModuleCtx<M: ModuleT> {
     runtime: runtime::Handle,
     base: ProjectBaseRef,
+    config: EngineConfig,
+    groups: GroupLevels,
+    devices: DeviceLinks,
+    rehearsal: Rehearsal,
+    zone_audio: ZoneAudio,
+    diagnostics: Diagnostics,
     link: ModuleLink<M>,
 }
 
@@ -22,6 +31,37 @@ impl<M: ModuleT> ModuleCtx<M> {
         self.base.clone()
     }
 
+    pub fn config(&self) -> EngineConfig {
+        self.config
+    }
+
+    pub fn groups(&self) -> GroupLevels {
+        self.groups.clone()
+    }
+
+    pub fn devices(&self) -> DeviceLinks {
+        self.devices.clone()
+    }
+
+    pub fn rehearsal(&self) -> Rehearsal {
+        self.rehearsal.clone()
+    }
+
+    pub fn zone_audio(&self) -> ZoneAudio {
+        self.zone_audio.clone()
+    }
+
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.diagnostics.clone()
+    }
+
+    /// Whether the engine is only being tried out. Modules then leave alone
+    /// anything outside the engine: they open no devices, send nothing over
+    /// the network and write no files
+    pub fn dry_run(&self) -> bool {
+        self.config.dry_run
+    }
+
     pub fn link(&self) -> ModuleLink<M> {
         self.link.clone()
     }
@@ -34,15 +74,35 @@ impl<M: ModuleT> ModuleCtx<M> {
             }
         });
     }
+
+    /// As spawn_async, with the event delivered at engine sample time `at`.
+    /// Events ready too late are delivered as soon as they can be
+    pub fn spawn_scheduled(&self, at: u64, f: impl Future<Output = M::Event> + Send + 'static) {
+        let mut link = self.link();
+        self.runtime.spawn(async move {
+            let _ = link.schedule_event(at, f.await).await;
+        });
+    }
+}
+
+enum ModuleEvent<E> {
+    Now(E),
+    At(u64, E),
 }
 
 pub struct ModuleLink<M: ModuleT> {
-    events: mpsc::Sender<M::Event>,
+    events: mpsc::Sender<ModuleEvent<M::Event>>,
 }
 
 impl<M: ModuleT> ModuleLink<M> {
     pub async fn send_event(&mut self, ev: M::Event) -> Result<(), ()> {
-        self.events.send(ev).await.map_err(|_| ())
+        self.events.send(ModuleEvent::Now(ev)).await.map_err(|_| ())
+    }
+
+    /// Delivers an event to the module at engine sample time `at`, see
+    /// ModuleT::receive_scheduled_event
+    pub async fn schedule_event(&mut self, at: u64, ev: M::Event) -> Result<(), ()> {
+        self.events.send(ModuleEvent::At(at, ev)).await.map_err(|_| ())
     }
 }
 
@@ -61,16 +121,26 @@ impl<M: ModuleT> fmt::Debug for ModuleLink<M> {
 
 pub struct ModuleHost<M: ModuleT> {
     module: M,
-    events: mpsc::Receiver<M::Event>,
+    events: mpsc::Receiver<ModuleEvent<M::Event>>,
+    schedule: Schedule<M::Event>,
+    diagnostics: Diagnostics,
+    block_size: usize,
 }
 
 impl<M: ModuleT> ModuleHost<M> {
-    fn new(params: M::Params, base: ProjectBaseRef) -> (Self, M::Indication) {
+    fn new(params: M::Params, base: ProjectBaseRef, config: EngineConfig, groups: GroupLevels, devices: DeviceLinks, rehearsal: Rehearsal, zone_audio: ZoneAudio) -> (Self, M::Indication) {
         let (events_tx, events_rx) = mpsc::channel(2);
+        let diagnostics = Diagnostics::default();
 
         let ctx = ModuleCtx {
             runtime: runtime::Handle::current(),
             base,
+            config,
+            groups,
+            devices,
+            rehearsal,
+            zone_audio,
+            diagnostics: diagnostics.clone(),
             link: ModuleLink { events: events_tx },
         };
 
@@ -79,10 +149,33 @@ impl<M: ModuleT> ModuleHost<M> {
         let host = ModuleHost {
             module,
             events: events_rx,
+            schedule: Schedule::new(),
+            diagnostics,
+            block_size: config.block_size,
         };
 
         (host, indication)
     }
+
+    // hands over the module's events, then runs its tick
+    fn tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<M::Indication> {
+        while let Ok(ev) = self.events.try_recv() {
+            match ev {
+                ModuleEvent::Now(ev) => self.module.receive_event(ev),
+                ModuleEvent::At(at, ev) => self.schedule.insert(at, ev),
+            }
+        }
+
+        // hand over everything due within this tick, the module
+        // decides how precisely to apply it
+        let end = t + self.block_size as u64;
+
+        while let Some((at, ev)) = self.schedule.pop_due(end) {
+            self.module.receive_scheduled_event(at, ev);
+        }
+
+        self.module.run_tick(t, inputs, outputs)
+    }
 }
 
 pub trait DynModuleHostT {
@@ -91,6 +184,11 @@ pub trait DynModuleHostT {
     fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Indication>;
     fn inputs(&self) -> &[Terminal];
     fn outputs(&self) -> &[Terminal];
+    fn latency(&self) -> usize;
+    fn clock(&self) -> Option<ClockRef>;
+    fn varispeed(&self) -> Option<f64>;
+    fn param_specs(&self) -> Vec<ParamSpec>;
+    fn diagnostics(&self) -> &Diagnostics;
 }
 
 macro_rules! gen_dyn_module_impls {
@@ -110,11 +208,7 @@ macro_rules! gen_dyn_module_impls {
                 }
 
                 fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Indication> {
-                    if let Some(ev) = self.events.try_recv().ok() {
-                        self.module.receive_event(ev);
-                    }
-
-                    self.module.run_tick(t, inputs, outputs)
+                    self.tick(t, inputs, outputs)
                         .map(Indication::$module)
                 }
 
@@ -125,6 +219,26 @@ macro_rules! gen_dyn_module_impls {
                 fn outputs(&self) -> &[Terminal] {
                     self.module.outputs()
                 }
+
+                fn latency(&self) -> usize {
+                    self.module.latency()
+                }
+
+                fn clock(&self) -> Option<ClockRef> {
+                    self.module.clock()
+                }
+
+                fn varispeed(&self) -> Option<f64> {
+                    self.module.varispeed()
+                }
+
+                fn param_specs(&self) -> Vec<ParamSpec> {
+                    self.module.param_specs()
+                }
+
+                fn diagnostics(&self) -> &Diagnostics {
+                    &self.diagnostics
+                }
             }
         )*
     }
@@ -132,19 +246,169 @@ macro_rules! gen_dyn_module_impls {
 
 macro_rules! gen_host_fn {
     ($( $mod_name:ident::$module:ident , )*) => {
-        pub fn host(params: ModuleParams, base: ProjectBaseRef) -> (DynModuleHost, Indication) {
+        pub fn host(params: ModuleParams, base: ProjectBaseRef, config: EngineConfig, groups: GroupLevels, devices: DeviceLinks, rehearsal: Rehearsal, zone_audio: ZoneAudio) -> (DynModuleHost, Indication) {
             match params {
                 $(
                     ModuleParams::$module(params) => {
-                        let (host, indication) = ModuleHost::<module::$mod_name::$module>::new(params, base);
+                        let (host, indication) = ModuleHost::<module::$mod_name::$module>::new(params, base, config, groups.clone(), devices.clone(), rehearsal.clone(), zone_audio.clone());
                         (Box::new(host) as DynModuleHost, Indication::$module(indication))
                     }
                 )*
+                ModuleParams::Plugin(params) => {
+                    plugin::host(params, base, config, groups, devices, rehearsal, zone_audio)
+                }
             }
         }
     }
 }
 
+/// Hosts a module registered with `plugin::register`, passing its params
+/// and indications through json
+struct PluginHost<M: ModuleT> {
+    kind: String,
+    host: ModuleHost<M>,
+}
+
+pub(crate) fn host_plugin<M>(params: PluginParams, base: ProjectBaseRef, config: EngineConfig, groups: GroupLevels, devices: DeviceLinks, rehearsal: Rehearsal, zone_audio: ZoneAudio)
+    -> Result<(DynModuleHost, Indication), serde_json::Error>
+    where M: ModuleT, M::Params: Serialize + DeserializeOwned, M::Indication: Serialize
+{
+    let module_params = serde_json::from_str(&params.json)?;
+    let (host, indication) = ModuleHost::<M>::new(module_params, base, config, groups, devices, rehearsal, zone_audio);
+    let host = PluginHost { kind: params.kind, host };
+
+    Ok((Box::new(host), plugin_indication(&indication)))
+}
+
+fn plugin_indication(indication: &impl Serialize) -> Indication {
+    Indication::Plugin(serde_json::to_string(indication).expect("serde_json::to_string"))
+}
+
+impl<M> DynModuleHostT for PluginHost<M>
+    where M: ModuleT, M::Params: Serialize + DeserializeOwned, M::Indication: Serialize
+{
+    fn params(&self) -> ModuleParams {
+        ModuleParams::Plugin(PluginParams {
+            kind: self.kind.clone(),
+            json: serde_json::to_string(&self.host.module.params()).expect("serde_json::to_string"),
+        })
+    }
+
+    fn update(&mut self, new_params: ModuleParams) -> Option<Indication> {
+        let json = match new_params {
+            ModuleParams::Plugin(params) if params.kind == self.kind => params.json,
+            new_params => panic!("module params mismatch! module = {}, params = {:?}", self.kind, new_params),
+        };
+
+        // params come from clients as text, so may not parse:
+        match serde_json::from_str(&json) {
+            Ok(params) => {
+                self.host.diagnostics.clear("params");
+                self.host.module.update(params).map(|indication| plugin_indication(&indication))
+            }
+            Err(e) => {
+                self.host.diagnostics.error("params", format!("Params not applied: {}", e));
+                None
+            }
+        }
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Indication> {
+        self.host.tick(t, inputs, outputs)
+            .map(|indication| plugin_indication(&indication))
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        self.host.module.inputs()
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        self.host.module.outputs()
+    }
+
+    fn latency(&self) -> usize {
+        self.host.module.latency()
+    }
+
+    fn clock(&self) -> Option<ClockRef> {
+        self.host.module.clock()
+    }
+
+    fn varispeed(&self) -> Option<f64> {
+        self.host.module.varispeed()
+    }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        self.host.module.param_specs()
+    }
+
+    fn diagnostics(&self) -> &Diagnostics {
+        &self.host.diagnostics
+    }
+}
+
+/// Stands in for a plugin module which can't be created, eg. one saved by
+/// a build that registered it and opened by one that doesn't. It does
+/// nothing but hold on to its params, so that saving the project keeps them
+struct MissingPlugin {
+    params: PluginParams,
+    diagnostics: Diagnostics,
+}
+
+pub(crate) fn missing_plugin(params: PluginParams, message: String) -> (DynModuleHost, Indication) {
+    let diagnostics = Diagnostics::default();
+    diagnostics.error("plugin", message);
+
+    let host = MissingPlugin { params, diagnostics };
+    (Box::new(host), Indication::Plugin("null".to_owned()))
+}
+
+impl DynModuleHostT for MissingPlugin {
+    fn params(&self) -> ModuleParams {
+        ModuleParams::Plugin(self.params.clone())
+    }
+
+    fn update(&mut self, new_params: ModuleParams) -> Option<Indication> {
+        if let ModuleParams::Plugin(params) = new_params {
+            self.params = params;
+        }
+
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, _inputs: &[InputRef], _outputs: &mut [OutputRef]) -> Option<Indication> {
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &[]
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &[]
+    }
+
+    fn latency(&self) -> usize {
+        0
+    }
+
+    fn clock(&self) -> Option<ClockRef> {
+        None
+    }
+
+    fn varispeed(&self) -> Option<f64> {
+        None
+    }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        Vec::new()
+    }
+
+    fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+}
+
 crate::enumerate_modules!{then gen_dyn_module_impls!}
 crate::enumerate_modules!{then gen_host_fn!}
 
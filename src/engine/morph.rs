@@ -0,0 +1,45 @@
+use serde_json::{Map, Value};
+
+use mixlab_protocol::{ModuleParams, MorphState};
+
+/// Returns params for the morph's current position, or None if either side
+/// has not been stored yet
+pub fn params_at(morph: &MorphState) -> Option<ModuleParams> {
+    match (&morph.a, &morph.b) {
+        (Some(a), Some(b)) => interpolate(a, b, morph.position),
+        _ => None,
+    }
+}
+
+pub fn interpolate(a: &ModuleParams, b: &ModuleParams, t: f64) -> Option<ModuleParams> {
+    let a = serde_json::to_value(a).ok()?;
+    let b = serde_json::to_value(b).ok()?;
+    serde_json::from_value(lerp(&a, &b, t)).ok()
+}
+
+// only floats are interpolated - integers in params tend to be indices,
+// sequence numbers and the like, so are treated like any other discrete value
+// and snapped to the nearer side
+fn lerp(a: &Value, b: &Value, t: f64) -> Value {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) if x.is_f64() || y.is_f64() => {
+            match (x.as_f64(), y.as_f64()) {
+                (Some(x), Some(y)) => Value::from(x + (y - x) * t),
+                _ => nearest(a, b, t),
+            }
+        }
+        (Value::Array(xs), Value::Array(ys)) if xs.len() == ys.len() => {
+            Value::Array(xs.iter().zip(ys).map(|(x, y)| lerp(x, y, t)).collect())
+        }
+        (Value::Object(xs), Value::Object(ys)) if xs.len() == ys.len() && xs.keys().all(|k| ys.contains_key(k)) => {
+            Value::Object(xs.iter()
+                .map(|(k, x)| (k.clone(), lerp(x, &ys[k], t)))
+                .collect::<Map<_, _>>())
+        }
+        _ => nearest(a, b, t),
+    }
+}
+
+fn nearest(a: &Value, b: &Value, t: f64) -> Value {
+    if t < 0.5 { a.clone() } else { b.clone() }
+}
@@ -0,0 +1,165 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde_json::Value;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ParamLink, ParamLinkId, ParamRef};
+
+use crate::engine::module::DynModuleHost;
+
+#[derive(Debug)]
+pub enum LinkError {
+    NoSuchModule(ModuleId),
+    NoSuchParam(ParamRef),
+    SelfLink,
+}
+
+pub fn validate(link: &ParamLink, modules: &HashMap<ModuleId, DynModuleHost>) -> Result<(), LinkError> {
+    if link.source == link.target {
+        return Err(LinkError::SelfLink);
+    }
+
+    for param in &[&link.source, &link.target] {
+        let module = modules.get(&param.module)
+            .ok_or(LinkError::NoSuchModule(param.module))?;
+
+        if read_param(&module.params(), &param.path).is_none() {
+            return Err(LinkError::NoSuchParam((*param).clone()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Pushes the params of `module_id` through any links it is the source of,
+/// following chains of links onwards. Each module is updated at most once so
+/// that cycles of links terminate. Returns the params of every module changed.
+pub fn propagate(
+    module_id: ModuleId,
+    links: &HashMap<ParamLinkId, ParamLink>,
+    modules: &mut HashMap<ModuleId, DynModuleHost>,
+) -> Vec<(ModuleId, ModuleParams)> {
+    let mut updated = Vec::new();
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+
+    visited.insert(module_id);
+    queue.push_back(module_id);
+
+    while let Some(source_id) = queue.pop_front() {
+        let source_params = match modules.get(&source_id) {
+            Some(module) => module.params(),
+            None => continue,
+        };
+
+        // sort for a consistent order when several links share a target:
+        let mut outgoing = links.iter()
+            .filter(|(_, link)| link.source.module == source_id)
+            .collect::<Vec<_>>();
+
+        outgoing.sort_by_key(|(id, _)| **id);
+
+        for (_, link) in outgoing {
+            if visited.contains(&link.target.module) {
+                continue;
+            }
+
+            let value = match read_param(&source_params, &link.source.path) {
+                Some(value) => value * link.scale + link.offset,
+                None => continue,
+            };
+
+            let target = match modules.get_mut(&link.target.module) {
+                Some(module) => module,
+                None => continue,
+            };
+
+            let new_params = match write_param(&target.params(), &link.target.path, value) {
+                Some(params) => params,
+                None => continue,
+            };
+
+            target.update(new_params);
+
+            visited.insert(link.target.module);
+            queue.push_back(link.target.module);
+            updated.push((link.target.module, target.params()));
+        }
+    }
+
+    updated
+}
+
+// module params are externally tagged, so serialize to `{"Variant": {..}}`
+// and resolve paths relative to the inner object:
+
+pub fn read_param(params: &ModuleParams, path: &str) -> Option<f64> {
+    let value = serde_json::to_value(params).ok()?;
+    let inner = value.as_object()?.values().next()?;
+
+    match inner.pointer(path)? {
+        Value::Number(num) => num.as_f64(),
+        Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+/// Returns updated params with the parameter at `path` set to `value`, or None
+/// if there is no such parameter or it already has that value
+pub fn write_param(params: &ModuleParams, path: &str, value: f64) -> Option<ModuleParams> {
+    let mut json = serde_json::to_value(params).ok()?;
+    let inner = json.as_object_mut()?.values_mut().next()?;
+    let slot = inner.pointer_mut(path)?;
+
+    let new_value = match &*slot {
+        Value::Bool(_) => Value::Bool(value >= 0.5),
+        Value::Number(num) if num.is_u64() => Value::from(f64::max(0.0, value.round()) as u64),
+        Value::Number(num) if num.is_i64() => Value::from(value.round() as i64),
+        Value::Number(_) => Value::from(value),
+        _ => return None,
+    };
+
+    if *slot == new_value {
+        return None;
+    }
+
+    *slot = new_value;
+
+    serde_json::from_value(json).ok()
+}
+
+/// Lists the kind of module and every numeric or boolean param it has, as
+/// paths `read_param` and `write_param` accept, along with their values
+pub fn list_params(params: &ModuleParams) -> Option<(String, Vec<(String, f64)>)> {
+    let value = serde_json::to_value(params).ok()?;
+    let (kind, inner) = value.as_object()?.iter().next()?;
+
+    let mut found = Vec::new();
+    collect_params(inner, String::new(), &mut found);
+    Some((kind.clone(), found))
+}
+
+fn collect_params(value: &Value, path: String, found: &mut Vec<(String, f64)>) {
+    match value {
+        Value::Number(num) => {
+            if let Some(num) = num.as_f64() {
+                found.push((path, num));
+            }
+        }
+        Value::Bool(b) => {
+            found.push((path, if *b { 1.0 } else { 0.0 }));
+        }
+        Value::Array(items) => {
+            for (idx, item) in items.iter().enumerate() {
+                collect_params(item, format!("{}/{}", path, idx), found);
+            }
+        }
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                // escaped as json pointers require:
+                let key = key.replace('~', "~0").replace('/', "~1");
+                collect_params(field, format!("{}/{}", path, key), found);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,71 @@
+use mixlab_protocol::{ParamSearch, ParamMatch, ParamRef};
+
+use crate::engine::param_link;
+use crate::engine::workspace::Workspace;
+
+// modules like the mixer have a great many params, so searches matching
+// most of them are cut short rather than flooding the client:
+const MAX_RESULTS: usize = 200;
+
+/// Finds params matching the search, favorites first and the rest in module
+/// then path order
+pub fn search(search: &ParamSearch, workspace: &Workspace) -> Vec<ParamMatch> {
+    let terms = search.query
+        .split_whitespace()
+        .map(|term| term.to_lowercase())
+        .collect::<Vec<_>>();
+
+    let mut module_ids = workspace.modules.keys()
+        .copied()
+        .filter(|module_id| search.module.map(|id| id == *module_id).unwrap_or(true))
+        .collect::<Vec<_>>();
+
+    module_ids.sort();
+
+    let mut favorites = Vec::new();
+    let mut others = Vec::new();
+
+    for module_id in module_ids {
+        let (kind, params) = match workspace.params(module_id).as_ref().and_then(param_link::list_params) {
+            Some(listed) => listed,
+            None => continue,
+        };
+
+        let favorite_paths = workspace.favorite_params.get(&module_id)
+            .map(|paths| paths.as_slice())
+            .unwrap_or(&[]);
+
+        for (path, value) in params {
+            let favorite = favorite_paths.contains(&path);
+
+            // an empty query lists just the favorites:
+            let matched = if terms.is_empty() {
+                favorite
+            } else {
+                let haystack = format!("{} {}", kind, path).to_lowercase();
+                terms.iter().all(|term| haystack.contains(term.as_str()))
+            };
+
+            if !matched {
+                continue;
+            }
+
+            let param_match = ParamMatch {
+                param: ParamRef { module: module_id, path },
+                value,
+                favorite,
+            };
+
+            if favorite {
+                favorites.push(param_match);
+            } else {
+                others.push(param_match);
+            }
+        }
+    }
+
+    favorites.into_iter()
+        .chain(others)
+        .take(MAX_RESULTS)
+        .collect()
+}
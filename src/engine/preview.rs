@@ -0,0 +1,290 @@
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+use std::time::Instant;
+
+use tokio::runtime;
+use tokio::sync::{oneshot, Semaphore};
+
+use mixlab_protocol::{ClockSource, LineType, ModuleId, ModuleParams, OutputId};
+
+use crate::engine::check::{panic_message, with_headless_engine};
+use crate::engine::timing::EngineStat;
+use crate::engine::{Output, Sample, CHANNELS};
+use crate::persist;
+use crate::project::ProjectBaseRef;
+use crate::stretch;
+
+const MAX_SECONDS: f64 = 30.0;
+
+// stretching much further than this falls apart audibly:
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 4.0;
+
+// modules open media and the like asynchronously, so the chain runs in real
+// time for a moment before any of it is kept:
+const WARMUP_SECONDS: f64 = 1.0;
+
+// after which it runs faster than real time, though not so much faster that
+// media decoding can't keep up:
+const RENDER_RATE: u32 = 4;
+
+// each render runs an engine of its own, at several times real time. more
+// than this at once wait their turn, rather than starve the live engine:
+const MAX_RENDERS: usize = 2;
+
+lazy_static::lazy_static! {
+    static ref RENDERS: Semaphore = Semaphore::new(MAX_RENDERS);
+}
+
+/// A short render of an audio output, and of every module feeding it
+#[derive(Debug, Clone, Copy)]
+pub struct PreviewRequest {
+    pub output: OutputId,
+    // length of the preview as heard, after stretching:
+    pub seconds: f64,
+    // 2.0 plays the chain at twice its speed, at the same pitch:
+    pub speed: f64,
+}
+
+#[derive(Debug)]
+pub enum PreviewError {
+    NoSuchOutput,
+    NotAudio,
+    BadLength(f64),
+    BadSpeed(f64),
+    Panicked(String),
+}
+
+/// Renders the chain ending at the requested output as a wav, in an engine
+/// of its own so that nothing of it reaches the live program. Modules play
+/// from the start, eg. media from its beginning
+pub async fn preview(workspace: persist::Workspace, base: ProjectBaseRef, request: PreviewRequest) -> Result<Vec<u8>, PreviewError> {
+    if !(request.seconds > 0.0 && request.seconds <= MAX_SECONDS) {
+        return Err(PreviewError::BadLength(request.seconds));
+    }
+
+    if !(request.speed >= MIN_SPEED && request.speed <= MAX_SPEED) {
+        return Err(PreviewError::BadSpeed(request.speed));
+    }
+
+    let save = chain(workspace, request.output.module_id())
+        .ok_or(PreviewError::NoSuchOutput)?;
+
+    let permit = RENDERS.acquire().await;
+
+    let tokio_runtime = runtime::Handle::current();
+    let (tx, rx) = oneshot::channel();
+
+    // like the engine itself, off the runtime as modules may block:
+    thread::spawn(move || {
+        // held by the thread, as a render carries on should the request
+        // that asked for it go away:
+        let _permit = permit;
+
+        tokio_runtime.enter(|| {
+            let _ = tx.send(render(save, base, request));
+        })
+    });
+
+    rx.await.expect("preview thread")
+}
+
+// the workspace cut down to the module and those feeding it, directly or not.
+// the rest would only cost time, or worse, play out live
+fn chain(mut save: persist::Workspace, module_id: ModuleId) -> Option<persist::Workspace> {
+    if !save.modules.contains_key(&module_id) {
+        return None;
+    }
+
+    let mut keep = HashSet::new();
+    let mut pending = vec![module_id];
+
+    while let Some(id) = pending.pop() {
+        if !keep.insert(id) {
+            continue;
+        }
+
+        if let Some(module) = save.modules.get(&id) {
+            pending.extend(module.inputs.iter().flatten().map(|output| output.module_id()));
+
+            // zone outputs play out whatever feeds their zone:
+            if let ModuleParams::ZoneOutput(params) = &module.params {
+                let source = params.zone
+                    .and_then(|zone| save.zones.get(&zone))
+                    .and_then(|zone| zone.source);
+
+                pending.extend(source.map(|source| source.module_id()));
+            }
+        }
+    }
+
+    save.modules.retain(|id, _| keep.contains(id));
+
+    save.param_links.retain(|_, link| {
+        keep.contains(&link.source.module) && keep.contains(&link.target.module)
+    });
+
+    save.modulations.retain(|_, modulation| {
+        keep.contains(&modulation.source.module_id()) && keep.contains(&modulation.target.module)
+    });
+
+    // ticks are driven by the render, whatever clocks the chain contains:
+    save.clock_source = ClockSource::Internal;
+
+    Some(save)
+}
+
+fn render(save: persist::Workspace, base: ProjectBaseRef, request: PreviewRequest) -> Result<Vec<u8>, PreviewError> {
+    let config = save.config;
+    let output_id = request.output;
+
+    with_headless_engine(save, base, |engine| {
+        let line_type = engine.workspace.borrow().modules.get(&output_id.module_id())
+            .and_then(|module| module.outputs().get(output_id.index()))
+            .map(|output| output.line_type())
+            .ok_or(PreviewError::NoSuchOutput)?;
+
+        if line_type == LineType::Video {
+            return Err(PreviewError::NotAudio);
+        }
+
+        let ticks_per_second = config.ticks_per_second() as f64;
+        let warmup = (WARMUP_SECONDS * ticks_per_second) as u64;
+
+        // rendered at speed, to be stretched back out to the length asked for:
+        let ticks = warmup + (request.seconds * request.speed * ticks_per_second).ceil() as u64;
+
+        let mut stat = EngineStat::new(config);
+        let mut samples = Vec::new();
+        let started = Instant::now();
+
+        for tick in 0..ticks {
+            let due = if tick < warmup {
+                started + config.tick_end(tick + 1)
+            } else {
+                let warm = config.tick_end(warmup);
+                started + warm + (config.tick_end(tick + 1) - warm) / RENDER_RATE
+            };
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                stat.record_tick(due, |tick_stat| {
+                    engine.run_tick_with(tick, tick_stat, |buffers| {
+                        if tick >= warmup {
+                            collect(buffers.get(&output_id), &mut samples, config.block_size);
+                        }
+                    })
+                })
+            }));
+
+            if let Err(panic) = result {
+                return Err(PreviewError::Panicked(panic_message(panic)));
+            }
+
+            let now = Instant::now();
+
+            if now < due {
+                thread::sleep(due - now);
+            }
+        }
+
+        let stretched = stretch::stretch(&samples, CHANNELS, config.sample_rate, request.speed);
+        Ok(encode_wav(&stretched, config.sample_rate))
+    })
+}
+
+// appends a tick of the output as interleaved stereo
+fn collect(output: Option<&Output>, samples: &mut Vec<Sample>, block_size: usize) {
+    match output {
+        Some(Output::Stereo(buffer)) => {
+            samples.extend_from_slice(buffer);
+        }
+        Some(Output::Mono(buffer)) => {
+            for sample in buffer {
+                samples.extend_from_slice(&[*sample; CHANNELS]);
+            }
+        }
+        // the module didn't run this tick:
+        Some(Output::Video(_)) | None => {
+            samples.resize(samples.len() + block_size * CHANNELS, 0.0);
+        }
+    }
+}
+
+// as 16 bit pcm, which any browser plays
+fn encode_wav(samples: &[Sample], sample_rate: usize) -> Vec<u8> {
+    let channels = CHANNELS as u16;
+    let data_len = samples.len() as u32 * 2;
+
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate as u32).to_le_bytes());
+    wav.extend_from_slice(&(sample_rate as u32 * channels as u32 * 2).to_le_bytes());
+    wav.extend_from_slice(&(channels * 2).to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+
+    for sample in samples {
+        let sample = (sample.max(-1.0).min(1.0) * i16::MAX as Sample) as i16;
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::encode_wav;
+
+    fn u16_at(wav: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(wav[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn u32_at(wav: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(wav[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn header_layout() {
+        let wav = encode_wav(&[0.0; 6], 48000);
+
+        assert_eq!(wav.len(), 44 + 12);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32_at(&wav, 4), 36 + 12);
+        assert_eq!(&wav[8..12], b"WAVE");
+        assert_eq!(&wav[12..16], b"fmt ");
+        assert_eq!(u32_at(&wav, 16), 16);
+        // pcm:
+        assert_eq!(u16_at(&wav, 20), 1);
+        assert_eq!(u16_at(&wav, 22), 2);
+        assert_eq!(u32_at(&wav, 24), 48000);
+        assert_eq!(u32_at(&wav, 28), 48000 * 2 * 2);
+        assert_eq!(u16_at(&wav, 32), 4);
+        assert_eq!(u16_at(&wav, 34), 16);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32_at(&wav, 40), 12);
+    }
+
+    #[test]
+    fn samples_clip_to_full_scale() {
+        let wav = encode_wav(&[1.0, -1.0, 2.0, -2.0, 0.0, 0.5], 44100);
+
+        let samples = wav[44..].chunks(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(samples, vec![i16::MAX, -i16::MAX, i16::MAX, -i16::MAX, 0, i16::MAX / 2]);
+    }
+}
@@ -0,0 +1,24 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether the workspace is being rehearsed, shared with modules so that
+/// input modules given stand-in media can play it instead of their live
+/// source
+#[derive(Debug, Clone, Default)]
+pub struct Rehearsal {
+    active: Arc<AtomicBool>,
+}
+
+impl Rehearsal {
+    pub fn new(active: bool) -> Self {
+        Rehearsal { active: Arc::new(AtomicBool::new(active)) }
+    }
+
+    pub fn active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub(in crate::engine) fn set(&self, active: bool) {
+        self.active.store(active, Ordering::Relaxed);
+    }
+}
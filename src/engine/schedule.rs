@@ -0,0 +1,152 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::ops::Range;
+
+/// Events queued against engine sample time (the `t` passed to run_tick plus
+/// sample offset). Events scheduled for the same sample are returned in the
+/// order they were scheduled.
+#[derive(Debug)]
+pub struct Schedule<T> {
+    queue: BinaryHeap<Reverse<Entry<T>>>,
+    seq: u64,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    at: u64,
+    seq: u64,
+    event: T,
+}
+
+// entries are ordered by time alone, the event itself is never compared:
+
+impl<T> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at, self.seq) == (other.at, other.seq)
+    }
+}
+
+impl<T> Eq for Entry<T> {}
+
+impl<T> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Entry<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.at, self.seq).cmp(&(other.at, other.seq))
+    }
+}
+
+impl<T> Default for Schedule<T> {
+    fn default() -> Self {
+        Schedule { queue: BinaryHeap::new(), seq: 0 }
+    }
+}
+
+impl<T> Schedule<T> {
+    pub fn new() -> Self {
+        Schedule::default()
+    }
+
+    pub fn insert(&mut self, at: u64, event: T) {
+        let seq = self.seq;
+        self.seq += 1;
+        self.queue.push(Reverse(Entry { at, seq, event }));
+    }
+
+    /// Sample time of the next event, if any
+    pub fn next_time(&self) -> Option<u64> {
+        self.queue.peek().map(|Reverse(entry)| entry.at)
+    }
+
+    /// Removes and returns the next event due before `end`. Events scheduled
+    /// in the past are returned as soon as possible rather than dropped.
+    pub fn pop_due(&mut self, end: u64) -> Option<(u64, T)> {
+        if self.next_time()? >= end {
+            return None;
+        }
+
+        self.queue.pop().map(|Reverse(entry)| (entry.at, entry.event))
+    }
+
+    /// Runs a block of `len` samples starting at sample time `t` in segments,
+    /// applying each event due within the block at the sample it was
+    /// scheduled for. `f` is given each event and each sample range
+    /// (relative to `t`) to render in turn
+    pub fn run_block(&mut self, t: u64, len: usize, mut f: impl FnMut(Segment<T>)) {
+        let end = t + len as u64;
+        let mut offset = 0;
+
+        while let Some((at, event)) = self.pop_due(end) {
+            let event_offset = at.saturating_sub(t) as usize;
+
+            if event_offset > offset {
+                f(Segment::Render(offset..event_offset));
+                offset = event_offset;
+            }
+
+            f(Segment::Event(event));
+        }
+
+        if offset < len {
+            f(Segment::Render(offset..len));
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Segment<T> {
+    Event(T),
+    Render(Range<usize>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Schedule, Segment};
+
+    fn run(schedule: &mut Schedule<&'static str>, t: u64, len: usize) -> Vec<Segment<&'static str>> {
+        let mut segments = Vec::new();
+        schedule.run_block(t, len, |segment| segments.push(segment));
+        segments
+    }
+
+    #[test]
+    fn splits_block_at_event_samples() {
+        let mut schedule = Schedule::new();
+        schedule.insert(1010, "b");
+        schedule.insert(1003, "a");
+        schedule.insert(1010, "c");
+        schedule.insert(1064, "next");
+
+        assert_eq!(run(&mut schedule, 1000, 64), vec![
+            Segment::Render(0..3),
+            Segment::Event("a"),
+            Segment::Render(3..10),
+            Segment::Event("b"),
+            Segment::Event("c"),
+            Segment::Render(10..64),
+        ]);
+
+        // due on the first sample of the following block:
+        assert_eq!(run(&mut schedule, 1064, 64), vec![
+            Segment::Event("next"),
+            Segment::Render(0..64),
+        ]);
+    }
+
+    #[test]
+    fn late_events_apply_at_block_start() {
+        let mut schedule = Schedule::new();
+        schedule.insert(10, "late");
+
+        assert_eq!(run(&mut schedule, 1000, 64), vec![
+            Segment::Event("late"),
+            Segment::Render(0..64),
+        ]);
+
+        assert_eq!(run(&mut schedule, 1064, 64), vec![Segment::Render(0..64)]);
+    }
+}
@@ -1,24 +1,33 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::time::{Instant, Duration};
 
-use mixlab_protocol::{ModuleId, PerformanceInfo, PerformanceAccount, PerformanceMetric, Microseconds};
+use mixlab_protocol::{ModuleId, PerformanceInfo, PerformanceAccount, PerformanceMetric, Microseconds, Overload, ClockInfo};
 
-use crate::engine;
+use crate::engine::EngineConfig;
 use crate::util;
 
-pub const TICK_BUDGET: Duration = Duration::from_micros(1_000_000 / engine::TICKS_PER_SECOND as u64);
-
 pub struct EngineStat {
+    tick_rate: usize,
+    tick_budget: Duration,
     is_realtime: bool,
     last_lagged: Option<Instant>,
+    last_overload: Option<OverloadedTick>,
     accounts: HashMap<PerformanceAccount, Stat>,
 }
 
+struct OverloadedTick {
+    tick_time: Duration,
+    module: Option<ModuleId>,
+}
+
 impl EngineStat {
-    pub fn new() -> Self {
+    pub fn new(config: EngineConfig) -> Self {
         EngineStat {
+            tick_rate: config.ticks_per_second(),
+            tick_budget: config.tick_budget(),
             is_realtime: false,
             last_lagged: None,
+            last_overload: None,
             accounts: HashMap::new(),
         }
     }
@@ -33,46 +42,78 @@ impl EngineStat {
 
         let tick_time = end - start;
 
-        if tick_time > TICK_BUDGET {
+        if tick_time > tick.stat.tick_budget {
             tick.stat.last_lagged = Some(Instant::now());
-            eprintln!("WARNING: tick ran over time! elapsed: {} us, budget: {} us", tick_time.as_micros(), TICK_BUDGET.as_micros());
+            tick.stat.last_overload = Some(OverloadedTick {
+                tick_time,
+                module: tick.heaviest_module.map(|(module_id, _)| module_id),
+            });
+
+            eprintln!("WARNING: tick ran over time! elapsed: {} us, budget: {} us", tick_time.as_micros(), tick.stat.tick_budget.as_micros());
         }
 
-        tick.stat.add_sample(PerformanceAccount::Engine, tick_time - tick.modules_accounted_for);
+        let engine_time = tick_time - tick.modules_accounted_for;
+        tick.stat.add_sample(PerformanceAccount::Engine, engine_time);
 
         retn
     }
 
-    pub fn report(&self) -> PerformanceInfo {
+    pub fn report(&self, clock: ClockInfo) -> PerformanceInfo {
         let time_since_lag = self.last_lagged.map(|time| Instant::now() - time);
+        let lag = util::temporal_warning(time_since_lag);
+
+        let overload = lag.and_then(|status| {
+            self.last_overload.as_ref().map(|overload| Overload {
+                status,
+                tick_time: micros(overload.tick_time),
+                module: overload.module,
+            })
+        });
 
         PerformanceInfo {
             realtime: self.is_realtime,
-            lag: util::temporal_warning(time_since_lag),
-            tick_rate: engine::TICKS_PER_SECOND,
-            tick_budget: Microseconds(TICK_BUDGET.as_micros() as u64),
+            lag,
+            tick_rate: self.tick_rate,
+            tick_budget: micros(self.tick_budget),
             accounts: self.accounts.iter().map(|(account, stat)| {
                 (*account, PerformanceMetric {
-                    last: Microseconds(stat.last().as_micros() as u64),
+                    last: micros(stat.last()),
+                    mean: micros(stat.mean()),
+                    max: micros(stat.max()),
                 })
-            }).collect()
+            }).collect(),
+            overload,
+            clock,
         }
     }
 
     pub fn remove_module(&mut self, module_id: ModuleId) {
         self.accounts.remove(&PerformanceAccount::Module(module_id));
+
+        if let Some(overload) = &mut self.last_overload {
+            if overload.module == Some(module_id) {
+                overload.module = None;
+            }
+        }
     }
 
     fn add_sample(&mut self, account: PerformanceAccount, sample: Duration) {
+        let window = usize::max(1, self.tick_rate);
+
         self.accounts.entry(account)
-            .and_modify(|stat| stat.add_sample(sample))
-            .or_insert(Stat::with_initial_sample(sample));
+            .or_insert_with(|| Stat::new(window))
+            .add_sample(sample);
     }
 }
 
+fn micros(duration: Duration) -> Microseconds {
+    Microseconds(duration.as_micros() as u64)
+}
+
 pub struct TickStat<'a> {
     stat: &'a mut EngineStat,
     modules_accounted_for: Duration,
+    heaviest_module: Option<(ModuleId, Duration)>,
 }
 
 impl<'a> TickStat<'a> {
@@ -80,6 +121,7 @@ impl<'a> TickStat<'a> {
         TickStat {
             stat,
             modules_accounted_for: Duration::from_micros(0),
+            heaviest_module: None,
         }
     }
 
@@ -89,27 +131,57 @@ impl<'a> TickStat<'a> {
         let end = Instant::now();
         let elapsed_time = end - start;
         self.modules_accounted_for += elapsed_time;
+
+        let heaviest = self.heaviest_module.map(|(_, time)| time);
+        if heaviest.map(|time| elapsed_time > time).unwrap_or(true) {
+            self.heaviest_module = Some((module_id, elapsed_time));
+        }
+
         self.stat.add_sample(PerformanceAccount::Module(module_id), elapsed_time);
         retn
     }
 }
 
+// rolling window of the most recent samples for an account
 struct Stat {
-    last: u128
+    window: usize,
+    samples: VecDeque<Duration>,
+    total: Duration,
 }
 
 impl Stat {
-    pub fn with_initial_sample(sample: Duration) -> Self {
+    pub fn new(window: usize) -> Self {
         Stat {
-            last: sample.as_micros(),
+            window,
+            samples: VecDeque::with_capacity(window),
+            total: Duration::from_micros(0),
         }
     }
 
     pub fn last(&self) -> Duration {
-        Duration::from_micros(self.last as u64)
+        self.samples.back().copied().unwrap_or_default()
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.samples.is_empty() {
+            Duration::from_micros(0)
+        } else {
+            self.total / self.samples.len() as u32
+        }
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.iter().max().copied().unwrap_or_default()
     }
 
     pub fn add_sample(&mut self, sample: Duration) {
-        self.last = sample.as_micros();
+        if self.samples.len() == self.window {
+            if let Some(oldest) = self.samples.pop_front() {
+                self.total -= oldest;
+            }
+        }
+
+        self.samples.push_back(sample);
+        self.total += sample;
     }
 }
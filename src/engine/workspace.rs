@@ -1,21 +1,51 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::{Deref, DerefMut};
 
 use tokio::sync::watch;
 
-use mixlab_protocol::{ModuleId, InputId, OutputId, TerminalId, WindowGeometry, Indication, LineType};
+use mixlab_protocol::{ModuleId, ModuleParams, InputId, OutputId, TerminalId, WindowGeometry, Indication, LineType, ParamLinkId, ParamLink, MorphState, ModulationId, Modulation, ClockSource, Viewport, ViewId, View, Binding, ParamSpec, ZoneId, Zone};
 
+use crate::engine::{DeviceLinks, EngineConfig, GroupLevels, Output, Rehearsal, ZoneAudio};
+use crate::engine::latency::Compensation;
 use crate::engine::module::{self, DynModuleHost};
+use crate::engine::modulation::{self, Modulated, ModulationError};
+use crate::engine::param_link::{self, LinkError};
+use crate::engine::zones::ZoneProcessor;
 use crate::persist;
 use crate::project::ProjectBaseRef;
 use crate::util::Sequence;
 
 pub struct Workspace {
+    pub(in crate::engine) config: EngineConfig,
     pub(in crate::engine) module_seq: Sequence,
     pub(in crate::engine) modules: HashMap<ModuleId, DynModuleHost>,
     pub(in crate::engine) geometry: HashMap<ModuleId, WindowGeometry>,
     pub(in crate::engine) connections: HashMap<InputId, OutputId>,
     pub(in crate::engine) indications: HashMap<ModuleId, Indication>,
+    pub(in crate::engine) groups: GroupLevels,
+    pub(in crate::engine) devices: DeviceLinks,
+    pub(in crate::engine) param_link_seq: Sequence,
+    pub(in crate::engine) param_links: HashMap<ParamLinkId, ParamLink>,
+    pub(in crate::engine) morphs: HashMap<ModuleId, MorphState>,
+    pub(in crate::engine) favorite_params: HashMap<ModuleId, Vec<String>>,
+    pub(in crate::engine) modulation_seq: Sequence,
+    pub(in crate::engine) modulations: HashMap<ModulationId, Modulation>,
+    pub(in crate::engine) clock_source: ClockSource,
+    pub(in crate::engine) rehearsal: Rehearsal,
+    pub(in crate::engine) viewport: Viewport,
+    pub(in crate::engine) view_seq: Sequence,
+    pub(in crate::engine) views: HashMap<ViewId, View>,
+    pub(in crate::engine) bindings: HashMap<String, Binding>,
+    pub(in crate::engine) zone_seq: Sequence,
+    pub(in crate::engine) zones: HashMap<ZoneId, Zone>,
+    pub(in crate::engine) zone_audio: ZoneAudio,
+    // not persisted, modulation is reapplied from scratch on load:
+    modulated: HashMap<ModuleId, Modulated>,
+    control_levels: HashMap<OutputId, f64>,
+    zone_processors: HashMap<ZoneId, ZoneProcessor>,
+    // as last sent out to clients:
+    param_specs: HashMap<ModuleId, Vec<ParamSpec>>,
+    pub(in crate::engine) latency: Compensation,
 }
 
 impl Workspace {
@@ -23,21 +53,67 @@ impl Workspace {
         let mut modules = HashMap::new();
         let mut geometry = HashMap::new();
         let mut indications = HashMap::new();
+        let mut morphs = HashMap::new();
+        let mut favorite_params = HashMap::new();
+        let groups = GroupLevels::new();
+        let devices = DeviceLinks::new(save.device_links.clone());
+        let rehearsal = Rehearsal::new(save.rehearsal);
+        let zone_audio = ZoneAudio::new();
+
+        for zone_id in save.zones.keys() {
+            zone_audio.create(*zone_id, save.config);
+        }
 
         // load modules and geometry
         for (module_id, saved_module) in &save.modules {
-            let (module, indication) = module::host(saved_module.params.clone(), base.clone());
+            let (module, indication) = module::host(saved_module.params.clone(), base.clone(), save.config, groups.clone(), devices.clone(), rehearsal.clone(), zone_audio.clone());
+            groups.sync(*module_id, Some(&saved_module.params));
             modules.insert(*module_id, module);
             geometry.insert(*module_id, saved_module.geometry.clone());
             indications.insert(*module_id, indication);
+
+            if let Some(morph) = &saved_module.morph {
+                morphs.insert(*module_id, morph.clone());
+            }
+
+            if !saved_module.favorite_params.is_empty() {
+                favorite_params.insert(*module_id, saved_module.favorite_params.clone());
+            }
         }
 
+        let param_specs = modules.iter()
+            .map(|(module_id, module)| (*module_id, module.param_specs()))
+            .collect();
+
         let mut workspace = Workspace {
+            config: save.config,
             module_seq: save.module_seq.clone(),
             modules,
             geometry,
             connections: HashMap::new(),
             indications,
+            groups,
+            devices,
+            param_link_seq: save.param_link_seq.clone(),
+            param_links: save.param_links.clone(),
+            morphs,
+            favorite_params,
+            modulation_seq: save.modulation_seq.clone(),
+            modulations: save.modulations.clone(),
+            clock_source: save.clock_source,
+            rehearsal,
+            viewport: save.viewport.clone(),
+            view_seq: save.view_seq.clone(),
+            views: save.views.clone(),
+            bindings: save.bindings.clone(),
+            zone_seq: save.zone_seq.clone(),
+            zones: save.zones.clone(),
+            zone_audio,
+            modulated: HashMap::new(),
+            control_levels: HashMap::new(),
+            zone_processors: HashMap::new(),
+            param_specs,
+            latency: Compensation::default(),
         };
 
         // load connections after loading all modules
@@ -57,10 +133,11 @@ impl Workspace {
 
     pub fn to_persist(&self) -> persist::Workspace {
         persist::Workspace {
+            config: self.config,
             module_seq: self.module_seq.clone(),
             modules: self.modules.iter()
                 .map(|(module_id, module)| {
-                    let params = module.params();
+                    let params = self.params(*module_id).unwrap_or_else(|| module.params());
 
                     let geometry = self.geometry.get(&module_id)
                         .cloned()
@@ -71,16 +148,106 @@ impl Workspace {
                         .map(|input_id| self.connections.get(&input_id).cloned())
                         .collect();
 
+                    let morph = self.morphs.get(&module_id).cloned();
+
+                    let favorite_params = self.favorite_params.get(&module_id)
+                        .cloned()
+                        .unwrap_or_default();
+
                     (*module_id, persist::Module {
                         params,
                         geometry,
                         inputs,
+                        morph,
+                        favorite_params,
                     })
                 })
-                .collect()
+                .collect(),
+            param_link_seq: self.param_link_seq.clone(),
+            param_links: self.param_links.clone(),
+            modulation_seq: self.modulation_seq.clone(),
+            modulations: self.modulations.clone(),
+            clock_source: self.clock_source,
+            rehearsal: self.rehearsal.active(),
+            viewport: self.viewport.clone(),
+            view_seq: self.view_seq.clone(),
+            views: self.views.clone(),
+            bindings: self.bindings.clone(),
+            device_links: self.devices.to_persist(),
+            zone_seq: self.zone_seq.clone(),
+            zones: self.zones.clone(),
         }
     }
 
+    /// Params of a module as set by hand, ie. without any modulation applied
+    pub fn params(&self, module_id: ModuleId) -> Option<ModuleParams> {
+        let params = self.modules.get(&module_id)?.params();
+
+        match self.modulated.get(&module_id) {
+            Some(modulated) => Some(modulated.base(&params)),
+            None => Some(params),
+        }
+    }
+
+    /// The order modules run in each tick, every module after the modules
+    /// its inputs are connected to
+    pub fn run_order(&self) -> Vec<ModuleId> {
+        // find terminal modules - modules which do not send their output to
+        // the input of any other module. sorted, so that the order is the
+        // same from one tick to the next
+        let mut terminal_modules = self.modules.keys().copied().collect::<HashSet<_>>();
+
+        for (_, output) in &self.connections {
+            terminal_modules.remove(&output.module_id());
+        }
+
+        let mut terminal_modules = terminal_modules.into_iter().collect::<Vec<_>>();
+        terminal_modules.sort();
+
+        // depth-first-search modules out via their inputs, starting from
+        // terminal modules
+
+        let mut topsort = Topsort {
+            modules: &self.modules,
+            connections: &self.connections,
+            run_order: Vec::new(),
+            seen: HashSet::new(),
+        };
+
+        for id in terminal_modules.into_iter() {
+            traverse(id, &mut topsort);
+        }
+
+        struct Topsort<'a> {
+            modules: &'a HashMap<ModuleId, DynModuleHost>,
+            connections: &'a HashMap<InputId, OutputId>,
+            run_order: Vec<ModuleId>,
+            seen: HashSet<ModuleId>,
+        }
+
+        fn traverse(module_id: ModuleId, state: &mut Topsort) {
+            if state.seen.contains(&module_id) {
+                return;
+            }
+
+            state.seen.insert(module_id);
+
+            let module = &state.modules[&module_id];
+
+            for i in 0..module.inputs().len() {
+                let terminal_id = InputId(module_id, i);
+
+                if let Some(output_id) = state.connections.get(&terminal_id) {
+                    traverse(output_id.module_id(), state);
+                }
+            }
+
+            state.run_order.push(module_id);
+        }
+
+        topsort.run_order
+    }
+
     fn terminal_type(&self, terminal: TerminalId) -> Option<LineType> {
         self.modules.get(&terminal.module_id()).and_then(|module| {
             match terminal {
@@ -105,7 +272,7 @@ impl Workspace {
             None => return Err(ConnectError::NoOutput),
         };
 
-        if input_type == output_type {
+        if output_type.can_connect(input_type) {
             Ok(self.connections.insert(input_id, output_id))
         } else {
             // type mismatch, don't connect
@@ -116,8 +283,187 @@ impl Workspace {
     pub fn disconnect(&mut self, input_id: InputId) -> Option<OutputId> {
         self.connections.remove(&input_id)
     }
+
+    pub fn link_params(&mut self, link: ParamLink) -> Result<ParamLinkId, LinkError> {
+        param_link::validate(&link, &self.modules)?;
+
+        let id = ParamLinkId(self.param_link_seq.next());
+        self.param_links.insert(id, link);
+        Ok(id)
+    }
+
+    pub fn unlink_params(&mut self, id: ParamLinkId) -> Option<ParamLink> {
+        self.param_links.remove(&id)
+    }
+
+    /// Removes all links to or from a module, returning their ids
+    pub fn unlink_module(&mut self, module_id: ModuleId) -> Vec<ParamLinkId> {
+        let ids = self.param_links.iter()
+            .filter(|(_, link)| link.source.module == module_id || link.target.module == module_id)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        for id in &ids {
+            self.param_links.remove(id);
+        }
+
+        ids
+    }
+
+    /// Finds modules whose param specs have changed since last called, or
+    /// which are new, returning their new specs
+    pub fn sync_param_specs(&mut self) -> Vec<(ModuleId, Vec<ParamSpec>)> {
+        let modules = &self.modules;
+        self.param_specs.retain(|module_id, _| modules.contains_key(module_id));
+
+        let mut changed = Vec::new();
+
+        for (module_id, module) in &self.modules {
+            let specs = module.param_specs();
+
+            if self.param_specs.get(module_id) != Some(&specs) {
+                self.param_specs.insert(*module_id, specs.clone());
+                changed.push((*module_id, specs));
+            }
+        }
+
+        changed.sort_by_key(|(module_id, _)| *module_id);
+        changed
+    }
+
+    /// Call after a module's params change to update any modules linked to it
+    pub fn propagate_params(&mut self, module_id: ModuleId) -> Vec<(ModuleId, ModuleParams)> {
+        let updated = param_link::propagate(module_id, &self.param_links, &mut self.modules);
+
+        for (module_id, params) in &updated {
+            self.groups.sync(*module_id, Some(params));
+        }
+
+        updated
+    }
+
+    pub fn modulate(&mut self, modulation: Modulation) -> Result<ModulationId, ModulationError> {
+        modulation::validate(&modulation, &self.modules)?;
+
+        let id = ModulationId(self.modulation_seq.next());
+        self.modulations.insert(id, modulation);
+        Ok(id)
+    }
+
+    pub fn unmodulate(&mut self, id: ModulationId) -> Option<Modulation> {
+        let modulation = self.modulations.remove(&id)?;
+        self.release_unmodulated();
+        Some(modulation)
+    }
+
+    /// Removes all modulations from or to a module, returning their ids
+    pub fn unmodulate_module(&mut self, module_id: ModuleId) -> Vec<ModulationId> {
+        let ids = self.modulations.iter()
+            .filter(|(_, modulation)| {
+                modulation.source.module_id() == module_id || modulation.target.module == module_id
+            })
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        for id in &ids {
+            self.modulations.remove(id);
+        }
+
+        self.modulated.remove(&module_id);
+        self.release_unmodulated();
+
+        ids
+    }
+
+    // puts modules which are no longer the target of any modulation back to
+    // their base params
+    pub(in crate::engine) fn release_unmodulated(&mut self) {
+        let targets = self.modulations.values()
+            .map(|modulation| modulation.target.module)
+            .collect::<HashSet<_>>();
+
+        let released = self.modulated.keys()
+            .filter(|module_id| !targets.contains(module_id))
+            .copied()
+            .collect::<Vec<_>>();
+
+        for module_id in released {
+            let params = self.params(module_id);
+            self.modulated.remove(&module_id);
+
+            if let (Some(module), Some(params)) = (self.modules.get_mut(&module_id), params) {
+                module.update(params.clone());
+                self.groups.sync(module_id, Some(&params));
+            }
+        }
+    }
+
+    /// Runs every modulation once with the control levels measured last
+    /// tick. Does not touch persisted state, base params are kept aside.
+    pub fn apply_modulations(&mut self) -> Vec<(ModuleId, Indication)> {
+        let mut indications = Vec::new();
+
+        let targets = self.modulations.values()
+            .map(|modulation| modulation.target.module)
+            .collect::<HashSet<_>>();
+
+        for module_id in targets {
+            let module = match self.modules.get_mut(&module_id) {
+                Some(module) => module,
+                None => continue,
+            };
+
+            let current = module.params();
+
+            let modulated = self.modulated.entry(module_id)
+                .or_insert_with(|| Modulated::new(current.clone()));
+
+            let base = modulated.base(&current);
+            let params = modulation::apply(module_id, &self.modulations, &self.control_levels, &base);
+
+            if let Some(indication) = module.update(params) {
+                indications.push((module_id, indication));
+            }
+
+            let applied = module.params();
+            self.groups.sync(module_id, Some(&applied));
+            modulated.record(base, &applied);
+        }
+
+        indications
+    }
+
+    /// Samples the outputs which modulations read from, call after each tick
+    pub fn measure_control_levels(&mut self, buffers: &HashMap<OutputId, Output>) {
+        self.control_levels.clear();
+
+        for modulation in self.modulations.values() {
+            if let Some(level) = buffers.get(&modulation.source).and_then(modulation::control_level) {
+                self.control_levels.insert(modulation.source, level);
+            }
+        }
+    }
+
+    /// Processes each zone's source as this tick left it, for zone outputs
+    /// to play out next tick. Call after each tick
+    pub fn process_zones(&mut self, buffers: &HashMap<OutputId, Output>) {
+        let config = self.config;
+        let zones = &self.zones;
+        let processors = &mut self.zone_processors;
+
+        processors.retain(|zone_id, _| zones.contains_key(zone_id));
+
+        for (zone_id, zone) in zones {
+            let processor = processors.entry(*zone_id)
+                .or_insert_with(|| ZoneProcessor::new(config));
+
+            let source = zone.source.and_then(|source| buffers.get(&source));
+            self.zone_audio.write(*zone_id, processor.process(zone, source, config));
+        }
+    }
 }
 
+#[derive(Debug)]
 pub enum ConnectError {
     NoInput,
     NoOutput,
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use mixlab_protocol::{LineType, Zone, ZoneId};
+
+use crate::engine::{EngineConfig, Output, Sample, CHANNELS};
+use crate::engine::latency::DelayLine;
+use crate::module::eq_three::ThreeBand;
+use crate::util;
+
+/// Longest delay a zone can be given, enough to line up speakers at the
+/// far end of a large room
+pub const MAX_DELAY_MS: f64 = 1000.0;
+
+/// Every zone's audio as of the last tick, processed for the zone. Shared
+/// with modules so that zone outputs can play their zone out wherever
+/// they're patched
+#[derive(Debug, Clone, Default)]
+pub struct ZoneAudio {
+    audio: Arc<RwLock<HashMap<ZoneId, Vec<Sample>>>>,
+}
+
+impl ZoneAudio {
+    pub fn new() -> Self {
+        ZoneAudio::default()
+    }
+
+    /// Copies a zone's last block of stereo audio into `output`. Returns
+    /// false if there's no such zone, leaving the output silent
+    pub fn read(&self, zone: ZoneId, output: &mut [Sample]) -> bool {
+        let audio = self.audio.read().unwrap();
+
+        match audio.get(&zone) {
+            Some(samples) => {
+                let len = samples.len().min(output.len());
+                output[..len].copy_from_slice(&samples[..len]);
+                util::zero(&mut output[len..]);
+                true
+            }
+            None => {
+                util::zero(output);
+                false
+            }
+        }
+    }
+
+    /// Starts a zone out silent, so that it's there to be read before it's
+    /// first processed
+    pub(in crate::engine) fn create(&self, zone: ZoneId, config: EngineConfig) {
+        self.write(zone, vec![0.0; config.block_size * CHANNELS]);
+    }
+
+    pub(in crate::engine) fn write(&self, zone: ZoneId, samples: Vec<Sample>) {
+        self.audio.write().unwrap().insert(zone, samples);
+    }
+
+    pub(in crate::engine) fn remove(&self, zone: ZoneId) {
+        self.audio.write().unwrap().remove(&zone);
+    }
+}
+
+/// The delay and eq of a single zone, kept from one tick to the next
+pub(in crate::engine) struct ZoneProcessor {
+    delay: DelayLine,
+    bands: Vec<ThreeBand>,
+}
+
+impl ZoneProcessor {
+    pub fn new(config: EngineConfig) -> Self {
+        ZoneProcessor {
+            delay: DelayLine::new(0),
+            bands: (0..CHANNELS).map(|_| ThreeBand::new(config.sample_rate)).collect(),
+        }
+    }
+
+    /// Processes a block of the zone's source into stereo for the zone.
+    /// Zones without a source, or with a video one, are silent
+    pub fn process(&mut self, zone: &Zone, source: Option<&Output>, config: EngineConfig) -> Vec<Sample> {
+        let block_len = config.block_size * CHANNELS;
+
+        // mono sources play out of both sides:
+        let converted = source.and_then(|output| output.convert_for(LineType::Stereo));
+
+        let input = match converted.as_ref().or(source) {
+            Some(Output::Stereo(samples)) => Output::Stereo(samples.clone()),
+            _ => Output::Stereo(vec![0.0; block_len]),
+        };
+
+        let delay_ms = zone.delay_ms.max(0.0).min(MAX_DELAY_MS);
+        self.delay.set_delay((delay_ms / 1000.0 * config.sample_rate as f64) as usize);
+
+        let mut samples = match self.delay.process(&input) {
+            Some(Output::Stereo(samples)) => samples,
+            _ => vec![0.0; block_len],
+        };
+
+        let gains = ThreeBand::gains(&zone.eq);
+
+        for frame in samples.chunks_mut(CHANNELS) {
+            for (sample, bands) in frame.iter_mut().zip(&mut self.bands) {
+                *sample = bands.process(*sample as f64, gains) as Sample;
+            }
+        }
+
+        samples
+    }
+}
@@ -11,9 +11,8 @@ use mixlab_codec::ogg::{self, OggStream};
 use mixlab_codec::{AudioStream, StreamRead, StreamError};
 use mixlab_util::time::{MediaTime, MediaDuration};
 
-use crate::engine::SAMPLE_RATE;
 use crate::listen::PeekTcpStream;
-use crate::source::{Registry, ListenError, SourceRecv, SourceSend};
+use crate::source::{Registry, ListenError, SourceRecv, SourceSend, AudioData};
 use crate::throttle::AudioThrottle;
 use crate::util::SyncRead;
 
@@ -91,13 +90,8 @@ fn run_decode_thread(mut send: SourceSend, stream: impl io::Read, content_type:
         return Ok(());
     }
 
-    if audio.sample_rate() != SAMPLE_RATE {
-        // not much we can do for now. TODO implement resampling
-        return Ok(());
-    }
-
     let mut timestamp = MediaTime::zero();
-    let mut throttle = AudioThrottle::new();
+    let mut throttle = AudioThrottle::new(audio.sample_rate());
 
     while let Some(packet) = audio.read().transpose() {
         match packet {
@@ -122,7 +116,12 @@ fn run_decode_thread(mut send: SourceSend, stream: impl io::Read, content_type:
                     }
                 }
 
-                send.write_audio(timestamp, samples)
+                let data = AudioData {
+                    sample_rate: audio.sample_rate(),
+                    samples,
+                };
+
+                send.write_audio(timestamp, data)
                     .map_err(|()| DecodeThreadError::ListenerDisconnected)?;
 
                 timestamp += MediaDuration::new(sample_count as i64, audio.sample_rate() as i64);
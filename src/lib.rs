@@ -0,0 +1,65 @@
+mod bench;
+mod browser;
+mod db;
+mod engine;
+mod icecast;
+mod listen;
+mod osc;
+mod persist;
+pub mod plugin;
+mod project;
+mod remote;
+mod resample;
+mod rtmp;
+mod server;
+mod source;
+mod stretch;
+mod throttle;
+mod util;
+mod video;
+
+#[macro_use]
+mod module;
+
+use structopt::StructOpt;
+use structopt::clap::AppSettings;
+
+// serving a project takes nothing but its path, so is what runs when no
+// other command is given:
+#[derive(StructOpt)]
+#[structopt(setting = AppSettings::SubcommandsNegateReqs)]
+struct Opts {
+    #[structopt(subcommand)]
+    command: Option<Command>,
+    #[structopt(flatten)]
+    run: server::RunOpts,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Measures what a project costs to run per tick on this machine
+    Bench(bench::BenchOpts),
+    /// Serves the frontend for a project running elsewhere with --link
+    Remote(remote::RemoteOpts),
+}
+
+/// Runs mixlab as its own binary does, taking options from the command
+/// line. Builds with modules of their own register them with
+/// `plugin::register` before calling this
+pub fn main() {
+    env_logger::init();
+
+    let mut runtime = tokio::runtime::Builder::new()
+        .enable_all()
+        .threaded_scheduler()
+        .build()
+        .unwrap();
+
+    let opts = Opts::from_args();
+
+    match opts.command {
+        Some(Command::Bench(opts)) => runtime.block_on(bench::run(opts)),
+        Some(Command::Remote(opts)) => runtime.block_on(remote::run(opts)),
+        None => runtime.block_on(server::run(opts.run)),
+    }
+}
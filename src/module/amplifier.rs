@@ -1,7 +1,7 @@
 use crate::engine::{self, Sample, InputRef, OutputRef};
 use crate::module::{ModuleT, LineType, Terminal};
 
-use mixlab_protocol::AmplifierParams;
+use mixlab_protocol::{AmplifierParams, ParamSpec, ParamUnit};
 
 #[derive(Debug)]
 pub struct Amplifier {
@@ -66,6 +66,13 @@ impl ModuleT for Amplifier {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("/amplitude", "Amplitude", ParamUnit::Percent, 0.0, 1.0),
+            ParamSpec::new("/mod_depth", "Mod depth", ParamUnit::Percent, 0.0, 1.0),
+        ]
+    }
 }
 
 pub fn depth(value: f64, depth: f64) -> f64 {
@@ -0,0 +1,217 @@
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use mixlab_protocol::{ArtNetOutputParams, ArtNetOutputIndication, DmxChannelParams, LineType, Terminal, ParamSpec, ParamUnit};
+
+use crate::engine::{self, InputRef, OutputRef};
+use crate::module::ModuleT;
+use crate::util;
+
+const ARTNET_PORT: u16 = 6454;
+const ARTNET_PROTOCOL_VERSION: u16 = 14;
+const OP_DMX: u16 = 0x5000;
+
+const DMX_UNIVERSE_SIZE: usize = 512;
+
+// art-net nodes expect no more than 44 frames a second, and a refresh at
+// least every few seconds even if nothing has changed:
+const MIN_SEND_INTERVAL: Duration = Duration::from_millis(23);
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct ArtNetOutput {
+    params: ArtNetOutputParams,
+    socket: Option<UdpSocket>,
+    destination: Option<SocketAddr>,
+    sequence: u8,
+    last_frame: Vec<u8>,
+    last_send: Option<Instant>,
+    last_error: Option<Instant>,
+    indication: ArtNetOutputIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for ArtNetOutput {
+    type Params = ArtNetOutputParams;
+    type Indication = ArtNetOutputIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        // with no socket nothing is sent:
+        let socket = if ctx.dry_run() {
+            None
+        } else {
+            UdpSocket::bind("0.0.0.0:0")
+                .and_then(|socket| {
+                    socket.set_nonblocking(true)?;
+                    socket.set_broadcast(true)?;
+                    Ok(socket)
+                })
+                .map_err(|e| eprintln!("artnet_output: could not open socket: {:?}", e))
+                .ok()
+        };
+
+        let indication = ArtNetOutputIndication { error: None };
+
+        let module = ArtNetOutput {
+            socket,
+            destination: parse_destination(&params.destination),
+            sequence: 0,
+            last_frame: Vec::new(),
+            last_send: None,
+            last_error: None,
+            indication: indication.clone(),
+            inputs: channel_inputs(&params.channels),
+            outputs: vec![],
+            params,
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        if params.channels.len() != self.params.channels.len() {
+            self.inputs = channel_inputs(&params.channels);
+        }
+
+        if params.destination != self.params.destination || params.universe != self.params.universe {
+            self.destination = parse_destination(&params.destination);
+            // send straight away rather than waiting for a change or keepalive
+            self.last_send = None;
+        }
+
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], _: &mut [OutputRef]) -> Option<Self::Indication> {
+        let now = Instant::now();
+
+        let frame = self.frame(inputs);
+
+        let due = match self.last_send {
+            None => true,
+            Some(last_send) => {
+                let elapsed = now - last_send;
+                (frame != self.last_frame && elapsed >= MIN_SEND_INTERVAL) || elapsed >= KEEPALIVE_INTERVAL
+            }
+        };
+
+        if due {
+            if self.send(&frame).is_err() {
+                self.last_error = Some(now);
+            }
+
+            self.last_send = Some(now);
+            self.last_frame = frame;
+        }
+
+        let indication = ArtNetOutputIndication {
+            error: util::temporal_warning(self.last_error.map(|time| now - time)),
+        };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        self.params.channels.iter()
+            .enumerate()
+            .map(|(idx, channel)| {
+                ParamSpec::new(format!("/channels/{}/level", idx), format!("Channel {} level", channel.address), ParamUnit::Percent, 0.0, 1.0)
+            })
+            .collect()
+    }
+}
+
+impl ArtNetOutput {
+    // connected control inputs take precedence over the level param:
+    fn frame(&self, inputs: &[InputRef]) -> Vec<u8> {
+        let mut frame = vec![0u8; DMX_UNIVERSE_SIZE];
+        let mut len = 0;
+
+        for (channel, input) in self.params.channels.iter().zip(inputs) {
+            let address = channel.address as usize;
+
+            if !(1..=DMX_UNIVERSE_SIZE).contains(&address) {
+                continue;
+            }
+
+            let level = if input.connected() {
+                input.expect_mono().last().map(|sample| *sample as f64).unwrap_or(0.0)
+            } else {
+                channel.level
+            };
+
+            frame[address - 1] = (f64::max(0.0, f64::min(1.0, level)) * 255.0).round() as u8;
+            len = usize::max(len, address);
+        }
+
+        // dmx data length must be even and at least 2:
+        len = usize::max(2, len + len % 2);
+        frame.truncate(len);
+        frame
+    }
+
+    fn send(&mut self, frame: &[u8]) -> Result<(), ()> {
+        let (socket, destination) = match (&self.socket, self.destination) {
+            (Some(socket), Some(destination)) => (socket, destination),
+            _ => return Err(()),
+        };
+
+        // sequence 0 means sequencing is disabled, so wrap around to 1:
+        self.sequence = self.sequence.checked_add(1).unwrap_or(1);
+
+        let packet = art_dmx(self.sequence, self.params.universe, frame);
+
+        // failures are reported via indication, logging them would flood
+        // the log at the frame rate
+        socket.send_to(&packet, destination)
+            .map(|_| ())
+            .map_err(|_| ())
+    }
+}
+
+fn art_dmx(sequence: u8, universe: u16, data: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(18 + data.len());
+    packet.extend(b"Art-Net\0");
+    packet.extend(&OP_DMX.to_le_bytes());
+    packet.extend(&ARTNET_PROTOCOL_VERSION.to_be_bytes());
+    packet.push(sequence);
+    // physical port, informational only:
+    packet.push(0);
+    // port-address is sent as sub-net/universe then net:
+    packet.push((universe & 0xff) as u8);
+    packet.push(((universe >> 8) & 0x7f) as u8);
+    packet.extend(&(data.len() as u16).to_be_bytes());
+    packet.extend(data);
+    packet
+}
+
+fn parse_destination(destination: &str) -> Option<SocketAddr> {
+    destination.trim().parse::<IpAddr>().ok()
+        .map(|ip| SocketAddr::new(ip, ARTNET_PORT))
+}
+
+fn channel_inputs(channels: &[DmxChannelParams]) -> Vec<Terminal> {
+    channels.iter().enumerate()
+        .map(|(i, _)| LineType::Mono.labeled(&(i + 1).to_string()))
+        .collect()
+}
@@ -0,0 +1,198 @@
+use std::collections::VecDeque;
+
+use mixlab_protocol::{BeatDetectorParams, BeatDetectorIndication, LineType, Terminal, ParamSpec, ParamUnit};
+
+use crate::engine::{self, InputRef, OutputRef, CHANNELS};
+use crate::module::ModuleT;
+
+// energy is measured over windows of this many samples per channel:
+const WINDOW_SIZE: usize = 512;
+
+// about one second of windows at 44.1khz, used as the local average an
+// onset must stand out against
+const HISTORY_WINDOWS: usize = 86;
+
+// windows quieter than this never count as onsets, so that noise in
+// near-silence does not trigger beats
+const MIN_ENERGY: f64 = 1e-5;
+
+// shortest gap between two onsets, caps detection at 300bpm:
+const MIN_ONSET_INTERVAL_MS: f64 = 200.0;
+
+// onsets considered for tempo estimation:
+const TEMPO_ONSETS: usize = 16;
+
+const MIN_BPM: f64 = 60.0;
+const MAX_BPM: f64 = 180.0;
+
+#[derive(Debug)]
+pub struct BeatDetector {
+    params: BeatDetectorParams,
+    sample_rate: usize,
+    window_energy: f64,
+    window_len: usize,
+    history: VecDeque<f64>,
+    onsets: VecDeque<u64>,
+    gate_until: u64,
+    indication: BeatDetectorIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for BeatDetector {
+    type Params = BeatDetectorParams;
+    type Indication = BeatDetectorIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let indication = BeatDetectorIndication { bpm: None, gate: false };
+
+        let module = BeatDetector {
+            params,
+            sample_rate: ctx.config().sample_rate,
+            window_energy: 0.0,
+            window_len: 0,
+            history: VecDeque::with_capacity(HISTORY_WINDOWS),
+            onsets: VecDeque::with_capacity(TEMPO_ONSETS),
+            gate_until: 0,
+            indication: indication.clone(),
+            inputs: vec![LineType::Stereo.labeled("Input")],
+            outputs: vec![LineType::Mono.labeled("Beat")],
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+        let output = outputs[0].expect_mono();
+
+        let gate_samples = (self.params.gate_ms / 1000.0 * self.sample_rate as f64) as u64;
+
+        for (i, (frame, out)) in input.chunks(CHANNELS).zip(output.iter_mut()).enumerate() {
+            let sample_seq = t + i as u64;
+
+            let mono = frame.iter().map(|x| *x as f64).sum::<f64>() / CHANNELS as f64;
+            self.window_energy += mono * mono;
+            self.window_len += 1;
+
+            if self.window_len == WINDOW_SIZE {
+                let energy = self.window_energy / WINDOW_SIZE as f64;
+                self.window_energy = 0.0;
+                self.window_len = 0;
+
+                if self.detect_onset(energy, sample_seq) {
+                    self.gate_until = sample_seq + gate_samples;
+                }
+            }
+
+            *out = if sample_seq < self.gate_until { 1.0 } else { 0.0 };
+        }
+
+        let indication = BeatDetectorIndication {
+            bpm: self.estimate_tempo(),
+            gate: t + output.len() as u64 <= self.gate_until,
+        };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("/sensitivity", "Sensitivity", ParamUnit::None, 1.0, 4.0),
+            ParamSpec::new("/gate_ms", "Gate", ParamUnit::Milliseconds, 5.0, 500.0).log(),
+        ]
+    }
+}
+
+impl BeatDetector {
+    fn detect_onset(&mut self, energy: f64, sample_seq: u64) -> bool {
+        let average = if self.history.is_empty() {
+            0.0
+        } else {
+            self.history.iter().sum::<f64>() / self.history.len() as f64
+        };
+
+        if self.history.len() == HISTORY_WINDOWS {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(energy);
+
+        // wait for a full history before detecting anything, the average of
+        // a handful of windows is meaningless
+        if self.history.len() < HISTORY_WINDOWS {
+            return false;
+        }
+
+        if energy < MIN_ENERGY || energy < average * self.params.sensitivity {
+            return false;
+        }
+
+        let min_interval = (MIN_ONSET_INTERVAL_MS / 1000.0 * self.sample_rate as f64) as u64;
+
+        if let Some(last) = self.onsets.back() {
+            if sample_seq - last < min_interval {
+                return false;
+            }
+        }
+
+        if self.onsets.len() == TEMPO_ONSETS {
+            self.onsets.pop_front();
+        }
+
+        self.onsets.push_back(sample_seq);
+        true
+    }
+
+    // median inter-onset interval, folded into a typical tempo range so that
+    // hearing only every other beat (or off-beats too) still gives a
+    // sensible reading
+    fn estimate_tempo(&self) -> Option<f64> {
+        if self.onsets.len() < 4 {
+            return None;
+        }
+
+        let mut tempos = self.onsets.iter()
+            .zip(self.onsets.iter().skip(1))
+            .map(|(a, b)| {
+                let mut bpm = 60.0 * self.sample_rate as f64 / (b - a) as f64;
+
+                while bpm < MIN_BPM { bpm *= 2.0; }
+                while bpm > MAX_BPM { bpm /= 2.0; }
+
+                bpm
+            })
+            .collect::<Vec<_>>();
+
+        tempos.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let median = tempos[tempos.len() / 2];
+
+        // round to a tenth of a bpm so that jitter does not flood the
+        // frontend with indication updates
+        Some((median * 10.0).round() / 10.0)
+    }
+}
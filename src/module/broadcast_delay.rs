@@ -0,0 +1,230 @@
+use std::collections::VecDeque;
+
+use mixlab_protocol::{BroadcastDelayParams, BroadcastDelayIndication, LineType, Terminal, BROADCAST_DELAY_MAX_SECONDS};
+use mixlab_util::time::MediaDuration;
+
+use crate::engine::{self, InputRef, OutputRef, Sample, CHANNELS};
+use crate::module::ModuleT;
+use crate::util;
+use crate::video;
+
+// the delay is built up, and brought back down, by playing the program
+// this much slower or faster than real time. slight enough to go unnoticed
+// in speech, so building 10 seconds back up after a dump takes 8 minutes:
+const ADJUST_RATE: f64 = 0.02;
+
+// like the replay buffer, video is held as decoded frames, which costs a lot
+// of memory for long delays
+#[derive(Debug)]
+pub struct BroadcastDelay {
+    ctx: engine::ModuleCtx<Self>,
+    params: BroadcastDelayParams,
+    buffer: Buffer,
+    indication: BroadcastDelayIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for BroadcastDelay {
+    type Params = BroadcastDelayParams;
+    type Indication = BroadcastDelayIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let indication = BroadcastDelayIndication {
+            delay_tenths: 0,
+            building: params.seconds > 0.0,
+        };
+
+        let module = BroadcastDelay {
+            ctx,
+            params,
+            buffer: Buffer::default(),
+            indication: indication.clone(),
+            inputs: vec![
+                LineType::Video.labeled("Video"),
+                LineType::Stereo.labeled("Audio"),
+            ],
+            outputs: vec![
+                LineType::Video.labeled("Video"),
+                LineType::Stereo.labeled("Audio"),
+            ],
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        let dump = params.dump != self.params.dump;
+
+        self.params = params;
+
+        if dump {
+            let sample_rate = self.ctx.config().sample_rate;
+            let seconds = f64::max(0.0, self.params.dump_seconds);
+            self.buffer.dump((seconds * sample_rate as f64) as usize);
+        }
+
+        self.indicate()
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let config = self.ctx.config();
+
+        self.buffer.push(inputs[1].expect_stereo(), inputs[0].expect_video(), config.sample_rate);
+
+        let (video_out, audio_out) = match outputs {
+            [video, audio] => (video.expect_video(), audio.expect_stereo()),
+            _ => unreachable!(),
+        };
+
+        let speed = match self.deficit() {
+            // within a tick of the target is close enough, rather than
+            // wavering either side of it:
+            deficit if deficit > config.block_size as i64 => 1.0 - ADJUST_RATE,
+            deficit if deficit < -(config.block_size as i64) => 1.0 + ADJUST_RATE,
+            _ => 1.0,
+        };
+
+        self.buffer.play(speed, config.sample_rate, video_out, audio_out);
+
+        self.indicate()
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+impl BroadcastDelay {
+    // samples short of the target delay, negative when over it:
+    fn deficit(&self) -> i64 {
+        let seconds = f64::max(0.0, f64::min(BROADCAST_DELAY_MAX_SECONDS, self.params.seconds));
+        let target = (seconds * self.ctx.config().sample_rate as f64) as i64;
+
+        target - self.buffer.len() as i64
+    }
+
+    fn indicate(&mut self) -> Option<BroadcastDelayIndication> {
+        let sample_rate = self.ctx.config().sample_rate;
+
+        let indication = BroadcastDelayIndication {
+            delay_tenths: (self.buffer.len() as u64 * 10) / sample_rate as u64,
+            building: self.deficit() > self.ctx.config().block_size as i64,
+        };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Buffer {
+    // interleaved stereo, the front is the next to be played:
+    audio: VecDeque<Sample>,
+    // fractional position between the first two buffered samples:
+    phase: f64,
+    // samples ever buffered, less those dumped. frame times are counted in
+    // the same samples, so that dumping leaves no gap in them:
+    end: u64,
+    // each frame with the time it starts at:
+    video: VecDeque<(u64, video::Frame)>,
+}
+
+impl Buffer {
+    fn len(&self) -> usize {
+        self.audio.len() / CHANNELS
+    }
+
+    // time of the next sample to be played, as frame times are counted:
+    fn position(&self) -> f64 {
+        (self.end - self.len() as u64) as f64 + self.phase
+    }
+
+    fn push(&mut self, audio: &[Sample], video: Option<&engine::VideoFrame>, sample_rate: usize) {
+        if let Some(frame) = video {
+            let offset = i64::max(0, frame.tick_offset.round_to_base(sample_rate as i64)) as u64;
+            self.video.push_back((self.end + offset, frame.data.clone()));
+        }
+
+        self.audio.extend(audio);
+        self.end += (audio.len() / CHANNELS) as u64;
+    }
+
+    // throws away the newest `len` samples, though never the sample being
+    // played, so that the output skips straight from what's airing now to
+    // whatever comes in next
+    fn dump(&mut self, len: usize) {
+        let len = usize::min(len, self.len().saturating_sub(1));
+
+        self.audio.truncate(self.audio.len() - len * CHANNELS);
+        self.end -= len as u64;
+
+        while let Some((at, _)) = self.video.back() {
+            if *at < self.end {
+                break;
+            }
+
+            self.video.pop_back();
+        }
+    }
+
+    fn play(&mut self, speed: f64, sample_rate: usize, video_out: &mut Option<engine::VideoFrame>, audio_out: &mut [Sample]) {
+        let tick_start = self.position();
+
+        // linearly interpolated, as the replay buffer plays slow motion:
+        for frame in audio_out.chunks_mut(CHANNELS) {
+            if self.len() < 2 {
+                util::zero(frame);
+                continue;
+            }
+
+            let frac = self.phase as Sample;
+
+            for (channel, out) in frame.iter_mut().enumerate() {
+                let a = self.audio[channel];
+                let b = self.audio[CHANNELS + channel];
+                *out = a + (b - a) * frac;
+            }
+
+            self.phase += speed;
+
+            while self.phase >= 1.0 && self.len() > 1 {
+                self.audio.drain(0..CHANNELS);
+                self.phase -= 1.0;
+            }
+        }
+
+        let tick_end = self.position();
+
+        // at most one frame per tick, the latest one due within it:
+        *video_out = None;
+
+        while let Some((at, frame)) = self.video.front() {
+            if *at as f64 >= tick_end {
+                break;
+            }
+
+            let tick_offset = (f64::max(0.0, *at as f64 - tick_start) / speed) as i64;
+
+            *video_out = Some(engine::VideoFrame {
+                data: frame.clone(),
+                tick_offset: MediaDuration::new(tick_offset, sample_rate as i64),
+            });
+
+            self.video.pop_front();
+        }
+    }
+}
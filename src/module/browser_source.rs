@@ -0,0 +1,170 @@
+use mixlab_protocol::{BrowserSourceParams, BrowserSourceIndication, LineType, Terminal};
+use mixlab_util::time::{MediaTime, MediaDuration};
+
+use crate::browser::{Page, PageSettings};
+use crate::engine::{self, InputRef, OutputRef, VideoFrame};
+use crate::module::ModuleT;
+use crate::video;
+
+// pages are laid out at this size at least, anything smaller is unusable:
+const MIN_DIMENSION: usize = 16;
+const MAX_DIMENSION: usize = 3840;
+
+#[derive(Debug)]
+pub struct BrowserSource {
+    ctx: engine::ModuleCtx<Self>,
+    params: BrowserSourceParams,
+    page: Option<Page>,
+    // the latest picture the page painted, repeated at the frame rate:
+    latest: Option<video::Frame>,
+    next_frame: Option<MediaTime>,
+    indication: BrowserSourceIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for BrowserSource {
+    type Params = BrowserSourceParams;
+    type Indication = BrowserSourceIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let indication = BrowserSourceIndication {
+            running: false,
+            error: false,
+        };
+
+        let mut module = BrowserSource {
+            ctx,
+            params,
+            page: None,
+            latest: None,
+            next_frame: None,
+            indication: indication.clone(),
+            inputs: vec![],
+            outputs: vec![
+                LineType::Video.unlabeled(),
+            ],
+        };
+
+        module.open();
+
+        let indication = module.indicate().unwrap_or(indication);
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        let reopen = params.url != self.params.url
+            || params.width != self.params.width
+            || params.height != self.params.height
+            || params.reload != self.params.reload;
+
+        self.params = params;
+
+        if reopen {
+            self.open();
+        }
+
+        self.indicate()
+    }
+
+    fn run_tick(&mut self, t: u64, _: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let config = self.ctx.config();
+        let now = config.media_time(t);
+        let end_of_tick = now + config.tick_duration();
+
+        if let Some(page) = &mut self.page {
+            if let Some(decoded) = page.recv_frame() {
+                self.latest = Some(video::Frame { decoded, duration_hint: MediaDuration::zero() });
+            }
+        }
+
+        let frame_duration = frame_duration(self.params.fps);
+        let next_frame = self.next_frame.get_or_insert(now);
+
+        // skip frames that fell due while there was nothing to show, rather
+        // than sending them all at once:
+        if *next_frame < now {
+            *next_frame = now;
+        }
+
+        *outputs[0].expect_video() = match &self.latest {
+            Some(latest) if *next_frame < end_of_tick => {
+                let tick_offset = *next_frame - now;
+
+                // at most one frame per tick:
+                while *next_frame < end_of_tick {
+                    *next_frame += frame_duration;
+                }
+
+                Some(VideoFrame {
+                    data: video::Frame {
+                        decoded: latest.decoded.clone(),
+                        duration_hint: frame_duration,
+                    },
+                    tick_offset,
+                })
+            }
+            _ => None,
+        };
+
+        self.indicate()
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+impl BrowserSource {
+    fn open(&mut self) {
+        self.latest = None;
+
+        // dropping the previous page closes its browser
+        self.page = None;
+
+        if self.params.url.is_empty() {
+            return;
+        }
+
+        self.page = Some(Page::open(PageSettings {
+            url: self.params.url.clone(),
+            width: clamp_dimension(self.params.width),
+            height: clamp_dimension(self.params.height),
+            color: self.ctx.config().colorimetry(),
+        }));
+    }
+
+    fn indicate(&mut self) -> Option<BrowserSourceIndication> {
+        let error = self.page.as_ref().map(|page| page.failed()).unwrap_or(false);
+
+        let indication = BrowserSourceIndication {
+            running: self.page.is_some() && !error,
+            error,
+        };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+}
+
+fn clamp_dimension(value: usize) -> usize {
+    value.max(MIN_DIMENSION).min(MAX_DIMENSION)
+}
+
+fn frame_duration(fps: f64) -> MediaDuration {
+    let fps = fps.max(1.0).min(60.0);
+    MediaDuration::new((1000.0 / fps) as i64, 1000)
+}
@@ -0,0 +1,89 @@
+use std::iter;
+
+use mixlab_protocol::{BusParams, LineType, Terminal, ParamSpec, ParamUnit};
+
+use crate::engine::{self, Sample, InputRef, OutputRef, CHANNELS};
+use crate::module::ModuleT;
+use crate::util;
+
+#[derive(Debug)]
+pub struct Bus {
+    params: BusParams,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Bus {
+    type Params = BusParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let bus = Bus {
+            inputs: params.inputs.iter().enumerate().map(|(i, _)| {
+                LineType::Stereo.labeled(&(i+1).to_string())
+            }).collect(),
+            outputs: vec![LineType::Stereo.labeled("Submix")],
+            params,
+        };
+
+        (bus, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        if params.inputs.len() != self.params.inputs.len() {
+            self.inputs = params.inputs.iter().enumerate().map(|(i, _)| {
+                LineType::Stereo.labeled(&(i+1).to_string())
+            }).collect();
+        }
+
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let output = outputs[0].expect_stereo();
+
+        util::zero(output);
+
+        for (idx, input_params) in self.params.inputs.iter().enumerate() {
+            let input = inputs[idx].expect_stereo();
+            let gain = input_params.gain.to_linear() * self.params.master;
+
+            // balance law: centre leaves both channels at unity, panning
+            // attenuates the opposite channel only
+            let pan = f64::max(-1.0, f64::min(1.0, input_params.pan));
+            let left = (gain * f64::min(1.0, 1.0 - pan)) as Sample;
+            let right = (gain * f64::min(1.0, 1.0 + pan)) as Sample;
+
+            for (out, frame) in output.chunks_mut(CHANNELS).zip(input.chunks(CHANNELS)) {
+                out[0] += frame[0] * left;
+                out[1] += frame[1] * right;
+            }
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        (0..self.params.inputs.len())
+            .flat_map(|idx| vec![
+                ParamSpec::new(format!("/inputs/{}/gain", idx), format!("Input {} gain", idx + 1), ParamUnit::Decibel, -24.0, 6.0),
+                ParamSpec::new(format!("/inputs/{}/pan", idx), format!("Input {} pan", idx + 1), ParamUnit::None, -1.0, 1.0),
+            ])
+            .chain(iter::once(ParamSpec::new("/master", "Master", ParamUnit::Percent, 0.0, 1.0)))
+            .collect()
+    }
+}
@@ -1,7 +1,7 @@
-use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE};
+use crate::engine::{self, InputRef, OutputRef};
 use crate::module::{ModuleT, LineType, Terminal};
 
-use mixlab_protocol::EnvelopeParams;
+use mixlab_protocol::{EnvelopeParams, ParamSpec, ParamUnit};
 
 type SampleSeq = u64;
 
@@ -13,8 +13,8 @@ enum EnvelopeState {
 }
 
 type Ms = f64;
-fn sample_seq_duration_ms(first: SampleSeq, last: SampleSeq) -> Ms {
-    (last - first) as f64 / SAMPLE_RATE as f64 * 1000.0
+fn sample_seq_duration_ms(first: SampleSeq, last: SampleSeq, sample_rate: usize) -> Ms {
+    (last - first) as f64 / sample_rate as f64 * 1000.0
 }
 
 fn clamp(x: f64) -> f64 {
@@ -31,11 +31,11 @@ fn invert(x: f64) -> f64 {
     1.0 - x
 }
 
-fn amplitude(params: &EnvelopeParams, state: &EnvelopeState, t: SampleSeq) -> f64 {
+fn amplitude(params: &EnvelopeParams, state: &EnvelopeState, t: SampleSeq, sample_rate: usize) -> f64 {
     match state {
         EnvelopeState::Initial => 0.0,
         EnvelopeState::TriggerOn {on} => {
-            let ms_since_on = sample_seq_duration_ms(*on, t);
+            let ms_since_on = sample_seq_duration_ms(*on, t, sample_rate);
 
             if ms_since_on < params.attack_ms {
                 // Currently in attack phase
@@ -49,7 +49,7 @@ fn amplitude(params: &EnvelopeParams, state: &EnvelopeState, t: SampleSeq) -> f6
             }
         }
         EnvelopeState::TriggerOff {off, off_amplitude} => {
-            let ms_since_off = sample_seq_duration_ms(*off, t);
+            let ms_since_off = sample_seq_duration_ms(*off, t, sample_rate);
             let release_amplitude = invert(clamp(1.0 / params.release_ms * ms_since_off));
 
             off_amplitude * release_amplitude
@@ -60,6 +60,7 @@ fn amplitude(params: &EnvelopeParams, state: &EnvelopeState, t: SampleSeq) -> f6
 #[derive(Debug)]
 pub struct Envelope {
     params: EnvelopeParams,
+    sample_rate: usize,
     state: EnvelopeState,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
@@ -70,9 +71,10 @@ impl ModuleT for Envelope {
     type Indication = ();
     type Event = ();
 
-    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
         (Self {
             params,
+            sample_rate: ctx.config().sample_rate,
             state: EnvelopeState::Initial,
             inputs: vec![LineType::Mono.unlabeled()],
             outputs: vec![LineType::Mono.unlabeled()],
@@ -107,13 +109,13 @@ impl ModuleT for Envelope {
                     if input[i] == 0.0 {
                         self.state = EnvelopeState::TriggerOff {
                             off: sample_seq,
-                            off_amplitude: amplitude(&self.params, &self.state, sample_seq)
+                            off_amplitude: amplitude(&self.params, &self.state, sample_seq, self.sample_rate)
                         };
                     }
                 }
             }
             // Then set output
-            output[i] = amplitude(&self.params, &self.state, sample_seq) as f32;
+            output[i] = amplitude(&self.params, &self.state, sample_seq, self.sample_rate) as f32;
         }
 
         None
@@ -126,4 +128,13 @@ impl ModuleT for Envelope {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("/attack_ms", "Attack", ParamUnit::Milliseconds, 5.0, 500.0).log(),
+            ParamSpec::new("/decay_ms", "Decay", ParamUnit::Milliseconds, 5.0, 1000.0).log(),
+            ParamSpec::new("/sustain_amplitude", "Sustain", ParamUnit::Percent, 0.0, 1.0),
+            ParamSpec::new("/release_ms", "Release", ParamUnit::Milliseconds, 5.0, 5000.0).log(),
+        ]
+    }
 }
@@ -0,0 +1,86 @@
+use mixlab_protocol::{EnvelopeFollowerParams, LineType, Terminal, ParamSpec, ParamUnit};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, CHANNELS};
+use crate::module::ModuleT;
+
+#[derive(Debug)]
+pub struct EnvelopeFollower {
+    params: EnvelopeFollowerParams,
+    sample_rate: usize,
+    level: f64,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for EnvelopeFollower {
+    type Params = EnvelopeFollowerParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        (Self {
+            params,
+            sample_rate: ctx.config().sample_rate,
+            level: 0.0,
+            inputs: vec![LineType::Stereo.labeled("Input")],
+            outputs: vec![LineType::Mono.labeled("Control")],
+        }, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+        let output = outputs[0].expect_mono();
+
+        let gain = self.params.gain.to_linear();
+        let attack = coefficient(self.params.attack_ms, self.sample_rate);
+        let release = coefficient(self.params.release_ms, self.sample_rate);
+
+        for (frame, out) in input.chunks(CHANNELS).zip(output.iter_mut()) {
+            let peak = frame.iter()
+                .map(|x| (*x as f64 * gain).abs())
+                .fold(0.0, f64::max);
+
+            // one pole smoothing, rising at the attack rate and falling at
+            // the release rate:
+            let coeff = if peak > self.level { attack } else { release };
+            self.level = peak + coeff * (self.level - peak);
+
+            *out = f64::min(1.0, self.level) as Sample;
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("/attack_ms", "Attack", ParamUnit::Milliseconds, 0.0, 500.0),
+            ParamSpec::new("/release_ms", "Release", ParamUnit::Milliseconds, 0.0, 2000.0),
+            ParamSpec::new("/gain", "Gain", ParamUnit::Decibel, -6.0, 24.0),
+        ]
+    }
+}
+
+fn coefficient(time_ms: f64, sample_rate: usize) -> f64 {
+    if time_ms <= 0.0 {
+        return 0.0;
+    }
+
+    f64::exp(-1.0 / (time_ms / 1000.0 * sample_rate as f64))
+}
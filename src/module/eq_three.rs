@@ -1,8 +1,8 @@
 use std::f64;
 
-use mixlab_protocol::EqThreeParams;
+use mixlab_protocol::{EqThreeParams, ParamSpec, ParamUnit};
 
-use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE};
+use crate::engine::{self, InputRef, OutputRef};
 use crate::module::{ModuleT, LineType, Terminal};
 
 const FREQ_LO: f64 = 420.0;
@@ -13,14 +13,7 @@ const VSA: f64 = 1.0 / 4294967295.0; // Very small amount (Denormal Fix)
 #[derive(Debug)]
 pub struct EqThree {
     params: EqThreeParams,
-
-    // filter 1 (low band)
-    lo: LowPass,
-    hi: LowPass,
-
-    // sample history
-    history: [f64; 3],
-
+    bands: ThreeBand,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
 }
@@ -30,15 +23,10 @@ impl ModuleT for EqThree {
     type Indication = ();
     type Event = ();
 
-    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
-        let lo = LowPass::new(FREQ_LO);
-        let hi = LowPass::new(FREQ_HI);
-
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
         let eq_three = Self {
             params,
-            lo,
-            hi,
-            history: [0.0; 3],
+            bands: ThreeBand::new(ctx.config().sample_rate),
             inputs: vec![LineType::Mono.unlabeled()],
             outputs: vec![LineType::Mono.unlabeled()],
         };
@@ -59,30 +47,10 @@ impl ModuleT for EqThree {
         let input = inputs[0].expect_mono();
         let output = outputs[0].expect_mono();
 
-        let gain_lo = self.params.gain_lo.to_linear();
-        let gain_mid = self.params.gain_mid.to_linear();
-        let gain_hi = self.params.gain_hi.to_linear();
+        let gains = ThreeBand::gains(&self.params);
 
         for (input, output) in input.iter().copied().zip(output.iter_mut()) {
-            let sample = input as f64;
-
-            let lo = self.lo.pump(sample);
-            let hi = self.history[0] - self.hi.pump(sample);
-
-            let mid = self.history[0] - (hi + lo);
-
-            // shift history
-            self.history[0] = self.history[1];
-            self.history[1] = self.history[2];
-            self.history[2] = sample;
-
-            // apply gain
-
-            let lo = lo * gain_lo;
-            let mid = mid * gain_mid;
-            let hi = hi * gain_hi;
-
-            *output = (lo + mid + hi) as f32;
+            *output = self.bands.process(input as f64, gains) as f32;
         }
 
         None
@@ -95,6 +63,62 @@ impl ModuleT for EqThree {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("/gain_lo", "Low", ParamUnit::Decibel, -24.0, 6.0),
+            ParamSpec::new("/gain_mid", "Mid", ParamUnit::Decibel, -24.0, 6.0),
+            ParamSpec::new("/gain_hi", "High", ParamUnit::Decibel, -24.0, 6.0),
+        ]
+    }
+}
+
+/// The filters EqThree is built from, for eq'ing a signal one sample at a
+/// time outside of a module, eg. an output zone
+#[derive(Debug)]
+pub struct ThreeBand {
+    // filter 1 (low band)
+    lo: LowPass,
+    hi: LowPass,
+
+    // sample history
+    history: [f64; 3],
+}
+
+impl ThreeBand {
+    pub fn new(sample_rate: usize) -> Self {
+        ThreeBand {
+            lo: LowPass::new(FREQ_LO, sample_rate),
+            hi: LowPass::new(FREQ_HI, sample_rate),
+            history: [0.0; 3],
+        }
+    }
+
+    /// Linear gains of the low, mid and high bands, worked out once per
+    /// block rather than per sample
+    pub fn gains(params: &EqThreeParams) -> [f64; 3] {
+        [params.gain_lo.to_linear(), params.gain_mid.to_linear(), params.gain_hi.to_linear()]
+    }
+
+    pub fn process(&mut self, sample: f64, gains: [f64; 3]) -> f64 {
+        let lo = self.lo.pump(sample);
+        let hi = self.history[0] - self.hi.pump(sample);
+
+        let mid = self.history[0] - (hi + lo);
+
+        // shift history
+        self.history[0] = self.history[1];
+        self.history[1] = self.history[2];
+        self.history[2] = sample;
+
+        // apply gain
+
+        let lo = lo * gains[0];
+        let mid = mid * gains[1];
+        let hi = hi * gains[2];
+
+        lo + mid + hi
+    }
 }
 
 #[derive(Debug)]
@@ -104,14 +128,14 @@ struct LowPass {
 }
 
 impl LowPass {
-    pub fn new(freq: f64) -> Self {
+    pub fn new(freq: f64, sample_rate: usize) -> Self {
         let mut filter = LowPass { freq: 0.0, poles: [0.0, 0.0, 0.0, 0.0] };
-        filter.set_freq(freq);
+        filter.set_freq(freq, sample_rate);
         filter
     }
 
-    pub fn set_freq(&mut self, freq: f64) {
-        self.freq = 2.0 * f64::sin(f64::consts::PI * freq / (SAMPLE_RATE as f64));
+    pub fn set_freq(&mut self, freq: f64, sample_rate: usize) {
+        self.freq = 2.0 * f64::sin(f64::consts::PI * freq / (sample_rate as f64));
     }
 
     pub fn pump(&mut self, sample: f64) -> f64 {
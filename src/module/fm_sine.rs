@@ -1,13 +1,14 @@
 use std::f64;
 
-use mixlab_protocol::{FmSineParams, LineType, Terminal};
+use mixlab_protocol::{FmSineParams, LineType, Terminal, ParamSpec, ParamUnit};
 
-use crate::engine::{self, Sample, InputRef, OutputRef, SAMPLE_RATE, CHANNELS};
+use crate::engine::{self, Sample, InputRef, OutputRef, CHANNELS};
 use crate::module::ModuleT;
 
 #[derive(Debug)]
 pub struct FmSine {
     params: FmSineParams,
+    sample_rate: usize,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
 }
@@ -17,9 +18,10 @@ impl ModuleT for FmSine {
     type Indication = ();
     type Event = ();
 
-    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
         (Self {
             params,
+            sample_rate: ctx.config().sample_rate,
             inputs: vec![LineType::Mono.unlabeled()],
             outputs: vec![LineType::Stereo.unlabeled()],
         }, ())
@@ -43,7 +45,7 @@ impl ModuleT for FmSine {
         let freq_mid = self.params.freq_lo + freq_amp;
 
         for i in 0..len {
-            let t = (t + i as u64) as f64 / SAMPLE_RATE as f64;
+            let t = (t + i as u64) as f64 / self.sample_rate as f64;
             let co = (freq_mid + freq_amp * input[i] as f64) * 2.0 * f64::consts::PI;
             let x = f64::sin(co * t);
 
@@ -62,4 +64,11 @@ impl ModuleT for FmSine {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("/freq_lo", "Freq lo", ParamUnit::Hertz, 20.0, 20000.0).log(),
+            ParamSpec::new("/freq_hi", "Freq hi", ParamUnit::Hertz, 20.0, 20000.0).log(),
+        ]
+    }
 }
@@ -0,0 +1,242 @@
+use std::time::{Duration, Instant};
+
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Request};
+use serde_json::{json, Value};
+
+use mixlab_protocol::{HueLightParams, HueLightIndication, LineType, Terminal, ParamSpec, ParamUnit};
+
+use crate::engine::{self, InputRef, OutputRef};
+use crate::module::ModuleT;
+use crate::util;
+
+// the bridge handles about ten light commands a second before it starts
+// dropping them, and each state change fades over the same interval so
+// that stepped updates still look smooth:
+const MIN_SEND_INTERVAL: Duration = Duration::from_millis(100);
+const TRANSITION_TIME: u16 = 1; // in multiples of 100ms
+
+// back off for a while after a failed request rather than hammering a
+// bridge that is unreachable or is rejecting us:
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+// give up waiting on a request after this long and allow another:
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub struct HueLight {
+    ctx: engine::ModuleCtx<Self>,
+    params: HueLightParams,
+    client: Client<HttpConnector>,
+    last_state: Option<LightState>,
+    next_send: Instant,
+    in_flight: Option<Instant>,
+    last_error: Option<Instant>,
+    indication: HueLightIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+#[derive(Debug)]
+pub enum HueLightEvent {
+    Sent(Result<(), ()>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LightState {
+    on: bool,
+    bri: u8,
+    hue: u16,
+    sat: u8,
+}
+
+impl ModuleT for HueLight {
+    type Params = HueLightParams;
+    type Indication = HueLightIndication;
+    type Event = HueLightEvent;
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let indication = HueLightIndication { error: None };
+
+        let module = HueLight {
+            ctx,
+            params,
+            client: Client::new(),
+            last_state: None,
+            next_send: Instant::now(),
+            in_flight: None,
+            last_error: None,
+            indication: indication.clone(),
+            inputs: vec![
+                LineType::Mono.labeled("Brightness"),
+                LineType::Mono.labeled("Hue"),
+                LineType::Mono.labeled("Saturation"),
+            ],
+            outputs: vec![],
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        if params.bridge != self.params.bridge
+            || params.username != self.params.username
+            || params.light != self.params.light
+        {
+            // a different light knows nothing of the state we last sent:
+            self.last_state = None;
+            self.next_send = Instant::now();
+        }
+
+        self.params = params;
+        None
+    }
+
+    fn receive_event(&mut self, event: HueLightEvent) {
+        match event {
+            HueLightEvent::Sent(result) => {
+                self.in_flight = None;
+
+                if result.is_err() {
+                    let now = Instant::now();
+                    self.last_error = Some(now);
+                    // resend once the bridge has had some time to recover:
+                    self.last_state = None;
+                    self.next_send = Instant::max(self.next_send, now + RETRY_INTERVAL);
+                }
+            }
+        }
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], _: &mut [OutputRef]) -> Option<Self::Indication> {
+        let now = Instant::now();
+
+        let state = self.light_state(inputs);
+
+        let idle = match self.in_flight {
+            None => true,
+            Some(sent) => now - sent >= REQUEST_TIMEOUT,
+        };
+
+        if idle && now >= self.next_send && !self.params.bridge.is_empty() && self.last_state != Some(state) {
+            self.send(state);
+            self.in_flight = Some(now);
+            self.next_send = now + MIN_SEND_INTERVAL;
+            self.last_state = Some(state);
+        }
+
+        let indication = HueLightIndication {
+            error: util::temporal_warning(self.last_error.map(|time| now - time)),
+        };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("/brightness", "Brightness", ParamUnit::Percent, 0.0, 1.0),
+            ParamSpec::new("/hue", "Hue", ParamUnit::Percent, 0.0, 1.0),
+            ParamSpec::new("/saturation", "Saturation", ParamUnit::Percent, 0.0, 1.0),
+        ]
+    }
+}
+
+impl HueLight {
+    // connected control inputs take precedence over params:
+    fn light_state(&self, inputs: &[InputRef]) -> LightState {
+        let level = |input: &InputRef, param: f64| {
+            let value = if input.connected() {
+                input.expect_mono().last().map(|sample| *sample as f64).unwrap_or(0.0)
+            } else {
+                param
+            };
+
+            f64::max(0.0, f64::min(1.0, value))
+        };
+
+        let brightness = level(&inputs[0], self.params.brightness);
+        let hue = level(&inputs[1], self.params.hue);
+        let saturation = level(&inputs[2], self.params.saturation);
+
+        LightState {
+            on: brightness > 0.0,
+            // bri 0 is not dimmest but invalid, the range is 1 - 254:
+            bri: 1 + (brightness * 253.0).round() as u8,
+            hue: (hue * 65535.0).round() as u16,
+            sat: (saturation * 254.0).round() as u8,
+        }
+    }
+
+    fn send(&self, state: LightState) {
+        if self.ctx.dry_run() {
+            return;
+        }
+
+        let uri = format!("http://{}/api/{}/lights/{}/state",
+            self.params.bridge.trim(),
+            self.params.username.trim(),
+            self.params.light.trim());
+
+        let body = if state.on {
+            json!({
+                "on": true,
+                "bri": state.bri,
+                "hue": state.hue,
+                "sat": state.sat,
+                "transitiontime": TRANSITION_TIME,
+            })
+        } else {
+            json!({ "on": false, "transitiontime": TRANSITION_TIME })
+        };
+
+        let client = self.client.clone();
+
+        // failures are reported via indication, logging them would flood
+        // the log at the send rate
+        self.ctx.spawn_async(async move {
+            HueLightEvent::Sent(put_state(client, uri, body).await)
+        });
+    }
+}
+
+async fn put_state(client: Client<HttpConnector>, uri: String, body: Value) -> Result<(), ()> {
+    let req = Request::put(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .map_err(|_| ())?;
+
+    let response = client.request(req).await.map_err(|_| ())?;
+
+    if !response.status().is_success() {
+        return Err(());
+    }
+
+    let body = hyper::body::to_bytes(response.into_body()).await.map_err(|_| ())?;
+
+    // the bridge answers 200 even when it rejects a command, with a list of
+    // results each either a success or an error:
+    let results = serde_json::from_slice::<Vec<Value>>(&body).map_err(|_| ())?;
+
+    if results.iter().any(|result| result.get("error").is_some()) {
+        return Err(());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,290 @@
+use std::mem;
+use std::ops::Range;
+
+use mixlab_protocol::{IdentInserterParams, IdentInserterIndication, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, Schedule, Segment, CHANNELS};
+use crate::module::ModuleT;
+use crate::module::media_source::{self, MediaAudioReader, OpenMedia, Playback};
+use crate::util;
+
+// how long the program takes to duck fully under an ident, and to come
+// back up after:
+const DUCK_RAMP_SECONDS: f64 = 0.25;
+
+// idents on an interval are opened this far ahead of time, so that they
+// start on the sample they're due:
+const CUE_AHEAD_SECONDS: f64 = 2.0;
+
+#[derive(Debug)]
+pub struct IdentInserter {
+    ctx: engine::ModuleCtx<Self>,
+    params: IdentInserterParams,
+    // an ident is opening, to duck the program ready for it:
+    opening: bool,
+    // sample time an interval ident opening ahead of time is due at:
+    cued: Option<u64>,
+    cues: Schedule<IdentInserterEvent>,
+    media: Option<OpenMedia>,
+    audio: MediaAudioReader,
+    ident_buffer: Vec<Sample>,
+    // gain on the program, ramping between unity and the duck level:
+    gain: f64,
+    // samples since the last ident started, for idents on an interval:
+    since_ident: u64,
+    indication: IdentInserterIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+#[derive(Debug)]
+pub enum IdentInserterEvent {
+    SetMedia(Option<OpenMedia>),
+}
+
+impl ModuleT for IdentInserter {
+    type Params = IdentInserterParams;
+    type Indication = IdentInserterIndication;
+    type Event = IdentInserterEvent;
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let block_size = ctx.config().block_size;
+
+        let mut module = IdentInserter {
+            ctx,
+            params,
+            opening: false,
+            cued: None,
+            cues: Schedule::new(),
+            media: None,
+            audio: MediaAudioReader::default(),
+            ident_buffer: vec![0.0; block_size * CHANNELS],
+            gain: 1.0,
+            since_ident: 0,
+            indication: IdentInserterIndication { playing: false, next_secs: None },
+            inputs: vec![LineType::Stereo.labeled("Program")],
+            outputs: vec![LineType::Stereo.labeled("Program")],
+        };
+
+        module.indicate();
+
+        let indication = module.indication.clone();
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        let trigger = params.trigger != self.params.trigger;
+
+        if params.media_id.is_some() && params.media_id != self.params.media_id {
+            self.ctx.diagnostics().clear("ident");
+        }
+
+        self.params = params;
+
+        if trigger {
+            self.start();
+        }
+
+        self.indicate()
+    }
+
+    fn receive_event(&mut self, event: IdentInserterEvent) {
+        match event {
+            IdentInserterEvent::SetMedia(media) => {
+                self.opening = false;
+                self.set_media(media);
+            }
+        }
+    }
+
+    // interval idents, applied from run_tick on the sample they're due
+    fn receive_scheduled_event(&mut self, at: u64, event: IdentInserterEvent) {
+        self.cues.insert(at, event);
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let config = self.ctx.config();
+        let block_size = config.block_size;
+
+        if let Some(interval) = self.interval() {
+            let cue_ahead = (CUE_AHEAD_SECONDS * config.sample_rate as f64) as u64;
+
+            if self.since_ident + cue_ahead >= interval {
+                self.cue(t + interval.saturating_sub(self.since_ident));
+            }
+        }
+
+        self.since_ident += block_size as u64;
+
+        if let Some(media) = &mut self.media {
+            let start_of_frame = config.media_time(t);
+            let end_of_frame = start_of_frame + config.tick_duration();
+
+            // idents with video have it read only to keep decoding going:
+            let _ = media.next_frame(start_of_frame, end_of_frame);
+        }
+
+        // taken while the block is run, nothing is cued from within it:
+        let mut cues = mem::take(&mut self.cues);
+
+        cues.run_block(t, block_size, |segment| match segment {
+            Segment::Event(IdentInserterEvent::SetMedia(media)) => {
+                // the interval runs from when the ident is due, not from
+                // the start of the block it's due in:
+                self.since_ident = (t + block_size as u64).saturating_sub(self.cued.unwrap_or(t));
+                self.cued = None;
+                self.set_media(media);
+            }
+            Segment::Render(range) => self.read_ident(range),
+        });
+
+        self.cues = cues;
+
+        let duck_ahead = (DUCK_RAMP_SECONDS * config.sample_rate as f64) as u64;
+        let ducking_for_cue = self.cued.map(|at| at <= t + block_size as u64 + duck_ahead).unwrap_or(false);
+
+        let duck = if self.opening || ducking_for_cue || self.media.is_some() {
+            10f64.powf(f64::min(0.0, self.params.duck_db) / 20.0)
+        } else {
+            1.0
+        };
+
+        let step = 1.0 / (DUCK_RAMP_SECONDS * config.sample_rate as f64);
+
+        let program = inputs[0].expect_stereo();
+        let output = outputs[0].expect_stereo();
+
+        for ((out, program), ident) in output.chunks_mut(CHANNELS).zip(program.chunks(CHANNELS)).zip(self.ident_buffer.chunks(CHANNELS)) {
+            self.gain = if self.gain < duck {
+                f64::min(duck, self.gain + step)
+            } else {
+                f64::max(duck, self.gain - step)
+            };
+
+            for channel in 0..CHANNELS {
+                out[channel] = program[channel] * self.gain as Sample + ident[channel];
+            }
+        }
+
+        self.indicate()
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+impl IdentInserter {
+    fn interval(&self) -> Option<u64> {
+        if self.params.interval_minutes > 0.0 {
+            Some((self.params.interval_minutes * 60.0 * self.ctx.config().sample_rate as f64) as u64)
+        } else {
+            None
+        }
+    }
+
+    fn start(&mut self) {
+        // an ident already playing is left to finish:
+        if self.opening || self.cued.is_some() || self.media.is_some() {
+            return;
+        }
+
+        let media_id = match self.params.media_id {
+            Some(media_id) => media_id,
+            None => {
+                self.ctx.diagnostics().warning("ident", "No ident media chosen");
+                return;
+            }
+        };
+
+        self.opening = true;
+        self.since_ident = 0;
+
+        let project = self.ctx.project();
+
+        self.ctx.spawn_async(async move {
+            IdentInserterEvent::SetMedia(media_source::open_media_with_audio(project, media_id, Playback::Once).await)
+        });
+    }
+
+    // opens an interval ident ahead of time, to start at sample time `at`
+    fn cue(&mut self, at: u64) {
+        if self.opening || self.cued.is_some() || self.media.is_some() {
+            return;
+        }
+
+        let media_id = match self.params.media_id {
+            Some(media_id) => media_id,
+            None => {
+                self.ctx.diagnostics().warning("ident", "No ident media chosen");
+                // tried again once the next interval is up:
+                self.since_ident = 0;
+                return;
+            }
+        };
+
+        self.cued = Some(at);
+
+        let project = self.ctx.project();
+
+        self.ctx.spawn_scheduled(at, async move {
+            IdentInserterEvent::SetMedia(media_source::open_media_with_audio(project, media_id, Playback::Once).await)
+        });
+    }
+
+    fn set_media(&mut self, media: Option<OpenMedia>) {
+        if media.is_none() {
+            self.ctx.diagnostics().error("ident", "Ident media is missing or could not be opened");
+        }
+
+        self.media = media;
+        self.audio.clear();
+    }
+
+    // reads the playing ident, if any, into a range of the block
+    fn read_ident(&mut self, range: Range<usize>) {
+        let buffer = &mut self.ident_buffer[range.start * CHANNELS..range.end * CHANNELS];
+        let mut ident_len = 0;
+
+        if let Some(media) = &mut self.media {
+            ident_len = self.audio.read(media, self.ctx.config().sample_rate, buffer);
+
+            if media.finished() && ident_len < buffer.len() {
+                self.media = None;
+            }
+        }
+
+        util::zero(&mut buffer[ident_len..]);
+    }
+
+    fn indicate(&mut self) -> Option<IdentInserterIndication> {
+        let playing = self.opening || self.media.is_some();
+        let sample_rate = self.ctx.config().sample_rate as u64;
+
+        let next_secs = match self.interval() {
+            Some(interval) if !playing => {
+                // rounded up, so that it reads zero only as the ident starts:
+                let remaining = interval.saturating_sub(self.since_ident);
+                Some((remaining + sample_rate - 1) / sample_rate)
+            }
+            _ => None,
+        };
+
+        let indication = IdentInserterIndication { playing, next_secs };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use std::mem;
+
+use derive_more::From;
+use num_rational::Rational64;
+use tokio::task;
+
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::codec::{self, CodecBuilder, RecvFrameError};
+use mixlab_codec::ffmpeg::{AvError, AvFrame, AvIoError, AvIoReader, Colorimetry, InputContainer, IoReader, PictureSettings, PixelFormat, SwsContext};
+use mixlab_protocol::{ImageSourceParams, ImageSourceIndication, MediaId, LineType, Terminal};
+use mixlab_util::time::{MediaTime, MediaDuration};
+
+use crate::engine::{self, InputRef, OutputRef, VideoFrame};
+use crate::module::ModuleT;
+use crate::project::ProjectBaseRef;
+use crate::project::media;
+use crate::project::stream::ReadStream;
+use crate::video;
+
+// animations are decoded in full up front so that they loop seamlessly,
+// which would take far too much memory for anything long:
+const MAX_FRAMES: usize = 1000;
+
+// frames of animated gifs often have no delay, which browsers show for 100ms:
+const DEFAULT_FRAME_MS: i64 = 100;
+
+#[derive(Debug)]
+pub struct ImageSource {
+    ctx: engine::ModuleCtx<Self>,
+    params: ImageSourceParams,
+    // bumped with each load, so that superseded loads can be ignored:
+    load_seq: u64,
+    loading: bool,
+    error: bool,
+    animation: Option<Playing>,
+    indication: ImageSourceIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+#[derive(Debug)]
+pub enum ImageSourceEvent {
+    Loaded(u64, Result<Animation, ()>),
+}
+
+/// Every frame of an animation, converted to yuva420p
+#[derive(Debug)]
+pub struct Animation {
+    // each frame with the offset from the start of the loop it is shown at:
+    frames: Vec<(MediaDuration, video::Frame)>,
+    length: MediaDuration,
+}
+
+#[derive(Debug)]
+struct Playing {
+    animation: Animation,
+    epoch: Option<MediaTime>,
+    // loop count and frame index last shown:
+    shown: Option<(i64, usize)>,
+}
+
+impl ModuleT for ImageSource {
+    type Params = ImageSourceParams;
+    type Indication = ImageSourceIndication;
+    type Event = ImageSourceEvent;
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let indication = ImageSourceIndication {
+            loading: false,
+            frames: 0,
+            error: false,
+        };
+
+        let mut module = ImageSource {
+            ctx,
+            params,
+            load_seq: 0,
+            loading: false,
+            error: false,
+            animation: None,
+            indication: indication.clone(),
+            inputs: vec![],
+            outputs: vec![
+                LineType::Video.unlabeled(),
+            ],
+        };
+
+        module.load();
+
+        let indication = module.indicate().unwrap_or(indication);
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        let old_params = mem::replace(&mut self.params, params);
+
+        let reload = self.params.media_id != old_params.media_id
+            || self.params.sequence != old_params.sequence
+            || (self.params.sequence && self.params.fps != old_params.fps);
+
+        if reload {
+            self.load();
+        }
+
+        self.indicate()
+    }
+
+    fn receive_event(&mut self, event: ImageSourceEvent) {
+        match event {
+            ImageSourceEvent::Loaded(seq, result) => {
+                if seq != self.load_seq {
+                    return;
+                }
+
+                self.loading = false;
+
+                match result {
+                    Ok(animation) => {
+                        self.animation = Some(Playing { animation, epoch: None, shown: None });
+                    }
+                    Err(()) => {
+                        self.error = true;
+                        self.ctx.diagnostics().error("media", "Image is missing or could not be loaded");
+                    }
+                }
+            }
+        }
+    }
+
+    fn run_tick(&mut self, t: u64, _: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let now = self.ctx.config().media_time(t);
+
+        *outputs[0].expect_video() = self.animation.as_mut()
+            .and_then(|playing| playing.next_frame(now));
+
+        self.indicate()
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+impl ImageSource {
+    fn load(&mut self) {
+        self.load_seq += 1;
+        self.animation = None;
+        self.error = false;
+        self.ctx.diagnostics().clear("media");
+
+        let media_id = match self.params.media_id {
+            Some(media_id) => media_id,
+            None => {
+                self.loading = false;
+                return;
+            }
+        };
+
+        self.loading = true;
+
+        let seq = self.load_seq;
+        let base = self.ctx.project();
+        let params = self.params.clone();
+        let color = self.ctx.config().colorimetry();
+
+        self.ctx.spawn_async(async move {
+            let result = load_animation(base, media_id, params, color).await
+                .map_err(|e| eprintln!("image_source: could not load {:?}: {:?}", media_id, e));
+
+            ImageSourceEvent::Loaded(seq, result)
+        });
+    }
+
+    fn indicate(&mut self) -> Option<ImageSourceIndication> {
+        let indication = ImageSourceIndication {
+            loading: self.loading,
+            frames: self.animation.as_ref().map(|playing| playing.animation.frames.len()).unwrap_or(0),
+            error: self.error,
+        };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+}
+
+impl Playing {
+    // returns the frame showing at `now` if it wasn't showing last tick
+    fn next_frame(&mut self, now: MediaTime) -> Option<VideoFrame> {
+        let epoch = *self.epoch.get_or_insert(now);
+        let elapsed = (now - epoch).as_rational();
+        let length = self.animation.length.as_rational();
+
+        let loop_count = (elapsed / length).to_integer();
+        let position = elapsed - length * loop_count;
+
+        let index = self.animation.frames.iter()
+            .rposition(|(start, _)| start.as_rational() <= position)
+            .unwrap_or(0);
+
+        if self.shown == Some((loop_count, index)) {
+            return None;
+        }
+
+        self.shown = Some((loop_count, index));
+
+        let (start, frame) = &self.animation.frames[index];
+
+        // the frame began before this tick, so is only shown for what remains
+        // of it:
+        let shown_for = frame.duration_hint.as_rational() - (position - start.as_rational());
+
+        Some(VideoFrame {
+            data: video::Frame {
+                decoded: frame.decoded.clone(),
+                duration_hint: MediaDuration::from(shown_for),
+            },
+            tick_offset: MediaDuration::zero(),
+        })
+    }
+}
+
+#[derive(Debug, From)]
+enum LoadError {
+    Database(rusqlite::Error),
+    NotFound,
+    CodecBuild(codec::BuildError),
+    CodecOpen(codec::OpenError),
+    NoFrames,
+    TooManyFrames,
+    RecvFrame(RecvFrameError),
+    Av(AvError),
+    Io(<ReadStream as IoReader>::Error),
+}
+
+impl From<AvIoError<ReadStream>> for LoadError {
+    fn from(e: AvIoError<ReadStream>) -> LoadError {
+        match e {
+            AvIoError::Av(e) => LoadError::Av(e),
+            AvIoError::Io(e) => LoadError::Io(e),
+        }
+    }
+}
+
+async fn load_animation(base: ProjectBaseRef, media_id: MediaId, params: ImageSourceParams, color: Colorimetry) -> Result<Animation, LoadError> {
+    let media_ids = if params.sequence {
+        sequence(&base, media_id).await?
+    } else {
+        vec![media_id]
+    };
+
+    let mut streams = Vec::new();
+
+    for media_id in media_ids {
+        streams.push(media::open(base.clone(), media_id).await?.ok_or(LoadError::NotFound)?);
+    }
+
+    let still_duration = if params.sequence && params.fps > 0.0 {
+        Some(MediaDuration::new((1000.0 / params.fps) as i64, 1000))
+    } else {
+        None
+    };
+
+    task::spawn_blocking(move || decode_animation(streams, still_duration, color))
+        .await
+        .expect("decode_animation")
+}
+
+// finds every item in the library numbered like the one given, in order
+async fn sequence(base: &ProjectBaseRef, media_id: MediaId) -> Result<Vec<MediaId>, LoadError> {
+    let library = media::library(base).await?;
+
+    let name = library.items.iter()
+        .find(|item| item.id == media_id)
+        .map(|item| item.name.clone())
+        .ok_or(LoadError::NotFound)?;
+
+    let (prefix, _, suffix) = match split_number(&name) {
+        Some(parts) => parts,
+        // not numbered, so a sequence of one:
+        None => return Ok(vec![media_id]),
+    };
+
+    let mut numbered = library.items.iter()
+        .filter_map(|item| match split_number(&item.name) {
+            Some((p, number, s)) if p == prefix && s == suffix => Some((number, item.id)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    numbered.sort_by_key(|(number, _)| *number);
+
+    Ok(numbered.into_iter().map(|(_, id)| id).collect())
+}
+
+// splits a name like "title_0012.png" about the last run of digits in it
+fn split_number(name: &str) -> Option<(&str, u64, &str)> {
+    let end = name.rfind(|c: char| c.is_ascii_digit())? + 1;
+
+    let start = name[..end].char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_ascii_digit())
+        .last()
+        .map(|(idx, _)| idx)?;
+
+    let number = name[start..end].parse().ok()?;
+
+    Some((&name[..start], number, &name[end..]))
+}
+
+// decodes every frame of each stream in turn. with a still duration, only
+// the first frame of each stream is used and shown for that long
+fn decode_animation(streams: Vec<ReadStream>, still_duration: Option<MediaDuration>, color: Colorimetry) -> Result<Animation, LoadError> {
+    let mut frames = Vec::new();
+    let mut length = MediaDuration::zero();
+    let mut convert: Option<(PictureSettings, SwsContext)> = None;
+
+    for stream in streams {
+        let decoded = decode_frames(stream, still_duration.is_some())?;
+
+        for (decoded, duration) in decoded {
+            let duration = match still_duration {
+                Some(duration) => duration,
+                None if duration.is_zero() => MediaDuration::new(DEFAULT_FRAME_MS, 1000),
+                None => duration,
+            };
+
+            let input = decoded.picture_settings();
+
+            // every frame is converted to the size of the first:
+            let output = match &convert {
+                Some((_, sws)) => sws.output_settings().clone(),
+                None => PictureSettings {
+                    width: input.width & !1,
+                    height: input.height & !1,
+                    pixel_format: PixelFormat::yuva420p(),
+                    color,
+                },
+            };
+
+            if convert.as_ref().map(|(settings, _)| settings) != Some(&input) {
+                convert = Some((input.clone(), SwsContext::new(input, output.clone())));
+            }
+
+            let (_, sws) = convert.as_mut().unwrap();
+            let mut converted = AvFrame::blank(&output);
+            sws.process(&decoded.frame_data(), &mut converted.frame_data_mut());
+
+            frames.push((length, video::Frame { decoded: converted, duration_hint: duration }));
+            length = length + duration;
+
+            if frames.len() > MAX_FRAMES {
+                return Err(LoadError::TooManyFrames);
+            }
+        }
+    }
+
+    if frames.is_empty() || length.as_rational() <= Rational64::from(0) {
+        return Err(LoadError::NoFrames);
+    }
+
+    Ok(Animation { frames, length })
+}
+
+fn decode_frames(stream: ReadStream, first_only: bool) -> Result<Vec<(AvFrame<Video>, MediaDuration)>, LoadError> {
+    let mut container = InputContainer::open(AvIoReader::new(stream))?;
+
+    let video_stream = container.streams().first().ok_or(LoadError::NoFrames)?;
+    let time_base = video_stream.time_base();
+    let codec_params = video_stream.codec_parameters();
+
+    let mut decode = CodecBuilder::<Video>::new(codec_params.codec_id, time_base)?
+        .with_parameters(codec_params)
+        .open_decoder()?;
+
+    let mut frames = Vec::new();
+    let mut reached_end_of_stream = false;
+
+    loop {
+        if !reached_end_of_stream {
+            match container.read_packet()? {
+                Some(pkt) => {
+                    if pkt.stream_index() != 0 {
+                        continue;
+                    }
+
+                    decode.send_packet(&pkt)?;
+                }
+                None => {
+                    decode.end_of_stream()?;
+                    reached_end_of_stream = true;
+                }
+            }
+        }
+
+        match decode.recv_frame() {
+            Ok(frame) => {
+                let duration = time_base.scale_duration(frame.packet_duration());
+                frames.push((frame, duration));
+
+                if first_only {
+                    break;
+                }
+
+                if frames.len() > MAX_FRAMES {
+                    return Err(LoadError::TooManyFrames);
+                }
+            }
+            Err(RecvFrameError::NeedMoreInput) => { continue; }
+            Err(RecvFrameError::Eof) => { break; }
+            Err(e) => { return Err(e.into()); }
+        }
+    }
+
+    Ok(frames)
+}
@@ -0,0 +1,531 @@
+use std::fmt::{self, Debug};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::time::Instant;
+
+use cpal::traits::{HostTrait, DeviceTrait, StreamTrait};
+use ringbuf::{RingBuffer, Producer, Consumer};
+
+use mixlab_protocol::{InputDeviceParams, InputDeviceIndication, LineType, Terminal, ParamSpec, ParamUnit};
+
+use crate::engine::{self, InputRef, OutputRef, ClockRef, SampleClock, DeviceLinks, ResolvedDevice, Diagnostics, CHANNELS};
+use crate::module::ModuleT;
+use crate::module::media_source::{OpenMedia, StandIn};
+use crate::module::output_device;
+use crate::util;
+
+// the monitor path moves audio in whatever blocks the devices call back
+// with, usually far smaller than an engine tick. it keeps this much in hand
+// beyond what the output asks for, against jitter between the two devices:
+const MONITOR_MARGIN_FRAMES: usize = 32;
+
+// the monitor path measures how much it's holding over this many callbacks,
+// and drops whatever was never needed, so that drift between the two
+// devices can't build up into a delay:
+const MONITOR_TRIM_CALLBACKS: usize = 100;
+
+// the engine path holds at most this many ticks in hand, for the same reason:
+const ENGINE_MAX_TICKS: usize = 4;
+
+const NO_CHANNEL: usize = usize::MAX;
+
+pub struct InputDevice {
+    ctx: engine::ModuleCtx<Self>,
+    params: InputDeviceParams,
+    sample_rate: usize,
+    block_size: usize,
+    host: cpal::Host,
+    devices: DeviceLinks,
+    resolved: ResolvedDevice,
+    monitor_resolved: ResolvedDevice,
+    // the local devices last opened (or tried) for the ones the params name:
+    opened: Option<String>,
+    monitor_opened: Option<String>,
+    diagnostics: Diagnostics,
+    stream: Option<InputStream>,
+    monitor: Option<cpal::Stream>,
+    // handed to the input callback, which feeds the monitor path directly
+    // while one is open:
+    monitor_tx: Arc<Mutex<Option<Producer<f32>>>>,
+    routing: Arc<Routing>,
+    primed: bool,
+    last_clip: Option<Instant>,
+    last_lag: Option<Instant>,
+    lag_flag: Arc<AtomicBool>,
+    // set when the indication changes outside of a tick:
+    indicate: bool,
+    // advanced as the device records, whether or not we kept up with it:
+    clock: ClockRef,
+    stand_in: StandIn,
+    indication: InputDeviceIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+#[derive(Debug)]
+pub enum InputDeviceEvent {
+    SetStandIn(Option<OpenMedia>),
+}
+
+struct InputStream {
+    rx: Consumer<f32>,
+    channels: usize,
+    // this field is never used directly but must not be dropped for the
+    // stream to continue recording:
+    _stream: cpal::Stream,
+}
+
+// shared with the device callbacks, so that changing channels or monitor
+// level doesn't mean reopening either stream
+struct Routing {
+    left: AtomicUsize,
+    right: AtomicUsize,
+    // linear gain as f32 bits:
+    monitor_gain: AtomicU32,
+}
+
+impl Debug for InputDevice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "InputDevice {{ params: {:?}, .. }}", self.params)
+    }
+}
+
+impl ModuleT for InputDevice {
+    type Params = InputDeviceParams;
+    type Indication = InputDeviceIndication;
+    type Event = InputDeviceEvent;
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let host = cpal::default_host();
+
+        let devices = Some(host.input_devices()
+            .map(|devices| devices
+                .flat_map(|device| -> Option<_> {
+                    let name = device.name().ok()?;
+                    let config = device.default_input_config().ok()?;
+                    Some((name, config.channels() as usize))
+                })
+                .collect())
+            .unwrap_or(Vec::new()));
+
+        let monitor_devices = Some(host.output_devices()
+            .map(|devices| devices
+                .flat_map(|device| device.name().ok())
+                .collect())
+            .unwrap_or(Vec::new()));
+
+        let default_device = host.default_input_device()
+            .and_then(|dev| dev.name().ok());
+
+        let indication = InputDeviceIndication {
+            clip: None,
+            lag: None,
+            default_device,
+            devices,
+            monitor_devices,
+            resolved: None,
+            monitor_resolved: None,
+        };
+
+        let mut device = InputDevice {
+            params: InputDeviceParams::default(),
+            sample_rate: ctx.config().sample_rate,
+            block_size: ctx.config().block_size,
+            host,
+            devices: ctx.devices(),
+            resolved: ResolvedDevice::default(),
+            monitor_resolved: ResolvedDevice::default(),
+            opened: None,
+            monitor_opened: None,
+            diagnostics: ctx.diagnostics(),
+            stream: None,
+            monitor: None,
+            monitor_tx: Arc::new(Mutex::new(None)),
+            routing: Arc::new(Routing {
+                left: AtomicUsize::new(NO_CHANNEL),
+                right: AtomicUsize::new(NO_CHANNEL),
+                monitor_gain: AtomicU32::new(1.0f32.to_bits()),
+            }),
+            primed: false,
+            last_clip: None,
+            last_lag: None,
+            lag_flag: Arc::new(AtomicBool::new(false)),
+            indicate: false,
+            clock: SampleClock::new(),
+            stand_in: StandIn::audio_only(),
+            inputs: vec![],
+            outputs: vec![LineType::Stereo.unlabeled()],
+            indication: indication.clone(),
+            ctx,
+        };
+
+        device.update(params);
+
+        let indication = device.indication.clone();
+        (device, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        let InputDeviceParams { device, left, right, monitor, monitor_gain, stand_in } = new_params;
+
+        // projects keep the device names they were set up with, which are
+        // mapped onto devices on this machine if it has none by that name:
+        let host = &self.host;
+
+        let resolved = self.devices.resolve_cached(&mut self.resolved, device.as_deref(), || {
+            Some(host.input_devices().ok()?
+                .filter_map(|dev| dev.name().ok())
+                .collect())
+        });
+
+        let monitor_resolved = self.devices.resolve_cached(&mut self.monitor_resolved, monitor.as_deref(), || {
+            Some(host.output_devices().ok()?
+                .filter_map(|dev| dev.name().ok())
+                .collect())
+        });
+
+        self.params.device = device;
+        self.params.monitor = monitor;
+
+        let dry_run = self.ctx.dry_run();
+
+        if self.opened != resolved {
+            // close the old stream before opening another on the same device:
+            self.stream = None;
+            self.primed = false;
+            self.stream = resolved.as_ref().filter(|_| !dry_run).and_then(|name| self.open_input(name));
+            self.opened = resolved.clone();
+            self.diagnostics.clear("stream");
+        }
+
+        if self.monitor_opened != monitor_resolved {
+            *self.monitor_tx.lock().unwrap() = None;
+            self.monitor = None;
+            self.monitor = monitor_resolved.as_ref().filter(|_| !dry_run).and_then(|name| self.open_monitor(name));
+            self.monitor_opened = monitor_resolved.clone();
+            self.diagnostics.clear("monitor_stream");
+        }
+
+        output_device::diagnose_device(&self.diagnostics, "device", self.params.device.as_deref(), resolved.as_deref(), self.stream.is_some() || dry_run);
+        output_device::diagnose_device(&self.diagnostics, "monitor", self.params.monitor.as_deref(), monitor_resolved.as_deref(), self.monitor.is_some() || dry_run);
+
+        let resolved = self.stream.as_ref().and(resolved);
+        let monitor_resolved = self.monitor.as_ref().and(monitor_resolved);
+
+        if self.indication.resolved != resolved || self.indication.monitor_resolved != monitor_resolved {
+            self.indication.resolved = resolved;
+            self.indication.monitor_resolved = monitor_resolved;
+            self.indicate = true;
+        }
+
+        // validate channel assignments against the device. they're kept as
+        // they are while the device is missing, so that they're still right
+        // when the project goes back to a machine which has it:
+        match &self.stream {
+            Some(stream) => {
+                self.params.left = left.filter(|left| *left < stream.channels);
+                self.params.right = right.filter(|right| *right < stream.channels);
+            }
+            None => {
+                self.params.left = left;
+                self.params.right = right;
+            }
+        }
+
+        self.params.monitor_gain = monitor_gain;
+        self.params.stand_in = stand_in;
+
+        self.routing.left.store(self.params.left.unwrap_or(NO_CHANNEL), Ordering::Relaxed);
+        self.routing.right.store(self.params.right.unwrap_or(NO_CHANNEL), Ordering::Relaxed);
+
+        let gain = monitor_gain.to_linear() as f32;
+        self.routing.monitor_gain.store(gain.to_bits(), Ordering::Relaxed);
+
+        None
+    }
+
+    fn receive_event(&mut self, event: InputDeviceEvent) {
+        match event {
+            InputDeviceEvent::SetStandIn(media) => {
+                self.stand_in.set_media(&self.ctx, media);
+            }
+        }
+    }
+
+    fn run_tick(&mut self, _t: u64, _: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let output = outputs[0].expect_stereo();
+        let wanted = self.block_size * CHANNELS;
+
+        let mut clip = false;
+
+        self.stand_in.sync(&self.ctx, self.params.stand_in, InputDeviceEvent::SetStandIn);
+
+        if self.stand_in.active() {
+            self.stand_in.read_audio(self.sample_rate, output);
+
+            // the device keeps recording, and the monitor path keeps playing
+            // it, but what the engine would have read is dropped. priming
+            // starts over once rehearsal ends:
+            if let Some(stream) = &mut self.stream {
+                for _ in 0..stream.rx.len() {
+                    stream.rx.pop();
+                }
+            }
+
+            self.primed = false;
+            clip = output.iter().any(|sample| *sample < -1.0 || *sample > 1.0);
+        } else {
+            match &mut self.stream {
+                Some(stream) => {
+                    // drop whatever has piled up beyond a few ticks, rather than
+                    // letting the engine path fall further and further behind:
+                    for _ in wanted * ENGINE_MAX_TICKS..stream.rx.len() {
+                        stream.rx.pop();
+                    }
+
+                    // wait for a full tick before starting, then treat any
+                    // shortfall as the engine outrunning the device:
+                    if !self.primed && stream.rx.len() >= wanted {
+                        self.primed = true;
+                    }
+
+                    let read = if self.primed { stream.rx.pop_slice(output) } else { 0 };
+
+                    if self.primed && read < wanted {
+                        self.lag_flag.store(true, Ordering::Relaxed);
+                        self.primed = false;
+                    }
+
+                    util::zero(&mut output[read..]);
+
+                    clip = output.iter().any(|sample| *sample < -1.0 || *sample > 1.0);
+                }
+                None => {
+                    util::zero(output);
+                }
+            }
+        }
+
+        let now = Instant::now();
+
+        if clip {
+            self.last_clip = Some(now);
+        }
+
+        if self.lag_flag.swap(false, Ordering::Relaxed) {
+            self.last_lag = Some(now);
+        }
+
+        let mut indication_changed = std::mem::take(&mut self.indicate);
+
+        let new_clip_status = util::temporal_warning(
+            self.last_clip.map(|time| now - time));
+
+        if self.indication.clip != new_clip_status {
+            self.indication.clip = new_clip_status;
+            indication_changed = true;
+        }
+
+        let new_lag_status = util::temporal_warning(
+            self.last_lag.map(|time| now - time));
+
+        if self.indication.lag != new_lag_status {
+            self.indication.lag = new_lag_status;
+            indication_changed = true;
+        }
+
+        if indication_changed {
+            Some(self.indication.clone())
+        } else {
+            None
+        }
+    }
+
+    fn clock(&self) -> Option<ClockRef> {
+        Some(self.clock.clone())
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("/monitor_gain", "Monitor level", ParamUnit::Decibel, -40.0, 12.0),
+        ]
+    }
+}
+
+impl InputDevice {
+    fn open_input(&self, name: &str) -> Option<InputStream> {
+        let device = self.host.input_devices().ok()?
+            .find(|dev| dev.name().map(|dev| dev == name).unwrap_or(false))?;
+
+        // we don't resample on input, so only accept devices which can run
+        // at the engine sample rate
+        let config = supported_input_config(&device, self.sample_rate)?;
+        let channels = config.channels() as usize;
+
+        let (tx, rx) = RingBuffer::<f32>::new(self.block_size * CHANNELS * ENGINE_MAX_TICKS * 2).split();
+
+        let callback = {
+            let mut tx = tx;
+            let monitor_tx = self.monitor_tx.clone();
+            let routing = self.routing.clone();
+            let clock = self.clock.clone();
+            let sample_rate = self.sample_rate;
+            let mut stereo = Vec::<f32>::new();
+
+            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                let frames = data.len() / channels;
+
+                clock.advance(frames, sample_rate);
+
+                let left = routing.left.load(Ordering::Relaxed);
+                let right = routing.right.load(Ordering::Relaxed);
+
+                stereo.clear();
+
+                for frame in data.chunks(channels) {
+                    stereo.push(frame.get(left).copied().unwrap_or(0.0));
+                    stereo.push(frame.get(right).copied().unwrap_or(0.0));
+                }
+
+                // the engine takes whole ticks and drops any excess itself:
+                tx.push_slice(&stereo);
+
+                // the monitor is fed straight from here, never waiting on
+                // an engine tick. skip a block rather than block the device
+                // thread while the monitor is being swapped:
+                if let Ok(mut monitor_tx) = monitor_tx.try_lock() {
+                    if let Some(monitor_tx) = monitor_tx.as_mut() {
+                        monitor_tx.push_slice(&stereo);
+                    }
+                }
+            }
+        };
+
+        let diagnostics = self.diagnostics.clone();
+
+        let stream = device.build_input_stream(&config.config(), callback, move |err| {
+                eprintln!("input stream error! {:?}", err);
+                diagnostics.error("stream", format!("Device error: {}", err));
+            })
+            .map_err(|e| eprintln!("input_device: could not open {}: {:?}", name, e))
+            .ok()?;
+
+        stream.play().expect("stream.play");
+
+        Some(InputStream { rx, channels, _stream: stream })
+    }
+
+    fn open_monitor(&self, name: &str) -> Option<cpal::Stream> {
+        let device = self.host.output_devices().ok()?
+            .find(|dev| dev.name().map(|dev| dev == name).unwrap_or(false))?;
+
+        let config = output_device::supported_config(&device, self.sample_rate)?;
+        let channels = config.channels() as usize;
+
+        // a tenth of a second, far more than the path ever holds:
+        let (tx, rx) = RingBuffer::<f32>::new(self.sample_rate / 10 * CHANNELS).split();
+
+        let callback = {
+            let mut rx = rx;
+            let routing = self.routing.clone();
+            let lag_flag = self.lag_flag.clone();
+            let mut primed = false;
+            let mut min_slack = usize::MAX;
+            let mut callbacks = 0;
+
+            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                let gain = f32::from_bits(routing.monitor_gain.load(Ordering::Relaxed));
+                let wanted = data.len() / channels * CHANNELS;
+
+                util::zero(data);
+
+                // hold off until there's enough to fill a block, then play
+                // through:
+                if !primed && rx.len() >= wanted {
+                    primed = true;
+                    min_slack = usize::MAX;
+                    callbacks = 0;
+                }
+
+                if !primed {
+                    return;
+                }
+
+                min_slack = min_slack.min(rx.len().saturating_sub(wanted));
+                callbacks += 1;
+
+                if callbacks == MONITOR_TRIM_CALLBACKS {
+                    // whole frames only, so left and right stay in step:
+                    let excess = min_slack.saturating_sub(MONITOR_MARGIN_FRAMES * CHANNELS) / CHANNELS * CHANNELS;
+
+                    for _ in 0..excess {
+                        rx.pop();
+                    }
+
+                    min_slack = usize::MAX;
+                    callbacks = 0;
+                }
+
+                for frame in data.chunks_mut(channels) {
+                    let (left, right) = match (rx.pop(), rx.pop()) {
+                        (Some(left), Some(right)) => (left, right),
+                        _ => {
+                            lag_flag.store(true, Ordering::Relaxed);
+                            primed = false;
+                            return;
+                        }
+                    };
+
+                    frame[0] = left * gain;
+
+                    if let Some(sample) = frame.get_mut(1) {
+                        *sample = right * gain;
+                    }
+                }
+            }
+        };
+
+        let diagnostics = self.diagnostics.clone();
+
+        let stream = device.build_output_stream(&config.config(), callback, move |err| {
+                eprintln!("monitor stream error! {:?}", err);
+                diagnostics.error("monitor_stream", format!("Monitor device error: {}", err));
+            })
+            .map_err(|e| eprintln!("input_device: could not open monitor {}: {:?}", name, e))
+            .ok()?;
+
+        stream.play().expect("stream.play");
+
+        *self.monitor_tx.lock().unwrap() = Some(tx);
+
+        Some(stream)
+    }
+}
+
+fn supported_input_config(device: &cpal::Device, sample_rate: usize) -> Option<cpal::SupportedStreamConfig> {
+    let sample_rate = cpal::SampleRate(sample_rate as u32);
+
+    let config = device.supported_input_configs().ok()?
+        .filter(|range| range.sample_format() == cpal::SampleFormat::F32)
+        .find(|range| range.min_sample_rate() <= sample_rate && sample_rate <= range.max_sample_rate())
+        .map(|range| range.with_sample_rate(sample_rate));
+
+    if config.is_none() {
+        eprintln!("input_device: {} does not support engine sample rate of {} Hz",
+            device.name().unwrap_or_default(), sample_rate.0);
+    }
+
+    config
+}
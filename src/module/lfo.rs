@@ -0,0 +1,115 @@
+use std::f64;
+
+use mixlab_protocol::{LfoParams, LfoWaveform, LineType, Terminal, ParamSpec, ParamUnit};
+
+use crate::engine::{self, InputRef, OutputRef, Sample};
+use crate::module::ModuleT;
+
+// control outputs are unipolar, centred on this level:
+const CENTRE: f64 = 0.5;
+
+#[derive(Debug)]
+pub struct Lfo {
+    params: LfoParams,
+    sample_rate: usize,
+    // position within the current cycle, 0.0 - 1.0. phase is accumulated
+    // rather than derived from t so that changing rate does not jump
+    phase: f64,
+    random: XorShift,
+    random_level: f64,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Lfo {
+    type Params = LfoParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let mut random = XorShift::new();
+        let random_level = random.next_bipolar();
+
+        (Self {
+            params,
+            sample_rate: ctx.config().sample_rate,
+            phase: 0.0,
+            random,
+            random_level,
+            inputs: vec![],
+            outputs: vec![LineType::Mono.labeled("Control")],
+        }, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, _: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let output = outputs[0].expect_mono();
+
+        let phase_step = f64::max(0.0, self.params.rate_hz) / self.sample_rate as f64;
+        let depth = f64::max(0.0, f64::min(1.0, self.params.depth));
+
+        for out in output.iter_mut() {
+            let value = match self.params.waveform {
+                LfoWaveform::Sine => f64::sin(self.phase * 2.0 * f64::consts::PI),
+                LfoWaveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+                LfoWaveform::Square => if self.phase < 0.5 { 1.0 } else { -1.0 },
+                LfoWaveform::Random => self.random_level,
+            };
+
+            *out = (CENTRE + value * depth * CENTRE) as Sample;
+
+            self.phase += phase_step;
+
+            if self.phase >= 1.0 {
+                self.phase = self.phase.fract();
+                self.random_level = self.random.next_bipolar();
+            }
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("/rate_hz", "Rate", ParamUnit::Hertz, 0.01, 20.0).log().precision(2),
+            ParamSpec::new("/depth", "Depth", ParamUnit::Percent, 0.0, 1.0),
+        ]
+    }
+}
+
+// tiny prng for the random waveform, quality is irrelevant here
+#[derive(Debug)]
+struct XorShift(u64);
+
+impl XorShift {
+    fn new() -> Self {
+        let seed = uuid::Uuid::new_v4().as_u128() as u64;
+        XorShift(seed | 1)
+    }
+
+    fn next_bipolar(&mut self) -> f64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+
+        (x >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::sync::mpsc::TrySendError;
+
+use mixlab_protocol::{LoudnessLoggerParams, LoudnessLoggerIndication, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, CHANNELS};
+use crate::module::ModuleT;
+use crate::project::loudness::{self, Entry, LoudnessLog};
+use crate::util;
+
+// loudness is measured per ITU-R BS.1770 in blocks of 100ms, short-term
+// loudness over the last 3 seconds of them, and logged every second:
+const BLOCKS_PER_SECOND: usize = 10;
+const SHORT_TERM_BLOCKS: usize = 30;
+
+// BS.1770's absolute gate, below which a source counts as silent:
+const SILENCE_LUFS: f64 = -70.0;
+
+#[derive(Debug)]
+pub struct LoudnessLogger {
+    ctx: engine::ModuleCtx<Self>,
+    params: LoudnessLoggerParams,
+    // started with the first entry, and again when the source is renamed:
+    log: Option<LoudnessLog>,
+    filters: Vec<KWeighting>,
+    block_len: usize,
+    // mean square of the block being measured so far, summed over channels:
+    block_sum: f64,
+    block_samples: usize,
+    blocks: VecDeque<f64>,
+    blocks_since_entry: usize,
+    indication: LoudnessLoggerIndication,
+    inputs: Vec<Terminal>,
+}
+
+impl ModuleT for LoudnessLogger {
+    type Params = LoudnessLoggerParams;
+    type Indication = LoudnessLoggerIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let sample_rate = ctx.config().sample_rate;
+
+        let indication = LoudnessLoggerIndication {
+            short_term_lufs: None,
+            logging: !ctx.rehearsal().active() && !ctx.dry_run(),
+            error: false,
+        };
+
+        let module = LoudnessLogger {
+            ctx,
+            params,
+            log: None,
+            filters: (0..CHANNELS).map(|_| KWeighting::new(sample_rate)).collect(),
+            block_len: sample_rate / BLOCKS_PER_SECOND,
+            block_sum: 0.0,
+            block_samples: 0,
+            blocks: VecDeque::with_capacity(SHORT_TERM_BLOCKS),
+            blocks_since_entry: 0,
+            indication: indication.clone(),
+            inputs: vec![LineType::Stereo.labeled("Program")],
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        if loudness::source_name(&params.source) != loudness::source_name(&self.params.source) {
+            self.log = None;
+            self.ctx.diagnostics().clear("log");
+            self.indication.error = false;
+        }
+
+        self.params = params;
+        Some(self.indication.clone())
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], _: &mut [OutputRef]) -> Option<Self::Indication> {
+        let mut indication = None;
+
+        for frame in inputs[0].expect_stereo().chunks(CHANNELS) {
+            for (sample, filter) in frame.iter().zip(&mut self.filters) {
+                let weighted = filter.process(*sample as f64);
+                self.block_sum += weighted * weighted;
+            }
+
+            self.block_samples += 1;
+
+            if self.block_samples == self.block_len {
+                if let Some(entry) = self.end_block() {
+                    indication = self.log(entry);
+                }
+            }
+        }
+
+        indication
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &[]
+    }
+}
+
+impl LoudnessLogger {
+    // returns an entry to log at the end of every second
+    fn end_block(&mut self) -> Option<Entry> {
+        if self.blocks.len() == SHORT_TERM_BLOCKS {
+            self.blocks.pop_front();
+        }
+
+        self.blocks.push_back(self.block_sum / self.block_samples as f64);
+        self.block_sum = 0.0;
+        self.block_samples = 0;
+        self.blocks_since_entry += 1;
+
+        if self.blocks_since_entry < BLOCKS_PER_SECOND {
+            return None;
+        }
+
+        self.blocks_since_entry = 0;
+
+        let mean_square = self.blocks.iter().sum::<f64>() / self.blocks.len() as f64;
+        let lufs = -0.691 + 10.0 * mean_square.log10();
+
+        Some(Entry {
+            time: util::unix_time(),
+            lufs: if lufs > SILENCE_LUFS { Some(lufs) } else { None },
+        })
+    }
+
+    fn log(&mut self, entry: Entry) -> Option<LoudnessLoggerIndication> {
+        // rehearsals aren't on air, so have nothing to be compliant with:
+        let logging = !self.ctx.rehearsal().active() && !self.ctx.dry_run();
+
+        if logging {
+            let source = loudness::source_name(&self.params.source);
+            let project = self.ctx.project();
+
+            let log = self.log.get_or_insert_with(|| {
+                LoudnessLog::start(project.loudness_dir().join(source))
+            });
+
+            match log.log(entry) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    self.ctx.diagnostics().warning("log", "Loudness log is falling behind, entries were dropped");
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    self.ctx.diagnostics().error("log", "Loudness log could not be written");
+                    self.indication.error = true;
+                }
+            }
+        }
+
+        self.indication.short_term_lufs = entry.lufs;
+        self.indication.logging = logging;
+        Some(self.indication.clone())
+    }
+}
+
+// the two stage pre-filter BS.1770 weights loudness through, a high shelf
+// for the head's effect and a high pass, with coefficients worked out for
+// any sample rate as in libebur128
+#[derive(Debug)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(sample_rate: usize) -> Self {
+        let rate = sample_rate as f64;
+
+        let shelf = {
+            let f0 = 1681.974450955533;
+            let gain_db = 3.999843853973347;
+            let q = 0.7071752369554196;
+
+            let k = (PI * f0 / rate).tan();
+            let vh = 10f64.powf(gain_db / 20.0);
+            let vb = vh.powf(0.4996667741545416);
+            let a0 = 1.0 + k / q + k * k;
+
+            Biquad::new(
+                [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+                [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            )
+        };
+
+        let high_pass = {
+            let f0 = 38.13547087602444;
+            let q = 0.5003270373238773;
+
+            let k = (PI * f0 / rate).tan();
+            let a0 = 1.0 + k / q + k * k;
+
+            Biquad::new(
+                [1.0, -2.0, 1.0],
+                [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            )
+        };
+
+        KWeighting { shelf, high_pass }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+#[derive(Debug)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    // transposed direct form II state:
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Biquad { b, a, z: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
@@ -1,21 +1,25 @@
+use std::cmp;
 use std::collections::VecDeque;
 use std::sync::mpsc::{self, SyncSender, Receiver, TryRecvError};
 use std::thread;
 
 use derive_more::From;
-use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::media::{Audio, Video};
 use mixlab_codec::ffmpeg::codec::{self, CodecBuilder, RecvFrameError, Decode};
-use mixlab_codec::ffmpeg::{AvError, AvIoError, AvIoReader, IoReader, InputContainer};
-use mixlab_protocol::{MediaId, MediaSourceParams};
-use mixlab_util::time::{MediaTime, MediaDuration, TimeBase};
+use mixlab_codec::ffmpeg::{sys as ff, AvError, AvIoError, AvIoReader, IoReader, InputContainer};
+use mixlab_protocol::{MediaId, MediaSourceParams, Deinterlace, ResampleQuality, Severity};
+use mixlab_util::time::{MediaTime, TimeBase};
 
-use crate::engine::{InputRef, OutputRef, VideoFrame, ModuleCtx, SAMPLE_RATE, TICKS_PER_SECOND};
+use crate::engine::{InputRef, OutputRef, VideoFrame, ModuleCtx, Sample, CHANNELS};
 use crate::module::{ModuleT, LineType, Terminal};
 use crate::project::media;
 use crate::project::ProjectBaseRef;
 use crate::project::stream::ReadStream;
+use crate::resample::Resampler;
 use crate::throttle::MediaThrottle;
+use crate::util;
 use crate::video;
+use crate::video::deinterlace::Deinterlacer;
 
 #[derive(Debug)]
 pub struct MediaSource {
@@ -35,8 +39,73 @@ pub enum MediaSourceEvent {
 pub struct OpenMedia {
     media_id: MediaId,
     rx: Receiver<Frame>,
+    audio_rx: Option<Receiver<MediaAudio>>,
     epoch: Option<MediaTime>,
     video_buffer: VecDeque<Frame>,
+    deinterlace: Deinterlace,
+    deinterlacer: Deinterlacer,
+    ended: bool,
+}
+
+impl OpenMedia {
+    pub fn media_id(&self) -> MediaId {
+        self.media_id
+    }
+
+    pub fn set_deinterlace(&mut self, deinterlace: Deinterlace) {
+        self.deinterlace = deinterlace;
+    }
+
+    /// Returns the frame to show in the tick spanning `start_of_frame` to
+    /// `end_of_frame`, if there is a new one. Playback starts from the first
+    /// call.
+    pub fn next_frame(&mut self, start_of_frame: MediaTime, end_of_frame: MediaTime) -> Option<VideoFrame> {
+        match self.rx.try_recv() {
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => {
+                self.ended = true;
+            }
+            Ok(frame) => {
+                let epoch = *self.epoch.get_or_insert(start_of_frame);
+                let pts = frame.pts.add_epoch(epoch);
+
+                for (offset, frame) in self.deinterlacer.process(self.deinterlace, frame.frame) {
+                    self.video_buffer.push_back(Frame {
+                        pts: pts + offset,
+                        frame,
+                    });
+                }
+            }
+        }
+
+        let frame = self.video_buffer.front()?;
+
+        if frame.pts < end_of_frame {
+            let video = VideoFrame {
+                data: frame.frame.clone(),
+                tick_offset: frame.pts - start_of_frame,
+            };
+
+            self.video_buffer.pop_front();
+
+            Some(video)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the next block of decoded audio, if the media was opened with
+    /// audio and there is one. Audio is decoded at the pace video plays at,
+    /// or with no video, as fast as it's read
+    pub fn next_audio(&mut self) -> Option<MediaAudio> {
+        self.audio_rx.as_ref()?.try_recv().ok()
+    }
+
+    /// Whether every frame has been played, only ever true for media opened
+    /// to play once
+    pub fn finished(&self) -> bool {
+        self.ended && self.video_buffer.is_empty()
+    }
 }
 
 impl ModuleT for MediaSource {
@@ -67,58 +136,60 @@ impl ModuleT for MediaSource {
     fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
         if self.params.media_id != params.media_id {
             self.params.media_id = params.media_id;
+            self.ctx.diagnostics().clear("media");
 
             let project = self.ctx.project();
 
             self.ctx.spawn_async(async move {
                 let media = match params.media_id {
-                    Some(media_id) => open_media(project, media_id).await,
+                    Some(media_id) => open_media(project, media_id, Playback::Loop).await,
                     None => None,
                 };
 
                 MediaSourceEvent::SetMedia(media)
             });
         }
+
+        self.params.deinterlace = params.deinterlace;
+
+        if let Some(media) = &mut self.media {
+            media.set_deinterlace(params.deinterlace);
+        }
+
         None
     }
 
     fn receive_event(&mut self, event: MediaSourceEvent) {
         match event {
-            MediaSourceEvent::SetMedia(media) => {
+            MediaSourceEvent::SetMedia(mut media) => {
+                if let Some(media) = &mut media {
+                    media.set_deinterlace(self.params.deinterlace);
+                }
+
+                if media.is_none() && self.params.media_id.is_some() {
+                    self.ctx.diagnostics().error("media", "Media is missing or could not be opened");
+                }
+
                 self.media = media;
             }
         }
     }
 
     fn run_tick(&mut self, t: u64, _: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
-        let start_of_frame = MediaTime::new(t as i64, SAMPLE_RATE as i64);
-        let end_of_frame = start_of_frame + MediaDuration::new(1, TICKS_PER_SECOND as i64);
+        let config = self.ctx.config();
+        let start_of_frame = config.media_time(t);
+        let end_of_frame = start_of_frame + config.tick_duration();
 
         if let Some(media) = &mut self.media {
-            match media.rx.try_recv() {
-                Err(TryRecvError::Empty) => {}
-                Err(TryRecvError::Disconnected) => {
-                    eprintln!("media_source: decode thread died");
-                }
-                Ok(frame) => {
-                    let epoch = *media.epoch.get_or_insert(start_of_frame);
+            let ended = media.ended;
 
-                    media.video_buffer.push_back(Frame {
-                        pts: frame.pts.add_epoch(epoch),
-                        frame: frame.frame,
-                    });
-                }
+            if let Some(frame) = media.next_frame(start_of_frame, end_of_frame) {
+                *outputs[0].expect_video() = Some(frame);
             }
 
-            if let Some(frame) = media.video_buffer.front() {
-                if frame.pts < end_of_frame {
-                    *outputs[0].expect_video() = Some(VideoFrame {
-                        data: frame.frame.clone(),
-                        tick_offset: frame.pts - start_of_frame,
-                    });
-
-                    media.video_buffer.pop_front();
-                }
+            if media.ended && !ended {
+                eprintln!("media_source: decode thread died");
+                self.ctx.diagnostics().error("media", "Playback stopped, the media could not be decoded");
             }
         }
 
@@ -134,19 +205,202 @@ impl ModuleT for MediaSource {
     }
 }
 
-async fn open_media(project: ProjectBaseRef, media_id: MediaId) -> Option<OpenMedia> {
+/// Media an input module plays in place of its live source while the
+/// workspace is being rehearsed. It's opened when rehearsal starts, so that
+/// every rehearsal plays it from the top, and closed again when it ends
+#[derive(Debug, Default)]
+pub struct StandIn {
+    // for modules which only play the stand-in's audio:
+    audio_only: bool,
+    media_id: Option<MediaId>,
+    rehearsing: bool,
+    media: Option<OpenMedia>,
+    audio: MediaAudioReader,
+}
+
+impl StandIn {
+    /// A stand-in for modules with no video output. Its video isn't decoded
+    /// at all, rather than left to back up unread
+    pub fn audio_only() -> Self {
+        StandIn { audio_only: true, ..StandIn::default() }
+    }
+
+    /// Whether the module should play the stand-in rather than its source
+    pub fn active(&self) -> bool {
+        self.rehearsing && self.media_id.is_some()
+    }
+
+    /// Opens or closes the stand-in as rehearsal starts and ends or its
+    /// media changes. Modules call this every tick, and hand the media that
+    /// comes back to them through `event` on to `set_media`
+    pub fn sync<M: ModuleT>(&mut self, ctx: &ModuleCtx<M>, media_id: Option<MediaId>, event: fn(Option<OpenMedia>) -> M::Event) {
+        let rehearsing = ctx.rehearsal().active();
+
+        if self.rehearsing == rehearsing && self.media_id == media_id {
+            return;
+        }
+
+        self.rehearsing = rehearsing;
+        self.media_id = media_id;
+        self.media = None;
+        self.audio.clear();
+
+        let diagnostics = ctx.diagnostics();
+
+        match media_id {
+            Some(media_id) if rehearsing => {
+                diagnostics.report("stand_in", Severity::Info, "Rehearsing, playing stand-in media");
+
+                let project = ctx.project();
+                let audio_only = self.audio_only;
+
+                ctx.spawn_async(async move {
+                    let media = if audio_only {
+                        open_audio_only(project, media_id, Playback::Loop).await
+                    } else {
+                        open_media_with_audio(project, media_id, Playback::Loop).await
+                    };
+
+                    event(media)
+                });
+            }
+            _ => {
+                diagnostics.clear("stand_in");
+            }
+        }
+    }
+
+    pub fn set_media<M: ModuleT>(&mut self, ctx: &ModuleCtx<M>, media: Option<OpenMedia>) {
+        // opened for a rehearsal since ended, or media since replaced:
+        let opened = media.as_ref().map(OpenMedia::media_id);
+
+        if !self.active() || (opened.is_some() && opened != self.media_id) {
+            return;
+        }
+
+        if media.is_none() {
+            ctx.diagnostics().error("stand_in", "Stand-in media is missing or could not be opened");
+        }
+
+        self.media = media;
+    }
+
+    pub fn next_frame(&mut self, start_of_frame: MediaTime, end_of_frame: MediaTime) -> Option<VideoFrame> {
+        self.media.as_mut()?.next_frame(start_of_frame, end_of_frame)
+    }
+
+    /// Fills `output` with the stand-in's audio at the engine's
+    /// `sample_rate`, silent where there is none, eg. until it opens
+    pub fn read_audio(&mut self, sample_rate: usize, output: &mut [Sample]) {
+        let len = match &mut self.media {
+            Some(media) => self.audio.read(media, sample_rate, output),
+            None => 0,
+        };
+
+        util::zero(&mut output[len..]);
+    }
+}
+
+/// Reads the audio of media opened with `open_media_with_audio`, resampled
+/// to the engine's rate
+#[derive(Debug, Default)]
+pub struct MediaAudioReader {
+    resampler: Option<Resampler>,
+    // resampled audio at engine rate not yet written to output:
+    pending: Vec<Sample>,
+}
+
+impl MediaAudioReader {
+    /// Fills as much of `output` as there is audio ready for, returning how
+    /// many samples were written
+    pub fn read(&mut self, media: &mut OpenMedia, sample_rate: usize, output: &mut [Sample]) -> usize {
+        // read no further ahead than needed, it's what paces decoding:
+        while self.pending.len() < output.len() {
+            let audio = match media.next_audio() {
+                Some(audio) => audio,
+                None => break,
+            };
+
+            let stale = match &self.resampler {
+                Some(resampler) => resampler.input_rate() != audio.sample_rate,
+                None => true,
+            };
+
+            if stale {
+                self.resampler = Some(Resampler::new(ResampleQuality::default(), CHANNELS, audio.sample_rate, sample_rate));
+            }
+
+            self.resampler.as_mut().unwrap().process(&audio.samples, &mut self.pending);
+        }
+
+        let len = cmp::min(output.len(), self.pending.len());
+        output[0..len].copy_from_slice(&self.pending[0..len]);
+        self.pending.drain(0..len);
+        len
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Playback {
+    Loop,
+    Once,
+}
+
+/// Decoded audio as interleaved stereo, at the media's own sample rate
+#[derive(Debug)]
+pub struct MediaAudio {
+    pub sample_rate: usize,
+    pub samples: Vec<Sample>,
+}
+
+pub async fn open_media(project: ProjectBaseRef, media_id: MediaId, playback: Playback) -> Option<OpenMedia> {
+    open(project, media_id, playback, true, false).await
+}
+
+/// Like `open_media`, but also decodes the media's first audio stream, if it
+/// has one. Audio must then be read with `next_audio` as it plays, or
+/// playback stalls waiting on it
+pub async fn open_media_with_audio(project: ProjectBaseRef, media_id: MediaId, playback: Playback) -> Option<OpenMedia> {
+    open(project, media_id, playback, true, true).await
+}
+
+/// Like `open_media_with_audio`, but decodes only the audio. No frames ever
+/// come from `next_frame`, so it needn't be called
+pub async fn open_audio_only(project: ProjectBaseRef, media_id: MediaId, playback: Playback) -> Option<OpenMedia> {
+    open(project, media_id, playback, false, true).await
+}
+
+async fn open(project: ProjectBaseRef, media_id: MediaId, playback: Playback, with_video: bool, with_audio: bool) -> Option<OpenMedia> {
     match media::open(project, media_id).await {
         Ok(Some(stream)) => {
             let (tx, rx) = mpsc::sync_channel(2);
+
+            // audio frames are shorter than video frames, so more of them can
+            // be decoded ahead at once:
+            let (audio_tx, audio_rx) = if with_audio {
+                let (audio_tx, audio_rx) = mpsc::sync_channel(16);
+                (Some(audio_tx), Some(audio_rx))
+            } else {
+                (None, None)
+            };
+
             thread::spawn(move || {
-                let result = run_decode_thread(stream, tx);
+                let result = run_decode_thread(stream, tx, audio_tx, playback, with_video);
                 println!("decode thread said: {:?}", result);
             });
             Some(OpenMedia {
                 media_id,
                 rx,
+                audio_rx,
                 epoch: None,
                 video_buffer: VecDeque::new(),
+                deinterlace: Deinterlace::default(),
+                deinterlacer: Deinterlacer::default(),
+                ended: false,
             })
         }
         Ok(None) => None,
@@ -182,7 +436,7 @@ impl From<AvIoError<ReadStream>> for DecodeError {
     }
 }
 
-fn run_decode_thread(stream: ReadStream, tx: SyncSender<Frame>) -> Result<(), DecodeError> {
+fn run_decode_thread(stream: ReadStream, tx: SyncSender<Frame>, audio_tx: Option<SyncSender<MediaAudio>>, playback: Playback, with_video: bool) -> Result<(), DecodeError> {
     let container = InputContainer::open(AvIoReader::new(stream))?;
 
     for (idx, stream) in container.streams().iter().enumerate() {
@@ -190,6 +444,19 @@ fn run_decode_thread(stream: ReadStream, tx: SyncSender<Frame>) -> Result<(), De
         // println!("            Time base: {}", stream.time_base());
     }
 
+    // audio only media, eg. idents, and media opened for its audio alone are
+    // paced by the audio being read rather than by a throttle. tx is held on
+    // to meanwhile, so that the media isn't taken to have ended:
+    let audio_only = !with_video ||
+        container.streams()[0].codec_parameters().codec_type == ff::AVMediaType_AVMEDIA_TYPE_AUDIO;
+
+    if audio_only {
+        return match audio_tx {
+            Some(tx) => play_audio_only(container, tx, playback),
+            None => Err(DecodeError::NoFrames),
+        };
+    }
+
     let video_stream = &container.streams()[0];
     let video_time_base = video_stream.time_base();
     let video_codec_params = video_stream.codec_parameters();
@@ -198,10 +465,16 @@ fn run_decode_thread(stream: ReadStream, tx: SyncSender<Frame>) -> Result<(), De
         .with_parameters(video_codec_params)
         .open_decoder()?;
 
+    let audio = match audio_tx {
+        Some(tx) => open_audio(&container, tx)?,
+        None => None,
+    };
+
     let mut play = PlaybackContext {
         container,
         video_decode,
         video_time_base,
+        audio,
         throttle: MediaThrottle::new(),
         tx,
     };
@@ -209,7 +482,16 @@ fn run_decode_thread(stream: ReadStream, tx: SyncSender<Frame>) -> Result<(), De
     let mut iter_start = MediaTime::zero();
 
     while let Some(iter_end) = play_once(&mut play, iter_start)? {
+        if playback == Playback::Once {
+            break;
+        }
+
         play.video_decode.flush_buffers();
+
+        if let Some(audio) = &mut play.audio {
+            audio.decode.flush_buffers();
+        }
+
         play.container.seek(MediaTime::zero())?;
         iter_start = iter_end;
     }
@@ -221,10 +503,108 @@ struct PlaybackContext {
     container: InputContainer<ReadStream>,
     video_decode: Decode<Video>,
     video_time_base: TimeBase,
+    audio: Option<AudioContext>,
     throttle: MediaThrottle,
     tx: SyncSender<Frame>,
 }
 
+struct AudioContext {
+    stream_index: i32,
+    decode: Decode<Audio>,
+    tx: SyncSender<MediaAudio>,
+}
+
+impl AudioContext {
+    // passes on everything the decoder has ready, returns false once the
+    // receiver has gone away
+    fn drain(&mut self) -> Result<bool, DecodeError> {
+        loop {
+            match self.decode.recv_frame() {
+                Ok(decoded) => {
+                    let samples = match decoded.stereo_samples() {
+                        Some(samples) => samples,
+                        // unsupported sample format, play the video silent:
+                        None => continue,
+                    };
+
+                    let audio = MediaAudio {
+                        sample_rate: decoded.sample_rate(),
+                        samples,
+                    };
+
+                    if self.tx.send(audio).is_err() {
+                        return Ok(false);
+                    }
+                }
+                Err(RecvFrameError::NeedMoreInput) |
+                Err(RecvFrameError::Eof) => { return Ok(true); }
+                Err(e) => { return Err(e.into()); }
+            }
+        }
+    }
+}
+
+fn open_audio(container: &InputContainer<ReadStream>, tx: SyncSender<MediaAudio>) -> Result<Option<AudioContext>, DecodeError> {
+    let stream_index = container.streams().iter()
+        .position(|stream| stream.codec_parameters().codec_type == ff::AVMediaType_AVMEDIA_TYPE_AUDIO);
+
+    let stream_index = match stream_index {
+        Some(index) => index,
+        None => { return Ok(None); }
+    };
+
+    let stream = &container.streams()[stream_index];
+    let codec_params = stream.codec_parameters();
+
+    let decode = CodecBuilder::<Audio>::new(codec_params.codec_id, stream.time_base())?
+        .with_parameters(codec_params)
+        .open_decoder()?;
+
+    Ok(Some(AudioContext {
+        stream_index: stream_index as i32,
+        decode,
+        tx,
+    }))
+}
+
+fn play_audio_only(mut container: InputContainer<ReadStream>, tx: SyncSender<MediaAudio>, playback: Playback) -> Result<(), DecodeError> {
+    let mut audio = open_audio(&container, tx)?.ok_or(DecodeError::NoFrames)?;
+    let mut played = false;
+
+    loop {
+        match container.read_packet()? {
+            Some(pkt) => {
+                if pkt.stream_index() != audio.stream_index {
+                    continue;
+                }
+
+                audio.decode.send_packet(&pkt)?;
+                played = true;
+
+                if !audio.drain()? {
+                    return Ok(());
+                }
+            }
+            None => {
+                audio.decode.end_of_stream()?;
+
+                if !audio.drain()? || playback == Playback::Once {
+                    return Ok(());
+                }
+
+                // rather than seek around an empty stream forever:
+                if !played {
+                    return Err(DecodeError::NoFrames);
+                }
+
+                played = false;
+                audio.decode.flush_buffers();
+                container.seek(MediaTime::zero())?;
+            }
+        }
+    }
+}
+
 fn play_once(play: &mut PlaybackContext, iter_start: MediaTime) -> Result<Option<MediaTime>, DecodeError> {
     let mut iter_end = None;
     let mut reached_end_of_stream = false;
@@ -234,6 +614,18 @@ fn play_once(play: &mut PlaybackContext, iter_start: MediaTime) -> Result<Option
         if !reached_end_of_stream {
             match play.container.read_packet()? {
                 Some(pkt) => {
+                    if let Some(audio) = &mut play.audio {
+                        if pkt.stream_index() == audio.stream_index {
+                            audio.decode.send_packet(&pkt)?;
+
+                            if !audio.drain()? {
+                                return Ok(None);
+                            }
+
+                            continue;
+                        }
+                    }
+
                     if pkt.stream_index() != 0 {
                         continue;
                     }
@@ -242,6 +634,15 @@ fn play_once(play: &mut PlaybackContext, iter_start: MediaTime) -> Result<Option
                 }
                 None => {
                     play.video_decode.end_of_stream()?;
+
+                    if let Some(audio) = &mut play.audio {
+                        audio.decode.end_of_stream()?;
+
+                        if !audio.drain()? {
+                            return Ok(None);
+                        }
+                    }
+
                     reached_end_of_stream = true;
                 }
             }
@@ -1,4 +1,4 @@
-use mixlab_protocol::{MixerParams, LineType, Terminal};
+use mixlab_protocol::{MixerParams, LineType, Terminal, ParamSpec, ParamUnit};
 
 use crate::engine::{self, Sample, InputRef, OutputRef};
 use crate::module::ModuleT;
@@ -7,6 +7,7 @@ use crate::util;
 #[derive(Debug)]
 pub struct Mixer {
     params: MixerParams,
+    groups: engine::GroupLevels,
     ctx: Option<engine::ModuleCtx<Self>>,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
@@ -27,6 +28,7 @@ impl ModuleT for Mixer {
                 LineType::Stereo.labeled("Cue"),
             ],
             params,
+            groups: ctx.groups(),
             ctx: Some(ctx),
         };
 
@@ -56,7 +58,8 @@ impl ModuleT for Mixer {
 
         for (ch, channel) in self.params.channels.iter().enumerate() {
             let input = inputs[ch].expect_stereo();
-            let channel_gain = channel.fader * channel.gain.to_linear();
+            let channel_gain = channel.fader * channel.gain.to_linear()
+                * self.groups.level(channel.group);
 
             for i in 0..len {
                 master[i] += (input[i] as f64 * channel_gain) as Sample;
@@ -77,4 +80,13 @@ impl ModuleT for Mixer {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        (0..self.params.channels.len())
+            .flat_map(|idx| vec![
+                ParamSpec::new(format!("/channels/{}/gain", idx), format!("Channel {} gain", idx + 1), ParamUnit::Decibel, -24.0, 6.0),
+                ParamSpec::new(format!("/channels/{}/fader", idx), format!("Channel {} fader", idx + 1), ParamUnit::Percent, 0.0, 1.0),
+            ])
+            .collect()
+    }
 }
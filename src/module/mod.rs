@@ -1,8 +1,8 @@
 use std::any::Any;
 
-use mixlab_protocol::{Terminal, LineType};
+use mixlab_protocol::{Terminal, LineType, ParamSpec};
 
-use crate::engine::{InputRef, OutputRef, ModuleCtx};
+use crate::engine::{InputRef, OutputRef, ModuleCtx, ClockRef};
 
 pub trait ModuleT: Any + Sized {
     type Params;
@@ -12,10 +12,29 @@ pub trait ModuleT: Any + Sized {
     fn create(params: Self::Params, ctx: ModuleCtx<Self>) -> (Self, Self::Indication);
     fn params(&self) -> Self::Params;
     fn receive_event(&mut self, _: Self::Event) {}
+    // called at the start of the tick containing sample time `at`. modules
+    // needing sample accuracy should queue the event in an engine::Schedule
+    // and apply it from run_tick via Schedule::run_block
+    fn receive_scheduled_event(&mut self, _at: u64, ev: Self::Event) {
+        self.receive_event(ev)
+    }
     fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication>;
     fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication>;
     fn inputs(&self) -> &[Terminal];
     fn outputs(&self) -> &[Terminal];
+    // samples by which outputs lag inputs, for modules that buffer
+    // internally. the engine delays parallel paths to match
+    fn latency(&self) -> usize { 0 }
+    // for modules driven by a device or network source running at its own
+    // rate, which the engine can be clocked from
+    fn clock(&self) -> Option<ClockRef> { None }
+    // parts per million by which the module is currently speeding up (or
+    // slowing down) its output to chase a device clock
+    fn varispeed(&self) -> Option<f64> { None }
+    // units, ranges and curves of the module's numeric params, for clients
+    // to present them by. may change along with the params, eg. a mixer's
+    // channels
+    fn param_specs(&self) -> Vec<ParamSpec> { Vec::new() }
 }
 
 macro_rules! gen_modules {
@@ -29,20 +48,39 @@ macro_rules! enumerate_modules {
     (then $cb:ident!) => {
         $cb!{
             amplifier::Amplifier,
+            artnet_output::ArtNetOutput,
+            beat_detector::BeatDetector,
+            broadcast_delay::BroadcastDelay,
+            browser_source::BrowserSource,
+            bus::Bus,
             envelope::Envelope,
+            envelope_follower::EnvelopeFollower,
             eq_three::EqThree,
             fm_sine::FmSine,
+            hue_light::HueLight,
+            ident_inserter::IdentInserter,
+            image_source::ImageSource,
+            input_device::InputDevice,
+            lfo::Lfo,
+            loudness_logger::LoudnessLogger,
             mixer::Mixer,
             monitor::Monitor,
+            multiviewer::Multiviewer,
+            null_test::NullTest,
             oscillator::Oscillator,
             output_device::OutputDevice,
             plotter::Plotter,
+            preview_overlay::PreviewOverlay,
+            recorder::Recorder,
+            replay_buffer::ReplayBuffer,
             stereo_panner::StereoPanner,
             stereo_splitter::StereoSplitter,
             stream_input::StreamInput,
             stream_output::StreamOutput,
             trigger::Trigger,
+            vca_group::VcaGroup,
             video_mixer::VideoMixer,
+            zone_output::ZoneOutput,
             media_source::MediaSource,
         }
     }
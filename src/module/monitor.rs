@@ -4,17 +4,16 @@ use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 
 use fdk_aac::enc as aac;
-use futures::sink::SinkExt;
+use futures::sink::{Sink, SinkExt};
 use tokio::sync::broadcast;
 use uuid::Uuid;
-use warp::ws::{self, WebSocket};
 
-use mixlab_codec::ffmpeg::PictureSettings;
+use mixlab_codec::ffmpeg::{Colorimetry, PictureSettings};
 use mixlab_mux::mp4::{Mp4Params, TrackData, AdtsFrame, AvcFrame};
 use mixlab_protocol::{LineType, Terminal, MonitorIndication, MonitorTransportPacket};
 use mixlab_util::time::MediaTime;
 
-use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE};
+use crate::engine::{self, InputRef, OutputRef};
 use crate::module::ModuleT;
 use crate::video::encode::{EncodeStream, AudioCtx, AudioParams, VideoCtx, VideoParams, StreamSegment, Profile};
 
@@ -30,7 +29,8 @@ struct Stream {
     live: Arc<broadcast::Sender<StreamSegment>>,
 }
 
-pub async fn stream(socket_id: Uuid, mut client: WebSocket) -> Result<(), ()> {
+/// Streams a monitor out to a client, over a websocket or a remote link
+pub async fn stream<S: Sink<Vec<u8>> + Unpin>(socket_id: Uuid, mut client: S) -> Result<(), ()> {
     let (params, mut stream) = (*SOCKETS).lock()
         .unwrap()
         .get(&socket_id)
@@ -65,17 +65,18 @@ pub async fn stream(socket_id: Uuid, mut client: WebSocket) -> Result<(), ()> {
     Ok(())
 }
 
-async fn send_packet(websocket: &mut WebSocket, packet: MonitorTransportPacket) -> Result<(), ()> {
+async fn send_packet<S: Sink<Vec<u8>> + Unpin>(client: &mut S, packet: MonitorTransportPacket) -> Result<(), ()> {
     // should never fail:
     let bytes = bincode::serialize(&packet).unwrap();
 
-    websocket.send(ws::Message::binary(bytes)).await
+    client.send(bytes).await
         .map_err(|_| ())
 }
 
 #[derive(Debug)]
 pub struct Monitor {
     epoch: Option<MediaTime>,
+    sample_rate: usize,
     socket_id: Uuid,
     codec: AsyncCodec,
     inputs: Vec<Terminal>,
@@ -86,12 +87,14 @@ impl ModuleT for Monitor {
     type Indication = MonitorIndication;
     type Event = ();
 
-    fn create(_: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+    fn create(_: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let sample_rate = ctx.config().sample_rate;
         let socket_id = Uuid::new_v4();
-        let codec = AsyncCodec::start(socket_id);
+        let codec = AsyncCodec::start(socket_id, sample_rate, ctx.config().colorimetry());
 
         let module = Monitor {
             epoch: None,
+            sample_rate,
             socket_id,
             codec,
             inputs: vec![
@@ -117,7 +120,7 @@ impl ModuleT for Monitor {
             _ => unreachable!()
         };
 
-        let absolute_timestamp = MediaTime::new(time as i64, SAMPLE_RATE as i64);
+        let absolute_timestamp = MediaTime::new(time as i64, self.sample_rate as i64);
         let epoch = *self.epoch.get_or_insert(absolute_timestamp);
         let timestamp = absolute_timestamp.remove_epoch(epoch);
 
@@ -150,9 +153,9 @@ struct AsyncCodec {
 }
 
 impl AsyncCodec {
-    pub fn start(socket_id: Uuid) -> AsyncCodec {
+    pub fn start(socket_id: Uuid, sample_rate: usize, color: Colorimetry) -> AsyncCodec {
         let (codec_tx, codec_rx) = mpsc::sync_channel(2);
-        thread::spawn(move || run_codec_thread(socket_id, codec_rx));
+        thread::spawn(move || run_codec_thread(socket_id, sample_rate, color, codec_rx));
 
         AsyncCodec {
             codec_tx,
@@ -182,17 +185,17 @@ struct Tick {
     video: Option<engine::VideoFrame>,
 }
 
-fn run_codec_thread(socket_id: Uuid, rx: mpsc::Receiver<Tick>) {
+fn run_codec_thread(socket_id: Uuid, sample_rate: usize, color: Colorimetry, rx: mpsc::Receiver<Tick>) {
     // create encoders
     let audio_ctx = AudioCtx::new(AudioParams {
         bit_rate: aac::BitRate::VbrVeryHigh,
-        sample_rate: SAMPLE_RATE,
+        sample_rate,
         transport: aac::Transport::Adts,
     });
 
     let video_ctx = VideoCtx::new(VideoParams {
-        picture: PictureSettings::yuv420p(MONITOR_WIDTH, MONITOR_HEIGHT),
-        time_base: SAMPLE_RATE,
+        picture: PictureSettings::yuv420p(MONITOR_WIDTH, MONITOR_HEIGHT).with_color(color),
+        time_base: sample_rate,
         profile: Profile::Monitor,
     });
 
@@ -203,7 +206,8 @@ fn run_codec_thread(socket_id: Uuid, rx: mpsc::Receiver<Tick>) {
         dcr.write_to(&mut dcr_bytes);
 
         Mp4Params {
-            timescale: SAMPLE_RATE as u32,
+            timescale: sample_rate as u32,
+            sample_rate: sample_rate as u32,
             width: MONITOR_WIDTH as u32,
             height: MONITOR_HEIGHT as u32,
             dcr: Cow::Owned(dcr_bytes),
@@ -0,0 +1,298 @@
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::AvFrame;
+use mixlab_protocol::{MultiviewerParams, LineType, Terminal, MULTIVIEWER_SOURCES};
+use mixlab_util::time::{MediaTime, MediaDuration};
+
+use crate::engine::{self, EngineConfig, InputRef, OutputRef, CHANNELS};
+use crate::module::ModuleT;
+use crate::video;
+use crate::video::draw::{self, Canvas, Yuv};
+use crate::video::encode::DynamicScaler;
+
+const OUTPUT_WIDTH: usize = 1280;
+const OUTPUT_HEIGHT: usize = 720;
+
+// video inputs are program, preview, then each source:
+const PROGRAM: usize = 0;
+const PREVIEW: usize = 1;
+const VIDEO_INPUTS: usize = 2 + MULTIVIEWER_SOURCES;
+
+// gap between a tile's edge and its picture, leaving room for the border:
+const TILE_INSET: usize = 4;
+const LABEL_PADDING: usize = 3;
+
+const METER_WIDTH: usize = 10;
+const METER_FLOOR_DB: f64 = -60.0;
+const METER_YELLOW_DB: f64 = -18.0;
+const METER_RED_DB: f64 = -6.0;
+// peak meters fall back at 20db per second:
+const METER_FALLBACK_DB_PER_SEC: f64 = 20.0;
+
+#[derive(Debug)]
+pub struct Multiviewer {
+    params: MultiviewerParams,
+    config: EngineConfig,
+    tiles: Vec<Tile>,
+    meter: [f64; CHANNELS],
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+#[derive(Debug)]
+struct Tile {
+    rect: Rect,
+    scaler: DynamicScaler,
+    stored: Option<StoredFrame>,
+}
+
+#[derive(Debug)]
+struct StoredFrame {
+    active_until: MediaTime,
+    frame: AvFrame<Video>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+}
+
+impl ModuleT for Multiviewer {
+    type Params = MultiviewerParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let mut inputs = vec![
+            LineType::Video.labeled("Program"),
+            LineType::Video.labeled("Preview"),
+        ];
+
+        inputs.extend((0..MULTIVIEWER_SOURCES).map(|i|
+            LineType::Video.labeled(&(i + 1).to_string())));
+
+        inputs.push(LineType::Stereo.labeled("Audio"));
+
+        let tiles = (0..VIDEO_INPUTS).map(|idx| {
+            let rect = tile_rect(idx);
+
+            let picture = ctx.config().picture(
+                rect.w - TILE_INSET * 2,
+                rect.h - TILE_INSET * 2);
+
+            Tile {
+                rect,
+                scaler: DynamicScaler::new(picture),
+                stored: None,
+            }
+        }).collect();
+
+        let module = Multiviewer {
+            params,
+            config: ctx.config(),
+            tiles,
+            meter: [0.0; CHANNELS],
+            inputs,
+            outputs: vec![LineType::Video.labeled("Output")],
+        };
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let absolute_timestamp = self.config.media_time(t);
+
+        for (tile, input) in self.tiles.iter_mut().zip(inputs) {
+            // expire stored frames
+            if let Some(stored) = &tile.stored {
+                if absolute_timestamp >= stored.active_until {
+                    tile.stored = None;
+                }
+            }
+
+            // receive new input frames
+            if let Some(video) = input.expect_video() {
+                let mut frame = video.data.decoded.clone();
+                let scaled = tile.scaler.scale(&mut frame).clone();
+
+                tile.stored = Some(StoredFrame {
+                    active_until: absolute_timestamp + video.tick_offset + video.data.duration_hint,
+                    frame: scaled,
+                });
+            }
+        }
+
+        self.measure_audio(inputs[VIDEO_INPUTS].expect_stereo());
+
+        let mut output_frame = AvFrame::blank(&self.config.picture(OUTPUT_WIDTH, OUTPUT_HEIGHT));
+
+        {
+            let mut canvas = Canvas::new(&mut output_frame);
+
+            for (idx, tile) in self.tiles.iter().enumerate() {
+                self.draw_tile(&mut canvas, idx, tile);
+            }
+
+            self.draw_meters(&mut canvas, self.tiles[PROGRAM].rect);
+        }
+
+        *outputs[0].expect_video() = Some(engine::VideoFrame {
+            data: video::Frame {
+                decoded: output_frame,
+                duration_hint: self.config.tick_duration(),
+            },
+            tick_offset: MediaDuration::new(0, 1),
+        });
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+impl Multiviewer {
+    fn label(&self, idx: usize) -> String {
+        match idx {
+            PROGRAM => "Program".to_owned(),
+            PREVIEW => "Preview".to_owned(),
+            _ => {
+                let source = idx - 2;
+                self.params.labels.get(source)
+                    .cloned()
+                    .unwrap_or_else(|| (source + 1).to_string())
+            }
+        }
+    }
+
+    fn draw_tile(&self, canvas: &mut Canvas, idx: usize, tile: &Tile) {
+        let Rect { x, y, w, h } = tile.rect;
+
+        match &tile.stored {
+            Some(stored) => canvas.blit(x + TILE_INSET, y + TILE_INSET, &stored.frame),
+            None => {
+                let text = "No signal";
+                canvas.text(
+                    x + (w - draw::text_width(text)) / 2,
+                    y + (h - draw::TEXT_HEIGHT) / 2,
+                    text,
+                    draw::GREY);
+            }
+        }
+
+        // tally borders, red for program and green for preview as on most
+        // hardware multiviewers
+        let (border, thickness) = match idx {
+            PROGRAM => (draw::RED, TILE_INSET),
+            PREVIEW => (draw::GREEN, TILE_INSET),
+            _ => (draw::GREY, 1),
+        };
+
+        canvas.outline(x, y, w, h, thickness, border);
+
+        // truncate labels too long to fit the tile:
+        let max_chars = (w - (TILE_INSET + LABEL_PADDING) * 2) / draw::text_width(" ");
+        let label = self.label(idx).chars().take(max_chars).collect::<String>();
+        let label_width = draw::text_width(&label);
+        let label_height = draw::TEXT_HEIGHT + LABEL_PADDING * 2;
+        let label_x = x + (w - label_width) / 2;
+        let label_y = y + h - TILE_INSET - label_height;
+
+        canvas.fill(label_x - LABEL_PADDING, label_y, label_width + LABEL_PADDING * 2, label_height, draw::BLACK);
+        canvas.text(label_x, label_y + LABEL_PADDING, &label, draw::WHITE);
+    }
+
+    fn measure_audio(&mut self, samples: &[f32]) {
+        let tick_secs = self.config.block_size as f64 / self.config.sample_rate as f64;
+        let fallback = 10f64.powf(-METER_FALLBACK_DB_PER_SEC * tick_secs / 20.0);
+
+        for (channel, level) in self.meter.iter_mut().enumerate() {
+            let peak = samples.iter()
+                .skip(channel)
+                .step_by(CHANNELS)
+                .fold(0.0, |peak, sample| f64::max(peak, sample.abs() as f64));
+
+            *level = f64::max(peak, *level * fallback);
+        }
+    }
+
+    // one meter per channel inside the right edge of the program tile:
+    fn draw_meters(&self, canvas: &mut Canvas, program: Rect) {
+        let top = program.y + TILE_INSET * 3;
+        let height = program.h - TILE_INSET * 6 - draw::TEXT_HEIGHT - LABEL_PADDING * 2;
+
+        for (channel, level) in self.meter.iter().enumerate() {
+            let x = program.x + program.w - TILE_INSET * 3 - (CHANNELS - channel) * (METER_WIDTH + 2);
+
+            canvas.fill(x, top, METER_WIDTH, height, draw::BLACK);
+
+            let lit = meter_height(20.0 * level.log10(), height);
+
+            // light each segment of the meter up to the current level, from
+            // the bottom up:
+            let segments = [
+                (METER_FLOOR_DB, METER_YELLOW_DB, draw::GREEN),
+                (METER_YELLOW_DB, METER_RED_DB, draw::YELLOW),
+                (METER_RED_DB, 0.0, draw::RED),
+            ];
+
+            for (from_db, to_db, color) in segments.iter() {
+                let from = meter_height(*from_db, height);
+                let to = usize::min(meter_height(*to_db, height), lit);
+
+                if to > from {
+                    fill_up(canvas, x, top + height, from, to, *color);
+                }
+            }
+        }
+    }
+}
+
+fn fill_up(canvas: &mut Canvas, x: usize, bottom: usize, from: usize, to: usize, color: Yuv) {
+    canvas.fill(x, bottom - to, METER_WIDTH, to - from, color);
+}
+
+fn meter_height(db: f64, height: usize) -> usize {
+    let frac = (db - METER_FLOOR_DB) / -METER_FLOOR_DB;
+    (f64::max(0.0, f64::min(1.0, frac)) * height as f64) as usize
+}
+
+// preview and program side by side across the top half, sources in a grid
+// of four across the bottom half:
+fn tile_rect(idx: usize) -> Rect {
+    let (half_w, half_h) = (OUTPUT_WIDTH / 2, OUTPUT_HEIGHT / 2);
+
+    match idx {
+        PREVIEW => Rect { x: 0, y: 0, w: half_w, h: half_h },
+        PROGRAM => Rect { x: half_w, y: 0, w: half_w, h: half_h },
+        _ => {
+            let source = idx - 2;
+            let columns = 4;
+            let rows = (MULTIVIEWER_SOURCES + columns - 1) / columns;
+            let (w, h) = (OUTPUT_WIDTH / columns, half_h / rows);
+
+            Rect {
+                x: (source % columns) * w,
+                y: half_h + (source / columns) * h,
+                w,
+                h,
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use mixlab_protocol::{NullTestParams, NullTestIndication, LineType, Terminal, Decibel};
+
+use crate::engine::{self, InputRef, OutputRef, CHANNELS};
+use crate::module::ModuleT;
+
+// the residual is reported over windows of this many per second, often
+// enough to catch a brief divergence without flooding clients:
+const WINDOWS_PER_SECOND: usize = 4;
+
+#[derive(Debug)]
+pub struct NullTest {
+    params: NullTestParams,
+    window_size: usize,
+    window: Window,
+    failed: bool,
+    indication: NullTestIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    // interleaved, so counting both channels:
+    samples: usize,
+    sum_squares: f64,
+    peak: f64,
+    exact: bool,
+}
+
+impl Window {
+    fn new() -> Self {
+        Window { exact: true, ..Window::default() }
+    }
+}
+
+impl ModuleT for NullTest {
+    type Params = NullTestParams;
+    type Indication = NullTestIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let indication = NullTestIndication::default();
+
+        let module = NullTest {
+            params,
+            window_size: ctx.config().sample_rate / WINDOWS_PER_SECOND,
+            window: Window::new(),
+            failed: false,
+            indication: indication.clone(),
+            inputs: vec![
+                LineType::Stereo.labeled("A"),
+                LineType::Stereo.labeled("B"),
+            ],
+            outputs: vec![
+                LineType::Stereo.labeled("Residual"),
+            ],
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        let reset = params.reset != self.params.reset;
+        self.params = params;
+
+        if reset {
+            self.failed = false;
+            self.window = Window::new();
+
+            let indication = NullTestIndication { failed: false, ..self.indication.clone() };
+            self.indication = indication.clone();
+            return Some(indication);
+        }
+
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let connected = inputs[0].connected() && inputs[1].connected();
+
+        let a = inputs[0].expect_stereo();
+        let b = inputs[1].expect_stereo();
+        let residual = outputs[0].expect_stereo();
+
+        for ((a, b), out) in a.iter().zip(b).zip(residual.iter_mut()) {
+            // compared before subtracting, so that nothing is lost to
+            // rounding and only identical samples count as null:
+            if a != b {
+                self.window.exact = false;
+            }
+
+            *out = a - b;
+        }
+
+        if !connected {
+            // nothing to compare, start measuring afresh once both are
+            // patched in:
+            self.window = Window::new();
+
+            if self.indication.connected {
+                self.indication = NullTestIndication { failed: self.failed, ..NullTestIndication::default() };
+                return Some(self.indication.clone());
+            }
+
+            return None;
+        }
+
+        for sample in residual.iter() {
+            let sample = *sample as f64;
+            self.window.sum_squares += sample * sample;
+            self.window.peak = self.window.peak.max(sample.abs());
+        }
+
+        self.window.samples += residual.len();
+
+        if self.window.samples < self.window_size * CHANNELS {
+            return None;
+        }
+
+        let window = std::mem::replace(&mut self.window, Window::new());
+
+        if !window.exact {
+            self.failed = true;
+        }
+
+        let (residual_rms, residual_peak) = if window.exact {
+            (None, None)
+        } else {
+            let rms = (window.sum_squares / window.samples as f64).sqrt();
+            (Some(Decibel::from_linear(rms)), Some(Decibel::from_linear(window.peak)))
+        };
+
+        let indication = NullTestIndication {
+            connected,
+            residual_rms,
+            residual_peak,
+            null: window.exact,
+            failed: self.failed,
+        };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
@@ -7,20 +7,46 @@ use std::time::Instant;
 use cpal::traits::{HostTrait, DeviceTrait, StreamTrait};
 use ringbuf::{RingBuffer, Producer};
 
-use mixlab_protocol::{OutputDeviceParams, OutputDeviceIndication, LineType, Terminal};
+use mixlab_protocol::{OutputDeviceParams, OutputDeviceIndication, LineType, Terminal, ResampleQuality};
 
-use crate::engine::{self, Sample, InputRef, OutputRef, CHANNELS};
+use crate::engine::{self, Sample, InputRef, OutputRef, ClockRef, SampleClock, DeviceLinks, ResolvedDevice, Diagnostics, CHANNELS};
 use crate::module::ModuleT;
+use crate::resample::Resampler;
 use crate::util;
 
+// the device buffer is held at whatever fill it settles at this long after
+// the stream starts:
+const VARISPEED_SETTLE_SECONDS: usize = 1;
+
+// fill is smoothed over this long, evening out the device's callback size:
+const VARISPEED_SMOOTHING_SECONDS: f64 = 1.0;
+
+// fill error is corrected over roughly this long:
+const VARISPEED_CORRECTION_SECONDS: f64 = 10.0;
+
+// the most the speed is adjusted by, well short of an audible pitch change:
+const VARISPEED_MAX_SKEW: f64 = 0.002;
+
 pub struct OutputDevice {
     params: OutputDeviceParams,
+    sample_rate: usize,
+    ticks_per_second: usize,
     host: cpal::Host,
+    devices: DeviceLinks,
+    resolved: ResolvedDevice,
+    // the local device last opened (or tried) for the one the params name:
+    opened: Option<String>,
+    diagnostics: Diagnostics,
+    dry_run: bool,
     scratch: Vec<Sample>,
     stream: Option<OutputStream>,
     last_clip: Option<Instant>,
     last_lag: Option<Instant>,
     lag_flag: Arc<AtomicBool>,
+    // set when the indication changes outside of a tick:
+    indicate: bool,
+    // advanced as the device plays, whether or not we kept up with it:
+    clock: ClockRef,
     indication: OutputDeviceIndication,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
@@ -29,6 +55,7 @@ pub struct OutputDevice {
 struct OutputStream {
     tx: Producer<f32>,
     config: cpal::StreamConfig,
+    varispeed: Option<Varispeed>,
     // this field is never used directly but must not be dropped for the
     // stream to continue playing:
     _stream: cpal::Stream,
@@ -45,7 +72,7 @@ impl ModuleT for OutputDevice {
     type Indication = OutputDeviceIndication;
     type Event = ();
 
-    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
         let host = cpal::default_host();
 
         // TODO - see if we can update devices as they are added/removed from host
@@ -67,21 +94,35 @@ impl ModuleT for OutputDevice {
             devices,
             clip: None,
             lag: None,
+            resolved: None,
         };
 
-        let device = OutputDevice {
-            params,
+        let mut device = OutputDevice {
+            params: params.clone(),
+            sample_rate: ctx.config().sample_rate,
+            ticks_per_second: ctx.config().ticks_per_second(),
             host,
+            devices: ctx.devices(),
+            resolved: ResolvedDevice::default(),
+            opened: None,
+            diagnostics: ctx.diagnostics(),
+            dry_run: ctx.dry_run(),
             scratch: Vec::new(),
             stream: None,
             last_clip: None,
             last_lag: None,
             lag_flag: Arc::new(AtomicBool::new(false)),
+            indicate: false,
+            clock: SampleClock::new(),
             inputs: vec![LineType::Stereo.unlabeled()],
             outputs: vec![],
             indication: indication.clone(),
         };
 
+        // open the device now, rather than waiting for the params to change:
+        device.update(params);
+
+        let indication = device.indication.clone();
         (device, indication)
     }
 
@@ -90,18 +131,36 @@ impl ModuleT for OutputDevice {
     }
 
     fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
-        let OutputDeviceParams { device, left, right } = new_params;
+        let OutputDeviceParams { device, left, right, varispeed } = new_params;
+
+        // projects keep the device name they were set up with, which is
+        // mapped onto a device on this machine if it has none by that name:
+        let host = &self.host;
+
+        let resolved = self.devices.resolve_cached(&mut self.resolved, device.as_deref(), || {
+            Some(host.output_devices().ok()?
+                .filter_map(|dev| dev.name().ok())
+                .collect())
+        });
 
-        if self.params.device != device {
-            let output_device = self.host.output_devices()
-                .ok()
-                .and_then(|devices| {
-                    devices.into_iter().find(|dev| dev.name().map(|dev| Some(dev) == device).unwrap_or(false))
-                });
+        self.params.device = device;
 
-            if let Some(output_device) = output_device {
-                let config = output_device.default_output_config()
-                    .expect("default_output_format");
+        if self.opened != resolved {
+            self.opened = resolved.clone();
+
+            let output_device = resolved.as_ref().and_then(|resolved| {
+                self.host.output_devices().ok()?
+                    .find(|dev| dev.name().ok().as_ref() == Some(resolved))
+            });
+
+            // we don't resample on output, so only accept devices which can
+            // run at the engine sample rate
+            let output = output_device.filter(|_| !self.dry_run).and_then(|device| {
+                let config = supported_config(&device, self.sample_rate)?;
+                Some((device, config))
+            });
+
+            if let Some((output_device, config)) = output {
 
                 let (tx, mut rx) = RingBuffer::<f32>::new(65536).split();
 
@@ -109,11 +168,16 @@ impl ModuleT for OutputDevice {
                         &config.config(),
                         {
                             let lag_flag = self.lag_flag.clone();
+                            let clock = self.clock.clone();
+                            let channels = config.channels() as usize;
+                            let sample_rate = self.sample_rate;
                             let mut backoff_ticks = 0;
                             move |data: &mut [f32], _info| {
                                 // TOOD info param contains timestamp for sample block
                                 // consider how we might be able to use this
 
+                                clock.advance(data.len() / channels, sample_rate);
+
                                 if backoff_ticks > 0 {
                                     backoff_ticks -= 1;
                                     util::zero(data);
@@ -129,8 +193,12 @@ impl ModuleT for OutputDevice {
                                 }
                             }
                         },
-                        |err| {
-                            eprintln!("output stream error! {:?}", err);
+                        {
+                            let diagnostics = self.diagnostics.clone();
+                            move |err| {
+                                eprintln!("output stream error! {:?}", err);
+                                diagnostics.error("stream", format!("Device error: {}", err));
+                            }
                         })
                     .expect("build_output_stream");
 
@@ -139,17 +207,40 @@ impl ModuleT for OutputDevice {
                 let stream = OutputStream {
                     tx,
                     config: config.config(),
+                    varispeed: None,
                     _stream: stream,
                 };
 
-                self.params.device = device.clone();
                 self.stream = Some(stream);
             } else {
                 self.stream = None;
             }
+
+            // errors from the last stream don't carry over to this one:
+            self.diagnostics.clear("stream");
+
+            let opened = self.stream.as_ref().and(resolved.clone());
+
+            if self.indication.resolved != opened {
+                self.indication.resolved = opened;
+                self.indicate = true;
+            }
         }
 
-        if let Some(stream) = self.stream.as_ref() {
+        // a dry run opens nothing, so there's nothing to fail to open:
+        diagnose_device(&self.diagnostics, "device", self.params.device.as_deref(), resolved.as_deref(), self.stream.is_some() || self.dry_run);
+
+        self.params.varispeed = varispeed;
+
+        if let Some(stream) = self.stream.as_mut() {
+            // chase the device's clock, starting over from its current fill
+            // whenever varispeed is turned on:
+            if !varispeed {
+                stream.varispeed = None;
+            } else if stream.varispeed.is_none() {
+                stream.varispeed = Some(Varispeed::new(self.sample_rate, self.ticks_per_second));
+            }
+
             // zero scratch buffer if channel assignments change so that we don't
             // keep playing left over data:
 
@@ -177,6 +268,11 @@ impl ModuleT for OutputDevice {
         let mut clip = false;
 
         if let Some(stream) = &mut self.stream {
+            let input = match &mut stream.varispeed {
+                Some(varispeed) => varispeed.process(input),
+                None => input,
+            };
+
             let output_channels = stream.config.channels as usize;
             let samples_per_channel = input.len() / CHANNELS;
             let scratch_len = samples_per_channel * output_channels;
@@ -208,6 +304,10 @@ impl ModuleT for OutputDevice {
             }
 
             stream.tx.push_slice(&self.scratch[0..(samples_per_channel * output_channels)]);
+
+            if let Some(varispeed) = &mut stream.varispeed {
+                varispeed.update(stream.tx.len() / output_channels);
+            }
         }
 
         let now = Instant::now();
@@ -220,7 +320,7 @@ impl ModuleT for OutputDevice {
             self.last_lag = Some(now);
         }
 
-        let mut indication_changed = false;
+        let mut indication_changed = std::mem::take(&mut self.indicate);
 
         let new_clip_status = util::temporal_warning(
             self.last_clip.map(|time| now - time));
@@ -245,6 +345,15 @@ impl ModuleT for OutputDevice {
         }
     }
 
+    fn clock(&self) -> Option<ClockRef> {
+        Some(self.clock.clone())
+    }
+
+    fn varispeed(&self) -> Option<f64> {
+        let varispeed = self.stream.as_ref()?.varispeed.as_ref()?;
+        Some(varispeed.skew * 1_000_000.0)
+    }
+
     fn inputs(&self) -> &[Terminal] {
         &self.inputs
     }
@@ -253,3 +362,99 @@ impl ModuleT for OutputDevice {
         &self.outputs
     }
 }
+
+// resamples the engine's output ever so slightly faster or slower to match
+// the rate the device is actually playing at, rather than letting its buffer
+// run dry or overflow
+struct Varispeed {
+    resampler: Resampler,
+    output: Vec<Sample>,
+    sample_rate: usize,
+    ticks_per_second: usize,
+    settle_ticks: usize,
+    // device buffer fill in frames, smoothed:
+    fill: Option<f64>,
+    target: Option<f64>,
+    // positive when input is played back faster than nominal:
+    skew: f64,
+}
+
+impl Varispeed {
+    fn new(sample_rate: usize, ticks_per_second: usize) -> Self {
+        Varispeed {
+            resampler: Resampler::new(ResampleQuality::Polyphase, CHANNELS, sample_rate, sample_rate),
+            output: Vec::new(),
+            sample_rate,
+            ticks_per_second,
+            settle_ticks: VARISPEED_SETTLE_SECONDS * ticks_per_second,
+            fill: None,
+            target: None,
+            skew: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: &[Sample]) -> &[Sample] {
+        self.output.clear();
+        self.resampler.process(input, &mut self.output);
+        &self.output
+    }
+
+    // adjusts speed for the next tick from how much the device has buffered
+    fn update(&mut self, buffered_frames: usize) {
+        let buffered_frames = buffered_frames as f64;
+        let smoothing = 1.0 / (VARISPEED_SMOOTHING_SECONDS * self.ticks_per_second as f64);
+
+        let fill = match self.fill {
+            Some(fill) => fill + (buffered_frames - fill) * smoothing,
+            None => buffered_frames,
+        };
+
+        self.fill = Some(fill);
+
+        if self.settle_ticks > 0 {
+            self.settle_ticks -= 1;
+            return;
+        }
+
+        let target = *self.target.get_or_insert(fill);
+
+        // a filling buffer means the device is playing slower than we are
+        // producing, so consume input faster to produce less of it:
+        let error = (fill - target) / (VARISPEED_CORRECTION_SECONDS * self.sample_rate as f64);
+
+        self.skew = error.max(-VARISPEED_MAX_SKEW).min(VARISPEED_MAX_SKEW);
+        self.resampler.set_varispeed(1.0 + self.skew);
+    }
+}
+
+/// Reports a device the params name which is missing from this machine, or
+/// which couldn't be opened
+pub fn diagnose_device(diagnostics: &Diagnostics, key: &'static str, named: Option<&str>, resolved: Option<&str>, opened: bool) {
+    match (named, resolved) {
+        (Some(named), None) => {
+            diagnostics.warning(key, format!("{} isn't on this machine, link it to a device that is", named));
+        }
+        (_, Some(resolved)) if !opened => {
+            diagnostics.error(key, format!("Could not open {}", resolved));
+        }
+        _ => {
+            diagnostics.clear(key);
+        }
+    }
+}
+
+pub fn supported_config(device: &cpal::Device, sample_rate: usize) -> Option<cpal::SupportedStreamConfig> {
+    let sample_rate = cpal::SampleRate(sample_rate as u32);
+
+    let config = device.supported_output_configs().ok()?
+        .filter(|range| range.sample_format() == cpal::SampleFormat::F32)
+        .find(|range| range.min_sample_rate() <= sample_rate && sample_rate <= range.max_sample_rate())
+        .map(|range| range.with_sample_rate(sample_rate));
+
+    if config.is_none() {
+        eprintln!("output_device: {} does not support engine sample rate of {} Hz",
+            device.name().unwrap_or_default(), sample_rate.0);
+    }
+
+    config
+}
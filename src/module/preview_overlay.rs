@@ -0,0 +1,159 @@
+use mixlab_codec::ffmpeg::{ColorRange, Colorimetry, PictureSettings};
+use mixlab_protocol::{PreviewOverlayParams, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef};
+use crate::module::ModuleT;
+use crate::video;
+use crate::video::draw::{self, Canvas};
+use crate::video::encode::DynamicScaler;
+
+// studio range luma, as zebra levels are relative to it:
+const LUMA_BLACK: f64 = 16.0;
+const LUMA_WHITE: f64 = 235.0;
+
+const ZEBRA_STRIPE_WIDTH: usize = 4;
+
+// sum of horizontal and vertical luma differences either side of a pixel
+// above which it is considered in focus:
+const PEAKING_THRESHOLD: i32 = 64;
+
+// action safe and title safe, as fractions of picture width and height:
+const SAFE_AREAS: [f64; 2] = [0.93, 0.9];
+const SAFE_AREA_LINE: usize = 2;
+const CENTRE_MARK: usize = 20;
+
+#[derive(Debug)]
+pub struct PreviewOverlay {
+    params: PreviewOverlayParams,
+    // overlays are only drawn into yuv420p frames:
+    scaler: Option<DynamicScaler>,
+    frame_count: usize,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for PreviewOverlay {
+    type Params = PreviewOverlayParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = PreviewOverlay {
+            params,
+            scaler: None,
+            frame_count: 0,
+            inputs: vec![LineType::Video.labeled("Input")],
+            outputs: vec![LineType::Video.labeled("Output")],
+        };
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = match inputs[0].expect_video() {
+            Some(input) => input,
+            None => {
+                *outputs[0].expect_video() = None;
+                return None;
+            }
+        };
+
+        let params = &self.params;
+
+        if !params.safe_area && !params.zebra && !params.focus_peaking {
+            *outputs[0].expect_video() = Some(input.clone());
+            return None;
+        }
+
+        let mut frame = input.data.decoded.clone();
+        let input_settings = frame.picture_settings();
+
+        // yuv420p needs even dimensions, and zebra levels are in limited
+        // range:
+        let target = PictureSettings::yuv420p(input_settings.width & !1, input_settings.height & !1)
+            .with_color(Colorimetry { range: ColorRange::Limited, ..input_settings.color });
+
+        if self.scaler.as_ref().map(|scaler| scaler.output()) != Some(&target) {
+            self.scaler = Some(DynamicScaler::new(target));
+        }
+
+        let mut frame = self.scaler.as_mut().unwrap().scale(&mut frame).clone();
+
+        {
+            let mut canvas = Canvas::new(&mut frame);
+
+            // zebra and peaking both look at the picture as it came in,
+            // not each other's markings
+            let luma = canvas.luma();
+            let width = canvas.width();
+            let height = canvas.height();
+
+            if params.zebra {
+                let threshold = (LUMA_BLACK + params.zebra_level * (LUMA_WHITE - LUMA_BLACK)) as u8;
+                // stripes crawl along by a pixel each frame, as on cameras:
+                let phase = self.frame_count;
+
+                canvas.paint(|x, y| {
+                    luma[y * width + x] >= threshold
+                        && ((x + y + phase) / ZEBRA_STRIPE_WIDTH) % 2 == 0
+                }, draw::BLACK);
+            }
+
+            if params.focus_peaking {
+                canvas.paint(|x, y| {
+                    if x == 0 || y == 0 || x + 1 == width || y + 1 == height {
+                        return false;
+                    }
+
+                    let at = |x: usize, y: usize| luma[y * width + x] as i32;
+
+                    let contrast = (at(x + 1, y) - at(x - 1, y)).abs()
+                        + (at(x, y + 1) - at(x, y - 1)).abs();
+
+                    contrast > PEAKING_THRESHOLD
+                }, draw::RED);
+            }
+
+            if params.safe_area {
+                for fraction in SAFE_AREAS.iter() {
+                    let w = (width as f64 * fraction) as usize;
+                    let h = (height as f64 * fraction) as usize;
+                    canvas.outline((width - w) / 2, (height - h) / 2, w, h, SAFE_AREA_LINE, draw::WHITE);
+                }
+
+                let (cx, cy) = (width / 2, height / 2);
+                canvas.fill(cx.saturating_sub(CENTRE_MARK / 2), cy - SAFE_AREA_LINE / 2, CENTRE_MARK, SAFE_AREA_LINE, draw::WHITE);
+                canvas.fill(cx - SAFE_AREA_LINE / 2, cy.saturating_sub(CENTRE_MARK / 2), SAFE_AREA_LINE, CENTRE_MARK, draw::WHITE);
+            }
+        }
+
+        self.frame_count = self.frame_count.wrapping_add(1);
+
+        *outputs[0].expect_video() = Some(engine::VideoFrame {
+            data: video::Frame {
+                decoded: frame,
+                duration_hint: input.data.duration_hint,
+            },
+            tick_offset: input.tick_offset,
+        });
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
@@ -0,0 +1,261 @@
+use std::fs;
+use std::io;
+use std::iter;
+use std::path::Path;
+use std::time::Instant;
+
+use mixlab_codec::ffmpeg::PictureSettings;
+use mixlab_mux::mp4::Chapter;
+use mixlab_protocol::{RecorderParams, RecorderIndication, LineType, Terminal};
+use mixlab_util::time::MediaTime;
+
+use crate::engine::{self, EngineConfig, InputRef, OutputRef};
+use crate::module::ModuleT;
+use crate::util;
+use crate::video::record::{Recording, SendError};
+
+const RECORD_WIDTH: usize = 1280;
+const RECORD_HEIGHT: usize = 720;
+
+// number of sources that can be recorded to files of their own alongside the
+// program:
+const ISO_CHANNELS: usize = 4;
+
+// inputs preceding the iso inputs:
+const PROGRAM_VIDEO: usize = 0;
+const PROGRAM_AUDIO: usize = 1;
+
+#[derive(Debug)]
+pub struct Recorder {
+    ctx: engine::ModuleCtx<Self>,
+    params: RecorderParams,
+    active: Option<ActiveRecording>,
+    last_lag: Option<Instant>,
+    indication: RecorderIndication,
+    inputs: Vec<Terminal>,
+}
+
+// every file of a take is timestamped from the same epoch, so that they line
+// up with each other in an editor
+#[derive(Debug)]
+struct ActiveRecording {
+    take: String,
+    program: Recording,
+    // iso files are created on the first tick for each iso input connected
+    // at the time:
+    isos: Option<Vec<Option<Recording>>>,
+    epoch: Option<MediaTime>,
+    // timestamp of the latest tick sent, where markers are placed:
+    position: MediaTime,
+    // every marker so far, for iso files opened after some were placed:
+    chapters: Vec<Chapter>,
+    failed: bool,
+}
+
+impl ModuleT for Recorder {
+    type Params = RecorderParams;
+    type Indication = RecorderIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let mut module = Recorder {
+            ctx,
+            params: params.clone(),
+            active: None,
+            last_lag: None,
+            indication: RecorderIndication { take: None, error: false, lag: None },
+            inputs: vec![
+                LineType::Video.labeled("Video"),
+                LineType::Stereo.labeled("Audio"),
+            ].into_iter()
+                .chain((0..ISO_CHANNELS).map(|i| LineType::Video.labeled(&format!("ISO {}", i + 1))))
+                .collect(),
+        };
+
+        // a project saved while recording starts a new recording when opened.
+        // its marker count is as saved, only later bumps place markers:
+        if params.recording && !module.ctx.dry_run() {
+            module.active = Some(module.start());
+        }
+
+        module.indicate(Instant::now());
+
+        let indication = module.indication.clone();
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        if params.recording != self.params.recording {
+            self.active = if params.recording && !self.ctx.dry_run() {
+                Some(self.start())
+            } else {
+                // dropping the recording finishes its files
+                None
+            };
+        }
+
+        if params.marker != self.params.marker {
+            if let Some(active) = &mut self.active {
+                active.add_marker();
+            }
+        }
+
+        self.params = params;
+        self.indicate(Instant::now())
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], _: &mut [OutputRef]) -> Option<Self::Indication> {
+        let now = Instant::now();
+
+        if let Some(active) = &mut self.active {
+            let audio = inputs[PROGRAM_AUDIO].expect_stereo();
+
+            let timestamp = self.ctx.config().media_time(t);
+            let epoch = *active.epoch.get_or_insert(timestamp);
+            let timestamp = timestamp.remove_epoch(epoch);
+            active.position = timestamp;
+
+            let iso_inputs = &inputs[PROGRAM_AUDIO + 1..];
+
+            if active.isos.is_none() {
+                let project = self.ctx.project();
+                let take_dir = project.recordings_dir().join(&active.take);
+                let config = self.ctx.config();
+                let chapters = &active.chapters;
+
+                active.isos = Some(iso_inputs.iter().enumerate()
+                    .map(|(i, input)| if input.connected() {
+                        let path = take_dir.join(format!("iso-{}.mp4", i + 1));
+                        let project = project.clone();
+                        let recording = Recording::start(path, record_picture(config), config.sample_rate,
+                            move |path| project.recording_finished(path));
+
+                        for chapter in chapters {
+                            recording.add_chapter(chapter.clone());
+                        }
+
+                        Some(recording)
+                    } else {
+                        None
+                    })
+                    .collect());
+            }
+
+            let isos = active.isos.as_mut().unwrap();
+
+            // iso files carry the program audio:
+            let recordings = iter::once((&mut active.program, &inputs[PROGRAM_VIDEO]))
+                .chain(isos.iter_mut()
+                    .zip(iso_inputs)
+                    .filter_map(|(recording, input)| recording.as_mut().map(|recording| (recording, input))));
+
+            for (recording, input) in recordings {
+                match recording.send(timestamp, audio, input.expect_video()) {
+                    Ok(()) => {}
+                    Err(SendError::Lagged) => { self.last_lag = Some(now); }
+                    Err(SendError::Failed) => { active.failed = true; }
+                }
+            }
+        }
+
+        self.indicate(now)
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &[]
+    }
+}
+
+impl Recorder {
+    fn start(&self) -> ActiveRecording {
+        let project = self.ctx.project();
+
+        let take = claim_take(&project.recordings_dir()).unwrap_or_else(|e| {
+            // the recording fails to start and says so in its indication:
+            eprintln!("recorder: could not create take directory: {:?}", e);
+            format!("take-{}", util::unix_time())
+        });
+
+        let path = project.recordings_dir().join(&take).join("program.mp4");
+
+        let config = self.ctx.config();
+        let program = Recording::start(path, record_picture(config), config.sample_rate,
+            move |path| project.recording_finished(path));
+
+        ActiveRecording {
+            take,
+            program,
+            isos: None,
+            epoch: None,
+            position: MediaTime::zero(),
+            chapters: Vec::new(),
+            failed: false,
+        }
+    }
+
+    fn indicate(&mut self, now: Instant) -> Option<RecorderIndication> {
+        let indication = RecorderIndication {
+            take: self.active.as_ref().map(|active| active.take.clone()),
+            error: self.active.as_ref().map(|active| active.failed).unwrap_or(false),
+            lag: util::temporal_warning(self.last_lag.map(|time| now - time)),
+        };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+}
+
+impl ActiveRecording {
+    fn add_marker(&mut self) {
+        let chapter = Chapter {
+            start: self.position,
+            title: format!("Marker {}", self.chapters.len() + 1),
+        };
+
+        let isos = self.isos.iter().flatten().flatten();
+
+        for recording in iter::once(&self.program).chain(isos) {
+            recording.add_chapter(chapter.clone());
+        }
+
+        self.chapters.push(chapter);
+    }
+}
+
+// takes started within the same second are told apart by a suffix. the
+// directory is created here, so that no two takes ever share one
+fn claim_take(recordings: &Path) -> Result<String, io::Error> {
+    fs::create_dir_all(recordings)?;
+
+    let time = util::unix_time();
+    let mut seq = 1;
+
+    loop {
+        let take = match seq {
+            1 => format!("take-{}", time),
+            _ => format!("take-{}-{}", time, seq),
+        };
+
+        match fs::create_dir(recordings.join(&take)) {
+            Ok(()) => return Ok(take),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => { seq += 1; }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn record_picture(config: EngineConfig) -> PictureSettings {
+    config.picture(RECORD_WIDTH, RECORD_HEIGHT)
+}
@@ -0,0 +1,360 @@
+use std::cmp;
+use std::collections::VecDeque;
+
+use tokio::task;
+
+use mixlab_protocol::{ReplayBufferParams, ReplayBufferIndication, LineType, Terminal, REPLAY_BUFFER_MAX_SECONDS};
+use mixlab_util::time::{MediaTime, MediaDuration};
+
+use crate::engine::{self, EngineConfig, InputRef, OutputRef, Sample, CHANNELS};
+use crate::module::ModuleT;
+use crate::project::ProjectBaseRef;
+use crate::project::media::{MediaUpload, UploadInfo};
+use crate::util;
+use crate::video;
+use crate::video::record::{self, Tick};
+
+const SAVE_WIDTH: usize = 1280;
+const SAVE_HEIGHT: usize = 720;
+
+// replays are split into ticks of this many samples for encoding:
+const SAVE_BLOCK_SIZE: usize = 1024;
+
+// slowest playback allowed, a speed of zero would never finish:
+const MIN_SPEED: f64 = 0.1;
+
+// history is kept as decoded frames so that a replay can start playing
+// straight away, which costs a lot of memory for long buffers
+#[derive(Debug)]
+pub struct ReplayBuffer {
+    ctx: engine::ModuleCtx<Self>,
+    params: ReplayBufferParams,
+    history: History,
+    playback: Option<Playback>,
+    saving: usize,
+    save_error: bool,
+    indication: ReplayBufferIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+#[derive(Debug)]
+pub enum ReplayBufferEvent {
+    Saved(Result<(), ()>),
+}
+
+impl ModuleT for ReplayBuffer {
+    type Params = ReplayBufferParams;
+    type Indication = ReplayBufferIndication;
+    type Event = ReplayBufferEvent;
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let indication = ReplayBufferIndication {
+            playing: false,
+            saving: false,
+            save_error: false,
+        };
+
+        let module = ReplayBuffer {
+            ctx,
+            params,
+            history: History::default(),
+            playback: None,
+            saving: 0,
+            save_error: false,
+            indication: indication.clone(),
+            inputs: vec![
+                LineType::Video.labeled("Video"),
+                LineType::Stereo.labeled("Audio"),
+            ],
+            outputs: vec![
+                LineType::Video.labeled("Replay"),
+                LineType::Stereo.labeled("Replay Audio"),
+            ],
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        let replay = params.replay != self.params.replay;
+
+        self.params = params;
+
+        if replay {
+            self.replay();
+        }
+
+        self.indicate()
+    }
+
+    fn receive_event(&mut self, event: ReplayBufferEvent) {
+        match event {
+            ReplayBufferEvent::Saved(result) => {
+                self.saving -= 1;
+                self.save_error = result.is_err();
+            }
+        }
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let sample_rate = self.ctx.config().sample_rate;
+
+        let seconds = f64::max(0.0, f64::min(REPLAY_BUFFER_MAX_SECONDS, self.params.seconds));
+        let max_len = (seconds * sample_rate as f64) as usize;
+
+        self.history.push(t, inputs[1].expect_stereo(), inputs[0].expect_video(), sample_rate, max_len);
+
+        let (video_out, audio_out) = match outputs {
+            [video, audio] => (video.expect_video(), audio.expect_stereo()),
+            _ => unreachable!(),
+        };
+
+        let playing = match &mut self.playback {
+            Some(playback) => {
+                let speed = f64::max(MIN_SPEED, self.params.speed);
+                playback.play(speed, sample_rate, video_out, audio_out)
+            }
+            None => {
+                *video_out = None;
+                util::zero(audio_out);
+                false
+            }
+        };
+
+        if !playing {
+            self.playback = None;
+        }
+
+        self.indicate()
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+impl ReplayBuffer {
+    fn replay(&mut self) {
+        let clip = self.history.clip();
+
+        if clip.len() == 0 {
+            return;
+        }
+
+        if self.params.save {
+            self.saving += 1;
+
+            let base = self.ctx.project();
+            let config = self.ctx.config();
+            let clip = clip.clone();
+
+            self.ctx.spawn_async(async move {
+                ReplayBufferEvent::Saved(save_clip(base, clip, config).await)
+            });
+        }
+
+        self.playback = Some(Playback {
+            clip,
+            position: 0.0,
+            next_video: 0,
+        });
+    }
+
+    fn indicate(&mut self) -> Option<ReplayBufferIndication> {
+        let indication = ReplayBufferIndication {
+            playing: self.playback.is_some(),
+            saving: self.saving > 0,
+            save_error: self.save_error,
+        };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct History {
+    // interleaved stereo:
+    audio: VecDeque<Sample>,
+    // engine time just past the newest buffered audio, in samples:
+    end: u64,
+    // each frame with the engine time it starts at:
+    video: VecDeque<(u64, video::Frame)>,
+}
+
+impl History {
+    fn push(&mut self, t: u64, audio: &[Sample], video: Option<&engine::VideoFrame>, sample_rate: usize, max_len: usize) {
+        self.audio.extend(audio);
+        self.end = t + (audio.len() / CHANNELS) as u64;
+
+        if let Some(frame) = video {
+            let offset = cmp::max(0, frame.tick_offset.round_to_base(sample_rate as i64)) as u64;
+            self.video.push_back((t + offset, frame.data.clone()));
+        }
+
+        let excess = self.audio.len().saturating_sub(max_len * CHANNELS);
+        self.audio.drain(0..excess);
+
+        // keep the frame still showing at the start of the buffer:
+        let start = self.start();
+
+        while self.video.len() > 1 && self.video[1].0 <= start {
+            self.video.pop_front();
+        }
+    }
+
+    fn start(&self) -> u64 {
+        self.end - (self.audio.len() / CHANNELS) as u64
+    }
+
+    fn clip(&self) -> Clip {
+        let start = self.start();
+
+        Clip {
+            audio: self.audio.iter().copied().collect(),
+            video: self.video.iter()
+                .map(|(at, frame)| (at.saturating_sub(start), frame.clone()))
+                .collect(),
+        }
+    }
+}
+
+// a captured stretch of history, with frame times in samples from its start
+#[derive(Debug, Clone)]
+struct Clip {
+    audio: Vec<Sample>,
+    video: Vec<(u64, video::Frame)>,
+}
+
+impl Clip {
+    fn len(&self) -> usize {
+        self.audio.len() / CHANNELS
+    }
+
+    // silence past the end of the clip:
+    fn sample(&self, index: usize, channel: usize) -> Sample {
+        self.audio.get(index * CHANNELS + channel).copied().unwrap_or(0.0)
+    }
+
+    // splits the clip into ticks for encoding at its original speed
+    fn ticks(&self, sample_rate: usize) -> impl Iterator<Item = Tick> + '_ {
+        let mut next_video = 0;
+
+        (0..self.len()).step_by(SAVE_BLOCK_SIZE).map(move |start| {
+            let end = cmp::min(start + SAVE_BLOCK_SIZE, self.len());
+
+            // at most one frame per tick, the latest one due within it:
+            let mut video = None;
+
+            while let Some((at, frame)) = self.video.get(next_video) {
+                if *at >= end as u64 {
+                    break;
+                }
+
+                video = Some(engine::VideoFrame {
+                    data: frame.clone(),
+                    tick_offset: MediaDuration::new(at.saturating_sub(start as u64) as i64, sample_rate as i64),
+                });
+
+                next_video += 1;
+            }
+
+            Tick {
+                timestamp: MediaTime::new(start as i64, sample_rate as i64),
+                audio: self.audio[start * CHANNELS..end * CHANNELS].to_vec(),
+                video,
+            }
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Playback {
+    clip: Clip,
+    // in clip samples, fractional when slowed down:
+    position: f64,
+    next_video: usize,
+}
+
+impl Playback {
+    // returns false once the clip has played out
+    fn play(&mut self, speed: f64, sample_rate: usize, video_out: &mut Option<engine::VideoFrame>, audio_out: &mut [Sample]) -> bool {
+        let tick_start = self.position;
+
+        // linear interpolation, so slow motion lowers the pitch as tape would:
+        for frame in audio_out.chunks_mut(CHANNELS) {
+            let index = self.position as usize;
+            let frac = (self.position - index as f64) as Sample;
+
+            for (channel, out) in frame.iter_mut().enumerate() {
+                let a = self.clip.sample(index, channel);
+                let b = self.clip.sample(index + 1, channel);
+                *out = a + (b - a) * frac;
+            }
+
+            self.position += speed;
+        }
+
+        *video_out = None;
+
+        while let Some((at, frame)) = self.clip.video.get(self.next_video) {
+            if *at as f64 >= self.position {
+                break;
+            }
+
+            // offsets and durations stretch as playback slows:
+            let tick_offset = (f64::max(0.0, *at as f64 - tick_start) / speed) as i64;
+            let duration = (frame.duration_hint.round_to_base(sample_rate as i64) as f64 / speed) as i64;
+
+            *video_out = Some(engine::VideoFrame {
+                data: video::Frame {
+                    decoded: frame.decoded.clone(),
+                    duration_hint: MediaDuration::new(duration, sample_rate as i64),
+                },
+                tick_offset: MediaDuration::new(tick_offset, sample_rate as i64),
+            });
+
+            self.next_video += 1;
+        }
+
+        (self.position as usize) < self.clip.len()
+    }
+}
+
+async fn save_clip(base: ProjectBaseRef, clip: Clip, config: EngineConfig) -> Result<(), ()> {
+    let sample_rate = config.sample_rate;
+
+    let mp4 = task::spawn_blocking(move || {
+        let mut mp4 = Vec::new();
+        let picture = config.picture(SAVE_WIDTH, SAVE_HEIGHT);
+        record::write_mp4(&mut mp4, picture, sample_rate, clip.ticks(sample_rate)).map(|_| mp4)
+    }).await.expect("encode replay");
+
+    let mp4 = mp4.map_err(|e| eprintln!("replay_buffer: could not encode replay: {:?}", e))?;
+
+    let info = UploadInfo {
+        name: format!("replay-{}.mp4", util::unix_time()),
+        kind: "video/mp4".to_owned(),
+    };
+
+    let result = async {
+        let mut upload = MediaUpload::new(base, info).await?;
+        upload.receive_bytes(&mp4).await?;
+        upload.finalize().await
+    }.await;
+
+    result.map_err(|e| eprintln!("replay_buffer: could not save replay: {:?}", e))
+}
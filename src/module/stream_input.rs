@@ -1,26 +1,42 @@
 use std::cmp;
+use std::collections::VecDeque;
 
-use mixlab_protocol::{StreamInputParams, LineType, Terminal, StreamProtocol};
+use mixlab_protocol::{StreamInputParams, LineType, Terminal, StreamProtocol, ResampleQuality};
 use mixlab_util::time::{MediaTime, MediaDuration};
 
-use crate::engine::{self, InputRef, OutputRef, Sample, VideoFrame, SAMPLE_RATE};
+use crate::engine::{self, InputRef, OutputRef, Sample, VideoFrame, ClockRef, CHANNELS};
 use crate::icecast;
 use crate::module::ModuleT;
+use crate::module::media_source::{OpenMedia, StandIn};
+use crate::resample::Resampler;
 use crate::rtmp;
-use crate::source::{SourceRecv, SourceId, Frame, AudioData, VideoData};
+use crate::source::{SourceRecv, SourceId, Frame, VideoData};
 use crate::util;
+use crate::video::deinterlace::Deinterlacer;
 
 #[derive(Debug)]
 pub struct StreamInput {
+    ctx: engine::ModuleCtx<Self>,
     params: StreamInputParams,
+    sample_rate: usize,
     recv: Option<SourceRecv>,
     source: Option<SourceTiming>,
-    audio_frame: Option<Frame<AudioData>>,
-    video_frame: Option<Frame<VideoData>>,
+    resampler: Option<Resampler>,
+    // resampled audio at engine rate not yet written to output:
+    audio_pending: Vec<Sample>,
+    // received frames not yet due, more than one when bob deinterlacing:
+    video_pending: VecDeque<Frame<VideoData>>,
+    deinterlacer: Deinterlacer,
+    stand_in: StandIn,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
 }
 
+#[derive(Debug)]
+pub enum StreamInputEvent {
+    SetStandIn(Option<OpenMedia>),
+}
+
 #[derive(Debug)]
 struct SourceTiming {
     id: SourceId,
@@ -30,17 +46,23 @@ struct SourceTiming {
 impl ModuleT for StreamInput {
     type Params = StreamInputParams;
     type Indication = ();
-    type Event = ();
+    type Event = StreamInputEvent;
 
-    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
-        let recv = listen_mountpoint(&params);
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        // a dry run mustn't take the mountpoint from a live engine:
+        let recv = if ctx.dry_run() { None } else { listen_mountpoint(&params) };
 
         let module = StreamInput {
+            sample_rate: ctx.config().sample_rate,
+            ctx,
             params,
             recv,
             source: None,
-            audio_frame: None,
-            video_frame: None,
+            resampler: None,
+            audio_pending: Vec::new(),
+            video_pending: VecDeque::new(),
+            deinterlacer: Deinterlacer::default(),
+            stand_in: StandIn::default(),
             inputs: vec![],
             outputs: vec![
                 LineType::Video.labeled("Video"),
@@ -59,7 +81,9 @@ impl ModuleT for StreamInput {
         let current_mountpoint = self.recv.as_ref().map(|recv| recv.channel_name());
         let new_mountpoint = new_params.mountpoint.as_ref().map(String::as_str);
 
-        if current_mountpoint != new_mountpoint || self.params.protocol != new_params.protocol {
+        let changed = current_mountpoint != new_mountpoint || self.params.protocol != new_params.protocol;
+
+        if changed && !self.ctx.dry_run() {
             // TODO - tell the user about this one too
             self.recv = listen_mountpoint(&new_params);
         }
@@ -69,60 +93,94 @@ impl ModuleT for StreamInput {
         None
     }
 
+    fn receive_event(&mut self, event: StreamInputEvent) {
+        match event {
+            StreamInputEvent::SetStandIn(media) => {
+                self.stand_in.set_media(&self.ctx, media);
+            }
+        }
+    }
+
     fn run_tick(&mut self, engine_time: u64, _: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
-        let engine_time = MediaTime::new(engine_time as i64, SAMPLE_RATE as i64);
+        let engine_time = MediaTime::new(engine_time as i64, self.sample_rate as i64);
 
-        let (video_out, mut audio_out) = match outputs {
+        let (video_out, audio_out) = match outputs {
             [video, audio] => (video.expect_video(), audio.expect_stereo()),
             _ => unimplemented!(),
         };
 
-        let tick_duration = MediaDuration::new(audio_out.len() as i64 / 2, SAMPLE_RATE as i64);
+        let tick_duration = MediaDuration::new((audio_out.len() / CHANNELS) as i64, self.sample_rate as i64);
 
-        let video_frame = self.video_frame.take()
-            .or_else(|| {
-                self.recv.as_mut()
-                    .and_then(|recv| recv.read_video())
-            });
+        self.stand_in.sync(&self.ctx, self.params.stand_in, StreamInputEvent::SetStandIn);
 
-        let existing_source_id = self.source.as_ref().map(|src| src.id);
+        if self.stand_in.active() {
+            *video_out = self.stand_in.next_frame(engine_time, engine_time + tick_duration);
+            self.stand_in.read_audio(self.sample_rate, audio_out);
 
-        // process audio frames. we may have to consume multiple input audio
-        // frames to fill the output buffer
-        while audio_out.len() > 0 {
-            let audio_frame = self.audio_frame.take()
-                .or_else(|| {
-                    self.recv.as_mut()
-                        .and_then(|recv| recv.read_audio())
-                    });
+            // the live stream is still read from, or the sender would find
+            // the buffers full and drop the connection. its timing starts
+            // over once rehearsal ends:
+            if let Some(recv) = &mut self.recv {
+                while recv.read_video().is_some() {}
+                while recv.read_audio().is_some() {}
+            }
 
-            if let Some(mut frame) = audio_frame {
-                if existing_source_id != Some(frame.source_id) {
-                    // source changed
-                    self.source = Some(SourceTiming {
-                        id: frame.source_id,
-                        epoch: engine_time.remove_epoch(frame.source_time),
-                    });
-                }
+            self.source = None;
+            self.audio_pending.clear();
+            self.video_pending.clear();
 
-                let len = cmp::min(audio_out.len(), frame.data.len());
+            return None;
+        }
+
+        if self.video_pending.is_empty() {
+            if let Some(frame) = self.recv.as_mut().and_then(|recv| recv.read_video()) {
+                let fields = self.deinterlacer.process(self.params.deinterlace, frame.data);
 
-                for i in 0..len {
-                    audio_out[i] = convert_sample(frame.data[i]);
+                for (offset, data) in fields {
+                    self.video_pending.push_back(Frame {
+                        source_id: frame.source_id,
+                        source_time: frame.source_time + offset,
+                        data,
+                    });
                 }
+            }
+        }
 
-                audio_out = &mut audio_out[len..];
+        let video_frame = self.video_pending.pop_front();
 
-                if len < frame.data.len() {
-                    frame.data.drain(0..len);
-                    self.audio_frame = Some(frame);
-                }
-            } else {
-                util::zero(audio_out);
-                break;
+        let existing_source_id = self.source.as_ref().map(|src| src.id);
+
+        // process audio frames. we may have to consume multiple input audio
+        // frames to fill the output buffer
+        while self.audio_pending.len() < audio_out.len() {
+            let frame = match self.recv.as_mut().and_then(|recv| recv.read_audio()) {
+                Some(frame) => frame,
+                None => break,
+            };
+
+            if existing_source_id != Some(frame.source_id) {
+                // source changed
+                self.source = Some(SourceTiming {
+                    id: frame.source_id,
+                    epoch: engine_time.remove_epoch(frame.source_time),
+                });
             }
+
+            let resampler = resampler_for(&mut self.resampler, self.params.resample_quality, frame.data.sample_rate, self.sample_rate);
+
+            let samples = frame.data.samples.iter()
+                .copied()
+                .map(convert_sample)
+                .collect::<Vec<_>>();
+
+            resampler.process(&samples, &mut self.audio_pending);
         }
 
+        let len = cmp::min(audio_out.len(), self.audio_pending.len());
+        audio_out[0..len].copy_from_slice(&self.audio_pending[0..len]);
+        util::zero(&mut audio_out[len..]);
+        self.audio_pending.drain(0..len);
+
         *video_out = video_frame.and_then(|frame| {
             let tick_offset = self.source.as_ref()
                 .map(|source| {
@@ -133,7 +191,7 @@ impl ModuleT for StreamInput {
 
             if tick_offset > tick_duration {
                 // frame is not due for this tick, put it back
-                self.video_frame = Some(frame);
+                self.video_pending.push_front(frame);
                 None
             } else {
                 Some(VideoFrame {
@@ -146,6 +204,10 @@ impl ModuleT for StreamInput {
         None
     }
 
+    fn clock(&self) -> Option<ClockRef> {
+        self.recv.as_ref().map(|recv| recv.clock().clone())
+    }
+
     fn inputs(&self) -> &[Terminal] {
         &self.inputs
     }
@@ -164,6 +226,19 @@ fn listen_mountpoint(params: &StreamInputParams) -> Option<SourceRecv> {
     }
 }
 
+fn resampler_for(resampler: &mut Option<Resampler>, quality: ResampleQuality, input_rate: usize, output_rate: usize) -> &mut Resampler {
+    let stale = match resampler {
+        Some(resampler) => resampler.quality() != quality || resampler.input_rate() != input_rate,
+        None => true,
+    };
+
+    if stale {
+        *resampler = Some(Resampler::new(quality, CHANNELS, input_rate, output_rate));
+    }
+
+    resampler.as_mut().unwrap()
+}
+
 fn convert_sample(sample: i16) -> Sample {
     // i16::min_value is a greater absolute distance away from 0 than max_value
     // divide by it rather than max_value to prevent clipping
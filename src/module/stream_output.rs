@@ -1,5 +1,9 @@
+use std::cmp;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::thread;
+use std::time::{Duration, Instant};
 
 use bytes::BytesMut;
 use derive_more::From;
@@ -9,26 +13,40 @@ use tokio::net::TcpStream;
 use tokio::runtime;
 use tokio::sync::oneshot;
 
-use mixlab_codec::ffmpeg::PictureSettings;
+use mixlab_codec::ffmpeg::{Colorimetry, PictureSettings};
 use mixlab_protocol::{StreamOutputParams, LineType, Terminal, StreamOutputIndication, StreamOutputLiveStatus};
 use mixlab_util::time::MediaTime;
 
-use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE};
+use crate::engine::{self, InputRef, OutputRef, Diagnostics};
 use crate::module::ModuleT;
 use crate::rtmp;
 use crate::rtmp::packet::{AudioPacket, VideoPacket, VideoFrameType, VideoPacketType};
-use crate::rtmp::client::{self, StreamMetadata, PublishInfo, PublishClient};
+use crate::rtmp::client::{self, StreamMetadata, PublishInfo, PublishClient, PublishError};
 use crate::video::encode::{EncodeStream, AudioCtx, AudioParams, VideoCtx, VideoParams, StreamSegment, Profile};
 
 const OUTPUT_WIDTH: usize = 1120;
 const OUTPUT_HEIGHT: usize = 700;
 
+// reconnect delay doubles with each failed attempt up to this limit:
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+const BITRATE_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Debug)]
 pub struct StreamOutput {
     params: StreamOutputParams,
+    sample_rate: usize,
+    color: Colorimetry,
     connection: Connection,
+    // which of the primary and backup ingests is in use, see fail_over:
+    backup: bool,
+    failovers: u32,
     inputs: Vec<Terminal>,
     indication: StreamOutputIndication,
+    bitrate: BitrateMeter,
+    diagnostics: Diagnostics,
+    dry_run: bool,
 }
 
 impl ModuleT for StreamOutput {
@@ -36,20 +54,30 @@ impl ModuleT for StreamOutput {
     type Indication = StreamOutputIndication;
     type Event = ();
 
-    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
         let indic = StreamOutputIndication {
             live: StreamOutputLiveStatus::Offline,
             error: false,
+            kbps: None,
+            backup: false,
+            failovers: 0,
         };
 
         let module = StreamOutput {
             params,
+            sample_rate: ctx.config().sample_rate,
+            color: ctx.config().colorimetry(),
             connection: Connection::Offline,
+            backup: false,
+            failovers: 0,
             inputs: vec![
                 LineType::Video.labeled("Video"),
                 LineType::Stereo.labeled("Audio"),
             ],
             indication: indic.clone(),
+            bitrate: BitrateMeter::new(),
+            diagnostics: ctx.diagnostics(),
+            dry_run: ctx.dry_run(),
         };
 
         (module, indic)
@@ -68,11 +96,9 @@ impl ModuleT for StreamOutput {
         if self.connection.is_active() {
             if new_params.disconnect_seq == new_params.seq {
                 self.connection = Connection::Offline;
-
-                Some(StreamOutputIndication {
-                    live: StreamOutputLiveStatus::Offline,
-                    error: false,
-                })
+                self.diagnostics.clear("connection");
+                self.diagnostics.clear("ingest");
+                self.indicate()
             } else {
                 // cannot change params on a live stream output
                 None
@@ -80,24 +106,24 @@ impl ModuleT for StreamOutput {
         } else {
             self.params = new_params;
 
-            if self.params.connect_seq == self.params.seq {
-                // connect with current details
-                let (completion_tx, completion_rx) = oneshot::channel();
+            if self.params.connect_seq == self.params.seq && !self.dry_run {
+                self.backup = false;
+                self.failovers = 0;
+                self.diagnostics.clear("ingest");
 
-                // spawn task to connect to RTMP
-                tokio::spawn({
-                    let params = self.params.clone();
-                    async move {
-                        let _ = completion_tx.send(connect_rtmp(params.clone()).await);
+                // a misconfigured backup is caught now, rather than once
+                // the primary has failed and it's needed:
+                if self.has_backup() {
+                    if let Err(e) = parse_ingest(&self.params.backup_rtmp_url) {
+                        self.diagnostics.error("connection", format!("Backup ingest is misconfigured: {:?}", e));
+                        self.connection = Connection::Failed(Some(e));
+                        return self.indicate();
                     }
-                });
-
-                self.connection = Connection::Connecting(completion_rx);
+                }
 
-                Some(StreamOutputIndication {
-                    live: StreamOutputLiveStatus::Connecting,
-                    error: false,
-                })
+                // connect with current details
+                self.connection = self.connect(0);
+                self.indicate()
             } else {
                 None
             }
@@ -110,7 +136,7 @@ impl ModuleT for StreamOutput {
             _ => unreachable!()
         };
 
-        let timestamp = MediaTime::new(engine_time as i64, SAMPLE_RATE as i64);
+        let timestamp = MediaTime::new(engine_time as i64, self.sample_rate as i64);
 
         let live = match &mut self.connection {
             Connection::Offline => {
@@ -119,12 +145,29 @@ impl ModuleT for StreamOutput {
             Connection::Failed(_) => {
                 return self.indicate();
             }
-            Connection::Connecting(completion) => {
+            Connection::Reconnecting { at, attempt } => {
+                let (at, attempt) = (*at, *attempt);
+
+                if Instant::now() >= at {
+                    self.connection = self.connect(attempt);
+                }
+
+                return self.indicate();
+            }
+            Connection::Connecting(completion, attempt) => {
                 use oneshot::error::TryRecvError;
 
+                let attempt = *attempt;
+
                 match completion.try_recv() {
                     Ok(Ok(publish)) => {
-                        self.connection = Connection::Live(LiveOutputTask::start(timestamp, publish));
+                        self.connection = Connection::Live(LiveOutputTask::start(timestamp, publish, self.sample_rate, self.color));
+                        self.bitrate = BitrateMeter::new();
+                        self.diagnostics.clear("connection");
+
+                        if !self.backup {
+                            self.diagnostics.clear("ingest");
+                        }
 
                         match &mut self.connection {
                             Connection::Live(live) => live,
@@ -134,7 +177,18 @@ impl ModuleT for StreamOutput {
                     Ok(Err(e)) => {
                         // failed to connect
                         eprintln!("StreamOutput failed to connect: {:?}", e);
-                        self.connection = Connection::Failed(Some(e));
+
+                        self.connection = if e.is_retryable() && self.has_backup() {
+                            self.diagnostics.warning("connection", format!("Could not connect, failing over: {:?}", e));
+                            self.fail_over(attempt)
+                        } else if e.is_retryable() {
+                            self.diagnostics.warning("connection", format!("Could not connect, retrying: {:?}", e));
+                            Connection::reconnect(attempt + 1)
+                        } else {
+                            self.diagnostics.error("connection", format!("Could not connect: {:?}", e));
+                            Connection::Failed(Some(e))
+                        };
+
                         return self.indicate();
                     }
                     Err(TryRecvError::Empty) => {
@@ -158,9 +212,20 @@ impl ModuleT for StreamOutput {
         };
 
         match live.send(msg) {
-            Ok(()) => {}
+            Ok(()) => {
+                self.bitrate.sample(live.bytes_sent());
+            }
             Err(()) => {
-                self.connection = Connection::Failed(None);
+                // live output thread exited, the connection has dropped
+                if self.has_backup() {
+                    eprintln!("StreamOutput connection lost, failing over");
+                    self.diagnostics.warning("connection", "Connection lost, failing over");
+                    self.connection = self.fail_over(0);
+                } else {
+                    eprintln!("StreamOutput connection lost, reconnecting");
+                    self.diagnostics.warning("connection", "Connection lost, reconnecting");
+                    self.connection = Connection::reconnect(0);
+                }
             }
         }
 
@@ -185,8 +250,22 @@ enum RtmpConnectError {
     Client(client::Error),
 }
 
-async fn connect_rtmp(params: StreamOutputParams) -> Result<PublishClient, RtmpConnectError> {
-    let url = url::Url::parse(&params.rtmp_url)?;
+impl RtmpConnectError {
+    // errors in the stream configuration will not fix themselves on retry:
+    fn is_retryable(&self) -> bool {
+        match self {
+            RtmpConnectError::Url(_) |
+            RtmpConnectError::UnsupportedScheme |
+            RtmpConnectError::MissingHost => false,
+            RtmpConnectError::Io(_) |
+            RtmpConnectError::Client(_) => true,
+        }
+    }
+}
+
+// host, port and app name to publish to for an ingest url
+fn parse_ingest(rtmp_url: &str) -> Result<(String, u16, String), RtmpConnectError> {
+    let url = url::Url::parse(rtmp_url)?;
 
     if url.scheme() != "rtmp" {
         return Err(RtmpConnectError::UnsupportedScheme);
@@ -200,14 +279,20 @@ async fn connect_rtmp(params: StreamOutputParams) -> Result<PublishClient, RtmpC
     assert!(path.chars().nth(0) == Some('/'));
     let app_name = &path[1..];
 
-    let conn = TcpStream::connect((hostname, port)).await?;
+    Ok((hostname.to_owned(), port, app_name.to_owned()))
+}
+
+async fn connect_rtmp(rtmp_url: String, stream_key: String, sample_rate: usize) -> Result<PublishClient, RtmpConnectError> {
+    let (hostname, port, app_name) = parse_ingest(&rtmp_url)?;
+
+    let conn = TcpStream::connect((hostname.as_str(), port)).await?;
     conn.set_nodelay(true)?;
 
     let client = client::start(conn)
         .await?
         .publish(PublishInfo {
-            app_name: app_name.to_owned(),
-            stream_key: params.rtmp_stream_key.to_owned(),
+            app_name,
+            stream_key,
             meta: StreamMetadata {
                 video_width: Some(OUTPUT_WIDTH as u32),
                 video_height: Some(OUTPUT_HEIGHT as u32),
@@ -216,7 +301,7 @@ async fn connect_rtmp(params: StreamOutputParams) -> Result<PublishClient, RtmpC
                 video_bitrate_kbps: None, //Some(2500),
                 audio_codec: Some("aac1".to_owned()),
                 audio_bitrate_kbps: Some(160),
-                audio_sample_rate: Some(SAMPLE_RATE as u32),
+                audio_sample_rate: Some(sample_rate as u32),
                 audio_channels: Some(2),
                 audio_is_stereo: Some(true),
                 encoder: Some("Mixlab".to_owned()),
@@ -228,23 +313,97 @@ async fn connect_rtmp(params: StreamOutputParams) -> Result<PublishClient, RtmpC
 }
 
 impl StreamOutput {
+    fn connect(&self, attempt: u32) -> Connection {
+        let (completion_tx, completion_rx) = oneshot::channel();
+
+        // spawn task to connect to RTMP
+        tokio::spawn({
+            let (rtmp_url, stream_key) = self.ingest();
+            let sample_rate = self.sample_rate;
+            async move {
+                let _ = completion_tx.send(connect_rtmp(rtmp_url, stream_key, sample_rate).await);
+            }
+        });
+
+        Connection::Connecting(completion_rx, attempt)
+    }
+
+    fn has_backup(&self) -> bool {
+        !self.params.backup_rtmp_url.trim().is_empty()
+    }
+
+    // url and stream key of the ingest in use
+    fn ingest(&self) -> (String, String) {
+        if self.backup {
+            (self.params.backup_rtmp_url.clone(), self.params.backup_rtmp_stream_key.clone())
+        } else {
+            (self.params.rtmp_url.clone(), self.params.rtmp_stream_key.clone())
+        }
+    }
+
+    // switches to the other ingest once the one in use has failed. this is
+    // cold failover: there is only ever the one connection, and the other
+    // ingest is only connected to once it's needed, so every switch drops
+    // the stream for as long as connecting takes. the first failure fails
+    // over straight away, after that both have failed in a row and the
+    // switches back off as reconnects do. the stream stays on whichever
+    // ingest last connected until that one fails in turn
+    fn fail_over(&mut self, attempt: u32) -> Connection {
+        self.backup = !self.backup;
+        self.failovers += 1;
+
+        if self.backup {
+            eprintln!("stream_output: primary ingest failed, switching to backup (failover {})", self.failovers);
+            self.diagnostics.warning("ingest", "Primary ingest failed, streaming to backup");
+        } else {
+            eprintln!("stream_output: backup ingest failed, switching to primary (failover {})", self.failovers);
+            self.diagnostics.warning("ingest", "Backup ingest failed, streaming to primary");
+        }
+
+        if attempt == 0 {
+            self.connect(1)
+        } else {
+            Connection::reconnect(attempt + 1)
+        }
+    }
+
     fn indicate(&mut self) -> Option<StreamOutputIndication> {
         let new_indication = match &self.connection {
             Connection::Offline => StreamOutputIndication {
                 live: StreamOutputLiveStatus::Offline,
                 error: false,
+                kbps: None,
+                backup: false,
+                failovers: self.failovers,
             },
             Connection::Failed(_) => StreamOutputIndication {
                 live: StreamOutputLiveStatus::Offline,
                 error: true,
+                kbps: None,
+                backup: self.backup,
+                failovers: self.failovers,
             },
-            Connection::Connecting(_) => StreamOutputIndication {
+            Connection::Connecting(_, 0) => StreamOutputIndication {
                 live: StreamOutputLiveStatus::Connecting,
                 error: false,
+                kbps: None,
+                backup: self.backup,
+                failovers: self.failovers,
+            },
+            Connection::Connecting(_, attempt) |
+            Connection::Reconnecting { attempt, .. } => StreamOutputIndication {
+                live: StreamOutputLiveStatus::Reconnecting { attempt: *attempt },
+                error: true,
+                kbps: None,
+                backup: self.backup,
+                failovers: self.failovers,
             },
             Connection::Live(_) => StreamOutputIndication {
                 live: StreamOutputLiveStatus::Live,
                 error: false,
+                kbps: self.bitrate.kbps,
+                backup: self.backup,
+                failovers: self.failovers,
             },
         };
 
@@ -262,7 +421,9 @@ impl StreamOutput {
 enum Connection {
     Offline,
     Failed(Option<RtmpConnectError>),
-    Connecting(oneshot::Receiver<Result<PublishClient, RtmpConnectError>>),
+    // carries the number of failed attempts preceding this one:
+    Connecting(oneshot::Receiver<Result<PublishClient, RtmpConnectError>>, u32),
+    Reconnecting { at: Instant, attempt: u32 },
     Live(LiveOutputTask),
 }
 
@@ -271,15 +432,56 @@ impl Connection {
         match self {
             Connection::Offline => false,
             Connection::Failed(_) => false,
-            Connection::Connecting(_) => true,
+            Connection::Connecting(..) => true,
+            Connection::Reconnecting { .. } => true,
             Connection::Live(_) => true,
         }
     }
+
+    fn reconnect(attempt: u32) -> Connection {
+        let backoff = RECONNECT_BACKOFF_MIN * 2u32.saturating_pow(cmp::min(attempt, 16));
+        let backoff = cmp::min(backoff, RECONNECT_BACKOFF_MAX);
+
+        Connection::Reconnecting {
+            at: Instant::now() + backoff,
+            attempt: cmp::max(attempt, 1),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BitrateMeter {
+    since: Instant,
+    bytes_at: usize,
+    kbps: Option<u32>,
+}
+
+impl BitrateMeter {
+    fn new() -> Self {
+        BitrateMeter {
+            since: Instant::now(),
+            bytes_at: 0,
+            kbps: None,
+        }
+    }
+
+    fn sample(&mut self, bytes_sent: usize) {
+        let now = Instant::now();
+        let elapsed = now - self.since;
+
+        if elapsed >= BITRATE_INTERVAL {
+            let bits = (bytes_sent - self.bytes_at) as f64 * 8.0;
+            self.kbps = Some((bits / elapsed.as_secs_f64() / 1000.0).round() as u32);
+            self.since = now;
+            self.bytes_at = bytes_sent;
+        }
+    }
 }
 
 #[derive(Debug)]
 struct LiveOutputTask {
     tx: mpsc::SyncSender<LiveOutputMsg>,
+    bytes_sent: Arc<AtomicUsize>,
 }
 
 enum LiveOutputMsg {
@@ -287,25 +489,44 @@ enum LiveOutputMsg {
 }
 
 impl LiveOutputTask {
-    pub fn start(epoch: MediaTime, publish: PublishClient) -> Self {
+    pub fn start(epoch: MediaTime, publish: PublishClient, sample_rate: usize, color: Colorimetry) -> Self {
         let runtime = runtime::Handle::current();
         let (tx, rx) = mpsc::sync_channel(100);
-
-        thread::spawn(move || {
-            runtime.enter(move || {
-                let mut live = LiveOutput::start(epoch, publish);
-
-                while let Ok(msg) = rx.recv() {
-                    match msg {
-                        LiveOutputMsg::Tick { timestamp, audio, video } => {
-                            live.tick(timestamp, audio, video);
+        let bytes_sent = Arc::new(AtomicUsize::new(0));
+
+        thread::spawn({
+            let bytes_sent = bytes_sent.clone();
+
+            move || {
+                runtime.enter(move || {
+                    let mut live = match LiveOutput::start(epoch, publish, sample_rate, color, bytes_sent) {
+                        Ok(live) => live,
+                        Err(e) => {
+                            eprintln!("StreamOutput failed to start stream: {:?}", e);
+                            return;
+                        }
+                    };
+
+                    while let Ok(msg) = rx.recv() {
+                        match msg {
+                            LiveOutputMsg::Tick { timestamp, audio, video } => {
+                                if let Err(e) = live.tick(timestamp, audio, video) {
+                                    // dropping rx signals the module to reconnect
+                                    eprintln!("StreamOutput publish failed: {:?}", e);
+                                    return;
+                                }
+                            }
                         }
                     }
-                }
-            });
+                });
+            }
         });
 
-        LiveOutputTask { tx }
+        LiveOutputTask { tx, bytes_sent }
+    }
+
+    pub fn bytes_sent(&self) -> usize {
+        self.bytes_sent.load(Ordering::Relaxed)
     }
 
     pub fn send(&mut self, msg: LiveOutputMsg) -> Result<(), ()> {
@@ -330,23 +551,24 @@ struct LiveOutput {
     epoch: MediaTime,
     encode: EncodeStream,
     publish: PublishClient,
+    bytes_sent: Arc<AtomicUsize>,
 }
 
 impl LiveOutput {
-    pub fn start(epoch: MediaTime, mut publish: PublishClient) -> Self {
+    pub fn start(epoch: MediaTime, mut publish: PublishClient, sample_rate: usize, color: Colorimetry, bytes_sent: Arc<AtomicUsize>) -> Result<Self, PublishError> {
         let audio_ctx = AudioCtx::new(AudioParams {
             bit_rate: aac::BitRate::Cbr(160000),
-            sample_rate: SAMPLE_RATE,
+            sample_rate,
             transport: aac::Transport::Raw,
         });
 
         // configuration buffer is ASC when raw transport is in use:
         let asc = audio_ctx.configuration_data();
-        publish.publish_audio(AudioPacket::AacSequenceHeader(asc), RtmpTimestamp::new(0)).expect("TODO");
+        publish.publish_audio(AudioPacket::AacSequenceHeader(asc), RtmpTimestamp::new(0))?;
 
         let video_ctx = VideoCtx::new(VideoParams {
-            picture: PictureSettings::yuv420p(OUTPUT_WIDTH, OUTPUT_HEIGHT),
-            time_base: SAMPLE_RATE,
+            picture: PictureSettings::yuv420p(OUTPUT_WIDTH, OUTPUT_HEIGHT).with_color(color),
+            time_base: sample_rate,
             profile: Profile::Stream,
         });
 
@@ -359,18 +581,19 @@ impl LiveOutput {
             packet_type: VideoPacketType::SequenceHeader,
             composition_time: 0,
             data: dsc,
-        }, RtmpTimestamp::new(0)).expect("TODO");
+        }, RtmpTimestamp::new(0))?;
 
         let encode = EncodeStream::new(audio_ctx, video_ctx);
 
-        LiveOutput {
+        Ok(LiveOutput {
             epoch,
             encode,
             publish,
-        }
+            bytes_sent,
+        })
     }
 
-    pub fn tick(&mut self, timestamp: MediaTime, audio: Vec<engine::Sample>, video: Option<engine::VideoFrame>) {
+    pub fn tick(&mut self, timestamp: MediaTime, audio: Vec<engine::Sample>, video: Option<engine::VideoFrame>) -> Result<(), PublishError> {
         self.encode.send_audio(&audio);
 
         if let Some(video_frame) = video {
@@ -386,11 +609,14 @@ impl LiveOutput {
             match segment {
                 StreamSegment::Audio(audio) => {
                     let timestamp = RtmpTimestamp::new(audio.decode_timestamp.round_to_base(rtmp::TIME_BASE.into()) as u32);
-                    self.publish.publish_audio(AudioPacket::AacRawData(audio.frame), timestamp).expect("TODO");
+                    let len = audio.frame.len();
+                    let result = self.publish.publish_audio(AudioPacket::AacRawData(audio.frame), timestamp);
+                    self.account(len, result)?;
                 }
                 StreamSegment::Video(video) => {
                     let timestamp = RtmpTimestamp::new(video.decode_timestamp.round_to_base(rtmp::TIME_BASE.into()) as u32);
-                    self.publish.publish_video(VideoPacket {
+                    let len = video.frame.data.len();
+                    let result = self.publish.publish_video(VideoPacket {
                         frame_type: if video.frame.is_key_frame {
                             VideoFrameType::KeyFrame
                         } else {
@@ -399,9 +625,24 @@ impl LiveOutput {
                         packet_type: VideoPacketType::Nalu,
                         composition_time: video.frame.composition_time.round_to_base(rtmp::TIME_BASE.into()) as u32,
                         data: video.frame.data,
-                    }, timestamp).expect("TODO");
+                    }, timestamp);
+                    self.account(len, result)?;
                 }
             }
         }
+
+        Ok(())
+    }
+
+    fn account(&self, len: usize, result: Result<(), PublishError>) -> Result<(), PublishError> {
+        match result {
+            Ok(()) => {
+                self.bytes_sent.fetch_add(len, Ordering::Relaxed);
+                Ok(())
+            }
+            // the rtmp client is backed up, drop this packet and carry on
+            Err(PublishError::Lagged) => Ok(()),
+            Err(e) => Err(e),
+        }
     }
 }
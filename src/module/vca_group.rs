@@ -0,0 +1,48 @@
+use mixlab_protocol::{VcaGroupParams, Terminal, ParamSpec, ParamUnit};
+
+use crate::engine::{self, InputRef, OutputRef};
+use crate::module::ModuleT;
+
+// a vca group has no signal path of its own, member channels look up its
+// fader level via engine::GroupLevels
+#[derive(Debug)]
+pub struct VcaGroup {
+    params: VcaGroupParams,
+}
+
+impl ModuleT for VcaGroup {
+    type Params = VcaGroupParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        (VcaGroup { params }, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, _: &[InputRef], _: &mut [OutputRef]) -> Option<Self::Indication> {
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &[]
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &[]
+    }
+
+    fn param_specs(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("/fader", "Fader", ParamUnit::Percent, 0.0, 1.0),
+        ]
+    }
+}
@@ -1,3 +1,6 @@
+use std::iter;
+use std::mem;
+
 use itertools::Itertools;
 
 use mixlab_codec::ffmpeg::media::Video;
@@ -5,17 +8,58 @@ use mixlab_codec::ffmpeg::{AvFrame, PictureSettings, PixelFormat};
 use mixlab_protocol::{VideoMixerParams, LineType, Terminal, VIDEO_MIXER_CHANNELS};
 use mixlab_util::time::{MediaTime, MediaDuration};
 
-use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE, TICKS_PER_SECOND};
+use crate::engine::{self, EngineConfig, InputRef, OutputRef};
 use crate::module::ModuleT;
+use crate::module::media_source::{self, OpenMedia, Playback};
 use crate::video;
 use crate::video::encode::DynamicScaler;
 
+// composited over the program, after the channel inputs:
+const OVERLAY_INPUT: usize = VIDEO_MIXER_CHANNELS;
+
 #[derive(Debug)]
 pub struct VideoMixer {
+    ctx: engine::ModuleCtx<Self>,
     params: VideoMixerParams,
+    config: EngineConfig,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
     channels: Vec<Channel>,
+    overlay: Overlay,
+    stinger: Stinger,
+}
+
+#[derive(Debug)]
+pub enum VideoMixerEvent {
+    StingerLoaded(Option<OpenMedia>),
+}
+
+#[derive(Debug, Default)]
+struct Stinger {
+    // opened ahead of time so that a take starts playing straight away:
+    ready: Option<OpenMedia>,
+    playing: Option<PlayingStinger>,
+}
+
+#[derive(Debug)]
+struct PlayingStinger {
+    media: OpenMedia,
+    started: Option<MediaTime>,
+    cut_at: MediaDuration,
+    cut: bool,
+    // fader position from before the take, mixed until the cut point:
+    held_fader: f64,
+    overlay: Overlay,
+}
+
+// a picture with alpha, kept scaled to the output so that it's only scaled
+// again when it or the output changes
+#[derive(Debug, Default)]
+struct Overlay {
+    frame: Option<AvFrame<Video>>,
+    active_until: Option<MediaTime>,
+    scaler: Option<DynamicScaler>,
+    scaled: Option<AvFrame<Video>>,
 }
 
 #[derive(Debug)]
@@ -34,14 +78,16 @@ struct StoredFrame {
 impl ModuleT for VideoMixer {
     type Params = VideoMixerParams;
     type Indication = ();
-    type Event = ();
+    type Event = VideoMixerEvent;
 
-    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
-        let mixer = VideoMixer {
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let mut mixer = VideoMixer {
             params,
+            config: ctx.config(),
+            ctx,
             inputs: (0..VIDEO_MIXER_CHANNELS).map(|i|
                 LineType::Video.labeled(&(i + 1).to_string())
-            ).collect(),
+            ).chain(iter::once(LineType::Video.labeled("Overlay"))).collect(),
             outputs: vec![
                 LineType::Video.labeled("Output"),
                 LineType::Video.labeled("A"),
@@ -53,8 +99,12 @@ impl ModuleT for VideoMixer {
                     scaler: None,
                 }
             }).collect(),
+            overlay: Overlay::default(),
+            stinger: Stinger::default(),
         };
 
+        mixer.load_stinger();
+
         (mixer, ())
     }
 
@@ -63,10 +113,32 @@ impl ModuleT for VideoMixer {
     }
 
     fn update(&mut self, new_params: VideoMixerParams) -> Option<Self::Indication> {
-        self.params = new_params;
+        let old_params = mem::replace(&mut self.params, new_params);
+
+        if self.params.stinger.media_id != old_params.stinger.media_id {
+            self.stinger.ready = None;
+            self.load_stinger();
+        }
+
+        if self.params.stinger.take != old_params.stinger.take {
+            self.take_stinger(old_params.fader);
+        }
+
         None
     }
 
+    fn receive_event(&mut self, event: VideoMixerEvent) {
+        match event {
+            VideoMixerEvent::StingerLoaded(Some(media)) => {
+                // ignore media that has since been replaced:
+                if Some(media.media_id()) == self.params.stinger.media_id {
+                    self.stinger.ready = Some(media);
+                }
+            }
+            VideoMixerEvent::StingerLoaded(None) => {}
+        }
+    }
+
     fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
         let (out, out_a, out_b) = match &mut outputs[0..3] {
             [a, b, c] => (a, b, c),
@@ -76,6 +148,9 @@ impl ModuleT for VideoMixer {
         let out_a = out_a.expect_video();
         let out_b = out_b.expect_video();
 
+        let (inputs, overlay_input) = inputs.split_at(OVERLAY_INPUT);
+        let overlay_input = overlay_input[0].expect_video();
+
         // send channel specific outputs
         {
             *out_a = self.params.a
@@ -89,7 +164,8 @@ impl ModuleT for VideoMixer {
                 .cloned();
         }
 
-        let absolute_timestamp = MediaTime::new(t as i64, SAMPLE_RATE as i64);
+        let absolute_timestamp = self.config.media_time(t);
+        let fader = self.advance_stinger(absolute_timestamp, absolute_timestamp + self.config.tick_duration());
 
         // expire stored frames
         for channel in &mut self.channels {
@@ -100,6 +176,16 @@ impl ModuleT for VideoMixer {
             }
         }
 
+        if let Some(video) = overlay_input {
+            self.overlay.set_frame(
+                video.data.decoded.clone(),
+                Some(absolute_timestamp + video.tick_offset + video.data.duration_hint));
+        } else if let Some(active_until) = self.overlay.active_until {
+            if absolute_timestamp >= active_until {
+                self.overlay = Overlay::default();
+            }
+        }
+
         // calculate compatible output picture settings
         let target = inputs.iter().enumerate()
             .flat_map(|(idx, input)| {
@@ -111,7 +197,7 @@ impl ModuleT for VideoMixer {
             .fold1(unify_picture_settings);
 
         let target = match target {
-            Some(target) => target,
+            Some(target) => target.with_color(self.config.colorimetry()),
             None => {
                 // no inputs and no stored pictures - no work for us to do here
                 return None;
@@ -165,7 +251,7 @@ impl ModuleT for VideoMixer {
                 .and_then(|ch| ch.stored.as_ref())
                 .map(|stored| stored.frame.frame_data());
 
-            let crossfade = (self.params.fader * 255.0) as u8;
+            let crossfade = (fader * 255.0) as u8;
 
             unsafe {
                 for component in pixfmt.components() {
@@ -238,10 +324,16 @@ impl ModuleT for VideoMixer {
             }
         }
 
+        self.overlay.overlay(&mut output_frame, &target);
+
+        if let Some(playing) = &mut self.stinger.playing {
+            playing.overlay.overlay(&mut output_frame, &target);
+        }
+
         *out = Some(engine::VideoFrame {
             data: video::Frame {
                 decoded: output_frame,
-                duration_hint: MediaDuration::new(1, TICKS_PER_SECOND as i64), // TODO this assumes 1 output frame per tick
+                duration_hint: self.config.tick_duration(), // TODO this assumes 1 output frame per tick
             },
             tick_offset: MediaDuration::new(0, 1),
         });
@@ -258,6 +350,138 @@ impl ModuleT for VideoMixer {
     }
 }
 
+impl VideoMixer {
+    fn load_stinger(&self) {
+        if let Some(media_id) = self.params.stinger.media_id {
+            let project = self.ctx.project();
+
+            self.ctx.spawn_async(async move {
+                let media = media_source::open_media(project, media_id, Playback::Once).await;
+                VideoMixerEvent::StingerLoaded(media)
+            });
+        }
+    }
+
+    fn take_stinger(&mut self, previous_fader: f64) {
+        let media = match self.stinger.ready.take() {
+            Some(media) => media,
+            // nothing loaded, the fader change takes effect immediately:
+            None => return,
+        };
+
+        // a take during a take holds whatever is still on screen:
+        let held_fader = match &self.stinger.playing {
+            Some(playing) if !playing.cut => playing.held_fader,
+            _ => previous_fader,
+        };
+
+        self.stinger.playing = Some(PlayingStinger {
+            media,
+            started: None,
+            cut_at: MediaDuration::new(self.params.stinger.cut_ms as i64, 1000),
+            cut: false,
+            held_fader,
+            overlay: Overlay::default(),
+        });
+
+        // each take plays the clip from the start, so open it again for next
+        // time:
+        self.load_stinger();
+    }
+
+    // returns the fader position to mix with this tick
+    fn advance_stinger(&mut self, start_of_frame: MediaTime, end_of_frame: MediaTime) -> f64 {
+        let playing = match &mut self.stinger.playing {
+            Some(playing) => playing,
+            None => return self.params.fader,
+        };
+
+        let started = *playing.started.get_or_insert(start_of_frame);
+
+        if let Some(frame) = playing.media.next_frame(start_of_frame, end_of_frame) {
+            // shown until the stinger finishes:
+            playing.overlay.set_frame(frame.data.decoded, None);
+        }
+
+        if start_of_frame - started >= playing.cut_at {
+            playing.cut = true;
+        }
+
+        let fader = if playing.cut { self.params.fader } else { playing.held_fader };
+
+        if playing.media.finished() {
+            self.stinger.playing = None;
+            return self.params.fader;
+        }
+
+        fader
+    }
+}
+
+impl Overlay {
+    fn set_frame(&mut self, frame: AvFrame<Video>, active_until: Option<MediaTime>) {
+        self.frame = Some(frame);
+        self.active_until = active_until;
+        self.scaled = None;
+    }
+
+    fn overlay(&mut self, output: &mut AvFrame<Video>, target: &PictureSettings) {
+        let overlay_target = PictureSettings {
+            pixel_format: PixelFormat::yuva420p(),
+            ..target.clone()
+        };
+
+        if self.scaler.as_ref().map(|scaler| scaler.output()) != Some(&overlay_target) {
+            self.scaler = Some(DynamicScaler::new(overlay_target));
+            self.scaled = None;
+        }
+
+        if self.scaled.is_none() {
+            if let Some(frame) = &self.frame {
+                let mut frame = frame.clone();
+                let scaler = self.scaler.as_mut().unwrap();
+                self.scaled = Some(scaler.scale(&mut frame).clone());
+            }
+        }
+
+        if let Some(scaled) = &self.scaled {
+            overlay_alpha(output, scaled);
+        }
+    }
+}
+
+// blends a yuva420p picture over a yuv420p picture of the same size:
+fn overlay_alpha(output: &mut AvFrame<Video>, overlay: &AvFrame<Video>) {
+    const ALPHA_PLANE: usize = 3;
+
+    let pict = output.picture_settings();
+    let out = output.frame_data_mut();
+    let over = overlay.frame_data();
+
+    unsafe {
+        let alpha_stride = over.stride(ALPHA_PLANE);
+        let alpha = over.data(ALPHA_PLANE);
+
+        for plane in 0..3 {
+            // chroma planes are subsampled 2x2, sample alpha at the top left
+            // of each block:
+            let shift = if plane == 0 { 0 } else { 1 };
+
+            for y in 0..(pict.height >> shift) {
+                let out_line = out.data(plane).add(y * out.stride(plane));
+                let over_line = over.data(plane).add(y * over.stride(plane));
+                let alpha_line = alpha.add((y << shift) * alpha_stride);
+
+                for x in 0..(pict.width >> shift) {
+                    let a = *alpha_line.add(x << shift) as u16;
+                    let out_px = out_line.add(x);
+                    *out_px = ((*out_px as u16 * (255 - a) + *over_line.add(x) as u16 * a) / 255) as u8;
+                }
+            }
+        }
+    }
+}
+
 impl Channel {
     pub fn rescale(&mut self, target: &PictureSettings) {
         let current = self.scaler.as_ref().map(|scaler| scaler.output());
@@ -289,9 +513,6 @@ fn unify_picture_settings(a: PictureSettings, b: PictureSettings) -> PictureSett
     let aligned_width = (width + horz_mask) & !horz_mask;
     let aligned_height = (height + vert_mask) & !vert_mask;
 
-    PictureSettings {
-        width: aligned_width,
-        height: aligned_height,
-        pixel_format: pixfmt,
-    }
+    // color is decided by the project rather than the inputs:
+    PictureSettings::yuv420p(aligned_width, aligned_height)
 }
@@ -0,0 +1,77 @@
+use mixlab_protocol::{ZoneOutputParams, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, ZoneAudio};
+use crate::module::ModuleT;
+use crate::util;
+
+/// Plays out an output zone, for patching into the device or stream that
+/// feeds it. The zone is heard a block late, as it's processed once every
+/// module has run
+#[derive(Debug)]
+pub struct ZoneOutput {
+    ctx: engine::ModuleCtx<Self>,
+    params: ZoneOutputParams,
+    zones: ZoneAudio,
+    // as last reported, so the diagnostic is only touched on change:
+    missing: bool,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for ZoneOutput {
+    type Params = ZoneOutputParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = ZoneOutput {
+            zones: ctx.zone_audio(),
+            ctx,
+            params,
+            missing: false,
+            outputs: vec![LineType::Stereo.labeled("Zone")],
+        };
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, _inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let output = outputs[0].expect_stereo();
+
+        let missing = match self.params.zone {
+            Some(zone) => !self.zones.read(zone, output),
+            None => {
+                util::zero(output);
+                false
+            }
+        };
+
+        if missing != self.missing {
+            if missing {
+                self.ctx.diagnostics().warning("zone", "Zone no longer exists");
+            } else {
+                self.ctx.diagnostics().clear("zone");
+            }
+
+            self.missing = missing;
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &[]
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
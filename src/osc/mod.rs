@@ -0,0 +1,507 @@
+mod packet;
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+
+use derive_more::From;
+use futures::stream::{self, StreamExt};
+use serde_json::Value;
+use tokio::net::UdpSocket;
+use tokio::sync::{broadcast, mpsc};
+
+use mixlab_protocol::{ClientSequence, Indication, ModuleId, ModuleParams, MorphSlot, ParamSpec, ServerUpdate, WorkspaceMessage, WorkspaceOp, WorkspaceState};
+
+use crate::engine::{self, EngineError, EngineEvent, EngineSession};
+use crate::project::ProjectHandle;
+use crate::util::{self, Sequence};
+
+use packet::{Arg, Message};
+
+// largest datagram osc allows over udp:
+const MAX_PACKET_SIZE: usize = 65507;
+
+// OSC namespace, where <module> is either the numeric module id or the name
+// of a VCA group with any characters not allowed in OSC addresses replaced
+// by underscores:
+//
+//   /module/<module>/params/<path>  f   set the param at json pointer <path>
+//   /module/<module>/normalized/<path>
+//                                   f   set the param from a 0.0 - 1.0 position
+//                                       along its range, for params the
+//                                       module describes
+//   /module/<module>/morph          f   move the module's morph position
+//   /module/<module>/morph/a|b          store the current params as A or B
+//   /binding/<name>                     invoke the named binding
+//   /auth                           s   the server's owner key. when it has
+//                                       one, peers send this first, and are
+//                                       neither listened to nor sent
+//                                       feedback until they have
+//
+// every numeric or boolean param, indication and morph position is fed back
+// to each peer that has sent us a message, under /module/<module>/params/..,
+// /module/<module>/normalized/.., /module/<module>/indication/.. and
+// /module/<module>/morph
+
+#[derive(Debug, From)]
+pub enum OscError {
+    Io(io::Error),
+    Engine(EngineError),
+}
+
+#[derive(Debug)]
+enum RemoteError {
+    BadAddress(String),
+    NoSuchModule(String),
+    NoSuchParam(String),
+    MissingArgument(String),
+    Engine(EngineError),
+}
+
+pub async fn run(addr: SocketAddr, project: ProjectHandle, owner_key: Option<String>) -> Result<(), OscError> {
+    let socket = UdpSocket::bind(addr).await?;
+    let (mut recv, mut send) = socket.split();
+
+    println!("OSC control listening on udp://{}", addr);
+
+    let (mut packet_tx, mut packet_rx) = mpsc::channel::<(SocketAddr, Vec<u8>)>(64);
+
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; MAX_PACKET_SIZE];
+
+        loop {
+            match recv.recv_from(&mut buf).await {
+                Ok((len, peer)) => {
+                    if packet_tx.send((peer, buf[..len].to_vec())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    eprintln!("osc: recv failed: {:?}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut peers = HashSet::new();
+
+    // our mirror of the workspace is only valid while we see every update, so
+    // reconnect and start over from fresh state if we fall behind:
+    loop {
+        let (state, engine_events, engine) = project.connect_engine().await?;
+
+        let mut remote = Remote::new(engine);
+        let feedback = remote.replace_workspace(state);
+        send_feedback(&mut send, &mut peers, feedback).await;
+
+        enum Event {
+            Packet((SocketAddr, Vec<u8>)),
+            Engine(Result<EngineEvent, broadcast::RecvError>),
+        }
+
+        let mut events = stream::select(
+            packet_rx.by_ref().map(Event::Packet),
+            engine_events.map(Event::Engine));
+
+        while let Some(event) = events.next().await {
+            match event {
+                Event::Packet((peer, bytes)) => {
+                    let messages = match packet::parse(&bytes) {
+                        Ok(messages) => messages,
+                        Err(e) => {
+                            eprintln!("osc: bad packet from {}: {:?}", peer, e);
+                            continue;
+                        }
+                    };
+
+                    for msg in messages {
+                        if msg.address == "/auth" {
+                            if authenticates(&msg, owner_key.as_deref()) {
+                                peers.insert(peer);
+                            } else {
+                                eprintln!("osc: wrong owner key from {}", peer);
+                            }
+
+                            continue;
+                        }
+
+                        if owner_key.is_none() {
+                            peers.insert(peer);
+                        }
+
+                        if !peers.contains(&peer) {
+                            eprintln!("osc: ignoring {} from {}, which has not sent /auth", msg.address, peer);
+                            continue;
+                        }
+
+                        if let Err(e) = remote.receive(msg) {
+                            eprintln!("osc: {:?}", e);
+                        }
+                    }
+                }
+                Event::Engine(Err(broadcast::RecvError::Lagged(skipped))) => {
+                    eprintln!("osc: lagged {} messages behind, resyncing", skipped);
+                    break;
+                }
+                Event::Engine(Err(broadcast::RecvError::Closed)) => {
+                    return Err(OscError::Engine(EngineError::Stopped));
+                }
+                Event::Engine(Ok(EngineEvent::ServerUpdate(update))) => {
+                    let feedback = remote.server_update(update);
+                    send_feedback(&mut send, &mut peers, feedback).await;
+                }
+                Event::Engine(Ok(EngineEvent::Sync(_))) => {}
+            }
+        }
+    }
+}
+
+fn authenticates(msg: &Message, owner_key: Option<&str>) -> bool {
+    let given = match msg.args.first() {
+        Some(Arg::Str(given)) => given.as_bytes(),
+        _ => &[],
+    };
+
+    match owner_key {
+        Some(key) => util::constant_time_eq(given, key.as_bytes()),
+        None => true,
+    }
+}
+
+async fn send_feedback(send: &mut tokio::net::udp::SendHalf, peers: &mut HashSet<SocketAddr>, feedback: Vec<Message>) {
+    if feedback.is_empty() {
+        return;
+    }
+
+    let packets = feedback.iter().map(packet::encode).collect::<Vec<_>>();
+    let mut gone = Vec::new();
+
+    for peer in peers.iter() {
+        for packet in &packets {
+            if send.send_to(packet, peer).await.is_err() {
+                gone.push(*peer);
+                break;
+            }
+        }
+    }
+
+    for peer in gone {
+        peers.remove(&peer);
+    }
+}
+
+struct Remote {
+    engine: EngineSession,
+    sequence: Sequence,
+    modules: HashMap<ModuleId, ModuleParams>,
+    specs: HashMap<ModuleId, Vec<ParamSpec>>,
+    // last value fed back (or received) for each module and path, so that
+    // only changes are sent:
+    sent: HashMap<(ModuleId, String), f64>,
+}
+
+impl Remote {
+    fn new(engine: EngineSession) -> Self {
+        Remote {
+            engine,
+            sequence: Sequence::new(),
+            modules: HashMap::new(),
+            specs: HashMap::new(),
+            sent: HashMap::new(),
+        }
+    }
+
+    fn receive(&mut self, msg: Message) -> Result<(), RemoteError> {
+        if let Some(name) = msg.address.strip_prefix("/binding/") {
+            // as with morph buttons, only act on the press:
+            if msg.args.first().and_then(Arg::as_f64) == Some(0.0) {
+                return Ok(());
+            }
+
+            return self.send_op(WorkspaceOp::InvokeBinding(name.to_owned()));
+        }
+
+        let rest = msg.address.strip_prefix("/module/")
+            .ok_or_else(|| RemoteError::BadAddress(msg.address.clone()))?;
+
+        let (segment, command) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx + 1..]),
+            None => return Err(RemoteError::BadAddress(msg.address.clone())),
+        };
+
+        let module_id = self.resolve(segment)
+            .ok_or_else(|| RemoteError::NoSuchModule(segment.to_owned()))?;
+
+        let value = msg.args.first().and_then(Arg::as_f64);
+
+        if let Some(path) = command.strip_prefix("params/") {
+            let path = format!("/{}", path);
+            let value = value.ok_or_else(|| RemoteError::MissingArgument(msg.address.clone()))?;
+            return self.set_param(module_id, path, value);
+        }
+
+        if let Some(path) = command.strip_prefix("normalized/") {
+            let path = format!("/{}", path);
+            let position = value.ok_or_else(|| RemoteError::MissingArgument(msg.address.clone()))?;
+
+            let spec = self.specs.get(&module_id)
+                .and_then(|specs| specs.iter().find(|spec| spec.path == path))
+                .ok_or_else(|| RemoteError::NoSuchParam(path.clone()))?;
+
+            let value = spec.value_at(position);
+            self.sent.insert((module_id, format!("normalized{}", path)), spec.position(value));
+            return self.set_param(module_id, path, value);
+        }
+
+        match command {
+            "morph" => {
+                let value = value.ok_or_else(|| RemoteError::MissingArgument(msg.address.clone()))?;
+                let position = f64::max(0.0, f64::min(1.0, value));
+                self.sent.insert((module_id, "morph".to_owned()), position);
+                self.send_op(WorkspaceOp::SetMorph(module_id, position))
+            }
+            "morph/a" | "morph/b" => {
+                // buttons on most control surfaces send 1 on press and 0 on
+                // release, only act on the press
+                if value == Some(0.0) {
+                    return Ok(());
+                }
+
+                let slot = if command == "morph/a" { MorphSlot::A } else { MorphSlot::B };
+                self.send_op(WorkspaceOp::StoreMorphState(module_id, slot))
+            }
+            _ => Err(RemoteError::BadAddress(msg.address.clone())),
+        }
+    }
+
+    fn set_param(&mut self, module_id: ModuleId, path: String, value: f64) -> Result<(), RemoteError> {
+        let params = &self.modules[&module_id];
+
+        if engine::read_param(params, &path).is_none() {
+            return Err(RemoteError::NoSuchParam(path));
+        }
+
+        let params = match engine::write_param(params, &path, value) {
+            Some(params) => params,
+            // already set to this value:
+            None => return Ok(()),
+        };
+
+        // apply locally straight away so that further messages arriving
+        // before the engine echoes this update build on it, and so that the
+        // sender does not get its own value fed back to it
+        self.modules.insert(module_id, params.clone());
+        if let Some(value) = engine::read_param(&params, &path) {
+            self.sent.insert((module_id, format!("params{}", path)), value);
+        }
+
+        self.send_op(WorkspaceOp::UpdateModuleParams(module_id, params))
+    }
+
+    fn send_op(&mut self, op: WorkspaceOp) -> Result<(), RemoteError> {
+        let sequence = ClientSequence(self.sequence.next());
+
+        self.engine.update(WorkspaceMessage { sequence, op })
+            .map_err(RemoteError::Engine)
+    }
+
+    fn resolve(&self, segment: &str) -> Option<ModuleId> {
+        if let Some(id) = segment.parse::<usize>().ok().and_then(NonZeroUsize::new) {
+            let id = ModuleId(id);
+            return if self.modules.contains_key(&id) { Some(id) } else { None };
+        }
+
+        self.modules.keys()
+            .copied()
+            .find(|id| self.label(*id).as_deref() == Some(segment))
+    }
+
+    fn label(&self, module_id: ModuleId) -> Option<String> {
+        match self.modules.get(&module_id)? {
+            ModuleParams::VcaGroup(params) if !params.name.is_empty() => {
+                Some(address_safe(&params.name))
+            }
+            _ => None,
+        }
+    }
+
+    fn replace_workspace(&mut self, state: WorkspaceState) -> Vec<Message> {
+        self.modules = state.modules.into_iter().collect();
+        self.specs = state.param_specs.into_iter().collect();
+        self.sent.clear();
+
+        let mut feedback = Vec::new();
+
+        let mut module_ids = self.modules.keys().copied().collect::<Vec<_>>();
+        module_ids.sort();
+
+        for module_id in module_ids {
+            let params = self.modules[&module_id].clone();
+            feedback.extend(self.feedback_params(module_id, &params));
+        }
+
+        for (module_id, indication) in state.indications {
+            feedback.extend(self.feedback_indication(module_id, &indication));
+        }
+
+        for (module_id, morph) in state.morphs {
+            feedback.extend(self.feedback_value(module_id, "morph".to_owned(), morph.position));
+        }
+
+        feedback
+    }
+
+    fn server_update(&mut self, update: ServerUpdate) -> Vec<Message> {
+        match update {
+            ServerUpdate::CreateModule { id, params, indication, .. } => {
+                self.modules.insert(id, params.clone());
+                let mut feedback = self.feedback_params(id, &params);
+                feedback.extend(self.feedback_indication(id, &indication));
+                feedback
+            }
+            ServerUpdate::UpdateModuleParams(id, params) => {
+                self.modules.insert(id, params.clone());
+                self.feedback_params(id, &params)
+            }
+            ServerUpdate::UpdateModuleIndication(id, indication) => {
+                self.feedback_indication(id, &indication)
+            }
+            ServerUpdate::DeleteModule(id) => {
+                self.modules.remove(&id);
+                self.specs.remove(&id);
+                self.sent.retain(|(module_id, _), _| *module_id != id);
+                Vec::new()
+            }
+            ServerUpdate::UpdateMorph(id, morph) => {
+                let position = morph.map(|morph| morph.position).unwrap_or(0.0);
+                self.feedback_value(id, "morph".to_owned(), position)
+            }
+            ServerUpdate::UpdateParamSpecs(id, specs) => {
+                self.specs.insert(id, specs);
+
+                match self.modules.get(&id).cloned() {
+                    Some(params) => self.feedback_params(id, &params),
+                    None => Vec::new(),
+                }
+            }
+            ServerUpdate::ReplaceWorkspace(state) => {
+                self.replace_workspace(state)
+            }
+            ServerUpdate::UpdateWindowGeometry(..) |
+            ServerUpdate::CreateConnection(..) |
+            ServerUpdate::DeleteConnection(..) |
+            ServerUpdate::GainStagingReport(..) |
+            ServerUpdate::CreateParamLink(..) |
+            ServerUpdate::DeleteParamLink(..) |
+            ServerUpdate::CreateModulation(..) |
+            ServerUpdate::DeleteModulation(..) |
+            ServerUpdate::SetClockSource(..) |
+            ServerUpdate::SetRehearsal(..) |
+            ServerUpdate::UpdateViewport(..) |
+            ServerUpdate::UpdateView(..) |
+            ServerUpdate::UpdateViewGeometry(..) |
+            ServerUpdate::UpdateBinding(..) |
+            ServerUpdate::UpdateDeviceLink(..) |
+            ServerUpdate::UpdateZone(..) |
+            ServerUpdate::UpdateFavoriteParams(..) |
+            ServerUpdate::UpdateModuleDiagnostics(..) => Vec::new(),
+        }
+    }
+
+    fn feedback_params(&mut self, module_id: ModuleId, params: &ModuleParams) -> Vec<Message> {
+        let mut feedback = self.feedback_leaves(module_id, "params", serde_json::to_value(params).ok());
+
+        let specs = self.specs.get(&module_id).cloned().unwrap_or_default();
+
+        for spec in specs {
+            if let Some(value) = engine::read_param(params, &spec.path) {
+                feedback.extend(self.feedback_value(module_id, format!("normalized{}", spec.path), spec.position(value)));
+            }
+        }
+
+        feedback
+    }
+
+    fn feedback_indication(&mut self, module_id: ModuleId, indication: &Indication) -> Vec<Message> {
+        self.feedback_leaves(module_id, "indication", serde_json::to_value(indication).ok())
+    }
+
+    // params and indications are externally tagged, so serialize to
+    // `{"Variant": {..}}` and feed back paths relative to the inner value:
+    fn feedback_leaves(&mut self, module_id: ModuleId, prefix: &str, value: Option<Value>) -> Vec<Message> {
+        let inner = match value {
+            Some(Value::Object(map)) => map.into_iter().next().map(|(_, inner)| inner),
+            _ => None,
+        };
+
+        let mut leaves = Vec::new();
+
+        if let Some(inner) = inner {
+            collect_leaves(&inner, prefix.to_owned(), &mut leaves);
+        }
+
+        leaves.into_iter()
+            .flat_map(|(path, value)| self.feedback_value(module_id, path, value))
+            .collect()
+    }
+
+    fn feedback_value(&mut self, module_id: ModuleId, path: String, value: f64) -> Vec<Message> {
+        let key = (module_id, path);
+
+        if self.sent.get(&key) == Some(&value) {
+            return Vec::new();
+        }
+
+        let path = key.1.clone();
+        self.sent.insert(key, value);
+
+        let mut segments = vec![module_id.0.to_string()];
+        segments.extend(self.label(module_id));
+
+        segments.into_iter()
+            .map(|segment| Message {
+                address: format!("/module/{}/{}", segment, path),
+                args: vec![Arg::Float(value as f32)],
+            })
+            .collect()
+    }
+}
+
+fn collect_leaves(value: &Value, path: String, out: &mut Vec<(String, f64)>) {
+    match value {
+        Value::Number(num) => {
+            if let Some(num) = num.as_f64() {
+                out.push((path, num));
+            }
+        }
+        Value::Bool(b) => out.push((path, if *b { 1.0 } else { 0.0 })),
+        Value::Object(map) => {
+            for (key, value) in map {
+                collect_leaves(value, format!("{}/{}", path, escape_pointer(key)), out);
+            }
+        }
+        Value::Array(items) => {
+            for (idx, value) in items.iter().enumerate() {
+                collect_leaves(value, format!("{}/{}", path, idx), out);
+            }
+        }
+        Value::Null | Value::String(_) => {}
+    }
+}
+
+// json pointer escaping, so that fed back paths can be sent straight back:
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn address_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            ' ' | '#' | '*' | ',' | '/' | '?' | '[' | ']' | '{' | '}' => '_',
+            c if c.is_ascii_graphic() => c,
+            _ => '_',
+        })
+        .collect()
+}
@@ -0,0 +1,189 @@
+// just enough of OSC 1.0 to talk to common control surfaces. bundles are
+// flattened and their time tags ignored, everything is applied on receipt
+
+use std::convert::{TryFrom, TryInto};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Bool(bool),
+    Str(String),
+    Nil,
+}
+
+impl Arg {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Arg::Int(i) => Some(*i as f64),
+            Arg::Long(i) => Some(*i as f64),
+            Arg::Float(f) => Some(*f as f64),
+            Arg::Double(f) => Some(*f),
+            Arg::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            Arg::Str(_) | Arg::Nil => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub address: String,
+    pub args: Vec<Arg>,
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    Truncated,
+    BadString,
+    NoTypeTags,
+    UnsupportedType(char),
+    BadSize(i32),
+}
+
+const BUNDLE_TAG: &[u8] = b"#bundle\0";
+
+pub fn parse(packet: &[u8]) -> Result<Vec<Message>, ParseError> {
+    let mut messages = Vec::new();
+    parse_into(packet, &mut messages)?;
+    Ok(messages)
+}
+
+fn parse_into(packet: &[u8], messages: &mut Vec<Message>) -> Result<(), ParseError> {
+    if packet.starts_with(BUNDLE_TAG) {
+        let mut rd = Reader { buf: packet, pos: BUNDLE_TAG.len() };
+
+        // time tag:
+        rd.take(8)?;
+
+        while rd.pos < packet.len() {
+            let size = rd.int()?;
+            let size = usize::try_from(size).map_err(|_| ParseError::BadSize(size))?;
+            parse_into(rd.take(size)?, messages)?;
+        }
+
+        Ok(())
+    } else {
+        messages.push(parse_message(packet)?);
+        Ok(())
+    }
+}
+
+fn parse_message(packet: &[u8]) -> Result<Message, ParseError> {
+    let mut rd = Reader { buf: packet, pos: 0 };
+
+    let address = rd.string()?;
+
+    // type tags are optional in OSC 1.0, but everything sends them now
+    let tags = rd.string()?;
+    let tags = tags.strip_prefix(',').ok_or(ParseError::NoTypeTags)?;
+
+    let args = tags.chars()
+        .map(|tag| match tag {
+            'i' => rd.int().map(Arg::Int),
+            'h' => rd.take(8).map(|b| Arg::Long(i64::from_be_bytes(b.try_into().unwrap()))),
+            'f' => rd.take(4).map(|b| Arg::Float(f32::from_be_bytes(b.try_into().unwrap()))),
+            'd' => rd.take(8).map(|b| Arg::Double(f64::from_be_bytes(b.try_into().unwrap()))),
+            's' => rd.string().map(Arg::Str),
+            'T' => Ok(Arg::Bool(true)),
+            'F' => Ok(Arg::Bool(false)),
+            'N' => Ok(Arg::Nil),
+            _ => Err(ParseError::UnsupportedType(tag)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Message { address, args })
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ParseError> {
+        let end = self.pos.checked_add(len).ok_or(ParseError::Truncated)?;
+        let bytes = self.buf.get(self.pos..end).ok_or(ParseError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn int(&mut self) -> Result<i32, ParseError> {
+        self.take(4).map(|b| i32::from_be_bytes(b.try_into().unwrap()))
+    }
+
+    // strings are nul terminated then padded to a multiple of 4 bytes:
+    fn string(&mut self) -> Result<String, ParseError> {
+        let rest = self.buf.get(self.pos..).ok_or(ParseError::Truncated)?;
+        let len = rest.iter().position(|b| *b == 0).ok_or(ParseError::Truncated)?;
+
+        let s = std::str::from_utf8(&rest[..len])
+            .map_err(|_| ParseError::BadString)?
+            .to_owned();
+
+        self.take(padded(len + 1))?;
+        Ok(s)
+    }
+}
+
+fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+pub fn encode(msg: &Message) -> Vec<u8> {
+    let mut tags = String::from(",");
+    let mut args = Vec::new();
+
+    for arg in &msg.args {
+        match arg {
+            Arg::Int(i) => { tags.push('i'); args.extend(&i.to_be_bytes()); }
+            Arg::Long(i) => { tags.push('h'); args.extend(&i.to_be_bytes()); }
+            Arg::Float(f) => { tags.push('f'); args.extend(&f.to_be_bytes()); }
+            Arg::Double(f) => { tags.push('d'); args.extend(&f.to_be_bytes()); }
+            Arg::Bool(true) => { tags.push('T'); }
+            Arg::Bool(false) => { tags.push('F'); }
+            Arg::Str(s) => { tags.push('s'); write_string(&mut args, s); }
+            Arg::Nil => { tags.push('N'); }
+        }
+    }
+
+    let mut packet = Vec::new();
+    write_string(&mut packet, &msg.address);
+    write_string(&mut packet, &tags);
+    packet.extend(args);
+    packet
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend(s.as_bytes());
+    buf.resize(buf.len() + padded(s.len() + 1) - s.len(), 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, ParseError, BUNDLE_TAG};
+
+    fn bundle(size: i32) -> Vec<u8> {
+        let mut packet = BUNDLE_TAG.to_vec();
+        packet.extend_from_slice(&[0; 8]);
+        packet.extend_from_slice(&size.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn rejects_negative_element_size() {
+        match parse(&bundle(-4)) {
+            Err(ParseError::BadSize(-4)) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_element() {
+        match parse(&bundle(i32::max_value())) {
+            Err(ParseError::Truncated) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -2,14 +2,45 @@ use std::collections::HashMap;
 
 use serde::{Serialize, Deserialize};
 
-use mixlab_protocol::{ModuleId, ModuleParams, OutputId, WindowGeometry};
+use mixlab_protocol::{ModuleId, ModuleParams, OutputId, WindowGeometry, ParamLinkId, ParamLink, MorphState, ModulationId, Modulation, ClockSource, Viewport, ViewId, View, Binding, ZoneId, Zone};
 
+use crate::engine::EngineConfig;
 use crate::util::Sequence;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Workspace {
+    #[serde(default)]
+    pub config: EngineConfig,
     pub module_seq: Sequence,
     pub modules: HashMap<ModuleId, Module>,
+    #[serde(default)]
+    pub param_link_seq: Sequence,
+    #[serde(default)]
+    pub param_links: HashMap<ParamLinkId, ParamLink>,
+    #[serde(default)]
+    pub modulation_seq: Sequence,
+    #[serde(default)]
+    pub modulations: HashMap<ModulationId, Modulation>,
+    #[serde(default)]
+    pub clock_source: ClockSource,
+    #[serde(default)]
+    pub rehearsal: bool,
+    #[serde(default)]
+    pub viewport: Viewport,
+    #[serde(default)]
+    pub view_seq: Sequence,
+    #[serde(default)]
+    pub views: HashMap<ViewId, View>,
+    #[serde(default)]
+    pub bindings: HashMap<String, Binding>,
+    // device names the project refers to, relinked to a machine's own
+    // devices, keyed by machine name:
+    #[serde(default)]
+    pub device_links: HashMap<String, HashMap<String, String>>,
+    #[serde(default)]
+    pub zone_seq: Sequence,
+    #[serde(default)]
+    pub zones: HashMap<ZoneId, Zone>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -17,4 +48,8 @@ pub struct Module {
     pub params: ModuleParams,
     pub geometry: WindowGeometry,
     pub inputs: Vec<Option<OutputId>>,
+    #[serde(default)]
+    pub morph: Option<MorphState>,
+    #[serde(default)]
+    pub favorite_params: Vec<String>,
 }
@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use mixlab_protocol::{Indication, PluginInfo, PluginParams};
+
+use crate::engine::{self, DeviceLinks, DynModuleHost, GroupLevels, Rehearsal, ZoneAudio};
+use crate::project::ProjectBaseRef;
+
+// everything a module needs to be written outside of mixlab:
+pub use mixlab_protocol::{LineType, ParamSpec, Terminal};
+pub use crate::engine::{ClockRef, Diagnostics, EngineConfig, InputRef, ModuleCtx, OutputRef, Sample, Schedule, Segment, VideoFrame, CHANNELS};
+pub use crate::module::ModuleT;
+
+type HostFn = fn(PluginParams, ProjectBaseRef, EngineConfig, GroupLevels, DeviceLinks, Rehearsal, ZoneAudio)
+    -> Result<(DynModuleHost, Indication), serde_json::Error>;
+
+struct Registration {
+    label: String,
+    default_params: String,
+    host: HostFn,
+}
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: RwLock<BTreeMap<String, Registration>> = RwLock::new(BTreeMap::new());
+}
+
+/// Registers a module from outside mixlab, offered in the catalog as
+/// `label` and created with `default_params`. It's saved under `kind`, so
+/// projects using it open only in builds registering the same kind.
+/// Registering a kind again replaces it
+pub fn register<M>(kind: &str, label: &str, default_params: M::Params)
+    where M: ModuleT, M::Params: Serialize + DeserializeOwned, M::Indication: Serialize
+{
+    let registration = Registration {
+        label: label.to_owned(),
+        default_params: serde_json::to_string(&default_params).expect("serde_json::to_string"),
+        host: engine::host_plugin::<M>,
+    };
+
+    REGISTRY.write().unwrap().insert(kind.to_owned(), registration);
+}
+
+pub(crate) fn catalog() -> Vec<PluginInfo> {
+    REGISTRY.read().unwrap().iter()
+        .map(|(kind, registration)| PluginInfo {
+            label: registration.label.clone(),
+            default_params: PluginParams {
+                kind: kind.clone(),
+                json: registration.default_params.clone(),
+            },
+        })
+        .collect()
+}
+
+pub(crate) fn host(params: PluginParams, base: ProjectBaseRef, config: EngineConfig, groups: GroupLevels, devices: DeviceLinks, rehearsal: Rehearsal, zone_audio: ZoneAudio) -> (DynModuleHost, Indication) {
+    // not held while the module is created:
+    let host = REGISTRY.read().unwrap().get(&params.kind).map(|registration| registration.host);
+
+    let result = match host {
+        Some(host) => host(params.clone(), base, config, groups, devices, rehearsal, zone_audio)
+            .map_err(|e| format!("Params could not be read: {}", e)),
+        None => Err(format!("Module type {} isn't registered in this build", params.kind)),
+    };
+
+    // a module that can't be created still keeps its params, so that they
+    // survive the project being saved by this build:
+    result.unwrap_or_else(|message| engine::missing_plugin(params, message))
+}
@@ -1,6 +1,9 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{self, Debug};
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use derive_more::From;
 use futures::stream::{Stream, StreamExt};
@@ -8,21 +11,32 @@ use rusqlite::{self, Connection, OptionalExtension};
 use tokio::sync::watch;
 use tokio::{io, task, runtime};
 
+use mixlab_codec::ffmpeg::ColorSpace;
 use mixlab_protocol as protocol;
-use mixlab_protocol::{WorkspaceState, PerformanceInfo};
+use mixlab_protocol::{WorkspaceState, WorkspaceOp, PerformanceInfo, ProjectInfo, BackupStatus, MediaSyncStatus, SnapshotId, SnapshotInfo, GuestToken, GuestTokenRequest};
 
 use crate::db;
 use crate::engine::{self, EngineHandle, EngineEvents, EngineError, EngineSession, WorkspaceEmbryo};
 use crate::persist;
 
+pub mod backup;
+pub mod copy;
+pub mod guest;
+pub mod loudness;
 pub mod stream;
 pub mod media;
+pub mod snapshot;
+pub mod sync;
+
+// minimum time between automatic backups of the workspace:
+const BACKUP_INTERVAL: Duration = Duration::from_secs(5 * 60);
 
 #[derive(Clone)]
 pub struct ProjectHandle {
     base: ProjectBaseRef,
     engine: EngineHandle,
     notify: NotifyRx,
+    workspace: watch::Receiver<persist::Workspace>,
 }
 
 pub struct ProjectBase {
@@ -33,6 +47,17 @@ pub struct ProjectBase {
     // in the blocking context and pass it as an Arc rather than a reference
     database: Arc<std::sync::Mutex<Connection>>,
 
+    // deleted on close unless kept:
+    scratch: AtomicBool,
+
+    backup: Option<backup::Backup>,
+
+    // latest push of the media library to another instance:
+    media_sync: std::sync::Mutex<Option<MediaSyncStatus>>,
+
+    // live guest tokens by token:
+    guests: std::sync::Mutex<HashMap<String, GuestToken>>,
+
     notify: NotifyTx,
 }
 
@@ -49,7 +74,13 @@ pub enum OpenError {
     Io(io::Error),
     Json(serde_json::Error),
     Database(rusqlite::Error),
+    Config(engine::ConfigError),
+    Duplicate(copy::DuplicateError),
+    Guest(guest::GuestError),
     NotDirectory,
+    NotFound,
+    #[from(ignore)]
+    NoSuchModule(protocol::ModuleId),
 }
 
 impl ProjectBase {
@@ -65,14 +96,65 @@ impl ProjectBase {
         }).await.expect("blocking database section")
     }
 
-    async fn attach(path: PathBuf, notify: NotifyTx) -> Result<Self, rusqlite::Error> {
-        let mut sqlite_path = path.clone();
-        sqlite_path.set_extension("mixlab");
-        let database = db::attach(sqlite_path).await?;
+    /// Loads a snapshot to be restored over `current`, which is backed up
+    /// first so that the restore can be undone
+    pub async fn recall_snapshot(&self, id: SnapshotId, current: &persist::Workspace) -> Result<persist::Workspace, RestoreError> {
+        let restored = snapshot::load(self, id).await?;
+
+        snapshot::backup(self, current).await?;
+        let _ = self.notify.snapshots.broadcast(());
+
+        Ok(restored)
+    }
+
+    /// Recordings are kept in a directory alongside the project database
+    pub fn recordings_dir(&self) -> PathBuf {
+        copy::recordings_path(&self.path)
+    }
+
+    /// Loudness logs are kept in a directory alongside the project database,
+    /// one subdirectory per source
+    pub fn loudness_dir(&self) -> PathBuf {
+        copy::loudness_path(&self.path)
+    }
+
+    /// Queues a finished recording to be backed up, if the project has a
+    /// backup target
+    pub fn recording_finished(&self, path: PathBuf) {
+        if let Some(backup) = &self.backup {
+            backup.queue_recording(path);
+        }
+    }
+
+    fn update_media_sync(&self, f: impl FnOnce(&mut MediaSyncStatus)) {
+        if let Some(status) = self.media_sync.lock().unwrap().as_mut() {
+            f(status);
+        }
+
+        let _ = self.notify.media_sync.broadcast(());
+    }
+
+    pub fn info(&self) -> ProjectInfo {
+        let name = self.path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        ProjectInfo {
+            name,
+            scratch: self.scratch.load(Ordering::SeqCst),
+        }
+    }
+
+    async fn attach(path: PathBuf, scratch: bool, backup: Option<backup::Backup>, notify: NotifyTx) -> Result<Self, rusqlite::Error> {
+        let database = db::attach(copy::database_path(&path)).await?;
 
         Ok(ProjectBase {
             path,
             database: Arc::new(std::sync::Mutex::new(database)),
+            scratch: AtomicBool::new(scratch),
+            backup,
+            media_sync: std::sync::Mutex::new(None),
+            guests: std::sync::Mutex::new(HashMap::new()),
             notify,
         })
     }
@@ -83,12 +165,27 @@ impl ProjectBase {
                 |row| row.get(0)).optional()
         }).await?;
 
-        let workspace = match serialized {
-            Some(serialized) => serde_json::from_slice(&serialized)?,
-            None => persist::Workspace::default(),
+        let serialized = match serialized {
+            Some(serialized) => serialized,
+            None => { return Ok(persist::Workspace::default()); }
         };
 
-        Ok(workspace)
+        match serde_json::from_slice(&serialized) {
+            Ok(workspace) => Ok(workspace),
+            Err(e) => {
+                eprintln!("project: could not read workspace: {:?}", e);
+
+                let recovered = self.with_database(|conn| snapshot::newest_valid(conn)).await?;
+
+                match recovered {
+                    Some((id, workspace)) => {
+                        eprintln!("project: recovered workspace from snapshot {}", id.0);
+                        Ok(workspace)
+                    }
+                    None => Err(e.into()),
+                }
+            }
+        }
     }
 
     async fn write_workspace(&self, workspace: &persist::Workspace) -> Result<(), rusqlite::Error> {
@@ -106,20 +203,138 @@ impl ProjectBase {
     }
 }
 
-pub async fn open_or_create(path: PathBuf) -> Result<ProjectHandle, OpenError> {
-    let (notify_tx, notify_rx) = notify();
-    let base = ProjectBase::attach(path, notify_tx).await?;
+/// Overrides for the engine settings stored in a project
+#[derive(Debug, Default)]
+pub struct EngineSettings {
+    pub sample_rate: Option<usize>,
+    pub block_size: Option<usize>,
+    pub color_space: Option<ColorSpace>,
+}
+
+impl EngineSettings {
+    // returns whether any setting was overridden
+    fn apply(&self, workspace: &mut persist::Workspace) -> bool {
+        workspace.config.sample_rate = self.sample_rate.unwrap_or(workspace.config.sample_rate);
+        workspace.config.block_size = self.block_size.unwrap_or(workspace.config.block_size);
+        workspace.config.color_space = self.color_space.unwrap_or(workspace.config.color_space);
+
+        self.sample_rate.is_some() || self.block_size.is_some() || self.color_space.is_some()
+    }
+}
+
+pub async fn open_or_create(path: PathBuf, settings: EngineSettings, backup: Option<backup::BackupConfig>) -> Result<ProjectHandle, OpenError> {
+    open(path, false, settings, backup).await
+}
+
+/// Opens a throwaway copy of the project at `path`, or a new empty project
+/// if there's nothing there yet. The copy is deleted when closed, unless
+/// it's kept in the meantime
+pub async fn open_scratch(path: PathBuf, settings: EngineSettings, backup: Option<backup::BackupConfig>) -> Result<ProjectHandle, OpenError> {
+    let scratch_path = copy::scratch_path(&path);
+
+    if copy::exists(&path) {
+        task::spawn_blocking({
+            let scratch_path = scratch_path.clone();
+            move || copy::copy_project(&path, &scratch_path)
+        }).await.expect("copy project")?;
+    }
+
+    open(scratch_path, true, settings, backup).await
+}
+
+/// Opens the project headlessly, runs it through engine::check and closes it
+/// again. Engine settings given are tried out without being saved
+pub async fn check(path: PathBuf, settings: EngineSettings, ticks: u64) -> Result<engine::CheckReport, OpenError> {
+    if !copy::exists(&path) {
+        return Err(OpenError::NotFound);
+    }
+
+    let (notify_tx, _notify_rx) = notify();
+    let base = ProjectBase::attach(path, false, None, notify_tx).await?;
+    let mut workspace = base.read_workspace().await?;
+
+    settings.apply(&mut workspace);
+    workspace.config.validate()?;
+
+    Ok(engine::check(workspace, Arc::new(base), ticks).await)
+}
+
+/// Opens the project headlessly and runs it through engine::bench at every
+/// combination of the sample rates and block sizes given, the project's own
+/// where none are
+pub async fn bench(path: PathBuf, sample_rates: Vec<usize>, block_sizes: Vec<usize>, target: engine::BenchTarget, ticks: u64) -> Result<engine::BenchReport, OpenError> {
+    if !copy::exists(&path) {
+        return Err(OpenError::NotFound);
+    }
+
+    let (notify_tx, _notify_rx) = notify();
+    let base = ProjectBase::attach(path, false, None, notify_tx).await?;
     let workspace = base.read_workspace().await?;
 
+    if let engine::BenchTarget::Module(id) = target {
+        if !workspace.modules.contains_key(&id) {
+            return Err(OpenError::NoSuchModule(id));
+        }
+    }
+
+    let own = workspace.config;
+    let sample_rates = if sample_rates.is_empty() { vec![own.sample_rate] } else { sample_rates };
+    let block_sizes = if block_sizes.is_empty() { vec![own.block_size] } else { block_sizes };
+
+    let mut configs = Vec::new();
+
+    for sample_rate in &sample_rates {
+        for block_size in &block_sizes {
+            let config = engine::EngineConfig { sample_rate: *sample_rate, block_size: *block_size, ..own };
+            config.validate()?;
+            configs.push(config);
+        }
+    }
+
+    Ok(engine::bench(workspace, Arc::new(base), target, configs, ticks).await)
+}
+
+async fn open(path: PathBuf, scratch: bool, settings: EngineSettings, backup: Option<backup::BackupConfig>) -> Result<ProjectHandle, OpenError> {
+    let (notify_tx, notify_rx) = notify();
+
+    let (backup, backup_worker) = match backup {
+        Some(config) => {
+            let (backup, worker) = backup::new(config);
+            (Some(backup), Some(worker))
+        }
+        None => (None, None),
+    };
+
+    let base = ProjectBase::attach(path, scratch, backup, notify_tx).await?;
+    guest::load(&base).await?;
+    let mut workspace = base.read_workspace().await?;
+
+    let overridden = settings.apply(&mut workspace);
+
+    // refuse to start the engine with settings it can't honour
+    workspace.config.validate()?;
+
+    if overridden {
+        base.write_workspace(&workspace).await?;
+    }
+
     let base = Arc::new(base);
+    sync::backfill(base.clone());
+
+    if let Some(worker) = backup_worker {
+        worker.start(base.clone(), notify_rx.snapshots.clone()).await?;
+    }
 
     // start engine update thread
-    let (embryo, mut persist_rx) = WorkspaceEmbryo::new(workspace);
+    let (embryo, persist_rx) = WorkspaceEmbryo::new(workspace);
     let engine = engine::start(runtime::Handle::current(), embryo, base.clone());
 
     task::spawn({
         let base = base.clone();
+        let mut persist_rx = persist_rx.clone();
         async move {
+            let mut last_backup: Option<Instant> = None;
+
             while let Some(workspace) = persist_rx.recv().await {
                 match base.write_workspace(&workspace).await {
                     Ok(()) => {}
@@ -127,6 +342,21 @@ pub async fn open_or_create(path: PathBuf) -> Result<ProjectHandle, OpenError> {
                         eprintln!("project: could not persist workspace: {:?}", e);
                     }
                 }
+
+                let backup_due = last_backup
+                    .map(|time| time.elapsed() >= BACKUP_INTERVAL)
+                    .unwrap_or(true);
+
+                if backup_due {
+                    last_backup = Some(Instant::now());
+
+                    match snapshot::backup(&base, &workspace).await {
+                        Ok(()) => { let _ = base.notify.snapshots.broadcast(()); }
+                        Err(e) => {
+                            eprintln!("project: could not back up workspace: {:?}", e);
+                        }
+                    }
+                }
             }
         }
     });
@@ -135,6 +365,7 @@ pub async fn open_or_create(path: PathBuf) -> Result<ProjectHandle, OpenError> {
         base,
         engine,
         notify: notify_rx,
+        workspace: persist_rx,
     })
 }
 
@@ -146,7 +377,73 @@ impl ProjectHandle {
     pub fn notifications(&self) -> impl Stream<Item = Notification> {
         let perf_info = self.engine.performance_info().map(Notification::PerformanceInfo);
         let media = self.notify.media.clone().map(|()| Notification::MediaLibrary);
-        futures::stream::select(perf_info, media)
+        let snapshots = self.notify.snapshots.clone().map(|()| Notification::Snapshots);
+        let project = self.notify.project.clone().map(|()| Notification::Project);
+        let backup = self.notify.backup.clone().map(|()| Notification::Backup);
+        let media_sync = self.notify.media_sync.clone().map(|()| Notification::MediaSync);
+        let guests = self.notify.guests.clone().map(|()| Notification::GuestTokens);
+        futures::stream::select(perf_info,
+            futures::stream::select(media,
+                futures::stream::select(snapshots,
+                    futures::stream::select(project,
+                        futures::stream::select(backup,
+                            futures::stream::select(media_sync, guests))))))
+    }
+
+    pub fn guest_token(&self, token: &str) -> Option<GuestToken> {
+        guest::lookup(&self.base, token)
+    }
+
+    pub fn guest_tokens(&self) -> Vec<GuestToken> {
+        guest::list(&self.base)
+    }
+
+    pub async fn create_guest_token(&self, request: GuestTokenRequest) -> Result<GuestToken, rusqlite::Error> {
+        guest::create(&self.base, request).await
+    }
+
+    pub async fn revoke_guest_token(&self, token: String) -> Result<(), rusqlite::Error> {
+        guest::revoke(&self.base, token).await
+    }
+
+    /// Whether the guest may apply the op, checked against the workspace
+    /// as last persisted
+    pub fn guest_permits(&self, guest: &GuestToken, op: &WorkspaceOp) -> bool {
+        guest::permits(&guest.scope, &self.workspace.borrow(), op)
+    }
+
+    pub fn backup_status(&self) -> Option<BackupStatus> {
+        self.base.backup.as_ref().map(|backup| backup.status())
+    }
+
+    pub fn info(&self) -> ProjectInfo {
+        self.base.info()
+    }
+
+    /// Copies the project to a new project of the given name beside it,
+    /// which can be opened separately
+    pub async fn duplicate(&self, name: String) -> Result<PathBuf, copy::DuplicateError> {
+        copy::duplicate(&self.base, &name).await
+    }
+
+    /// Keeps a scratch project from being deleted when closed
+    pub fn keep(&self) {
+        if self.base.scratch.swap(false, Ordering::SeqCst) {
+            let _ = self.base.notify.project.broadcast(());
+        }
+    }
+
+    /// Deletes the project if it's still a scratch project. Called as the
+    /// server exits
+    pub async fn close(&self) -> io::Result<()> {
+        if !self.base.scratch.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        // holding the database keeps any last writes from landing halfway
+        // through deleting it:
+        let path = self.base.path.clone();
+        self.base.with_database(move |_| copy::delete(&path)).await
     }
 
     pub async fn begin_media_upload(&self, info: media::UploadInfo) -> Result<media::MediaUpload, media::UploadError> {
@@ -156,31 +453,150 @@ impl ProjectHandle {
     pub async fn fetch_media_library(&self) -> Result<protocol::MediaLibrary, rusqlite::Error> {
         media::library(&self.base).await
     }
+
+    pub async fn loudness_sources(&self) -> io::Result<BTreeMap<String, Vec<String>>> {
+        loudness::sources(self.base.loudness_dir()).await
+    }
+
+    pub async fn loudness_day(&self, source: String, date: String) -> io::Result<Option<loudness::LoudnessDay>> {
+        loudness::day(self.base.loudness_dir(), source, date).await
+    }
+
+    /// Renders a preview of an output and what feeds it as it stands now,
+    /// see engine::preview
+    pub async fn preview(&self, request: engine::PreviewRequest) -> Result<Vec<u8>, engine::PreviewError> {
+        let workspace = self.workspace.borrow().clone();
+        engine::preview(workspace, self.base.clone(), request).await
+    }
+
+    pub async fn media_manifest(&self) -> Result<Vec<sync::ManifestItem>, rusqlite::Error> {
+        let manifest = sync::manifest(&self.base).await?;
+        Ok(manifest.into_iter().map(|(_, item)| item).collect())
+    }
+
+    /// Starts pushing any media the instance at `remote` lacks to it in the
+    /// background. Returns false without starting if a push is already
+    /// underway
+    pub fn push_media(&self, remote: String) -> bool {
+        {
+            let mut media_sync = self.base.media_sync.lock().unwrap();
+
+            if media_sync.as_ref().map(|status| !status.finished).unwrap_or(false) {
+                return false;
+            }
+
+            *media_sync = Some(MediaSyncStatus {
+                remote: sync::display_remote(&remote),
+                missing: None,
+                transferred: 0,
+                uploading: None,
+                finished: false,
+                error: None,
+            });
+        }
+
+        let base = self.base.clone();
+
+        task::spawn(async move {
+            let result = sync::push(&base, &remote).await;
+
+            if let Err(e) = &result {
+                eprintln!("project: could not push media to {}: {:?}", sync::display_remote(&remote), e);
+            }
+
+            base.update_media_sync(|status| {
+                status.uploading = None;
+                status.finished = true;
+                status.error = result.err().map(|e| format!("{:?}", e));
+            });
+        });
+
+        true
+    }
+
+    pub fn media_sync_status(&self) -> Option<MediaSyncStatus> {
+        self.base.media_sync.lock().unwrap().clone()
+    }
+
+    pub async fn fetch_snapshots(&self) -> Result<Vec<SnapshotInfo>, rusqlite::Error> {
+        snapshot::list(&self.base).await
+    }
+
+    pub async fn create_snapshot(&self, name: String) -> Result<SnapshotId, rusqlite::Error> {
+        let workspace = self.workspace.borrow().clone();
+        let id = snapshot::create(&self.base, name, &workspace).await?;
+        let _ = self.base.notify.snapshots.broadcast(());
+        Ok(id)
+    }
+
+    pub async fn restore_snapshot(&self, id: SnapshotId) -> Result<(), RestoreError> {
+        let current = self.workspace.borrow().clone();
+        let restored = self.base.recall_snapshot(id, &current).await?;
+        self.engine.restore(restored)?;
+        Ok(())
+    }
+}
+
+#[derive(From, Debug)]
+pub enum RestoreError {
+    Snapshot(snapshot::SnapshotError),
+    Database(rusqlite::Error),
+    Engine(EngineError),
 }
 
 pub enum Notification {
     PerformanceInfo(Arc<PerformanceInfo>),
     MediaLibrary,
+    Snapshots,
+    Project,
+    Backup,
+    MediaSync,
+    GuestTokens,
 }
 
 pub struct NotifyTx {
     media: watch::Sender<()>,
+    snapshots: watch::Sender<()>,
+    project: watch::Sender<()>,
+    backup: watch::Sender<()>,
+    media_sync: watch::Sender<()>,
+    guests: watch::Sender<()>,
 }
 
 #[derive(Clone)]
 pub struct NotifyRx {
     media: watch::Receiver<()>,
+    snapshots: watch::Receiver<()>,
+    project: watch::Receiver<()>,
+    backup: watch::Receiver<()>,
+    media_sync: watch::Receiver<()>,
+    guests: watch::Receiver<()>,
 }
 
 pub fn notify() -> (NotifyTx, NotifyRx) {
     let (media_tx, media_rx) = watch::channel(());
+    let (snapshots_tx, snapshots_rx) = watch::channel(());
+    let (project_tx, project_rx) = watch::channel(());
+    let (backup_tx, backup_rx) = watch::channel(());
+    let (media_sync_tx, media_sync_rx) = watch::channel(());
+    let (guests_tx, guests_rx) = watch::channel(());
 
     let tx = NotifyTx {
         media: media_tx,
+        snapshots: snapshots_tx,
+        project: project_tx,
+        backup: backup_tx,
+        media_sync: media_sync_tx,
+        guests: guests_tx,
     };
 
     let rx = NotifyRx {
         media: media_rx,
+        snapshots: snapshots_rx,
+        project: project_rx,
+        backup: backup_rx,
+        media_sync: media_sync_rx,
+        guests: guests_rx,
     };
 
     (tx, rx)
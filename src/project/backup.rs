@@ -0,0 +1,410 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use derive_more::From;
+use futures::future::{self, BoxFuture};
+use futures::stream::{self, BoxStream, StreamExt};
+use rusqlite::{params, OptionalExtension};
+use tokio::sync::{mpsc, watch};
+use tokio::{task, time};
+
+use mixlab_protocol::{BackupStatus, SnapshotId};
+
+use crate::project::{ProjectBase, ProjectBaseRef};
+use crate::util;
+
+pub mod s3;
+
+// a file is given up on after failing this many times, backing off between
+// attempts:
+const MAX_ATTEMPTS: usize = 10;
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+// recordings are read and sent in chunks of this size, which is also the
+// granularity of the bandwidth limit:
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Somewhere backups can be uploaded to
+pub trait BackupTarget: Send + Sync {
+    /// Describes the target for display
+    fn describe(&self) -> String;
+
+    /// Uploads a file under `key`, replacing anything already there
+    fn upload(&self, key: &str, upload: Upload) -> BoxFuture<'static, Result<(), UploadError>>;
+}
+
+pub struct Upload {
+    pub length: u64,
+    pub body: BoxStream<'static, io::Result<Bytes>>,
+}
+
+#[derive(From, Debug)]
+pub enum UploadError {
+    Io(io::Error),
+    Http(hyper::Error),
+    Request(http::Error),
+    Database(rusqlite::Error),
+    #[from(ignore)]
+    Status(http::StatusCode, String),
+}
+
+pub struct BackupConfig {
+    pub target: Arc<dyn BackupTarget>,
+    // bytes per second, unlimited if None:
+    pub rate_limit: Option<u64>,
+}
+
+/// Queues the project's snapshots and finished recordings for upload to a
+/// backup target. Files that have been uploaded are noted in the project
+/// database, so that whatever was missed last time is picked up on open
+pub struct Backup {
+    jobs: mpsc::UnboundedSender<Job>,
+    status: Arc<Mutex<BackupStatus>>,
+}
+
+pub struct BackupWorker {
+    config: BackupConfig,
+    jobs: mpsc::UnboundedReceiver<Job>,
+    status: Arc<Mutex<BackupStatus>>,
+}
+
+enum Job {
+    Snapshot(SnapshotId),
+    Recording(PathBuf),
+}
+
+struct Pending {
+    key: String,
+    job: Job,
+    attempts: usize,
+    not_before: Instant,
+}
+
+pub fn new(config: BackupConfig) -> (Backup, BackupWorker) {
+    let (jobs_tx, jobs_rx) = mpsc::unbounded_channel();
+
+    let status = Arc::new(Mutex::new(BackupStatus {
+        target: config.target.describe(),
+        ..BackupStatus::default()
+    }));
+
+    let backup = Backup {
+        jobs: jobs_tx,
+        status: status.clone(),
+    };
+
+    let worker = BackupWorker {
+        config,
+        jobs: jobs_rx,
+        status,
+    };
+
+    (backup, worker)
+}
+
+impl Backup {
+    pub fn status(&self) -> BackupStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn queue_recording(&self, path: PathBuf) {
+        // only fails once the worker has stopped with the server:
+        let _ = self.jobs.send(Job::Recording(path));
+    }
+}
+
+impl BackupWorker {
+    /// Picks up recordings left over from last time and starts uploading in
+    /// the background. Must be called before the engine starts, so that no
+    /// recording is still being written to as it's found
+    pub async fn start(self, base: ProjectBaseRef, snapshots: watch::Receiver<()>) -> Result<(), rusqlite::Error> {
+        let uploaded = uploaded_keys(&base).await?;
+        let now = Instant::now();
+
+        let mut recordings = Vec::new();
+
+        if let Err(e) = find_files(&base.recordings_dir(), &mut recordings) {
+            eprintln!("backup: could not list recordings: {:?}", e);
+        }
+
+        let queue = recordings.into_iter()
+            .filter_map(|path| {
+                let key = recording_key(&base, &path)?;
+                Some(Pending { key, job: Job::Recording(path), attempts: 0, not_before: now })
+            })
+            .filter(|pending| !uploaded.contains(&pending.key))
+            .collect();
+
+        task::spawn(self.run(base, snapshots, queue));
+        Ok(())
+    }
+
+    async fn run(mut self, base: ProjectBaseRef, mut snapshots: watch::Receiver<()>, mut queue: Vec<Pending>) {
+        let mut queued = queue.iter().map(|pending| pending.key.clone()).collect::<HashSet<_>>();
+
+        loop {
+            self.update_status(&base, |status| status.pending = queue.len());
+
+            let now = Instant::now();
+
+            let index = match queue.iter().position(|pending| pending.not_before <= now) {
+                Some(index) => index,
+                None => {
+                    let next_retry = queue.iter().map(|pending| pending.not_before).min();
+
+                    let retry = async move {
+                        match next_retry {
+                            Some(instant) => time::delay_until(time::Instant::from_std(instant)).await,
+                            None => future::pending().await,
+                        }
+                    };
+
+                    tokio::select! {
+                        job = self.jobs.recv() => {
+                            let job = match job {
+                                Some(job) => job,
+                                None => { return; }
+                            };
+
+                            let key = match &job {
+                                Job::Snapshot(id) => Some(snapshot_key(*id)),
+                                Job::Recording(path) => recording_key(&base, path),
+                            };
+
+                            if let Some(key) = key {
+                                if queued.insert(key.clone()) {
+                                    queue.push(Pending { key, job, attempts: 0, not_before: now });
+                                }
+                            }
+                        }
+                        Some(()) = snapshots.recv() => {
+                            match pending_snapshots(&base).await {
+                                Ok(ids) => {
+                                    for id in ids {
+                                        let key = snapshot_key(id);
+
+                                        if queued.insert(key.clone()) {
+                                            queue.push(Pending { key, job: Job::Snapshot(id), attempts: 0, not_before: now });
+                                        }
+                                    }
+                                }
+                                Err(e) => {
+                                    eprintln!("backup: could not list snapshots: {:?}", e);
+                                }
+                            }
+                        }
+                        _ = retry => {}
+                    }
+
+                    continue;
+                }
+            };
+
+            let mut pending = queue.remove(index);
+
+            self.update_status(&base, |status| {
+                status.uploading = Some(pending.key.clone());
+                status.pending = queue.len();
+            });
+
+            let result = self.upload(&base, &pending).await;
+
+            self.update_status(&base, |status| status.uploading = None);
+
+            match result {
+                Ok(()) => {
+                    queued.remove(&pending.key);
+
+                    if let Err(e) = mark_uploaded(&base, pending.key.clone()).await {
+                        eprintln!("backup: could not record upload of {}: {:?}", pending.key, e);
+                    }
+
+                    self.update_status(&base, |status| status.uploaded += 1);
+                }
+                Err(e) => {
+                    eprintln!("backup: could not upload {}: {:?}", pending.key, e);
+
+                    pending.attempts += 1;
+
+                    let given_up = pending.attempts >= MAX_ATTEMPTS;
+
+                    self.update_status(&base, |status| {
+                        status.last_error = Some(format!("{}: {:?}", pending.key, e));
+
+                        if given_up {
+                            status.failed += 1;
+                        }
+                    });
+
+                    if given_up {
+                        queued.remove(&pending.key);
+                    } else {
+                        pending.not_before = Instant::now() + retry_delay(pending.attempts);
+                        queue.push(pending);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn upload(&self, base: &ProjectBase, pending: &Pending) -> Result<(), UploadError> {
+        let upload = match &pending.job {
+            Job::Snapshot(id) => {
+                match load_snapshot(base, *id).await? {
+                    Some(upload) => upload,
+                    // rotated out before its turn came:
+                    None => { return Ok(()); }
+                }
+            }
+            Job::Recording(path) => open_recording(path)?,
+        };
+
+        let upload = match self.config.rate_limit {
+            Some(rate) => throttle(upload, rate),
+            None => upload,
+        };
+
+        let key = format!("{}/{}", base.info().name, pending.key);
+        self.config.target.upload(&key, upload).await
+    }
+
+    fn update_status(&self, base: &ProjectBase, f: impl FnOnce(&mut BackupStatus)) {
+        f(&mut self.status.lock().unwrap());
+        let _ = base.notify.backup.broadcast(());
+    }
+}
+
+fn retry_delay(attempts: usize) -> Duration {
+    let factor = 1u32 << attempts.min(16);
+    (MIN_RETRY_DELAY * factor).min(MAX_RETRY_DELAY)
+}
+
+fn snapshot_key(id: SnapshotId) -> String {
+    format!("snapshots/{}.json", id.0)
+}
+
+fn recording_key(base: &ProjectBase, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(base.recordings_dir()).ok()?;
+
+    let components = relative.components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(format!("recordings/{}", components.join("/")))
+}
+
+fn find_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            find_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+async fn uploaded_keys(base: &ProjectBase) -> Result<HashSet<String>, rusqlite::Error> {
+    base.with_database(|conn| -> Result<HashSet<String>, rusqlite::Error> {
+        conn.prepare("SELECT key FROM backup_uploads")?
+            .query_map(rusqlite::NO_PARAMS, |row| row.get(0))?
+            .collect()
+    }).await
+}
+
+async fn mark_uploaded(base: &ProjectBase, key: String) -> Result<(), rusqlite::Error> {
+    let uploaded_at = util::unix_time() as i64;
+
+    base.with_database(move |conn| -> Result<(), rusqlite::Error> {
+        conn.execute(r"
+                INSERT INTO backup_uploads (key, uploaded_at) VALUES (?, ?)
+                ON CONFLICT (key) DO UPDATE SET uploaded_at = excluded.uploaded_at
+            ",
+            params![key, uploaded_at])?;
+
+        Ok(())
+    }).await
+}
+
+async fn pending_snapshots(base: &ProjectBase) -> Result<Vec<SnapshotId>, rusqlite::Error> {
+    base.with_database(|conn| -> Result<Vec<SnapshotId>, rusqlite::Error> {
+        conn.prepare(r"
+                SELECT id FROM workspace_snapshots
+                WHERE 'snapshots/' || id || '.json' NOT IN (SELECT key FROM backup_uploads)
+                ORDER BY id
+            ")?
+            .query_map(rusqlite::NO_PARAMS, |row| Ok(SnapshotId(row.get(0)?)))?
+            .collect()
+    }).await
+}
+
+async fn load_snapshot(base: &ProjectBase, id: SnapshotId) -> Result<Option<Upload>, rusqlite::Error> {
+    let serialized = base.with_database(move |conn| -> Result<Option<Vec<u8>>, rusqlite::Error> {
+        conn.query_row("SELECT serialized FROM workspace_snapshots WHERE id = ?",
+            params![id.0],
+            |row| row.get(0)).optional()
+    }).await?;
+
+    Ok(serialized.map(|serialized| Upload {
+        length: serialized.len() as u64,
+        body: stream::once(future::ready(Ok(Bytes::from(serialized)))).boxed(),
+    }))
+}
+
+fn open_recording(path: &Path) -> io::Result<Upload> {
+    let file = File::open(path)?;
+    let length = file.metadata()?.len();
+
+    let body = stream::unfold(Some(file), |file| async move {
+        let mut file = file?;
+
+        let read = task::spawn_blocking(move || -> io::Result<(File, Vec<u8>)> {
+            let mut buf = vec![0; CHUNK_SIZE];
+            let len = file.read(&mut buf)?;
+            buf.truncate(len);
+            Ok((file, buf))
+        }).await.expect("read recording");
+
+        match read {
+            Ok((_, buf)) if buf.is_empty() => None,
+            Ok((file, buf)) => Some((Ok(Bytes::from(buf)), Some(file))),
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+
+    Ok(Upload { length, body: body.boxed() })
+}
+
+// holds each chunk back until sending it keeps the upload within rate:
+fn throttle(upload: Upload, rate: u64) -> Upload {
+    let started = Instant::now();
+    let mut sent = 0u64;
+
+    let body = upload.body.then(move |chunk| {
+        let due = started + Duration::from_secs_f64(sent as f64 / rate as f64);
+
+        if let Ok(bytes) = &chunk {
+            sent += bytes.len() as u64;
+        }
+
+        async move {
+            time::delay_until(time::Instant::from_std(due)).await;
+            chunk
+        }
+    });
+
+    Upload { length: upload.length, body: body.boxed() }
+}
@@ -0,0 +1,192 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+use derive_more::From;
+use tokio::task;
+
+use crate::project::ProjectBase;
+
+#[derive(From, Debug)]
+pub enum DuplicateError {
+    Io(io::Error),
+    #[from(ignore)]
+    BadName(String),
+    #[from(ignore)]
+    AlreadyExists(PathBuf),
+}
+
+// a project is its database along with the recordings directory beside it,
+// both named after the project's path:
+
+pub fn database_path(path: &Path) -> PathBuf {
+    path.with_extension("mixlab")
+}
+
+pub fn recordings_path(path: &Path) -> PathBuf {
+    path.with_extension("recordings")
+}
+
+pub fn loudness_path(path: &Path) -> PathBuf {
+    path.with_extension("loudness")
+}
+
+pub fn exists(path: &Path) -> bool {
+    database_path(path).exists()
+}
+
+/// Duplicates an open project to a new project of the given name, alongside
+/// it in the same directory
+pub async fn duplicate(base: &ProjectBase, name: &str) -> Result<PathBuf, DuplicateError> {
+    let dest = sibling(&base.path, name)?;
+    let source = base.path.clone();
+
+    // every write to the database goes through its one connection, so
+    // holding it makes for a consistent copy. only the database is copied
+    // while it's held, as everything else waits on it meanwhile:
+    base.with_database({
+        let source = source.clone();
+        let dest = dest.clone();
+        move |_| copy_database(&source, &dest)
+    }).await?;
+
+    task::spawn_blocking({
+        let dest = dest.clone();
+        move || copy_files(&source, &dest)
+    }).await.expect("spawn_blocking")?;
+
+    Ok(dest)
+}
+
+/// Copies a project which isn't open to a new path. The copy shares its
+/// storage with the original where the filesystem can clone files, so even
+/// projects with a large media library duplicate quickly
+pub fn copy_project(source: &Path, dest: &Path) -> Result<(), DuplicateError> {
+    copy_database(source, dest)?;
+    copy_files(source, dest)
+}
+
+fn copy_database(source: &Path, dest: &Path) -> Result<(), DuplicateError> {
+    if exists(dest) {
+        return Err(DuplicateError::AlreadyExists(dest.to_owned()));
+    }
+
+    clone_path(&database_path(source), &database_path(dest))?;
+    Ok(())
+}
+
+// the recordings and loudness logs beside the database
+fn copy_files(source: &Path, dest: &Path) -> Result<(), DuplicateError> {
+    let recordings = recordings_path(source);
+
+    if recordings.exists() {
+        clone_path(&recordings, &recordings_path(dest))?;
+    }
+
+    let loudness = loudness_path(source);
+
+    if loudness.exists() {
+        clone_path(&loudness, &loudness_path(dest))?;
+    }
+
+    Ok(())
+}
+
+/// Picks a path for a scratch project started from the project at `path`,
+/// which need not exist
+pub fn scratch_path(path: &Path) -> PathBuf {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0);
+
+    let stem = path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "project".to_owned());
+
+    path.with_file_name(format!("{}-scratch-{}.mixlab", stem, stamp))
+}
+
+/// Deletes a project's database and recordings
+pub fn delete(path: &Path) -> io::Result<()> {
+    let database = database_path(path);
+    fs::remove_file(&database)?;
+
+    // left behind if sqlite was interrupted mid transaction:
+    let journal = database.with_extension("mixlab-journal");
+
+    if journal.exists() {
+        fs::remove_file(journal)?;
+    }
+
+    let recordings = recordings_path(path);
+
+    if recordings.exists() {
+        fs::remove_dir_all(recordings)?;
+    }
+
+    let loudness = loudness_path(path);
+
+    if loudness.exists() {
+        fs::remove_dir_all(loudness)?;
+    }
+
+    Ok(())
+}
+
+fn sibling(path: &Path, name: &str) -> Result<PathBuf, DuplicateError> {
+    let name = name.trim();
+
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && !name.contains(|c: char| c == '/' || c == '\\' || c.is_control());
+
+    if !valid {
+        return Err(DuplicateError::BadName(name.to_owned()));
+    }
+
+    // appended rather than set, so that a dot in the name isn't taken for
+    // the start of an extension:
+    Ok(path.with_file_name(format!("{}.mixlab", name)))
+}
+
+// cp knows how to clone files on filesystems that support it, falling back
+// to a plain copy elsewhere
+#[cfg(target_os = "linux")]
+fn clone_path(source: &Path, dest: &Path) -> io::Result<()> {
+    run_cp(Command::new("cp").arg("-R").arg("--reflink=auto").arg(source).arg(dest))
+}
+
+#[cfg(target_os = "macos")]
+fn clone_path(source: &Path, dest: &Path) -> io::Result<()> {
+    // -c clones on apfs, but fails outright on filesystems that can't:
+    run_cp(Command::new("cp").arg("-R").arg("-c").arg(source).arg(dest))
+        .or_else(|_| run_cp(Command::new("cp").arg("-R").arg(source).arg(dest)))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn clone_path(source: &Path, dest: &Path) -> io::Result<()> {
+    if source.is_dir() {
+        fs::create_dir_all(dest)?;
+
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            clone_path(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+
+        Ok(())
+    } else {
+        fs::copy(source, dest).map(|_| ())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run_cp(command: &mut Command) -> io::Result<()> {
+    let status = command.status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::Other, format!("cp failed: {}", status)))
+    }
+}
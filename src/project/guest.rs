@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::mem;
+
+use derive_more::From;
+use rusqlite::params;
+use uuid::Uuid;
+
+use mixlab_protocol::{GuestScope, GuestToken, GuestTokenRequest, ModuleId, ModuleParams, MorphState, ServerUpdate, WorkspaceOp, WorkspaceState};
+
+use crate::persist;
+use crate::project::ProjectBase;
+use crate::util;
+
+// links are meant for the length of a show, not for handing out standing
+// access:
+pub const MAX_DURATION_SECS: u64 = 7 * 24 * 60 * 60;
+
+#[derive(From, Debug)]
+pub enum GuestError {
+    Database(rusqlite::Error),
+    Json(serde_json::Error),
+}
+
+/// Reads the live tokens into memory, where sessions check against them,
+/// and deletes the rest
+pub async fn load(base: &ProjectBase) -> Result<(), GuestError> {
+    let now = util::unix_time() as i64;
+
+    let rows = base.with_database(move |conn| -> Result<Vec<(String, String, String, i64)>, rusqlite::Error> {
+        conn.execute("DELETE FROM guest_tokens WHERE expires_at <= ?", params![now])?;
+
+        conn.prepare("SELECT token, label, scope, expires_at FROM guest_tokens")?
+            .query_map(rusqlite::NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .collect()
+    }).await?;
+
+    let mut tokens = HashMap::new();
+
+    for (token, label, scope, expires_at) in rows {
+        let scope = serde_json::from_str(&scope)?;
+        tokens.insert(token.clone(), GuestToken { token, label, scope, expires_at });
+    }
+
+    *base.guests.lock().unwrap() = tokens;
+    Ok(())
+}
+
+pub async fn create(base: &ProjectBase, request: GuestTokenRequest) -> Result<GuestToken, rusqlite::Error> {
+    let duration = request.duration_secs.min(MAX_DURATION_SECS);
+
+    let token = GuestToken {
+        token: Uuid::new_v4().to_simple().to_string(),
+        label: request.label,
+        scope: request.scope,
+        expires_at: (util::unix_time() + duration) as i64,
+    };
+
+    let scope = serde_json::to_string(&token.scope).expect("serde_json::to_string");
+
+    base.with_database({
+        let token = token.clone();
+        move |conn| {
+            conn.execute("INSERT INTO guest_tokens (token, label, scope, expires_at) VALUES (?, ?, ?, ?)",
+                params![token.token, token.label, scope, token.expires_at])
+        }
+    }).await?;
+
+    base.guests.lock().unwrap().insert(token.token.clone(), token.clone());
+    let _ = base.notify.guests.broadcast(());
+
+    Ok(token)
+}
+
+pub async fn revoke(base: &ProjectBase, token: String) -> Result<(), rusqlite::Error> {
+    // taken out of memory first, so sessions using it are cut off even if
+    // the database can't be written:
+    base.guests.lock().unwrap().remove(&token);
+    let _ = base.notify.guests.broadcast(());
+
+    base.with_database(move |conn| {
+        conn.execute("DELETE FROM guest_tokens WHERE token = ?", params![token])
+    }).await?;
+
+    Ok(())
+}
+
+/// Looks up a token, as long as it hasn't expired or been revoked
+pub fn lookup(base: &ProjectBase, token: &str) -> Option<GuestToken> {
+    let now = util::unix_time() as i64;
+
+    base.guests.lock().unwrap().get(token)
+        .filter(|guest| guest.expires_at > now)
+        .cloned()
+}
+
+pub fn list(base: &ProjectBase) -> Vec<GuestToken> {
+    let mut tokens = base.guests.lock().unwrap().values().cloned().collect::<Vec<_>>();
+    tokens.sort_by_key(|guest| guest.expires_at);
+    tokens
+}
+
+/// Whether a guest with this scope may apply the op
+pub fn permits(scope: &GuestScope, workspace: &persist::Workspace, op: &WorkspaceOp) -> bool {
+    let in_scope = |module: ModuleId| match scope {
+        GuestScope::View(view) => workspace.views.get(view)
+            .map(|view| view.geometry(module).is_some())
+            .unwrap_or(false),
+    };
+
+    // guests only ever see these modules' params with secrets blanked out,
+    // so would blank them here too by sending them back whole:
+    let redacted = |module: ModuleId| workspace.modules.get(&module)
+        .map(|module| has_secrets(&module.params))
+        .unwrap_or(false);
+
+    // nor may guests point the server anywhere else on the network:
+    let redirects = |module: ModuleId, params: &ModuleParams| workspace.modules.get(&module)
+        .map(|module| changes_destination(&module.params, params))
+        .unwrap_or(true);
+
+    match op {
+        WorkspaceOp::UpdateModuleParams(module, params) => in_scope(*module)
+            && !redacted(*module)
+            && !redirects(*module, params),
+        WorkspaceOp::SetParam(param, _) => in_scope(param.module),
+        WorkspaceOp::SetMorph(module, _) => in_scope(*module),
+        // guests can't create modules, so a batch has nothing to stand in
+        // for:
+        WorkspaceOp::Batch(batch) => batch.placeholders.is_empty()
+            && batch.ops.iter().all(|op| permits(scope, workspace, op)),
+        _ => false,
+    }
+}
+
+/// Blanks out what guests mustn't see of a workspace, ie. stream ingests
+/// and keys, and api usernames
+pub fn redact_state(state: &mut WorkspaceState) {
+    for (_, params) in &mut state.modules {
+        redact(params);
+    }
+
+    for (_, morph) in &mut state.morphs {
+        redact_morph(morph);
+    }
+}
+
+pub fn redact_update(update: &mut ServerUpdate) {
+    match update {
+        ServerUpdate::CreateModule { params, .. } => redact(params),
+        ServerUpdate::UpdateModuleParams(_, params) => redact(params),
+        ServerUpdate::UpdateMorph(_, Some(morph)) => redact_morph(morph),
+        ServerUpdate::ReplaceWorkspace(state) => redact_state(state),
+        _ => {}
+    }
+}
+
+fn redact_morph(morph: &mut MorphState) {
+    for params in morph.a.iter_mut().chain(morph.b.iter_mut()) {
+        redact(params);
+    }
+}
+
+fn redact(params: &mut ModuleParams) {
+    match params {
+        ModuleParams::StreamOutput(params) => {
+            params.rtmp_url.clear();
+            params.rtmp_stream_key.clear();
+            params.backup_rtmp_url.clear();
+            params.backup_rtmp_stream_key.clear();
+        }
+        ModuleParams::HueLight(params) => {
+            params.username.clear();
+        }
+        _ => {}
+    }
+}
+
+// whether new params would have a module fetch from or send to a different
+// host. plugins' params are opaque, so any change to them might
+fn changes_destination(old: &ModuleParams, new: &ModuleParams) -> bool {
+    match (old, new) {
+        (ModuleParams::BrowserSource(old), ModuleParams::BrowserSource(new)) => old.url != new.url,
+        (ModuleParams::ArtNetOutput(old), ModuleParams::ArtNetOutput(new)) => old.destination != new.destination,
+        (ModuleParams::HueLight(old), ModuleParams::HueLight(new)) => old.bridge != new.bridge,
+        (ModuleParams::StreamOutput(old), ModuleParams::StreamOutput(new)) => {
+            old.rtmp_url != new.rtmp_url || old.backup_rtmp_url != new.backup_rtmp_url
+        }
+        (ModuleParams::Plugin(old), ModuleParams::Plugin(new)) => old.json != new.json || old.kind != new.kind,
+        // another kind of module altogether:
+        (old, new) => mem::discriminant(old) != mem::discriminant(new),
+    }
+}
+
+fn has_secrets(params: &ModuleParams) -> bool {
+    match params {
+        ModuleParams::StreamOutput(_) | ModuleParams::HueLight(_) => true,
+        _ => false,
+    }
+}
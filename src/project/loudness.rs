@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread;
+
+use serde::Serialize;
+use tokio::task;
+
+use crate::util;
+
+// entries waiting on the disk before new ones are dropped, a minute's worth:
+const QUEUE_LEN: usize = 60;
+
+/// A source's short-term loudness at one moment, none when it was silent
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct Entry {
+    pub time: u64,
+    pub lufs: Option<f64>,
+}
+
+/// A day's log of one source, as queried over http
+#[derive(Serialize, Debug)]
+pub struct LoudnessDay {
+    pub source: String,
+    pub date: String,
+    pub max_lufs: Option<f64>,
+    pub entries: Vec<Entry>,
+}
+
+/// Appends a source's loudness to a file per UTC day, starting the next
+/// day's file at midnight. Written from a thread of its own, so the engine
+/// never waits on the disk
+#[derive(Debug)]
+pub struct LoudnessLog {
+    tx: SyncSender<Entry>,
+}
+
+impl LoudnessLog {
+    pub fn start(dir: PathBuf) -> Self {
+        let (tx, rx) = mpsc::sync_channel(QUEUE_LEN);
+
+        thread::spawn(move || {
+            if let Err(e) = run_writer(&dir, rx) {
+                eprintln!("loudness: could not write log in {:?}: {:?}", dir, e);
+            }
+        });
+
+        LoudnessLog { tx }
+    }
+
+    /// Fails with `Full` if the disk is falling behind, in which case the
+    /// entry is dropped, or `Disconnected` once the log can't be written
+    pub fn log(&self, entry: Entry) -> Result<(), TrySendError<Entry>> {
+        self.tx.try_send(entry)
+    }
+}
+
+fn run_writer(dir: &Path, rx: Receiver<Entry>) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let mut current: Option<(String, fs::File)> = None;
+
+    for entry in rx {
+        let date = util::utc_date(entry.time);
+
+        let stale = current.as_ref()
+            .map(|(file_date, _)| *file_date != date)
+            .unwrap_or(true);
+
+        if stale {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(dir.join(format!("{}.tsv", date)))?;
+
+            current = Some((date, file));
+        }
+
+        let (_, file) = current.as_mut().unwrap();
+
+        let line = match entry.lufs {
+            Some(lufs) => format!("{}\t{:.1}\n", entry.time, lufs),
+            None => format!("{}\t-inf\n", entry.time),
+        };
+
+        // one unbuffered write per line, so that a crash loses at most the
+        // line being written, and loggers whose names come out as the same
+        // source append whole lines rather than interleave them:
+        file.write_all(line.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Source names as used for directory names, so that any name a user gives
+/// a logger stays inside the log directory
+pub fn source_name(name: &str) -> String {
+    let name = name.trim().chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect::<String>();
+
+    if name.is_empty() {
+        "program".to_owned()
+    } else {
+        name
+    }
+}
+
+/// Every source logged, with the days logged for each, oldest first
+pub async fn sources(dir: PathBuf) -> io::Result<BTreeMap<String, Vec<String>>> {
+    task::spawn_blocking(move || -> io::Result<_> {
+        let mut sources = BTreeMap::new();
+
+        if !dir.exists() {
+            return Ok(sources);
+        }
+
+        for source in fs::read_dir(&dir)? {
+            let source = source?;
+
+            if !source.file_type()?.is_dir() {
+                continue;
+            }
+
+            let mut days = fs::read_dir(source.path())?
+                .filter_map(|day| day.ok())
+                .filter_map(|day| day.file_name().into_string().ok())
+                .filter(|name| name.ends_with(".tsv"))
+                .map(|name| name[..name.len() - 4].to_owned())
+                .collect::<Vec<_>>();
+
+            days.sort();
+
+            sources.insert(source.file_name().to_string_lossy().into_owned(), days);
+        }
+
+        Ok(sources)
+    }).await.expect("spawn_blocking")
+}
+
+/// A day's log of a source, or none if there isn't one
+pub async fn day(dir: PathBuf, source: String, date: String) -> io::Result<Option<LoudnessDay>> {
+    // both go into a path, so are held to the names we write:
+    if source_name(&source) != source || !date.chars().all(|c| c.is_ascii_digit() || c == '-') {
+        return Ok(None);
+    }
+
+    task::spawn_blocking(move || -> io::Result<_> {
+        let path = dir.join(&source).join(format!("{}.tsv", date));
+
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => { return Ok(None); }
+            Err(e) => { return Err(e); }
+        };
+
+        let mut entries = Vec::new();
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let mut fields = line.split('\t');
+
+            // a line cut short by a crash is skipped rather than failing
+            // the whole day:
+            let time = match fields.next().and_then(|time| time.parse().ok()) {
+                Some(time) => time,
+                None => continue,
+            };
+
+            let lufs = match fields.next() {
+                Some("-inf") => None,
+                Some(lufs) => match lufs.parse() {
+                    Ok(lufs) => Some(lufs),
+                    Err(_) => continue,
+                },
+                None => continue,
+            };
+
+            entries.push(Entry { time, lufs });
+        }
+
+        let max_lufs = entries.iter()
+            .filter_map(|entry| entry.lufs)
+            .fold(None, |max: Option<f64>, lufs| Some(max.map_or(lufs, |max| max.max(lufs))));
+
+        Ok(Some(LoudnessDay { source, date, max_lufs, entries }))
+    }).await.expect("spawn_blocking")
+}
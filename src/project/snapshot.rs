@@ -0,0 +1,109 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use derive_more::From;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use mixlab_protocol::{SnapshotId, SnapshotInfo};
+
+use crate::persist;
+use crate::project::ProjectBase;
+
+// number of automatic backups kept, oldest are deleted first. named
+// snapshots are never rotated out:
+pub const BACKUP_COUNT: i64 = 20;
+
+#[derive(From, Debug)]
+pub enum SnapshotError {
+    Database(rusqlite::Error),
+    Json(serde_json::Error),
+    NoSuchSnapshot,
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+async fn insert(base: &ProjectBase, name: Option<String>, workspace: &persist::Workspace)
+    -> Result<SnapshotId, rusqlite::Error>
+{
+    let serialized = serde_json::to_vec(workspace).expect("serde_json::to_vec");
+    let created_at = now();
+
+    base.with_database(move |conn| -> Result<SnapshotId, rusqlite::Error> {
+        conn.execute(
+            "INSERT INTO workspace_snapshots (name, created_at, serialized) VALUES (?, ?, ?)",
+            params![name, created_at, serialized])?;
+
+        Ok(SnapshotId(conn.last_insert_rowid()))
+    }).await
+}
+
+/// Takes an automatic backup of the workspace, rotating out old backups
+pub async fn backup(base: &ProjectBase, workspace: &persist::Workspace) -> Result<(), rusqlite::Error> {
+    insert(base, None, workspace).await?;
+
+    base.with_database(|conn| -> Result<(), rusqlite::Error> {
+        conn.execute(r"
+                DELETE FROM workspace_snapshots WHERE name IS NULL AND id NOT IN (
+                    SELECT id FROM workspace_snapshots WHERE name IS NULL
+                    ORDER BY id DESC LIMIT ?
+                )
+            ",
+            params![BACKUP_COUNT])?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn create(base: &ProjectBase, name: String, workspace: &persist::Workspace) -> Result<SnapshotId, rusqlite::Error> {
+    insert(base, Some(name), workspace).await
+}
+
+pub async fn list(base: &ProjectBase) -> Result<Vec<SnapshotInfo>, rusqlite::Error> {
+    base.with_database(|conn| -> Result<Vec<SnapshotInfo>, rusqlite::Error> {
+        conn.prepare("SELECT id, name, created_at FROM workspace_snapshots ORDER BY id DESC")?
+            .query_map(rusqlite::NO_PARAMS,
+                |row| Ok(SnapshotInfo {
+                    id: SnapshotId(row.get(0)?),
+                    name: row.get(1)?,
+                    created_at: row.get(2)?,
+                })
+            )?
+            .collect()
+    }).await
+}
+
+pub async fn load(base: &ProjectBase, id: SnapshotId) -> Result<persist::Workspace, SnapshotError> {
+    let serialized = base.with_database(move |conn| -> Result<Option<Vec<u8>>, rusqlite::Error> {
+        conn.query_row("SELECT serialized FROM workspace_snapshots WHERE id = ?",
+            params![id.0],
+            |row| row.get(0)).optional()
+    }).await?;
+
+    let serialized = serialized.ok_or(SnapshotError::NoSuchSnapshot)?;
+    Ok(serde_json::from_slice(&serialized)?)
+}
+
+/// Finds the most recent snapshot which still deserializes, for recovering
+/// from a corrupt workspace
+pub fn newest_valid(conn: &mut Connection) -> Result<Option<(SnapshotId, persist::Workspace)>, rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT id, serialized FROM workspace_snapshots ORDER BY id DESC")?;
+
+    let rows = stmt.query_map(rusqlite::NO_PARAMS,
+        |row| Ok((SnapshotId(row.get(0)?), row.get::<_, Vec<u8>>(1)?)))?;
+
+    for row in rows {
+        let (id, serialized) = row?;
+
+        match serde_json::from_slice(&serialized) {
+            Ok(workspace) => { return Ok(Some((id, workspace))); }
+            Err(e) => {
+                eprintln!("project: skipping unreadable snapshot {}: {:?}", id.0, e);
+            }
+        }
+    }
+
+    Ok(None)
+}
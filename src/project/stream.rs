@@ -4,9 +4,11 @@ use std::io::SeekFrom;
 use std::mem;
 
 use rusqlite::{params, OptionalExtension, types::ValueRef};
+use sha2::{Digest, Sha256};
 use mixlab_codec::ffmpeg;
 
 use crate::project::ProjectBaseRef;
+use crate::util;
 
 const STREAM_BLOB_SIZE: usize = 1024 * 1024;
 
@@ -24,6 +26,7 @@ pub async fn create(base: ProjectBaseRef) -> Result<WriteStream, rusqlite::Error
         id: stream_id,
         offset: 0,
         buff: Vec::with_capacity(STREAM_BLOB_SIZE),
+        hasher: Sha256::default(),
     })
 }
 
@@ -33,6 +36,7 @@ pub struct WriteStream {
     id: StreamId,
     offset: i64,
     buff: Vec<u8>,
+    hasher: Sha256,
 }
 
 impl WriteStream {
@@ -43,6 +47,7 @@ impl WriteStream {
             let (this_chunk, remaining) = bytes.split_at(take);
 
             self.buff.extend(this_chunk);
+            self.hasher.input(this_chunk);
 
             bytes = remaining;
 
@@ -56,7 +61,16 @@ impl WriteStream {
 
     pub async fn finalize(mut self) -> Result<StreamId, rusqlite::Error> {
         self.flush().await?;
-        Ok(self.id)
+
+        let id = self.id;
+        let sha256 = util::hex(&mem::take(&mut self.hasher).result());
+
+        self.base.with_database(move |conn| {
+            conn.execute(r"UPDATE streams SET sha256 = ? WHERE id = ?",
+                params![sha256, id.0])
+        }).await?;
+
+        Ok(id)
     }
 
     async fn flush(&mut self) -> Result<(), rusqlite::Error> {
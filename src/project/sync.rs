@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+
+use bytes::Bytes;
+use derive_more::From;
+use futures::stream;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Request};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::task;
+
+use mixlab_codec::ffmpeg::IoReader;
+use mixlab_protocol::MediaId;
+
+use crate::project::{media, ProjectBaseRef};
+use crate::project::stream::{ReadStream, StreamId};
+use crate::util;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Media library entry as exchanged between instances, which know items by
+/// content rather than by id
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestItem {
+    pub sha256: String,
+    pub name: String,
+    pub kind: String,
+    pub size: u64,
+}
+
+#[derive(From, Debug)]
+pub enum SyncError {
+    Database(rusqlite::Error),
+    Http(hyper::Error),
+    Request(http::Error),
+    Json(serde_json::Error),
+    #[from(ignore)]
+    Status(http::StatusCode),
+    #[from(ignore)]
+    MissingMedia(MediaId),
+}
+
+/// The media library by content, one entry per distinct item. Streams
+/// written before content hashing are hashed here the first time
+pub async fn manifest(base: &ProjectBaseRef) -> Result<Vec<(MediaId, ManifestItem)>, rusqlite::Error> {
+    hash_streams(base).await?;
+
+    let items = base.with_database(|conn| -> Result<Vec<(MediaId, ManifestItem)>, rusqlite::Error> {
+        conn.prepare(r"
+                SELECT media.id, streams.sha256, media.name, media.kind, streams.size FROM media
+                INNER JOIN streams ON streams.id = media.stream_id
+                ORDER BY media.id
+            ")?
+            .query_map(rusqlite::NO_PARAMS,
+                |row| Ok((MediaId(row.get(0)?), ManifestItem {
+                    sha256: row.get(1)?,
+                    name: row.get(2)?,
+                    kind: row.get(3)?,
+                    size: row.get::<_, i64>(4)? as u64,
+                }))
+            )?
+            .collect()
+    }).await?;
+
+    let mut seen = HashSet::new();
+
+    Ok(items.into_iter()
+        .filter(|(_, item)| seen.insert(item.sha256.clone()))
+        .collect())
+}
+
+/// Hashes streams written before content hashing in the background, so
+/// that the first manifest asked for after upgrading isn't kept waiting
+pub fn backfill(base: ProjectBaseRef) {
+    task::spawn(async move {
+        if let Err(e) = hash_streams(&base).await {
+            eprintln!("sync: could not hash media: {:?}", e);
+        }
+    });
+}
+
+// the database is only held to list the streams and to write each hash
+// back, each stream is read a chunk at a time in between
+async fn hash_streams(base: &ProjectBaseRef) -> Result<(), rusqlite::Error> {
+    // streams still being uploaded have no media row yet:
+    let unhashed = base.with_database(|conn| -> Result<Vec<i64>, rusqlite::Error> {
+        conn.prepare(r"
+                SELECT id FROM streams
+                WHERE sha256 IS NULL AND id IN (SELECT stream_id FROM media)
+            ")?
+            .query_map(rusqlite::NO_PARAMS, |row| row.get::<_, i64>(0))?
+            .collect()
+    }).await?;
+
+    for stream_id in unhashed {
+        let reader = match ReadStream::open(base.clone(), StreamId(stream_id)).await? {
+            Some(reader) => reader,
+            None => continue,
+        };
+
+        let sha256 = task::spawn_blocking(move || -> Result<String, rusqlite::Error> {
+            let mut reader = reader;
+            let mut hasher = Sha256::default();
+            let mut buf = vec![0; CHUNK_SIZE];
+
+            loop {
+                let len = reader.read(&mut buf)?;
+
+                if len == 0 {
+                    break;
+                }
+
+                hasher.input(&buf[..len]);
+            }
+
+            Ok(util::hex(&hasher.result()))
+        }).await.expect("hash stream")?;
+
+        // the backfill and a manifest may both have hashed it:
+        base.with_database(move |conn| {
+            conn.execute("UPDATE streams SET sha256 = ? WHERE id = ? AND sha256 IS NULL",
+                params![sha256, stream_id])
+        }).await?;
+    }
+
+    Ok(())
+}
+
+/// Uploads to the instance at `remote` every item in the library it doesn't
+/// already have, reporting progress through the project's sync status
+pub async fn push(base: &ProjectBaseRef, remote: &str) -> Result<(), SyncError> {
+    let (remote, query) = remote_origin(remote);
+    let client = Client::new();
+
+    let local = manifest(base).await?;
+    let present = fetch_manifest(&client, &remote, &query).await?.into_iter()
+        .map(|item| item.sha256)
+        .collect::<HashSet<_>>();
+
+    let missing = local.into_iter()
+        .filter(|(_, item)| !present.contains(&item.sha256))
+        .collect::<Vec<_>>();
+
+    base.update_media_sync(|status| status.missing = Some(missing.len()));
+
+    for (id, item) in missing {
+        base.update_media_sync(|status| status.uploading = Some(item.name.clone()));
+
+        upload(&client, &remote, &query, base, id, &item).await?;
+
+        base.update_media_sync(|status| {
+            status.uploading = None;
+            status.transferred += 1;
+        });
+    }
+
+    Ok(())
+}
+
+/// The remote as shown while pushing, without any owner key given with it
+pub fn display_remote(remote: &str) -> String {
+    remote_origin(remote).0
+}
+
+// accepts a bare host:port as well as a url. any query string, ie. the
+// remote's ?key=, is split off to be given with every request:
+fn remote_origin(remote: &str) -> (String, String) {
+    let remote = remote.trim();
+
+    let (remote, query) = match remote.find('?') {
+        Some(idx) => (&remote[..idx], &remote[idx..]),
+        None => (remote, ""),
+    };
+
+    let remote = remote.trim_end_matches('/');
+
+    let origin = if remote.contains("://") {
+        remote.to_owned()
+    } else {
+        format!("http://{}", remote)
+    };
+
+    (origin, query.to_owned())
+}
+
+async fn fetch_manifest(client: &Client<HttpConnector>, remote: &str, query: &str) -> Result<Vec<ManifestItem>, SyncError> {
+    let response = client.get(format!("{}/_media/manifest{}", remote, query).parse().map_err(http::Error::from)?).await?;
+
+    if !response.status().is_success() {
+        return Err(SyncError::Status(response.status()));
+    }
+
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+async fn upload(client: &Client<HttpConnector>, remote: &str, query: &str, base: &ProjectBaseRef, id: MediaId, item: &ManifestItem) -> Result<(), SyncError> {
+    let reader = media::open(base.clone(), id).await?
+        .ok_or(SyncError::MissingMedia(id))?;
+
+    let body = stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+
+        let read = task::spawn_blocking(move || -> Result<_, rusqlite::Error> {
+            let mut buf = vec![0; CHUNK_SIZE];
+            let len = reader.read(&mut buf)?;
+            buf.truncate(len);
+            Ok((reader, buf))
+        }).await.expect("read media");
+
+        match read {
+            Ok((_, buf)) if buf.is_empty() => None,
+            Ok((reader, buf)) => Some((Ok(Bytes::from(buf)), Some(reader))),
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+
+    // the remote takes it through the same endpoint as uploads from the ui:
+    let request = Request::post(format!("{}/_upload/{}{}", remote, utf8_percent_encode(&item.name, NON_ALPHANUMERIC), query))
+        .header("content-type", item.kind.as_str())
+        .header("content-length", item.size.to_string())
+        .body(Body::wrap_stream(body))?;
+
+    let response = client.request(request).await?;
+
+    if !response.status().is_success() {
+        return Err(SyncError::Status(response.status()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use futures::sink::{Sink, SinkExt};
+use futures::stream::{Stream, StreamExt};
+use structopt::StructOpt;
+use uuid::Uuid;
+use warp::Filter;
+use warp::ws::{self, Ws, WebSocket};
+
+use crate::server;
+
+pub mod link;
+
+use link::{LinkHello, LinkRx, LinkTx};
+
+/// Serves the frontend here for a project running on another machine, one
+/// started with --link. Sessions and monitors are carried over to it, with
+/// the project, its media and the engine all staying where it runs. Nothing
+/// else is: uploading media, loudness logs and previews are only served by
+/// the engine's own http server. Links are unencrypted, so `engine` should
+/// be the near end of a tunnel to it, eg. ssh -L or a tls proxy
+#[derive(StructOpt)]
+pub struct RemoteOpts {
+    #[structopt(short, long, default_value = "127.0.0.1:8000")]
+    listen: SocketAddr,
+    // as given to the engine:
+    #[structopt(long)]
+    link_secret: String,
+    // address the engine accepts links on, as host:port:
+    engine: String,
+}
+
+struct Engine {
+    addr: String,
+    secret: String,
+}
+
+pub async fn run(opts: RemoteOpts) {
+    let engine = Arc::new(Engine { addr: opts.engine, secret: opts.link_secret });
+
+    let websocket = warp::get()
+        .and(warp::path("session"))
+        .and(warp::ws())
+        .and(warp::query::<HashMap<String, String>>())
+        .map({
+            let engine = engine.clone();
+            move |ws: Ws, query: HashMap<String, String>| {
+                let engine = engine.clone();
+                ws.on_upgrade(move |websocket| {
+                    relay(websocket, engine, LinkHello::Session(query))
+                })
+            }
+        });
+
+    let monitor_socket = warp::get()
+        .and(warp::path!("_monitor" / Uuid))
+        .and(warp::ws())
+        .and(warp::query::<HashMap<String, String>>())
+        .map({
+            let engine = engine.clone();
+            move |socket_id: Uuid, ws: Ws, query: HashMap<String, String>| {
+                let engine = engine.clone();
+                ws.on_upgrade(move |websocket| {
+                    relay(websocket, engine, LinkHello::Monitor(socket_id.as_u128(), query))
+                })
+            }
+        });
+
+    let routes = server::static_content()
+        .or(websocket)
+        .or(monitor_socket)
+        .with(warp::log("mixlab-http"));
+
+    println!("Mixlab is now running at http://{}, controlling the engine at {}", opts.listen, engine.addr);
+
+    tokio::select! {
+        _ = warp::serve(routes).run(opts.listen) => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+}
+
+// carries a websocket over a link of its own until either end closes
+async fn relay(websocket: WebSocket, engine: Arc<Engine>, hello: LinkHello) {
+    let (link_rx, link_tx) = match link::connect(&engine.addr, &engine.secret, hello).await {
+        Ok(link) => link,
+        Err(e) => {
+            eprintln!("remote: could not link to engine at {}: {:?}", engine.addr, e);
+            return;
+        }
+    };
+
+    let (client_tx, client_rx) = websocket.split();
+
+    tokio::select! {
+        _ = upstream(client_rx, link_tx) => {}
+        _ = downstream(link_rx, server::binary_sink(client_tx)) => {}
+    }
+}
+
+async fn upstream(mut client_rx: impl Stream<Item = Result<ws::Message, warp::Error>> + Unpin, mut link_tx: LinkTx) {
+    while let Some(Ok(msg)) = client_rx.next().await {
+        if !msg.is_binary() {
+            continue;
+        }
+
+        if let Err(e) = link_tx.send(msg.as_bytes().to_vec()).await {
+            eprintln!("remote: link to engine failed: {:?}", e);
+            return;
+        }
+    }
+}
+
+async fn downstream(mut link_rx: LinkRx, mut client_tx: impl Sink<Vec<u8>> + Unpin) {
+    while let Some(frame) = link_rx.next().await {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(e) => {
+                eprintln!("remote: link to engine failed: {:?}", e);
+                return;
+            }
+        };
+
+        if let Err(_) = client_tx.send(frame).await {
+            // client disconnected
+            return;
+        }
+    }
+}
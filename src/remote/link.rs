@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::time::Duration;
+
+use derive_more::From;
+use futures::channel::mpsc;
+use futures::stream::{self, Stream, StreamExt};
+use serde::{Serialize, Deserialize};
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time;
+
+use crate::util;
+
+// bumped whenever what's carried over a link changes, as the control
+// server and engine may not be upgraded together:
+const LINK_VERSION: u32 = 2;
+
+// frames are never near this big, anything over it is garbage:
+const MAX_FRAME_LEN: u32 = 64 * 1024 * 1024;
+
+// hellos are held to much less, as they're read before the other end has
+// given the secret:
+const MAX_HELLO_LEN: u32 = 4 * 1024;
+
+// for the other end to send its hello in, before it's hung up on:
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+
+// frames queued to be written before sending waits:
+const SEND_BUFFER: usize = 16;
+
+/// What a link is opened to carry, sent first thing by the control server.
+/// Every browser session and every monitor gets a link of its own
+#[derive(Serialize, Deserialize, Debug)]
+pub enum LinkHello {
+    // each with the query string it was opened with, so that the engine
+    // checks owner keys and guest links as it would for its own:
+    Session(HashMap<String, String>),
+    Monitor(u128, HashMap<String, String>),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Hello {
+    version: u32,
+    // shared by the engine and control server, given with --link-secret:
+    secret: String,
+    kind: LinkHello,
+}
+
+#[derive(Debug, From)]
+pub enum LinkError {
+    Io(io::Error),
+    Bincode(bincode::Error),
+    Timeout(time::Elapsed),
+    #[from(ignore)]
+    Version(u32),
+    BadSecret,
+}
+
+pub type LinkRx = Pin<Box<dyn Stream<Item = Result<Vec<u8>, io::Error>> + Send>>;
+pub type LinkTx = mpsc::Sender<Vec<u8>>;
+
+/// Opens a link to an engine from the control server
+pub async fn connect(addr: &str, secret: &str, kind: LinkHello) -> Result<(LinkRx, LinkTx), LinkError> {
+    let mut conn = TcpStream::connect(addr).await?;
+    conn.set_nodelay(true)?;
+
+    let hello = bincode::serialize(&Hello { version: LINK_VERSION, secret: secret.to_owned(), kind })?;
+    write_frame(&mut conn, &hello).await?;
+
+    Ok(split(conn))
+}
+
+/// Takes the hello off a link the engine has accepted, refusing the link
+/// unless it gives `secret`. Links are plain tcp, secret and all, so are
+/// only ever to be opened over a tunnel, eg. ssh port forwarding or tls
+pub async fn accept(mut conn: TcpStream, secret: &str) -> Result<(LinkHello, LinkRx, LinkTx), LinkError> {
+    conn.set_nodelay(true)?;
+
+    let hello = time::timeout(HELLO_TIMEOUT, read_frame(&mut conn, MAX_HELLO_LEN)).await??;
+    let hello = bincode::deserialize::<Hello>(&hello)?;
+
+    if hello.version != LINK_VERSION {
+        return Err(LinkError::Version(hello.version));
+    }
+
+    if !util::constant_time_eq(hello.secret.as_bytes(), secret.as_bytes()) {
+        return Err(LinkError::BadSecret);
+    }
+
+    let (rx, tx) = split(conn);
+    Ok((hello.kind, rx, tx))
+}
+
+fn split(conn: TcpStream) -> (LinkRx, LinkTx) {
+    let (rx, tx) = io::split(conn);
+
+    let rx = stream::unfold(Some(rx), |rx| async move {
+        let mut rx = rx?;
+
+        match read_frame(&mut rx, MAX_FRAME_LEN).await {
+            Ok(frame) => Some((Ok(frame), Some(rx))),
+            // the other end hung up:
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+
+    // written from a task of its own, so that the link can be sent to as a
+    // sink. sending fails once this has:
+    let (frame_tx, mut frame_rx) = mpsc::channel::<Vec<u8>>(SEND_BUFFER);
+
+    tokio::spawn(async move {
+        let mut tx = tx;
+
+        while let Some(frame) = frame_rx.next().await {
+            if let Err(e) = write_frame(&mut tx, &frame).await {
+                eprintln!("link: write failed: {:?}", e);
+                break;
+            }
+        }
+    });
+
+    (Box::pin(rx), frame_tx)
+}
+
+async fn read_frame(rx: &mut (impl AsyncRead + Unpin), max_len: u32) -> Result<Vec<u8>, io::Error> {
+    let len = rx.read_u32().await?;
+
+    if len > max_len {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "link frame too long"));
+    }
+
+    let mut frame = vec![0; len as usize];
+    rx.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+async fn write_frame(tx: &mut (impl AsyncWrite + Unpin), frame: &[u8]) -> Result<(), io::Error> {
+    tx.write_u32(frame.len() as u32).await?;
+    tx.write_all(frame).await?;
+    tx.flush().await
+}
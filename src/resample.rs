@@ -0,0 +1,248 @@
+use std::cmp;
+use std::f64;
+
+use mixlab_protocol::ResampleQuality;
+
+use crate::engine::Sample;
+
+// number of fractional phases precomputed for the polyphase filter bank
+const POLYPHASE_PHASES: usize = 64;
+
+#[derive(Debug)]
+pub struct Resampler {
+    quality: ResampleQuality,
+    channels: usize,
+    input_rate: usize,
+    output_rate: usize,
+    // input frames advanced per output frame:
+    step: f64,
+    // set once varispeed is used, after which input is always resampled so
+    // that history stays continuous even at matching rates:
+    varispeed: bool,
+    // normalized lowpass cutoff, below 1.0 when downsampling:
+    cutoff: f64,
+    // half the kernel width in frames:
+    half_width: usize,
+    // fractional position of the next output frame, in frames, relative to
+    // the start of history:
+    position: f64,
+    // interleaved input samples not yet fully consumed:
+    history: Vec<Sample>,
+    polyphase: Option<Vec<Vec<f64>>>,
+}
+
+impl Resampler {
+    pub fn new(quality: ResampleQuality, channels: usize, input_rate: usize, output_rate: usize) -> Self {
+        let step = input_rate as f64 / output_rate as f64;
+        let cutoff = f64::min(1.0, output_rate as f64 / input_rate as f64);
+
+        let half_width = match quality {
+            ResampleQuality::Linear => 1,
+            ResampleQuality::Polyphase => 4,
+            ResampleQuality::Sinc => 16,
+        };
+
+        let polyphase = match quality {
+            ResampleQuality::Polyphase => Some(polyphase_table(half_width, cutoff)),
+            ResampleQuality::Linear | ResampleQuality::Sinc => None,
+        };
+
+        Resampler {
+            quality,
+            channels,
+            input_rate,
+            output_rate,
+            step,
+            varispeed: false,
+            cutoff,
+            half_width,
+            // prefill history with silence so that the first output frame
+            // has a full kernel's worth of past samples to look at:
+            position: half_width as f64,
+            history: vec![0.0; half_width * channels],
+            polyphase,
+        }
+    }
+
+    pub fn quality(&self) -> ResampleQuality {
+        self.quality
+    }
+
+    pub fn input_rate(&self) -> usize {
+        self.input_rate
+    }
+
+    pub fn output_rate(&self) -> usize {
+        self.output_rate
+    }
+
+    /// Plays input back at `speed` times its nominal rate, for chasing a
+    /// clock running slightly faster or slower than the input's. Above 1.0
+    /// input is consumed faster and fewer output samples are produced.
+    pub fn set_varispeed(&mut self, speed: f64) {
+        self.varispeed = true;
+        self.step = self.input_rate as f64 / self.output_rate as f64 * speed;
+    }
+
+    /// Resamples interleaved `input`, appending as many output samples as
+    /// can be produced to `output`. Any input not yet consumed is retained
+    /// for the next call.
+    pub fn process(&mut self, input: &[Sample], output: &mut Vec<Sample>) {
+        if self.input_rate == self.output_rate && !self.varispeed {
+            output.extend_from_slice(input);
+            return;
+        }
+
+        self.history.extend_from_slice(input);
+
+        let frames = self.history.len() / self.channels;
+
+        loop {
+            let base = self.position.floor() as usize;
+
+            if base + self.half_width >= frames {
+                // not enough lookahead for the next output frame yet
+                break;
+            }
+
+            let frac = self.position - base as f64;
+
+            for ch in 0..self.channels {
+                output.push(self.interpolate(base, frac, ch) as Sample);
+            }
+
+            self.position += self.step;
+        }
+
+        // discard input frames which no future output frame will look at
+        let base = self.position.floor() as usize;
+        let consumed = cmp::min(base + 1 - self.half_width, frames);
+        self.history.drain(0..(consumed * self.channels));
+        self.position -= consumed as f64;
+    }
+
+    fn sample(&self, frame: usize, channel: usize) -> f64 {
+        self.history[frame * self.channels + channel] as f64
+    }
+
+    fn interpolate(&self, base: usize, frac: f64, channel: usize) -> f64 {
+        match self.quality {
+            ResampleQuality::Linear => {
+                let a = self.sample(base, channel);
+                let b = self.sample(base + 1, channel);
+                a + (b - a) * frac
+            }
+            ResampleQuality::Polyphase => {
+                // polyphase table is always present for this quality:
+                let table = self.polyphase.as_ref().unwrap();
+                let phase = (frac * POLYPHASE_PHASES as f64).round() as usize;
+                let kernel = &table[phase];
+                let first = base + 1 - self.half_width;
+
+                kernel.iter().enumerate()
+                    .map(|(tap, weight)| weight * self.sample(first + tap, channel))
+                    .sum()
+            }
+            ResampleQuality::Sinc => {
+                let first = base + 1 - self.half_width;
+                let mut sum = 0.0;
+                let mut norm = 0.0;
+
+                for tap in 0..(self.half_width * 2) {
+                    let x = (first + tap) as f64 - (base as f64 + frac);
+                    let weight = kernel(x, self.half_width, self.cutoff);
+                    sum += weight * self.sample(first + tap, channel);
+                    norm += weight;
+                }
+
+                if norm == 0.0 { sum } else { sum / norm }
+            }
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let px = f64::consts::PI * x;
+        f64::sin(px) / px
+    }
+}
+
+// blackman windowed sinc, zero outside of [-half_width, half_width]
+fn kernel(x: f64, half_width: usize, cutoff: f64) -> f64 {
+    let t = x / half_width as f64;
+
+    if t.abs() >= 1.0 {
+        return 0.0;
+    }
+
+    let window = 0.42
+        + 0.5 * f64::cos(f64::consts::PI * t)
+        + 0.08 * f64::cos(2.0 * f64::consts::PI * t);
+
+    cutoff * sinc(cutoff * x) * window
+}
+
+fn polyphase_table(half_width: usize, cutoff: f64) -> Vec<Vec<f64>> {
+    (0..=POLYPHASE_PHASES).map(|phase| {
+        let frac = phase as f64 / POLYPHASE_PHASES as f64;
+
+        let mut taps = (0..(half_width * 2))
+            .map(|tap| {
+                // distance of this tap from the interpolated position:
+                let x = tap as f64 + 1.0 - half_width as f64 - frac;
+                kernel(x, half_width, cutoff)
+            })
+            .collect::<Vec<_>>();
+
+        // normalize for unity gain at DC
+        let sum: f64 = taps.iter().sum();
+
+        if sum != 0.0 {
+            for tap in taps.iter_mut() {
+                *tap /= sum;
+            }
+        }
+
+        taps
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use mixlab_protocol::ResampleQuality;
+    use super::Resampler;
+
+    fn resample_dc(quality: ResampleQuality) -> Vec<f32> {
+        let mut resampler = Resampler::new(quality, 2, 48000, 44100);
+        let input = vec![0.5; 48000 * 2];
+        let mut output = Vec::new();
+        resampler.process(&input, &mut output);
+        output
+    }
+
+    #[test]
+    fn output_length_tracks_ratio() {
+        for quality in &[ResampleQuality::Linear, ResampleQuality::Polyphase, ResampleQuality::Sinc] {
+            let output = resample_dc(*quality);
+            let frames = output.len() / 2;
+
+            // allow for the kernel's lookahead held back in history
+            assert!(frames <= 44100 && frames > 44100 - 32, "{:?}: {} frames", quality, frames);
+        }
+    }
+
+    #[test]
+    fn preserves_dc() {
+        for quality in &[ResampleQuality::Linear, ResampleQuality::Polyphase, ResampleQuality::Sinc] {
+            let output = resample_dc(*quality);
+
+            // skip the initial ramp in from prefilled silence
+            for sample in &output[64..] {
+                assert!((sample - 0.5).abs() < 0.001, "{:?}: {}", quality, sample);
+            }
+        }
+    }
+}
@@ -18,7 +18,7 @@ use mixlab_codec::ffmpeg::{AvError, AvPacketRef, PacketInfo};
 use mixlab_util::time::{MediaDuration, MediaTime, TimeBase};
 
 use crate::listen::PeekTcpStream;
-use crate::source::{Registry, ConnectError, SourceRecv, SourceSend, ListenError};
+use crate::source::{Registry, ConnectError, SourceRecv, SourceSend, ListenError, AudioData};
 use crate::video;
 
 pub mod client;
@@ -226,11 +226,6 @@ fn receive_audio_packet(
                 Ok(()) => {
                     let sample_rate = ctx.audio_codec.stream_info().sampleRate;
 
-                    if sample_rate != 44100 {
-                        // TODO fix me
-                        panic!("expected stream sample rate to be 44100");
-                    }
-
                     let frame_time = MediaDuration::new(pcm_buffer.len() as i64 / 2, sample_rate as i64);
 
                     pcm_buffer.truncate(ctx.audio_codec.decoded_frame_size());
@@ -238,7 +233,12 @@ fn receive_audio_packet(
 
                     // TODO do we use ctx.audio_timestamp or the rtmp timestamp here?
 
-                    ctx.source.write_audio(ctx.audio_timestamp, pcm_buffer)
+                    let data = AudioData {
+                        sample_rate: sample_rate as usize,
+                        samples: pcm_buffer,
+                    };
+
+                    ctx.source.write_audio(ctx.audio_timestamp, data)
                         .map_err(|()| RtmpError::SourceSend)?;
 
                     ctx.audio_timestamp += frame_time;
@@ -1,98 +1,277 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::pin::Pin;
+use std::process;
 use std::sync::Arc;
+use std::time::Duration;
 
 use bytes::Buf;
 use derive_more::From;
+use futures::channel::mpsc::SendError;
+use futures::future;
 use futures::sink::{Sink, SinkExt};
-use futures::stream::{self, Stream, StreamExt};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use percent_encoding::percent_decode;
 use structopt::StructOpt;
+use tokio::io;
+use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::broadcast;
 use tokio::sync::mpsc;
+use tokio::time;
 use uuid::Uuid;
-use warp::Filter;
+use warp::{Filter, Rejection};
 use warp::reply::{self, Reply};
 use warp::ws::{self, Ws, WebSocket};
 
-use mixlab_protocol::{ClientMessage, ServerMessage};
+use mixlab_codec::ffmpeg::ColorSpace;
+use mixlab_protocol::{Access, Batch, ClientMessage, ModuleId, OutputId, ServerMessage, WorkspaceMessage, WorkspaceOp};
 
-use crate::engine::EngineEvent;
+use crate::engine::{EngineEvent, PreviewRequest};
 use crate::listen::{self, Disambiguation};
 use crate::project::{self, ProjectHandle, Notification};
-use crate::{icecast, module, rtmp};
+use crate::project::backup::BackupConfig;
+use crate::project::backup::s3::S3Target;
+use crate::remote::link::{self, LinkHello};
+use crate::{browser, icecast, module, osc, rtmp, util};
 
 #[derive(StructOpt)]
 pub struct RunOpts {
     #[structopt(short, long, default_value = "127.0.0.1:8000")]
     listen: SocketAddr,
-    workspace_path: PathBuf,
+    // udp address to accept OSC remote control on, disabled if not given:
+    #[structopt(long)]
+    osc: Option<SocketAddr>,
+    // engine settings to store in the project, applied before opening:
+    #[structopt(long)]
+    sample_rate: Option<usize>,
+    #[structopt(long)]
+    block_size: Option<usize>,
+    // bt601 or bt709:
+    #[structopt(long)]
+    color_space: Option<ColorSpace>,
+    // chromium executable that browser sources render with:
+    #[structopt(long)]
+    browser: Option<PathBuf>,
+    // work in a throwaway copy of the project, deleted on exit unless kept:
+    #[structopt(long)]
+    scratch: bool,
+    // S3 compatible endpoint to back snapshots and recordings up to, eg.
+    // http://localhost:9000. credentials are taken from AWS_ACCESS_KEY_ID
+    // and AWS_SECRET_ACCESS_KEY:
+    #[structopt(long)]
+    backup_endpoint: Option<String>,
+    #[structopt(long, default_value = "mixlab")]
+    backup_bucket: String,
+    #[structopt(long, default_value = "us-east-1")]
+    backup_region: String,
+    // prepended to the keys of everything uploaded:
+    #[structopt(long, default_value = "")]
+    backup_prefix: String,
+    // in kilobytes per second, so backups don't starve outgoing streams:
+    #[structopt(long)]
+    backup_rate_limit: Option<u64>,
+    // when set, sessions, monitors and media and loudness requests must
+    // either give this as ?key= or hold a guest link, and OSC peers must
+    // send it to /auth. otherwise anyone who can reach the server has full
+    // control, and guest links only narrow what their holders see:
+    #[structopt(long)]
+    owner_key: Option<String>,
+    // open the project without serving it, run every module for a while
+    // and report on any that fail or run over budget, then exit:
+    #[structopt(long)]
+    check: bool,
+    // tcp address to accept links from `mixlab remote` on, so that the
+    // project can be run here and controlled from elsewhere. sessions over
+    // a link are held to the owner key as any other. links are unencrypted,
+    // so bind this to localhost and tunnel to it, eg. with ssh -L or a tls
+    // proxy. only sessions and monitors are carried over links, uploads,
+    // loudness logs and previews are still served from here alone:
+    #[structopt(long)]
+    link: Option<SocketAddr>,
+    // which remotes must give to link, required with --link:
+    #[structopt(long)]
+    link_secret: Option<String>,
+    // only left out for another command, eg. `mixlab bench`:
+    #[structopt(required = true)]
+    workspace_path: Option<PathBuf>,
 }
 
+// at the default settings, a few seconds' worth:
+const CHECK_TICKS: u64 = 300;
+
+// length of a preview when none is asked for:
+const PREVIEW_SECONDS: f64 = 10.0;
+
 struct Server {
     project: ProjectHandle,
+    owner_key: Option<String>,
 }
 
 type ServerRef = Arc<Server>;
 
 impl Server {
-    pub fn new(project: ProjectHandle) -> Self {
+    pub fn new(project: ProjectHandle, owner_key: Option<String>) -> Self {
         Server {
             project,
+            owner_key,
+        }
+    }
+
+    /// Who a session or request is from, going by the ?key= or ?guest= it
+    /// was made with. None if it's from neither owner nor live guest link
+    fn access(&self, query: &HashMap<String, String>) -> Option<Access> {
+        match (query.get("guest"), &self.owner_key) {
+            (Some(token), _) => self.project.guest_token(token).map(Access::Guest),
+            (None, Some(key)) => {
+                let given = query.get("key").map(String::as_bytes).unwrap_or_default();
+
+                if util::constant_time_eq(given, key.as_bytes()) {
+                    Some(Access::Owner)
+                } else {
+                    None
+                }
+            }
+            (None, None) => Some(Access::Owner),
         }
     }
 }
 
+// rejects requests from anyone but the owner, or with `guests` also those
+// from live guest links
+fn with_access(server: ServerRef, guests: bool) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::query::<HashMap<String, String>>()
+        .and_then(move |query: HashMap<String, String>| {
+            let server = server.clone();
+            async move {
+                match server.access(&query) {
+                    Some(Access::Owner) => Ok(()),
+                    Some(Access::Guest(_)) if guests => Ok(()),
+                    _ => Err(warp::reject::not_found()),
+                }
+            }
+        })
+        .untuple_one()
+}
+
 pub async fn run(opts: RunOpts) {
-    let project = project::open_or_create(opts.workspace_path).await
-        .expect("create_or_open_project");
+    let workspace_path = opts.workspace_path.expect("workspace_path");
 
-    let server = Arc::new(Server::new(project));
+    // a link has the run of the project, so is never left open to anyone:
+    if opts.link.is_some() && opts.link_secret.as_deref().map_or(true, str::is_empty) {
+        eprintln!("--link needs a --link-secret, for remotes to link with");
+        process::exit(1);
+    }
 
-    let index = warp::path::end()
-        .map(index);
+    if let Some(browser) = opts.browser {
+        browser::set_executable(browser);
+    }
 
-    let style = warp::path!("style.css")
-        .map(style);
+    let settings = project::EngineSettings {
+        sample_rate: opts.sample_rate,
+        block_size: opts.block_size,
+        color_space: opts.color_space,
+    };
+
+    if opts.check {
+        let report = match project::check(workspace_path, settings, CHECK_TICKS).await {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("check: {:?}", e);
+                process::exit(1);
+            }
+        };
 
-    let js = warp::path!("app.js")
-        .map(js);
+        println!("{}", report);
+        process::exit(if report.passed() { 0 } else { 1 });
+    }
 
-    let wasm = warp::path!("app.wasm")
-        .map(wasm);
+    let backup = opts.backup_endpoint.map(|endpoint| {
+        let target = S3Target::new(&endpoint, opts.backup_bucket, opts.backup_region, opts.backup_prefix)
+            .expect("backup target");
 
-    let static_content = warp::get()
-        .and(index
-            .or(style)
-            .or(js)
-            .or(wasm));
+        BackupConfig {
+            target: Arc::new(target),
+            rate_limit: opts.backup_rate_limit.filter(|rate| *rate > 0).map(|rate| rate * 1024),
+        }
+    });
+
+    let project = if opts.scratch {
+        project::open_scratch(workspace_path, settings, backup).await
+    } else {
+        project::open_or_create(workspace_path, settings, backup).await
+    }.expect("create_or_open_project");
+
+    if project.info().scratch {
+        println!("Working in scratch project {}", project.info().name);
+    }
+
+    if let Some(osc_addr) = opts.osc {
+        let project = project.clone();
+        let owner_key = opts.owner_key.clone();
+        tokio::spawn(async move {
+            if let Err(e) = osc::run(osc_addr, project, owner_key).await {
+                eprintln!("osc: {:?}", e);
+            }
+        });
+    }
+
+    let server = Arc::new(Server::new(project.clone(), opts.owner_key));
+
+    if let Some(link_addr) = opts.link {
+        let mut listener = TcpListener::bind(link_addr).await
+            .expect("bind link address");
+
+        println!("Accepting remote links on {}", link_addr);
+
+        let server = server.clone();
+        let secret = Arc::new(opts.link_secret.unwrap_or_default());
+
+        tokio::spawn(async move {
+            let mut incoming = listener.incoming();
+
+            while let Some(conn) = incoming.next().await {
+                match conn {
+                    Ok(conn) => { tokio::spawn(link_session(conn, server.clone(), secret.clone())); }
+                    Err(e) => {
+                        eprintln!("link: {:?}", e);
+                        break;
+                    }
+                }
+            }
+        });
+    }
 
     let websocket = warp::get()
         .and(warp::path("session"))
         .and(warp::ws())
+        .and(warp::query::<HashMap<String, String>>())
         .map({
             let server = server.clone();
-            move |ws: Ws| {
+            move |ws: Ws, query: HashMap<String, String>| {
                 let server = server.clone();
                 ws.on_upgrade(move |websocket| {
-                    session(websocket, server.clone())
+                    websocket_session(websocket, server.clone(), query)
                 })
             }
         });
 
     let monitor_socket = warp::get()
         .and(warp::path!("_monitor" / Uuid))
+        .and(with_access(server.clone(), true))
         .and(warp::ws())
         .map(move |socket_id: Uuid, ws: Ws| {
             ws.on_upgrade(move |websocket| async move {
-                let _ = module::monitor::stream(socket_id, websocket).await;
+                let _ = module::monitor::stream(socket_id, binary_sink(websocket)).await;
             })
         });
 
     let media_upload = warp::post()
         .and(warp::path!("_upload" / String)
             .map(|filename: String| percent_decode(filename.as_bytes()).decode_utf8_lossy().into_owned()))
+        .and(with_access(server.clone(), false))
         .and(warp::header::<String>("content-type"))
         .and(warp::filters::body::stream())
         .and_then({
@@ -116,10 +295,104 @@ pub async fn run(opts: RunOpts) {
             }
         });
 
-    let routes = static_content
+    // lets other instances see what media this one already has:
+    let media_manifest = warp::get()
+        .and(warp::path!("_media" / "manifest"))
+        .and(with_access(server.clone(), false))
+        .and_then({
+            let server = server.clone();
+            move || {
+                let server = server.clone();
+                async move {
+                    server.project.media_manifest().await
+                        .map(|manifest| warp::reply::json(&manifest))
+                        .map_err(|e| {
+                            eprintln!("media manifest failed: {:?}", e);
+                            warp::reject::not_found()
+                        })
+                }
+            }
+        });
+
+    // loudness logged by each source, for showing compliance after the fact.
+    // days are UTC, as YYYY-MM-DD:
+    let loudness_sources = warp::get()
+        .and(warp::path!("_loudness"))
+        .and(with_access(server.clone(), true))
+        .and_then({
+            let server = server.clone();
+            move || {
+                let server = server.clone();
+                async move {
+                    server.project.loudness_sources().await
+                        .map(|sources| warp::reply::json(&sources))
+                        .map_err(|e| {
+                            eprintln!("loudness sources failed: {:?}", e);
+                            warp::reject::not_found()
+                        })
+                }
+            }
+        });
+
+    let loudness_day = warp::get()
+        .and(warp::path!("_loudness" / String / String))
+        .and(with_access(server.clone(), true))
+        .and_then({
+            let server = server.clone();
+            move |source, date| {
+                let server = server.clone();
+                async move {
+                    match server.project.loudness_day(source, date).await {
+                        Ok(Some(day)) => Ok(warp::reply::json(&day)),
+                        Ok(None) => Err(warp::reject::not_found()),
+                        Err(e) => {
+                            eprintln!("loudness log failed: {:?}", e);
+                            Err(warp::reject::not_found())
+                        }
+                    }
+                }
+            }
+        });
+
+    // an offline render of an output and the chain feeding it, eg. to
+    // audition a preset at ?speed=2 before putting it on air:
+    let preview = warp::get()
+        .and(warp::path!("_preview" / NonZeroUsize / usize))
+        .and(with_access(server.clone(), false))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then({
+            let server = server.clone();
+            move |module_id, output_index, query: HashMap<String, String>| {
+                let server = server.clone();
+                async move {
+                    let param = |name: &str, default: f64| {
+                        query.get(name).and_then(|value| value.parse().ok()).unwrap_or(default)
+                    };
+
+                    let request = PreviewRequest {
+                        output: OutputId(ModuleId(module_id), output_index),
+                        seconds: param("seconds", PREVIEW_SECONDS),
+                        speed: param("speed", 1.0),
+                    };
+
+                    server.project.preview(request).await
+                        .map(|wav| content("audio/wav", wav))
+                        .map_err(|e| {
+                            eprintln!("preview failed: {:?}", e);
+                            warp::reject::not_found()
+                        })
+                }
+            }
+        });
+
+    let routes = static_content()
         .or(websocket)
         .or(monitor_socket)
         .or(media_upload)
+        .or(media_manifest)
+        .or(loudness_sources)
+        .or(loudness_day)
+        .or(preview)
         .with(warp::log("mixlab-http"));
 
     let warp = warp::serve(routes);
@@ -155,7 +428,60 @@ pub async fn run(opts: RunOpts) {
         }
     });
 
-    warp.run_incoming(incoming_rx).await;
+    tokio::select! {
+        _ = warp.run_incoming(incoming_rx) => {}
+        _ = shutdown() => {}
+    }
+
+    if let Err(e) = project.close().await {
+        eprintln!("failed to delete scratch project: {:?}", e);
+    }
+}
+
+// ctrl-c, or on unix also being stopped by a service manager or the like,
+// either of which leaves time to clean up a scratch project
+async fn shutdown() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+
+            return;
+        }
+    }
+
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// The frontend, as served by this server and by `mixlab remote`
+pub fn static_content() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let index = warp::path::end()
+        .map(index);
+
+    let style = warp::path!("style.css")
+        .map(style);
+
+    let js = warp::path!("app.js")
+        .map(js);
+
+    let wasm = warp::path!("app.wasm")
+        .map(wasm);
+
+    warp::get()
+        .and(index
+            .or(style)
+            .or(js)
+            .or(wasm))
+}
+
+/// Sends each buffer given over a websocket as a binary message
+pub fn binary_sink(websocket: impl Sink<ws::Message, Error = warp::Error>) -> impl Sink<Vec<u8>, Error = warp::Error> {
+    websocket.with(|msg: Vec<u8>| future::ok::<_, warp::Error>(ws::Message::binary(msg)))
 }
 
 fn content(content_type: &str, reply: impl Reply) -> impl Reply {
@@ -194,15 +520,81 @@ fn wasm() -> impl Reply {
     content("application/wasm", app_wasm)
 }
 
-async fn session(websocket: WebSocket, server: ServerRef) {
+// a session's messages to and from its client, who may be in a browser
+// connected directly or reach the engine through a remote link
+type SessionRx = Pin<Box<dyn Stream<Item = Result<Vec<u8>, RecvError>> + Send>>;
+type SessionTx = Pin<Box<dyn Sink<Vec<u8>, Error = TxError> + Send>>;
+
+#[derive(Debug, From)]
+enum RecvError {
+    Warp(warp::Error),
+    Link(io::Error),
+}
+
+async fn websocket_session(websocket: WebSocket, server: ServerRef, query: HashMap<String, String>) {
     let (tx, rx) = websocket.split();
+
+    let rx = rx.filter_map(|msg| future::ready(match msg {
+        Ok(msg) if msg.is_binary() => Some(Ok(msg.as_bytes().to_vec())),
+        Ok(_) => None,
+        Err(e) => Some(Err(RecvError::Warp(e))),
+    }));
+
+    let tx = binary_sink(tx).sink_map_err(TxError::Warp);
+
+    session(Box::pin(rx), Box::pin(tx), server, query).await
+}
+
+async fn link_session(conn: TcpStream, server: ServerRef, secret: Arc<String>) {
+    let (hello, rx, tx) = match link::accept(conn, &secret).await {
+        Ok(link) => link,
+        Err(e) => {
+            eprintln!("link: refusing connection: {:?}", e);
+            return;
+        }
+    };
+
+    match hello {
+        LinkHello::Session(query) => {
+            let rx = rx.map_err(RecvError::Link);
+            let tx = tx.sink_map_err(TxError::Link);
+            session(Box::pin(rx), Box::pin(tx), server, query).await
+        }
+        LinkHello::Monitor(socket_id, query) => {
+            if server.access(&query).is_none() {
+                println!("refusing monitor: missing owner key or guest link");
+                return;
+            }
+
+            let _ = module::monitor::stream(Uuid::from_u128(socket_id), tx).await;
+        }
+    }
+}
+
+async fn session(rx: SessionRx, tx: SessionTx, server: ServerRef, query: HashMap<String, String>) {
+    let access = match server.access(&query) {
+        Some(access) => access,
+        None if query.contains_key("guest") => {
+            println!("refusing session: guest link expired or revoked");
+            return;
+        }
+        None => {
+            println!("refusing session: missing owner key");
+            return;
+        }
+    };
+
     let mut tx = ClientTx(tx);
 
     let notifications = server.project.notifications();
 
-    let (state, engine_ops, engine) = server.project.connect_engine().await
+    let (mut state, engine_ops, engine) = server.project.connect_engine().await
         .expect("connect engine");
 
+    if let Access::Guest(_) = access {
+        project::guest::redact_state(&mut state);
+    }
+
     let library = server.project.fetch_media_library().await
         .expect("fetch_media_library");
 
@@ -214,17 +606,70 @@ async fn session(websocket: WebSocket, server: ServerRef) {
         .await
         .expect("tx.send MediaLibrary");
 
+    let snapshots = server.project.fetch_snapshots().await
+        .expect("fetch_snapshots");
+
+    tx.send(ServerMessage::Snapshots(snapshots))
+        .await
+        .expect("tx.send Snapshots");
+
+    tx.send(ServerMessage::Project(server.project.info()))
+        .await
+        .expect("tx.send Project");
+
+    if let Some(status) = server.project.backup_status() {
+        tx.send(ServerMessage::Backup(status))
+            .await
+            .expect("tx.send Backup");
+    }
+
+    if let Some(status) = server.project.media_sync_status() {
+        tx.send(ServerMessage::MediaSync(status))
+            .await
+            .expect("tx.send MediaSync");
+    }
+
+    tx.send(ServerMessage::Access(access.clone()))
+        .await
+        .expect("tx.send Access");
+
+    if let Access::Owner = access {
+        tx.send(ServerMessage::GuestTokens(server.project.guest_tokens()))
+            .await
+            .expect("tx.send GuestTokens");
+    }
+
     enum Event {
-        ClientMessage(Result<ws::Message, warp::Error>),
+        ClientMessage(Result<Vec<u8>, RecvError>),
         Engine(Result<EngineEvent, broadcast::RecvError>),
         Notification(Notification),
+        GuestExpired,
     }
 
+    // guests are cut off the moment their link runs out, whether or not
+    // they're sending anything:
+    let expires_at = match &access {
+        Access::Guest(guest) => Some(guest.expires_at),
+        Access::Owner => None,
+    };
+
+    let expiry = stream::once(async move {
+        match expires_at {
+            Some(expires_at) => {
+                let remaining = expires_at.saturating_sub(util::unix_time() as i64).max(0) as u64;
+                time::delay_for(Duration::from_secs(remaining)).await;
+            }
+            None => future::pending::<()>().await,
+        }
+    });
+
     let mut events = stream::select(
         rx.map(Event::ClientMessage),
         stream::select(
-            engine_ops.map(Event::Engine),
-            notifications.map(Event::Notification)));
+            stream::select(
+                engine_ops.map(Event::Engine),
+                notifications.map(Event::Notification)),
+            Box::pin(expiry).map(|()| Event::GuestExpired)));
 
     while let Some(event) = events.next().await {
         match event {
@@ -233,21 +678,118 @@ async fn session(websocket: WebSocket, server: ServerRef) {
                 return;
             }
             Event::ClientMessage(Ok(msg)) => {
-                if !msg.is_binary() {
-                    continue;
-                }
-
-                let msg = bincode::deserialize::<ClientMessage>(msg.as_bytes())
+                let msg = bincode::deserialize::<ClientMessage>(&msg)
                     .expect("bincode::deserialize");
 
+                let msg = match &access {
+                    Access::Owner => msg,
+                    Access::Guest(guest) => {
+                        // checked on every message, so that revoking a link
+                        // or letting it expire cuts off sessions using it:
+                        let guest = match server.project.guest_token(&guest.token) {
+                            Some(guest) => guest,
+                            None => {
+                                println!("disconnecting guest: link expired or revoked");
+                                return;
+                            }
+                        };
+
+                        match msg {
+                            ClientMessage::Workspace(msg) if !server.project.guest_permits(&guest, &msg.op) => {
+                                println!("guest {:?} not permitted op: {:?}", guest.label, msg.op);
+
+                                // the client waits on every op it sends to
+                                // be acknowledged in order, so send a no-op
+                                // along in its place:
+                                ClientMessage::Workspace(WorkspaceMessage {
+                                    sequence: msg.sequence,
+                                    op: WorkspaceOp::Batch(Batch { placeholders: Vec::new(), ops: Vec::new() }),
+                                })
+                            }
+                            msg @ ClientMessage::Workspace(_) => msg,
+                            msg @ ClientMessage::SearchParams(_) => msg,
+                            msg => {
+                                println!("guest {:?} not permitted message: {:?}", guest.label, msg);
+                                continue;
+                            }
+                        }
+                    }
+                };
+
                 match msg {
                     ClientMessage::Workspace(msg) => {
                         if let Err(e) = engine.update(msg) {
                             println!("Engine update failed: {:?}", e);
                         }
                     }
+                    ClientMessage::CreateSnapshot(name) => {
+                        if let Err(e) = server.project.create_snapshot(name).await {
+                            eprintln!("failed to create snapshot: {:?}", e);
+                        }
+                    }
+                    ClientMessage::RestoreSnapshot(id) => {
+                        if let Err(e) = server.project.restore_snapshot(id).await {
+                            eprintln!("failed to restore snapshot: {:?}", e);
+                        }
+                    }
+                    ClientMessage::SearchParams(search) => {
+                        let results = match engine.search_params(search.clone()).await {
+                            Ok(results) => results,
+                            Err(e) => {
+                                eprintln!("failed to search params: {:?}", e);
+                                continue;
+                            }
+                        };
+
+                        if let Err(_) = tx.send(ServerMessage::ParamSearchResults(search, results)).await {
+                            // client disconnected
+                            return;
+                        }
+                    }
+                    ClientMessage::DuplicateProject(name) => {
+                        match server.project.duplicate(name).await {
+                            Ok(path) => { println!("duplicated project to {}", path.display()); }
+                            Err(e) => { eprintln!("failed to duplicate project: {:?}", e); }
+                        }
+                    }
+                    ClientMessage::KeepProject => {
+                        server.project.keep();
+                    }
+                    ClientMessage::PushMedia(remote) => {
+                        if !server.project.push_media(remote) {
+                            eprintln!("media is already being pushed, not starting another");
+                        }
+                    }
+                    ClientMessage::CreateGuestToken(request) => {
+                        if let Err(e) = server.project.create_guest_token(request).await {
+                            eprintln!("failed to create guest token: {:?}", e);
+                        }
+                    }
+                    ClientMessage::RevokeGuestToken(token) => {
+                        if let Err(e) = server.project.revoke_guest_token(token).await {
+                            eprintln!("failed to revoke guest token: {:?}", e);
+                        }
+                    }
+                    ClientMessage::Introspect => {
+                        let introspection = match engine.introspect().await {
+                            Ok(introspection) => introspection,
+                            Err(e) => {
+                                eprintln!("failed to introspect engine: {:?}", e);
+                                continue;
+                            }
+                        };
+
+                        if let Err(_) = tx.send(ServerMessage::Introspection(introspection)).await {
+                            // client disconnected
+                            return;
+                        }
+                    }
                 }
             }
+            Event::GuestExpired => {
+                println!("disconnecting guest: link expired");
+                return;
+            }
             Event::Engine(Err(broadcast::RecvError::Lagged(skipped))) => {
                 println!("disconnecting client: lagged {} messages behind", skipped);
                 return;
@@ -259,7 +801,13 @@ async fn session(websocket: WebSocket, server: ServerRef) {
             Event::Engine(Ok(event)) => {
                 // sequence is only applicable if it belongs to this session:
                 let msg = match event {
-                    EngineEvent::ServerUpdate(update) => Some(ServerMessage::Update(update)),
+                    EngineEvent::ServerUpdate(mut update) => {
+                        if let Access::Guest(_) = access {
+                            project::guest::redact_update(&mut update);
+                        }
+
+                        Some(ServerMessage::Update(update))
+                    }
                     EngineEvent::Sync(clock) => {
                         if clock.0 == engine.session_id() {
                             Some(ServerMessage::Sync(clock.1))
@@ -293,6 +841,38 @@ async fn session(websocket: WebSocket, server: ServerRef) {
                             }
                         }
                     }
+                    Notification::Snapshots => {
+                        match server.project.fetch_snapshots().await {
+                            Ok(snapshots) => Some(ServerMessage::Snapshots(snapshots)),
+                            Err(e) => {
+                                eprintln!("failed to query snapshots: {:?}", e);
+                                None
+                            }
+                        }
+                    }
+                    Notification::Project => {
+                        Some(ServerMessage::Project(server.project.info()))
+                    }
+                    Notification::Backup => {
+                        server.project.backup_status().map(ServerMessage::Backup)
+                    }
+                    Notification::MediaSync => {
+                        server.project.media_sync_status().map(ServerMessage::MediaSync)
+                    }
+                    Notification::GuestTokens => {
+                        match &access {
+                            Access::Owner => Some(ServerMessage::GuestTokens(server.project.guest_tokens())),
+                            Access::Guest(guest) => {
+                                // tokens change when revoked:
+                                if server.project.guest_token(&guest.token).is_none() {
+                                    println!("disconnecting guest: link revoked");
+                                    return;
+                                }
+
+                                None
+                            }
+                        }
+                    }
                 };
 
                 if let Some(msg) = msg {
@@ -344,15 +924,16 @@ async fn handle_upload(
 #[derive(Debug, From)]
 pub enum TxError {
     Warp(warp::Error),
+    Link(SendError),
     Bincode(bincode::Error),
 }
 
-pub struct ClientTx<S>(S);
+struct ClientTx(SessionTx);
 
-impl<S: Sink<ws::Message, Error = warp::Error> + Unpin> ClientTx<S> {
+impl ClientTx {
     pub async fn send<'a>(&mut self, msg: ServerMessage<'a>) -> Result<(), TxError> {
         let msg = bincode::serialize(&msg)?;
-        self.0.send(ws::Message::binary(msg)).await?;
+        self.0.send(msg).await?;
         Ok(())
     }
 }
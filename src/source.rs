@@ -8,6 +8,7 @@ use ringbuf::{RingBuffer, Producer, Consumer};
 
 use mixlab_util::time::MediaTime;
 
+use crate::engine::{ClockRef, SampleClock, CHANNELS};
 use crate::util::Sequence;
 use crate::video;
 
@@ -38,6 +39,8 @@ pub struct SourceId(NonZeroUsize);
 pub struct SourceShared {
     channel_name: String,
     recv_online: AtomicBool,
+    // advanced as audio arrives from whichever source is connected:
+    clock: ClockRef,
 }
 
 #[derive(Debug)]
@@ -60,7 +63,12 @@ pub struct SourceSend {
     tx: Option<TxPair>,
 }
 
-pub type AudioData = Vec<i16>;
+#[derive(Debug)]
+pub struct AudioData {
+    pub sample_rate: usize,
+    pub samples: Vec<i16>,
+}
+
 pub type VideoData = video::Frame;
 
 #[derive(Debug)]
@@ -100,6 +108,7 @@ impl Registry {
         let shared = Arc::new(SourceShared {
             channel_name: channel_name.to_owned(),
             recv_online: AtomicBool::new(true),
+            clock: SampleClock::new(),
         });
 
         let recv = SourceRecv {
@@ -155,6 +164,8 @@ impl SourceSend {
             // tx is always Some for a valid (non-dropped) SourceSend:
             let tx = self.tx.as_mut().unwrap();
 
+            self.shared.clock.advance(data.samples.len() / CHANNELS, data.sample_rate);
+
             let frame = Frame {
                 source_id: self.source_id,
                 source_time: timestamp,
@@ -218,6 +229,10 @@ impl SourceRecv {
         &self.shared.channel_name
     }
 
+    pub fn clock(&self) -> &ClockRef {
+        &self.shared.clock
+    }
+
     pub fn read_audio(&mut self) -> Option<Frame<AudioData>> {
         self.audio_rx.pop()
     }
@@ -0,0 +1,184 @@
+use std::cmp;
+use std::f64::consts::PI;
+
+use crate::engine::Sample;
+
+// length of the windows overlapped, long enough to hold a period of most
+// pitched sounds while short enough not to smear transients much:
+const WINDOW_SECONDS: f64 = 0.04;
+
+// the similarity search looks at every nth sample only, which is plenty to
+// line up waveforms and keeps stretching well ahead of real time:
+const SEARCH_STEP: usize = 4;
+
+/// Changes the speed of interleaved audio without changing its pitch, by
+/// waveform similarity overlap-add. The output is built of overlapping
+/// windows of the input, each taken from about where the speed puts it but
+/// shifted to line up with the window before it
+pub fn stretch(input: &[Sample], channels: usize, sample_rate: usize, speed: f64) -> Vec<Sample> {
+    let frames = input.len() / channels;
+    let out_frames = (frames as f64 / speed) as usize;
+
+    // even, so that windows overlap by exactly half:
+    let window_len = cmp::max(4, (sample_rate as f64 * WINDOW_SECONDS) as usize) & !1;
+    let hop = window_len / 2;
+    let tolerance = hop / 2;
+
+    if (speed - 1.0).abs() < f64::EPSILON || frames < window_len * 2 {
+        return input[..cmp::min(frames, out_frames) * channels].to_vec();
+    }
+
+    // windows are lined up by their mono mix:
+    let mono = input.chunks(channels)
+        .map(|frame| frame.iter().sum::<Sample>() / channels as Sample)
+        .collect::<Vec<_>>();
+
+    // hann windows overlapping by half sum to one:
+    let window = (0..window_len)
+        .map(|i| (0.5 - 0.5 * (2.0 * PI * i as f64 / window_len as f64).cos()) as Sample)
+        .collect::<Vec<_>>();
+
+    let mut output = vec![0.0; (out_frames + window_len) * channels];
+    let mut weight = vec![0.0; out_frames + window_len];
+    let last_start = frames - window_len;
+    let mut previous = None;
+
+    for index in 0.. {
+        let out_pos = index * hop;
+
+        if out_pos >= out_frames {
+            break;
+        }
+
+        let nominal = cmp::min(last_start, (index as f64 * hop as f64 * speed) as usize);
+
+        let pos = match previous {
+            // the input right after the previous window is what would carry
+            // on from it seamlessly, so the window most like it is taken:
+            Some(previous) if previous + hop + hop <= frames => {
+                let lo = nominal.saturating_sub(tolerance);
+                let hi = cmp::min(last_start, nominal + tolerance);
+                best_match(&mono, previous + hop, lo, hi, hop)
+            }
+            _ => nominal,
+        };
+
+        for (i, w) in window.iter().enumerate() {
+            let src = (pos + i) * channels;
+            let dst = (out_pos + i) * channels;
+
+            for channel in 0..channels {
+                output[dst + channel] += input[src + channel] * w;
+            }
+
+            weight[out_pos + i] += w;
+        }
+
+        previous = Some(pos);
+    }
+
+    // only the very start and end are short of a full overlap:
+    for (frame, weight) in output.chunks_mut(channels).zip(&weight) {
+        if *weight > 1e-3 {
+            for sample in frame {
+                *sample /= weight;
+            }
+        }
+    }
+
+    output.truncate(out_frames * channels);
+    output
+}
+
+// the position in lo..=hi whose next `len` samples correlate best with those
+// at `reference`
+fn best_match(mono: &[Sample], reference: usize, lo: usize, hi: usize, len: usize) -> usize {
+    let reference = &mono[reference..reference + len];
+
+    let score = |pos: usize| {
+        let candidate = &mono[pos..pos + len];
+        let mut dot = 0.0;
+        let mut energy = 0.0;
+
+        for i in (0..len).step_by(SEARCH_STEP) {
+            dot += reference[i] as f64 * candidate[i] as f64;
+            energy += candidate[i] as f64 * candidate[i] as f64;
+        }
+
+        // normalized, so that loud candidates aren't favoured just for being
+        // loud:
+        dot / f64::max(energy.sqrt(), 1e-9)
+    };
+
+    let mut best = (lo, f64::NEG_INFINITY);
+
+    for pos in lo..=hi {
+        let score = score(pos);
+
+        if score > best.1 {
+            best = (pos, score);
+        }
+    }
+
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::engine::Sample;
+    use super::stretch;
+
+    const SAMPLE_RATE: usize = 44100;
+    const FREQ: f64 = 440.0;
+
+    fn sine(seconds: f64) -> Vec<Sample> {
+        (0..(seconds * SAMPLE_RATE as f64) as usize)
+            .flat_map(|i| {
+                let sample = (2.0 * PI * FREQ * i as f64 / SAMPLE_RATE as f64).sin() as Sample * 0.5;
+                vec![sample, sample]
+            })
+            .collect()
+    }
+
+    // by counting zero crossings of the left channel, leaving out the ends,
+    // which aren't fully overlapped
+    fn frequency(output: &[Sample]) -> f64 {
+        let left = output.iter().step_by(2).copied().collect::<Vec<_>>();
+        let middle = &left[left.len() / 8..left.len() * 7 / 8];
+
+        let crossings = middle.windows(2)
+            .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+            .count();
+
+        crossings as f64 / 2.0 / (middle.len() as f64 / SAMPLE_RATE as f64)
+    }
+
+    #[test]
+    fn length_tracks_speed() {
+        let input = sine(2.0);
+
+        for speed in &[0.5, 0.8, 1.5, 2.0] {
+            let output = stretch(&input, 2, SAMPLE_RATE, *speed);
+            assert_eq!(output.len() / 2, (input.len() as f64 / 2.0 / speed) as usize, "speed {}", speed);
+        }
+    }
+
+    #[test]
+    fn preserves_pitch() {
+        let input = sine(2.0);
+
+        for speed in &[0.5, 0.8, 1.5, 2.0] {
+            let output = stretch(&input, 2, SAMPLE_RATE, *speed);
+            let freq = frequency(&output);
+            assert!((freq - FREQ).abs() < FREQ * 0.02, "speed {}: {} Hz", speed, freq);
+        }
+    }
+
+    #[test]
+    fn unchanged_at_normal_speed() {
+        let input = sine(0.5);
+        assert_eq!(stretch(&input, 2, SAMPLE_RATE, 1.0), input);
+    }
+}
@@ -4,16 +4,16 @@ use std::time::{Duration, Instant};
 
 use mixlab_util::time::MediaTime;
 
-use crate::engine::SAMPLE_RATE;
-
 pub struct AudioThrottle {
+    sample_rate: usize,
     started: Option<Instant>,
     samples_sent: u64,
 }
 
 impl AudioThrottle {
-    pub fn new() -> AudioThrottle {
+    pub fn new(sample_rate: usize) -> AudioThrottle {
         AudioThrottle {
+            sample_rate,
             started: None,
             samples_sent: 0,
         }
@@ -22,7 +22,7 @@ impl AudioThrottle {
     pub fn send_samples(&mut self, sample_count: usize) {
         let started = *self.started.get_or_insert_with(Instant::now);
 
-        let elapsed = Duration::from_micros((self.samples_sent * 1_000_000) / SAMPLE_RATE as u64);
+        let elapsed = Duration::from_micros((self.samples_sent * 1_000_000) / self.sample_rate as u64);
         let sleep_until = started + elapsed;
         let now = Instant::now();
 
@@ -1,6 +1,7 @@
 use std::io;
 use std::num::NonZeroUsize;
-use std::time::Duration;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use futures::executor::block_on;
 use num_rational::Rational64;
@@ -29,6 +30,49 @@ pub fn zero(slice: &mut [f32]) {
     }
 }
 
+/// Seconds since the unix epoch, for naming files
+pub fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
+
+/// This machine's name, for settings which only hold on the machine they
+/// were made on
+pub fn machine_name() -> &'static str {
+    lazy_static::lazy_static! {
+        static ref NAME: String = Command::new("hostname").output().ok()
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|name| name.trim().to_owned())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "localhost".to_owned());
+    }
+
+    &NAME
+}
+
+/// The UTC date of a unix time, as YYYY-MM-DD
+pub fn utc_date(unix_time: u64) -> String {
+    let (year, month, day) = civil_from_days((unix_time / 86400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Days since the unix epoch to a gregorian calendar date as year, month
+/// and day, after Howard Hinnant's algorithm
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
 pub struct SyncRead<T>(pub T);
 
 impl<T: AsyncRead + Unpin> io::Read for SyncRead<T> {
@@ -38,6 +82,20 @@ impl<T: AsyncRead + Unpin> io::Read for SyncRead<T> {
     }
 }
 
+/// Compares secrets in time that doesn't depend on how much of them match
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Lowercase hex, as digests are written
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
 #[allow(unused)]
 pub fn decimal(ratio: Rational64) -> String {
     let micros = (ratio * 1_000_000).to_integer();
@@ -58,3 +116,27 @@ pub fn temporal_warning(time_since_event: Option<Duration>) -> Option<TemporalWa
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::utc_date;
+
+    #[test]
+    fn utc_date_of_known_days() {
+        assert_eq!(utc_date(0), "1970-01-01");
+        assert_eq!(utc_date(946598400), "1999-12-31");
+        assert_eq!(utc_date(1677628800), "2023-03-01");
+    }
+
+    #[test]
+    fn utc_date_of_leap_days() {
+        assert_eq!(utc_date(951782400), "2000-02-29");
+        assert_eq!(utc_date(1709164800 - 1), "2024-02-28");
+        assert_eq!(utc_date(1709164800), "2024-02-29");
+        assert_eq!(utc_date(1709164800 + 86399), "2024-02-29");
+        assert_eq!(utc_date(1709164800 + 86400), "2024-03-01");
+        // not a leap year, being a century not divisible by 400:
+        assert_eq!(utc_date(4107542400 - 1), "2100-02-28");
+        assert_eq!(utc_date(4107542400), "2100-03-01");
+    }
+}
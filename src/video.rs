@@ -1,4 +1,7 @@
+pub mod deinterlace;
+pub mod draw;
 pub mod encode;
+pub mod record;
 
 use mixlab_codec::ffmpeg::media::Video;
 use mixlab_codec::ffmpeg::AvFrame;
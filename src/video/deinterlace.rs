@@ -0,0 +1,168 @@
+use std::ptr;
+
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::{AvFrame, ColorFormat, PictureData};
+use mixlab_protocol::Deinterlace;
+use mixlab_util::time::MediaDuration;
+
+use crate::video;
+
+// difference in a sample between frames above which that part of the picture
+// is taken to be moving:
+const MOTION_THRESHOLD: u8 = 10;
+
+#[derive(Debug, Default)]
+pub struct Deinterlacer {
+    // previous input frame, for motion detection:
+    previous: Option<AvFrame<Video>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Top,
+    Bottom,
+}
+
+impl Field {
+    fn has_line(self, y: usize) -> bool {
+        match self {
+            Field::Top => y % 2 == 0,
+            Field::Bottom => y % 2 == 1,
+        }
+    }
+}
+
+impl Deinterlacer {
+    /// Deinterlaces a frame, returning the resulting frames along with their
+    /// offsets from the input frame's timestamp. Frames in formats other than
+    /// 8 bit planar yuv are passed through untouched.
+    pub fn process(&mut self, mode: Deinterlace, frame: video::Frame) -> Vec<(MediaDuration, video::Frame)> {
+        if mode == Deinterlace::Weave || !supported(&frame.decoded) {
+            self.previous = None;
+            return vec![(MediaDuration::zero(), frame)];
+        }
+
+        // frames not flagged as interlaced are assumed to be top field first,
+        // which is the more common order for hd capture:
+        let (first, second) = if frame.decoded.is_interlaced() && !frame.decoded.top_field_first() {
+            (Field::Bottom, Field::Top)
+        } else {
+            (Field::Top, Field::Bottom)
+        };
+
+        let output = match mode {
+            Deinterlace::Weave => unreachable!(),
+            Deinterlace::Bob => {
+                let half = MediaDuration::from(frame.duration_hint.as_rational() / 2);
+
+                vec![
+                    (MediaDuration::zero(), video::Frame {
+                        decoded: interpolate(&frame.decoded, first, None),
+                        duration_hint: half,
+                    }),
+                    (half, video::Frame {
+                        decoded: interpolate(&frame.decoded, second, None),
+                        duration_hint: half,
+                    }),
+                ]
+            }
+            Deinterlace::Adaptive => {
+                vec![(MediaDuration::zero(), video::Frame {
+                    decoded: interpolate(&frame.decoded, first, self.previous.as_ref()),
+                    duration_hint: frame.duration_hint,
+                })]
+            }
+        };
+
+        self.previous = Some(frame.decoded);
+
+        output
+    }
+}
+
+fn supported(frame: &AvFrame<Video>) -> bool {
+    let pixdesc = frame.pixel_format().descriptor();
+
+    pixdesc.planar()
+        && pixdesc.color() == ColorFormat::Yuv
+        && pixdesc.components().all(|comp| comp.step() == 1)
+}
+
+// keeps the lines of `field` and fills in the lines between them by
+// averaging their neighbours. given the previous frame, samples which
+// haven't moved since are woven in from the other field instead, as yadif
+// does, keeping full vertical resolution for still parts of the picture
+fn interpolate(frame: &AvFrame<Video>, field: Field, previous: Option<&AvFrame<Video>>) -> AvFrame<Video> {
+    let picture = frame.picture_settings();
+    let previous = previous.filter(|previous| previous.picture_settings() == picture);
+
+    let mut output = AvFrame::blank(&picture);
+    output.copy_props_from(frame);
+
+    {
+        let input = frame.frame_data();
+        let previous = previous.map(|previous| previous.frame_data());
+        let out = output.frame_data_mut();
+
+        let pixdesc = picture.pixel_format.descriptor();
+        let mut done = [false; 8];
+
+        for comp in pixdesc.components() {
+            let plane = comp.plane();
+
+            if done[plane] {
+                continue;
+            }
+
+            done[plane] = true;
+
+            let width = picture.width >> comp.log2_horz();
+            let height = picture.height >> comp.log2_vert();
+
+            for y in 0..height {
+                unsafe {
+                    let out_line = out.data(plane).add(y * out.stride(plane));
+
+                    if field.has_line(y) || height < 2 {
+                        ptr::copy_nonoverlapping(line(&input, plane, y), out_line, width);
+                        continue;
+                    }
+
+                    // lines either side belong to the field being kept:
+                    let above = if y > 0 { y - 1 } else { y + 1 };
+                    let below = if y + 1 < height { y + 1 } else { y - 1 };
+
+                    for x in 0..width {
+                        let a = *line(&input, plane, above).add(x);
+                        let b = *line(&input, plane, below).add(x);
+                        let spatial = ((a as u16 + b as u16 + 1) / 2) as u8;
+
+                        let still = previous.as_ref().map(|previous| {
+                            [above, y, below].iter().all(|&y| {
+                                let now = *line(&input, plane, y).add(x);
+                                let before = *line(previous, plane, y).add(x);
+                                diff(now, before) < MOTION_THRESHOLD
+                            })
+                        }).unwrap_or(false);
+
+                        *out_line.add(x) = if still {
+                            *line(&input, plane, y).add(x)
+                        } else {
+                            spatial
+                        };
+                    }
+                }
+            }
+        }
+    }
+
+    output
+}
+
+unsafe fn line(data: &PictureData, plane: usize, y: usize) -> *const u8 {
+    data.data(plane).add(y * data.stride(plane))
+}
+
+fn diff(a: u8, b: u8) -> u8 {
+    if a > b { a - b } else { b - a }
+}
@@ -0,0 +1,247 @@
+// simple drawing into yuv420p frames, for composited monitoring outputs
+// rather than anything that needs to look good on air
+
+use std::ptr;
+
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::{AvFrame, PictureDataMut, PixelFormat};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Yuv(pub u8, pub u8, pub u8);
+
+// bt.601 studio range:
+pub const BLACK: Yuv = Yuv(16, 128, 128);
+pub const GREY: Yuv = Yuv(90, 128, 128);
+pub const WHITE: Yuv = Yuv(235, 128, 128);
+pub const RED: Yuv = Yuv(81, 90, 240);
+pub const GREEN: Yuv = Yuv(145, 54, 34);
+pub const YELLOW: Yuv = Yuv(210, 16, 146);
+
+// glyphs are 5x7 pixels, drawn at GLYPH_SCALE
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+const GLYPH_SCALE: usize = 2;
+
+pub const TEXT_HEIGHT: usize = GLYPH_HEIGHT * GLYPH_SCALE;
+const TEXT_ADVANCE: usize = (GLYPH_WIDTH + 1) * GLYPH_SCALE;
+
+pub struct Canvas<'a> {
+    data: PictureDataMut<'a>,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Canvas<'a> {
+    pub fn new(frame: &'a mut AvFrame<Video>) -> Self {
+        let picture = frame.picture_settings();
+        assert!(picture.pixel_format == PixelFormat::yuv420p());
+
+        Canvas {
+            data: frame.frame_data_mut(),
+            width: picture.width,
+            height: picture.height,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Copies out the luma plane, one byte per pixel with no padding
+    pub fn luma(&self) -> Vec<u8> {
+        let mut luma = Vec::with_capacity(self.width * self.height);
+
+        unsafe {
+            let stride = self.data.stride(0);
+
+            for row in 0..self.height {
+                let line = self.data.data(0).add(row * stride) as *const u8;
+                luma.extend_from_slice(std::slice::from_raw_parts(line, self.width));
+            }
+        }
+
+        luma
+    }
+
+    /// Paints every pixel for which `mask` returns true. Chroma is shared
+    /// between 2x2 blocks of pixels, so is painted for the top left pixel
+    /// of each block only.
+    pub fn paint(&mut self, mut mask: impl FnMut(usize, usize) -> bool, color: Yuv) {
+        let Yuv(luma, u, v) = color;
+
+        unsafe {
+            let strides = [self.data.stride(0), self.data.stride(1), self.data.stride(2)];
+
+            for y in 0..self.height {
+                let luma_line = self.data.data(0).add(y * strides[0]);
+                let u_line = self.data.data(1).add((y / 2) * strides[1]);
+                let v_line = self.data.data(2).add((y / 2) * strides[2]);
+
+                for x in 0..self.width {
+                    if mask(x, y) {
+                        *luma_line.add(x) = luma;
+
+                        if x % 2 == 0 && y % 2 == 0 {
+                            *u_line.add(x / 2) = u;
+                            *v_line.add(x / 2) = v;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn fill(&mut self, x: usize, y: usize, w: usize, h: usize, color: Yuv) {
+        let Yuv(luma, u, v) = color;
+
+        self.fill_plane(0, x, y, w, h, luma);
+        self.fill_plane(1, x / 2, y / 2, (w + 1) / 2, (h + 1) / 2, u);
+        self.fill_plane(2, x / 2, y / 2, (w + 1) / 2, (h + 1) / 2, v);
+    }
+
+    pub fn outline(&mut self, x: usize, y: usize, w: usize, h: usize, thickness: usize, color: Yuv) {
+        let thickness = usize::min(thickness, usize::min(w, h) / 2);
+
+        self.fill(x, y, w, thickness, color);
+        self.fill(x, y + h - thickness, w, thickness, color);
+        self.fill(x, y, thickness, h, color);
+        self.fill(x + w - thickness, y, thickness, h, color);
+    }
+
+    /// Draws text with its top left corner at x, y, clipped to the canvas.
+    /// Characters without a glyph are drawn as '?'
+    pub fn text(&mut self, x: usize, y: usize, text: &str, color: Yuv) {
+        for (idx, c) in text.chars().enumerate() {
+            let glyph_x = x + idx * TEXT_ADVANCE;
+
+            if glyph_x >= self.width {
+                break;
+            }
+
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                        self.fill(
+                            glyph_x + col * GLYPH_SCALE,
+                            y + row * GLYPH_SCALE,
+                            GLYPH_SCALE,
+                            GLYPH_SCALE,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copies a yuv420p frame onto the canvas with its top left corner at
+    /// x, y, clipped to the canvas
+    pub fn blit(&mut self, x: usize, y: usize, frame: &AvFrame<Video>) {
+        let picture = frame.picture_settings();
+        assert!(picture.pixel_format == PixelFormat::yuv420p());
+
+        let src = frame.frame_data();
+
+        for plane in 0..3 {
+            let shift = if plane == 0 { 0 } else { 1 };
+
+            let (dst_x, dst_y) = (x >> shift, y >> shift);
+            let (plane_width, plane_height) = (self.width >> shift, self.height >> shift);
+
+            if dst_x >= plane_width || dst_y >= plane_height {
+                continue;
+            }
+
+            let w = usize::min(picture.width >> shift, plane_width - dst_x);
+            let h = usize::min(picture.height >> shift, plane_height - dst_y);
+
+            unsafe {
+                let src_stride = src.stride(plane);
+                let dst_stride = self.data.stride(plane);
+
+                for row in 0..h {
+                    ptr::copy_nonoverlapping(
+                        src.data(plane).add(row * src_stride),
+                        self.data.data(plane).add((dst_y + row) * dst_stride + dst_x),
+                        w,
+                    );
+                }
+            }
+        }
+    }
+
+    fn fill_plane(&mut self, plane: usize, x: usize, y: usize, w: usize, h: usize, value: u8) {
+        let shift = if plane == 0 { 0 } else { 1 };
+        let (plane_width, plane_height) = (self.width >> shift, self.height >> shift);
+
+        if x >= plane_width || y >= plane_height {
+            return;
+        }
+
+        let w = usize::min(w, plane_width - x);
+        let h = usize::min(h, plane_height - y);
+
+        unsafe {
+            let stride = self.data.stride(plane);
+
+            for row in 0..h {
+                ptr::write_bytes(self.data.data(plane).add((y + row) * stride + x), value, w);
+            }
+        }
+    }
+}
+
+pub fn text_width(text: &str) -> usize {
+    text.chars().count() * TEXT_ADVANCE
+}
+
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        ' ' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000],
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '/' => [0b00001, 0b00010, 0b00010, 0b00100, 0b01000, 0b01000, 0b10000],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    }
+}
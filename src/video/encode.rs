@@ -9,7 +9,6 @@ use num_rational::Ratio;
 use mixlab_codec::avc::DecoderConfigurationRecord;
 use mixlab_codec::avc::encode::{AvcEncoder, AvcParams, Preset, Tune, RateControl};
 use mixlab_codec::ffmpeg::media::Video;
-use mixlab_codec::ffmpeg::sys;
 use mixlab_codec::ffmpeg::{AvFrame, AvPacket, PictureSettings, SwsContext};
 use mixlab_mux::mp4::AvcFrame;
 use mixlab_util::time::{MediaTime, MediaDuration};
@@ -29,6 +28,8 @@ pub struct EncodeStream {
     video_segments: VecDeque<VideoSegment>,
     video_timestamp: MediaTime,
     video_ctx: VideoCtx,
+    // attributed to packets left in the encoder when the stream finishes:
+    last_video_duration: MediaDuration,
 }
 
 impl EncodeStream {
@@ -40,6 +41,7 @@ impl EncodeStream {
             video_segments: VecDeque::new(),
             video_timestamp: MediaTime::new(0, 1),
             video_ctx,
+            last_video_duration: MediaDuration::new(0, 1),
         }
     }
 
@@ -97,10 +99,17 @@ impl EncodeStream {
         frame.set_presentation_timestamp(frame_start_in_base);
         self.video_ctx.send_frame(frame);
 
+        self.last_video_duration = MediaDuration::new(duration_in_base, time_base);
+        self.receive_video();
+    }
+
+    fn receive_video(&mut self) {
+        let time_base = self.video_ctx.time_base;
+
         while let Some(packet) = self.video_ctx.recv_packet() {
             self.video_segments.push_back(VideoSegment {
                 decode_timestamp: MediaTime::new(packet.decode_timestamp(), time_base),
-                duration: MediaDuration::new(duration_in_base, time_base),
+                duration: self.last_video_duration,
                 frame: AvcFrame {
                     is_key_frame: packet.is_key_frame(),
                     composition_time: MediaDuration::new(packet.presentation_timestamp() - packet.decode_timestamp(), time_base),
@@ -121,6 +130,32 @@ impl EncodeStream {
             self.video_segments.pop_front().map(StreamSegment::Video)
         }
     }
+
+    /// Ends the stream, returning every segment not yet received in decode
+    /// order including those the video encoder was holding back
+    pub fn finish(&mut self) -> Vec<StreamSegment> {
+        self.video_ctx.send_eof();
+        self.receive_video();
+
+        let mut segments = Vec::new();
+
+        loop {
+            let audio_first = match (self.audio_segments.front(), self.video_segments.front()) {
+                (Some(audio), Some(video)) => audio.decode_timestamp < video.decode_timestamp,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+
+            segments.push(if audio_first {
+                StreamSegment::Audio(self.audio_segments.pop_front().unwrap())
+            } else {
+                StreamSegment::Video(self.video_segments.pop_front().unwrap())
+            });
+        }
+
+        segments
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -238,6 +273,7 @@ pub struct VideoParams {
 pub enum Profile {
     Monitor,
     Stream,
+    Record,
 }
 
 impl VideoCtx {
@@ -248,25 +284,30 @@ impl VideoCtx {
         let params = AvcParams {
             time_base: time_base,
             pixel_format: picture.pixel_format,
-            color_space: sys::AVColorSpace_AVCOL_SPC_UNSPECIFIED,
+            color: picture.color,
             picture_width: picture.width,
             picture_height: picture.height,
             rate_control: match params.profile {
                 // cannot use constant bitrate in zero latency mode apparently:
                 Profile::Monitor => RateControl::ConstantQuality { crf: 30 },
                 Profile::Stream => RateControl::ConstantBitRate { bitrate: 1_500_000 },
+                Profile::Record => RateControl::ConstantQuality { crf: 18 },
             },
             preset: match params.profile {
                 Profile::Monitor => Preset::Veryfast,
                 Profile::Stream => Preset::Slow,
+                Profile::Record => Preset::Fast,
             },
             tune: match params.profile {
                 Profile::Monitor => Some(Tune::Zerolatency),
                 Profile::Stream => Some(Tune::Film),
+                Profile::Record => Some(Tune::Film),
             },
             gop_size: match params.profile {
                 Profile::Monitor => Some(1), // every frame is key frame
                 Profile::Stream => Some(60),
+                // frequent key frames keep recordings easy to seek and cut:
+                Profile::Record => Some(30),
             },
         };
 
@@ -294,10 +335,14 @@ impl VideoCtx {
         self.codec.send_frame(frame).unwrap();
     }
 
+    pub fn send_eof(&mut self) {
+        self.codec.send_eof().unwrap();
+    }
+
     pub fn recv_packet(&mut self) -> Option<AvPacket> {
         match self.codec.recv_packet() {
             Ok(pkt) => Some(pkt),
-            Err(e) if e.again() => { return None; }
+            Err(e) if e.again() || e.eof() => { return None; }
             Err(e) => { panic!("recv_packet errored: {:?}", e); }
         }
     }
@@ -339,14 +384,14 @@ impl DynamicScaler {
         let input_picture = frame.picture_settings();
         let output_picture = &self.output;
 
-        if &frame.picture_settings() == output_picture {
-            // no scaling necessary
+        if &input_picture == output_picture {
+            // no scaling or color conversion necessary
             return frame;
         }
 
         // reset cached swscale instance if it does not match input frame
         if let Some(scale) = self.scale.as_ref() {
-            if scale.frame.picture_settings() != input_picture {
+            if scale.ctx.input_settings() != &input_picture {
                 self.scale = None;
             }
         }
@@ -368,6 +413,7 @@ impl DynamicScaler {
                 width: scaled_width,
                 height: scaled_height,
                 pixel_format: output_picture.pixel_format,
+                color: output_picture.color,
             };
 
             let letterbox_x = pixdesc.align_horizontal((output_picture.width - scaled_width) / 2);
@@ -392,6 +438,8 @@ impl DynamicScaler {
         );
 
         scale.frame.copy_props_from(frame);
+        // copying props brings the input's color tags along with them:
+        scale.frame.set_colorimetry(self.output.color);
 
         &mut scale.frame
     }
@@ -0,0 +1,204 @@
+use std::borrow::Cow;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use fdk_aac::enc as aac;
+
+use mixlab_codec::ffmpeg::PictureSettings;
+use mixlab_mux::mp4::{self, Mp4Mux, Mp4Params, TrackData, AdtsFrame, AvcFrame, Chapter};
+use mixlab_util::time::MediaTime;
+
+use crate::engine;
+use crate::video::encode::{EncodeStream, AudioCtx, AudioParams, VideoCtx, VideoParams, StreamSegment, Profile};
+
+// ticks buffered while the encoder catches up, a recording should only drop
+// ticks as a last resort:
+const TICK_BUFFER: usize = 1000;
+
+/// Video and audio being encoded to an mp4 file on a thread of its own. The
+/// file is fragmented so that everything written up to a crash or power loss
+/// is still playable. Dropping the recording finishes the file, after which
+/// `finished` is called with its path.
+#[derive(Debug)]
+pub struct Recording {
+    tx: mpsc::SyncSender<Tick>,
+    failed: Arc<AtomicBool>,
+    // written into the file when it is finished:
+    chapters: Arc<Mutex<Vec<Chapter>>>,
+}
+
+pub struct Tick {
+    // relative to the start of the file:
+    pub timestamp: MediaTime,
+    pub audio: Vec<engine::Sample>,
+    pub video: Option<engine::VideoFrame>,
+}
+
+#[derive(Debug)]
+pub enum SendError {
+    // encoder is not keeping up, this tick is missing from the recording:
+    Lagged,
+    // the recording has stopped, see the log for why:
+    Failed,
+}
+
+impl Recording {
+    pub fn start(path: PathBuf, picture: PictureSettings, sample_rate: usize, finished: impl FnOnce(PathBuf) + Send + 'static) -> Self {
+        let (tx, rx) = mpsc::sync_channel(TICK_BUFFER);
+        let failed = Arc::new(AtomicBool::new(false));
+        let chapters = Arc::new(Mutex::new(Vec::new()));
+
+        thread::spawn({
+            let failed = failed.clone();
+            let chapters = chapters.clone();
+
+            move || {
+                match run_recording_thread(&path, picture, sample_rate, rx, &chapters) {
+                    Ok(()) => finished(path),
+                    Err(e) => {
+                        eprintln!("recording: could not write {}: {:?}", path.display(), e);
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+        });
+
+        Recording { tx, failed, chapters }
+    }
+
+    pub fn add_chapter(&self, chapter: Chapter) {
+        self.chapters.lock().unwrap().push(chapter);
+    }
+
+    /// Sends a tick of media, timestamped relative to the start of the
+    /// recording
+    pub fn send(&mut self, timestamp: MediaTime, audio: &[engine::Sample], video: Option<&engine::VideoFrame>) -> Result<(), SendError> {
+        use mpsc::TrySendError;
+
+        if self.failed.load(Ordering::Relaxed) {
+            return Err(SendError::Failed);
+        }
+
+        let tick = Tick {
+            timestamp,
+            audio: audio.to_vec(),
+            video: video.cloned(),
+        };
+
+        match self.tx.try_send(tick) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => Err(SendError::Lagged),
+            Err(TrySendError::Disconnected(_)) => Err(SendError::Failed),
+        }
+    }
+}
+
+fn run_recording_thread(path: &Path, picture: PictureSettings, sample_rate: usize, rx: mpsc::Receiver<Tick>, chapters: &Mutex<Vec<Chapter>>) -> Result<(), io::Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // never overwrite an earlier recording:
+    let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    let mut file = BufWriter::new(file);
+
+    // runs until the recording is dropped:
+    let chapter_offset = write_mp4(&mut file, picture, sample_rate, rx.iter())?;
+
+    let chapters = chapters.lock().unwrap().clone();
+
+    if !chapters.is_empty() {
+        // players show whatever precedes the first chapter as untitled:
+        let chapters = iter::once(Chapter { start: MediaTime::zero(), title: "Start".to_owned() })
+            .chain(chapters)
+            .collect::<Vec<_>>();
+
+        file.seek(SeekFrom::Start(chapter_offset as u64))?;
+        file.write_all(&mp4::chapter_box(&chapters))?;
+    }
+
+    file.flush()
+}
+
+/// Encodes ticks of media to `out` as a fragmented mp4, returning the offset
+/// of the space left for chapters in its header
+pub fn write_mp4(out: &mut impl Write, picture: PictureSettings, sample_rate: usize, ticks: impl Iterator<Item = Tick>) -> Result<usize, io::Error> {
+    let audio_ctx = AudioCtx::new(AudioParams {
+        bit_rate: aac::BitRate::Cbr(256000),
+        sample_rate,
+        transport: aac::Transport::Adts,
+    });
+
+    let video_ctx = VideoCtx::new(VideoParams {
+        picture: picture.clone(),
+        time_base: sample_rate,
+        profile: Profile::Record,
+    });
+
+    let mp4_params = {
+        let mut dcr = vec![];
+        video_ctx.decoder_configuration_record().write_to(&mut dcr);
+
+        Mp4Params {
+            timescale: sample_rate as u32,
+            sample_rate: sample_rate as u32,
+            width: picture.width as u32,
+            height: picture.height as u32,
+            dcr: Cow::Owned(dcr),
+        }
+    };
+
+    let (mut mux, init) = Mp4Mux::new(mp4_params);
+    let (init, chapter_offset) = mp4::reserve_chapter_space(&init);
+    out.write_all(&init)?;
+
+    let mut encode = EncodeStream::new(audio_ctx, video_ctx);
+
+    for tick in ticks {
+        encode.send_audio(&tick.audio);
+
+        if let Some(video_frame) = tick.video {
+            let frame_timestamp = tick.timestamp + video_frame.tick_offset;
+            let frame = video_frame.data.decoded.clone();
+
+            encode.send_video(frame_timestamp, video_frame.data.duration_hint, frame);
+        }
+
+        // gaps in the video are filled so that the recording stays in sync
+        // with engine time:
+        encode.barrier(tick.timestamp);
+
+        while let Some(segment) = encode.recv_segment() {
+            write_segment(out, &mut mux, segment)?;
+        }
+    }
+
+    for segment in encode.finish() {
+        write_segment(out, &mut mux, segment)?;
+    }
+
+    Ok(chapter_offset)
+}
+
+fn write_segment(out: &mut impl Write, mux: &mut Mp4Mux, segment: StreamSegment) -> Result<(), io::Error> {
+    let data = match segment {
+        StreamSegment::Audio(audio) => {
+            mux.write_track(audio.duration, &TrackData::Audio(AdtsFrame(audio.frame)))
+        }
+        StreamSegment::Video(video) => {
+            mux.write_track(video.duration, &TrackData::Video(AvcFrame {
+                is_key_frame: video.frame.is_key_frame,
+                composition_time: video.frame.composition_time,
+                data: video.frame.data,
+            }))
+        }
+    };
+
+    out.write_all(&data)
+}